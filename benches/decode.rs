@@ -0,0 +1,84 @@
+// The pipeline's modules (and those of their tests, which clippy checks
+// too) are built into the benchmark itself, as the crate only has its
+// binary to link against:
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/autotone.rs"]
+mod autotone;
+#[path = "../src/band.rs"]
+mod band;
+#[path = "../src/clipboard.rs"]
+mod clipboard;
+#[path = "../src/codec.rs"]
+mod codec;
+#[path = "../src/detect.rs"]
+mod detect;
+#[path = "../src/filter.rs"]
+mod filter;
+#[path = "../src/gate.rs"]
+mod gate;
+#[path = "../src/json.rs"]
+mod json;
+#[path = "../src/keyer.rs"]
+mod keyer;
+#[path = "../src/message.rs"]
+mod message;
+#[path = "../src/morse.rs"]
+mod morse;
+#[path = "../src/prelude.rs"]
+mod prelude;
+#[path = "../src/scan.rs"]
+mod scan;
+#[path = "../src/speed.rs"]
+mod speed;
+#[path = "../src/term.rs"]
+mod term;
+#[path = "../src/threshold.rs"]
+mod threshold;
+#[path = "../src/wav.rs"]
+mod wav;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use detect::{Channel, PipelineOptions, SignalPipeline};
+use keyer::SAMPLE_RATE;
+use std::hint::black_box;
+
+/// The frames of each buffer, about what PipeWire delivers.
+const BUFFER_FRAMES: usize = 1024;
+
+/// One second of a 700 Hz tone sending PARIS at 20 WPM, on both channels.
+fn one_second_of_stereo() -> Vec<f32> {
+    let mut mono = Vec::new();
+    for (freq, duration) in morse::encode_morse("PARIS PARIS", 60, 700.0) {
+        let n = (SAMPLE_RATE * duration / 1000) as usize;
+        mono.extend((0..n).map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            0.5 * (2.0 * std::f32::consts::PI * freq * t).sin()
+        }));
+    }
+    mono.resize(SAMPLE_RATE as usize, 0.0);
+    mono.iter().flat_map(|&s| [s, s]).collect()
+}
+
+/// Decodes one second of 48 kHz stereo audio, a buffer at a time as it
+/// is captured, reporting the frames decoded per second.
+fn decode_stereo(c: &mut Criterion) {
+    let stereo = one_second_of_stereo();
+    let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+    let mut block = Vec::with_capacity(BUFFER_FRAMES);
+    let mut group = c.benchmark_group("pipeline");
+    group.throughput(Throughput::Elements(SAMPLE_RATE as u64));
+    group.bench_function("one second of 48 kHz stereo", |b| {
+        b.iter(|| {
+            for buffer in stereo.chunks(BUFFER_FRAMES * 2) {
+                block.clear();
+                block.extend(buffer.chunks(2).map(|frame| Channel::Mix.sample(frame)));
+                black_box(pipeline.push_samples(&block, SAMPLE_RATE));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode_stereo);
+criterion_main!(benches);
@@ -0,0 +1,281 @@
+use crate::codec::{encode, Separators};
+use crate::practice::{split_characters, DrillResults};
+use crate::review::grade;
+use std::str::FromStr;
+use std::time::Duration;
+use tabled::settings::style::Style;
+use tabled::{Table, Tabled};
+
+/// The Q-codes and abbreviations heard most on the air, with what they
+/// mean.
+pub const ABBREVIATIONS: [(&str, &str); 48] = [
+    ("QRL", "Is this frequency in use?"),
+    ("QRM", "Interference from other stations"),
+    ("QRN", "Static, atmospheric noise"),
+    ("QRO", "Increase power"),
+    ("QRP", "Decrease power, low power"),
+    ("QRQ", "Send faster"),
+    ("QRS", "Send slower"),
+    ("QRT", "Stop sending, closing down"),
+    ("QRU", "I have nothing more for you"),
+    ("QRV", "I am ready"),
+    ("QRX", "Wait, stand by"),
+    ("QRZ", "Who is calling me?"),
+    ("QSB", "Your signal is fading"),
+    ("QSL", "I acknowledge receipt, confirmation card"),
+    ("QSO", "A contact"),
+    ("QSY", "Change frequency"),
+    ("QTH", "My location is"),
+    ("AGN", "Again"),
+    ("ANT", "Antenna"),
+    ("BK", "Break, back to you"),
+    ("CFM", "Confirm"),
+    ("CL", "Closing down"),
+    ("CPY", "Copy"),
+    ("CQ", "Calling any station"),
+    ("CUL", "See you later"),
+    ("DE", "From, this is"),
+    ("DX", "Distance, a faraway station"),
+    ("ES", "And"),
+    ("FB", "Fine business, excellent"),
+    ("FER", "For"),
+    ("GA", "Good afternoon, go ahead"),
+    ("GE", "Good evening"),
+    ("GM", "Good morning"),
+    ("HR", "Here"),
+    ("HW?", "How do you copy?"),
+    ("NR", "Number"),
+    ("OM", "Old man, a fellow operator"),
+    ("PSE", "Please"),
+    ("PWR", "Power"),
+    ("R", "Received, roger"),
+    ("RIG", "Radio equipment"),
+    ("RST", "Signal report: readability, strength, tone"),
+    ("SRI", "Sorry"),
+    ("TNX", "Thanks"),
+    ("UR", "Your, you are"),
+    ("WX", "Weather"),
+    ("73", "Best regards"),
+    ("88", "Love and kisses"),
+];
+
+/// Which way an abbreviation drill goes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// Hear the code, and type it back.
+    Hear,
+    /// See the meaning, and type (or key) the abbreviation.
+    Read,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hear" => Ok(Direction::Hear),
+            "read" => Ok(Direction::Read),
+            _ => Err(format!("Unknown direction: {s}")),
+        }
+    }
+}
+
+/// Abbreviations and their meanings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Meanings {
+    entries: Vec<(String, String)>,
+}
+
+impl Meanings {
+    /// The built-in `ABBREVIATIONS`.
+    pub fn builtin() -> Self {
+        Self {
+            entries: ABBREVIATIONS
+                .iter()
+                .map(|(code, meaning)| (code.to_string(), meaning.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Adds the abbreviations of a TOML file to the table, each a key
+    /// and its meaning, replacing the meaning of those already in it:
+    ///
+    /// ```toml
+    /// QRL = "Is this frequency in use?"
+    /// "HW?" = "How do you copy?"
+    /// ```
+    pub fn extend_from_toml(&mut self, text: &str) -> Result<(), String> {
+        let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        for (code, meaning) in table {
+            let toml::Value::String(meaning) = meaning else {
+                return Err(format!("The meaning of {code} must be a string"));
+            };
+            let code = code.trim().to_uppercase();
+            if code.is_empty() || code.contains(char::is_whitespace) {
+                return Err(format!("{code:?} must be a single word"));
+            }
+            if let Some(character) = split_characters(&code)
+                .into_iter()
+                .find(|character| encode(character, &Separators::default()).is_empty())
+            {
+                return Err(format!("{code}: no morse code for {character:?}"));
+            }
+            if meaning.trim().is_empty() {
+                return Err(format!("{code} needs a meaning"));
+            }
+            match self.entries.iter_mut().find(|(c, _)| *c == code) {
+                Some(entry) => entry.1 = meaning,
+                None => self.entries.push((code, meaning)),
+            }
+        }
+        Ok(())
+    }
+
+    /// The abbreviations, to drill as words.
+    pub fn codes(&self) -> Vec<String> {
+        self.entries.iter().map(|(code, _)| code.clone()).collect()
+    }
+
+    pub fn meaning(&self, code: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(c, _)| c == code)
+            .map(|(_, meaning)| meaning.as_str())
+    }
+
+    /// An abbreviation and its meaning, e.g. `QTH (My location is)`.
+    pub fn answer(&self, code: &str) -> String {
+        match self.meaning(code) {
+            Some(meaning) => format!("{code} ({meaning})"),
+            None => code.to_string(),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct UnrecognizedRow {
+    abbreviation: String,
+    meaning: String,
+    copied: String,
+    mean_time: String,
+}
+
+/// Prints the abbreviations of a drill that were missed, or copied
+/// slower than a perfect review grade, as not yet recognized at full
+/// speed.
+///
+/// # Arguments
+/// * `dot_duration` - The dot length the drill was sent at.
+///
+/// # Returns
+/// The table, if any were, to be copied.
+pub fn print_unrecognized(
+    results: &DrillResults,
+    meanings: &Meanings,
+    dot_duration: u32,
+) -> Option<String> {
+    // Fast enough, as for the reviews:
+    let fast = Duration::from_millis(dot_duration.into()) * 2;
+    let mut rows: Vec<UnrecognizedRow> = Vec::new();
+    let mut codes: Vec<&str> = results
+        .groups
+        .iter()
+        .map(|group| group.sent.as_str())
+        .collect();
+    codes.sort_unstable();
+    codes.dedup();
+    for code in codes {
+        let groups: Vec<_> = results
+            .groups
+            .iter()
+            .filter(|group| group.sent == code)
+            .collect();
+        let letters = code.chars().count() as u32;
+        if groups
+            .iter()
+            .all(|group| grade(group.exact, group.time, fast * letters) == 5)
+        {
+            continue;
+        }
+        let exact = groups.iter().filter(|group| group.exact).count();
+        let time: Duration = groups.iter().map(|group| group.time).sum();
+        rows.push(UnrecognizedRow {
+            abbreviation: code.to_string(),
+            meaning: meanings.meaning(code).unwrap_or_default().to_string(),
+            copied: format!("{exact}/{}", groups.len()),
+            mean_time: format!("{:.1}s", time.as_secs_f32() / groups.len() as f32),
+        });
+    }
+    if rows.is_empty() {
+        println!("\nEvery abbreviation was recognized at full speed.");
+        return None;
+    }
+    let text = Table::new(&rows).with(Style::rounded()).to_string();
+    println!("\nNot yet recognized at full speed:\n");
+    println!("{text}");
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fecr_quiz::QuizResult;
+    use crate::practice::GroupCopy;
+
+    #[test]
+    fn test_meanings() {
+        let mut meanings = Meanings::builtin();
+        assert_eq!(meanings.codes().len(), ABBREVIATIONS.len());
+        for code in meanings.codes() {
+            assert!(!encode(&code, &Separators::default()).is_empty(), "{code}");
+        }
+        assert_eq!(meanings.answer("QTH"), "QTH (My location is)");
+        meanings
+            .extend_from_toml("qrl = \"Busy?\"\n\"POTA\" = \"Parks on the air\"")
+            .unwrap();
+        assert_eq!(meanings.meaning("QRL"), Some("Busy?"));
+        assert_eq!(meanings.meaning("POTA"), Some("Parks on the air"));
+        assert_eq!(meanings.codes().len(), ABBREVIATIONS.len() + 1);
+        assert!(meanings.extend_from_toml("QRL = 1").is_err());
+        assert!(meanings.extend_from_toml("\"Q R\" = \"two\"").is_err());
+        assert!(meanings.extend_from_toml("\"Q#\" = \"hash\"").is_err());
+        assert_eq!("read".parse::<Direction>(), Ok(Direction::Read));
+    }
+
+    #[test]
+    fn test_unrecognized() {
+        let copy = |sent: &str, exact, ms| GroupCopy {
+            sent: sent.to_string(),
+            copied: sent.to_string(),
+            exact,
+            time: Duration::from_millis(ms),
+            wpm: 20,
+            tone: 600.0,
+            accuracy: exact as u32 as f64,
+            attempt: 1,
+        };
+        let results = DrillResults {
+            characters: QuizResult {
+                prompts: Vec::new(),
+                responses: Vec::new(),
+                reaction_times: Vec::new(),
+                typed_ahead: Vec::new(),
+                typed: Vec::new(),
+                sent_at: Vec::new(),
+            },
+            groups: vec![
+                copy("QTH", true, 100),
+                copy("WX", true, 2000),
+                copy("TNX", false, 100),
+                copy("QTH", true, 200),
+            ],
+            trials: Vec::new(),
+            review: Vec::new(),
+            paused: Duration::ZERO,
+            incomplete: false,
+        };
+        let table = print_unrecognized(&results, &Meanings::builtin(), 60).unwrap();
+        assert!(table.contains("TNX") && table.contains("WX"));
+        assert!(!table.contains("QTH"));
+    }
+}
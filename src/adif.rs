@@ -0,0 +1,139 @@
+use chrono::{DateTime, Utc};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The version of the ADIF specification the files are written to.
+pub const ADIF_VERSION: &str = "3.1.4";
+
+/// A field of ADIF, e.g. `<CALL:5>K1ABC`. The data of ADI files is
+/// ASCII, so anything else is written as `?`, and the line breaks as
+/// spaces, the length being counted in bytes.
+pub fn field(name: &str, value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| match c {
+            '\r' | '\n' => ' ',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '?',
+        })
+        .collect();
+    format!("<{}:{}>{value}", name.to_uppercase(), value.len())
+}
+
+/// The header of an ADI file, ending with `<EOH>`. Its text before the
+/// first field is ignored by the readers.
+pub fn header(created: DateTime<Utc>) -> String {
+    format!(
+        "Exported by {} {}\n{}\n{}\n{}\n{}\n<EOH>\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        field("ADIF_VER", ADIF_VERSION),
+        field("PROGRAMID", env!("CARGO_PKG_NAME")),
+        field("PROGRAMVERSION", env!("CARGO_PKG_VERSION")),
+        field(
+            "CREATED_TIMESTAMP",
+            &created.format("%Y%m%d %H%M%S").to_string()
+        ),
+    )
+}
+
+/// A record of ADIF, its fields in order, e.g. a contact.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AdifRecord {
+    pub fields: Vec<(String, String)>,
+}
+
+impl AdifRecord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a field, unless its value is empty, as ADIF leaves the
+    /// fields that aren't known out.
+    pub fn with(mut self, name: &str, value: &str) -> Self {
+        if !value.is_empty() {
+            self.fields.push((name.to_uppercase(), value.to_string()));
+        }
+        self
+    }
+
+    /// The record, as a line of an ADI file ending with `<EOR>`.
+    pub fn to_adi(&self) -> String {
+        let mut line: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(name, value)| field(name, value))
+            .collect();
+        line.push("<EOR>\n".to_string());
+        line.join(" ")
+    }
+}
+
+/// An ADI file, the records appended to it.
+pub struct AdifFile {
+    file: std::fs::File,
+}
+
+impl AdifFile {
+    /// Opens a file to append to, starting it with the header when it is
+    /// new (or empty).
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(header(Utc::now()).as_bytes())?;
+        }
+        Ok(Self { file })
+    }
+
+    /// Appends a record, right away, so that a crash loses none.
+    pub fn write(&mut self, record: &AdifRecord) -> io::Result<()> {
+        self.file.write_all(record.to_adi().as_bytes())?;
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_field() {
+        assert_eq!(field("call", "K1ABC"), "<CALL:5>K1ABC");
+        assert_eq!(field("COMMENT", ""), "<COMMENT:0>");
+        // The length is of the data, whatever is in it:
+        assert_eq!(field("COMMENT", "<EOR> 5:5"), "<COMMENT:9><EOR> 5:5");
+        assert_eq!(field("NAME", "José\r\nÅsa"), "<NAME:9>Jos?  ?sa");
+        assert_eq!(field("QTH", "a\tb"), "<QTH:3>a?b");
+    }
+
+    #[test]
+    fn test_framing() {
+        let created = Utc.with_ymd_and_hms(2024, 11, 2, 18, 30, 5).unwrap();
+        let header = header(created);
+        assert!(header.contains("<ADIF_VER:5>3.1.4\n"));
+        assert!(header.contains("<CREATED_TIMESTAMP:15>20241102 183005\n"));
+        assert!(header.ends_with("\n<EOH>\n"));
+        // The preamble must not start with a field:
+        assert!(!header.starts_with('<'));
+
+        let record = AdifRecord::new()
+            .with("call", "K1ABC")
+            .with("RST_SENT", "")
+            .with("MODE", "CW");
+        assert_eq!(record.to_adi(), "<CALL:5>K1ABC <MODE:2>CW <EOR>\n");
+        assert_eq!(AdifRecord::new().to_adi(), "<EOR>\n");
+
+        let path = std::env::temp_dir().join(format!("adif-test-{}.adi", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        for _ in 0..2 {
+            AdifFile::open(&path).unwrap().write(&record).unwrap();
+        }
+        let adi = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(adi.matches("<EOH>").count(), 1);
+        assert!(adi
+            .ends_with("<EOH>\n<CALL:5>K1ABC <MODE:2>CW <EOR>\n<CALL:5>K1ABC <MODE:2>CW <EOR>\n"));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::prelude::*;
+use crossterm::style::Stylize;
+use regex::{Regex, RegexBuilder};
+use std::process::Command;
+
+/// Watches the decoded text for some patterns, e.g. a callsign or CQ,
+/// to ring the bell as soon as one is heard, and to run a command with
+/// each message it is in, with `listen --alert`.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Alerts {
+    patterns: Vec<Regex>,
+    /// The command to run, with the message as its last argument.
+    exec: Option<String>,
+    // How many matches were found in the message so far:
+    matched: usize,
+}
+
+#[allow(dead_code)]
+impl Alerts {
+    /// The patterns are regular expressions, matched without regard to
+    /// case.
+    pub fn new(patterns: &[String], exec: Option<String>) -> Result<Self, regex::Error> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| RegexBuilder::new(pattern).case_insensitive(true).build())
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            patterns,
+            exec,
+            matched: 0,
+        })
+    }
+
+    /// Whether a pattern is in some text.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.patterns.iter().any(|pattern| pattern.is_match(text))
+    }
+
+    fn matches(&self, text: &str) -> usize {
+        self.patterns
+            .iter()
+            .map(|pattern| pattern.find_iter(text).count())
+            .sum()
+    }
+
+    /// Looks at the message so far.
+    ///
+    /// # Returns
+    /// Whether to ring the bell, as a pattern was just completed.
+    pub fn partial(&mut self, text: &str) -> bool {
+        let matched = self.matches(text);
+        let ring = matched > self.matched;
+        self.matched = self.matched.max(matched);
+        ring
+    }
+
+    /// Looks at a message that ended, running the command if a pattern
+    /// is in it.
+    ///
+    /// # Returns
+    /// Whether to ring the bell, when it didn't already ring for it.
+    pub fn message(&mut self, text: &str) -> bool {
+        let matched = self.matches(text);
+        let ring = matched > self.matched;
+        self.matched = 0;
+        if matched > 0 {
+            self.run(text);
+        }
+        ring
+    }
+
+    fn run(&self, message: &str) {
+        let Some(exec) = &self.exec else {
+            return;
+        };
+        let mut words = exec.split_whitespace();
+        let Some(program) = words.next() else {
+            return;
+        };
+        match Command::new(program).args(words).arg(message).spawn() {
+            // Waited for elsewhere, not to hold up the decoding:
+            Ok(mut child) => {
+                std::thread::spawn(move || child.wait());
+            }
+            Err(e) => error!("Could not run {program}: {e}"),
+        }
+    }
+
+    /// Where the patterns match in some text, as byte ranges in order,
+    /// an overlapping one only from the end of the one before it.
+    pub fn ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut found: Vec<(usize, usize)> = self
+            .patterns
+            .iter()
+            .flat_map(|pattern| pattern.find_iter(text))
+            .filter(|m| !m.is_empty())
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        found.sort();
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in found {
+            let done = ranges.last().map_or(0, |last| last.1);
+            if end > done {
+                ranges.push((start.max(done), end));
+            }
+        }
+        ranges
+    }
+
+    /// Highlights what the patterns match in some text.
+    pub fn highlight(&self, text: &str) -> String {
+        let mut highlighted = String::new();
+        let mut done = 0;
+        for (start, end) in self.ranges(text) {
+            highlighted.push_str(&text[done..start]);
+            highlighted.push_str(&text[start..end].yellow().bold().to_string());
+            done = end;
+        }
+        highlighted.push_str(&text[done..]);
+        highlighted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alerts() {
+        let patterns = ["cq".to_string(), r"N0CALL\b".to_string()];
+        let mut alerts = Alerts::new(&patterns, None).unwrap();
+        // The bell rings as soon as a pattern is complete:
+        let rings: Vec<bool> = ["C", "CQ", "CQ ", "CQ C", "CQ CQ", "CQ CQ DE N0CAL"]
+            .iter()
+            .map(|text| alerts.partial(text))
+            .collect();
+        assert_eq!(rings, [false, true, false, false, true, false]);
+        // N0CALL is only complete at the end of the message:
+        assert!(alerts.message("CQ CQ DE N0CALL"));
+        assert!(alerts.is_match("n0call") && !alerts.is_match("N0CALLX"));
+        assert!(!alerts.partial("QRZ"));
+        assert!(!alerts.message("QRZ"));
+
+        assert!(Alerts::new(&["(".to_string()], None).is_err());
+    }
+
+    #[test]
+    fn test_highlight() {
+        let patterns = ["cq".to_string(), "q d".to_string()];
+        let alerts = Alerts::new(&patterns, None).unwrap();
+        let bright = |text: &str| text.yellow().bold().to_string();
+        assert_eq!(
+            alerts.highlight("CQ DE CQ"),
+            format!("{}{}E {}", bright("CQ"), bright(" D"), bright("CQ"))
+        );
+        assert_eq!(alerts.highlight("QRZ"), "QRZ");
+        assert_eq!(alerts.ranges("CQ DE CQ"), [(0, 2), (2, 4), (6, 8)]);
+    }
+}
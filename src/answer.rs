@@ -0,0 +1,242 @@
+use crate::codec::{decode, Separators};
+use crate::detect::DECODER_WORD_DOTS;
+use crate::input::{read_copy, Copied, Flow, KeyReader};
+use crate::morse::Charset;
+use crate::send::{character_starts, key_word, read_keying, track_speed, Keyed, Keying, Keys};
+use crate::sidetone::{Sidetone, SidetoneOptions};
+use crate::speed::SpeedTracker;
+use std::io::{stdout, Write};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// How the copies of a drill are answered.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnswerMode {
+    /// Typed on the keyboard.
+    Type,
+    /// Keyed in morse code, and decoded.
+    Key,
+}
+
+impl FromStr for AnswerMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "type" => Ok(AnswerMode::Type),
+            "key" => Ok(AnswerMode::Key),
+            _ => Err(format!("Unknown answer mode: {s}")),
+        }
+    }
+}
+
+/// Where the copy of each group of a drill comes from.
+pub trait AnswerSource {
+    /// The keys read, which pause and stop the drill too.
+    fn keys(&self) -> &KeyReader;
+
+    /// Reads the copy of something that was played, as `read_copy` does.
+    fn read(
+        &mut self,
+        flow: &mut Flow,
+        sound_end: Instant,
+        timeout: Option<Duration>,
+        typeahead: bool,
+    ) -> Copied;
+
+    /// How the copies were keyed so far, if they were.
+    fn keying(&self) -> Option<&Keying> {
+        None
+    }
+}
+
+/// The copies typed on the keyboard.
+pub struct Keyboard {
+    keys: KeyReader,
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Self {
+            keys: KeyReader::start(),
+        }
+    }
+}
+
+impl AnswerSource for Keyboard {
+    fn keys(&self) -> &KeyReader {
+        &self.keys
+    }
+
+    fn read(
+        &mut self,
+        flow: &mut Flow,
+        sound_end: Instant,
+        timeout: Option<Duration>,
+        typeahead: bool,
+    ) -> Copied {
+        read_copy(&self.keys, flow, sound_end, timeout, typeahead)
+    }
+}
+
+/// The copies keyed with the keys of the keyboard, as a straight key or
+/// paddles, with a sidetone, and decoded.
+pub struct Keyer {
+    keys: KeyReader,
+    sidetone: Sidetone,
+    how: Keys,
+    /// The speed of the paddles, and the one to expect of a straight key
+    /// until it is measured.
+    dot_duration: u32,
+    charset: Charset,
+    tracker: SpeedTracker,
+    // The dot duration of the sender measured so far:
+    dot: u32,
+    keying: Keying,
+}
+
+impl Keyer {
+    pub fn new(
+        how: Keys,
+        sidetone: &SidetoneOptions,
+        dot_duration: u32,
+        charset: Charset,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            keys: KeyReader::with_releases()?,
+            sidetone: Sidetone::start(sidetone)?,
+            how,
+            dot_duration,
+            charset,
+            tracker: SpeedTracker::new(),
+            dot: dot_duration,
+            keying: Keying::default(),
+        })
+    }
+}
+
+/// The characters decoded from keyed code, each at the time its first
+/// mark started, and a space between the words.
+fn timed_characters(code: &str, starts: &[Instant], charset: Charset) -> Vec<(char, Instant)> {
+    let (mut typed, mut starts) = (Vec::new(), starts.iter());
+    let mut last = None;
+    for token in code.split(' ').filter(|token| !token.is_empty()) {
+        if token == "/" {
+            typed.extend(last.map(|at| (' ', at)));
+            continue;
+        }
+        let Some(&at) = starts.next() else {
+            break;
+        };
+        last = Some(at);
+        let decoded = decode(token, &Separators::default(), charset);
+        typed.extend(decoded.chars().map(|ch| (ch, at)));
+    }
+    typed
+}
+
+impl AnswerSource for Keyer {
+    fn keys(&self) -> &KeyReader {
+        &self.keys
+    }
+
+    /// Keys the copy once the sound has ended, for the sidetone not to
+    /// play over it, until a silence as long as the decoder takes to end
+    /// a word, or Enter. Each character is timed from when its first
+    /// mark was keyed.
+    fn read(
+        &mut self,
+        flow: &mut Flow,
+        sound_end: Instant,
+        timeout: Option<Duration>,
+        _typeahead: bool,
+    ) -> Copied {
+        let paused = flow.paused();
+        std::thread::sleep(sound_end.saturating_duration_since(Instant::now()));
+        self.keys.clear_flow(flow);
+        if !flow.wait(&self.keys) {
+            return Copied::Stop;
+        }
+        let deadline = timeout.map(|timeout| sound_end + timeout + (flow.paused() - paused));
+        // The copy ends after a long enough silence, at whichever speed
+        // is the slowest:
+        let word_end =
+            Duration::from_millis((DECODER_WORD_DOTS * self.dot.max(self.dot_duration)).into());
+        let (start, signals) = match key_word(
+            &self.keys,
+            flow,
+            &self.sidetone,
+            self.how,
+            self.dot_duration,
+            word_end,
+            deadline,
+        ) {
+            Keyed::Signals(start, signals) => (start, signals),
+            Keyed::TimedOut => return Copied::TimedOut(deadline.unwrap_or(sound_end)),
+            Keyed::Stop => return Copied::Stop,
+        };
+        // The keyer sends at its own speed, the sender's is measured:
+        if let Keys::Straight(_) = self.how {
+            self.dot = track_speed(&mut self.tracker, &signals, self.dot);
+        }
+        let (code, keying) = read_keying(&signals, self.dot);
+        self.keying.extend(&keying);
+        let typed = timed_characters(
+            &code,
+            &character_starts(start, &signals, self.dot),
+            self.charset,
+        );
+        let length: u32 = signals.iter().map(|&(_, ms)| ms).sum();
+        print!("{}", typed.iter().map(|&(ch, _)| ch).collect::<String>());
+        stdout().flush().unwrap();
+        Copied::Typed(typed, start + Duration::from_millis(length.into()))
+    }
+
+    fn keying(&self) -> Option<&Keying> {
+        Some(&self.keying)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timed_characters() {
+        let (start, ms) = (Instant::now(), Duration::from_millis);
+        // C Q, and E after a word space, at 60 ms dots:
+        let signals = [
+            (true, 180),
+            (false, 60),
+            (true, 60),
+            (false, 60),
+            (true, 180),
+            (false, 60),
+            (true, 60),
+            (false, 180),
+            (true, 180),
+            (false, 60),
+            (true, 180),
+            (false, 60),
+            (true, 60),
+            (false, 60),
+            (true, 180),
+            (false, 420),
+            (true, 60),
+        ];
+        let (code, _) = read_keying(&signals, 60);
+        assert_eq!(code, "-.-. --.- / .");
+        let starts = character_starts(start, &signals, 60);
+        assert_eq!(starts, [start, start + ms(840), start + ms(2040)]);
+        assert_eq!(
+            timed_characters(&code, &starts, Charset::Latin),
+            [
+                ('C', start),
+                ('Q', start + ms(840)),
+                (' ', start + ms(840)),
+                ('E', start + ms(2040))
+            ]
+        );
+        assert_eq!("key".parse::<AnswerMode>(), Ok(AnswerMode::Key));
+    }
+}
@@ -0,0 +1,151 @@
+/// Number of samples analyzed together. At 48kHz this is ~85ms, which
+/// resolves frequencies about 12Hz apart.
+const FRAME_SIZE: usize = 4096;
+
+/// The strongest frequency must have at least this many times the
+/// median power of the search range to count as a narrowband tone.
+const PEAK_TO_MEDIAN: f64 = 8.0;
+
+/// Searches a range of audio frequencies for the strongest tone.
+///
+/// A bank of Goertzel detectors, one every `step` Hz, accumulates the
+/// power found at each frequency. After each estimation window the
+/// frequency with the most power is reported, as long as it stands out
+/// from the rest of the range.
+pub struct ToneTracker {
+    frequencies: Vec<f64>,
+    coefficients: Vec<f64>,
+    powers: Vec<f64>,
+    frame: Vec<f64>,
+    frames_per_window: usize,
+    frames: usize,
+}
+
+impl ToneTracker {
+    /// Creates a new ToneTracker.
+    ///
+    /// # Arguments
+    /// * `min_freq` - The lowest frequency to search in Hz.
+    /// * `max_freq` - The highest frequency to search in Hz.
+    /// * `step` - The spacing between the searched frequencies in Hz.
+    /// * `window` - How many seconds of audio to analyze for each estimate.
+    /// * `sample_rate` - The sampling frequency in Hz.
+    pub fn new(min_freq: f32, max_freq: f32, step: f32, window: f32, sample_rate: u32) -> Self {
+        let count = ((max_freq - min_freq) / step).floor().max(0.0) as usize + 1;
+        let frequencies: Vec<f64> = (0..count)
+            .map(|i| (min_freq + step * i as f32) as f64)
+            .collect();
+        let coefficients = frequencies
+            .iter()
+            .map(|f| 2.0 * (2.0 * std::f64::consts::PI * f / sample_rate as f64).cos())
+            .collect();
+        let frames_per_window = ((window * sample_rate as f32) as usize / FRAME_SIZE).max(1);
+        Self {
+            powers: vec![0.0; frequencies.len()],
+            frequencies,
+            coefficients,
+            frame: Vec::with_capacity(FRAME_SIZE),
+            frames_per_window,
+            frames: 0,
+        }
+    }
+
+    /// Adds captured (unfiltered) samples to the current estimation window.
+    ///
+    /// # Returns
+    /// The frequency of the strongest tone in Hz, once per window, if
+    /// a tone was found.
+    pub fn push<S: Copy + Into<f64>>(&mut self, samples: &[S]) -> Option<f32> {
+        let mut estimate = None;
+        for &sample in samples {
+            self.frame.push(sample.into());
+            if self.frame.len() == FRAME_SIZE {
+                self.analyze_frame();
+                self.frame.clear();
+                self.frames += 1;
+                if self.frames == self.frames_per_window {
+                    estimate = self.estimate();
+                    self.powers.iter_mut().for_each(|p| *p = 0.0);
+                    self.frames = 0;
+                }
+            }
+        }
+        estimate
+    }
+
+    fn analyze_frame(&mut self) {
+        for (power, coeff) in self.powers.iter_mut().zip(&self.coefficients) {
+            let (mut s1, mut s2) = (0.0, 0.0);
+            for &sample in &self.frame {
+                let s0 = sample + coeff * s1 - s2;
+                s2 = s1;
+                s1 = s0;
+            }
+            *power += s1 * s1 + s2 * s2 - coeff * s1 * s2;
+        }
+    }
+
+    fn estimate(&self) -> Option<f32> {
+        let (peak, &peak_power) = self
+            .powers
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))?;
+        let mut sorted = self.powers.clone();
+        sorted.sort_by(f64::total_cmp);
+        let median = sorted[sorted.len() / 2];
+        if peak_power <= 0.0 || peak_power < median * PEAK_TO_MEDIAN {
+            return None;
+        }
+        // Refine the estimate between neighboring frequencies with a parabola:
+        let mut freq = self.frequencies[peak];
+        if peak > 0 && peak + 1 < self.powers.len() {
+            let (a, b, c) = (self.powers[peak - 1], peak_power, self.powers[peak + 1]);
+            let denominator = a - 2.0 * b + c;
+            if denominator != 0.0 {
+                let offset = (0.5 * (a - c) / denominator).clamp(-0.5, 0.5);
+                freq += offset * (self.frequencies[1] - self.frequencies[0]);
+            }
+        }
+        Some(freq as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    #[test]
+    fn test_tone_tracker_finds_tone() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let mut tracker = ToneTracker::new(300.0, 1200.0, 10.0, 2.0, SAMPLE_RATE);
+        let mut estimates = Vec::new();
+        for block in 0..300 {
+            let samples: Vec<f64> = (0..480)
+                .map(|i| {
+                    let t = (block * 480 + i) as f64 / SAMPLE_RATE as f64;
+                    0.3 * (2.0 * std::f64::consts::PI * 642.0 * t).sin() + rng.gen_range(-0.3..0.3)
+                })
+                .collect();
+            estimates.extend(tracker.push(&samples));
+        }
+        assert!(!estimates.is_empty());
+        for estimate in estimates {
+            assert!((estimate - 642.0).abs() < 5.0, "estimate {estimate}");
+        }
+    }
+
+    #[test]
+    fn test_tone_tracker_ignores_noise() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+        let mut tracker = ToneTracker::new(300.0, 1200.0, 10.0, 1.0, SAMPLE_RATE);
+        let samples: Vec<f64> = (0..SAMPLE_RATE * 3)
+            .map(|_| rng.gen_range(-0.5..0.5))
+            .collect();
+        assert_eq!(tracker.push(&samples), None);
+        assert_eq!(tracker.push(&vec![0.0; SAMPLE_RATE as usize * 2]), None);
+    }
+}
@@ -0,0 +1,300 @@
+use crate::json::{Json, Object};
+use crate::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::{Arc, Mutex};
+
+/// Slow fading of the signal, going down by `depth` and back up again
+/// over `period` seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Qsb {
+    pub period: f32,
+    /// How much of the signal fades away at the bottom of a fade,
+    /// [0.0..1.0].
+    pub depth: f32,
+}
+
+impl FromStr for Qsb {
+    type Err = String;
+
+    /// A period in seconds and a depth, as a fraction or a percentage,
+    /// e.g. `10:0.5`, `8s:70%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("QSB must be a period in seconds and a depth, like 10:0.5: {s}");
+        let (period, depth) = s.split_once(':').ok_or_else(invalid)?;
+        let period = period
+            .trim()
+            .trim_end_matches('s')
+            .parse::<f32>()
+            .ok()
+            .filter(|period| *period > 0.0 && period.is_finite())
+            .ok_or_else(invalid)?;
+        let depth = match depth.trim().strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().map(|percent| percent / 100.0),
+            None => depth.trim().parse::<f32>(),
+        }
+        .ok()
+        .filter(|depth| (0.0..=1.0).contains(depth))
+        .ok_or_else(invalid)?;
+        Ok(Qsb { period, depth })
+    }
+}
+
+/// A seed, or one picked at random if None, small enough to be typed
+/// again.
+pub fn seed_or_random(seed: Option<u64>) -> u64 {
+    seed.unwrap_or_else(|| rand::thread_rng().gen_range(0..1_000_000))
+}
+
+/// The conditions of the band that practice audio is sent through: the
+/// noise, the static crashes and the fading.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Conditions {
+    /// In dB, of the tone while it is on against the white noise, over
+    /// the whole band of the samples.
+    pub snr: Option<f32>,
+    /// About how many static crashes a second there are.
+    pub qrn: Option<f32>,
+    pub qsb: Option<Qsb>,
+    /// Seeds the noise, so that the same band can be heard again.
+    pub seed: u64,
+}
+
+impl Conditions {
+    /// The conditions of some settings, or None if they leave the band
+    /// clean.
+    ///
+    /// # Arguments
+    /// * `seed` - The seed of the noise, or None to pick one at random,
+    ///   small enough to be typed again.
+    pub fn new(
+        snr: Option<f32>,
+        qrn: Option<f32>,
+        qsb: Option<Qsb>,
+        seed: Option<u64>,
+    ) -> Option<Self> {
+        if snr.is_none() && qrn.is_none() && qsb.is_none() {
+            return None;
+        }
+        Some(Self {
+            snr,
+            qrn,
+            qsb,
+            seed: seed_or_random(seed),
+        })
+    }
+
+    pub fn to_json(self) -> String {
+        Object::new()
+            .number("snr_db", self.snr)
+            .number("qrn", self.qrn)
+            .number("qsb_period_s", self.qsb.map(|qsb| qsb.period))
+            .number("qsb_depth", self.qsb.map(|qsb| qsb.depth))
+            .string("seed", self.seed.to_string().as_str())
+            .end()
+    }
+
+    pub fn from_json(json: &Json) -> Option<Self> {
+        let number = |key| json.get(key).and_then(Json::as_f64).map(|v| v as f32);
+        let qsb = match (number("qsb_period_s"), number("qsb_depth")) {
+            (Some(period), Some(depth)) => Some(Qsb { period, depth }),
+            _ => None,
+        };
+        Some(Self {
+            snr: number("snr_db"),
+            qrn: number("qrn"),
+            qsb,
+            seed: json.get("seed")?.as_u64()?,
+        })
+    }
+}
+
+impl std::fmt::Display for Conditions {
+    /// E.g. `SNR 6 dB, QRN 2/s, QSB 10 s 50%`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(snr) = self.snr {
+            parts.push(format!("SNR {snr} dB"));
+        }
+        if let Some(qrn) = self.qrn {
+            parts.push(format!("QRN {qrn}/s"));
+        }
+        if let Some(qsb) = self.qsb {
+            parts.push(format!("QSB {} s {:.0}%", qsb.period, qsb.depth * 100.0));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// How loud a static crash is at most, against the tone.
+const CRASH_LEVEL: f32 = 2.0;
+
+/// How long a static crash takes to die away, at most, in seconds.
+const CRASH_DECAY: f32 = 0.03;
+
+struct State {
+    conditions: Conditions,
+    rng: StdRng,
+    // The samples faded so far, for the fading to go on from one
+    // message to the next:
+    position: u64,
+    phase: f32,
+    // The static crash dying away, as its level and how much of it is
+    // left after each sample:
+    crash: (f32, f32),
+}
+
+/// Sends practice audio through the conditions of a band, the noise
+/// going on from one message to the next. Clones share the band.
+#[derive(Clone)]
+pub struct Band(Arc<Mutex<State>>);
+
+impl Band {
+    pub fn new(conditions: Conditions) -> Self {
+        let mut rng = StdRng::seed_from_u64(conditions.seed);
+        let phase = rng.gen_range(0.0..std::f32::consts::TAU);
+        Self(Arc::new(Mutex::new(State {
+            conditions,
+            rng,
+            position: 0,
+            phase,
+            crash: (0.0, 0.0),
+        })))
+    }
+
+    /// Fades some samples, then adds the noise and the static crashes.
+    ///
+    /// # Arguments
+    /// * `volume` - The peak amplitude of the tone, that the noise is
+    ///   measured against.
+    pub fn apply(&self, samples: &mut [f32], volume: f32, sample_rate: u32) {
+        let mut state = self.0.lock().unwrap();
+        let state = &mut *state;
+        let rate = sample_rate.max(1) as f32;
+        let conditions = state.conditions;
+        let sigma = conditions
+            .snr
+            .map_or(0.0, |snr| volume / 2f32.sqrt() / 10f32.powf(snr / 20.0));
+        let crashes = conditions.qrn.map_or(0.0, |qrn| qrn / rate);
+        for sample in samples.iter_mut() {
+            if let Some(qsb) = conditions.qsb {
+                let period = ((qsb.period * rate) as u64).max(1);
+                let angle =
+                    std::f32::consts::TAU * (state.position % period) as f32 / period as f32;
+                *sample *= 1.0 - qsb.depth * (0.5 - 0.5 * (angle + state.phase).cos());
+            }
+            state.position += 1;
+            if sigma > 0.0 {
+                *sample += sigma * gaussian(&mut state.rng);
+            }
+            if crashes > 0.0 {
+                if state.rng.gen::<f32>() < crashes {
+                    let decay = state.rng.gen_range(0.1..1.0) * CRASH_DECAY * rate;
+                    state.crash = (
+                        state.rng.gen_range(0.2..1.0) * CRASH_LEVEL * volume,
+                        (-1.0 / decay).exp(),
+                    );
+                }
+                let (level, decay) = &mut state.crash;
+                *sample += *level * gaussian(&mut state.rng);
+                *level *= *decay;
+            }
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// A sample of Gaussian noise, by the Box-Muller transform.
+fn gaussian(rng: &mut impl Rng) -> f32 {
+    let (u1, u2): (f32, f32) = (rng.gen_range(f32::EPSILON..1.0), rng.gen());
+    (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn power(samples: &[f32]) -> f32 {
+        samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32
+    }
+
+    #[test]
+    fn test_qsb() {
+        assert_eq!(
+            "10:0.5".parse::<Qsb>(),
+            Ok(Qsb {
+                period: 10.0,
+                depth: 0.5
+            })
+        );
+        assert_eq!(
+            "8s:70%".parse::<Qsb>(),
+            Ok(Qsb {
+                period: 8.0,
+                depth: 0.7
+            })
+        );
+        assert!("10".parse::<Qsb>().is_err());
+        assert!("0:0.5".parse::<Qsb>().is_err());
+        assert!("inf:0.5".parse::<Qsb>().is_err());
+        assert!("10:1.5".parse::<Qsb>().is_err());
+    }
+
+    #[test]
+    fn test_conditions() {
+        assert_eq!(Conditions::new(None, None, None, Some(1)), None);
+        let qsb = Some(Qsb {
+            period: 10.0,
+            depth: 0.5,
+        });
+        let conditions = Conditions::new(Some(-3.0), None, qsb, Some(42)).unwrap();
+        assert_eq!(conditions.to_string(), "SNR -3 dB, QSB 10 s 50%");
+        let json = Json::parse(&conditions.to_json()).unwrap();
+        assert_eq!(Conditions::from_json(&json), Some(conditions));
+        let conditions = Conditions::new(Some(-3.0), None, None, Some(u64::MAX)).unwrap();
+        let json = Json::parse(&conditions.to_json()).unwrap();
+        assert_eq!(Conditions::from_json(&json), Some(conditions));
+        assert!(Conditions::new(None, Some(1.0), None, None).unwrap().seed < 1_000_000);
+        // Still JSON, with the number that isn't one left out:
+        let conditions = Conditions::new(Some(f32::NAN), Some(1.0), None, Some(1)).unwrap();
+        let json = Json::parse(&conditions.to_json()).unwrap();
+        assert_eq!(json.get("snr_db"), Some(&Json::Null));
+    }
+
+    #[test]
+    fn test_band() {
+        let rate = 8000;
+        let conditions = Conditions::new(Some(0.0), None, None, Some(7)).unwrap();
+        let mut quiet = vec![0.0; rate as usize];
+        Band::new(conditions).apply(&mut quiet, 0.5, rate);
+        // At 0 dB, the noise is as strong as the tone:
+        assert!((power(&quiet) - 0.125).abs() < 0.01, "{}", power(&quiet));
+        // The same seed is the same noise, going on from block to block:
+        let band = Band::new(conditions);
+        let mut again = vec![0.0; rate as usize];
+        band.apply(&mut again[..100], 0.5, rate);
+        band.apply(&mut again[100..], 0.5, rate);
+        assert_eq!(again, quiet);
+        // Faded all the way down, half a period after the top:
+        let qsb = Qsb {
+            period: 2.0,
+            depth: 1.0,
+        };
+        let mut faded = vec![0.5; 2 * rate as usize];
+        Band::new(Conditions::new(None, None, Some(qsb), Some(7)).unwrap())
+            .apply(&mut faded, 0.5, rate);
+        let (low, high) = faded.iter().fold((1.0_f32, 0.0_f32), |(low, high), s| {
+            (low.min(*s), high.max(*s))
+        });
+        assert!(low < 0.01 && high > 0.49, "{low} {high}");
+        // Static crashes, in silence:
+        let mut crashes = vec![0.0; rate as usize * 4];
+        Band::new(Conditions::new(None, Some(5.0), None, Some(7)).unwrap()).apply(
+            &mut crashes,
+            0.5,
+            rate,
+        );
+        assert!(crashes.iter().any(|s| s.abs() > 0.2));
+        assert!(crashes.iter().filter(|s| s.abs() > 0.01).count() < crashes.len() / 2);
+    }
+}
@@ -0,0 +1,417 @@
+use crate::band::{Band, Conditions};
+use crate::callsign::random_callsign;
+use crate::codec::{encode, Separators};
+use crate::detect::{Channel, TextOptions};
+use crate::filter::Detector;
+use crate::keyer::{Keyer, Timing, SAMPLE_RATE};
+use crate::morse::wpm_to_dot_length;
+use crate::score::Score;
+use crate::wav::{decode_samples, read_channel};
+use crate::words::{read_words, WordPicker, WORDS};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tabled::settings::style::Style;
+use tabled::{Table, Tabled};
+
+/// The amplitude of the tones rendered, which the noise is measured
+/// against.
+const TONE_AMPLITUDE: f32 = 0.5;
+
+/// The words of a text rendered, after its callsign.
+const TEXT_WORDS: (usize, usize) = (3, 5);
+
+/// One recording of a corpus, and the text expected of it.
+pub struct Case {
+    pub name: String,
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub expected: String,
+    /// The speed it was sent at, when it is known.
+    pub wpm: Option<u32>,
+    /// Its signal to noise ratio in dB, when it is known.
+    pub snr: Option<f32>,
+}
+
+/// The speeds and the noise that texts are rendered at, every speed at
+/// every signal to noise ratio.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub wpms: Vec<u32>,
+    /// In dB, of the tone while it is on against the white noise, over
+    /// the whole band of the samples.
+    pub snrs: Vec<f32>,
+    /// About how many static crashes a second there are, on top of the
+    /// white noise.
+    pub impulses: f32,
+    pub tone_freq: f32,
+}
+
+/// The name of a case of the grid, e.g. `cq_20wpm_-10db`, from which
+/// `tags` reads the speed and the noise back.
+fn tagged(name: &str, wpm: u32, snr: f32) -> String {
+    format!("{name}_{wpm}wpm_{snr}db")
+}
+
+/// The speed and the signal to noise ratio of a case, from the parts of
+/// its name, e.g. `20wpm` and `-10db` in `cq_20wpm_-10db`.
+fn tags(name: &str) -> (Option<u32>, Option<f32>) {
+    let mut found = (None, None);
+    for part in name.to_lowercase().split('_') {
+        if let Some(wpm) = part.strip_suffix("wpm") {
+            found.0 = wpm.parse().ok().or(found.0);
+        } else if let Some(snr) = part.strip_suffix("db") {
+            found.1 = snr.parse().ok().or(found.1);
+        }
+    }
+    found
+}
+
+/// Renders a text as a keyed tone, with noise, after some silence to
+/// calibrate on and with enough after it for the message to end.
+pub fn render(text: &str, wpm: u32, snr: f32, grid: &Grid, rng: &mut impl Rng) -> Vec<f32> {
+    let dot_duration = wpm_to_dot_length(wpm.max(1));
+    let code = encode(text, &Separators::default());
+    let events = Timing::new(dot_duration, None).key_events(&code);
+    let mut keyer = Keyer::new(&events, SAMPLE_RATE, grid.tone_freq, TONE_AMPLITUDE, 5.0);
+    let mut samples = vec![0.0; SAMPLE_RATE as usize / 2];
+    let mut block = [0.0; 512];
+    loop {
+        let count = keyer.fill(&mut block);
+        samples.extend_from_slice(&block[..count]);
+        if count < block.len() {
+            break;
+        }
+    }
+    let after = (SAMPLE_RATE * dot_duration * 25 / 1000) as usize;
+    samples.resize(samples.len() + after, 0.0);
+    let qrn = Some(grid.impulses).filter(|&impulses| impulses > 0.0);
+    let conditions = Conditions::new(Some(snr), qrn, None, Some(rng.gen()));
+    if let Some(conditions) = conditions {
+        Band::new(conditions).apply(&mut samples, TONE_AMPLITUDE, SAMPLE_RATE);
+    }
+    samples
+}
+
+/// Renders a text at every point of the grid.
+fn render_grid(name: &str, text: &str, grid: &Grid, rng: &mut impl Rng) -> Vec<Case> {
+    let mut cases = Vec::new();
+    for &wpm in &grid.wpms {
+        for &snr in &grid.snrs {
+            cases.push(Case {
+                name: tagged(name, wpm, snr),
+                samples: render(text, wpm, snr, grid, rng),
+                sample_rate: SAMPLE_RATE,
+                expected: text.to_string(),
+                wpm: Some(wpm),
+                snr: Some(snr),
+            });
+        }
+    }
+    cases
+}
+
+/// Makes up a synthetic corpus of `count` random texts, each a callsign
+/// and a few common words, rendered at every point of the grid.
+pub fn synthesize(grid: &Grid, count: usize, rng: &mut impl Rng) -> Vec<Case> {
+    let picker = WordPicker::new(read_words(WORDS, (2, 6)));
+    let mut cases = Vec::new();
+    for n in 1..=count {
+        let mut words = vec![random_callsign(rng)];
+        for _ in 0..rng.gen_range(TEXT_WORDS.0..=TEXT_WORDS.1) {
+            words.push(picker.pick(rng).to_string());
+        }
+        cases.extend(render_grid(
+            &format!("synth{n:03}"),
+            &words.join(" "),
+            grid,
+            rng,
+        ));
+    }
+    cases
+}
+
+/// Reads the corpus of a directory: each text file with the WAV file of
+/// the same name, e.g. `cq.txt` and `cq.wav`, or, without one, rendered
+/// at every point of the grid. The speed and the noise of a recording
+/// are read from its name, e.g. `cq_20wpm_-10db.wav`, when it has them.
+pub fn load(
+    dir: &Path,
+    grid: &Grid,
+    rng: &mut impl Rng,
+) -> Result<Vec<Case>, Box<dyn std::error::Error>> {
+    let mut texts: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "txt"))
+        .collect();
+    texts.sort();
+    let mut cases = Vec::new();
+    for text in texts {
+        let name = text
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let expected = std::fs::read_to_string(&text)?.trim().to_string();
+        let wav = text.with_extension("wav");
+        if !wav.exists() {
+            cases.extend(render_grid(&name, &expected.to_uppercase(), grid, rng));
+            continue;
+        }
+        let (samples, sample_rate) = read_channel(&wav.to_string_lossy(), Channel::Mix)
+            .map_err(|e| format!("Could not read {}: {e}", wav.display()))?;
+        let (wpm, snr) = tags(&name);
+        cases.push(Case {
+            name,
+            samples,
+            sample_rate,
+            expected,
+            wpm,
+            snr,
+        });
+    }
+    Ok(cases)
+}
+
+/// Writes a corpus to a directory as it is read by `load`, to decode it
+/// again later, e.g. after changing the detector.
+pub fn save(dir: &Path, cases: &[Case]) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(dir)?;
+    for case in cases {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: case.sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = WavWriter::create(dir.join(format!("{}.wav", case.name)), spec)?;
+        for &sample in &case.samples {
+            writer.write_sample(sample)?;
+        }
+        writer.finalize()?;
+        std::fs::write(
+            dir.join(format!("{}.txt", case.name)),
+            format!("{}\n", case.expected),
+        )?;
+    }
+    Ok(())
+}
+
+/// The settings the corpus is decoded with, as `listen` would.
+pub struct Decoder {
+    /// The tone to decode, or None to search for it.
+    pub tone_freq: Option<f32>,
+    pub tone_range: (f32, f32),
+    pub bandwidth: f32,
+    pub threshold: Option<f32>,
+    pub detector: Detector,
+    /// The dot duration, unless the speed of a case is known.
+    pub dot_duration: u32,
+    /// Whether to follow the speed, from `dot_duration`, rather than be
+    /// told the speed of each case.
+    pub adaptive_speed: bool,
+    pub text_options: TextOptions,
+}
+
+impl Decoder {
+    /// Decodes a case.
+    ///
+    /// # Returns
+    /// All of its messages, a space apart.
+    fn decode(&self, case: &Case) -> Result<String, Box<dyn std::error::Error>> {
+        let dot_duration = match (self.adaptive_speed, case.wpm) {
+            (false, Some(wpm)) => wpm_to_dot_length(wpm.max(1)),
+            _ => self.dot_duration,
+        };
+        let (tone_freq, tone_range) = match self.tone_freq {
+            Some(tone_freq) => (tone_freq, None),
+            None => (
+                (self.tone_range.0 + self.tone_range.1) / 2.0,
+                Some(self.tone_range),
+            ),
+        };
+        let messages = decode_samples(
+            &case.samples,
+            case.sample_rate,
+            tone_freq,
+            tone_range,
+            self.bandwidth,
+            self.threshold,
+            dot_duration / 4,
+            self.detector,
+            dot_duration,
+            dot_duration * 20,
+            self.adaptive_speed,
+            self.text_options.clone(),
+        )?;
+        Ok(messages.join(" "))
+    }
+}
+
+/// How well some cases were decoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Tally {
+    cases: usize,
+    characters: Score,
+    words: Score,
+}
+
+impl Tally {
+    fn add(&mut self, characters: Score, words: Score) {
+        self.cases += 1;
+        self.characters.add(characters);
+        self.words.add(words);
+    }
+}
+
+#[derive(Tabled)]
+struct BreakdownRow {
+    group: String,
+    recordings: usize,
+    cer: String,
+    wer: String,
+}
+
+/// A table of the tallies of a breakdown, e.g. by speed.
+fn breakdown<K: Ord>(tallies: &BTreeMap<K, Tally>, label: impl Fn(&K) -> String) -> String {
+    let rows: Vec<BreakdownRow> = tallies
+        .iter()
+        .map(|(key, tally)| BreakdownRow {
+            group: label(key),
+            recordings: tally.cases,
+            cer: format!("{:.1}%", tally.characters.error_rate() * 100.0),
+            wer: format!("{:.1}%", tally.words.error_rate() * 100.0),
+        })
+        .collect();
+    Table::new(&rows).with(Style::rounded()).to_string()
+}
+
+/// Decodes every case of a corpus, printing those decoded wrong as it
+/// goes, then the error rates of the characters and of the words, of
+/// all of them and by speed and signal to noise ratio.
+pub fn bench(cases: &[Case], decoder: &Decoder) -> Result<(), Box<dyn std::error::Error>> {
+    if cases.is_empty() {
+        return Err("The corpus has no recording".into());
+    }
+    let started = Instant::now();
+    let mut total = Tally::default();
+    let mut audio = Duration::ZERO;
+    let mut by_wpm: BTreeMap<Option<u32>, Tally> = BTreeMap::new();
+    // By tenths of a dB, to be ordered:
+    let mut by_snr: BTreeMap<Option<i64>, Tally> = BTreeMap::new();
+    for case in cases {
+        let decoded = decoder.decode(case)?;
+        let characters = Score::of(&decoded, &case.expected);
+        let words = Score::of_words(&decoded, &case.expected);
+        if characters.errors > 0 {
+            println!("{}: {characters}", case.name);
+            println!("  expected: {}", case.expected);
+            println!("  decoded:  {decoded}");
+        }
+        total.add(characters, words);
+        by_wpm.entry(case.wpm).or_default().add(characters, words);
+        by_snr
+            .entry(case.snr.map(|snr| (snr * 10.0).round() as i64))
+            .or_default()
+            .add(characters, words);
+        audio += Duration::from_secs_f64(case.samples.len() as f64 / case.sample_rate as f64);
+    }
+    let unknown = || "unknown".to_string();
+    println!(
+        "\n{} recordings, {:.1} minutes of audio, decoded in {:.1} s",
+        total.cases,
+        audio.as_secs_f64() / 60.0,
+        started.elapsed().as_secs_f64()
+    );
+    println!("Characters: {}", total.characters);
+    println!(
+        "Words: WER {:.1}% ({} wrong of {})",
+        total.words.error_rate() * 100.0,
+        total.words.errors,
+        total.words.length
+    );
+    println!("\nBy speed:\n");
+    println!(
+        "{}",
+        breakdown(&by_wpm, |wpm| wpm
+            .map_or_else(unknown, |wpm| format!("{wpm} WPM")))
+    );
+    println!("\nBy signal to noise ratio:\n");
+    println!(
+        "{}",
+        breakdown(&by_snr, |snr| snr
+            .map_or_else(unknown, |snr| format!("{} dB", snr as f32 / 10.0)))
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn grid() -> Grid {
+        Grid {
+            wpms: vec![15, 25],
+            snrs: vec![20.0, -10.0],
+            impulses: 0.0,
+            tone_freq: 700.0,
+        }
+    }
+
+    fn decoder() -> Decoder {
+        Decoder {
+            tone_freq: Some(700.0),
+            tone_range: (300.0, 1200.0),
+            bandwidth: 200.0,
+            threshold: None,
+            detector: Detector::Peak,
+            dot_duration: 60,
+            adaptive_speed: false,
+            text_options: TextOptions::default(),
+        }
+    }
+
+    #[test]
+    fn test_tags() {
+        assert_eq!(tagged("cq", 20, -10.0), "cq_20wpm_-10db");
+        assert_eq!(tags("cq_20wpm_-10db"), (Some(20), Some(-10.0)));
+        assert_eq!(tags("synth001_15WPM_2.5dB"), (Some(15), Some(2.5)));
+        assert_eq!(tags("cq-de-k1abc"), (None, None));
+    }
+
+    #[test]
+    fn test_synthesize() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let cases = synthesize(&grid(), 2, &mut rng);
+        assert_eq!(cases.len(), 8);
+        assert_eq!(cases[0].wpm, Some(15));
+        assert_eq!(cases[1].snr, Some(-10.0));
+        assert_eq!(cases[0].expected, cases[3].expected);
+        assert_ne!(cases[0].expected, cases[4].expected);
+        // Clean enough, it is decoded as it was sent:
+        let decoded = decoder().decode(&cases[0]).unwrap();
+        assert_eq!(decoded, cases[0].expected);
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir().join(format!("bench-{}", std::process::id()));
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+        let grid = grid();
+        let cases = render_grid("cq", "CQ DE K1ABC", &grid, &mut rng);
+        save(&dir, &cases[..1]).unwrap();
+        std::fs::write(dir.join("tnx.txt"), "tnx fer call\n").unwrap();
+        let loaded = load(&dir, &grid, &mut rng).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        // The recording, then the text rendered over the grid:
+        assert_eq!(loaded.len(), 5);
+        assert_eq!(loaded[0].name, "cq_15wpm_20db");
+        assert_eq!(loaded[0].samples, cases[0].samples);
+        assert_eq!((loaded[0].wpm, loaded[0].snr), (Some(15), Some(20.0)));
+        assert_eq!(loaded[1].expected, "TNX FER CALL");
+        assert!(bench(&loaded, &decoder()).is_ok());
+    }
+}
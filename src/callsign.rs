@@ -0,0 +1,97 @@
+use rand::Rng;
+use regex::Regex;
+
+/// Callsign prefixes, each with how often it is heard on the air.
+const PREFIXES: [(&str, u32); 24] = [
+    ("W", 20),
+    ("K", 20),
+    ("N", 10),
+    ("AA", 3),
+    ("KB", 2),
+    ("KD", 2),
+    ("VE", 6),
+    ("VA", 2),
+    ("G", 6),
+    ("M", 2),
+    ("DL", 6),
+    ("DK", 2),
+    ("JA", 6),
+    ("JH", 2),
+    ("F", 3),
+    ("I", 3),
+    ("EA", 3),
+    ("ON", 2),
+    ("PA", 2),
+    ("OK", 2),
+    ("SP", 2),
+    ("UA", 2),
+    ("VK", 2),
+    ("PY", 2),
+];
+
+const LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Makes up a callsign: a prefix, a digit, and 1 to 3 suffix letters,
+/// e.g. K1ABC.
+pub fn random_callsign(rng: &mut impl Rng) -> String {
+    let total: u32 = PREFIXES.iter().map(|&(_, weight)| weight).sum();
+    let mut pick = rng.gen_range(0..total);
+    let prefix = PREFIXES
+        .iter()
+        .find(|&&(_, weight)| {
+            let found = pick < weight;
+            pick = pick.saturating_sub(weight);
+            found
+        })
+        .map_or("K", |&(prefix, _)| prefix);
+    let suffix: String = (0..rng.gen_range(1..=3))
+        .map(|_| LETTERS[rng.gen_range(0..LETTERS.len())] as char)
+        .collect();
+    format!("{prefix}{}{suffix}", rng.gen_range(0..=9))
+}
+
+/// A callsign, with a prefix or a suffix after a slash, e.g. `K1ABC`,
+/// `2E0XYZ`, `DL/W5XYZ` or `W1AW/P`, all of it decoded.
+#[allow(dead_code)]
+pub fn callsign_regex() -> Regex {
+    Regex::new(r"^([A-Z0-9]{1,4}/)?([0-9]?[A-Z]{1,2}|[A-Z][0-9])[0-9][A-Z]{1,4}(/[A-Z0-9]{1,4})?$")
+        .unwrap()
+}
+
+/// Reads a list of callsigns, the first word of each line. Empty lines
+/// and comments starting with '#' are skipped.
+pub fn read_callsigns(text: &str) -> Vec<String> {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_uppercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_callsign() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let callsign = random_callsign(&mut rng);
+            let digit = callsign.find(|c: char| c.is_ascii_digit()).unwrap();
+            assert!(PREFIXES
+                .iter()
+                .any(|&(prefix, _)| prefix == &callsign[..digit]));
+            let suffix = &callsign[digit + 1..];
+            assert!((1..=3).contains(&suffix.len()), "{callsign}");
+            assert!(suffix.bytes().all(|b| LETTERS.contains(&b)), "{callsign}");
+        }
+    }
+
+    #[test]
+    fn test_read_callsigns() {
+        assert_eq!(
+            read_callsigns("# calls heard\nk1abc 599 MA\n\n  DL/W5XYZ\nja1zz\n"),
+            vec!["K1ABC", "DL/W5XYZ", "JA1ZZ"]
+        );
+    }
+}
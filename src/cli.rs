@@ -1,4 +1,164 @@
-use clap::{value_parser, Arg, Command};
+use clap::{value_parser, Arg, ArgGroup, Command};
+
+/// A number that is neither NaN nor infinite, which `value_parser!(f32)`
+/// would take.
+fn finite(v: &str) -> Result<f32, String> {
+    v.parse::<f32>()
+        .ok()
+        .filter(|val| val.is_finite())
+        .ok_or_else(|| String::from("Must be a finite number"))
+}
+
+/// The separators of morse code text, for encode and decode.
+fn separator_arg() -> Arg {
+    Arg::new("separator")
+        .long("separator")
+        .num_args(2)
+        .value_names(["LETTER", "WORD"])
+        .default_values([" ", " / "])
+        .help("What is written between the characters, and between the words, of morse code")
+}
+
+/// The signal threshold, for listen and selftest.
+fn threshold_arg() -> Arg {
+    Arg::new("threshold")
+        .short('t')
+        .long("threshold")
+        .value_parser(|v: &str| {
+            if v == "auto" {
+                return Ok(None);
+            }
+            v.parse::<f32>()
+                .map_err(|_| {
+                    String::from("Threshold must be a valid floating-point number or 'auto'")
+                })
+                .and_then(|val| {
+                    if (0.0..=1.0).contains(&val) {
+                        Ok(Some(val))
+                    } else {
+                        Err(String::from("Threshold must be between 0.0 and 1.0"))
+                    }
+                })
+        })
+        .help("Minimal signal value threshold [0.0..1.0] ('auto' to follow the noise floor)")
+}
+
+/// The tone detection method, for listen and selftest.
+fn detector_arg() -> Arg {
+    Arg::new("detector")
+        .long("detector")
+        .value_parser(["peak", "goertzel"])
+        .default_value("peak")
+        .help(
+            "Tone detection method: filtered signal level (peak), or the fraction of energy at the tone frequency (goertzel)",
+        )
+}
+
+/// The Farnsworth speed, for send, render, practice and listen.
+fn farnsworth_arg() -> Arg {
+    Arg::new("farnsworth")
+        .long("farnsworth")
+        .value_name("WPM")
+        .value_parser(value_parser!(u32).range(1..))
+        .help("Spread the characters out to this slower overall speed (Farnsworth timing)")
+}
+
+/// Longer gaps between words, for send, render and practice.
+fn word_space_arg() -> Arg {
+    Arg::new("extra-word-space")
+        .long("extra-word-space")
+        .value_name("FACTOR")
+        .default_value("1.0")
+        .value_parser(|v: &str| {
+            v.parse::<f32>()
+                .ok()
+                .filter(|val| *val >= 1.0)
+                .ok_or_else(|| String::from("Extra word space must be a factor of at least 1.0"))
+        })
+        .help("Make the gaps between words this many times longer, to make them stand out")
+}
+
+/// The noise, static crashes and fading that practice audio is sent
+/// through, for practice, send and render.
+fn band_args() -> Vec<Arg> {
+    vec![
+        Arg::new("noise-snr")
+            .long("noise-snr")
+            .value_name("DB")
+            .allow_negative_numbers(true)
+            .value_parser(finite)
+            .help("Mix in white noise, at this signal to noise ratio in dB over the whole band"),
+        Arg::new("qrn")
+            .long("qrn")
+            .value_name("RATE")
+            .value_parser(|v: &str| {
+                v.parse::<f32>()
+                    .ok()
+                    .filter(|val| *val > 0.0 && val.is_finite())
+                    .ok_or_else(|| String::from("QRN must be a positive number of crashes a second"))
+            })
+            .help("Add about this many static crashes a second"),
+        Arg::new("qsb")
+            .long("qsb")
+            .value_name("PERIOD:DEPTH")
+            .value_parser(|v: &str| v.parse::<crate::band::Qsb>())
+            .help("Fade the signal slowly, by DEPTH [0.0..1.0 or a percentage] every PERIOD seconds, e.g. 10:0.5"),
+        Arg::new("seed")
+            .long("seed")
+            .value_name("N")
+            .value_parser(value_parser!(u64))
+            .help("Seed the noise of --noise-snr, --qrn and --qsb, and in practice everything else drawn at random (the groups, words, callsigns, QSOs, tones and speeds), to repeat them exactly"),
+    ]
+}
+
+/// The serial port of a Winkeyer, for send and cwdaemon.
+fn winkeyer_arg() -> Arg {
+    Arg::new("winkeyer").long("winkeyer").value_name("PORT")
+}
+
+/// The text and sound of morse code, for send and render.
+/// The help of the exports, listing their columns.
+const EXPORT_HELP: &str = "Export each character (or word, or exchange) sent, as csv:<path> or json:<path>. The CSV has a header and these columns, and the JSON is an array of objects with the same keys: session_id, timestamp (RFC 3339, when the sound ended), sent, typed (empty or null when nothing was), correct (true or false), reaction_ms (from the end of the sound), wpm, attempt, total_ms (over all of the attempts), tone_hz";
+
+fn sending_args() -> Vec<Arg> {
+    vec![
+        Arg::new("input")
+            .value_name("TEXT")
+            .num_args(1..)
+            .conflicts_with("file")
+            .help("The text to send (read from stdin if not given)"),
+        Arg::new("file")
+            .short('f')
+            .long("file")
+            .value_name("TEXT_FILE")
+            .help("Read the text to send from a file"),
+        farnsworth_arg(),
+        word_space_arg(),
+        Arg::new("volume")
+            .long("volume")
+            .default_value("0.5")
+            .value_parser(|v: &str| {
+                v.parse::<f32>()
+                    .ok()
+                    .filter(|val| (0.0..=1.0).contains(val))
+                    .ok_or_else(|| String::from("Volume must be between 0.0 and 1.0"))
+            })
+            .help("The volume of the tone [0.0..1.0]"),
+        Arg::new("rise-time")
+            .long("rise-time")
+            .value_name("MS")
+            .default_value("5")
+            .value_parser(|v: &str| {
+                v.parse::<f32>()
+                    .ok()
+                    .filter(|val| *val >= 0.0)
+                    .ok_or_else(|| {
+                        String::from("Rise time must be a positive number of milliseconds")
+                    })
+            })
+            .help("How long each tone fades in and out, to avoid key clicks"),
+    ]
+}
 
 pub fn app() -> Command {
     Command::new(env!("CARGO_PKG_NAME"))
@@ -20,20 +180,45 @@ pub fn app() -> Command {
                 .global(true)
                 .num_args(1)
                 .value_name("WPM")
-                .value_parser(value_parser!(u32))
+                .value_parser(|v: &str| match v {
+                    "auto" => Ok(None),
+                    v => v
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|&wpm| wpm > 0)
+                        .map(Some)
+                        .ok_or_else(|| String::from("WPM must be a positive number or 'auto'")),
+                })
                 .help(
-                    "Sets the speed in words per minute [default: 20]",
+                    "Sets the speed in words per minute [default: 20] ('auto' to follow the sender with listen)",
                 ),
         )
         .arg(
             Arg::new("tone")
                 .long("tone")
+                .alias("tone-freq")
                 .global(true)
                 .num_args(1)
                 .value_name("TONE_FREQ")
-                .value_parser(value_parser!(f32))
+                .value_parser(|v: &str| match v {
+                    "auto" => Ok(None),
+                    v => v
+                        .parse::<f32>()
+                        .map(Some)
+                        .map_err(|_| String::from("Tone must be a frequency in Hz or 'auto'")),
+                })
                 .default_value("440.0")
-                .help("Sets the tone frequency in Hz"),
+                .help("Sets the tone frequency in Hz ('auto' to search for it with listen)"),
+        )
+        .arg(
+            Arg::new("charset")
+                .long("charset")
+                .global(true)
+                .num_args(1)
+                .value_name("CHARSET")
+                .value_parser(|v: &str| v.parse::<crate::morse::Charset>())
+                .default_value("latin")
+                .help("The alphabet to read morse code in: latin, cyrillic or wabun (Japanese kana)"),
         )
         .arg(
             Arg::new("text")
@@ -53,6 +238,32 @@ pub fn app() -> Command {
                     "Output sound in addition to the --text option",
                 ),
         )
+        .arg(
+            Arg::new("accessible")
+                .long("accessible")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help(
+                    "Print plain lines of text for a screen reader, without moving the cursor or using colors",
+                ),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .global(true)
+                .num_args(1)
+                .value_name("NAME")
+                .value_parser(|v: &str| crate::profile::validate(v).map(|_| v.to_string()))
+                .help("The profile to record the history in and to take the default settings from [default: $CODE_SMORE_PROFILE, the config file, or 'default']"),
+        )
+        .arg(
+            Arg::new("preset")
+                .long("preset")
+                .global(true)
+                .num_args(1)
+                .value_name("NAME")
+                .help("The preset of the config file to take the default settings from, e.g. shack or headphones"),
+        )
         .arg(
             Arg::new("log")
                 .long("log")
@@ -82,8 +293,7 @@ pub fn app() -> Command {
                     Arg::new("characters")
                         .short('c')
                         .long("characters")
-                        .default_value("ABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890")
-                        .help("Character set to shuffle/randomize for the quiz"),
+                        .help("Character set to shuffle/randomize for the quiz [default: the letters and numbers of --charset]"),
                 )
                 .arg(
                     Arg::new("baseline-calibration")
@@ -116,109 +326,1839 @@ pub fn app() -> Command {
     shuffled)",
                         )
                         .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("outlier-iqr")
+                        .long("outlier-iqr")
+                        .value_name("K")
+                        .default_value("3")
+                        .value_parser(value_parser!(f64))
+                        .help("Leave the reaction times more than K interquartile ranges above the third quartile out of the averages and the FECR score, as outliers"),
+                )
+                .arg(
+                    Arg::new("histogram")
+                        .long("histogram")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Draw a histogram of the reaction times"),
                 ),
         )
-        .subcommand(Command::new("test-sound").about(
-            "Test that sound is working",
-        ))
         .subcommand(
-            Command::new("read")
-                .about(
-                    "Read text from stdin and output it as morse code",
+            Command::new("icr")
+                .about("Start an Instant Character Recognition drill: characters one at a time, each to be typed within a strict response window")
+                .arg(
+                    Arg::new("characters")
+                        .short('c')
+                        .long("characters")
+                        .help("The characters to drill [default: the letters and numbers of --charset]"),
                 )
                 .arg(
-                    Arg::new("morse")
-                        .long("morse")
+                    Arg::new("trials")
+                        .long("trials")
+                        .value_name("N")
+                        .default_value("50")
+                        .value_parser(value_parser!(u32).range(1..)),
+                )
+                .arg(
+                    Arg::new("window-ms")
+                        .long("window-ms")
+                        .value_name("MS")
+                        .default_value("800")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .help("How long after the end of a character its answer may be typed: a later one is a miss, even if it is right"),
+                )
+                .arg(
+                    Arg::new("pressure")
+                        .long("pressure")
                         .action(clap::ArgAction::SetTrue)
-                        .help(
-                            "Input text is already morse encoded",
-                        ),
+                        .help("Shrink the window as the answers inside it get more accurate, and widen it again when they fall well behind"),
+                )
+                .arg(
+                    Arg::new("min-window-ms")
+                        .long("min-window-ms")
+                        .value_name("MS")
+                        .default_value("250")
+                        .value_parser(value_parser!(u64))
+                        .requires("pressure")
+                        .help("The narrowest the --pressure window shrinks to"),
+                )
+                .arg(
+                    Arg::new("pressure-step-ms")
+                        .long("pressure-step-ms")
+                        .value_name("MS")
+                        .default_value("50")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .requires("pressure")
+                        .help("How much the --pressure window shrinks, or widens, at a time"),
+                )
+                .arg(
+                    Arg::new("pressure-target")
+                        .long("pressure-target")
+                        .value_name("PERCENT")
+                        .default_value("90")
+                        .value_parser(|v: &str| {
+                            v.parse::<f64>()
+                                .ok()
+                                .filter(|val| (0.0..=100.0).contains(val))
+                                .ok_or_else(|| String::from("The target must be between 0 and 100"))
+                        })
+                        .requires("pressure")
+                        .help("The percentage of the last 10 answers right inside the window that shrinks it"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_name("N")
+                        .value_parser(value_parser!(u64))
+                        .help("Seed the characters drawn at random, to repeat the same drill exactly"),
                 ),
         )
         .subcommand(
-            Command::new("listen")
-                .about(
-                    "listen to morse code from a file or audio device and output it",
+            Command::new("practice")
+                .about("Practice copying random groups of characters, in Koch lessons or from a pool of your own, callsigns, words, contest exchanges, or whole contacts")
+                .arg(
+                    Arg::new("koch")
+                        .long("koch")
+                        .value_name("LESSON")
+                        .value_parser(value_parser!(u32).range(1..=40))
+                        .help("The Koch lesson [1..40], practicing its first N+1 characters (K M U R E S ...)"),
                 )
                 .arg(
-                    Arg::new("morse")
-                        .long("morse")
+                    Arg::new("groups")
+                        .long("groups")
                         .action(clap::ArgAction::SetTrue)
-                        .help(
-                            "Output text in morse code",
-                        ),
+                        .help("Practice random code groups of the --characters"),
+                )
+                .arg(
+                    Arg::new("callsigns")
+                        .long("callsigns")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Practice copying callsigns, made up or from --from-file"),
+                )
+                .arg(
+                    Arg::new("words")
+                        .long("words")
+                        .value_name("WORDS_FILE")
+                        .num_args(0..=1)
+                        .help("Practice copying whole words, from a file of them separated by spaces or lines [default: the most common English words and ham abbreviations]"),
+                )
+                .arg(
+                    Arg::new("abbreviations")
+                        .long("abbreviations")
+                        .value_name("TOML_FILE")
+                        .num_args(0..=1)
+                        .help("Practice the Q-codes and ham abbreviations as words, showing their meanings, with those of a TOML file of them (like QRL = \"Is this frequency in use?\") added to the built-in ones"),
+                )
+                .arg(
+                    Arg::new("direction")
+                        .long("direction")
+                        .value_name("DIRECTION")
+                        .default_value("hear")
+                        .value_parser(|v: &str| v.parse::<crate::abbreviations::Direction>())
+                        .requires("abbreviations")
+                        .help("hear each of the --abbreviations and type it back, or read its meaning and type (or --send) the abbreviation"),
+                )
+                .arg(
+                    Arg::new("head-copy")
+                        .long("head-copy")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("words")
+                        .help("Send the --words in phrases, to copy each one from memory once it has been played"),
                 )
                 .arg(
-                    Arg::new("threshold")
-                        .short('t')
-                        .long("threshold")
+                    Arg::new("phrase-words")
+                        .long("phrase-words")
+                        .value_name("N or MIN-MAX")
+                        .default_value("1")
+                        .value_parser(|v: &str| {
+                            let (min, max) = v.split_once('-').unwrap_or((v, v));
+                            match (min.trim().parse::<usize>(), max.trim().parse::<usize>()) {
+                                (Ok(min), Ok(max)) if 0 < min && min <= max => Ok((min, max)),
+                                _ => Err(String::from(
+                                    "Phrase length must be a number of words, or a range like 1-4",
+                                )),
+                            }
+                        })
+                        .requires("head-copy")
+                        .help("How many words a --head-copy phrase has, or a range to pick from at random"),
+                )
+                .arg(
+                    Arg::new("send")
+                        .long("send")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("word-list")
+                        .conflicts_with("head-copy")
+                        .help("Practice sending the --words (or --abbreviations) instead, keying them with a key of the keyboard as a straight key, to be decoded and graded"),
+                )
+                .arg(
+                    Arg::new("answer-mode")
+                        .long("answer-mode")
+                        .value_name("MODE")
+                        .value_parser(|v: &str| v.parse::<crate::answer::AnswerMode>())
+                        .conflicts_with_all(["send", "qso", "copy-behind"])
+                        .help("How to answer each group: type it (type), or key it with the --send-key or --iambic paddles, to be decoded (key), the report showing how evenly it was keyed [default: type]"),
+                )
+                .group(ArgGroup::new("keying").args(["send", "answer-mode"]))
+                .arg(
+                    Arg::new("send-key")
+                        .long("send-key")
+                        .value_name("KEY")
+                        .default_value("space")
+                        .value_parser(crate::input::parse_straight_key)
+                        .requires("keying")
+                        .conflicts_with("iambic")
+                        .help("The key to --send, or key the answers, with: space, a character, an arrow (left, right, up or down), or tab, insert, home, end, or f1 to f12"),
+                )
+                .arg(
+                    Arg::new("iambic")
+                        .long("iambic")
+                        .value_name("MODE")
+                        .num_args(0..=1)
+                        .default_missing_value("b")
+                        .value_parser(|v: &str| v.parse::<crate::iambic::Mode>())
+                        .requires("keying")
+                        .help("Key with two keys as the paddles of an iambic keyer, sending at the --wpm speed, in mode a or b [default: b]"),
+                )
+                .arg(
+                    Arg::new("paddles")
+                        .long("paddles")
+                        .value_name("DIT,DAH")
+                        .default_value("z,x")
+                        .value_parser(|v: &str| {
+                            let (dit, dah) = v
+                                .split_once(',')
+                                .ok_or("Paddles must be two keys, like z,x or left,right")?;
+                            let dit = crate::input::parse_straight_key(dit.trim())?;
+                            let dah = crate::input::parse_straight_key(dah.trim())?;
+                            match dit != dah {
+                                true => Ok((dit, dah)),
+                                false => Err(String::from("The paddles must be two different keys")),
+                            }
+                        })
+                        .requires("iambic")
+                        .help("The keys of the --iambic dit and dah paddles, e.g. left,right"),
+                )
+                .arg(
+                    Arg::new("paddle-reverse")
+                        .long("paddle-reverse")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("iambic")
+                        .help("Swap the --paddles, sending dahs with the first key and dits with the second"),
+                )
+                .arg(
+                    Arg::new("weight")
+                        .long("weight")
+                        .value_name("PERCENT")
+                        .default_value("50")
+                        .value_parser(value_parser!(u32).range(25..=75))
+                        .requires("iambic")
+                        .help("The weight of the --iambic keyer [25..75]: more makes its marks longer, and the spaces between them shorter"),
+                )
+                .arg(
+                    Arg::new("sidetone-freq")
+                        .long("sidetone-freq")
+                        .value_name("TONE_FREQ")
+                        .value_parser(value_parser!(f32))
+                        .requires("keying")
+                        .help("The frequency in Hz of the sidetone heard while keying [default: the --tone]"),
+                )
+                .arg(
+                    Arg::new("sidetone-volume")
+                        .long("sidetone-volume")
+                        .default_value("0.5")
                         .value_parser(|v: &str| {
                             v.parse::<f32>()
-                                .map_err(|_| String::from("Threshold must be a valid floating-point number"))
-                                .and_then(|val| {
-                                    if (0.0..=1.0).contains(&val) {
-                                        Ok(val)
-                                    } else {
-                                        Err(String::from("Threshold must be between 0.0 and 1.0"))
-                                    }
-                                })
+                                .ok()
+                                .filter(|val| (0.0..=1.0).contains(val))
+                                .ok_or_else(|| String::from("Volume must be between 0.0 and 1.0"))
                         })
-                        .help(
-                            "Minimal signal value threshold [0.0..1.0]",
-                        ),
+                        .requires("keying")
+                        .help("The volume of the sidetone [0.0..1.0]"),
                 )
                 .arg(
-                    Arg::new("bandwidth")
-                        .short('W')
-                        .long("bandwidth")
+                    Arg::new("sidetone-rise-time")
+                        .long("sidetone-rise-time")
+                        .value_name("MS")
+                        .default_value("5")
+                        .value_parser(|v: &str| {
+                            v.parse::<f32>().ok().filter(|val| *val >= 0.0).ok_or_else(|| {
+                                String::from("Rise time must be a positive number of milliseconds")
+                            })
+                        })
+                        .requires("keying")
+                        .help("How long the sidetone fades in and out, to avoid key clicks"),
+                )
+                .arg(
+                    Arg::new("sidetone-target")
+                        .long("sidetone-target")
+                        .value_name("NODE")
+                        .requires("keying")
+                        .help("Play the sidetone to a specific PipeWire node (name or id)"),
+                )
+                .arg(
+                    Arg::new("exchange")
+                        .long("exchange")
+                        .value_name("TEMPLATE")
+                        .num_args(0..=1)
+                        .default_missing_value(crate::contest::DEFAULT_EXCHANGE)
+                        .help("Practice copying contest exchanges, with the fields {NR} (a serial number), {RST}, {ZONE}, {STATE}, {NAME} and {CALL}, e.g. 'NR {NR} {STATE}' [default: '{RST} {NR}']"),
+                )
+                .arg(
+                    Arg::new("cut-numbers")
+                        .long("cut-numbers")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("exchange")
+                        .help("Send the numbers of the exchanges with T for 0 and N for 9 (5NN for 599), accepting either when copied"),
+                )
+                .arg(
+                    Arg::new("qso")
+                        .long("qso")
+                        .value_name("SCRIPT")
+                        .num_args(0..=1)
+                        .default_missing_value("ragchew")
+                        .help("Practice copying a whole contact, from the script ragchew, contest or pota, or a script file of your own [default: ragchew]"),
+                )
+                .arg(
+                    Arg::new("copy-behind")
+                        .long("copy-behind")
+                        .value_name("N")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .conflicts_with_all(["callsigns", "words", "exchange", "qso", "review"])
+                        .help("Send the characters of --koch or --groups as one stream, to copy each one N characters behind the sound"),
+                )
+                .arg(
+                    Arg::new("stream-length")
+                        .long("stream-length")
+                        .value_name("CHARACTERS")
+                        .default_value("50")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .requires("copy-behind")
+                        .help("How many characters the --copy-behind stream has"),
+                )
+                .arg(
+                    Arg::new("review")
+                        .long("review")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Review the characters and words that are due today, or else learn the new characters of the current Koch lesson"),
+                )
+                .arg(
+                    Arg::new("mycall")
+                        .long("mycall")
+                        .value_name("CALLSIGN")
+                        .requires("qso")
+                        .help("Your callsign in the --qso contacts [default: made up]"),
+                )
+                .arg(
+                    Arg::new("script")
+                        .long("script")
+                        .value_name("FILE")
+                        .conflicts_with_all(["copy-behind", "send", "head-copy", "speed-ramp"])
+                        .help("Practice a drill script: the sections of a TOML file sent one after the other, each with its own text or generated items, speed, tone and band conditions (see scripts/ for examples)"),
+                )
+                .group(
+                    ArgGroup::new("mode")
+                        .args(["koch", "groups", "callsigns", "words", "abbreviations", "exchange", "qso", "review", "script"])
+                        .required(true),
+                )
+                .group(ArgGroup::new("word-list").args(["words", "abbreviations"]))
+                .arg(
+                    Arg::new("from-file")
+                        .long("from-file")
+                        .value_name("CALLS_FILE")
+                        .requires("callsigns")
+                        .help("Pick the callsigns from a file, the first word of each line"),
+                )
+                .arg(
+                    Arg::new("min-len")
+                        .long("min-len")
+                        .value_name("N")
+                        .default_value("1")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .requires("words")
+                        .help("Only practice words of at least this many characters"),
+                )
+                .arg(
+                    Arg::new("max-len")
+                        .long("max-len")
+                        .value_name("N")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .requires("words")
+                        .help("Only practice words of at most this many characters"),
+                )
+                .arg(
+                    Arg::new("characters")
+                        .short('c')
+                        .long("characters")
+                        .alias("chars")
+                        .value_name("POOL")
+                        .conflicts_with_all(["callsigns", "words", "exchange", "qso", "review"])
+                        .help("The characters to practice: characters, ranges (A-Z), or the sets letters, digits, punctuation and prosigns, separated by commas or spaces [default: those of the lesson, or the letters and numbers of --charset]"),
+                )
+                .arg(
+                    Arg::new("exclude")
+                        .long("exclude")
+                        .value_name("POOL")
+                        .default_value("")
+                        .hide_default_value(true)
+                        .conflicts_with_all(["callsigns", "words", "exchange", "qso", "review"])
+                        .help("Characters to leave out of the practice, written like --characters"),
+                )
+                .arg(
+                    Arg::new("focus-weak")
+                        .long("focus-weak")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["callsigns", "words", "exchange", "qso", "review"])
+                        .help("Send the characters with a low accuracy, or a slow reaction time, in the recorded sessions more often"),
+                )
+                .arg(
+                    Arg::new("focus-mix")
+                        .long("focus-mix")
+                        .value_name("RATIO")
+                        .default_value("0.5")
+                        .requires("focus-weak")
+                        .value_parser(|v: &str| {
+                            v.parse::<f64>()
+                                .ok()
+                                .filter(|val| (0.0..=1.0).contains(val))
+                                .ok_or_else(|| String::from("Focus mix must be between 0 and 1"))
+                        })
+                        .help("The share of the characters picked by weakness, the rest being picked evenly so that the strong characters still appear"),
+                )
+                .arg(
+                    Arg::new("group-size")
+                        .long("group-size")
+                        .alias("group-length")
+                        .value_name("N or MIN-MAX")
+                        .default_value("5")
+                        .value_parser(|v: &str| {
+                            let (min, max) = v.split_once('-').unwrap_or((v, v));
+                            match (min.trim().parse::<usize>(), max.trim().parse::<usize>()) {
+                                (Ok(min), Ok(max)) if 0 < min && min <= max => Ok((min, max)),
+                                _ => Err(String::from(
+                                    "Group size must be a number, or a range like 3-7",
+                                )),
+                            }
+                        })
+                        .help("The number of characters in each group, or a range to pick it from"),
+                )
+                .arg(
+                    Arg::new("count")
+                        .long("count")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .help("Stop after this many groups"),
+                )
+                .arg(
+                    Arg::new("duration")
+                        .long("duration")
+                        .value_name("MINUTES")
                         .value_parser(|v: &str| {
                             v.parse::<f32>()
-                                .map_err(|_| String::from("Bandwidth must be a valid floating-point number"))
-                                .and_then(|val| {
-                                    if (0.0..=1000.0).contains(&val) {
-                                        Ok(val)
-                                    } else {
-                                        Err(String::from("Threshold must be between 0.0Hz and 1000.0Hz"))
-                                    }
-                                })
+                                .ok()
+                                .filter(|val| *val > 0.0)
+                                .ok_or_else(|| String::from("Duration must be a positive number of minutes"))
                         })
-                        .help(
-                            "Minimal signal value threshold [0.0..1.0]",
-                        ),
+                        .help("Stop after this long [default: 5, unless --count is given]"),
                 )
+                .arg(farnsworth_arg().default_value("10"))
+                .arg(word_space_arg())
                 .arg(
-                    Arg::new("file")
-                        .short('f')
-                        .long("file")
-                        .help("Read morse code from an audio file")
-                        .conflicts_with("device"), // Ensures `--file` and `--device` are mutually exclusive
+                    Arg::new("speed-ramp")
+                        .long("speed-ramp")
+                        .value_name("START:END")
+                        .value_parser(|v: &str| {
+                            match v.split_once(':').map(|(start, end)| {
+                                (start.trim().parse::<u32>(), end.trim().parse::<u32>())
+                            }) {
+                                Some((Ok(start), Ok(end))) if 0 < start && start < end => {
+                                    Ok((start, end))
+                                }
+                                _ => Err(String::from(
+                                    "Speed ramp must be two speeds in WPM, climbing, like 15:25",
+                                )),
+                            }
+                        })
+                        .help("Climb from the START speed to the END speed in WPM over the drill, instead of sending at --wpm"),
                 )
                 .arg(
-                    Arg::new("device")
-                        .short('d')
-                        .long("device")
-                        .help("Read morse code from an audio device")
-                        .conflicts_with("file"), // Ensures `--device` and `--file` are mutually exclusive
-                ),
-        )
-        .subcommand(
-            Command::new("completions")
-                .about(
-                    "Generates shell completions
-    script (tab completion)",
+                    Arg::new("ramp-every")
+                        .long("ramp-every")
+                        .value_name("GROUPS")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .requires("speed-ramp")
+                        .help("Climb in steps of --ramp-step every this many groups, rather than smoothly"),
                 )
-                .hide(true)
                 .arg(
-                    Arg::new("shell")
-                        .help("The shell to generate completions for")
-                        .required(false)
-                        .value_parser(["bash", "zsh", "fish"]),
-                ),
-        )
-        .subcommand(
-            Command::new("credits")
-                .about(
-                    "Prints license information for all dependencies",
+                    Arg::new("ramp-step")
+                        .long("ramp-step")
+                        .value_name("WPM")
+                        .default_value("1")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .requires("ramp-every")
+                        .help("How much faster each step of --ramp-every is"),
                 )
-        )
+                .arg(
+                    Arg::new("ramp-floor")
+                        .long("ramp-floor")
+                        .value_name("PERCENT")
+                        .requires("speed-ramp")
+                        .value_parser(|v: &str| {
+                            v.parse::<f32>()
+                                .ok()
+                                .filter(|val| (0.0..=100.0).contains(val))
+                                .ok_or_else(|| String::from("Ramp floor must be between 0 and 100"))
+                        })
+                        .help("Stop climbing while the accuracy at the current speed is below this"),
+                )
+                .arg(
+                    Arg::new("random-speed")
+                        .long("random-speed")
+                        .value_name("MIN:MAX")
+                        .value_parser(|v: &str| {
+                            match v.split_once(':').map(|(min, max)| {
+                                (min.trim().parse::<u32>(), max.trim().parse::<u32>())
+                            }) {
+                                Some((Ok(min), Ok(max))) if 0 < min && min <= max => Ok((min, max)),
+                                _ => Err(String::from(
+                                    "Random speed must be two speeds in WPM, like 18:24",
+                                )),
+                            }
+                        })
+                        .conflicts_with_all(["speed-ramp", "qso", "copy-behind", "send"])
+                        .help("Send each group at a random character speed between MIN and MAX in WPM, instead of at --wpm"),
+                )
+                .arg(
+                    Arg::new("random-pitch")
+                        .long("random-pitch")
+                        .value_name("LOW:HIGH")
+                        .value_parser(|v: &str| {
+                            match v.split_once(':').map(|(low, high)| {
+                                (low.trim().parse::<f32>(), high.trim().parse::<f32>())
+                            }) {
+                                Some((Ok(low), Ok(high))) if 0.0 < low && low <= high => {
+                                    Ok((low, high))
+                                }
+                                _ => Err(String::from(
+                                    "Random pitch must be two tones in Hz, like 500:800",
+                                )),
+                            }
+                        })
+                        .conflicts_with_all(["qso", "copy-behind", "send"])
+                        .help("Send each group at a random tone between LOW and HIGH in Hz, instead of at --tone"),
+                )
+                .arg(
+                    Arg::new("rufz")
+                        .long("rufz")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("callsigns")
+                        .conflicts_with_all(["speed-ramp", "random-speed", "attempts", "copy-behind", "send", "script"])
+                        .help("Climb a RufzXP style speed ladder: each callsign copied exactly sends the next faster, each error slower, and the calls score by their speed, over --count calls [default: 50]"),
+                )
+                .arg(
+                    Arg::new("rufz-up")
+                        .long("rufz-up")
+                        .value_name("STEP")
+                        .default_value("2")
+                        .value_parser(|v: &str| v.parse::<crate::rufz::Step>())
+                        .requires("rufz")
+                        .help("How much faster the --rufz ladder gets after a call copied exactly, in WPM or as a percentage, like 5%"),
+                )
+                .arg(
+                    Arg::new("rufz-down")
+                        .long("rufz-down")
+                        .value_name("STEP")
+                        .default_value("2")
+                        .value_parser(|v: &str| v.parse::<crate::rufz::Step>())
+                        .requires("rufz")
+                        .help("How much slower the --rufz ladder gets after an error, in WPM or as a percentage"),
+                )
+                .arg(
+                    Arg::new("rufz-scoring")
+                        .long("rufz-scoring")
+                        .value_name("SCORING")
+                        .default_value("partial")
+                        .value_parser(|v: &str| v.parse::<crate::rufz::Scoring>())
+                        .requires("rufz")
+                        .help("How a --rufz call scores: the speed for each character copied right (partial) or for each character of the calls copied exactly only (exact)"),
+                )
+                .arg(
+                    Arg::new("interferer")
+                        .long("interferer")
+                        .value_name("CONTENT")
+                        .num_args(0..=1)
+                        .default_missing_value("calls")
+                        .value_parser(|v: &str| v.parse::<crate::interference::Content>())
+                        .conflicts_with_all(["qso", "copy-behind", "send"])
+                        .help("Have another station send at the same time as each group, to practice copying one signal only: random callsigns (calls, the default) or common words (text). It is not scored"),
+                )
+                .arg(
+                    Arg::new("interferer-offset-hz")
+                        .long("interferer-offset-hz")
+                        .value_name("HZ")
+                        .default_value("150")
+                        .allow_negative_numbers(true)
+                        .value_parser(finite)
+                        .requires("interferer")
+                        .help("The pitch of the --interferer, in Hz from that of the group"),
+                )
+                .arg(
+                    Arg::new("interferer-level-db")
+                        .long("interferer-level-db")
+                        .value_name("DB")
+                        .default_value("-6")
+                        .allow_negative_numbers(true)
+                        .value_parser(finite)
+                        .requires("interferer")
+                        .help("The level of the --interferer, in dB from that of the group"),
+                )
+                .arg(
+                    Arg::new("interferer-start")
+                        .long("interferer-start")
+                        .value_name("WHEN")
+                        .default_value("during")
+                        .value_parser(|v: &str| v.parse::<crate::interference::Start>())
+                        .requires("interferer")
+                        .help("Whether the --interferer is already sending when the group starts (before) or breaks in while it is sent (during)"),
+                )
+                .arg(
+                    Arg::new("outlier-iqr")
+                        .long("outlier-iqr")
+                        .value_name("K")
+                        .default_value("3")
+                        .value_parser(value_parser!(f64))
+                        .help("Leave the reaction times more than K interquartile ranges above the third quartile out of the averages and the FECR score, as outliers"),
+                )
+                .arg(
+                    Arg::new("histogram")
+                        .long("histogram")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["words", "exchange", "qso"])
+                        .help("Draw a histogram of the reaction times"),
+                )
+                .arg(
+                    Arg::new("copy-on-exit")
+                        .long("copy-on-exit")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("qso")
+                        .help("Copy the tables of the summary to the clipboard at the end (or to a temporary file, without a clipboard)"),
+                )
+                .arg(
+                    Arg::new("feedback")
+                        .long("feedback")
+                        .value_name("KIND")
+                        .default_value("none")
+                        .value_parser(|v: &str| v.parse::<crate::feedback::Feedback>())
+                        .conflicts_with("qso")
+                        .help("Tell right after each copy whether it was right: none, sound (a high chirp or a low buzz), visual (the copy colored green or red) or both"),
+                )
+                .arg(
+                    Arg::new("export")
+                        .long("export")
+                        .value_name("FORMAT:PATH")
+                        .action(clap::ArgAction::Append)
+                        .value_parser(|v: &str| v.parse::<crate::export::Export>())
+                        .conflicts_with("qso")
+                        .help(EXPORT_HELP),
+                )
+                .arg(
+                    Arg::new("plain")
+                        .long("plain")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Print the drill as lines of text, without the panes of the accuracy and the latest groups, as when the output is not a terminal"),
+                )
+                .arg(
+                    Arg::new("confusion")
+                        .long("confusion")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["words", "exchange", "qso"])
+                        .help("Show which characters were copied as others, telling the typos of keys next to each other apart"),
+                )
+                .arg(
+                    Arg::new("resend")
+                        .long("resend")
+                        .value_name("WPM")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .help("Send a missed group again at this slower speed, before moving on"),
+                )
+                .arg(
+                    Arg::new("reveal")
+                        .long("reveal")
+                        .value_name("HOW")
+                        .num_args(0..=1)
+                        .default_missing_value("once")
+                        .value_parser(|v: &str| v.parse::<crate::practice::Reveal>())
+                        .conflicts_with_all(["resend", "qso"])
+                        .help("After a missed group, show it and play it again, once (the default) or slow: twice, slower. It is not scored"),
+                )
+                .arg(
+                    Arg::new("reveal-delay-ms")
+                        .long("reveal-delay-ms")
+                        .value_name("MS")
+                        .default_value("1500")
+                        .value_parser(value_parser!(u64))
+                        .requires("reveal")
+                        .help("How long the answer of --reveal stays on the screen before the next group, at least"),
+                )
+                .arg(
+                    Arg::new("answer-timeout-ms")
+                        .long("answer-timeout-ms")
+                        .value_name("MS")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .conflicts_with("qso")
+                        .help("Count a group as missed when Enter isn't pressed this long after its last character was played, and move on"),
+                )
+                .arg(
+                    Arg::new("attempts")
+                        .long("attempts")
+                        .value_name("N")
+                        .default_value("1")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .conflicts_with("qso")
+                        .help("Play a group that was missed again, up to this many times in all, before it is scored as a miss"),
+                )
+                .arg(
+                    Arg::new("no-typeahead")
+                        .long("no-typeahead")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("qso")
+                        .help("Ignore the keys typed while a group is still playing, to wait for its end before copying it"),
+                )
+                .arg(
+                    Arg::new("pause-key")
+                        .long("pause-key")
+                        .value_name("KEY")
+                        .default_value("esc")
+                        .value_parser(crate::input::parse_key)
+                        .conflicts_with("qso")
+                        .help("The key that pauses the drill: esc, tab, insert, home, end, or f1 to f12. Press any key to resume it, or q to stop it; Ctrl-C stops it at any time"),
+                )
+                .arg(
+                    Arg::new("review-missed")
+                        .long("review-missed")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("qso")
+                        .help("Send the groups missed again in review rounds at the end, the first copies only being scored"),
+                )
+                .arg(
+                    Arg::new("review-passes")
+                        .long("review-passes")
+                        .value_name("N")
+                        .default_value("2")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .requires("review-missed")
+                        .help("The most review rounds, each sending again the groups missed in the one before"),
+                )
+                .arg(
+                    Arg::new("review-speed-offset")
+                        .long("review-speed-offset")
+                        .value_name("WPM")
+                        .default_value("0")
+                        .value_parser(value_parser!(u32))
+                        .requires("review-missed")
+                        .help("Send the review rounds this much slower"),
+                )
+                .arg(
+                    Arg::new("pass")
+                        .long("pass")
+                        .value_name("PERCENT")
+                        .default_value("90")
+                        .value_parser(|v: &str| {
+                            v.parse::<f32>()
+                                .ok()
+                                .filter(|val| (0.0..=100.0).contains(val))
+                                .ok_or_else(|| String::from("Pass accuracy must be between 0 and 100"))
+                        })
+                        .help("The session accuracy needed to move on to the next lesson"),
+                )
+                .arg(
+                    Arg::new("history")
+                        .long("history")
+                        .value_name("FILE")
+                        .help("Where to record the Koch lessons [default: koch-history.txt in the user data directory]"),
+                )
+                .args(band_args()),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Show the trends of the recorded practice sessions")
+                .arg(
+                    Arg::new("last")
+                        .long("last")
+                        .value_name("N")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .help("How many of the latest sessions to show [default: 10, or all with --char]"),
+                )
+                .arg(
+                    Arg::new("char")
+                        .long("char")
+                        .value_name("CHARACTER")
+                        .help("Show the history of one character, e.g. K or <AR>"),
+                )
+                .arg(
+                    Arg::new("trend")
+                        .long("trend")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["char", "due"])
+                        .help("Draw sparklines of the accuracy and the reaction time of each character over the last sessions"),
+                )
+                .arg(
+                    Arg::new("sort")
+                        .long("sort")
+                        .value_name("ORDER")
+                        .default_value("worst")
+                        .value_parser(|v: &str| v.parse::<crate::stats::TrendOrder>())
+                        .requires("trend")
+                        .help("The order of the characters of --trend: worst, alphabetical or improved"),
+                )
+                .arg(
+                    Arg::new("ascii")
+                        .long("ascii")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("trend")
+                        .help("Draw the sparklines in plain ASCII [default: when the terminal is not UTF-8]"),
+                )
+                .arg(
+                    Arg::new("due")
+                        .long("due")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("char")
+                        .help("List when each character and word is due for --review"),
+                )
+                .subcommand(
+                    Command::new("confusion")
+                        .about("Show the characters most often copied as others in the recorded sessions")
+                        .arg(
+                            Arg::new("last")
+                                .long("last")
+                                .value_name("N")
+                                .value_parser(value_parser!(u32).range(1..))
+                                .help("Only count the latest sessions [default: all]"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Export the trials of the recorded sessions")
+                        .arg(
+                            Arg::new("to")
+                                .value_name("FORMAT:PATH")
+                                .required(true)
+                                .num_args(1..)
+                                .value_parser(|v: &str| v.parse::<crate::export::Export>())
+                                .help(EXPORT_HELP),
+                        )
+                        .arg(
+                            Arg::new("last")
+                                .long("last")
+                                .value_name("N")
+                                .value_parser(value_parser!(u32).range(1..))
+                                .help("Only export the latest sessions [default: all]"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Print where the config file is, or the settings in effect")
+                .arg(
+                    Arg::new("show")
+                        .long("show")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Print the settings in effect, as a config file noting where each came from"),
+                ),
+        )
+        .subcommand(
+            Command::new("profile")
+                .about("List, create or delete the profiles, each with its own history and settings")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List the profiles, marking the one in use"))
+                .subcommand(
+                    Command::new("create")
+                        .about("Create a new profile")
+                        .arg(Arg::new("name").value_name("NAME").required(true)),
+                )
+                .subcommand(
+                    Command::new("delete")
+                        .about("Delete a profile, with all of its history and settings")
+                        .arg(Arg::new("name").value_name("NAME").required(true))
+                        .arg(
+                            Arg::new("yes")
+                                .long("yes")
+                                .short('y')
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Delete it without asking first"),
+                        ),
+                ),
+        )
+        .subcommand(Command::new("test-sound").about(
+            "Test that sound is working",
+        ))
+        .subcommand(
+            Command::new("read")
+                .about(
+                    "Read text from stdin and output it as morse code",
+                )
+                .arg(
+                    Arg::new("morse")
+                        .long("morse")
+                        .action(clap::ArgAction::SetTrue)
+                        .help(
+                            "Input text is already morse encoded",
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("encode")
+                .about("Encode text as morse code text, e.g. CQ as -.-. --.-")
+                .arg(
+                    Arg::new("input")
+                        .value_name("TEXT")
+                        .num_args(1..)
+                        .help("The text to encode (read from stdin, a line at a time, if not given)"),
+                )
+                .arg(separator_arg()),
+        )
+        .subcommand(
+            Command::new("decode")
+                .about("Decode morse code text, e.g. -.-. --.- as CQ")
+                .arg(
+                    Arg::new("input")
+                        .value_name("CODE")
+                        .num_args(1..)
+                        .allow_hyphen_values(true)
+                        .help("The morse code to decode (read from stdin, a line at a time, if not given)"),
+                )
+                .arg(separator_arg()),
+        )
+        .subcommand(
+            Command::new("send")
+                .alias("play")
+                .about("Send text as morse code sound through PipeWire")
+                .args(sending_args())
+                .args(band_args())
+                .arg(
+                    Arg::new("target")
+                        .short('d')
+                        .long("target")
+                        .alias("device")
+                        .value_name("NODE")
+                        .help("Play to a specific PipeWire node (name or id)"),
+                )
+                .arg(
+                    Arg::new("key-serial")
+                        .long("key-serial")
+                        .value_name("PORT:LINE")
+                        .value_parser(|v: &str| v.parse::<crate::serial::KeySerial>())
+                        .help("Key a transmitter with a line of a serial port instead of playing sound (e.g. /dev/ttyUSB0:dtr or /dev/ttyUSB0:rts)"),
+                )
+                .arg(
+                    Arg::new("key-gpio")
+                        .long("key-gpio")
+                        .value_name("PIN")
+                        .value_parser(|v: &str| v.parse::<crate::gpio::GpioPin>())
+                        .help("Key a transmitter with a GPIO pin instead of playing sound (e.g. 17, or gpiochip4:17) (needs the 'gpio' feature)"),
+                )
+                .arg(
+                    Arg::new("ptt-gpio")
+                        .long("ptt-gpio")
+                        .value_name("PIN")
+                        .value_parser(|v: &str| v.parse::<crate::gpio::GpioPin>())
+                        .requires("key-gpio")
+                        .help("Raise a second GPIO pin as the PTT while sending"),
+                )
+                .arg(
+                    Arg::new("gpio-active-low")
+                        .long("gpio-active-low")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("key-gpio")
+                        .help("Drive the GPIO pins low to key, instead of high"),
+                )
+                .arg(
+                    Arg::new("mopp-send")
+                        .long("mopp-send")
+                        .value_name("HOST:PORT")
+                        .num_args(0..=1)
+                        .default_missing_value("255.255.255.255:7373")
+                        .help("Send the words over MOPP (the protocol of the Morserino-32) instead of playing sound, e.g. to a server of a practice net [default: 255.255.255.255:7373, the local network]"),
+                )
+                .arg(winkeyer_arg().help("Send the text with a Winkeyer (of K1EL) on a serial port instead of playing sound, showing each character as it is sent (e.g. /dev/ttyUSB0)"))
+                .group(ArgGroup::new("keying").args(["key-serial", "key-gpio", "mopp-send", "winkeyer"]))
+                .arg(
+                    Arg::new("ptt")
+                        .long("ptt")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("key-serial")
+                        .help("Raise the other line of the serial port as the PTT while sending"),
+                )
+                .arg(
+                    Arg::new("lead-in")
+                        .long("lead-in")
+                        .value_name("MS")
+                        .default_value("50")
+                        .value_parser(value_parser!(u64))
+                        .help("How long the PTT is raised before the first mark"),
+                )
+                .arg(
+                    Arg::new("tail")
+                        .long("tail")
+                        .value_name("MS")
+                        .default_value("20")
+                        .value_parser(value_parser!(u64))
+                        .help("How long the PTT stays raised after the last mark"),
+                )
+                .arg(
+                    Arg::new("key-test")
+                        .long("key-test")
+                        .value_name("DITS")
+                        .num_args(0..=1)
+                        .default_missing_value("100")
+                        .value_parser(value_parser!(usize))
+                        .requires("keying")
+                        .conflicts_with_all(["input", "file", "winkeyer"])
+                        .help("Key a string of dits instead of the text, to check the line with a scope [default: 100]"),
+                ),
+        )
+        .subcommand(
+            Command::new("render")
+                .about("Render text as morse code sound to a WAV file")
+                .args(sending_args())
+                .args(band_args())
+                .arg(
+                    Arg::new("out")
+                        .short('o')
+                        .long("out")
+                        .value_name("WAV_FILE")
+                        .required(true)
+                        .help("The WAV file to write"),
+                )
+                .arg(
+                    Arg::new("padding")
+                        .long("padding")
+                        .value_name("SECONDS")
+                        .default_value("0")
+                        .value_parser(|v: &str| {
+                            v.parse::<f32>()
+                                .ok()
+                                .filter(|val| *val >= 0.0)
+                                .ok_or_else(|| String::from("Padding must be a positive number of seconds"))
+                        })
+                        .help("Silence to add before and after the morse code"),
+                )
+                .arg(
+                    Arg::new("preamble")
+                        .long("preamble")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Start with VVV, to tune in to"),
+                ),
+        )
+        .subcommand(
+            Command::new("selftest")
+                .about("Play a message to an audio device and decode it again, to test the audio routing")
+                .arg(
+                    Arg::new("message")
+                        .long("message")
+                        .default_value("PARIS PARIS")
+                        .help("The message to send"),
+                )
+                .arg(threshold_arg())
+                .arg(detector_arg())
+                .arg(
+                    Arg::new("target")
+                        .short('d')
+                        .long("target")
+                        .alias("device")
+                        .value_name("NODE")
+                        .help("The PipeWire sink (name or id) to play to and capture from, instead of the default one"),
+                ),
+        )
+        .subcommand(
+            Command::new("bench-decode")
+                .about("Measure how well the decoder copies a corpus of recordings, or a synthetic one, with the error rates of its characters and words")
+                .arg(
+                    Arg::new("dir")
+                        .value_name("DIR")
+                        .help("The corpus: each text file with the WAV file of the same name, or rendered over the grid without one (written to, with --synthesize)"),
+                )
+                .arg(
+                    Arg::new("synthesize")
+                        .long("synthesize")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Make up random texts, and render them with noise over the grid"),
+                )
+                .group(
+                    ArgGroup::new("corpus")
+                        .args(["dir", "synthesize"])
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("count")
+                        .long("count")
+                        .value_name("N")
+                        .default_value("3")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .requires("synthesize")
+                        .help("How many random texts to render at each point of the grid"),
+                )
+                .arg(
+                    Arg::new("wpm-grid")
+                        .long("wpm-grid")
+                        .value_name("WPM,...")
+                        .value_delimiter(',')
+                        .default_value("12,20,30")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .help("The speeds the texts are rendered at"),
+                )
+                .arg(
+                    Arg::new("snr-grid")
+                        .long("snr-grid")
+                        .value_name("DB,...")
+                        .value_delimiter(',')
+                        .default_value("10,-10,-15,-20")
+                        .allow_negative_numbers(true)
+                        .value_parser(value_parser!(f32))
+                        .help("The signal to noise ratios the texts are rendered at, in dB over the whole band"),
+                )
+                .arg(
+                    Arg::new("impulses")
+                        .long("impulses")
+                        .value_name("PER_SECOND")
+                        .default_value("2")
+                        .value_parser(|v: &str| {
+                            v.parse::<f32>()
+                                .ok()
+                                .filter(|val| *val >= 0.0)
+                                .ok_or_else(|| String::from("Impulses must be a rate of at least 0"))
+                        })
+                        .help("About how many static crashes of impulse noise a second are added to the white noise"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_name("N")
+                        .default_value("1")
+                        .value_parser(value_parser!(u64))
+                        .help("Seed the random texts and noise, to render the same ones again"),
+                )
+                .arg(threshold_arg())
+                .arg(detector_arg())
+                .arg(
+                    Arg::new("bandwidth")
+                        .short('W')
+                        .long("bandwidth")
+                        .value_name("HZ")
+                        .default_value("200")
+                        .value_parser(value_parser!(f32))
+                        .help("The width of the bandpass filter around the tone"),
+                ),
+        )
+        .subcommand(
+            Command::new("listen")
+                .about(
+                    "listen to morse code from a file or audio device and output it",
+                )
+                .arg(
+                    Arg::new("morse")
+                        .long("morse")
+                        .action(clap::ArgAction::SetTrue)
+                        .help(
+                            "Output text in morse code",
+                        ),
+                )
+                .arg(
+                    Arg::new("show-symbols")
+                        .long("show-symbols")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("morse")
+                        .help("Show the dots and dashes heard for each character, e.g. C[-.-.]"),
+                )
+                .arg(
+                    Arg::new("no-prosigns")
+                        .long("no-prosigns")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Write prosigns as the characters they share a code with, e.g. + for <AR>"),
+                )
+                .arg(
+                    Arg::new("error-char")
+                        .long("error-char")
+                        .value_name("CHAR")
+                        .default_value("?")
+                        .value_parser(|v: &str| {
+                            let mut chars = v.chars();
+                            match (chars.next(), chars.next()) {
+                                (ch, None) => Ok(ch),
+                                _ => Err(String::from("Error char must be a single character, or '' to drop them")),
+                            }
+                        })
+                        .help("Written for characters that couldn't be decoded ('' to drop them)"),
+                )
+                .arg(
+                    Arg::new("max-errors")
+                        .long("max-errors")
+                        .value_name("N")
+                        .default_value("4")
+                        .value_parser(value_parser!(usize))
+                        .help("Suppress runs of more than N undecodable characters as noise (0 to show them all)"),
+                )
+                .arg(
+                    farnsworth_arg()
+                        .help("The slower overall speed the sender spreads the characters out to (Farnsworth timing), so its longer letter gaps don't end words"),
+                )
+                .arg(threshold_arg())
+                .arg(
+                    Arg::new("debounce")
+                        .long("debounce")
+                        .value_name("FRACTION")
+                        .default_value("0.25")
+                        .value_parser(|v: &str| {
+                            v.parse::<f32>()
+                                .map_err(|_| String::from("Debounce must be a valid floating-point number"))
+                                .and_then(|val| {
+                                    if (0.0..=1.0).contains(&val) {
+                                        Ok(val)
+                                    } else {
+                                        Err(String::from("Debounce must be between 0.0 and 1.0"))
+                                    }
+                                })
+                        })
+                        .help(
+                            "Ignore tones and gaps shorter than this fraction of a dot [0.0..1.0]",
+                        ),
+                )
+                .arg(
+                    Arg::new("timeout-dots")
+                        .long("timeout-dots")
+                        .value_name("DOTS")
+                        .default_value("20")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .help("End the message after a silence as long as this many dots"),
+                )
+                .arg(
+                    Arg::new("ring-capacity")
+                        .long("ring-capacity")
+                        .value_name("SAMPLES")
+                        .default_value("65536")
+                        .value_parser(value_parser!(usize))
+                        .help("How many captured samples may wait to be decoded before they are dropped"),
+                )
+                .arg(
+                    Arg::new("bandwidth")
+                        .short('W')
+                        .long("bandwidth")
+                        .value_parser(|v: &str| {
+                            v.parse::<f32>()
+                                .map_err(|_| String::from("Bandwidth must be a valid floating-point number"))
+                                .and_then(|val| {
+                                    if (0.0..=1000.0).contains(&val) {
+                                        Ok(val)
+                                    } else {
+                                        Err(String::from("Threshold must be between 0.0Hz and 1000.0Hz"))
+                                    }
+                                })
+                        })
+                        .help(
+                            "Minimal signal value threshold [0.0..1.0]",
+                        ),
+                )
+                .arg(
+                    Arg::new("tone-range")
+                        .long("tone-range")
+                        .value_name("MIN:MAX")
+                        .default_value("300:1200")
+                        .value_parser(|v: &str| {
+                            let parse = |f: &str| f.trim().parse::<f32>().ok();
+                            match v.split_once(':').map(|(a, b)| (parse(a), parse(b))) {
+                                Some((Some(min), Some(max))) if 0.0 < min && min < max => {
+                                    Ok((min, max))
+                                }
+                                _ => Err(String::from(
+                                    "Tone range must be given as MIN:MAX in Hz (e.g. 300:1200)",
+                                )),
+                            }
+                        })
+                        .help("The range of frequencies in Hz to search with --tone auto"),
+                )
+                .arg(
+                    Arg::new("channel")
+                        .long("channel")
+                        .value_name("N|mix")
+                        .default_value("mix")
+                        .value_parser(|v: &str| v.parse::<crate::detect::Channel>())
+                        .help("The audio channel to decode (numbered from 0), or mix them all together"),
+                )
+                .arg(
+                    Arg::new("split-stereo")
+                        .long("split-stereo")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("channel")
+                        .help("Decode the left and right channels as two separate stations"),
+                )
+                .arg(
+                    Arg::new("tone-freq-left")
+                        .long("tone-freq-left")
+                        .value_name("TONE_FREQ")
+                        .value_parser(value_parser!(f32))
+                        .requires("split-stereo")
+                        .help("The tone frequency in Hz of the left station with --split-stereo"),
+                )
+                .arg(
+                    Arg::new("tone-freq-right")
+                        .long("tone-freq-right")
+                        .value_name("TONE_FREQ")
+                        .value_parser(value_parser!(f32))
+                        .requires("split-stereo")
+                        .help("The tone frequency in Hz of the right station with --split-stereo"),
+                )
+                .arg(
+                    Arg::new("scan")
+                        .long("scan")
+                        .value_name("LOW:HIGH:STEP")
+                        .value_parser(|v: &str| v.parse::<crate::scan::ScanRange>())
+                        .conflicts_with_all(["split-stereo", "tone", "waterfall", "mopp-listen"])
+                        .help("Decode every station from LOW to HIGH Hz at once, with a channel every STEP Hz (e.g. 400:900:50), each message tagged with its tone"),
+                )
+                .arg(detector_arg())
+                .arg(
+                    Arg::new("file")
+                        .short('f')
+                        .long("file")
+                        .value_name("WAV_FILE")
+                        .help("Decode morse code from a WAV file instead of live audio")
+                        .conflicts_with("target"), // Ensures `--file` and `--target` are mutually exclusive
+                )
+                .arg(
+                    Arg::new("replay")
+                        .long("replay")
+                        .value_name("WAV_FILE")
+                        .num_args(1..)
+                        .conflicts_with_all(["file", "target", "scan"])
+                        .help("Decode recordings (e.g. of --record) as fast as they can be, with how long each took"),
+                )
+                .arg(
+                    Arg::new("expect")
+                        .long("expect")
+                        .value_name("TEXT_FILE")
+                        .num_args(1..)
+                        .requires("replay")
+                        .help("The text expected of each recording of --replay, to print how many characters were decoded wrong"),
+                )
+                .arg(
+                    Arg::new("speed")
+                        .long("speed")
+                        .value_name("max|FACTOR")
+                        .default_value("max")
+                        .value_parser(|v: &str| v.parse::<crate::replay::ReplaySpeed>())
+                        .help("How fast --replay goes: as fast as it can, or a number of times faster than it was recorded, e.g. 10x"),
+                )
+                .arg(
+                    Arg::new("target")
+                        .short('d')
+                        .long("target")
+                        .alias("device")
+                        .value_name("NODE")
+                        .help("Read morse code from a specific PipeWire node (name or id)")
+                        .conflicts_with("file"), // Ensures `--target` and `--file` are mutually exclusive
+                )
+                .arg(
+                    Arg::new("list-targets")
+                        .long("list-targets")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("List the PipeWire nodes that may be used with --target"),
+                )
+                .arg(
+                    Arg::new("waterfall")
+                        .long("waterfall")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["file", "split-stereo"])
+                        .help("Show a waterfall of the audio spectrum, and tune the tone with the left and right arrow keys"),
+                )
+                .arg(
+                    Arg::new("no-meter")
+                        .long("no-meter")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Don't show the signal level and tuning meter on the status line"),
+                )
+                .arg(
+                    Arg::new("monitor")
+                        .long("monitor")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("file")
+                        .help("Show when the tone is detected, with its level and the threshold, as it happens"),
+                )
+                .arg(
+                    Arg::new("monitor-bell")
+                        .long("monitor-bell")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("monitor")
+                        .help("Ring the terminal bell whenever the monitor hears a tone start"),
+                )
+                .arg(
+                    Arg::new("monitor-only")
+                        .long("monitor-only")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("monitor")
+                        .help("Show the monitor without decoding the text"),
+                )
+                .arg(
+                    Arg::new("monitor-audio")
+                        .long("monitor-audio")
+                        .value_name("SINK")
+                        .num_args(0..=1)
+                        .default_missing_value("")
+                        .conflicts_with_all(["file", "scan"])
+                        .help("Play what the bandpass filter hears, to the default sink or another one, to set it by ear (m mutes it)"),
+                )
+                .arg(
+                    Arg::new("monitor-audio-volume")
+                        .long("monitor-audio-volume")
+                        .value_name("VOLUME")
+                        .default_value("0.5")
+                        .value_parser(|v: &str| match v.parse::<f32>() {
+                            Ok(volume) if (0.0..=1.0).contains(&volume) => Ok(volume),
+                            _ => Err(String::from("Volume must be from 0.0 to 1.0")),
+                        })
+                        .requires("monitor-audio")
+                        .help("The volume of --monitor-audio, from 0.0 to 1.0"),
+                )
+                .arg(
+                    Arg::new("monitor-audio-marker")
+                        .long("monitor-audio-marker")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("monitor-audio")
+                        .help("Mark the tones the decoder hears with --monitor-audio, with a quieter tone an octave above"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("monitor-audio")
+                        .help("Play --monitor-audio even to the sink whose monitor is captured, which feeds it back"),
+                )
+                .arg(
+                    Arg::new("record")
+                        .long("record")
+                        .value_name("WAV_FILE")
+                        .conflicts_with("file")
+                        .help("Write the audio captured to a WAV file while decoding, e.g. to report a bad decode"),
+                )
+                .arg(
+                    Arg::new("record-filtered")
+                        .long("record-filtered")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("record")
+                        .conflicts_with("scan")
+                        .help("Record the audio as it comes out of the bandpass filter, instead of as it is captured"),
+                )
+                .arg(
+                    Arg::new("record-split")
+                        .long("record-split")
+                        .value_name("MINUTES|SIZE")
+                        .requires("record")
+                        .value_parser(|v: &str| v.parse::<crate::recording::Split>())
+                        .help("Start a new numbered WAV file after some minutes like 30min, or at a size like 100MB"),
+                )
+                .arg(
+                    Arg::new("log-file")
+                        .long("log-file")
+                        .value_name("PATH")
+                        .conflicts_with("file")
+                        .help("Append each message heard to a file, as JSON lines if it is named .jsonl, or to stdout (as JSON) instead of the display if it is -"),
+                )
+                .arg(
+                    Arg::new("log-rotate")
+                        .long("log-rotate")
+                        .value_name("daily|SIZE")
+                        .requires("log-file")
+                        .value_parser(|v: &str| v.parse::<crate::transcript::Rotation>())
+                        .help("Start a new log file each day, or when it would get bigger than a size like 10MB"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .value_name("text|json")
+                        .default_value("text")
+                        .value_parser(|v: &str| v.parse::<crate::events::OutputFormat>())
+                        .conflicts_with_all(["file", "morse", "waterfall", "monitor"])
+                        .help("Write a line of JSON for each decoded character and message, instead of the display"),
+                )
+                .arg(
+                    Arg::new("verbose-events")
+                        .long("verbose-events")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("With --output json, also write when each tone starts and ends, and the level of the tone"),
+                )
+                .arg(
+                    Arg::new("plain")
+                        .long("plain")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["waterfall", "monitor"])
+                        .help("Print each message on a line of its own, without redrawing the screen, as when the output is not a terminal"),
+                )
+                .arg(
+                    Arg::new("alert")
+                        .long("alert")
+                        .value_name("PATTERN")
+                        .action(clap::ArgAction::Append)
+                        .help("Ring the bell and highlight the text when a pattern (a regular expression, in any case) is heard, e.g. your callsign. Can be given more than once"),
+                )
+                .arg(
+                    Arg::new("alert-exec")
+                        .long("alert-exec")
+                        .value_name("COMMAND")
+                        .requires("alert")
+                        .help("Run a command with each message an alert is in, as its last argument, e.g. notify-send"),
+                )
+                .arg(
+                    Arg::new("notify")
+                        .long("notify")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("file")
+                        .help("Show a desktop notification for each message heard"),
+                )
+                .arg(
+                    Arg::new("speak")
+                        .long("speak")
+                        .value_name("messages|words")
+                        .num_args(0..=1)
+                        .default_missing_value("messages")
+                        .value_parser(|v: &str| v.parse::<crate::speech::SpeakMode>())
+                        .conflicts_with("file")
+                        .help("Speak each message heard aloud, or each word as it is complete, with espeak-ng or spd-say [default: messages]"),
+                )
+                .arg(
+                    Arg::new("speak-min-length")
+                        .long("speak-min-length")
+                        .value_name("N")
+                        .default_value("2")
+                        .value_parser(value_parser!(usize))
+                        .help("Don't --speak the messages shorter than N characters, as they are likely noise"),
+                )
+                .arg(
+                    Arg::new("serve")
+                        .long("serve")
+                        .value_name("tcp|ws:ADDRESS:PORT")
+                        .value_parser(|v: &str| v.parse::<crate::server::ServeAddr>())
+                        .conflicts_with("file")
+                        .help("Stream the decoded text to every client that connects over TCP, e.g. tcp:0.0.0.0:7373, or as JSON over a WebSocket, with a page showing it, e.g. ws:0.0.0.0:7374"),
+                )
+                .arg(
+                    Arg::new("serve-format")
+                        .long("serve-format")
+                        .value_name("text|json")
+                        .default_value("text")
+                        .value_parser(|v: &str| v.parse::<crate::events::OutputFormat>())
+                        .requires("serve")
+                        .help("Stream the messages over tcp as lines of text, or each character and message as a line of JSON"),
+                )
+                .arg(
+                    Arg::new("serve-backlog")
+                        .long("serve-backlog")
+                        .value_name("N")
+                        .default_value("20")
+                        .value_parser(value_parser!(usize))
+                        .help("How many of the last messages are sent to a client as it connects"),
+                )
+                .arg(
+                    Arg::new("udp-target")
+                        .long("udp-target")
+                        .value_name("HOST:PORT")
+                        .conflicts_with("file")
+                        .help("Send a UDP datagram for each message heard, e.g. to logging software, to a host:port or [IPv6]:port"),
+                )
+                .arg(
+                    Arg::new("udp-format")
+                        .long("udp-format")
+                        .value_name("json|plain")
+                        .default_value("json")
+                        .value_parser(|v: &str| v.parse::<crate::udp::UdpFormat>())
+                        .requires("udp-target")
+                        .help("Send the message as JSON (with the time, the speed and the tone), or only its text"),
+                )
+                .arg(
+                    Arg::new("udp-source-port")
+                        .long("udp-source-port")
+                        .value_name("PORT")
+                        .value_parser(value_parser!(u16))
+                        .requires("udp-target")
+                        .help("The port to send the --udp-target datagrams from [default: any free port]"),
+                )
+                .arg(
+                    Arg::new("mqtt")
+                        .long("mqtt")
+                        .value_name("mqtt://[USER:PASSWORD@]BROKER[:PORT]")
+                        .value_parser(|v: &str| v.parse::<crate::mqtt::MqttUrl>())
+                        .conflicts_with("file")
+                        .help("Publish each message heard to an MQTT broker, as JSON, and the status of each station every 10 seconds"),
+                )
+                .arg(
+                    Arg::new("mqtt-topic")
+                        .long("mqtt-topic")
+                        .value_name("TOPIC")
+                        .default_value("code-smore/decoded")
+                        .requires("mqtt")
+                        .help("The topic the messages are published to"),
+                )
+                .arg(
+                    Arg::new("mqtt-status-topic")
+                        .long("mqtt-status-topic")
+                        .value_name("TOPIC")
+                        .requires("mqtt")
+                        .help("The topic the level of the tone and the speed are published to [default: the --mqtt-topic, then /status]"),
+                )
+                .arg(
+                    Arg::new("mqtt-retain")
+                        .long("mqtt-retain")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("mqtt")
+                        .help("Have the broker keep the last message, for the clients that subscribe later"),
+                )
+                .arg(
+                    Arg::new("mqtt-username")
+                        .long("mqtt-username")
+                        .value_name("USER")
+                        .requires("mqtt")
+                        .help("The username to connect to the broker with, instead of the one in the URL"),
+                )
+                .arg(
+                    Arg::new("mqtt-password")
+                        .long("mqtt-password")
+                        .value_name("PASSWORD")
+                        .requires("mqtt")
+                        .help("The password to connect to the broker with, instead of the one in the URL"),
+                )
+                .arg(
+                    Arg::new("rigctl")
+                        .long("rigctl")
+                        .value_name("HOST:PORT")
+                        .num_args(0..=1)
+                        .default_missing_value("localhost:4532")
+                        .help("Tag the messages and events with the frequency and mode of the radio, asked of a running rigctld [default: localhost:4532]"),
+                )
+                .arg(
+                    Arg::new("rigctl-follow")
+                        .long("rigctl-follow")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("rigctl")
+                        .help("Tag the messages with the frequency of the signal (the VFO and the offset of the tone), instead of the VFO"),
+                )
+                .arg(
+                    Arg::new("spot")
+                        .long("spot")
+                        .value_name("FILE|URL")
+                        .value_parser(|v: &str| v.parse::<crate::spot::SpotTarget>())
+                        .help("Spot the stations heard calling CQ, appending DX cluster lines to a file, or posting JSON to an http:// endpoint"),
+                )
+                .arg(
+                    Arg::new("spotter")
+                        .long("spotter")
+                        .value_name("CALLSIGN")
+                        .default_value("N0CALL")
+                        .requires("spot")
+                        .help("The callsign the spots are from, in the DX cluster lines"),
+                )
+                .arg(
+                    Arg::new("spot-window")
+                        .long("spot-window")
+                        .value_name("MINUTES")
+                        .default_value("10")
+                        .value_parser(value_parser!(u64))
+                        .requires("spot")
+                        .help("Spot each callsign only once within this many minutes"),
+                )
+                .arg(
+                    Arg::new("mopp-listen")
+                        .long("mopp-listen")
+                        .value_name("ADDR:PORT")
+                        .num_args(0..=1)
+                        .default_missing_value("0.0.0.0:7373")
+                        .conflicts_with_all(["target", "file"])
+                        .help("Decode the words received over MOPP (the protocol of the Morserino-32) instead of sound, on a UDP port or a multicast group [default: 0.0.0.0:7373]"),
+                )
+                .arg(
+                    Arg::new("fldigi")
+                        .long("fldigi")
+                        .value_name("URL")
+                        .num_args(0..=1)
+                        .default_missing_value("http://localhost:7362")
+                        .value_parser(|v: &str| v.parse::<crate::fldigi::FldigiUrl>())
+                        .help("Push the characters decoded into the RX text of fldigi, over its XML-RPC [default: http://localhost:7362]"),
+                )
+                .arg(
+                    Arg::new("fldigi-poll")
+                        .long("fldigi-poll")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("fldigi")
+                        .conflicts_with("rigctl")
+                        .help("Tag the messages and events with the frequency fldigi is tuned to"),
+                )
+                .arg(
+                    Arg::new("log-qso")
+                        .long("log-qso")
+                        .value_name("FILE.adi")
+                        .requires("mycall")
+                        .help("Collect the contacts heard with --mycall and another callsign, and log them to an ADIF file once confirmed at the end"),
+                )
+                .arg(
+                    Arg::new("mycall")
+                        .long("mycall")
+                        .value_name("CALLSIGN")
+                        .requires("log-qso")
+                        .help("Your callsign, for --log-qso"),
+                )
+                .arg(
+                    Arg::new("type-output")
+                        .long("type-output")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("allow-uinput")
+                        .help("Type the characters decoded into the window that has the focus, with a virtual keyboard (Linux only)"),
+                )
+                .arg(
+                    Arg::new("allow-uinput")
+                        .long("allow-uinput")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("type-output")
+                        .help("Agree to --type-output making a keyboard with /dev/uinput, which needs write access to it (e.g. with a udev rule), and types into whatever window has the focus"),
+                )
+                .arg(
+                    Arg::new("type-layout")
+                        .long("type-layout")
+                        .value_name("LAYOUT")
+                        .default_value("us")
+                        .value_parser(|v: &str| v.parse::<crate::uinput::Layout>())
+                        .help("The keyboard layout of the desktop, for --type-output: us, de, fr, or a file of lines like '? slash+shift'"),
+                )
+                .arg(
+                    Arg::new("type-rate")
+                        .long("type-rate")
+                        .value_name("CPS")
+                        .default_value("20")
+                        .value_parser(value_parser!(u32).range(1..=100))
+                        .help("The most characters typed a second, with --type-output"),
+                )
+                .arg(
+                    Arg::new("copy-on-exit")
+                        .long("copy-on-exit")
+                        .value_name("WHAT")
+                        .num_args(0..=1)
+                        .default_missing_value("transcript")
+                        .value_parser(|v: &str| v.parse::<crate::clipboard::CopyOnExit>())
+                        .help("Copy the messages of the session (transcript), or the last one (message), to the clipboard on quitting, or to a temporary file without a clipboard [default: transcript]"),
+                )
+                .arg(
+                    Arg::new("scrollback")
+                        .long("scrollback")
+                        .value_name("MESSAGES")
+                        .default_value("10000")
+                        .value_parser(value_parser!(usize))
+                        .help("How many of the latest messages are kept, to page back through (with h or Page Up), search (with /), copy and dump"),
+                )
+                .arg(
+                    Arg::new("dump-session")
+                        .long("dump-session")
+                        .value_name("FILE")
+                        .help("Write the messages kept of the session to a file on quitting"),
+                ),
+        )
+        .subcommand(
+            Command::new("cwdaemon")
+                .about("Key the sidetone for contest loggers (e.g. tlf, cqrlog), as cwdaemon does over UDP")
+                .args(
+                    sending_args()
+                        .into_iter()
+                        .filter(|arg| ["volume", "rise-time"].contains(&arg.get_id().as_str())),
+                )
+                .arg(
+                    Arg::new("port")
+                        .long("port")
+                        .value_name("PORT")
+                        .default_value("6789")
+                        .value_parser(value_parser!(u16))
+                        .help("The UDP port to listen on"),
+                )
+                .arg(
+                    Arg::new("target")
+                        .short('d')
+                        .long("target")
+                        .alias("device")
+                        .value_name("NODE")
+                        .help("Play to a specific PipeWire node (name or id)"),
+                )
+                .arg(
+                    winkeyer_arg()
+                        .conflicts_with("target")
+                        .help("Send the text with a Winkeyer (of K1EL) on a serial port instead of the sidetone (e.g. /dev/ttyUSB0)"),
+                ),
+        )
+        .subcommand(
+            Command::new("connect")
+                .about("Show the decoded text streamed by listen --serve on another machine")
+                .arg(
+                    Arg::new("address")
+                        .value_name("ADDRESS:PORT")
+                        .required(true)
+                        .value_parser(|v: &str| v.parse::<crate::server::ServeAddr>())
+                        .help("Where the listen --serve is, e.g. radio.local:7373"),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about(
+                    "Generates shell completions
+    script (tab completion)",
+                )
+                .hide(true)
+                .arg(
+                    Arg::new("shell")
+                        .help("The shell to generate completions for")
+                        .required(false)
+                        .value_parser(["bash", "zsh", "fish"]),
+                ),
+        )
+        .subcommand(
+            Command::new("credits")
+                .about(
+                    "Prints license information for all dependencies",
+                )
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+
+    #[test]
+    fn test_finite() {
+        let practice = |args: &[&str]| {
+            app().try_get_matches_from(
+                [
+                    "code-smore",
+                    "practice",
+                    "--groups",
+                    "--interferer",
+                    "calls",
+                ]
+                .iter()
+                .chain(args),
+            )
+        };
+        assert!(practice(&["--noise-snr", "-3"]).is_ok());
+        assert!(practice(&["--interferer-level-db", "-12"]).is_ok());
+        for value in ["nan", "inf"] {
+            for arg in [
+                "--noise-snr",
+                "--qrn",
+                "--interferer-offset-hz",
+                "--interferer-level-db",
+            ] {
+                assert_eq!(
+                    practice(&[arg, value]).unwrap_err().kind(),
+                    ErrorKind::ValueValidation,
+                    "{arg} {value}"
+                );
+            }
+            assert_eq!(
+                practice(&["--qsb", &format!("{value}:0.5")])
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::ValueValidation
+            );
+        }
+    }
 }
@@ -0,0 +1,132 @@
+use crate::prelude::*;
+use chrono::Local;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The programs that put their stdin on the clipboard, with their
+/// arguments, and the variable of the display they need: wl-clipboard
+/// on Wayland, then xclip or xsel on X11.
+const PROGRAMS: &[(&str, &[&str], &str)] = &[
+    ("wl-copy", &[], "WAYLAND_DISPLAY"),
+    ("xclip", &["-selection", "clipboard"], "DISPLAY"),
+    ("xsel", &["--clipboard", "--input"], "DISPLAY"),
+];
+
+/// What `listen --copy-on-exit` copies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CopyOnExit {
+    /// The messages of the session.
+    Transcript,
+    /// The last message completed.
+    Message,
+}
+
+impl FromStr for CopyOnExit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transcript" => Ok(CopyOnExit::Transcript),
+            "message" => Ok(CopyOnExit::Message),
+            _ => Err(format!("Must be transcript or message: {s}")),
+        }
+    }
+}
+
+/// Where a text was copied to.
+#[derive(Debug, PartialEq)]
+pub enum Copied {
+    /// On the clipboard, by this program.
+    Clipboard(&'static str),
+    /// To a file, there being no clipboard.
+    File(PathBuf),
+}
+
+impl std::fmt::Display for Copied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Copied::Clipboard(_) => write!(f, "Copied to the clipboard"),
+            Copied::File(path) => write!(f, "No clipboard, written to {}", path.display()),
+        }
+    }
+}
+
+/// Gives a text to a program on its stdin.
+fn pipe(program: &str, args: &[&str], text: &str) -> io::Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("The stdin of the child was piped")
+        .write_all(text.as_bytes())?;
+    let status = child.wait()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!("{program} failed: {status}"))),
+    }
+}
+
+/// Writes a text to a new file of a directory.
+fn copy_to_file(text: &str, dir: &Path) -> io::Result<Copied> {
+    let name = format!(
+        "code-smore-{}.txt",
+        Local::now().format("%Y%m%d-%H%M%S%.3f")
+    );
+    let path = dir.join(name);
+    std::fs::write(&path, text)?;
+    Ok(Copied::File(path))
+}
+
+/// Puts a text on the clipboard, with the first of the programs of the
+/// display that works. Without any (e.g. in a headless session, or over
+/// SSH), the text is written to a temporary file instead.
+pub fn copy(text: &str) -> io::Result<Copied> {
+    for &(program, args, display) in PROGRAMS {
+        if std::env::var_os(display).is_none() {
+            continue;
+        }
+        match pipe(program, args, text) {
+            Ok(()) => return Ok(Copied::Clipboard(program)),
+            Err(e) => debug!("Could not copy with {program}: {e}"),
+        }
+    }
+    copy_to_file(text, &std::env::temp_dir())
+}
+
+/// Copies a text, telling where it went, or why it couldn't.
+pub fn copy_and_tell(text: &str) -> String {
+    match copy(text) {
+        Ok(copied) => copied.to_string(),
+        Err(e) => format!("Could not copy: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipe() {
+        assert!(pipe("cat", &[], "CQ CQ DE K1ABC").is_ok());
+        assert!(pipe("false", &[], "CQ").is_err());
+        assert!(pipe("no-such-clipboard", &[], "CQ").is_err());
+    }
+
+    #[test]
+    fn test_copy_to_file() {
+        let dir = std::env::temp_dir();
+        let copied = copy_to_file("CQ CQ DE K1ABC\n", &dir).unwrap();
+        let Copied::File(path) = &copied else {
+            panic!("not copied to a file: {copied:?}");
+        };
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "CQ CQ DE K1ABC\n");
+        assert!(copied.to_string().starts_with("No clipboard, written to"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
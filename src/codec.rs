@@ -0,0 +1,138 @@
+use crate::morse::{char_to_morse, prosign, Charset};
+
+/// What is written between the characters, and between the words, of
+/// morse code text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Separators {
+    pub letter: String,
+    pub word: String,
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Self {
+            letter: " ".to_string(),
+            word: " / ".to_string(),
+        }
+    }
+}
+
+/// Splits text on a separator. The spaces around a separator don't
+/// matter, unless it is only made of spaces.
+fn split<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let pieces: Vec<&str> = match separator.trim() {
+        _ if separator.is_empty() => vec![text],
+        "" => text.split(separator).collect(),
+        trimmed => text.split(trimmed).collect(),
+    };
+    pieces
+        .into_iter()
+        .map(str::trim)
+        .filter(|piece| !piece.is_empty())
+        .collect()
+}
+
+/// Finds the dots and dashes of each character of one word. A prosign
+/// written in angle brackets, e.g. `<AR>`, is sent as one character.
+fn encode_word(word: &str) -> Vec<String> {
+    let mut codes = Vec::new();
+    let mut chars = word.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '<' {
+            if let Some((name, rest)) = chars.as_str().split_once('>') {
+                let code: String = name.chars().filter_map(char_to_morse).collect();
+                codes.extend(Some(code).filter(|code| !code.is_empty()));
+                chars = rest.chars();
+                continue;
+            }
+        }
+        codes.extend(char_to_morse(ch));
+    }
+    codes
+}
+
+/// Encodes text as morse code, e.g. `-.-. --.- / -.. .`. Characters
+/// that have no morse code are left out.
+pub fn encode(text: &str, separators: &Separators) -> String {
+    text.split_whitespace()
+        .map(encode_word)
+        .filter(|codes| !codes.is_empty())
+        .map(|codes| codes.join(&separators.letter))
+        .collect::<Vec<String>>()
+        .join(&separators.word)
+}
+
+/// Decodes morse code text, the same way as the audio decoder writes
+/// it out. Prosigns are written in angle brackets, and characters that
+/// couldn't be decoded as '?'.
+pub fn decode(code: &str, separators: &Separators, charset: Charset) -> String {
+    split(code, &separators.word)
+        .into_iter()
+        .map(|word| {
+            split(word, &separators.letter)
+                .into_iter()
+                // Any whitespace between the characters will do too:
+                .flat_map(|code| code.split_whitespace())
+                .map(|code| match prosign(code) {
+                    // Kana share their codes with the prosigns:
+                    Some(prosign) if charset != Charset::Wabun => format!("<{prosign}>"),
+                    _ => charset.letter(code).unwrap_or('?').to_string(),
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let separators = Separators::default();
+        assert_eq!(
+            encode("CQ CQ DE K1ABC", &separators),
+            "-.-. --.- / -.-. --.- / -.. . / -.- .---- .- -... -.-."
+        );
+        assert_eq!(
+            encode("73 <SK>  tu<AR>", &separators),
+            "--... ...-- / ...-.- / - ..- .-.-."
+        );
+        // Without a closing bracket, there is no prosign:
+        assert_eq!(encode("<AR E", &separators), ".- .-. / .");
+    }
+
+    #[test]
+    fn test_decode() {
+        let separators = Separators::default();
+        let decode = |code| decode(code, &separators, Charset::Latin);
+        assert_eq!(decode("-.-. --.-"), "CQ");
+        assert_eq!(
+            decode("-.-. --.- / -.. .  / -.- .---- .- -... -.-."),
+            "CQ DE K1ABC"
+        );
+        assert_eq!(decode("--... ...-- / ...-.- / ........"), "73 <SK> ?");
+        assert_eq!(decode(".-.-.-/..--.."), ". ?");
+    }
+
+    #[test]
+    fn test_separators() {
+        let separators = Separators {
+            letter: "|".to_string(),
+            word: " || ".to_string(),
+        };
+        let code = encode("CQ DE", &separators);
+        assert_eq!(code, "-.-.|--.- || -..|.");
+        assert_eq!(decode(&code, &separators, Charset::Latin), "CQ DE");
+    }
+
+    #[test]
+    fn test_decode_charset() {
+        let code = ".--. .-. .. .-- . -";
+        let separators = Separators::default();
+        assert_eq!(decode(code, &separators, Charset::Cyrillic), "ПРИВЕТ");
+        assert_eq!(decode(code, &separators, Charset::Latin), "PRIWET");
+        assert_eq!(encode("привет", &separators), code);
+    }
+}
@@ -0,0 +1,514 @@
+use crate::prelude::*;
+use clap::{ArgAction, ArgMatches, Command};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// The section of the options that all of the commands have.
+pub const OUTPUT: &str = "output";
+
+/// Options that replace each other: whichever is set last is used.
+const REPLACING: [[&str; 2]; 1] = [["dot", "wpm"]];
+
+/// The configuration file, e.g. `~/.config/code-smore/config.toml`.
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("config.toml"))
+}
+
+/// An option set in a config file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Setting {
+    /// `output`, or the command that the option is of.
+    pub section: String,
+    /// The name of the option, without the dashes.
+    pub key: String,
+    pub value: toml::Value,
+    /// The line it was set on, counting from 1.
+    pub line: Option<usize>,
+}
+
+impl Setting {
+    /// Where it was set, for the warnings.
+    fn place(&self, file: &str) -> String {
+        match self.line {
+            Some(line) => format!("on line {line} of {file}"),
+            None => format!("in {file}"),
+        }
+    }
+}
+
+/// The settings of one file, or one preset of it, and what they came
+/// from (e.g. "preset shack"), lowest in precedence first.
+pub struct Layer {
+    pub origin: String,
+    /// The file they were read from, for the warnings.
+    pub file: String,
+    pub settings: Vec<Setting>,
+}
+
+/// The lines that the keys of a TOML document are on, by their dotted
+/// path, e.g. `presets.shack.listen.bandwidth`.
+pub fn key_lines(text: &str) -> HashMap<String, usize> {
+    let unquote = |key: &str| {
+        key.split('.')
+            .map(|part| part.trim().trim_matches(|ch| ch == '"' || ch == '\''))
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+    let mut table = String::new();
+    let mut lines = HashMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.trim_start_matches('[');
+            table = unquote(header.split(']').next().unwrap_or_default());
+            lines.entry(table.clone()).or_insert(number + 1);
+        } else if let Some((key, _)) = line.split_once('=').filter(|_| !line.starts_with('#')) {
+            let key = match table.is_empty() {
+                true => unquote(key),
+                false => format!("{table}.{}", unquote(key)),
+            };
+            lines.entry(key).or_insert(number + 1);
+        }
+    }
+    lines
+}
+
+/// Reads the options of one section, everything in it being an option.
+fn section_settings(
+    section: &str,
+    table: &toml::Table,
+    path: &str,
+    lines: &HashMap<String, usize>,
+) -> Vec<Setting> {
+    table
+        .iter()
+        .map(|(key, value)| Setting {
+            section: section.to_string(),
+            key: key.clone(),
+            value: value.clone(),
+            line: lines.get(&format!("{path}.{key}")).copied(),
+        })
+        .collect()
+}
+
+/// A config file: the profile and preset it chooses, its sections of
+/// options, and its presets of more sections, e.g.:
+///
+/// ```toml
+/// preset = "shack"
+///
+/// [output]
+/// tone = 700
+///
+/// [listen]
+/// bandwidth = 200
+///
+/// [presets.headphones.output]
+/// tone = 550
+/// ```
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    pub profile: Option<String>,
+    pub preset: Option<String>,
+    pub settings: Vec<Setting>,
+    pub presets: BTreeMap<String, Vec<Setting>>,
+    /// The keys that are not sections, presets, or options.
+    pub unknown: Vec<Setting>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        let lines = key_lines(text);
+        let mut config = Config::default();
+        let name = |key: &str, value: &toml::Value| match value {
+            toml::Value::String(name) => Ok(Some(name.clone())),
+            _ => Err(format!("The {key} must be a string")),
+        };
+        for (key, value) in &table {
+            match (key.as_str(), value) {
+                ("profile", value) => {
+                    config.profile = name(key, value)?;
+                    crate::profile::validate(config.profile.as_deref().unwrap_or_default())?;
+                }
+                ("preset", value) => config.preset = name(key, value)?,
+                ("presets", toml::Value::Table(presets)) => {
+                    for (preset, sections) in presets {
+                        let toml::Value::Table(sections) = sections else {
+                            return Err(format!("The preset {preset} must be a table of sections"));
+                        };
+                        let settings = config.presets.entry(preset.clone()).or_default();
+                        for (section, options) in sections {
+                            let path = format!("presets.{preset}.{section}");
+                            match options {
+                                toml::Value::Table(options) => settings
+                                    .extend(section_settings(section, options, &path, &lines)),
+                                _ => config.unknown.push(Setting {
+                                    section: format!("presets.{preset}"),
+                                    key: section.clone(),
+                                    value: options.clone(),
+                                    line: lines.get(&path).copied(),
+                                }),
+                            }
+                        }
+                    }
+                }
+                (section, toml::Value::Table(options)) => config
+                    .settings
+                    .extend(section_settings(section, options, section, &lines)),
+                _ => config.unknown.push(Setting {
+                    section: String::new(),
+                    key: key.clone(),
+                    value: value.clone(),
+                    line: lines.get(key).copied(),
+                }),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Reads the config file, if there is one, warning of what can't be
+    /// read in it.
+    pub fn load() -> Self {
+        let Some(path) = path() else {
+            return Config::default();
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Config::default(),
+            Err(e) => {
+                warn!("Could not read {}: {e}", path.display());
+                return Config::default();
+            }
+        };
+        let config = Config::parse(&text).unwrap_or_else(|e| {
+            warn!("Ignoring {}: {e}", path.display());
+            Config::default()
+        });
+        for setting in &config.unknown {
+            warn!(
+                "Unknown key {} {}",
+                setting.key,
+                setting.place(&path.display().to_string())
+            );
+        }
+        config
+    }
+}
+
+/// Where each option that is set came from, by section and option.
+pub type Origins = BTreeMap<(String, String), String>;
+
+/// Whether an option can be set by a config file: not one of those
+/// choosing which drill or keyer is used, as those exclude each other,
+/// and not one that is read before the config file is.
+fn configurable(command: &Command, arg: &clap::Arg) -> bool {
+    let exclusive = command
+        .get_groups()
+        .any(|group| !group.clone().is_multiple() && group.get_args().any(|id| id == arg.get_id()));
+    let early =
+        ["help", "version", "profile", "preset", "verbose"].contains(&arg.get_id().as_str());
+    arg.get_long().is_some() && !arg.is_hide_set() && !exclusive && !early
+}
+
+/// The options of a section, those of `output` being the global ones.
+fn section_args<'a>(cmd: &'a Command, section: &str) -> Option<(&'a Command, Vec<&'a clap::Arg>)> {
+    let (command, global) = match section {
+        OUTPUT => (cmd, true),
+        _ => (cmd.find_subcommand(section)?, false),
+    };
+    let args = command
+        .get_arguments()
+        .filter(|arg| arg.is_global_set() == global)
+        .collect();
+    Some((command, args))
+}
+
+/// The option that a key of a section sets, by its name or an alias.
+fn find_arg<'a>(args: &[&'a clap::Arg], key: &str) -> Option<&'a clap::Arg> {
+    args.iter().copied().find(|arg| {
+        arg.get_id() == key
+            || arg.get_long() == Some(key)
+            || arg
+                .get_all_aliases()
+                .is_some_and(|aliases| aliases.contains(&key))
+    })
+}
+
+/// The values of an option, as they would be given on the command line.
+fn values(arg: &clap::Arg, value: &toml::Value) -> Result<Vec<String>, String> {
+    let flag = matches!(arg.get_action(), ArgAction::SetTrue | ArgAction::SetFalse);
+    let value_text = |value: &toml::Value| match value {
+        toml::Value::String(text) => Ok(text.clone()),
+        toml::Value::Integer(_) | toml::Value::Float(_) => Ok(value.to_string()),
+        toml::Value::Boolean(_) if flag => Ok(value.to_string()),
+        _ => Err(format!("{value} is not a value of --{}", arg.get_id())),
+    };
+    let values = match value {
+        toml::Value::Array(values) => values.iter().map(value_text).collect::<Result<_, _>>()?,
+        value => vec![value_text(value)?],
+    };
+    if flag {
+        return match values[..] {
+            [ref value] if value == "true" || value == "false" => Ok(values),
+            _ => Err(format!("--{} must be true or false", arg.get_id())),
+        };
+    }
+    // Parses them the way the command line would:
+    let probe = Command::new("config")
+        .no_binary_name(true)
+        .arg(
+            clap::Arg::new("value")
+                .num_args(1..)
+                .allow_hyphen_values(true)
+                .value_parser(arg.get_value_parser().clone()),
+        )
+        .try_get_matches_from(&values);
+    match probe {
+        Ok(_) => Ok(values),
+        Err(e) => {
+            let e = e.to_string();
+            let line = e.lines().next().unwrap_or_default();
+            Err(line.rsplit("': ").next().unwrap_or(line).to_string())
+        }
+    }
+}
+
+/// Makes the options set by the layers of settings the defaults of the
+/// command line, each layer over the ones before it, warning of the
+/// settings that are not options.
+pub fn apply(mut cmd: Command, layers: &[Layer]) -> (Command, Origins) {
+    let mut defaults: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+    let mut origins = Origins::new();
+    for layer in layers {
+        for setting in &layer.settings {
+            let place = setting.place(&layer.file);
+            let Some((command, args)) = section_args(&cmd, &setting.section) else {
+                warn!("Unknown section [{}] {place}", setting.section);
+                continue;
+            };
+            let Some(arg) = find_arg(&args, &setting.key) else {
+                warn!(
+                    "Unknown key {} of [{}] {place}",
+                    setting.key, setting.section
+                );
+                continue;
+            };
+            if !configurable(command, arg) {
+                warn!(
+                    "Ignoring {} of [{}] {place}: it can only be given on the command line",
+                    setting.key, setting.section
+                );
+                continue;
+            }
+            match values(arg, &setting.value) {
+                Ok(values) => {
+                    let id = arg.get_id().to_string();
+                    for pair in REPLACING.iter().filter(|pair| pair.contains(&id.as_str())) {
+                        for other in pair.iter().filter(|&&other| other != id) {
+                            let key = (setting.section.clone(), other.to_string());
+                            defaults.remove(&key);
+                            origins.remove(&key);
+                        }
+                    }
+                    let key = (setting.section.clone(), id);
+                    defaults.insert(key.clone(), values);
+                    origins.insert(key, layer.origin.clone());
+                }
+                Err(e) => warn!("Ignoring {} {place}: {e}", setting.key),
+            }
+        }
+    }
+    for ((section, id), values) in defaults {
+        // The defaults of the command line live as long as it does:
+        let values: Vec<&'static str> = values
+            .into_iter()
+            .map(|value| &*Box::leak(value.into_boxed_str()))
+            .collect();
+        let default = |arg: clap::Arg| arg.default_values(values);
+        cmd = match section.as_str() {
+            OUTPUT => cmd.mut_arg(id, default),
+            _ => cmd.mut_subcommand(section, |command| command.mut_arg(id, default)),
+        };
+    }
+    (cmd, origins)
+}
+
+/// Writes values the way they are written in a config file.
+fn format_values(values: &[String]) -> String {
+    let format = |value: &String| match value.parse::<f64>() {
+        Ok(_) => value.clone(),
+        Err(_) if value == "true" || value == "false" => value.clone(),
+        Err(_) => toml::Value::String(value.clone()).to_string(),
+    };
+    match values {
+        [value] => format(value),
+        _ => format!(
+            "[{}]",
+            values.iter().map(format).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+/// The options that are in effect, written as a config file with where
+/// each came from.
+///
+/// # Arguments
+/// * `top` - The keys of the top of the file, e.g. the profile, with
+///   their values and where they came from.
+/// * `sections` - The sections to show the options of.
+pub fn show(
+    cmd: &Command,
+    matches: &ArgMatches,
+    origins: &Origins,
+    top: &[(&str, Option<String>, String)],
+    sections: &[String],
+) -> String {
+    let mut text = String::new();
+    for (key, value, origin) in top {
+        match value {
+            Some(value) => {
+                text += &format!(
+                    "{key} = {}  # {origin}\n",
+                    format_values(std::slice::from_ref(value))
+                )
+            }
+            None => text += &format!("# {key} is not set\n"),
+        }
+    }
+    for section in sections {
+        let Some((command, args)) = section_args(cmd, section) else {
+            continue;
+        };
+        text += &format!("\n[{section}]\n");
+        let mut args: Vec<_> = args
+            .into_iter()
+            .filter(|arg| configurable(command, arg))
+            .collect();
+        args.sort_by_key(|arg| arg.get_id());
+        for arg in args {
+            let id = arg.get_id().as_str();
+            let given = section == OUTPUT
+                && matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine);
+            let (values, origin): (Vec<String>, &str) = match given {
+                true => (
+                    matches
+                        .get_raw(id)
+                        .into_iter()
+                        .flatten()
+                        .map(|value| value.to_string_lossy().to_string())
+                        .collect(),
+                    "the command line",
+                ),
+                false => (
+                    match (arg.get_default_values(), arg.get_action()) {
+                        // The flags are false unless given, true for these:
+                        ([], ArgAction::SetTrue) => vec!["false".to_string()],
+                        ([], ArgAction::SetFalse) => vec!["true".to_string()],
+                        (values, _) => values
+                            .iter()
+                            .map(|value| value.to_string_lossy().to_string())
+                            .collect(),
+                    },
+                    origins
+                        .get(&(section.clone(), id.to_string()))
+                        .map(String::as_str)
+                        .unwrap_or("default"),
+                ),
+            };
+            match values.is_empty() {
+                true => text += &format!("# {id} is not set\n"),
+                false => text += &format!("{id} = {}  # {origin}\n", format_values(&values)),
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(text: &str) -> Layer {
+        Layer {
+            origin: "the config file".to_string(),
+            file: "config.toml".to_string(),
+            settings: Config::parse(text).unwrap().settings,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            "profile = \"cw\"\n\
+             preset = \"shack\"\n\
+             colour = 1\n\
+             \n\
+             [listen]\n\
+             bandwidth = 200\n\
+             \n\
+             [presets.shack.output]\n\
+             tone = 700\n",
+        )
+        .unwrap();
+        assert_eq!(config.profile.as_deref(), Some("cw"));
+        assert_eq!(config.preset.as_deref(), Some("shack"));
+        assert_eq!(
+            config.settings,
+            [Setting {
+                section: "listen".to_string(),
+                key: "bandwidth".to_string(),
+                value: toml::Value::Integer(200),
+                line: Some(6),
+            }]
+        );
+        assert_eq!(config.presets["shack"][0].key, "tone");
+        assert_eq!(config.presets["shack"][0].line, Some(9));
+        assert_eq!(config.unknown[0].key, "colour");
+        assert_eq!(config.unknown[0].line, Some(3));
+        assert!(Config::parse("profile = \"../x\"").is_err());
+        assert!(Config::parse("profile = 1").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let base = layer("[output]\ntone = 700\ndot = 50\n[listen]\nbandwidth = 300\nbandwith = 1");
+        let preset = Layer {
+            origin: "preset headphones".to_string(),
+            ..layer("[output]\nwpm = 25\ntext = true\n[listen]\nbandwidth = \"wide\"")
+        };
+        let (cmd, origins) = apply(crate::cli::app(), &[base, preset]);
+        let matches = cmd
+            .clone()
+            .try_get_matches_from(["code-smore", "listen"])
+            .unwrap();
+        assert_eq!(matches.get_one::<Option<f32>>("tone"), Some(&Some(700.0)));
+        // The speed replaced the dot duration:
+        assert_eq!(matches.get_one::<Option<u32>>("wpm"), Some(&Some(25)));
+        assert_eq!(matches.get_one::<u32>("dot"), None);
+        assert!(matches.get_flag("text"));
+        // Not a bandwidth, so the one before it is kept:
+        let (_, listen) = matches.subcommand().unwrap();
+        assert_eq!(listen.get_one::<f32>("bandwidth"), Some(&300.0));
+        assert_eq!(
+            origins[&(OUTPUT.to_string(), "wpm".to_string())],
+            "preset headphones"
+        );
+        // The command line still wins:
+        let matches = cmd
+            .clone()
+            .try_get_matches_from(["code-smore", "listen", "--tone", "600", "-W", "100"])
+            .unwrap();
+        assert_eq!(matches.get_one::<Option<f32>>("tone"), Some(&Some(600.0)));
+        let (_, listen) = matches.subcommand().unwrap();
+        assert_eq!(listen.get_one::<f32>("bandwidth"), Some(&100.0));
+        let shown = show(&cmd, &matches, &origins, &[], &[OUTPUT.to_string()]);
+        assert!(
+            shown.contains("tone = 600  # the command line\n"),
+            "{shown}"
+        );
+        assert!(shown.contains("wpm = 25  # preset headphones\n"), "{shown}");
+        assert!(shown.contains("# dot is not set\n"), "{shown}");
+        assert!(shown.contains("sound = false  # default\n"), "{shown}");
+    }
+}
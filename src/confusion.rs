@@ -0,0 +1,190 @@
+use crate::fecr_quiz::QuizResult;
+use tabled::settings::style::Style;
+use tabled::{Table, Tabled};
+
+/// The rows of a QWERTY keyboard, with how far each is shifted to the
+/// right, in keys.
+const KEYBOARD: [(&str, f64); 4] = [
+    ("1234567890", 0.0),
+    ("QWERTYUIOP", 0.5),
+    ("ASDFGHJKL", 0.75),
+    ("ZXCVBNM", 1.25),
+];
+
+/// Where a key is on the keyboard: its row, and how far to the right.
+fn key_position(key: &str) -> Option<(usize, f64)> {
+    let mut chars = key.chars();
+    let key = chars.next()?.to_ascii_uppercase();
+    if chars.next().is_some() {
+        return None;
+    }
+    KEYBOARD
+        .iter()
+        .enumerate()
+        .find_map(|(row, (keys, shift))| keys.find(key).map(|column| (row, column as f64 + shift)))
+}
+
+/// Whether two keys are next to each other on a QWERTY keyboard, so
+/// that typing one for the other is more likely a slip of the finger
+/// than of the ear.
+pub fn adjacent_keys(a: &str, b: &str) -> bool {
+    match (key_position(a), key_position(b)) {
+        (Some((row_a, x_a)), Some((row_b, x_b))) => match row_a.abs_diff(row_b) {
+            0 => (x_a - x_b).abs() <= 1.0,
+            1 => (x_a - x_b).abs() <= 0.75,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// A character that was copied as another, and how many times.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Confusion {
+    pub sent: String,
+    pub typed: String,
+    pub count: u32,
+}
+
+/// Counts the characters copied as others, the most frequent first.
+/// Those that were missed, with nothing typed, are not confusions.
+pub fn confusions(results: &QuizResult) -> Vec<Confusion> {
+    let pairs = results
+        .prompts
+        .iter()
+        .zip(&results.typed)
+        .filter_map(|(sent, typed)| {
+            let typed = typed.as_ref()?;
+            (!typed.eq_ignore_ascii_case(sent)).then(|| Confusion {
+                sent: sent.clone(),
+                typed: typed.to_uppercase(),
+                count: 1,
+            })
+        });
+    merge(pairs)
+}
+
+/// Adds up the counts of the same confusions, e.g. over some sessions,
+/// the most frequent first.
+pub fn merge(confusions: impl IntoIterator<Item = Confusion>) -> Vec<Confusion> {
+    let mut merged: Vec<Confusion> = Vec::new();
+    for confusion in confusions {
+        match merged
+            .iter_mut()
+            .find(|c| c.sent == confusion.sent && c.typed == confusion.typed)
+        {
+            Some(same) => same.count += confusion.count,
+            None => merged.push(confusion),
+        }
+    }
+    merged.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| (&a.sent, &a.typed).cmp(&(&b.sent, &b.typed)))
+    });
+    merged
+}
+
+#[derive(Tabled)]
+struct ConfusionRow {
+    sent: String,
+    typed: String,
+    count: u32,
+    of_times_sent: String,
+}
+
+/// Prints the confusions, those of the ear apart from the keys that are
+/// next to each other.
+///
+/// # Arguments
+/// * `times_sent` - How many times a character was sent.
+pub fn print_confusions(confusions: &[Confusion], times_sent: impl Fn(&str) -> u32) {
+    if confusions.is_empty() {
+        println!("\nNo characters were copied as others.");
+        return;
+    }
+    let (keyboard, heard): (Vec<&Confusion>, Vec<&Confusion>) = confusions
+        .iter()
+        .partition(|confusion| adjacent_keys(&confusion.sent, &confusion.typed));
+    let rows = |confusions: Vec<&Confusion>| -> Vec<ConfusionRow> {
+        confusions
+            .into_iter()
+            .map(|confusion| ConfusionRow {
+                sent: confusion.sent.clone(),
+                typed: confusion.typed.clone(),
+                count: confusion.count,
+                of_times_sent: format!(
+                    "{:.0}%",
+                    confusion.count as f64 / times_sent(&confusion.sent).max(1) as f64 * 100.0
+                ),
+            })
+            .collect()
+    };
+    if !heard.is_empty() {
+        println!("\nCharacters heard as others:\n");
+        println!("{}", Table::new(rows(heard)).with(Style::rounded()));
+    }
+    if !keyboard.is_empty() {
+        println!("\nTypos, of keys next to each other:\n");
+        println!("{}", Table::new(rows(keyboard)).with(Style::rounded()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_adjacent_keys() {
+        assert!(adjacent_keys("J", "K"));
+        assert!(adjacent_keys("q", "a"));
+        assert!(adjacent_keys("H", "B"));
+        assert!(adjacent_keys("1", "q"));
+        assert!(!adjacent_keys("D", "B"));
+        assert!(!adjacent_keys("Q", "S"));
+        assert!(!adjacent_keys("H", "S"));
+        assert!(!adjacent_keys("K", "K<AR>"));
+        assert!(!adjacent_keys(".", ","));
+    }
+
+    #[test]
+    fn test_confusions() {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let results = QuizResult {
+            prompts: strings(&["H", "H", "K", "H", "B", "E"]),
+            responses: vec![
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(true),
+            ],
+            reaction_times: vec![Some(Duration::ZERO); 6],
+            typed_ahead: vec![false; 6],
+            typed: vec![
+                Some("s".to_string()),
+                Some("S".to_string()),
+                Some("J".to_string()),
+                None,
+                Some("D".to_string()),
+                Some("e".to_string()),
+            ],
+            sent_at: vec![chrono::Local::now(); 6],
+        };
+        let pair = |sent: &str, typed: &str, count| Confusion {
+            sent: sent.to_string(),
+            typed: typed.to_string(),
+            count,
+        };
+        assert_eq!(
+            confusions(&results),
+            vec![pair("H", "S", 2), pair("B", "D", 1), pair("K", "J", 1)]
+        );
+        assert_eq!(
+            merge([pair("K", "J", 1), pair("H", "S", 2), pair("K", "J", 2)]),
+            vec![pair("K", "J", 3), pair("H", "S", 2)]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::callsign::random_callsign;
+use crate::qso::{check_fields, fill_fields, NAMES, STATES};
+use rand::Rng;
+
+/// The exchange sent unless a template is given.
+pub const DEFAULT_EXCHANGE: &str = "{RST} {NR}";
+
+/// The fields an exchange template can have.
+const FIELDS: [&str; 6] = ["NR", "RST", "ZONE", "STATE", "NAME", "CALL"];
+
+/// The fields that are numbers, which can be copied as numbers.
+const NUMBERS: [&str; 3] = ["{NR}", "{RST}", "{ZONE}"];
+
+/// How often a serial number is sent again, rather than the next one.
+const REPEAT_CHANCE: f64 = 0.1;
+
+/// Sends numbers the way contesters shorten them, T for 0 and N for 9,
+/// e.g. 5NN for 599.
+fn cut_number(number: &str) -> String {
+    number
+        .chars()
+        .map(|ch| match ch {
+            '0' => 'T',
+            '9' => 'N',
+            ch => ch,
+        })
+        .collect()
+}
+
+/// Reads a number that may be sent with cut numbers. Leading zeros
+/// don't matter, e.g. 7, 007 and TT7 are the same.
+fn read_number(word: &str, cut_numbers: bool) -> Option<u32> {
+    let digits: String = word
+        .chars()
+        .map(|ch| match ch.to_ascii_uppercase() {
+            'T' if cut_numbers => '0',
+            'N' if cut_numbers => '9',
+            ch => ch,
+        })
+        .collect();
+    match digits.bytes().all(|b| b.is_ascii_digit()) {
+        true => digits.parse().ok(),
+        false => None,
+    }
+}
+
+/// Contest exchanges, with a serial number counting up.
+pub struct Exchange {
+    template: String,
+    serial: u32,
+    cut_numbers: bool,
+}
+
+impl Exchange {
+    /// # Arguments
+    /// * `template` - The exchange, with fields like `{NR}` for the
+    ///   serial number.
+    /// * `cut_numbers` - Whether to send the numbers with T for 0 and N
+    ///   for 9.
+    pub fn new(template: &str, cut_numbers: bool, rng: &mut impl Rng) -> Result<Self, String> {
+        check_fields(template, |field| FIELDS.contains(&field))
+            .map_err(|e| format!("The exchange has {e}"))?;
+        Ok(Exchange {
+            template: template.trim().to_uppercase(),
+            // As if joining a contest that has been going for a while:
+            serial: rng.gen_range(1..=200),
+            cut_numbers,
+        })
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Fills in the next exchange. The serial number counts up, but
+    /// now and then the same one is sent again.
+    pub fn next(&mut self, rng: &mut impl Rng) -> String {
+        if !rng.gen_bool(REPEAT_CHANCE) {
+            self.serial += 1;
+        }
+        let serial = self.serial;
+        let exchange = fill_fields(&self.template, |field| match field {
+            "NR" => format!("{serial:03}"),
+            "RST" => "599".to_string(),
+            "ZONE" => rng.gen_range(1..=40).to_string(),
+            "STATE" => STATES[rng.gen_range(0..STATES.len())].to_string(),
+            "NAME" => NAMES[rng.gen_range(0..NAMES.len())].to_string(),
+            _ => random_callsign(&mut *rng),
+        });
+        exchange
+            .split_whitespace()
+            .map(
+                |word| match self.cut_numbers && word.bytes().all(|b| b.is_ascii_digit()) {
+                    true => cut_number(word),
+                    false => word.to_string(),
+                },
+            )
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Whether an exchange was copied. The numbers may be copied with or
+    /// without their leading zeros, and with cut numbers too when they
+    /// are sent that way, but not the other fields: TN is a state, not 9.
+    pub fn check(&self, sent: &str, copied: &str) -> bool {
+        let sent: Vec<&str> = sent.split_whitespace().collect();
+        let copied: Vec<&str> = copied.split_whitespace().collect();
+        let numbers = self
+            .template
+            .split_whitespace()
+            .map(|word| NUMBERS.contains(&word))
+            .chain(std::iter::repeat(false));
+        sent.len() == copied.len()
+            && sent
+                .iter()
+                .zip(copied)
+                .zip(numbers)
+                .all(|((sent, copied), number)| {
+                    sent.eq_ignore_ascii_case(copied)
+                        || number
+                            && read_number(sent, self.cut_numbers)
+                                .is_some_and(|n| read_number(copied, self.cut_numbers) == Some(n))
+                })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_exchange() {
+        let mut rng = rand::thread_rng();
+        let mut exchange = Exchange::new("nr {NR} {zone}", false, &mut rng).unwrap();
+        let mut last = 0;
+        for _ in 0..50 {
+            let sent = exchange.next(&mut rng);
+            let words: Vec<&str> = sent.split(' ').collect();
+            assert_eq!(words[0], "NR");
+            assert_eq!(words[1].len(), 3);
+            let serial: u32 = words[1].parse().unwrap();
+            assert!(serial == last || serial == last + 1 || last == 0, "{sent}");
+            last = serial;
+            assert!((1..=40).contains(&words[2].parse::<u32>().unwrap()));
+        }
+        assert!(Exchange::new("{RST} {GRID}", false, &mut rng).is_err());
+
+        let mut exchange = Exchange::new("{RST} {NR}", true, &mut rng).unwrap();
+        let sent = exchange.next(&mut rng);
+        assert!(sent.starts_with("5NN "), "{sent}");
+        assert!(!sent.contains(['0', '9']), "{sent}");
+    }
+
+    #[test]
+    fn test_check_exchange() {
+        let mut rng = rand::thread_rng();
+        let plain = Exchange::new(DEFAULT_EXCHANGE, false, &mut rng).unwrap();
+        assert!(plain.check("599 007", "599 7"));
+        assert!(plain.check("599 MA", "599 ma"));
+        assert!(!plain.check("599 007", "5NN 7"));
+        assert!(!plain.check("599 007", "599"));
+        assert!(!plain.check("599 123", "599 124"));
+
+        let cut = Exchange::new(DEFAULT_EXCHANGE, true, &mut rng).unwrap();
+        assert!(cut.check("5NN TT7", "599 7"));
+        assert!(cut.check("5NN TT7", "5NN TT7"));
+        assert!(cut.check("5NN 1T2", "5nn 102"));
+        assert!(!cut.check("5NN 1T2", "5NN 1T3"));
+
+        let state = Exchange::new("{STATE} {NR}", true, &mut rng).unwrap();
+        assert!(state.check("TN 1T2", "tn 102"));
+        assert!(!state.check("TN 1T2", "9 102"));
+        assert!(!state.check("TN 1T2", "TN 1T2 5NN"));
+    }
+}
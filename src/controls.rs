@@ -0,0 +1,179 @@
+use crate::detect::SignalPipeline;
+use crate::input::Keystroke;
+use crate::morse::{dot_length_to_wpm, wpm_to_dot_length};
+use crossterm::event::KeyCode;
+
+/// How much `+` and `-` raise and lower the threshold, as a factor.
+const THRESHOLD_STEP: f32 = 1.1;
+
+/// How far `[` and `]` move the tone, in Hz.
+pub const TONE_STEP: f32 = 10.0;
+
+/// A setting changed with a key while listening.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+    /// Multiplies the threshold.
+    Threshold(f32),
+    /// Moves the tone, in Hz.
+    Tone(f32),
+    /// Makes the decoder faster or slower, in WPM.
+    Speed(i32),
+    /// Forgets the message received so far, and clears the screen.
+    Clear,
+    /// Copies the messages of the session to the clipboard.
+    CopyTranscript,
+    /// Copies the last message completed to the clipboard.
+    CopyMessage,
+    /// Shows the messages of the session, to page back through them.
+    Scrollback,
+    /// Shows the messages of the session, to search them.
+    Search,
+    /// Mutes the audio of --monitor-audio, or turns it back on.
+    Mute,
+    Quit,
+}
+
+#[allow(dead_code)]
+impl Control {
+    /// The control of a key: `+`/`-` for the threshold, `[`/`]` (or the
+    /// left and right arrows) for the tone, `,`/`.` for the speed, `c`
+    /// to clear, `y`/`Y` to copy the session or the last message, `h`
+    /// (or Page Up) to page back through it, `/` to search it, `m` to
+    /// mute the audio, and `q` (or ESC, or Ctrl-C) to quit.
+    pub fn from_key(key: &Keystroke) -> Option<Control> {
+        if key.interrupt() {
+            return Some(Control::Quit);
+        }
+        match key.code {
+            KeyCode::Char('+') | KeyCode::Char('=') => Some(Control::Threshold(THRESHOLD_STEP)),
+            KeyCode::Char('-') => Some(Control::Threshold(1.0 / THRESHOLD_STEP)),
+            KeyCode::Char('[') | KeyCode::Left => Some(Control::Tone(-TONE_STEP)),
+            KeyCode::Char(']') | KeyCode::Right => Some(Control::Tone(TONE_STEP)),
+            KeyCode::Char(',') => Some(Control::Speed(-1)),
+            KeyCode::Char('.') => Some(Control::Speed(1)),
+            KeyCode::Char('c') => Some(Control::Clear),
+            KeyCode::Char('y') => Some(Control::CopyTranscript),
+            KeyCode::Char('Y') => Some(Control::CopyMessage),
+            KeyCode::Char('h') | KeyCode::PageUp => Some(Control::Scrollback),
+            KeyCode::Char('/') => Some(Control::Search),
+            KeyCode::Char('m') => Some(Control::Mute),
+            KeyCode::Char('q') | KeyCode::Esc => Some(Control::Quit),
+            _ => None,
+        }
+    }
+
+    /// Changes the settings of a station's pipeline, keeping the tone
+    /// between `lowest` and `highest` Hz. Clear, the copies, the
+    /// scrollback, Mute and Quit are left to the caller, and so is the
+    /// threshold while an adaptive one is still measuring the signal.
+    pub fn apply(&self, pipeline: &mut SignalPipeline, lowest: f32, highest: f32) {
+        match *self {
+            Control::Threshold(factor) => {
+                if let Some(threshold) = pipeline.threshold() {
+                    pipeline.set_threshold((threshold * factor).max(0.001));
+                }
+            }
+            Control::Tone(step) => {
+                pipeline.tune((pipeline.tone_freq() + step).clamp(lowest, highest));
+            }
+            Control::Speed(step) => {
+                let wpm = dot_length_to_wpm(pipeline.dot_duration()) as i32 + step;
+                pipeline.set_dot_duration(wpm_to_dot_length(wpm.max(1) as u32));
+            }
+            Control::Clear
+            | Control::CopyTranscript
+            | Control::CopyMessage
+            | Control::Scrollback
+            | Control::Search
+            | Control::Mute
+            | Control::Quit => {}
+        }
+    }
+}
+
+/// The settings of a station, as they would be given on the command
+/// line, e.g. `--tone 710 --threshold 0.330 --wpm 18`. `tone` is the
+/// name of the tone argument of the station.
+#[allow(dead_code)]
+pub fn arguments(pipeline: &SignalPipeline, tone: &str) -> String {
+    let threshold = match pipeline.threshold() {
+        Some(threshold) => format!("{threshold:.3}"),
+        None => "auto".to_string(),
+    };
+    format!(
+        "--{tone} {:.0} --threshold {threshold} --wpm {}",
+        pipeline.tone_freq(),
+        dot_length_to_wpm(pipeline.dot_duration())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::PipelineOptions;
+    use std::time::Instant;
+
+    fn key(code: KeyCode) -> Keystroke {
+        Keystroke {
+            code,
+            at: Instant::now(),
+            ctrl: false,
+            released: false,
+        }
+    }
+
+    #[test]
+    fn test_controls() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions {
+            dot_duration: wpm_to_dot_length(18),
+            ..Default::default()
+        });
+        assert_eq!(
+            arguments(&pipeline, "tone"),
+            "--tone 700 --threshold 0.300 --wpm 18"
+        );
+        for code in [']', ']', '+', '.', '.'] {
+            let control = Control::from_key(&key(KeyCode::Char(code))).unwrap();
+            control.apply(&mut pipeline, 150.0, 2000.0);
+        }
+        assert_eq!(
+            arguments(&pipeline, "tone-freq-left"),
+            "--tone-freq-left 720 --threshold 0.330 --wpm 20"
+        );
+        // The tone stays in range:
+        for _ in 0..100 {
+            Control::Tone(-TONE_STEP).apply(&mut pipeline, 150.0, 2000.0);
+        }
+        assert_eq!(pipeline.tone_freq(), 150.0);
+
+        let mut ctrl_c = key(KeyCode::Char('c'));
+        assert_eq!(Control::from_key(&ctrl_c), Some(Control::Clear));
+        ctrl_c.ctrl = true;
+        assert_eq!(Control::from_key(&ctrl_c), Some(Control::Quit));
+        assert_eq!(
+            Control::from_key(&key(KeyCode::Char('Y'))),
+            Some(Control::CopyMessage)
+        );
+        assert_eq!(
+            Control::from_key(&key(KeyCode::PageUp)),
+            Some(Control::Scrollback)
+        );
+        assert_eq!(
+            Control::from_key(&key(KeyCode::Char('m'))),
+            Some(Control::Mute)
+        );
+        assert_eq!(Control::from_key(&key(KeyCode::Char('x'))), None);
+    }
+
+    #[test]
+    fn test_adaptive_threshold_arguments() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions {
+            threshold: None,
+            adaptive_speed: true,
+            ..Default::default()
+        });
+        // Not known yet, so it can't be moved:
+        Control::Threshold(THRESHOLD_STEP).apply(&mut pipeline, 150.0, 2000.0);
+        assert!(arguments(&pipeline, "tone").contains("--threshold auto"));
+    }
+}
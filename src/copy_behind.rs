@@ -0,0 +1,403 @@
+use crate::band::Band;
+use crate::codec::{encode, Separators};
+use crate::export::{timestamp, wall_clock, Trial};
+use crate::input::{Flow, KeyReader};
+use crate::keyer::{Keyer, Timing, SAMPLE_RATE};
+use crate::morse::{dot_length_to_wpm, MorsePlayer};
+use crate::practice::split_characters;
+use crossterm::event::KeyCode;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::io::{stdout, Write};
+use std::time::{Duration, Instant};
+use tabled::settings::style::Style;
+use tabled::{Table, Tabled};
+use textwrap::wrap;
+
+/// The options of a copy behind drill.
+pub struct CopyBehind {
+    pub pool: Vec<String>,
+    /// How many characters behind the sound to copy.
+    pub lag: u32,
+    /// How many characters to send.
+    pub length: u32,
+    pub dot_duration: u32,
+    pub farnsworth: Option<u32>,
+    pub tone_freq: f32,
+    /// The noise, static crashes and fading to send it through.
+    pub band: Option<Band>,
+}
+
+/// A stream of characters that was sent, and what was typed to copy it.
+pub struct Stream {
+    pub sent: Vec<String>,
+    /// When the sound of each character was started and ended.
+    pub start: Instant,
+    pub ends: Vec<Instant>,
+    /// Each key typed, and when.
+    pub typed: Vec<(char, Instant)>,
+    pub wpm: u32,
+    pub tone_freq: f32,
+    /// Whether it was stopped before it was copied to the end.
+    pub stopped: bool,
+}
+
+/// How a character of the stream was copied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BehindCopy {
+    pub copy: Option<String>,
+    pub correct: bool,
+    /// When it was typed, if it was.
+    pub at: Option<Instant>,
+    /// How many characters later the sound was when it was typed.
+    pub lag: Option<u32>,
+}
+
+/// Which character of a stream is being played at some time: the one
+/// that has not ended yet, or after the stream, the one that would be at
+/// the same pace.
+fn playing(start: Instant, ends: &[Instant], at: Instant) -> usize {
+    let played = ends.iter().filter(|&&end| end <= at).count();
+    match ends.last() {
+        Some(&last) if played == ends.len() => {
+            let pace = (last - start) / ends.len() as u32;
+            played
+                + (at.saturating_duration_since(last).as_nanos() / pace.as_nanos().max(1)) as usize
+        }
+        _ => played,
+    }
+}
+
+/// Lines up the characters copied with those sent, as the longest
+/// common subsequence of them, so that a character missed doesn't make
+/// all of the ones after it wrong. A character copied wrong in place of
+/// one that was missed is paired with it.
+///
+/// # Returns
+/// The character copied for each one sent, by its index, if any.
+fn pair_copy(sent: &[String], copied: &[String]) -> Vec<Option<usize>> {
+    let mut common = vec![vec![0; copied.len() + 1]; sent.len() + 1];
+    for i in (0..sent.len()).rev() {
+        for j in (0..copied.len()).rev() {
+            common[i][j] = match sent[i] == copied[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+    let mut pairs = vec![None; sent.len()];
+    let (mut i, mut j) = (0, 0);
+    // The characters missed and copied since the last two that matched:
+    let (mut missed, mut extra) = (Vec::new(), Vec::new());
+    let flush =
+        |pairs: &mut Vec<Option<usize>>, missed: &mut Vec<usize>, extra: &mut Vec<usize>| {
+            for (i, j) in missed.drain(..).zip(extra.drain(..)) {
+                pairs[i] = Some(j);
+            }
+        };
+    while i < sent.len() || j < copied.len() {
+        if i < sent.len() && j < copied.len() && sent[i] == copied[j] {
+            flush(&mut pairs, &mut missed, &mut extra);
+            pairs[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if j == copied.len() || (i < sent.len() && common[i + 1][j] >= common[i][j + 1]) {
+            missed.push(i);
+            i += 1;
+        } else {
+            extra.push(j);
+            j += 1;
+        }
+    }
+    flush(&mut pairs, &mut missed, &mut extra);
+    pairs
+}
+
+impl Stream {
+    /// Scores the copy of each character of the stream.
+    pub fn score(&self) -> Vec<BehindCopy> {
+        let text: String = self.typed.iter().map(|&(ch, _)| ch).collect();
+        let copied: Vec<String> = split_characters(&text)
+            .into_iter()
+            .filter(|character| !character.trim().is_empty())
+            .collect();
+        // A character is typed with its last key, e.g. a prosign's `>`:
+        let mut keys = self.typed.iter().filter(|(ch, _)| !ch.is_whitespace());
+        let times: Vec<Instant> = copied
+            .iter()
+            .filter_map(|character| {
+                let key = keys.by_ref().take(character.chars().count()).last();
+                key.map(|&(_, at)| at)
+            })
+            .collect();
+        pair_copy(&self.sent, &copied)
+            .into_iter()
+            .enumerate()
+            .map(|(i, pair)| match pair {
+                Some(j) => BehindCopy {
+                    copy: Some(copied[j].clone()),
+                    correct: copied[j] == self.sent[i],
+                    at: Some(times[j]),
+                    lag: Some(playing(self.start, &self.ends, times[j]).saturating_sub(i) as u32),
+                },
+                None => BehindCopy {
+                    copy: None,
+                    correct: false,
+                    at: None,
+                    lag: None,
+                },
+            })
+            .collect()
+    }
+
+    /// The copy of each character, as the trials of the stream.
+    pub fn trials(&self) -> Vec<Trial> {
+        self.score()
+            .into_iter()
+            .zip(&self.sent)
+            .zip(&self.ends)
+            .map(|((copy, sent), &end)| {
+                let reaction = copy.at.map(|at| at.saturating_duration_since(end));
+                Trial {
+                    timestamp: timestamp(&wall_clock(end)),
+                    sent: sent.clone(),
+                    typed: copy.copy,
+                    correct: copy.correct,
+                    reaction,
+                    wpm: self.wpm,
+                    attempt: 1,
+                    total: reaction,
+                    tone: Some(self.tone_freq),
+                }
+            })
+            .collect()
+    }
+
+    /// The percentage of the characters copied.
+    pub fn accuracy(&self) -> f64 {
+        let correct = self.score().iter().filter(|copy| copy.correct).count();
+        correct as f64 / self.sent.len().max(1) as f64 * 100.0
+    }
+}
+
+/// Sends a stream of random characters of the pool at once, and reads
+/// the keys typed to copy it without showing them, until Enter is
+/// pressed after the end of the stream.
+pub fn run_copy_behind(drill: &CopyBehind, rng: &mut impl Rng) -> Stream {
+    let paragraph = format!(
+        "Copy behind drill of: {}\n\nA stream of {} characters will be played. Type each one {} behind the sound: {}. What you type is hidden until the end. Press Enter once the stream has ended and you have copied it, or Ctrl-C to stop early.\n",
+        drill.pool.join(" "),
+        drill.length,
+        drill.lag,
+        match drill.lag {
+            1 => "while the next one plays".to_string(),
+            lag => format!("while the one {lag} after it plays"),
+        }
+    );
+    for line in wrap(&paragraph, 70) {
+        println!("{}", line);
+    }
+
+    let sent: Vec<String> = (0..drill.length)
+        .filter_map(|_| drill.pool.choose(rng).cloned())
+        .collect();
+    let code = encode(&sent.concat(), &Separators::default());
+    let timing = Timing::new(drill.dot_duration, drill.farnsworth);
+    let player = MorsePlayer::new();
+    let keys = KeyReader::start();
+    // Without a pause key, the ESC key stops like Ctrl-C:
+    let mut flow = Flow::new(None);
+    println!("\nReady? Press Enter to begin.");
+    match keys.next() {
+        Some(key) if key.code == KeyCode::Enter => {}
+        _ => {
+            return Stream {
+                sent: Vec::new(),
+                start: Instant::now(),
+                ends: Vec::new(),
+                typed: Vec::new(),
+                wpm: dot_length_to_wpm(drill.dot_duration),
+                tone_freq: drill.tone_freq,
+                stopped: true,
+            }
+        }
+    }
+
+    let _raw = keys.raw_mode();
+    keys.clear();
+    print!("> ");
+    stdout().flush().unwrap();
+    let _sound = player.start_keyer(
+        Keyer::new(
+            &timing.key_events(&code),
+            SAMPLE_RATE,
+            drill.tone_freq,
+            0.5,
+            5.0,
+        )
+        .with_band(drill.band.clone()),
+    );
+    let start = Instant::now();
+    let ends: Vec<Instant> = timing
+        .character_ends(&code)
+        .into_iter()
+        .map(|end| start + Duration::from_millis(end.into()))
+        .collect();
+    let stream_end = *ends.last().unwrap_or(&start);
+    let mut typed = Vec::new();
+    let mut stopped = false;
+    let mut stdout = stdout();
+    while let Some(key) = keys.next() {
+        if flow.key(&key, true) {
+            stopped = true;
+            break;
+        }
+        match key.code {
+            KeyCode::Char(ch) => {
+                typed.push((ch, key.at));
+                print!("*");
+            }
+            KeyCode::Backspace if typed.pop().is_some() => print!("\u{8} \u{8}"),
+            KeyCode::Enter if key.at >= stream_end => break,
+            _ => {}
+        }
+        stdout.flush().unwrap();
+    }
+    print!("\r\n");
+    Stream {
+        sent,
+        start,
+        ends,
+        typed,
+        wpm: dot_length_to_wpm(drill.dot_duration),
+        tone_freq: drill.tone_freq,
+        stopped,
+    }
+}
+
+#[derive(Tabled)]
+struct LagRow {
+    lag: String,
+    characters: usize,
+    accuracy: String,
+}
+
+/// Prints the stream sent and what was copied, and the accuracy of the
+/// characters copied at each lag.
+///
+/// # Returns
+/// The table of the lags, unless nothing was sent.
+pub fn print_report(stream: &Stream, lag: u32) -> Option<String> {
+    if stream.sent.is_empty() {
+        return None;
+    }
+    if stream.stopped {
+        println!("\nDrill stopped.");
+    }
+    let copies = stream.score();
+    let copied: Vec<String> = copies
+        .iter()
+        .map(|copy| copy.copy.clone().unwrap_or("_".to_string()))
+        .collect();
+    println!("\nSent:   {}", stream.sent.join(" "));
+    println!("Copied: {}", copied.join(" "));
+    let correct = copies.iter().filter(|copy| copy.correct).count();
+    println!(
+        "\nCopied {correct}/{} characters ({:.0}%)",
+        stream.sent.len(),
+        stream.accuracy()
+    );
+
+    let max = copies.iter().filter_map(|copy| copy.lag).max().unwrap_or(0);
+    let rows: Vec<LagRow> = (0..=max)
+        .map(Some)
+        .chain([None])
+        .filter_map(|at_lag| {
+            let copies: Vec<&BehindCopy> =
+                copies.iter().filter(|copy| copy.lag == at_lag).collect();
+            let correct = copies.iter().filter(|copy| copy.correct).count();
+            (!copies.is_empty()).then(|| LagRow {
+                lag: match at_lag {
+                    Some(at_lag) if at_lag == lag => format!("{at_lag} (target)"),
+                    Some(at_lag) => at_lag.to_string(),
+                    None => "not copied".to_string(),
+                },
+                characters: copies.len(),
+                accuracy: format!("{:.0}%", correct as f64 / copies.len() as f64 * 100.0),
+            })
+        })
+        .collect();
+    let table = Table::new(rows).with(Style::rounded()).to_string();
+    println!("\nBy how many characters behind the sound they were typed:\n");
+    println!("{table}");
+    let sustained = copies
+        .iter()
+        .filter(|copy| copy.correct && copy.lag.is_some_and(|at_lag| at_lag >= lag))
+        .count();
+    println!(
+        "\nCopied at least {lag} behind: {:.0}% of the characters",
+        sustained as f64 / stream.sent.len() as f64 * 100.0
+    );
+    Some(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(characters: &[&str]) -> Vec<String> {
+        characters.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pair_copy() {
+        let sent = strings(&["K", "M", "R", "S", "U"]);
+        // M missed, and S copied as H:
+        assert_eq!(
+            pair_copy(&sent, &strings(&["K", "R", "H", "U"])),
+            [Some(0), None, Some(1), Some(2), Some(3)]
+        );
+        assert_eq!(pair_copy(&sent, &[]), [None; 5]);
+    }
+
+    #[test]
+    fn test_copy_behind() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        // A character every 200ms, all copied 2 behind, the last two
+        // after the stream ended:
+        let stream = Stream {
+            sent: strings(&["K", "<AR>", "M", "R", "S"]),
+            start,
+            ends: vec![at(200), at(400), at(600), at(800), at(1000)],
+            typed: "k<ar>mxs"
+                .chars()
+                .zip([500, 640, 660, 680, 700, 850, 1150, 1350])
+                .map(|(ch, ms)| (ch, at(ms)))
+                .collect(),
+            wpm: 20,
+            tone_freq: 600.0,
+            stopped: false,
+        };
+        let lags: Vec<(Option<u32>, bool)> = stream
+            .score()
+            .iter()
+            .map(|copy| (copy.lag, copy.correct))
+            .collect();
+        assert_eq!(
+            lags,
+            [
+                (Some(2), true),
+                (Some(2), true),
+                (Some(2), true),
+                (Some(2), false),
+                (Some(2), true)
+            ]
+        );
+        assert_eq!(stream.accuracy(), 80.0);
+        let trials = stream.trials();
+        assert_eq!(trials[1].sent, "<AR>");
+        assert_eq!(trials[1].reaction, Some(Duration::from_millis(300)));
+        assert_eq!(trials[3].typed.as_deref(), Some("X"));
+    }
+}
@@ -0,0 +1,151 @@
+/// The escape that starts each command, after which is its letter or
+/// digit and its value.
+const ESC: u8 = 0x1B;
+
+/// A datagram a client of cwdaemon sends, e.g. a logger like tlf or
+/// cqrlog.
+///
+/// Anything else than a command is text to send. A text ending with a
+/// caret is answered with the same text, once it was sent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Request {
+    /// Text to send, and whether to answer once it was sent (`^`).
+    Text { text: String, echo: bool },
+    /// `ESC 0`: back to the speed, tone and weight it started with.
+    Reset,
+    /// `ESC 2`: the speed, in WPM.
+    Speed(u32),
+    /// `ESC 3`: the tone, in Hz, or 0 to send silently.
+    Tone(u32),
+    /// `ESC 4`: stop sending at once, forgetting the text waiting.
+    Abort,
+    /// `ESC 5`: stop the daemon.
+    Exit,
+    /// `ESC 6`: not to be aborted until the text waiting was sent.
+    WordMode,
+    /// `ESC 7`: the weight, from -50 to 50, lengthening the marks
+    /// against the spaces.
+    Weight(i32),
+    /// `ESC a`: the transmitter keyed, or not.
+    Ptt(bool),
+    /// `ESC c`: a steady tone for some seconds, to tune with.
+    Tune(u32),
+    /// `ESC d`: how long the transmitter is keyed before the text is
+    /// sent, in milliseconds.
+    PttDelay(u32),
+    /// `ESC g`: the volume, in percent.
+    Volume(u32),
+    /// `ESC h`: answer with the text, once the text before it was sent.
+    Echo(String),
+    /// A command that does nothing here, e.g. choosing a device.
+    Ignored(char),
+    /// A command that doesn't parse, with why.
+    Invalid(String),
+}
+
+/// The value of a command within its range, as cwdaemon has it.
+fn number<T: std::str::FromStr + PartialOrd>(value: &str, min: T, max: T) -> Option<T> {
+    value
+        .trim()
+        .parse::<T>()
+        .ok()
+        .filter(|n| min <= *n && *n <= max)
+}
+
+impl Request {
+    pub fn parse(datagram: &[u8]) -> Request {
+        // Loggers often end what they send with a line feed or a NUL:
+        let datagram = String::from_utf8_lossy(datagram);
+        let datagram = datagram.trim_end_matches(['\0', '\r', '\n']);
+        let Some(command) = datagram.strip_prefix(ESC as char) else {
+            // The caret is the last character that is not a space:
+            let text = datagram.trim_end();
+            return match text.strip_suffix('^') {
+                Some(text) => Request::Text {
+                    text: text.to_string(),
+                    echo: true,
+                },
+                None => Request::Text {
+                    text: datagram.to_string(),
+                    echo: false,
+                },
+            };
+        };
+        let mut chars = command.chars();
+        let Some(letter) = chars.next() else {
+            return Request::Invalid("a command without a letter".to_string());
+        };
+        let value = chars.as_str();
+        let invalid = || Request::Invalid(format!("{letter}{value}"));
+        match letter {
+            '0' => Request::Reset,
+            '2' => number(value, 4, 60).map_or_else(invalid, Request::Speed),
+            '3' => match number(value, 0, 4000) {
+                Some(tone) if tone == 0 || tone >= 50 => Request::Tone(tone),
+                _ => invalid(),
+            },
+            '4' => Request::Abort,
+            '5' => Request::Exit,
+            '6' => Request::WordMode,
+            '7' => number(value, -50, 50).map_or_else(invalid, Request::Weight),
+            'a' => match value.trim() {
+                "0" => Request::Ptt(false),
+                "1" => Request::Ptt(true),
+                _ => invalid(),
+            },
+            'c' => number(value, 0, 10).map_or_else(invalid, Request::Tune),
+            'd' => number(value, 0, 50).map_or_else(invalid, Request::PttDelay),
+            'g' => number(value, 0, 100).map_or_else(invalid, Request::Volume),
+            'h' => Request::Echo(value.to_string()),
+            // The keying and sound devices, the band switch, SSB and the
+            // deprecated port:
+            '8' | '9' | 'b' | 'e' | 'f' | 'i' => Request::Ignored(letter),
+            _ => invalid(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_requests() {
+        // As tlf and cqrlog send them:
+        let traffic: [(&[u8], Request); 14] = [
+            (b"\x1b2" as &[u8], Request::Invalid("2".to_string())),
+            (b"\x1b228", Request::Speed(28)),
+            (b"\x1b3700", Request::Tone(700)),
+            (b"\x1b30", Request::Tone(0)),
+            (b"\x1b7-10", Request::Weight(-10)),
+            (b"\x1bd15", Request::PttDelay(15)),
+            (b"\x1ba1", Request::Ptt(true)),
+            (b"\x1b4", Request::Abort),
+            (b"\x1bc3", Request::Tune(3)),
+            (b"\x1bf/dev/null", Request::Ignored('f')),
+            (
+                b"CQ TEST DE N0CALL N0CALL TEST\0",
+                Request::Text {
+                    text: "CQ TEST DE N0CALL N0CALL TEST".to_string(),
+                    echo: false,
+                },
+            ),
+            (
+                b"5NN 001 ^\r\n",
+                Request::Text {
+                    text: "5NN 001 ".to_string(),
+                    echo: true,
+                },
+            ),
+            (b"\x1bh12345", Request::Echo("12345".to_string())),
+            (b"\x1b5", Request::Exit),
+        ];
+        for (datagram, request) in traffic {
+            assert_eq!(Request::parse(datagram), request, "{datagram:?}");
+        }
+        // Out of the ranges cwdaemon has:
+        assert!(matches!(Request::parse(b"\x1b261"), Request::Invalid(_)));
+        assert!(matches!(Request::parse(b"\x1b320"), Request::Invalid(_)));
+        assert!(matches!(Request::parse(b"\x1bz"), Request::Invalid(_)));
+    }
+}
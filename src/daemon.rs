@@ -0,0 +1,434 @@
+use crate::codec;
+use crate::cwdaemon::Request;
+use crate::keyer::Timing;
+use crate::morse::wpm_to_dot_length;
+use crate::prelude::*;
+use crate::sidetone::{Sidetone, SidetoneOptions};
+use crate::winkeyer::Winkeyer;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// How the text is sent, as the commands of the clients change it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub wpm: u32,
+    /// The tone of the sidetone, in Hz, or 0 to send silently.
+    pub tone: u32,
+    /// From -50 to 50, how much longer (or shorter) the marks are made,
+    /// in percent of a dot, the spaces after them being as much shorter.
+    pub weight: i32,
+    /// How long the transmitter is keyed before the text, in ms.
+    pub ptt_delay: u32,
+    /// In percent.
+    pub volume: u32,
+}
+
+/// Something for the keying thread to do.
+enum Job {
+    /// Send some morse code, e.g. `-.-. --.-` for the text `CQ`,
+    /// answering the client after if there is an answer.
+    Send {
+        text: String,
+        code: String,
+        settings: Settings,
+        answer: Option<(String, SocketAddr)>,
+    },
+    /// A steady tone, to tune with.
+    Tune(Duration, Settings),
+    /// Stop sending, and forget what is waiting.
+    Abort,
+}
+
+/// The jobs for a keying thread, with those that came while it was
+/// busy with another.
+struct Jobs {
+    receiver: Receiver<Job>,
+    waiting: VecDeque<Job>,
+}
+
+impl Jobs {
+    /// The next job, once there is one, or None once the daemon stopped.
+    fn next(&mut self) -> Option<Job> {
+        self.waiting
+            .pop_front()
+            .or_else(|| self.receiver.recv().ok())
+    }
+
+    /// Waits for some time, taking in the jobs that come meanwhile.
+    ///
+    /// # Returns
+    /// Whether the job was aborted, or the daemon stopped.
+    fn wait(&mut self, length: Duration) -> bool {
+        let until = Instant::now() + length;
+        loop {
+            match self
+                .receiver
+                .recv_timeout(until.saturating_duration_since(Instant::now()))
+            {
+                Ok(Job::Abort) => {
+                    self.waiting.clear();
+                    return true;
+                }
+                Ok(job) => self.waiting.push_back(job),
+                Err(RecvTimeoutError::Timeout) => return false,
+                Err(RecvTimeoutError::Disconnected) => return true,
+            }
+        }
+    }
+}
+
+/// The times the key is down and up, with the marks made longer (or
+/// shorter) by the weight, and the spaces after them as much shorter.
+fn weighted(events: Vec<(bool, u32)>, dot: u32, weight: i32) -> Vec<(bool, u32)> {
+    let change = dot as i32 * weight / 100;
+    let mut carried = 0;
+    events
+        .into_iter()
+        .map(|(down, ms)| {
+            let ms = ms as i32;
+            match down {
+                true => {
+                    let weighted = (ms + change).max(1);
+                    carried = weighted - ms;
+                    (true, weighted as u32)
+                }
+                false => (false, (ms - std::mem::take(&mut carried)).max(0) as u32),
+            }
+        })
+        .collect()
+}
+
+/// Keys the sidetone for the jobs as they come, one element at a time,
+/// so that an abort stops it within the element being sent.
+struct Keying {
+    jobs: Jobs,
+    socket: UdpSocket,
+    options: SidetoneOptions,
+    sidetone: Option<Sidetone>,
+}
+
+impl Keying {
+    /// The sidetone for some settings, started again when its tone or
+    /// volume changed.
+    fn sidetone(&mut self, settings: &Settings) -> Option<&Sidetone> {
+        if settings.tone == 0 {
+            return None;
+        }
+        let options = SidetoneOptions {
+            freq: settings.tone as f32,
+            volume: settings.volume as f32 / 100.0,
+            ..self.options.clone()
+        };
+        if self.sidetone.is_none() || options != self.options {
+            self.sidetone = None;
+            match Sidetone::start(&options) {
+                Ok(sidetone) => self.sidetone = Some(sidetone),
+                Err(e) => error!("{e}"),
+            }
+            self.options = options;
+        }
+        self.sidetone.as_ref()
+    }
+
+    /// Sends the marks, until the end or an abort.
+    fn key(&mut self, events: &[(bool, u32)], settings: &Settings) -> bool {
+        for &(down, ms) in events {
+            let start = Instant::now();
+            let length = Duration::from_millis(ms as u64);
+            if down {
+                if let Some(sidetone) = self.sidetone(settings) {
+                    sidetone.mark(start, length);
+                }
+            }
+            if self.jobs.wait(length) {
+                // Cut the mark short:
+                if let Some(sidetone) = &self.sidetone {
+                    sidetone.cut(Instant::now());
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    fn run(mut self) {
+        loop {
+            let Some(job) = self.jobs.next() else {
+                return;
+            };
+            match job {
+                Job::Send {
+                    code,
+                    settings,
+                    answer,
+                    ..
+                } => {
+                    let dot = wpm_to_dot_length(settings.wpm);
+                    let events = weighted(
+                        Timing::new(dot, None).key_events(&code),
+                        dot,
+                        settings.weight,
+                    );
+                    if settings.ptt_delay > 0
+                        && !events.is_empty()
+                        && self
+                            .jobs
+                            .wait(Duration::from_millis(settings.ptt_delay as u64))
+                    {
+                        continue;
+                    }
+                    if self.key(&events, &settings) {
+                        if let Some((answer, client)) = answer {
+                            let _ = self.socket.send_to(answer.as_bytes(), client);
+                        }
+                    }
+                }
+                Job::Tune(length, settings) => {
+                    let ms = length.as_millis() as u32;
+                    self.key(&[(true, ms)], &settings);
+                }
+                Job::Abort => {}
+            }
+        }
+    }
+}
+
+/// Sends the text of the jobs with a Winkeyer as they come, showing
+/// each character as the keyer sends it. The keyer makes its own
+/// sidetone, the tone and volume of the settings being left to it.
+struct WinkeyerKeying {
+    jobs: Jobs,
+    socket: UdpSocket,
+    winkeyer: Winkeyer<File>,
+    /// The settings last given to the keyer.
+    settings: Option<Settings>,
+}
+
+impl WinkeyerKeying {
+    /// Gives the keyer the settings, if they changed.
+    fn apply(&mut self, settings: &Settings) -> io::Result<()> {
+        if self.settings.as_ref() == Some(settings) {
+            return Ok(());
+        }
+        self.winkeyer.set_speed(settings.wpm)?;
+        self.winkeyer.set_weight(settings.weight)?;
+        self.winkeyer.set_ptt(
+            Duration::from_millis(settings.ptt_delay as u64),
+            Duration::ZERO,
+        )?;
+        self.settings = Some(settings.clone());
+        Ok(())
+    }
+
+    /// Does a job.
+    fn job(&mut self, job: Job) -> io::Result<()> {
+        match job {
+            Job::Send {
+                text,
+                settings,
+                answer,
+                ..
+            } => {
+                self.apply(&settings)?;
+                let jobs = &mut self.jobs;
+                let sent = text.is_empty()
+                    || self.winkeyer.send(
+                        &text,
+                        |c| {
+                            print!("{c}");
+                            let _ = io::stdout().flush();
+                        },
+                        || jobs.wait(Duration::ZERO),
+                    )?;
+                if !text.is_empty() {
+                    println!();
+                }
+                if let Some((answer, client)) = answer.filter(|_| sent) {
+                    let _ = self.socket.send_to(answer.as_bytes(), client);
+                }
+            }
+            Job::Tune(length, settings) => {
+                self.apply(&settings)?;
+                self.winkeyer.key_down(true)?;
+                self.jobs.wait(length);
+                self.winkeyer.key_down(false)?;
+            }
+            Job::Abort => self.winkeyer.clear()?,
+        }
+        Ok(())
+    }
+
+    fn run(mut self) {
+        while let Some(job) = self.jobs.next() {
+            if let Err(e) = self.job(job) {
+                error!("Could not key the Winkeyer: {e}");
+            }
+        }
+    }
+}
+
+/// Keys the sidetone (or a Winkeyer, if given one) as cwdaemon does,
+/// for the contest loggers that send their text to it over UDP, with
+/// `cwdaemon`. See `cwdaemon::Request` for the commands.
+pub fn serve(
+    port: u16,
+    settings: Settings,
+    options: SidetoneOptions,
+    winkeyer: Option<Winkeyer<File>>,
+) -> io::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    info!("Listening for cwdaemon clients on UDP port {port}");
+    let (jobs, receiver) = channel::<Job>();
+    let receiver = Jobs {
+        receiver,
+        waiting: VecDeque::new(),
+    };
+    let socket_clone = socket.try_clone()?;
+    match winkeyer {
+        Some(winkeyer) => {
+            let keying = WinkeyerKeying {
+                jobs: receiver,
+                socket: socket_clone,
+                winkeyer,
+                settings: None,
+            };
+            std::thread::spawn(move || keying.run());
+        }
+        None => {
+            let keying = Keying {
+                jobs: receiver,
+                socket: socket_clone,
+                options,
+                sidetone: None,
+            };
+            std::thread::spawn(move || keying.run());
+        }
+    }
+    let defaults = settings.clone();
+    let mut settings = settings;
+    let mut datagram = [0; 1024];
+    loop {
+        let (len, client) = socket.recv_from(&mut datagram)?;
+        let request = Request::parse(&datagram[..len]);
+        debug!("{client}: {request:?}");
+        if handle(request, client, &mut settings, &defaults, &jobs) {
+            return Ok(());
+        }
+    }
+}
+
+/// Does what a client asks.
+///
+/// # Returns
+/// Whether to stop the daemon.
+fn handle(
+    request: Request,
+    client: SocketAddr,
+    settings: &mut Settings,
+    defaults: &Settings,
+    jobs: &Sender<Job>,
+) -> bool {
+    let send = |text: String, answer: Option<String>, settings: &Settings| {
+        let _ = jobs.send(Job::Send {
+            code: codec::encode(&text, &codec::Separators::default()),
+            text,
+            settings: settings.clone(),
+            answer: answer.map(|answer| (answer, client)),
+        });
+    };
+    match request {
+        Request::Text { text, echo } => {
+            let answer = echo.then(|| format!("{text}\r\n"));
+            send(text.to_uppercase(), answer, settings);
+        }
+        Request::Echo(text) => send(String::new(), Some(format!("h{text}\r\n")), settings),
+        Request::Reset => *settings = defaults.clone(),
+        Request::Speed(wpm) => settings.wpm = wpm,
+        Request::Tone(tone) => settings.tone = tone,
+        Request::Weight(weight) => settings.weight = weight,
+        Request::PttDelay(delay) => settings.ptt_delay = delay,
+        Request::Volume(volume) => settings.volume = volume,
+        Request::Tune(seconds) => {
+            let _ = jobs.send(Job::Tune(
+                Duration::from_secs(seconds as u64),
+                settings.clone(),
+            ));
+        }
+        Request::Abort => {
+            let _ = jobs.send(Job::Abort);
+        }
+        Request::Exit => return true,
+        Request::WordMode | Request::Ptt(_) | Request::Ignored(_) => {}
+        Request::Invalid(command) => warn!("Invalid cwdaemon command from {client}: {command}"),
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted() {
+        let events = Timing::new(60, None).key_events("-. .");
+        assert_eq!(weighted(events.clone(), 60, 0), events);
+        assert_eq!(
+            weighted(events, 60, 50),
+            vec![
+                (true, 210),
+                (false, 30),
+                (true, 90),
+                (false, 150),
+                (true, 90)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle() {
+        let (jobs, receiver) = channel();
+        let client: SocketAddr = "127.0.0.1:6790".parse().unwrap();
+        let defaults = Settings {
+            wpm: 20,
+            tone: 700,
+            weight: 0,
+            ptt_delay: 0,
+            volume: 50,
+        };
+        let mut settings = defaults.clone();
+        let requests = [
+            b"\x1b228" as &[u8],
+            b"\x1b3600",
+            b"cq tnx^",
+            b"\x1b0",
+            b"\x1b5",
+        ];
+        let stopped: Vec<bool> = requests
+            .iter()
+            .map(|datagram| {
+                let request = Request::parse(datagram);
+                handle(request, client, &mut settings, &defaults, &jobs)
+            })
+            .collect();
+        assert_eq!(stopped, [false, false, false, false, true]);
+        assert_eq!(settings, defaults);
+        match receiver.try_recv() {
+            Ok(Job::Send {
+                text,
+                code,
+                settings,
+                answer,
+            }) => {
+                assert_eq!(text, "CQ TNX");
+                assert_eq!(code, "-.-. --.- / - -. -..-");
+                assert_eq!((settings.wpm, settings.tone), (28, 600));
+                assert_eq!(answer, Some(("cq tnx\r\n".to_string(), client)));
+            }
+            _ => panic!("nothing to send"),
+        }
+    }
+}
@@ -0,0 +1,1332 @@
+use crate::autotone::ToneTracker;
+use crate::filter::{BandpassFilter, Detector};
+use crate::gate::ToneGate;
+use crate::keyer::Timing;
+use crate::morse::{prosign, text_to_morse, Charset, WABUN_END, WABUN_START};
+use crate::speed::SpeedTracker;
+use crate::threshold::ToneThreshold;
+use log::debug;
+use morse_codec::decoder::{Decoder, MorseDecoder};
+use regex::Regex;
+use std::str::FromStr;
+
+/// Which of the captured audio channels is decoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    /// Average all of the channels into one.
+    Mix,
+    /// Only use one channel (0 is the first, or left, channel).
+    Index(usize),
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mix" => Ok(Channel::Mix),
+            n => n
+                .parse::<usize>()
+                .map(Channel::Index)
+                .map_err(|_| format!("Channel must be a channel number or 'mix': {s}")),
+        }
+    }
+}
+
+impl Channel {
+    /// Picks the sample to decode from one frame of interleaved samples.
+    pub fn sample(&self, frame: &[f32]) -> f32 {
+        match self {
+            Channel::Mix if frame.is_empty() => 0.0,
+            Channel::Mix => frame.iter().sum::<f32>() / frame.len() as f32,
+            Channel::Index(index) => frame.get(*index).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Checks that the channel exists in audio with `channels` channels.
+    pub fn check(&self, channels: usize) -> Result<(), String> {
+        match self {
+            Channel::Index(index) if *index >= channels => Err(format!(
+                "Channel {index} does not exist, the audio has {channels} channel(s) (numbered from 0)"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// One signal to decode from the captured audio.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Station {
+    /// Shown with the decoded text when there is more than one station.
+    pub label: Option<String>,
+    pub channel: Channel,
+    pub tone_freq: f32,
+    /// If given, search this range (in Hz) for the tone instead.
+    pub tone_range: Option<(f32, f32)>,
+}
+
+/// How the decoded text is written out, and split into words.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextOptions {
+    /// Show the dots and dashes heard for each character, e.g. `C[-.-.]`.
+    pub show_symbols: bool,
+    /// Write the prosigns, e.g. `<AR>`, rather than the letters they share a code with.
+    pub prosigns: bool,
+    /// Written for a character that couldn't be decoded, or None to drop it.
+    pub error_char: Option<char>,
+    /// Runs of more than this many undecodable characters are dropped
+    /// as noise (or never, when 0).
+    pub max_errors: usize,
+    /// The alphabet the dots and dashes are read in.
+    pub charset: Charset,
+    /// The overall speed in WPM that the sender spreads the characters
+    /// out to with Farnsworth timing, so that its long letter gaps don't
+    /// end words.
+    pub farnsworth: Option<u32>,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            show_symbols: false,
+            prosigns: true,
+            error_char: Some('?'),
+            max_errors: 4,
+            charset: Charset::Latin,
+            farnsworth: None,
+        }
+    }
+}
+
+/// One character of the decoded message, ready to be written out.
+struct DecodedChar {
+    text: String,
+    // The dots and dashes that were heard:
+    heard: String,
+    space: bool,
+    error: bool,
+}
+
+/// Something that happened while decoding, for the frontend to show.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeEvent {
+    /// A tone (or the gap before it) ended after `duration` milliseconds.
+    Signal { is_high: bool, duration: u32 },
+    /// The message received so far has changed.
+    Partial(String),
+    /// The message ended, after a long enough silence.
+    Message(String),
+    /// The tone frequency was found (or moved) with `--tone auto`.
+    ToneLocked(f32),
+    /// The adaptive threshold moved with `--threshold auto`.
+    Threshold(f32),
+    /// The dot duration (in milliseconds) changed with `--wpm auto`.
+    Speed(u32),
+    /// The measured dot duration of the sender changed, in milliseconds.
+    DotEstimate(u32),
+    /// What the threshold made of one window of samples, with
+    /// `with_levels` only.
+    Level(ToneLevel),
+}
+
+/// The detector level of a window of samples, and whether the
+/// threshold took it for a tone, before any debouncing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneLevel {
+    pub level: f32,
+    /// The threshold the level was compared with, if it is known yet.
+    pub threshold: Option<f32>,
+    /// The measured peak of the tone and noise floor, with an adaptive
+    /// threshold.
+    pub peak: Option<f32>,
+    pub noise: Option<f32>,
+    pub tone: bool,
+    /// How far the strongest tone in the passband was from the tone
+    /// frequency in Hz, when one was last heard.
+    pub offset: Option<f32>,
+}
+
+/// How often the frequency of the tone in the passband is measured, in
+/// seconds, for `ToneLevel::offset`.
+const TUNING_SECS: f32 = 0.25;
+
+/// The length of each window of samples that is analyzed, in milliseconds.
+pub const WINDOW_MS: u32 = 10;
+
+/// How far the estimated dot duration may move from the decoder's
+/// before the decoder is given the new speed, as a fraction.
+const SPEED_CHANGE: f32 = 0.2;
+
+/// Gaps at least this many dots long end a word. Words are 7 dots
+/// apart, and letters are often spaced out to more than their 3 dots.
+pub const WORD_GAP_DOTS: u32 = 6;
+
+/// The decoder only ends a word after a gap of this many dots.
+pub const DECODER_WORD_DOTS: u32 = 8;
+
+/// The longest that a letter gap is given to the decoder, in dots, so
+/// that it doesn't end a word after the long letter gaps of Farnsworth
+/// timing.
+const DECODER_LETTER_DOTS: u32 = 5;
+
+/// Turns captured audio samples into decoded morse code.
+///
+/// The samples go through the bandpass filter, the tone detector, the
+/// threshold and the debounce gate before the edges of each tone are
+/// given to the decoder. The samples are analyzed in fixed windows of
+/// `WINDOW_MS`, whatever size of blocks they arrive in, and time is
+/// measured by counting samples, so the same audio always decodes the
+/// same way.
+pub struct SignalPipeline {
+    tone_freq: f32,
+    tone_range: Option<(f32, f32)>,
+    bandwidth: f32,
+    detector: Detector,
+    dot_duration: u32,
+    timeout_duration: u32,
+    speed_tracker: SpeedTracker,
+    // The dot duration last sent as a DotEstimate event:
+    dot_estimate: Option<u32>,
+    adaptive_speed: bool,
+    text_options: TextOptions,
+    // The dots and dashes of the character being received:
+    elements: String,
+    // The dots and dashes heard for each character of the message
+    // (empty for the spaces between words):
+    symbols: Vec<String>,
+    sample_rate: u32,
+    // Built once the sample rate is known:
+    filter: Option<BandpassFilter>,
+    tone_tracker: Option<ToneTracker>,
+    tone_locked: bool,
+    tone_threshold: ToneThreshold,
+    gate: ToneGate,
+    decoder: MorseDecoder<9999>,
+    // The index of the next sample of the stream to be analyzed:
+    position: u64,
+    // The samples of the current analysis window, filtered in place:
+    window: Vec<f32>,
+    window_size: usize,
+    // Whether the decoder was given any tones since the last message:
+    pending: bool,
+    // The message last sent as a Partial event:
+    partial: String,
+    // Whether to send a Level event for every window:
+    levels: bool,
+    // Measures the offset of the tone when sending Level events:
+    tuner: Option<ToneTracker>,
+    offset: Option<f32>,
+    // The filtered samples, with whether the gate let a tone through,
+    // kept to be heard with --monitor-audio:
+    tap: Option<Vec<(f32, bool)>>,
+    whitespace_regex: Regex,
+}
+
+/// The settings of a SignalPipeline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineOptions {
+    /// The tone frequency in Hz.
+    pub tone_freq: f32,
+    /// If given, search this range (in Hz) for the tone instead.
+    pub tone_range: Option<(f32, f32)>,
+    /// The width of the bandpass filter in Hz.
+    pub bandwidth: f32,
+    /// The detector level of a tone, or None to adapt to the signal.
+    pub threshold: Option<f32>,
+    /// Ignore tones and gaps shorter than this, in milliseconds.
+    pub debounce_duration: u32,
+    /// How the level of the filtered signal is measured.
+    pub detector: Detector,
+    /// The expected dot duration in milliseconds.
+    pub dot_duration: u32,
+    /// End the message after a silence this long, in milliseconds.
+    pub timeout_duration: u32,
+    /// Follow the sender's speed, starting from `dot_duration`.
+    pub adaptive_speed: bool,
+    /// How the decoded text is written out.
+    pub text_options: TextOptions,
+}
+
+impl Default for PipelineOptions {
+    /// A 700 Hz tone at 20 WPM, over a fixed threshold, with the filter
+    /// and the timing that listen has at that speed.
+    fn default() -> Self {
+        Self {
+            tone_freq: 700.0,
+            tone_range: None,
+            bandwidth: 200.0,
+            threshold: Some(0.3),
+            debounce_duration: 15,
+            detector: Detector::Peak,
+            dot_duration: 60,
+            timeout_duration: 1200,
+            adaptive_speed: false,
+            text_options: TextOptions::default(),
+        }
+    }
+}
+
+impl SignalPipeline {
+    /// Creates a new SignalPipeline.
+    pub fn new(options: PipelineOptions) -> Self {
+        let PipelineOptions {
+            tone_freq,
+            tone_range,
+            bandwidth,
+            threshold,
+            debounce_duration,
+            detector,
+            dot_duration,
+            timeout_duration,
+            adaptive_speed,
+            text_options,
+        } = options;
+        Self {
+            tone_freq,
+            tone_range,
+            bandwidth,
+            detector,
+            dot_duration,
+            timeout_duration,
+            speed_tracker: SpeedTracker::new(),
+            dot_estimate: None,
+            adaptive_speed,
+            text_options,
+            elements: String::new(),
+            symbols: Vec::new(),
+            sample_rate: 0,
+            filter: None,
+            tone_tracker: None,
+            tone_locked: false,
+            tone_threshold: ToneThreshold::new(threshold),
+            gate: ToneGate::new(debounce_duration),
+            decoder: Decoder::<9999>::new()
+                .with_reference_short_ms(dot_duration as u16)
+                .build(),
+            position: 0,
+            window: Vec::new(),
+            window_size: 0,
+            pending: false,
+            partial: String::new(),
+            levels: false,
+            tuner: None,
+            offset: None,
+            tap: None,
+            whitespace_regex: Regex::new(r"\s+").unwrap(),
+        }
+    }
+
+    /// Also sends a Level event for every window of samples analyzed.
+    #[allow(dead_code)]
+    pub fn with_levels(mut self, levels: bool) -> Self {
+        self.levels = levels;
+        self
+    }
+
+    /// Also keeps the samples as they come out of the filter, to be
+    /// taken with `take_tap`.
+    #[allow(dead_code)]
+    pub fn with_tap(mut self, tap: bool) -> Self {
+        self.tap = tap.then(Vec::new);
+        self
+    }
+
+    /// The filtered samples since the last time, each with whether a
+    /// tone was on at the gate then.
+    #[allow(dead_code)]
+    pub fn take_tap(&mut self) -> Vec<(f32, bool)> {
+        self.tap.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// The frequency of the tone being decoded, in Hz.
+    #[allow(dead_code)]
+    pub fn tone_freq(&self) -> f32 {
+        self.tone_freq
+    }
+
+    /// Moves the filter to another tone frequency, e.g. to tune onto a
+    /// signal by hand. With `tone_range`, the tone is then only searched
+    /// for again once it drifts out of the passband.
+    #[allow(dead_code)]
+    pub fn tune(&mut self, tone_freq: f32) {
+        self.tone_freq = tone_freq;
+        self.tone_locked = true;
+        if self.sample_rate != 0 {
+            self.rebuild_filter();
+            self.rebuild_tuner();
+        }
+    }
+
+    /// The threshold in use, if it is known yet.
+    #[allow(dead_code)]
+    pub fn threshold(&self) -> Option<f32> {
+        self.tone_threshold.value()
+    }
+
+    /// Replaces the threshold with a fixed one.
+    #[allow(dead_code)]
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.tone_threshold = ToneThreshold::new(Some(threshold));
+    }
+
+    /// The dot duration the decoder expects, in milliseconds.
+    #[allow(dead_code)]
+    pub fn dot_duration(&self) -> u32 {
+        self.dot_duration
+    }
+
+    /// Gives the decoder another speed, keeping the message so far.
+    #[allow(dead_code)]
+    pub fn set_dot_duration(&mut self, dot_duration: u32) {
+        let dot_duration = dot_duration.max(1);
+        self.decoder = Decoder::<9999>::new()
+            .with_message(self.decoder.message.as_str(), true)
+            .with_reference_short_ms(dot_duration.min(u16::MAX as u32) as u16)
+            .build();
+        // Keep the timeout the same number of dots:
+        self.timeout_duration = self.timeout_duration * dot_duration / self.dot_duration;
+        self.dot_duration = dot_duration;
+    }
+
+    /// Forgets the message received so far.
+    #[allow(dead_code)]
+    pub fn clear(&mut self) {
+        self.decoder = Decoder::<9999>::new()
+            .with_reference_short_ms(self.dot_duration.min(u16::MAX as u32) as u16)
+            .build();
+        self.gate.reset(self.now());
+        self.elements.clear();
+        self.symbols.clear();
+        self.pending = false;
+        self.partial.clear();
+    }
+
+    /// Processes the next block of mono samples.
+    ///
+    /// # Arguments
+    /// * `samples` - The samples, normalized to -1.0..1.0.
+    /// * `rate` - The sample rate in Hz.
+    ///
+    /// # Returns
+    /// The events that happened during the block.
+    pub fn push_samples(&mut self, mut samples: &[f32], rate: u32) -> Vec<DecodeEvent> {
+        let mut events = Vec::new();
+        if samples.is_empty() || rate == 0 {
+            return events;
+        }
+        if rate != self.sample_rate {
+            if self.sample_rate != 0 {
+                // Keep the time the same at the new rate:
+                self.position = self.position * rate as u64 / self.sample_rate as u64;
+            }
+            self.sample_rate = rate;
+            self.window.clear();
+            self.window_size = (rate * WINDOW_MS / 1000).max(1) as usize;
+            self.rebuild_filter();
+            self.rebuild_tuner();
+            self.tone_tracker = self
+                .tone_range
+                .map(|(min_freq, max_freq)| ToneTracker::new(min_freq, max_freq, 10.0, 2.0, rate));
+        }
+        while !samples.is_empty() {
+            let count = (self.window_size - self.window.len()).min(samples.len());
+            self.window.extend_from_slice(&samples[..count]);
+            samples = &samples[count..];
+            if self.window.len() == self.window_size {
+                self.analyze_window(&mut events);
+                self.window.clear();
+            }
+        }
+        events
+    }
+
+    /// Gives the decoder a tone or a gap that wasn't heard in the
+    /// samples, e.g. an element of a packet of MOPP, of `duration`
+    /// milliseconds.
+    ///
+    /// # Returns
+    /// The events it made.
+    #[allow(dead_code)]
+    pub fn push_signal(&mut self, is_high: bool, duration: u32) -> Vec<DecodeEvent> {
+        let mut events = Vec::new();
+        self.signal(is_high, duration, &mut events);
+        events
+    }
+
+    /// Gives the tone detector the level of a window of `window` samples
+    /// measured elsewhere, e.g. by the bank of detectors of `--scan`, in
+    /// place of the samples. The pipeline then needs no filter of its own.
+    ///
+    /// # Returns
+    /// The events of the window.
+    #[allow(dead_code)]
+    pub fn push_level(&mut self, level: f32, window: usize, rate: u32) -> Vec<DecodeEvent> {
+        let mut events = Vec::new();
+        if rate == 0 {
+            return events;
+        }
+        if rate != self.sample_rate {
+            if self.sample_rate != 0 {
+                self.position = self.position * rate as u64 / self.sample_rate as u64;
+            }
+            self.sample_rate = rate;
+        }
+        self.position += window as u64;
+        self.detect(level, window, &mut events);
+        events
+    }
+
+    /// Finishes decoding at the end of the input, as if it were
+    /// followed by a long silence.
+    pub fn finish(&mut self) -> Vec<DecodeEvent> {
+        let mut events = Vec::new();
+        let now = self.now();
+        if let Some(duration) = self.gate.end_tone(now) {
+            self.signal(true, duration, &mut events);
+        }
+        let decoded = self.decoder.message.len();
+        self.decoder
+            .signal_event(self.decoder_duration(false, self.timeout_duration), false);
+        self.record_symbols(decoded);
+        self.gate.reset(now);
+        self.end_message(&mut events);
+        events
+    }
+
+    /// Milliseconds since the start of the stream, by the samples analyzed.
+    fn now(&self) -> u64 {
+        match self.sample_rate {
+            0 => 0,
+            rate => self.position * 1000 / rate as u64,
+        }
+    }
+
+    /// Detects the tone in one full window of samples.
+    fn analyze_window(&mut self, events: &mut Vec<DecodeEvent>) {
+        let rate = self.sample_rate;
+        self.position += self.window.len() as u64;
+
+        // Follow the tone frequency if it is being searched for:
+        if let Some(freq) = self
+            .tone_tracker
+            .as_mut()
+            .and_then(|t| t.push(&self.window))
+        {
+            // Re-center only once the tone drifts out of the passband:
+            if !self.tone_locked || (freq - self.tone_freq).abs() > self.bandwidth / 2.0 {
+                self.tone_locked = true;
+                self.tone_freq = freq;
+                self.rebuild_filter();
+                self.rebuild_tuner();
+                events.push(DecodeEvent::ToneLocked(freq));
+            }
+        }
+        if let Some(freq) = self.tuner.as_mut().and_then(|t| t.push(&self.window)) {
+            self.offset = Some(freq - self.tone_freq);
+        }
+
+        let filter = self.filter.as_mut().expect("expected filter");
+        filter.apply_in_place(&mut self.window);
+        let level = self
+            .detector
+            .level(&self.window, self.tone_freq.into(), rate as f64);
+        self.detect(level, self.window.len(), events);
+        if let Some(tap) = self.tap.as_mut() {
+            let on = self.gate.is_on();
+            tap.extend(self.window.iter().map(|&sample| (sample, on)));
+        }
+    }
+
+    /// Decides whether the tone is heard in a window of samples, from
+    /// its level, and gives the edges of the tones to the decoder.
+    fn detect(&mut self, level: f32, window: usize, events: &mut Vec<DecodeEvent>) {
+        let now = self.now();
+        let threshold = self.tone_threshold.current();
+        let tone_detected = self
+            .tone_threshold
+            .detect(level, window as f32 / self.sample_rate as f32);
+        if self.levels {
+            events.push(DecodeEvent::Level(ToneLevel {
+                level,
+                threshold,
+                peak: self.tone_threshold.peak(),
+                noise: self.tone_threshold.noise(),
+                tone: tone_detected,
+                offset: self.offset,
+            }));
+        }
+        if let Some(chosen) = self.tone_threshold.changed() {
+            events.push(DecodeEvent::Threshold(chosen));
+        }
+
+        // Detect message characters:
+        if let Some((is_high, duration)) = self.gate.update(tone_detected, now) {
+            self.signal(is_high, duration, events);
+        }
+
+        // Detect message end:
+        if self.pending && self.gate.since_change(now) > self.timeout_duration {
+            self.gate.reset(now);
+            self.end_message(events);
+        }
+    }
+
+    /// Rebuilds the tuner around the tone frequency, once it is known
+    /// or has moved.
+    fn rebuild_tuner(&mut self) {
+        let half = self.bandwidth / 2.0;
+        self.offset = None;
+        self.tuner = self.levels.then(|| {
+            ToneTracker::new(
+                self.tone_freq - half,
+                self.tone_freq + half,
+                5.0,
+                TUNING_SECS,
+                self.sample_rate,
+            )
+        });
+    }
+
+    fn rebuild_filter(&mut self) {
+        self.filter = Some(
+            BandpassFilter::new(
+                5,
+                self.tone_freq.into(),
+                self.bandwidth.into(),
+                self.sample_rate as f64,
+            )
+            .expect("expected filter"),
+        );
+    }
+
+    fn message(&self) -> String {
+        self.render().0
+    }
+
+    /// Writes out the decoded message.
+    ///
+    /// # Returns
+    /// The text, and the dots and dashes of any runs of undecodable
+    /// characters that were suppressed as noise.
+    fn render(&self) -> (String, Vec<String>) {
+        let options = &self.text_options;
+        // Wabun is switched on with DO and back off with SN:
+        let mut wabun = options.charset == Charset::Wabun;
+        let mut chars = Vec::new();
+        for (ch, heard) in self.decoder.message.as_str().chars().zip(&self.symbols) {
+            let space = ch.is_whitespace();
+            // Not every character the decoder knows is in our table:
+            let known = Some(text_to_morse(&ch.to_string())).filter(|c| !c.is_empty());
+            // The decoder shows characters it doesn't know as '?':
+            let unknown = ch == '?' && *heard != "..--..";
+            let code = known.clone().filter(|_| !unknown).unwrap_or(heard.clone());
+            let shift = match heard.as_str() {
+                WABUN_START if options.charset == Charset::Wabun => Some(("DO", true)),
+                WABUN_END if options.charset == Charset::Wabun => Some(("SN", false)),
+                _ => None,
+            };
+            let alphabet = match options.charset {
+                Charset::Wabun if !wabun => Charset::Latin,
+                charset => charset,
+            };
+            let letter = match alphabet {
+                Charset::Latin => None,
+                alphabet => alphabet.letter(&code),
+            }
+            .or((!unknown).then_some(ch));
+            // Kana share their codes with the prosigns:
+            let prosign = shift.map(|(name, _)| name).or_else(|| {
+                (options.prosigns && alphabet != Charset::Wabun)
+                    .then(|| prosign(heard))
+                    .flatten()
+                    .filter(|_| unknown || known.as_ref().is_none_or(|c| c == heard))
+            });
+            if let Some((_, on)) = shift {
+                wabun = on;
+            }
+            let error = !space && letter.is_none() && prosign.is_none();
+            let text = match (space, prosign, letter, options.show_symbols) {
+                (true, _, _, _) => ch.to_string(),
+                // The shifts are only written out along with the prosigns:
+                (_, Some(_), _, _) if shift.is_some() && !options.prosigns => String::new(),
+                (_, Some(prosign), _, true) => format!("<{prosign}>[{heard}]"),
+                (_, Some(prosign), _, false) => format!("<{prosign}>"),
+                (_, None, None, true) => format!("[{heard}]"),
+                (_, None, None, false) => options.error_char.map(String::from).unwrap_or_default(),
+                (_, None, Some(letter), true) => format!("{letter}[{code}]"),
+                (_, None, Some(letter), false) => letter.to_string(),
+            };
+            chars.push(DecodedChar {
+                text,
+                heard: heard.clone(),
+                space,
+                error,
+            });
+        }
+
+        // Drop the long runs of errors (even across words) as noise:
+        let mut suppressed = Vec::new();
+        let mut start = 0;
+        while options.max_errors > 0 && start < chars.len() {
+            if !chars[start].error {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            let mut errors = 0;
+            for (i, ch) in chars.iter().enumerate().skip(start) {
+                if ch.error {
+                    errors += 1;
+                    end = i;
+                } else if !ch.space {
+                    break;
+                }
+            }
+            if errors > options.max_errors {
+                let run = &mut chars[start..=end];
+                let heard: Vec<&str> = run
+                    .iter()
+                    .filter(|ch| ch.error)
+                    .map(|ch| ch.heard.as_str())
+                    .collect();
+                suppressed.push(heard.join(" "));
+                run.iter_mut().for_each(|ch| ch.text.clear());
+            }
+            start = end + 1;
+        }
+
+        let text: String = chars.iter().map(|ch| ch.text.as_str()).collect();
+        let text = self
+            .whitespace_regex
+            .replace_all(text.trim_start(), " ")
+            .to_string();
+        (text, suppressed)
+    }
+
+    /// Gives the dots and dashes heard to the characters the decoder
+    /// added to the message, since it was `decoded` characters long.
+    fn record_symbols(&mut self, decoded: usize) {
+        let added = self.decoder.message.len().saturating_sub(decoded);
+        for ch in self
+            .decoder
+            .message
+            .as_str()
+            .chars()
+            .skip(decoded)
+            .take(added)
+        {
+            if ch.is_whitespace() {
+                self.symbols.push(String::new());
+            } else {
+                self.symbols.push(std::mem::take(&mut self.elements));
+            }
+        }
+    }
+
+    /// Converts a duration for the decoder, which only takes u16 milliseconds.
+    ///
+    /// Gaps longer than the message timeout all mean the same thing (the
+    /// end of a word, at least), so they are shortened to it rather than
+    /// wrapping around to what could look like a short gap. Word gaps are
+    /// lengthened to what the decoder takes for one, as it never ends a
+    /// word after a gap of exactly 7 dots. With Farnsworth timing, letter
+    /// gaps are shortened to what the decoder takes for one.
+    fn decoder_duration(&self, is_high: bool, duration: u32) -> u16 {
+        // Judged by the sender's speed, as far as it is known:
+        let dot = self
+            .speed_tracker
+            .estimate(self.dot_duration)
+            .unwrap_or(self.dot_duration);
+        let reference = self.decoder.get_reference_short() as u32;
+        let word_gap = match self.text_options.farnsworth {
+            // Halfway between the spread out letter and word gaps:
+            Some(wpm) => {
+                let timing = Timing::new(dot, Some(wpm));
+                (WORD_GAP_DOTS * dot).max((timing.letter_gap + timing.word_gap) / 2)
+            }
+            None => WORD_GAP_DOTS * dot,
+        };
+        let duration = if is_high {
+            duration
+        } else if duration >= word_gap {
+            duration
+                .max(DECODER_WORD_DOTS * reference + 1)
+                .min(self.timeout_duration)
+        } else if self.text_options.farnsworth.is_some() {
+            duration
+                .min(DECODER_LETTER_DOTS * reference)
+                .min(self.timeout_duration)
+        } else {
+            duration.min(self.timeout_duration)
+        };
+        duration.min(u16::MAX as u32) as u16
+    }
+
+    fn signal(&mut self, is_high: bool, duration: u32, events: &mut Vec<DecodeEvent>) {
+        events.push(DecodeEvent::Signal { is_high, duration });
+        self.pending |= is_high;
+        let decoded = self.decoder.message.len();
+        if is_high {
+            // Classify the tone as the decoder will, against its dot duration:
+            let reference = self.decoder.get_reference_short() as u32;
+            let dash = reference > 0 && duration >= 2 * reference;
+            self.elements.push(if dash { '-' } else { '.' });
+        }
+        self.decoder
+            .signal_event(self.decoder_duration(is_high, duration), is_high);
+        self.record_symbols(decoded);
+        if is_high {
+            self.speed_tracker.push_tone(duration);
+        } else {
+            self.speed_tracker.push_gap(duration);
+            if self.decoder.message.len() != decoded {
+                self.estimate_speed(events);
+            }
+        }
+        let msg = self.message();
+        if msg != self.partial {
+            events.push(DecodeEvent::Partial(msg.clone()));
+            self.partial = msg;
+        }
+    }
+
+    /// Measures the sender's speed after each character, and with
+    /// `adaptive_speed` gives it to the decoder.
+    fn estimate_speed(&mut self, events: &mut Vec<DecodeEvent>) {
+        let Some(dot_duration) = self.speed_tracker.estimate(self.dot_duration) else {
+            return;
+        };
+        if self.dot_estimate != Some(dot_duration) {
+            self.dot_estimate = Some(dot_duration);
+            events.push(DecodeEvent::DotEstimate(dot_duration));
+        }
+        if self.adaptive_speed {
+            self.adapt_speed(dot_duration, events);
+        }
+    }
+
+    /// Gives the decoder the sender's speed, if it has moved far enough.
+    ///
+    /// This is only done between characters, when the decoder has no
+    /// tones buffered that would be lost.
+    fn adapt_speed(&mut self, dot_duration: u32, events: &mut Vec<DecodeEvent>) {
+        let reference = self.decoder.get_reference_short() as u32;
+        if (dot_duration.abs_diff(reference) as f32) < SPEED_CHANGE * reference as f32 {
+            return;
+        }
+        // The decoder's speed can only be set when it is built:
+        self.set_dot_duration(dot_duration);
+        events.push(DecodeEvent::Speed(dot_duration));
+    }
+
+    fn end_message(&mut self, events: &mut Vec<DecodeEvent>) {
+        if self.pending {
+            // Decode the last character, still waiting for a gap:
+            let decoded = self.decoder.message.len();
+            self.decoder.signal_event_end(false);
+            self.decoder.signal_event_end(true);
+            self.record_symbols(decoded);
+            let (msg, suppressed) = self.render();
+            for noise in suppressed {
+                debug!("suppressed noise: {noise}");
+            }
+            let msg = msg.trim().to_string();
+            if !msg.is_empty() {
+                events.push(DecodeEvent::Message(msg));
+            }
+            self.decoder.message.clear();
+            self.elements.clear();
+            self.symbols.clear();
+            self.pending = false;
+        }
+        self.partial.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyer::Timing;
+    use crate::morse::{encode_morse, morse_to_tones};
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Renders text as keyed sine bursts, followed by a second of silence.
+    fn synthesize(text: &str, dot_duration: u32, tone_freq: f32) -> Vec<f32> {
+        synthesize_tones(&encode_morse(text, dot_duration, tone_freq))
+    }
+
+    fn synthesize_tones(tones: &[(f32, u32)]) -> Vec<f32> {
+        let mut samples = Vec::new();
+        for &(freq, duration) in tones {
+            let n = (SAMPLE_RATE * duration / 1000) as usize;
+            for i in 0..n {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                samples.push(0.5 * (2.0 * std::f32::consts::PI * freq * t).sin());
+            }
+        }
+        samples.resize(samples.len() + SAMPLE_RATE as usize, 0.0);
+        samples
+    }
+
+    fn run(pipeline: &mut SignalPipeline, samples: &[f32]) -> Vec<DecodeEvent> {
+        let mut events: Vec<DecodeEvent> = samples
+            .chunks(480)
+            .flat_map(|block| pipeline.push_samples(block, SAMPLE_RATE))
+            .collect();
+        events.extend(pipeline.finish());
+        events
+    }
+
+    #[test]
+    fn test_pipeline_signal_timings() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+        let events = run(&mut pipeline, &synthesize("A", 60, 700.0));
+        let signals: Vec<(bool, u32)> = events
+            .iter()
+            .filter_map(|e| match e {
+                DecodeEvent::Signal { is_high, duration } => Some((*is_high, *duration)),
+                _ => None,
+            })
+            .collect();
+        // The leading gap, then a dot, a gap and a dash:
+        assert_eq!(signals.len(), 4, "{signals:?}");
+        for ((is_high, duration), expected) in
+            signals[1..]
+                .iter()
+                .zip([(true, 60), (false, 60), (true, 180)])
+        {
+            assert_eq!(*is_high, expected.0);
+            assert!(duration.abs_diff(expected.1) <= 30, "{signals:?}");
+        }
+    }
+
+    #[test]
+    fn test_pipeline_decodes_characters() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+        let events = run(&mut pipeline, &synthesize("CQ CQ", 60, 700.0));
+        let partials: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                DecodeEvent::Partial(msg) => Some(msg.as_str()),
+                _ => None,
+            })
+            .collect();
+        // The last character is only decoded once the message ends:
+        assert_eq!(partials, vec!["C", "CQ ", "CQ C"]);
+        assert_eq!(
+            events.last(),
+            Some(&DecodeEvent::Message("CQ CQ".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_pipeline_standard_word_gaps() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+        // Sent with gaps of exactly 3 and 7 dots:
+        let tones: Vec<(f32, u32)> = Timing::new(60, None)
+            .key_events("-.-. --.- / -.. .")
+            .iter()
+            .map(|&(down, duration)| (if down { 700.0 } else { 0.0 }, duration))
+            .collect();
+        let events = run(&mut pipeline, &synthesize_tones(&tones));
+        assert_eq!(
+            events.last(),
+            Some(&DecodeEvent::Message("CQ DE".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_pipeline_farnsworth_gaps() {
+        // 20 WPM characters, spread out to 10 WPM:
+        let tones: Vec<(f32, u32)> = Timing::new(60, Some(10))
+            .key_events("-.-. --.- / -.. .")
+            .iter()
+            .map(|&(down, duration)| (if down { 700.0 } else { 0.0 }, duration))
+            .collect();
+        let samples = synthesize_tones(&tones);
+        let decode = |farnsworth| {
+            let mut pipeline = SignalPipeline::new(PipelineOptions {
+                timeout_duration: 3000,
+                text_options: TextOptions {
+                    farnsworth,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            run(&mut pipeline, &samples).last().cloned()
+        };
+        // The letter gaps are longer than word gaps at 20 WPM:
+        assert_eq!(
+            decode(None),
+            Some(DecodeEvent::Message("C Q D E".to_string()))
+        );
+        assert_eq!(
+            decode(Some(10)),
+            Some(DecodeEvent::Message("CQ DE".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_buffer_size_does_not_change_decoding() {
+        let samples = synthesize("CQ DE K1ABC", 60, 700.0);
+        let decode = |buffer_size: usize| {
+            let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+            let mut events: Vec<DecodeEvent> = samples
+                .chunks(buffer_size)
+                .flat_map(|buffer| pipeline.push_samples(buffer, SAMPLE_RATE))
+                .collect();
+            events.extend(pipeline.finish());
+            events
+        };
+        let expected = decode(64);
+        assert_eq!(
+            expected.last(),
+            Some(&DecodeEvent::Message("CQ DE K1ABC".to_string()))
+        );
+        assert_eq!(decode(4096), expected);
+    }
+
+    #[test]
+    fn test_long_silence_before_message() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+        // Five minutes of silence, then a clean CQ:
+        let silence = vec![0.0; SAMPLE_RATE as usize];
+        let mut events = Vec::new();
+        for _ in 0..5 * 60 {
+            events.extend(pipeline.push_samples(&silence, SAMPLE_RATE));
+        }
+        events.extend(run(&mut pipeline, &synthesize("CQ", 60, 700.0)));
+        let messages: Vec<&DecodeEvent> = events
+            .iter()
+            .filter(|e| matches!(e, DecodeEvent::Message(_)))
+            .collect();
+        assert_eq!(messages, vec![&DecodeEvent::Message("CQ".to_string())]);
+    }
+
+    #[test]
+    fn test_message_timeout() {
+        let decode = |timeout_duration| {
+            let mut pipeline = SignalPipeline::new(PipelineOptions {
+                timeout_duration,
+                ..Default::default()
+            });
+            // A single character, with a second of silence after it:
+            synthesize("E", 60, 700.0)
+                .chunks(480)
+                .flat_map(|block| pipeline.push_samples(block, SAMPLE_RATE))
+                .filter(|e| matches!(e, DecodeEvent::Message(_)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(decode(600), vec![DecodeEvent::Message("E".to_string())]);
+        assert_eq!(decode(1500), vec![]);
+    }
+
+    #[test]
+    fn test_pipeline_follows_speed() {
+        let messages = |adaptive_speed| {
+            // Expecting 20 WPM, but sent at 6 WPM:
+            let mut pipeline = SignalPipeline::new(PipelineOptions {
+                adaptive_speed,
+                ..Default::default()
+            });
+            run(&mut pipeline, &synthesize("VVV CQ CQ DE K1ABC", 200, 700.0))
+                .into_iter()
+                .filter(|e| matches!(e, DecodeEvent::Message(_) | DecodeEvent::Speed(_)))
+                .collect::<Vec<_>>()
+        };
+        let ends_with_cq = |events: &[DecodeEvent]| matches!(events.last(), Some(DecodeEvent::Message(msg)) if msg.ends_with("CQ CQ DE K1ABC"));
+        // The first character is decoded at the wrong speed:
+        let adaptive = messages(true);
+        assert!(
+            matches!(adaptive[0], DecodeEvent::Speed(dot) if dot.abs_diff(200) < 20),
+            "{adaptive:?}"
+        );
+        assert!(ends_with_cq(&adaptive), "{adaptive:?}");
+        // Strict timing doesn't follow the sender:
+        let fixed = messages(false);
+        assert!(!fixed.iter().any(|e| matches!(e, DecodeEvent::Speed(_))));
+        assert!(!ends_with_cq(&fixed), "{fixed:?}");
+    }
+
+    #[test]
+    fn test_pipeline_measures_speed() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+        let events = run(&mut pipeline, &synthesize("PARIS PARIS", 80, 700.0));
+        let estimates: Vec<u32> = events
+            .iter()
+            .filter_map(|e| match e {
+                DecodeEvent::DotEstimate(dot) => Some(*dot),
+                _ => None,
+            })
+            .collect();
+        // Nothing until enough tones were heard, the four of the P:
+        let first = events
+            .iter()
+            .position(|e| matches!(e, DecodeEvent::DotEstimate(_)))
+            .unwrap();
+        assert_eq!(events[first + 1], DecodeEvent::Partial("P".to_string()));
+        assert!(
+            estimates.iter().all(|dot| dot.abs_diff(80) < 10),
+            "{estimates:?}"
+        );
+        // The decoder stays at the given speed:
+        assert!(!events.iter().any(|e| matches!(e, DecodeEvent::Speed(_))));
+    }
+
+    #[test]
+    fn test_pipeline_shows_symbols() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions {
+            text_options: TextOptions {
+                show_symbols: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        // Eight dots is the error signal, which the decoder has no character for:
+        let samples = synthesize_tones(&morse_to_tones("-.-. --.- / ........ ..--..", 60, 700.0));
+        let events = run(&mut pipeline, &samples);
+        assert_eq!(
+            events.last(),
+            Some(&DecodeEvent::Message(
+                "C[-.-.]Q[--.-] [........]?[..--..]".to_string()
+            ))
+        );
+    }
+
+    /// Renders a decoded message, with the dots and dashes heard for
+    /// each of its characters.
+    fn render(message: &str, heard: &[&str], text_options: TextOptions) -> (String, Vec<String>) {
+        let mut pipeline = SignalPipeline::new(PipelineOptions {
+            text_options,
+            ..Default::default()
+        });
+        pipeline.decoder.message.set_message(message, true).unwrap();
+        pipeline.symbols = heard.iter().map(|h| h.to_string()).collect();
+        pipeline.render()
+    }
+
+    #[test]
+    fn test_pipeline_prosigns() {
+        let code = "-.-. --.- / .-.-. / ...-.- / -...- / -.--. / .-... / ..--..";
+        let decode = |prosigns| {
+            let options = TextOptions {
+                prosigns,
+                ..Default::default()
+            };
+            let mut pipeline = SignalPipeline::new(PipelineOptions {
+                text_options: options,
+                ..Default::default()
+            });
+            let events = run(
+                &mut pipeline,
+                &synthesize_tones(&morse_to_tones(code, 60, 700.0)),
+            );
+            events.last().cloned()
+        };
+        assert_eq!(
+            decode(true),
+            Some(DecodeEvent::Message(
+                "CQ <AR> <SK> <BT> <KN> <AS> ?".to_string()
+            ))
+        );
+        // Without them, the decoder's own characters (or errors) are shown:
+        assert_eq!(
+            decode(false),
+            Some(DecodeEvent::Message("CQ + ? = ( ? ?".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_error_char() {
+        let heard = ["-.-.", "--.-", "", "........", "", "..--..", "-.-"];
+        let render_with = |error_char| {
+            let options = TextOptions {
+                error_char,
+                ..Default::default()
+            };
+            render("CQ ? ?K", &heard, options).0
+        };
+        // The second '?' was really sent:
+        assert_eq!(render_with(Some('?')), "CQ ? ?K");
+        assert_eq!(render_with(Some('#')), "CQ # ?K");
+        assert_eq!(render_with(None), "CQ ?K");
+    }
+
+    #[test]
+    fn test_noise_is_suppressed() {
+        let heard = [
+            "-.-", "", "........", "......--", "", ".-.-.-.-", "--------", "........", "", ".",
+        ];
+        let render_with = |max_errors| {
+            let options = TextOptions {
+                max_errors,
+                ..Default::default()
+            };
+            render("K ?? ??? E", &heard, options)
+        };
+        assert_eq!(
+            render_with(4),
+            (
+                "K E".to_string(),
+                vec!["........ ......-- .-.-.-.- -------- ........".to_string()]
+            )
+        );
+        assert_eq!(render_with(5), ("K ?? ??? E".to_string(), vec![]));
+        assert_eq!(render_with(0), ("K ?? ??? E".to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_cyrillic_charset() {
+        // ПРИВЕТ ЧШ, as the decoder reads it in Latin:
+        let heard = [".--.", ".-.", "..", ".--", ".", "-", "", "---.", "----"];
+        let options = TextOptions {
+            charset: Charset::Cyrillic,
+            ..Default::default()
+        };
+        assert_eq!(render("PRIWET ??", &heard, options).0, "ПРИВЕТ ЧШ");
+    }
+
+    #[test]
+    fn test_wabun_shifts() {
+        // DO, イロハ, SN, then CQ in Latin again:
+        let heard = [
+            "-..---", "", ".-", ".-.-", "-...", "", "...-.", "", "-.-.", "--.-",
+        ];
+        let render_with = |charset, prosigns| {
+            let options = TextOptions {
+                charset,
+                prosigns,
+                ..Default::default()
+            };
+            render("? A?B ? CQ", &heard, options).0
+        };
+        assert_eq!(render_with(Charset::Wabun, true), "<DO> イロハ <SN> CQ");
+        assert_eq!(render_with(Charset::Wabun, false), "イロハ CQ");
+        assert_eq!(render_with(Charset::Latin, true), "? A?B <SN> CQ");
+    }
+
+    #[test]
+    fn test_long_durations_saturate() {
+        let pipeline = SignalPipeline::new(PipelineOptions::default());
+        // Five minutes would wrap around to 37856ms as a u16:
+        assert_eq!(pipeline.decoder_duration(false, 300_000), 1200);
+        assert_eq!(pipeline.decoder_duration(false, 180), 180);
+        assert_eq!(pipeline.decoder_duration(true, 300_000), u16::MAX);
+    }
+
+    #[test]
+    fn test_stereo_copy_decodes_like_mono() {
+        let mono = synthesize("CQ DE K1ABC", 60, 700.0);
+        let stereo: Vec<f32> = mono.iter().flat_map(|&s| [s, s]).collect();
+        let decode = |samples: &[f32], channels: usize, channel: Channel| {
+            let selected: Vec<f32> = samples
+                .chunks(channels)
+                .map(|f| channel.sample(f))
+                .collect();
+            let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+            run(&mut pipeline, &selected)
+        };
+        let expected = decode(&mono, 1, Channel::Mix);
+        assert_eq!(
+            expected.last(),
+            Some(&DecodeEvent::Message("CQ DE K1ABC".to_string()))
+        );
+        assert_eq!(decode(&stereo, 2, Channel::Mix), expected);
+        assert_eq!(decode(&stereo, 2, Channel::Index(1)), expected);
+    }
+
+    #[test]
+    fn test_channel_selection() {
+        assert_eq!("mix".parse::<Channel>(), Ok(Channel::Mix));
+        assert_eq!("1".parse::<Channel>(), Ok(Channel::Index(1)));
+        assert!("left".parse::<Channel>().is_err());
+        assert_eq!(Channel::Mix.sample(&[0.2, 0.4]), 0.3);
+        assert_eq!(Channel::Index(0).sample(&[0.2, 0.4]), 0.2);
+        assert!(Channel::Index(1).check(2).is_ok());
+        assert!(Channel::Index(2).check(2).is_err());
+    }
+
+    #[test]
+    fn test_pipeline_ignores_other_tones() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+        let events = run(&mut pipeline, &synthesize("CQ CQ", 60, 1500.0));
+        assert!(!events.iter().any(|e| matches!(e, DecodeEvent::Message(_))));
+    }
+
+    #[test]
+    fn test_pipeline_levels() {
+        let pipeline = |levels| SignalPipeline::new(PipelineOptions::default()).with_levels(levels);
+        let levels = |events: Vec<DecodeEvent>| -> Vec<ToneLevel> {
+            events
+                .into_iter()
+                .filter_map(|e| match e {
+                    DecodeEvent::Level(level) => Some(level),
+                    _ => None,
+                })
+                .collect()
+        };
+        let samples = synthesize("T", 60, 700.0);
+        assert!(levels(run(&mut pipeline(false), &samples)).is_empty());
+        let dash = levels(run(&mut pipeline(true), &samples));
+        // One for every 10ms window, the dash taking 18 of them (and a
+        // little more as the filter rings):
+        assert_eq!(dash.len(), samples.len() / 480);
+        let tones: Vec<usize> = (0..dash.len()).filter(|&i| dash[i].tone).collect();
+        assert!((18..=21).contains(&tones.len()), "{tones:?}");
+        assert_eq!(tones.last().unwrap() - tones[0], tones.len() - 1);
+        for level in &dash {
+            assert_eq!(level.tone, level.level > level.threshold.unwrap());
+            assert_eq!((level.peak, level.noise), (None, None));
+        }
+
+        // The tone is measured 35Hz above where it was expected:
+        let tone = levels(run(
+            &mut pipeline(true),
+            &synthesize_tones(&[(735.0, 1000)]),
+        ));
+        assert_eq!(tone[0].offset, None);
+        let offset = tone.last().unwrap().offset.unwrap();
+        assert!((offset - 35.0).abs() < 3.0, "{offset}");
+    }
+
+    #[test]
+    fn test_pipeline_tap() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+        let samples = synthesize("T", 60, 700.0);
+        run(&mut pipeline, &samples);
+        assert!(pipeline.take_tap().is_empty());
+        let mut pipeline = pipeline.with_tap(true);
+        pipeline.push_samples(&samples[..4800], SAMPLE_RATE);
+        let tap = pipeline.take_tap();
+        assert_eq!(tap.len(), 4800);
+        // Filtered, and marked once the gate let the tone through:
+        assert!(tap.iter().any(|&(sample, _)| sample.abs() > 0.3));
+        assert!(tap.iter().all(|&(sample, _)| sample.abs() < 1.0));
+        assert!(!tap[0].1 && tap[4799].1);
+        assert!(pipeline.take_tap().is_empty());
+        // A window is only tapped once it is complete:
+        pipeline.push_samples(&samples[4800..5000], SAMPLE_RATE);
+        assert!(pipeline.take_tap().is_empty());
+    }
+
+    #[test]
+    fn test_pipeline_tune() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions {
+            tone_freq: 600.0,
+            ..Default::default()
+        });
+        pipeline.tune(650.0);
+        let samples = synthesize("CQ", 60, 950.0);
+        let decoded = |events: &[DecodeEvent]| {
+            events
+                .iter()
+                .any(|e| matches!(e, DecodeEvent::Message(msg) if msg == "CQ"))
+        };
+        assert!(!decoded(&run(&mut pipeline, &samples)));
+        // Tuned onto the signal, once the filter was built:
+        pipeline.tune(950.0);
+        assert_eq!(pipeline.tone_freq(), 950.0);
+        assert!(decoded(&run(&mut pipeline, &samples)));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::json::{Json, Object};
+use std::time::Duration;
+
+/// The value below which a fraction of the sorted values are,
+/// interpolated between the nearest two.
+pub fn percentile(sorted: &[Duration], fraction: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = fraction.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    let weight = rank - low as f64;
+    sorted[low].mul_f64(1.0 - weight) + sorted[high].mul_f64(weight)
+}
+
+/// The reaction time beyond which an answer is an outlier, e.g. one
+/// given late after a sneeze: more than `iqr_multiple` interquartile
+/// ranges above the third quartile. Only the slow ones are outliers, as
+/// an answer can't be faster than no time at all.
+///
+/// # Returns
+/// The limit, or None when there are too few times to tell (under 4).
+pub fn outlier_limit(times: &[Duration], iqr_multiple: f64) -> Option<Duration> {
+    if times.len() < 4 {
+        return None;
+    }
+    let mut sorted = times.to_vec();
+    sorted.sort();
+    let (q1, q3) = (percentile(&sorted, 0.25), percentile(&sorted, 0.75));
+    Some(q3 + (q3 - q1).mul_f64(iqr_multiple))
+}
+
+/// How some reaction times are spread, leaving out the outliers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Distribution {
+    pub mean: Duration,
+    pub median: Duration,
+    pub p90: Duration,
+    pub p95: Duration,
+    pub std_dev: Duration,
+    /// How many of the times were left out, as outliers.
+    pub outliers: u32,
+}
+
+impl Distribution {
+    /// The distribution of the times up to the outlier limit, if any.
+    pub fn of(times: &[Duration], limit: Option<Duration>) -> Self {
+        let mut kept: Vec<Duration> = times
+            .iter()
+            .copied()
+            .filter(|&time| limit.is_none_or(|limit| time <= limit))
+            .collect();
+        let outliers = (times.len() - kept.len()) as u32;
+        if kept.is_empty() {
+            return Distribution {
+                outliers,
+                ..Default::default()
+            };
+        }
+        kept.sort();
+        let mean = kept.iter().sum::<Duration>() / kept.len() as u32;
+        let variance = kept
+            .iter()
+            .map(|time| (time.as_secs_f64() - mean.as_secs_f64()).powi(2))
+            .sum::<f64>()
+            / kept.len() as f64;
+        Distribution {
+            mean,
+            median: percentile(&kept, 0.5),
+            p90: percentile(&kept, 0.9),
+            p95: percentile(&kept, 0.95),
+            std_dev: Duration::from_secs_f64(variance.sqrt()),
+            outliers,
+        }
+    }
+
+    pub fn to_json(self) -> String {
+        Object::new()
+            .number("mean_ms", self.mean.as_millis())
+            .number("median_ms", self.median.as_millis())
+            .number("p90_ms", self.p90.as_millis())
+            .number("p95_ms", self.p95.as_millis())
+            .number("stddev_ms", self.std_dev.as_millis())
+            .number("outliers", self.outliers)
+            .end()
+    }
+
+    pub fn from_json(json: &Json) -> Option<Self> {
+        let millis = |key| {
+            json.get(key)?
+                .as_f64()
+                .map(|ms| Duration::from_millis(ms as u64))
+        };
+        Some(Distribution {
+            mean: millis("mean_ms")?,
+            median: millis("median_ms")?,
+            p90: millis("p90_ms")?,
+            p95: millis("p95_ms")?,
+            std_dev: millis("stddev_ms")?,
+            outliers: json.get("outliers")?.as_f64()? as u32,
+        })
+    }
+}
+
+/// Draws a histogram of the reaction times up to the outlier limit, a
+/// line per range of times.
+///
+/// # Arguments
+/// * `bars` - How many ranges to split the times in, at most.
+/// * `width` - The length of the longest bar.
+pub fn histogram(
+    times: &[Duration],
+    limit: Option<Duration>,
+    bars: usize,
+    width: usize,
+) -> Vec<String> {
+    let kept: Vec<u128> = times
+        .iter()
+        .filter(|&&time| limit.is_none_or(|limit| time <= limit))
+        .map(Duration::as_millis)
+        .collect();
+    let (Some(&min), Some(&max)) = (kept.iter().min(), kept.iter().max()) else {
+        return Vec::new();
+    };
+    // Ranges of a round number of 10ms:
+    let step = ((max - min) / bars.max(1) as u128 / 10 + 1) * 10;
+    let start = min / step * step;
+    let mut counts = vec![0; ((max - start) / step + 1) as usize];
+    for ms in kept {
+        counts[((ms - start) / step) as usize] += 1;
+    }
+    let most = counts.iter().copied().max().unwrap_or(1);
+    counts
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| {
+            let from = start + i as u128 * step;
+            format!(
+                "{:>5}-{:<5} {} {}",
+                from,
+                format!("{}ms", from + step),
+                "#".repeat((count * width).div_ceil(most)),
+                count
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(times: &[u64]) -> Vec<Duration> {
+        times.iter().map(|&ms| Duration::from_millis(ms)).collect()
+    }
+
+    #[test]
+    fn test_percentile() {
+        let sorted = ms(&[100, 200, 300, 400, 500]);
+        assert_eq!(percentile(&sorted, 0.5), Duration::from_millis(300));
+        assert_eq!(percentile(&sorted, 0.9), Duration::from_millis(460));
+        assert_eq!(percentile(&sorted, 1.0), Duration::from_millis(500));
+        assert_eq!(percentile(&[], 0.5), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_distribution() {
+        // A sneeze, 8 seconds late:
+        let times = ms(&[300, 320, 340, 360, 380, 400, 8000]);
+        assert_eq!(outlier_limit(&times[..3], 3.0), None);
+        let limit = outlier_limit(&times, 3.0);
+        // Quartiles of 330 and 390ms, 60ms apart:
+        assert_eq!(limit, Some(Duration::from_millis(570)));
+        let distribution = Distribution::of(&times, limit);
+        assert_eq!(distribution.outliers, 1);
+        assert_eq!(distribution.mean, Duration::from_millis(350));
+        assert_eq!(distribution.median, Duration::from_millis(350));
+        assert_eq!(distribution.p90, Duration::from_millis(390));
+        assert_eq!(distribution.std_dev.as_millis(), 34);
+        let json = Json::parse(&distribution.to_json()).unwrap();
+        assert_eq!(
+            Distribution::from_json(&json).map(|d| (d.median, d.outliers)),
+            Some((distribution.median, 1))
+        );
+        assert_eq!(Distribution::of(&times, None).outliers, 0);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let times = ms(&[300, 320, 340, 410, 8000]);
+        assert_eq!(
+            histogram(&times, Some(Duration::from_millis(500)), 2, 4),
+            ["  300-360ms #### 3", "  360-420ms ## 1",]
+        );
+        assert!(histogram(&[], None, 2, 4).is_empty());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::detect::DecodeEvent;
+use crate::json::Object;
+use crate::rigctl::Tuning;
+use std::str::FromStr;
+
+/// How many windows of levels (10ms each) there are between the level
+/// events, not to make too many of them.
+const LEVEL_WINDOWS: usize = 10;
+
+/// What `listen` writes to stdout.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// The display for people to read.
+    #[default]
+    Text,
+    /// A line of JSON for each event, see `EventStream`.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Output must be 'text' or 'json': {s}")),
+        }
+    }
+}
+
+/// Turns the decoding of a station into lines of JSON, one object per
+/// event, for other programs to read. Every event has a `type`, and the
+/// time `t` it happened, in seconds since the Unix epoch:
+///
+/// - `{"type":"char","char":"C","t":1712345678.123,"wpm":22}` for each
+///   character decoded, the spaces between the words included, with the
+///   measured speed if it is known yet.
+/// - `{"type":"partial","text":"CQ CQ","t":...}` when the message so far
+///   changed in another way than by a character being added, e.g. when
+///   a character turned out to be noise.
+/// - `{"type":"message","text":"CQ CQ DE ...","start":...,"end":...,"wpm":22}`
+///   when a message ended, `start` being when its first character was
+///   decoded.
+///
+/// With `verbose` (and `--verbose-events`), also:
+///
+/// - `{"type":"tone_on","t":...,"gap_ms":180}` when a tone starts,
+///   after a gap of silence.
+/// - `{"type":"tone_off","t":...,"duration_ms":60}` when it ends.
+///
+/// With `levels`, also `{"type":"level","t":...,"level":0.12,"threshold":0.05}`
+/// ten times a second, the level of the tone and the threshold it is
+/// compared with, which is `null` until it is measured.
+///
+/// With --split-stereo, each event also has the `station`, `L` or `R`.
+#[allow(dead_code)]
+#[derive(Debug, Default)]
+pub struct EventStream {
+    pub verbose: bool,
+    pub levels: bool,
+    /// What the radio is tuned to, with --rigctl, added to each event.
+    pub tuning: Option<Tuning>,
+    station: Option<String>,
+    // The message so far, as the events have told it:
+    partial: String,
+    // When the first character of the message was decoded:
+    start: Option<f64>,
+    levels_seen: usize,
+}
+
+#[allow(dead_code)]
+impl EventStream {
+    pub fn new(station: Option<&str>, verbose: bool) -> Self {
+        Self {
+            verbose,
+            station: station.map(|label| label.trim_end_matches(':').to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Also writes the levels.
+    pub fn with_levels(mut self, levels: bool) -> Self {
+        self.levels = levels;
+        self
+    }
+
+    /// The lines of JSON for an event of the pipeline, that happened at
+    /// `t` seconds since the epoch, when the sender's speed was `wpm`.
+    pub fn lines(&mut self, event: &DecodeEvent, t: f64, wpm: Option<u32>) -> Vec<String> {
+        let mut lines = Vec::new();
+        let object = |kind| Object::new().string("type", kind);
+        let chars = |added: &str, lines: &mut Vec<Object>| {
+            for c in added.chars() {
+                lines.push(
+                    object("char")
+                        .string("char", c.to_string().as_str())
+                        .fixed("t", t, 3)
+                        .number("wpm", wpm),
+                );
+            }
+        };
+        match event {
+            DecodeEvent::Partial(text) => {
+                match text.strip_prefix(self.partial.as_str()) {
+                    Some(added) => chars(added, &mut lines),
+                    None => lines.push(
+                        object("partial")
+                            .string("text", text.as_str())
+                            .fixed("t", t, 3),
+                    ),
+                }
+                if !text.is_empty() {
+                    self.start.get_or_insert(t);
+                }
+                self.partial = text.clone();
+            }
+            DecodeEvent::Message(text) => {
+                // The last character may only come with the message:
+                if let Some(added) = text.strip_prefix(self.partial.as_str()) {
+                    chars(added, &mut lines);
+                }
+                lines.push(
+                    object("message")
+                        .string("text", text.as_str())
+                        .fixed("start", self.start.unwrap_or(t), 3)
+                        .fixed("end", t, 3)
+                        .number("wpm", wpm),
+                );
+                self.partial.clear();
+                self.start = None;
+            }
+            DecodeEvent::Level(level) if self.levels => {
+                self.levels_seen += 1;
+                if self.levels_seen % LEVEL_WINDOWS == 1 {
+                    lines.push(
+                        object("level")
+                            .fixed("t", t, 3)
+                            .fixed("level", level.level, 4)
+                            .fixed("threshold", level.threshold, 4),
+                    );
+                }
+            }
+            DecodeEvent::Signal { is_high, duration } if self.verbose => {
+                lines.push(match is_high {
+                    true => object("tone_off")
+                        .fixed("t", t, 3)
+                        .number("duration_ms", *duration),
+                    false => object("tone_on")
+                        .fixed("t", t, 3)
+                        .number("gap_ms", *duration),
+                });
+            }
+            _ => {}
+        }
+        lines
+            .into_iter()
+            .map(|line| {
+                let line = match &self.station {
+                    Some(station) => line.string("station", station.as_str()),
+                    None => line,
+                };
+                match &self.tuning {
+                    Some(tuning) => tuning.json_fields(line),
+                    None => line,
+                }
+                .end()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::{PipelineOptions, SignalPipeline, ToneLevel};
+    use crate::json::Json;
+    use crate::morse::encode_morse;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    #[test]
+    fn test_event_stream() {
+        let mut samples = Vec::new();
+        for (freq, duration) in encode_morse("CQ K", 60, 700.0) {
+            let n = (SAMPLE_RATE * duration / 1000) as usize;
+            samples.extend((0..n).map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                0.5 * (2.0 * std::f32::consts::PI * freq * t).sin()
+            }));
+        }
+        samples.resize(samples.len() + 2 * SAMPLE_RATE as usize, 0.0);
+        let mut pipeline = SignalPipeline::new(PipelineOptions::default());
+        let mut stream = EventStream::new(Some("L:"), true);
+        let mut output = String::new();
+        for (i, block) in samples.chunks(480).enumerate() {
+            let t = 1712345678.0 + i as f64 * 0.01;
+            for event in pipeline.push_samples(block, SAMPLE_RATE) {
+                for line in stream.lines(&event, t, Some(20)) {
+                    output += &line;
+                    output.push('\n');
+                }
+            }
+        }
+
+        // Every line of the stream is an object of its own:
+        let events: Vec<Json> = output
+            .lines()
+            .map(|line| Json::parse(line).unwrap_or_else(|| panic!("{line}")))
+            .collect();
+        let kind = |event: &Json| event.get("type").unwrap().as_str().unwrap().to_string();
+        assert!(events
+            .iter()
+            .all(|event| event.get("station").unwrap().as_str() == Some("L")));
+        let chars: String = events
+            .iter()
+            .filter(|event| kind(event) == "char")
+            .map(|event| event.get("char").unwrap().as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(chars, "CQ K");
+        let messages: Vec<&Json> = events.iter().filter(|e| kind(e) == "message").collect();
+        assert_eq!(messages.len(), 1);
+        let message = messages[0];
+        assert_eq!(message.get("text").unwrap().as_str(), Some("CQ K"));
+        assert_eq!(message.get("wpm").unwrap().as_f64(), Some(20.0));
+        let (start, end) = (
+            message.get("start").unwrap().as_f64().unwrap(),
+            message.get("end").unwrap().as_f64().unwrap(),
+        );
+        assert!(1712345678.0 < start && start < end, "{start} {end}");
+        // C is dah dit dah dit: four tones, the first 180ms long:
+        let tones: Vec<&Json> = events.iter().filter(|e| kind(e) == "tone_off").collect();
+        assert_eq!(tones.len(), 4 + 4 + 3);
+        let first = tones[0].get("duration_ms").unwrap().as_f64().unwrap();
+        assert!((first - 180.0).abs() <= 20.0, "{first}");
+        assert!(events.iter().any(|e| kind(e) == "tone_on"));
+
+        // Without verbose, only the text:
+        let mut stream = EventStream::new(None, false);
+        let signal = DecodeEvent::Signal {
+            is_high: true,
+            duration: 60,
+        };
+        assert!(stream.lines(&signal, 0.0, None).is_empty());
+        assert_eq!(
+            stream.lines(&DecodeEvent::Partial("E".to_string()), 1.5, None),
+            vec!["{\"type\":\"char\",\"char\":\"E\",\"t\":1.500,\"wpm\":null}"]
+        );
+        assert_eq!(
+            stream.lines(&DecodeEvent::Partial("T".to_string()), 2.0, None),
+            vec!["{\"type\":\"partial\",\"text\":\"T\",\"t\":2.000}"]
+        );
+
+        // The levels, ten times a second:
+        let level = DecodeEvent::Level(ToneLevel {
+            level: 0.125,
+            threshold: None,
+            peak: None,
+            noise: None,
+            tone: false,
+            offset: None,
+        });
+        assert!(stream.lines(&level, 3.0, None).is_empty());
+        stream.levels = true;
+        let lines: Vec<String> = (0..LEVEL_WINDOWS * 2)
+            .flat_map(|_| stream.lines(&level, 3.0, None))
+            .collect();
+        assert_eq!(
+            lines,
+            vec!["{\"type\":\"level\",\"t\":3.000,\"level\":0.1250,\"threshold\":null}"; 2]
+        );
+
+        // With --rigctl, after the station:
+        let mut stream = EventStream::new(Some("R:"), false);
+        stream.tuning = Some(Tuning {
+            freq_hz: Some(3560000),
+            mode: None,
+        });
+        assert_eq!(
+            stream.lines(&DecodeEvent::Message("K".to_string()), 4.0, Some(12)),
+            vec![
+                "{\"type\":\"char\",\"char\":\"K\",\"t\":4.000,\"wpm\":12,\"station\":\"R\",\"freq_hz\":3560000,\"mode\":null}",
+                "{\"type\":\"message\",\"text\":\"K\",\"start\":4.000,\"end\":4.000,\"wpm\":12,\"station\":\"R\",\"freq_hz\":3560000,\"mode\":null}",
+            ]
+        );
+    }
+}
@@ -0,0 +1,338 @@
+use crate::fecr_quiz::QuizResult;
+use crate::json::{Json, Object};
+use chrono::{DateTime, Local, TimeDelta};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// The columns of the exported trials, in order. They are kept as they
+/// are, new ones only coming last, so that the spreadsheets made from
+/// them keep working.
+pub const COLUMNS: [&str; 10] = [
+    "session_id",
+    "timestamp",
+    "sent",
+    "typed",
+    "correct",
+    "reaction_ms",
+    "wpm",
+    "attempt",
+    "total_ms",
+    "tone_hz",
+];
+
+/// One character or word sent during a session, and how it was copied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trial {
+    /// When its sound ended, in RFC 3339 with milliseconds.
+    pub timestamp: String,
+    pub sent: String,
+    /// What was typed for it, if anything.
+    pub typed: Option<String>,
+    pub correct: bool,
+    /// None when there was no answer in time.
+    pub reaction: Option<Duration>,
+    pub wpm: u32,
+    /// The attempt it was copied on, or the last one, from 1.
+    pub attempt: u32,
+    /// The reaction times of all of its attempts, added up.
+    pub total: Option<Duration>,
+    /// The tone it was sent at, in Hz, unless it was recorded before the
+    /// tones were.
+    pub tone: Option<f32>,
+}
+
+/// The time of day of an instant, e.g. of the end of a sound.
+pub fn wall_clock(at: Instant) -> DateTime<Local> {
+    let (now, clock) = (Instant::now(), Local::now());
+    let delta = |duration| TimeDelta::from_std(duration).unwrap_or_default();
+    match at > now {
+        true => clock + delta(at - now),
+        false => clock - delta(now - at),
+    }
+}
+
+/// Formats a time as the trials do.
+pub fn timestamp(time: &DateTime<Local>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string()
+}
+
+/// The trials of a quiz, all sent at the same speed and tone.
+pub fn quiz_trials(results: &QuizResult, wpm: u32, tone: f32) -> Vec<Trial> {
+    (0..results.responses.len())
+        .map(|i| Trial {
+            timestamp: timestamp(&results.sent_at[i]),
+            sent: results.prompts[i].clone(),
+            typed: results.typed[i].clone(),
+            correct: results.responses[i] == Some(true),
+            reaction: results.reaction_times[i],
+            wpm,
+            attempt: 1,
+            total: results.reaction_times[i],
+            tone: Some(tone),
+        })
+        .collect()
+}
+
+impl Trial {
+    /// Writes the trial as JSON, with the id of its session if given.
+    pub fn to_json(&self, session_id: Option<&str>) -> String {
+        let object = match session_id {
+            Some(id) => Object::new().string("session_id", id),
+            None => Object::new(),
+        };
+        object
+            .string("timestamp", self.timestamp.as_str())
+            .string("sent", self.sent.as_str())
+            .string("typed", self.typed.as_deref())
+            .bool("correct", self.correct)
+            .number("reaction_ms", self.reaction.map(|time| time.as_millis()))
+            .number("wpm", self.wpm)
+            .number("attempt", self.attempt)
+            .number("total_ms", self.total.map(|time| time.as_millis()))
+            .number("tone_hz", self.tone)
+            .end()
+    }
+
+    pub fn from_json(json: &Json) -> Option<Self> {
+        let millis = |value: &Json| value.as_f64().map(|ms| Duration::from_millis(ms as u64));
+        let reaction = millis(json.get("reaction_ms")?);
+        Some(Trial {
+            timestamp: json.get("timestamp")?.as_str()?.to_string(),
+            sent: json.get("sent")?.as_str()?.to_string(),
+            typed: json.get("typed")?.as_str().map(str::to_string),
+            correct: json.get("correct")?.as_bool()?,
+            reaction,
+            wpm: json.get("wpm")?.as_f64()? as u32,
+            // Not in the records made before there were attempts:
+            attempt: json
+                .get("attempt")
+                .and_then(Json::as_f64)
+                .map_or(1, |n| n as u32),
+            total: match json.get("total_ms") {
+                Some(total) => millis(total),
+                None => reaction,
+            },
+            // Not in the records made before the tones were:
+            tone: json
+                .get("tone_hz")
+                .and_then(Json::as_f64)
+                .map(|hz| hz as f32),
+        })
+    }
+
+    fn to_csv(&self, session_id: &str) -> String {
+        [
+            session_id,
+            &self.timestamp,
+            &self.sent,
+            self.typed.as_deref().unwrap_or(""),
+            &self.correct.to_string(),
+            &self
+                .reaction
+                .map_or(String::new(), |time| time.as_millis().to_string()),
+            &self.wpm.to_string(),
+            &self.attempt.to_string(),
+            &self
+                .total
+                .map_or(String::new(), |time| time.as_millis().to_string()),
+            &self.tone.map_or(String::new(), |tone| tone.to_string()),
+        ]
+        .map(csv_field)
+        .join(",")
+    }
+}
+
+/// Quotes a CSV field if it has to be, as RFC 4180 does.
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+/// Where to export the trials to, and how.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Export {
+    pub format: Format,
+    pub path: PathBuf,
+}
+
+impl FromStr for Export {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format = match s.split_once(':') {
+            Some(("csv", path)) if !path.is_empty() => Format::Csv,
+            Some(("json", path)) if !path.is_empty() => Format::Json,
+            _ => return Err(format!("Export must be csv:<path> or json:<path>, not {s}")),
+        };
+        Ok(Export {
+            format,
+            path: PathBuf::from(&s[s.find(':').unwrap_or_default() + 1..]),
+        })
+    }
+}
+
+/// Writes trials, each with the id of its session: in CSV with a header
+/// of the `COLUMNS`, or as a JSON array of objects with the same keys.
+pub fn write_trials(
+    writer: &mut impl Write,
+    format: Format,
+    trials: &[(&str, &Trial)],
+) -> io::Result<()> {
+    match format {
+        Format::Csv => {
+            writeln!(writer, "{}", COLUMNS.join(","))?;
+            for (session_id, trial) in trials {
+                writeln!(writer, "{}", trial.to_csv(session_id))?;
+            }
+        }
+        Format::Json => {
+            let rows: Vec<String> = trials
+                .iter()
+                .map(|(session_id, trial)| trial.to_json(Some(session_id)))
+                .collect();
+            writeln!(writer, "[\n{}\n]", rows.join(",\n"))?;
+        }
+    }
+    Ok(())
+}
+
+impl Export {
+    pub fn save(&self, trials: &[(&str, &Trial)]) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(&self.path)?);
+        write_trials(&mut file, self.format, trials)?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits CSV in records of fields, unquoting them.
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let (mut records, mut record, mut field) = (Vec::new(), Vec::new(), String::new());
+        let (mut quoted, mut chars) = (false, text.chars().peekable());
+        while let Some(ch) = chars.next() {
+            match (ch, quoted) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                ('"', _) => quoted = !quoted,
+                (',', false) => record.push(std::mem::take(&mut field)),
+                ('\n', false) => {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                _ => field.push(ch),
+            }
+        }
+        records
+    }
+
+    fn trials() -> Vec<Trial> {
+        let trial = |sent: &str, typed: Option<&str>, correct, ms: Option<u64>| Trial {
+            timestamp: "2024-11-02T18:30:01.250+01:00".to_string(),
+            sent: sent.to_string(),
+            typed: typed.map(str::to_string),
+            correct,
+            reaction: ms.map(Duration::from_millis),
+            wpm: 20,
+            attempt: 1,
+            total: ms.map(Duration::from_millis),
+            tone: Some(650.0),
+        };
+        vec![
+            trial(",", Some("\"."), false, Some(420)),
+            trial("Ж", Some("Ж"), true, Some(310)),
+            trial("<AR>", None, false, None),
+        ]
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let trials = trials();
+        let rows: Vec<(&str, &Trial)> = trials.iter().map(|trial| ("a,b", trial)).collect();
+        let mut csv = Vec::new();
+        write_trials(&mut csv, Format::Csv, &rows).unwrap();
+        let records = parse_csv(&String::from_utf8(csv).unwrap());
+        assert_eq!(records[0], COLUMNS);
+        assert_eq!(
+            records[1],
+            [
+                "a,b",
+                "2024-11-02T18:30:01.250+01:00",
+                ",",
+                "\".",
+                "false",
+                "420",
+                "20",
+                "1",
+                "420",
+                "650"
+            ]
+        );
+        assert_eq!(records[2][2..5], ["Ж", "Ж", "true"]);
+        assert_eq!(records[3][2..6], ["<AR>", "", "false", ""]);
+        assert_eq!(records.len(), 4);
+    }
+
+    #[test]
+    fn test_export_json() {
+        let trials = trials();
+        let rows: Vec<(&str, &Trial)> = trials.iter().map(|trial| ("a,b", trial)).collect();
+        let mut json = Vec::new();
+        write_trials(&mut json, Format::Json, &rows).unwrap();
+        let json = Json::parse(&String::from_utf8(json).unwrap()).unwrap();
+        let exported = json.as_array().unwrap();
+        assert_eq!(exported.len(), 3);
+        for (exported, trial) in exported.iter().zip(&trials) {
+            assert_eq!(
+                exported.get("session_id").and_then(Json::as_str),
+                Some("a,b")
+            );
+            assert_eq!(Trial::from_json(exported).as_ref(), Some(trial));
+        }
+        // Recorded before there were attempts:
+        let json = trials[0]
+            .to_json(None)
+            .replace(r#","attempt":1,"total_ms":420,"tone_hz":650"#, "");
+        assert!(!json.contains("attempt"));
+        assert_eq!(
+            Trial::from_json(&Json::parse(&json).unwrap()),
+            Some(Trial {
+                tone: None,
+                ..trials[0].clone()
+            })
+        );
+    }
+
+    #[test]
+    fn test_export_arg() {
+        assert_eq!(
+            "csv:out/trials.csv".parse(),
+            Ok(Export {
+                format: Format::Csv,
+                path: PathBuf::from("out/trials.csv")
+            })
+        );
+        assert_eq!(
+            "json:C:\\trials.json"
+                .parse::<Export>()
+                .map(|export| export.path),
+            Ok(PathBuf::from("C:\\trials.json"))
+        );
+        assert!("xml:trials.xml".parse::<Export>().is_err());
+        assert!("csv:".parse::<Export>().is_err());
+    }
+}
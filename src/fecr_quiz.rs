@@ -1,8 +1,11 @@
+use crate::distribution::{histogram, outlier_limit, Distribution};
+use crate::export::wall_clock;
+use crate::input::{reaction, KeyReader, Keystroke};
 use crate::morse::MorsePlayer;
+use chrono::{DateTime, Local};
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode},
-    terminal::{disable_raw_mode, enable_raw_mode},
+    event::KeyCode,
     terminal::{Clear, ClearType},
     ExecutableCommand,
 };
@@ -14,6 +17,10 @@ use tabled::settings::style::Style;
 use tabled::{Table, Tabled};
 use textwrap::wrap;
 
+/// Runs the quiz, and prints the results.
+///
+/// # Returns
+/// The results, or None if the quiz was stopped before it began.
 #[allow(clippy::too_many_arguments)]
 pub fn start_quiz(
     trials: u32,
@@ -24,7 +31,9 @@ pub fn start_quiz(
     randomize: bool,
     calibration: bool,
     baseline: u32,
-) {
+    outlier_iqr: f64,
+    show_histogram: bool,
+) -> Option<QuizResult> {
     let paragraph = match calibration {
         true => "Calibration process.\n\nThis process will measure your native keyboard typing skills to calculate your personal output latency. A series of characters will be displayed at the same time a tone is played. Enter the characters as fast as you can.\n".to_string(),
         false => format!("Fast Enough Character Recognition quiz.\n\nMorse encoded characters will be played back to you one at a time and you must type the character you hear as soon as you recognize it.\n\nThis test will include {trials} trials. You will be timed in your response. Your reaction time is subtracted from the baseline input latency of {baseline}ms.\n")
@@ -47,52 +56,33 @@ pub fn start_quiz(
         println!("\nYou may stop the quiz at any time by pressing the ESC key.\nTo begin the quiz press the Enter key.");
     }
 
-    // Enable raw mode to capture key presses
-    if let Err(e) = enable_raw_mode() {
-        eprintln!("Error enabling raw mode: {}", e);
-        return;
-    }
+    let keys = KeyReader::start();
+    // Raw mode captures key presses without Enter:
+    let _raw = keys.raw_mode();
 
     loop {
-        match event::read() {
-            Ok(Event::Key(key_event)) => {
-                match key_event.code {
-                    KeyCode::Enter => {
-                        // Start the quiz
-                        println!("Begin!");
-                        break;
-                    }
-                    KeyCode::Esc => {
-                        // Exit the quiz
-                        if calibration {
-                            println!("\nCalibration process terminated.");
-                        } else {
-                            println!("\nQuiz terminated.");
-                        }
-                        if let Err(e) = disable_raw_mode() {
-                            eprintln!("Error disabling raw mode: {}", e);
-                        }
-                        return;
-                    }
-                    _ => {}
-                }
-            }
-            Ok(_e) => {
-                //eprintln!("Unknown event");
-            }
-            Err(e) => {
-                eprintln!("Error reading event: {}", e);
+        match keys.next().map(|key| key.code) {
+            Some(KeyCode::Enter) => {
+                // Start the quiz
+                println!("Begin!");
                 break;
             }
+            Some(KeyCode::Esc) | None => {
+                // Exit the quiz
+                if calibration {
+                    println!("\nCalibration process terminated.");
+                } else {
+                    println!("\nQuiz terminated.");
+                }
+                return None;
+            }
+            _ => {}
         }
     }
-    // Disable raw mode
-    if let Err(e) = disable_raw_mode() {
-        eprintln!("Error disabling raw mode: {}", e);
-    }
 
     let results = reaction_time_quiz(
         &player,
+        &keys,
         char_set,
         trials,
         dot_duration,
@@ -102,23 +92,88 @@ pub fn start_quiz(
         calibration,
         if calibration { 0 } else { baseline },
     );
+    drop(_raw);
     print_results(
         &results,
         Duration::from_millis(dot_duration.into()),
         calibration,
         if calibration { 0 } else { baseline },
+        outlier_iqr,
+        show_histogram,
     );
+    Some(results)
 }
 
-struct QuizResult {
-    prompts: Vec<char>,
-    responses: Vec<Option<bool>>,
-    reaction_times: Vec<Option<Duration>>,
+/// The characters of a quiz, and how quickly and correctly each of them
+/// was answered.
+pub struct QuizResult {
+    pub prompts: Vec<String>,
+    pub responses: Vec<Option<bool>>,
+    pub reaction_times: Vec<Option<Duration>>,
+    /// Whether each was answered before its sound had ended.
+    pub typed_ahead: Vec<bool>,
+    /// What was typed for each, if anything.
+    pub typed: Vec<Option<String>>,
+    /// When the sound of each ended.
+    pub sent_at: Vec<DateTime<Local>>,
+}
+
+impl QuizResult {
+    /// The percentage of the characters that were answered correctly.
+    pub fn accuracy(&self) -> f64 {
+        let correct = self.responses.iter().filter(|&&r| r == Some(true)).count();
+        if self.prompts.is_empty() {
+            0.0
+        } else {
+            correct as f64 / self.prompts.len() as f64 * 100.0
+        }
+    }
+
+    /// The reaction time beyond which an answer is an outlier, see
+    /// `distribution::outlier_limit`.
+    pub fn outlier_limit(&self, iqr_multiple: f64) -> Option<Duration> {
+        let times: Vec<Duration> = self.reaction_times.iter().flatten().copied().collect();
+        outlier_limit(&times, iqr_multiple)
+    }
+
+    /// The mean reaction time, leaving out the outliers, in milliseconds:
+    /// the baseline that a calibration measures.
+    pub fn baseline(&self, iqr_multiple: f64) -> u32 {
+        let times: Vec<Duration> = self.reaction_times.iter().flatten().copied().collect();
+        Distribution::of(&times, self.outlier_limit(iqr_multiple))
+            .mean
+            .as_millis() as u32
+    }
+
+    /// The percentage of the characters that were answered correctly
+    /// within a time limit, leaving out those answered after the
+    /// outlier limit, if any.
+    pub fn fast_enough(&self, limit: Duration, outlier_limit: Option<Duration>) -> f64 {
+        let kept: Vec<usize> = (0..self.prompts.len())
+            .filter(|&i| {
+                outlier_limit
+                    .is_none_or(|outlier| self.reaction_times[i].is_none_or(|time| time <= outlier))
+            })
+            .collect();
+        let fast = kept
+            .iter()
+            .filter(|&&i| {
+                self.responses[i] == Some(true)
+                    && self.reaction_times[i].is_some_and(|time| time <= limit)
+            })
+            .count();
+        if kept.is_empty() {
+            0.0
+        } else {
+            fast as f64 / kept.len() as f64 * 100.0
+        }
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
 fn reaction_time_quiz(
     player: &MorsePlayer,
+    keys: &KeyReader,
     char_set: &str,
     trials: u32,
     dot_duration: u32,
@@ -131,19 +186,12 @@ fn reaction_time_quiz(
     let mut prompts = Vec::new();
     let mut responses = Vec::new();
     let mut reaction_times = Vec::new();
+    let mut typed_ahead = Vec::new();
+    let mut typed = Vec::new();
+    let mut sent_at = Vec::new();
 
     let mut stdout = stdout();
 
-    // Enable raw mode to capture key presses without Enter
-    if let Err(e) = enable_raw_mode() {
-        eprintln!("Error enabling raw mode: {}", e);
-        return QuizResult {
-            prompts,
-            responses,
-            reaction_times,
-        };
-    }
-
     let mut rng = rand::thread_rng();
 
     if randomize {
@@ -153,86 +201,86 @@ fn reaction_time_quiz(
             let target_letter = target_letter
                 .choose(&mut rng)
                 .expect("Could not generate random character");
-            prompts.push(*target_letter);
+            prompts.push(target_letter.to_string());
         }
     } else {
         let mut target_letters = char_set.chars().collect::<Vec<_>>();
         target_letters.shuffle(&mut rng);
         for i in 0..trials {
-            prompts.push(target_letters[i as usize % target_letters.len()]);
+            prompts.push(target_letters[i as usize % target_letters.len()].to_string());
         }
         prompts.shuffle(&mut rng);
     }
     for i in 0..trials {
         std::thread::sleep(Duration::from_millis(500));
-        let target_letter: char = prompts[i as usize];
-        // Clear the screen and display the letter
-        stdout.execute(Clear(ClearType::All)).unwrap();
-        stdout.execute(cursor::MoveTo(0, 0)).unwrap();
+        let target_letter = prompts[i as usize].clone();
+        // Clear the screen and display the letter, or just start a new
+        // line for a screen reader:
+        match crate::term::accessible() {
+            true => println!(),
+            false => {
+                stdout.execute(Clear(ClearType::All)).unwrap();
+                stdout.execute(cursor::MoveTo(0, 0)).unwrap();
+            }
+        }
         if text || calibration {
             print!("Type the letter:");
             stdout.flush().unwrap();
         }
-
-        if calibration {
-            player.play_nonblocking_tone(dot_duration, tone_freq);
-        } else {
-            player.play(&target_letter.to_string(), dot_duration, tone_freq);
-        }
+        // Forget any keys pressed between the trials:
+        keys.clear();
+
+        // Keys are read while the sound plays, so that an answer typed
+        // ahead of the end of the sound is noticed:
+        let (_sound, length) = match calibration {
+            true => {
+                player.play_nonblocking_tone(dot_duration, tone_freq);
+                (None, Duration::ZERO)
+            }
+            false => {
+                let (sound, length) = player.start(&target_letter, dot_duration, tone_freq);
+                (Some(sound), length)
+            }
+        };
+        // Reactions are timed from the end of the sound:
+        let sound_end = Instant::now() + length;
 
         if text || calibration {
             println!(" {target_letter}");
             stdout.flush().unwrap();
         }
 
-        // Start the timer
-        let start_time = Instant::now();
-
-        let mut is_correct = false;
-        let mut key_processed = false; // Ensure unique processing per key press
-
-        // Wait for user input (key press followed by release)
-        loop {
-            if let Ok(Event::Key(event)) = event::read() {
-                match event.kind {
-                    crossterm::event::KeyEventKind::Press if !key_processed => {
-                        if let KeyCode::Char(input_char) = event.code {
-                            is_correct = input_char.to_ascii_uppercase()
-                                == target_letter.to_ascii_uppercase();
-                            key_processed = true; // Block further processing until release
-                        }
-                        if event.code == KeyCode::Esc {
-                            disable_raw_mode().unwrap();
-                            println!("\nQuiz terminated.");
-                            // Truncate prompts to the same size as `responses`:
-                            if responses.len() < prompts.len() {
-                                prompts.truncate(responses.len());
-                            }
-                            if reaction_times.len() > responses.len() {
-                                reaction_times.truncate(responses.len());
-                            }
-                            return QuizResult {
-                                prompts,
-                                responses,
-                                reaction_times,
-                            };
-                        }
-                    }
-                    crossterm::event::KeyEventKind::Release => {
-                        key_processed = false; // Allow next key press
+        // Wait for the first character typed
+        let (input_char, typed_at) = loop {
+            match keys.next() {
+                Some(Keystroke {
+                    code: KeyCode::Char(ch),
+                    at,
+                    ..
+                }) => break (ch, at),
+                Some(key) if key.code != KeyCode::Esc => {}
+                _ => {
+                    println!("\nQuiz terminated.");
+                    // Truncate prompts to the same size as `responses`:
+                    if responses.len() < prompts.len() {
+                        prompts.truncate(responses.len());
                     }
-                    _ => {}
-                }
-
-                // Exit loop after processing a valid key
-                if key_processed {
-                    break;
+                    return QuizResult {
+                        prompts,
+                        responses,
+                        reaction_times,
+                        typed_ahead,
+                        typed,
+                        sent_at,
+                    };
                 }
             }
-        }
+        };
+        let is_correct = input_char
+            .to_uppercase()
+            .eq(target_letter.chars().flat_map(char::to_uppercase));
 
-        // Stop the timer
-        let elapsed = start_time.elapsed();
+        let (elapsed, ahead) = reaction(typed_at, sound_end);
         let baseline_duration = Duration::from_millis(baseline.into());
         let clamped_duration = if elapsed > baseline_duration {
             elapsed - baseline_duration
@@ -240,33 +288,54 @@ fn reaction_time_quiz(
             Duration::from_millis(0)
         };
         reaction_times.push(Some(clamped_duration));
+        typed_ahead.push(ahead);
+        typed.push(Some(input_char.to_uppercase().collect()));
+        sent_at.push(wall_clock(sound_end));
 
         responses.push(Some(is_correct));
     }
 
-    // Disable raw mode after the quiz
-    if let Err(e) = disable_raw_mode() {
-        eprintln!("Error disabling raw mode: {}", e);
-    }
-
     QuizResult {
         prompts,
         responses,
         reaction_times,
+        typed_ahead,
+        typed,
+        sent_at,
     }
 }
 
 #[derive(Tabled)]
 struct SummaryRow {
-    character: char,
+    character: String,
     count: u32,
     avg_correct_time: String,
     avg_incorrect_time: String,
     times_correct: u32,
     times_incorrect: u32,
+    median_time: String,
+    p90_time: String,
+    p95_time: String,
 }
 
-fn print_results(results: &QuizResult, dot_duration: Duration, calibration: bool, baseline: u32) {
+/// Prints how each character was answered, and the grade.
+///
+/// # Arguments
+/// * `outlier_iqr` - How many interquartile ranges above the third
+///   quartile a reaction time is an outlier, left out of the averages.
+/// * `show_histogram` - Whether to draw a histogram of the reaction
+///   times.
+///
+/// # Returns
+/// The table of the characters, to be copied.
+pub fn print_results(
+    results: &QuizResult,
+    dot_duration: Duration,
+    calibration: bool,
+    baseline: u32,
+    outlier_iqr: f64,
+    show_histogram: bool,
+) -> String {
     println!("\nTest complete!\n");
     let total = results.prompts.len();
     let correct = results
@@ -282,17 +351,18 @@ fn print_results(results: &QuizResult, dot_duration: Duration, calibration: bool
         .iter()
         .filter_map(|&time| time) // Filter out None values and keep Some(Duration)
         .sum();
-    let average_time = if total > 0 {
-        total_time / total as u32
-    } else {
-        Duration::default()
-    };
+    let times: Vec<Duration> = results.reaction_times.iter().flatten().copied().collect();
+    let limit = results.outlier_limit(outlier_iqr);
+    let kept = |time: &Duration| limit.is_none_or(|limit| *time <= limit);
+    let distribution = Distribution::of(&times, limit);
+    let average_time = distribution.mean;
 
     let correct_times: Vec<_> = results
         .reaction_times
         .iter()
-        .filter_map(|&time| time)
         .zip(results.responses.iter())
+        .filter_map(|(&time, is_correct)| Some((time?, is_correct)))
+        .filter(|(time, _)| kept(time))
         .filter_map(|(time, &is_correct)| {
             if is_correct.unwrap_or_default() {
                 Some(time)
@@ -305,8 +375,9 @@ fn print_results(results: &QuizResult, dot_duration: Duration, calibration: bool
     let incorrect_times: Vec<_> = results
         .reaction_times
         .iter()
-        .filter_map(|&time| time)
         .zip(results.responses.iter())
+        .filter_map(|(&time, is_correct)| Some((time?, is_correct)))
+        .filter(|(time, _)| kept(time))
         .filter_map(|(time, &is_correct)| {
             if !is_correct.unwrap_or_default() {
                 Some(time)
@@ -329,25 +400,33 @@ fn print_results(results: &QuizResult, dot_duration: Duration, calibration: bool
     };
 
     // Summary output by character
-    let mut character_stats: HashMap<char, (u32, Duration, Duration, u32, u32)> = HashMap::new();
+    #[allow(clippy::type_complexity)]
+    let mut character_stats: HashMap<&str, (u32, Duration, Duration, u32, u32, Vec<Duration>)> =
+        HashMap::new();
 
-    for (i, &prompt) in results.prompts.iter().enumerate() {
+    for (i, prompt) in results.prompts.iter().enumerate() {
         let entry = character_stats.entry(prompt).or_insert((
             0,
             Duration::default(),
             Duration::default(),
             0,
             0,
+            Vec::new(),
         ));
         entry.0 += 1; // Increment trial count
+        entry.5.extend(results.reaction_times[i]);
 
-        if let Some(res) = results.responses[i] {
+        if results.reaction_times[i].is_some_and(|time| !kept(&time)) {
+            // An outlier, left out of the averages
+        } else if let Some(res) = results.responses[i] {
+            // There is no time to add when there was no answer in time:
+            let time = results.reaction_times[i];
             if res {
-                entry.1 += results.reaction_times[i].expect("Reaction time not found");
+                entry.1 += time.into_iter().sum::<Duration>();
                 entry.3 += 1
             // Add to correct times
             } else {
-                entry.2 += results.reaction_times[i].expect("Reaction time not found");
+                entry.2 += time.into_iter().sum::<Duration>();
                 entry.4 += 1
                 // Add to incorrect times
             }
@@ -357,7 +436,11 @@ fn print_results(results: &QuizResult, dot_duration: Duration, calibration: bool
     let mut summary: Vec<SummaryRow> = character_stats
         .into_iter()
         .map(
-            |(character, (count, correct_time, incorrect_time, times_correct, times_incorrect))| {
+            |(
+                character,
+                (count, correct_time, incorrect_time, times_correct, times_incorrect, times),
+            )| {
+                let distribution = Distribution::of(&times, limit);
                 let avg_correct_time = if count > 0 {
                     correct_time / count
                 } else {
@@ -370,12 +453,15 @@ fn print_results(results: &QuizResult, dot_duration: Duration, calibration: bool
                 };
 
                 SummaryRow {
-                    character,
+                    character: character.to_string(),
                     count,
                     avg_correct_time: format!("{:.0?}ms", avg_correct_time.as_millis()),
                     avg_incorrect_time: format!("{:.0?}ms", avg_incorrect_time.as_millis()),
                     times_correct,
                     times_incorrect,
+                    median_time: format!("{}ms", distribution.median.as_millis()),
+                    p90_time: format!("{}ms", distribution.p90.as_millis()),
+                    p95_time: format!("{}ms", distribution.p95.as_millis()),
                 }
             },
         )
@@ -401,7 +487,7 @@ fn print_results(results: &QuizResult, dot_duration: Duration, calibration: bool
 
     // Create and style the table.
     let mut table = Table::new(&summary);
-    let table = table.with(Style::rounded());
+    let table = table.with(Style::rounded()).to_string();
 
     // Highlight rows where `times_incorrect > 0`
 
@@ -409,7 +495,7 @@ fn print_results(results: &QuizResult, dot_duration: Duration, calibration: bool
     println!("{}", table);
 
     // Overall results
-    let percentage_correct = (correct as f64 / total as f64) * 100.0;
+    let percentage_correct = results.accuracy();
     let speed_score = if average_correct_time <= dot_duration {
         "Excellent"
     } else if average_correct_time <= dot_duration * 2 {
@@ -439,13 +525,44 @@ fn print_results(results: &QuizResult, dot_duration: Duration, calibration: bool
         "Average incorrect reaction time: {:.2?}",
         average_incorrect_time
     );
+    println!("Median reaction time: {:.2?}", distribution.median);
+    println!(
+        "90th and 95th percentile reaction times: {:.2?}, {:.2?}",
+        distribution.p90, distribution.p95
+    );
+    println!(
+        "Standard deviation of the reaction times: {:.2?}",
+        distribution.std_dev
+    );
     println!("Total reaction time: {:.2?}", total_time);
+    if let Some(limit) = limit.filter(|_| distribution.outliers > 0) {
+        let outliers: Vec<String> = times
+            .iter()
+            .filter(|time| !kept(time))
+            .map(|time| format!("{time:.2?}"))
+            .collect();
+        println!(
+            "Outliers left out of the averages (over {limit:.2?}): {} ({})",
+            distribution.outliers,
+            outliers.join(", ")
+        );
+    }
+    if show_histogram {
+        println!("\nReaction times:\n");
+        for line in histogram(&times, limit, 10, 40) {
+            println!("{line}");
+        }
+    }
+    let ahead = results.typed_ahead.iter().filter(|&&ahead| ahead).count();
+    if ahead > 0 {
+        println!("Typed ahead of the sound (counted as no time): {ahead}");
+    }
 
     if calibration {
-        let average = average_time.as_millis();
-        println!("\nYour calibrated baseline score is: {average}");
-        println!("Provide this score as your baseline to the FECR quiz:");
-        println!("\n   code-smore fecr-quiz -b {average}")
+        println!(
+            "\nYour calibrated baseline score is: {}",
+            results.baseline(outlier_iqr)
+        );
     } else {
         println!("Baseline latency subtracted: {baseline}ms");
         println!(
@@ -464,4 +581,26 @@ Speed rating: {}",
             _ => (),
         }
     }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_print_timed_out() {
+        // The second E was copied, but not in time:
+        let results = QuizResult {
+            prompts: ["E", "E", "T"].map(String::from).into(),
+            responses: vec![Some(true), Some(true), Some(false)],
+            reaction_times: vec![Some(Duration::from_millis(300)), None, None],
+            typed_ahead: vec![false; 3],
+            typed: vec![Some("E".to_string()), Some("E".to_string()), None],
+            sent_at: vec![Local::now(); 3],
+        };
+        let table = print_results(&results, Duration::from_millis(60), false, 0, 3.0, true);
+        assert!(table.contains("300ms"), "{table}");
+        assert_eq!(results.baseline(3.0), 300);
+    }
 }
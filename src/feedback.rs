@@ -0,0 +1,98 @@
+use crossterm::style::Stylize;
+use std::str::FromStr;
+
+/// How an answer is told to be right or wrong, right after it is given.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Feedback {
+    None,
+    /// A high chirp when right, a low buzz when wrong.
+    Sound,
+    /// The copy is echoed again, green where it was right and red where
+    /// it was wrong.
+    Visual,
+    Both,
+}
+
+impl FromStr for Feedback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Feedback::None),
+            "sound" => Ok(Feedback::Sound),
+            "visual" => Ok(Feedback::Visual),
+            "both" => Ok(Feedback::Both),
+            _ => Err(format!("Unknown feedback: {s}")),
+        }
+    }
+}
+
+impl Feedback {
+    pub fn sound(&self) -> bool {
+        matches!(self, Feedback::Sound | Feedback::Both)
+    }
+
+    pub fn visual(&self) -> bool {
+        matches!(self, Feedback::Visual | Feedback::Both)
+    }
+}
+
+/// The tones of the feedback sound, as (frequency, milliseconds): a
+/// chirp going up when right, and a low buzz when wrong. Both are kept
+/// shorter than the gap before the next group.
+pub fn tones(correct: bool) -> Vec<(f32, u32)> {
+    match correct {
+        true => vec![(1200.0, 40), (1800.0, 60)],
+        false => vec![(160.0, 180)],
+    }
+}
+
+/// The copy of each character (or of a whole word) and whether it was
+/// right, colored green or red.
+pub fn colored(marks: &[(String, bool)]) -> String {
+    marks
+        .iter()
+        .map(|(copy, correct)| match correct {
+            true => copy.as_str().green().to_string(),
+            false => copy.as_str().red().to_string(),
+        })
+        .collect()
+}
+
+/// The copy of each character (or word) and whether it was right, in
+/// words, e.g. `right` or `wrong: K right, N wrong`.
+pub fn spoken(marks: &[(String, bool)]) -> String {
+    if marks.iter().all(|&(_, correct)| correct) {
+        return "right".to_string();
+    }
+    let marks: Vec<String> = marks
+        .iter()
+        .map(|(copy, correct)| match correct {
+            true => format!("{copy} right"),
+            false => format!("{copy} wrong"),
+        })
+        .collect();
+    format!("wrong: {}", marks.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feedback() {
+        assert_eq!("both".parse::<Feedback>().map(|f| f.sound()), Ok(true));
+        assert!(!"visual".parse::<Feedback>().unwrap().sound());
+        assert!("visual".parse::<Feedback>().unwrap().visual());
+        assert!("loud".parse::<Feedback>().is_err());
+        // The feedback fits in the half second gap between the groups:
+        for correct in [true, false] {
+            let length: u32 = tones(correct).iter().map(|&(_, ms)| ms).sum();
+            assert!(length < 500);
+        }
+        let marks = [("K".to_string(), true), ("N".to_string(), false)];
+        assert_eq!(colored(&marks), format!("{}{}", "K".green(), "N".red()));
+        assert_eq!(spoken(&marks), "wrong: K right, N wrong");
+        assert_eq!(spoken(&marks[..1]), "right");
+    }
+}
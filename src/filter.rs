@@ -1,6 +1,7 @@
 use iir_filters::filter::{DirectForm2Transposed, Filter};
 use iir_filters::filter_design::{butter, FilterType};
 use iir_filters::sos::zpk2sos;
+use std::str::FromStr;
 
 pub struct BandpassFilter {
     #[allow(dead_code)]
@@ -42,7 +43,190 @@ impl BandpassFilter {
     /// # Returns
     /// A `Vec<f64>` containing the filtered signal.
     #[allow(dead_code)]
-    pub fn apply(&mut self, input: &[f64]) -> Vec<f64> {
-        input.iter().map(|&x| self.filter.filter(x)).collect()
+    pub fn apply<S: Copy + Into<f64>>(&mut self, input: &[S]) -> Vec<f64> {
+        input
+            .iter()
+            .map(|&x| self.filter.filter(x.into()))
+            .collect()
+    }
+
+    /// Applies the bandpass filter to a signal, replacing the samples
+    /// with the filtered signal so that nothing is allocated.
+    #[allow(dead_code)]
+    pub fn apply_in_place(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            *sample = self.filter.filter(*sample as f64) as f32;
+        }
+    }
+}
+
+/// Measures the signal level of a block of samples.
+///
+/// This is the scaled average absolute sample value that `listen`
+/// compares against the `--threshold` setting.
+#[allow(dead_code)]
+pub fn signal_level<S: Copy + Into<f64>>(samples: &[S]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = samples.iter().map(|&s| s.into().abs()).sum();
+    (sum / samples.len() as f64) as f32 * 30.0
+}
+
+/// Measures how much of the energy in a block of samples is at `tone_freq`.
+///
+/// This runs the Goertzel algorithm for a single frequency and divides
+/// the power at that frequency by the total power of the block.
+///
+/// # Returns
+/// A ratio between 0.0 (no tone) and 1.0 (a pure tone at `tone_freq`).
+#[allow(dead_code)]
+pub fn goertzel_level<S: Copy + Into<f64>>(samples: &[S], tone_freq: f64, sample_rate: f64) -> f32 {
+    let n = samples.len() as f64;
+    let total_power: f64 = samples.iter().map(|&s| s.into().powi(2)).sum::<f64>() / n;
+    // Treat digital silence (and empty blocks) as no tone at all:
+    if samples.is_empty() || total_power < 1e-10 {
+        return 0.0;
+    }
+    let coeff = 2.0 * (2.0 * std::f64::consts::PI * tone_freq / sample_rate).cos();
+    let (mut s1, mut s2) = (0.0, 0.0);
+    for &sample in samples {
+        let s0 = sample.into() + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+    let magnitude_squared = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+    // A sine of amplitude A has a mean power of A²/2 and |X|² = (A·N/2)²:
+    let tone_power = 2.0 * magnitude_squared / (n * n);
+    (tone_power / total_power).min(1.0) as f32
+}
+
+/// The method used to decide if the tone is present in a block of samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Detector {
+    /// Compare the average filtered amplitude to the threshold.
+    Peak,
+    /// Compare the fraction of energy at the tone frequency to the threshold.
+    Goertzel,
+}
+
+impl FromStr for Detector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "peak" => Ok(Detector::Peak),
+            "goertzel" => Ok(Detector::Goertzel),
+            _ => Err(format!("Unknown detector: {s}")),
+        }
+    }
+}
+
+impl Detector {
+    /// Measures the level of a block of filtered samples, to be compared
+    /// against the `--threshold` setting.
+    #[allow(dead_code)]
+    pub fn level<S: Copy + Into<f64>>(
+        &self,
+        samples: &[S],
+        tone_freq: f64,
+        sample_rate: f64,
+    ) -> f32 {
+        match self {
+            Detector::Peak => signal_level(samples),
+            Detector::Goertzel => goertzel_level(samples, tone_freq, sample_rate),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn sine(freq: f64, amplitude: f64, start: usize, len: usize) -> Vec<f64> {
+        (start..start + len)
+            .map(|i| amplitude * (2.0 * std::f64::consts::PI * freq * i as f64 / SAMPLE_RATE).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_only_in_band_tone_crosses_threshold() {
+        let threshold = 0.3;
+        let buffer_len = 1024;
+        let mut in_band = BandpassFilter::new(5, 700.0, 200.0, SAMPLE_RATE).unwrap();
+        let mut out_of_band = BandpassFilter::new(5, 700.0, 200.0, SAMPLE_RATE).unwrap();
+        // Let the filters settle on the first few buffers, then measure:
+        for n in 0..8 {
+            let start = n * buffer_len;
+            let level_700 = signal_level(&in_band.apply(&sine(700.0, 0.5, start, buffer_len)));
+            let level_300 = signal_level(&out_of_band.apply(&sine(300.0, 0.5, start, buffer_len)));
+            if n >= 4 {
+                assert!(level_700 > threshold, "700Hz level {level_700}");
+                assert!(level_300 < threshold, "300Hz level {level_300}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_state_persists_across_buffers() {
+        let signal = sine(700.0, 0.5, 0, 4096);
+        let mut whole = BandpassFilter::new(5, 700.0, 200.0, SAMPLE_RATE).unwrap();
+        let mut chunked = BandpassFilter::new(5, 700.0, 200.0, SAMPLE_RATE).unwrap();
+        let expected = whole.apply(&signal);
+        let actual: Vec<f64> = signal
+            .chunks(333)
+            .flat_map(|chunk| chunked.apply(chunk))
+            .collect();
+        assert_eq!(expected, actual);
+    }
+
+    /// Uniform white noise from a fixed seed.
+    fn noise(amplitude: f64, len: usize, seed: u64) -> Vec<f64> {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        (0..len)
+            .map(|_| rng.gen_range(-amplitude..amplitude))
+            .collect()
+    }
+
+    fn mix(a: &[f64], b: &[f64]) -> Vec<f64> {
+        a.iter().zip(b).map(|(a, b)| a + b).collect()
+    }
+
+    #[test]
+    fn test_goertzel_level_pure_tone() {
+        let level = goertzel_level(&sine(700.0, 0.5, 0, 1024), 700.0, SAMPLE_RATE);
+        assert!(level > 0.95, "level {level}");
+        let level = goertzel_level(&sine(300.0, 0.5, 0, 1024), 700.0, SAMPLE_RATE);
+        assert!(level < 0.05, "level {level}");
+        assert_eq!(goertzel_level(&[0.0; 1024], 700.0, SAMPLE_RATE), 0.0);
+    }
+
+    #[test]
+    fn test_goertzel_detects_tone_in_noise() {
+        let threshold = 0.3;
+        let buffer_len = 2048;
+        let mut with_tone = BandpassFilter::new(5, 700.0, 200.0, SAMPLE_RATE).unwrap();
+        let mut without_tone = BandpassFilter::new(5, 700.0, 200.0, SAMPLE_RATE).unwrap();
+        for n in 0..8 {
+            let start = n * buffer_len;
+            // The tone is 17dB below the broadband noise:
+            let noisy = noise(0.85, buffer_len, n as u64);
+            let tone = sine(700.0, 0.1, start, buffer_len);
+            let signal = with_tone.apply(&mix(&tone, &noisy));
+            let silence = without_tone.apply(&noisy);
+            if n < 4 {
+                continue;
+            }
+            // The peak detector can't tell the tone apart from the noise:
+            assert!(Detector::Peak.level(&silence, 700.0, SAMPLE_RATE) > threshold);
+            // But the Goertzel detector can:
+            let tone_level = Detector::Goertzel.level(&signal, 700.0, SAMPLE_RATE);
+            let noise_level = Detector::Goertzel.level(&silence, 700.0, SAMPLE_RATE);
+            assert!(tone_level > threshold, "tone level {tone_level}");
+            assert!(noise_level < threshold, "noise level {noise_level}");
+        }
     }
 }
@@ -0,0 +1,462 @@
+use crate::prelude::*;
+use crate::rigctl::Tuning;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The method of fldigi that appends to its RX text.
+const ADD_RX: &str = "text.add_rx";
+/// How long the characters are held, to be sent together.
+const BATCH: Duration = Duration::from_millis(250);
+/// The most text kept while fldigi can't be reached, the oldest being
+/// dropped.
+const MAX_PENDING: usize = 4096;
+/// How often the frequency is asked of fldigi, with `--fldigi-poll`.
+const POLL: Duration = Duration::from_secs(2);
+/// The longest wait before calling fldigi again.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How long an answer of fldigi may take.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where fldigi serves XML-RPC, e.g. `http://localhost:7362`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FldigiUrl {
+    pub host: String,
+    pub port: u16,
+}
+
+impl FromStr for FldigiUrl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("https://") {
+            return Err(format!("HTTPS is not supported: {s}"));
+        }
+        let addr = s.strip_prefix("http://").unwrap_or(s);
+        let addr = addr.split('/').next().unwrap_or_default();
+        let (host, port) = match addr.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().ok()),
+            None => (addr, Some(7362)),
+        };
+        match port {
+            Some(port) if !host.is_empty() => Ok(FldigiUrl {
+                host: host.to_string(),
+                port,
+            }),
+            _ => Err(format!("fldigi must be like http://localhost:7362: {s}")),
+        }
+    }
+}
+
+/// Escapes the text of an XML element.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The call of a method, with string parameters only.
+fn method_call(method: &str, params: &[&str]) -> String {
+    let params: String = params
+        .iter()
+        .map(|param| {
+            format!(
+                "<param><value><string>{}</string></value></param>",
+                escape(param)
+            )
+        })
+        .collect();
+    format!(
+        "<?xml version=\"1.0\"?>\n<methodCall><methodName>{method}</methodName><params>{params}</params></methodCall>\n"
+    )
+}
+
+/// The text between a tag and its end, after the start of a string.
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = xml[start..].find(&format!("</{tag}>"))?;
+    Some(&xml[start..start + end])
+}
+
+/// The value a method answered, as text whatever its type (e.g. the
+/// `<double>` of a frequency), or its fault.
+fn method_response(xml: &str) -> io::Result<String> {
+    if let Some(fault) = element(xml, "fault") {
+        let message = element(fault, "string").unwrap_or(fault);
+        return Err(io::Error::other(format!(
+            "fldigi answered a fault: {}",
+            unescape(message.trim())
+        )));
+    }
+    let value = match element(xml, "params") {
+        Some(params) => element(params, "value").unwrap_or_default(),
+        None => return Err(io::Error::other("Not an XML-RPC answer")),
+    };
+    // An untyped value is a string:
+    let value = match value.trim_start().strip_prefix('<') {
+        Some(typed) => {
+            let tag = &typed[..typed.find('>').unwrap_or_default()];
+            match tag.ends_with('/') {
+                true => "",
+                false => element(value, tag).unwrap_or_default(),
+            }
+        }
+        None => value,
+    };
+    Ok(unescape(value))
+}
+
+/// Calls a method of fldigi, over a connection of its own.
+fn call(url: &FldigiUrl, method: &str, params: &[&str]) -> io::Result<String> {
+    let addr = (url.host.as_str(), url.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No address for {}", url.host),
+            )
+        })?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let body = method_call(method, params);
+    write!(
+        stream,
+        "POST /RPC2 HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: text/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        url.host,
+        url.port,
+        body.len()
+    )?;
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    if !status
+        .split_whitespace()
+        .nth(1)
+        .is_some_and(|code| code.starts_with('2'))
+    {
+        return Err(io::Error::other(format!(
+            "fldigi answered {}",
+            status.trim()
+        )));
+    }
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let mut body = Vec::new();
+    match length {
+        Some(length) => {
+            body.resize(length, 0);
+            reader.read_exact(&mut body)?;
+        }
+        None => {
+            reader.read_to_end(&mut body)?;
+        }
+    }
+    method_response(&String::from_utf8_lossy(&body))
+}
+
+/// Sends the text as it comes, a batch at a time, keeping it while
+/// fldigi can't be reached to send it when it is back.
+fn deliver(url: FldigiUrl, texts: Receiver<String>) {
+    let mut pending = String::new();
+    let mut backoff = Duration::from_secs(1);
+    let mut reachable = true;
+    loop {
+        if pending.is_empty() {
+            match texts.recv() {
+                Ok(text) => pending.push_str(&text),
+                Err(_) => return,
+            }
+        }
+        let until = Instant::now() + BATCH;
+        loop {
+            match texts.recv_timeout(until.saturating_duration_since(Instant::now())) {
+                Ok(text) => pending.push_str(&text),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) if pending.is_empty() => return,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        let excess = pending.chars().count().saturating_sub(MAX_PENDING);
+        if excess > 0 {
+            pending = pending.chars().skip(excess).collect();
+        }
+        match call(&url, ADD_RX, &[&pending]) {
+            Ok(_) => {
+                if !reachable {
+                    info!("fldigi at {}:{} is back", url.host, url.port);
+                    reachable = true;
+                }
+                backoff = Duration::from_secs(1);
+                pending.clear();
+            }
+            Err(e) => {
+                if reachable {
+                    warn!(
+                        "Could not send the text to fldigi at {}:{}: {e}",
+                        url.host, url.port
+                    );
+                    reachable = false;
+                }
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+/// Asks fldigi for the frequency it is tuned to, forever.
+fn poll(url: &FldigiUrl, freq: &Mutex<Option<u64>>) {
+    let mut backoff = Duration::from_secs(1);
+    let mut reachable = true;
+    loop {
+        let answer = call(url, "main.get_frequency", &[]).and_then(|answer| {
+            answer
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| io::Error::other(format!("Not a frequency: {answer}")))
+        });
+        match answer {
+            Ok(hz) => {
+                if !reachable {
+                    info!("fldigi at {}:{} is back", url.host, url.port);
+                    reachable = true;
+                }
+                backoff = Duration::from_secs(1);
+                *freq.lock().unwrap() = Some(hz.round() as u64);
+                std::thread::sleep(POLL);
+            }
+            Err(e) => {
+                if reachable {
+                    warn!(
+                        "Could not ask fldigi at {}:{} for the frequency: {e}",
+                        url.host, url.port
+                    );
+                    reachable = false;
+                }
+                *freq.lock().unwrap() = None;
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+/// Pushes the characters decoded into the RX text of fldigi, with
+/// `--fldigi`, and with `--fldigi-poll`, asks it for its frequency to
+/// tag the messages with. Each station of `listen` has its own clone,
+/// with what it has sent of its message.
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct Fldigi {
+    texts: Sender<String>,
+    freq: Option<Arc<Mutex<Option<u64>>>>,
+    sent: String,
+}
+
+#[allow(dead_code)]
+impl Fldigi {
+    pub fn start(url: FldigiUrl, poll_freq: bool) -> Self {
+        let (texts, receiver) = channel();
+        let freq = poll_freq.then(|| Arc::new(Mutex::new(None)));
+        if let Some(freq) = freq.clone() {
+            let url = url.clone();
+            std::thread::spawn(move || poll(&url, &freq));
+        }
+        std::thread::spawn(move || deliver(url, receiver));
+        Self {
+            texts,
+            freq,
+            sent: String::new(),
+        }
+    }
+
+    /// Sends the characters of the message received so far that weren't
+    /// yet. What was sent can't be taken back, so a character decoded
+    /// again differently stays as it was first sent.
+    pub fn partial(&mut self, message: &str) {
+        let new: String = message.chars().skip(self.sent.chars().count()).collect();
+        if !new.is_empty() {
+            let _ = self.texts.send(new);
+            self.sent = message.to_string();
+        }
+    }
+
+    /// Sends the rest of a message that ended, and a line break.
+    pub fn message(&mut self, message: &str) {
+        self.partial(message);
+        let _ = self.texts.send("\n".to_string());
+        self.sent.clear();
+    }
+
+    /// The frequency of fldigi, with `--fldigi-poll`.
+    pub fn tuning(&self) -> Option<Tuning> {
+        let freq = self.freq.as_ref()?;
+        Some(Tuning {
+            freq_hz: *freq.lock().unwrap(),
+            mode: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// Answers the calls like fldigi, sending the bodies of the calls
+    /// received.
+    fn mock_fldigi(listener: TcpListener, calls: Sender<String>) {
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("Content-Length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let body = String::from_utf8(body).unwrap();
+                let answer = match element(&body, "methodName") {
+                    Some("main.get_frequency") => "<value><double>14070000.000000</double></value>",
+                    Some(ADD_RX) => "<value></value>",
+                    _ => "",
+                };
+                let answer = match answer {
+                    "" => "<?xml version=\"1.0\"?><methodResponse><fault><value><struct><member><name>faultCode</name><value><i4>-1</i4></value></member><member><name>faultString</name><value><string>No such method</string></value></member></struct></value></fault></methodResponse>".to_string(),
+                    answer => format!("<?xml version=\"1.0\"?>\r\n<methodResponse><params><param>\r\n\t{answer}\r\n</param></params></methodResponse>\r\n"),
+                };
+                let _ = calls.send(body);
+                let mut stream = reader.into_inner();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nServer: XMLRPC++ 0.8\r\nContent-Type: text/xml\r\nContent-length: {}\r\n\r\n{answer}",
+                    answer.len()
+                )
+                .unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn test_xml_rpc() {
+        assert_eq!(
+            method_call("text.add_rx", &["<CQ> & 73"]),
+            "<?xml version=\"1.0\"?>\n<methodCall><methodName>text.add_rx</methodName><params><param><value><string>&lt;CQ&gt; &amp; 73</string></value></param></params></methodCall>\n"
+        );
+        assert_eq!(
+            method_response("<methodResponse><params><param><value>a &amp;lt; b</value></param></params></methodResponse>").unwrap(),
+            "a &lt; b"
+        );
+        assert!(method_response("<html>").is_err());
+        assert_eq!(
+            "http://localhost:7362".parse(),
+            Ok(FldigiUrl {
+                host: "localhost".to_string(),
+                port: 7362
+            })
+        );
+        assert_eq!("10.0.0.2".parse::<FldigiUrl>().unwrap().port, 7362);
+        assert!("https://localhost:7362".parse::<FldigiUrl>().is_err());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = FldigiUrl {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+        };
+        let (calls, received) = channel();
+        mock_fldigi(listener, calls);
+        assert_eq!(
+            call(&url, "main.get_frequency", &[]).unwrap(),
+            "14070000.000000"
+        );
+        assert_eq!(call(&url, ADD_RX, &["CQ"]).unwrap(), "");
+        let fault = call(&url, "text.add_rxx", &[]).unwrap_err();
+        assert!(fault.to_string().ends_with("No such method"), "{fault}");
+        assert_eq!(received.try_iter().count(), 3);
+    }
+
+    #[test]
+    fn test_fldigi() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = FldigiUrl {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+        };
+        let (calls, received) = channel();
+        mock_fldigi(listener, calls);
+        let mut fldigi = Fldigi::start(url.clone(), true);
+        // The characters as they are decoded, sent together:
+        for partial in ["C", "CQ", "CQ ", "CQ D", "CQ DE"] {
+            fldigi.partial(partial);
+        }
+        fldigi.message("CQ DE K1ABC");
+        let texts: Vec<String> = received
+            .iter()
+            .filter(|body| element(body, "methodName") == Some(ADD_RX))
+            .take(1)
+            .map(|body| unescape(element(&body, "string").unwrap()))
+            .collect();
+        assert_eq!(texts, ["CQ DE K1ABC\n"]);
+        let mut frequency = None;
+        for _ in 0..250 {
+            frequency = fldigi.tuning().and_then(|tuning| tuning.freq_hz);
+            if frequency.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(frequency, Some(14070000));
+        assert_eq!(Fldigi::start(url, false).tuning(), None);
+    }
+
+    #[test]
+    fn test_reconnect() {
+        // Nothing listening yet, so the text waits:
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = closed.local_addr().unwrap();
+        drop(closed);
+        let url = FldigiUrl {
+            host: "127.0.0.1".to_string(),
+            port: addr.port(),
+        };
+        let mut fldigi = Fldigi::start(url, false);
+        fldigi.message("TEST");
+        std::thread::sleep(BATCH * 2);
+        let Ok(listener) = TcpListener::bind(addr) else {
+            // The port was taken meanwhile:
+            return;
+        };
+        let (calls, received) = channel();
+        mock_fldigi(listener, calls);
+        let body = received.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(element(&body, "string"), Some("TEST\n"));
+    }
+}
@@ -0,0 +1,164 @@
+/// Debounces the detected tone state before it reaches the decoder.
+///
+/// A change of state only counts once it has lasted for the debounce
+/// duration, so short noise spikes (and short dropouts in a tone) are
+/// ignored. A confirmed change is dated back to when it first started,
+/// so the debounce doesn't shorten or lengthen the elements.
+pub struct ToneGate {
+    debounce_duration: u64,
+    state: bool,
+    last_change: u64,
+    pending_since: Option<u64>,
+}
+
+impl ToneGate {
+    /// Creates a new ToneGate.
+    ///
+    /// # Arguments
+    /// * `debounce_duration` - How long a change must last, in milliseconds.
+    pub fn new(debounce_duration: u32) -> Self {
+        Self {
+            debounce_duration: debounce_duration as u64,
+            state: false,
+            last_change: 0,
+            pending_since: None,
+        }
+    }
+
+    /// Updates the gate with the latest detected tone state.
+    ///
+    /// # Arguments
+    /// * `tone` - Whether a tone was detected.
+    /// * `now` - The current time in milliseconds.
+    ///
+    /// # Returns
+    /// When a change is confirmed, the state that ended and how long it
+    /// lasted in milliseconds, ready for `decoder.signal_event`.
+    pub fn update(&mut self, tone: bool, now: u64) -> Option<(bool, u32)> {
+        if tone == self.state {
+            self.pending_since = None;
+            return None;
+        }
+        let since = *self.pending_since.get_or_insert(now);
+        if now - since < self.debounce_duration {
+            return None;
+        }
+        let ended = (self.state, saturate(since - self.last_change));
+        self.state = tone;
+        self.last_change = since;
+        self.pending_since = None;
+        Some(ended)
+    }
+
+    /// Whether a tone is on, as last confirmed.
+    pub fn is_on(&self) -> bool {
+        self.state
+    }
+
+    /// Milliseconds since the last confirmed change of state.
+    pub fn since_change(&self, now: u64) -> u32 {
+        saturate(now - self.last_change)
+    }
+
+    /// Ends a tone that is still on when the input ends.
+    ///
+    /// # Returns
+    /// How long the tone lasted in milliseconds, if there was one.
+    pub fn end_tone(&mut self, now: u64) -> Option<u32> {
+        if !self.state {
+            return None;
+        }
+        let end = self.pending_since.unwrap_or(now);
+        let duration = saturate(end - self.last_change);
+        self.reset(end);
+        Some(duration)
+    }
+
+    /// Goes back to silence, as at the end of a message.
+    pub fn reset(&mut self, now: u64) {
+        self.state = false;
+        self.last_change = now;
+        self.pending_since = None;
+    }
+}
+
+/// Converts milliseconds to u32, without wrapping around after ~49 days.
+fn saturate(duration: u64) -> u32 {
+    u32::try_from(duration).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::threshold::ToneThreshold;
+
+    const BLOCK_MS: u64 = 10;
+
+    /// Runs a sequence of detector levels, one per block, through a fixed
+    /// threshold and the gate, returning the confirmed events.
+    fn run(levels: &[f32], debounce_duration: u32) -> Vec<(bool, u32)> {
+        let mut threshold = ToneThreshold::new(Some(0.3));
+        let mut gate = ToneGate::new(debounce_duration);
+        levels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &level)| {
+                let tone = threshold.detect(level, BLOCK_MS as f32 / 1000.0);
+                gate.update(tone, i as u64 * BLOCK_MS)
+            })
+            .collect()
+    }
+
+    fn levels(pattern: &[(f32, usize)]) -> Vec<f32> {
+        pattern
+            .iter()
+            .flat_map(|&(level, blocks)| std::iter::repeat_n(level, blocks))
+            .collect()
+    }
+
+    #[test]
+    fn test_tone_gate_passes_elements() {
+        let events = run(
+            &levels(&[(0.0, 10), (1.0, 6), (0.0, 6), (1.0, 18), (0.0, 5)]),
+            15,
+        );
+        assert_eq!(
+            events,
+            vec![(false, 100), (true, 60), (false, 60), (true, 180)]
+        );
+    }
+
+    #[test]
+    fn test_tone_gate_ignores_spikes() {
+        // One block spikes in the silence, and a dropout in the tone:
+        let events = run(
+            &levels(&[
+                (0.0, 10),
+                (1.0, 1),
+                (0.0, 10),
+                (1.0, 8),
+                (0.0, 1),
+                (1.0, 8),
+                (0.0, 5),
+            ]),
+            15,
+        );
+        assert_eq!(events, vec![(false, 210), (true, 170)]);
+    }
+
+    #[test]
+    fn test_tone_gate_without_debounce() {
+        let events = run(&levels(&[(0.0, 10), (1.0, 1), (0.0, 5)]), 0);
+        assert_eq!(events, vec![(false, 100), (true, 10)]);
+    }
+
+    #[test]
+    fn test_tone_gate_hysteresis() {
+        // A tone hovering around the threshold doesn't chatter:
+        let events = run(
+            &levels(&[(0.0, 10), (0.35, 2), (0.28, 3), (0.32, 3), (0.0, 5)]),
+            15,
+        );
+        assert_eq!(events, vec![(false, 100), (true, 80)]);
+    }
+}
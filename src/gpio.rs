@@ -0,0 +1,278 @@
+use crate::keying::{Output, Signal};
+use crate::prelude::*;
+
+/// A GPIO line, e.g. `17`, or `gpiochip4:17` on a chip other than the
+/// first (the header of a Raspberry Pi 5 on some kernels).
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpioPin {
+    pub chip: String,
+    pub offset: u32,
+}
+
+#[cfg_attr(not(feature = "gpio"), allow(dead_code))]
+impl GpioPin {
+    /// The character device of the chip.
+    pub fn chip_path(&self) -> String {
+        match self.chip.starts_with('/') {
+            true => self.chip.clone(),
+            false => format!("/dev/{}", self.chip),
+        }
+    }
+}
+
+impl FromStr for GpioPin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (chip, offset) = s.rsplit_once(':').unwrap_or(("gpiochip0", s));
+        match offset.parse() {
+            Ok(offset) if !chip.is_empty() => Ok(GpioPin {
+                chip: chip.to_string(),
+                offset,
+            }),
+            _ => Err(format!(
+                "GPIO pin must be its number, e.g. 17 or gpiochip4:17: {s}"
+            )),
+        }
+    }
+}
+
+/// A GPIO output, the backend of the pins.
+#[cfg_attr(not(feature = "gpio"), allow(dead_code))]
+pub trait Pin: Send {
+    fn write(&mut self, high: bool) -> io::Result<()>;
+}
+
+/// The key on one pin, and the PTT on another, either active-high or
+/// active-low. Both are made inactive whenever it is dropped, even by a
+/// panic.
+#[cfg_attr(not(feature = "gpio"), allow(dead_code))]
+pub struct GpioKey<P: Pin> {
+    key: P,
+    ptt: Option<P>,
+    active_low: bool,
+}
+
+#[cfg_attr(not(feature = "gpio"), allow(dead_code))]
+impl<P: Pin> GpioKey<P> {
+    /// Keys with the pins, each of them made inactive first.
+    pub fn new(mut key: P, mut ptt: Option<P>, active_low: bool) -> io::Result<Self> {
+        key.write(active_low)?;
+        if let Some(ptt) = &mut ptt {
+            ptt.write(active_low)?;
+        }
+        Ok(Self {
+            key,
+            ptt,
+            active_low,
+        })
+    }
+}
+
+impl<P: Pin> Output for GpioKey<P> {
+    fn set(&mut self, signal: Signal, raised: bool) -> io::Result<()> {
+        let high = raised != self.active_low;
+        match (signal, &mut self.ptt) {
+            (Signal::Key, _) => self.key.write(high),
+            (Signal::Ptt, Some(ptt)) => ptt.write(high),
+            (Signal::Ptt, None) => Ok(()),
+        }
+    }
+}
+
+impl<P: Pin> Drop for GpioKey<P> {
+    fn drop(&mut self) {
+        let _ = self.key.write(self.active_low);
+        if let Some(ptt) = &mut self.ptt {
+            let _ = ptt.write(self.active_low);
+        }
+    }
+}
+
+/// Requests the pins of the GPIO character devices, to key with.
+#[cfg(all(feature = "gpio", target_os = "linux"))]
+pub fn open(key: &GpioPin, ptt: Option<&GpioPin>, active_low: bool) -> io::Result<Box<dyn Output>> {
+    let inactive = active_low;
+    let ptt = ptt
+        .map(|pin| chardev::Line::request(pin, inactive))
+        .transpose()?;
+    let key = chardev::Line::request(key, inactive)?;
+    Ok(Box::new(GpioKey::new(key, ptt, active_low)?))
+}
+
+#[cfg(not(all(feature = "gpio", target_os = "linux")))]
+pub fn open(
+    _key: &GpioPin,
+    _ptt: Option<&GpioPin>,
+    _active_low: bool,
+) -> io::Result<Box<dyn Output>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "GPIO keying needs Linux, and a build with the 'gpio' feature",
+    ))
+}
+
+/// The lines of the GPIO character devices (`/dev/gpiochipN`), as
+/// their (v1) ioctls in `linux/gpio.h` have them.
+#[cfg(all(feature = "gpio", target_os = "linux"))]
+mod chardev {
+    use super::{GpioPin, Pin};
+    use crate::prelude::*;
+    use std::fs::File;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    #[repr(C)]
+    struct HandleRequest {
+        line_offsets: [u32; 64],
+        flags: u32,
+        default_values: [u8; 64],
+        consumer_label: [u8; 32],
+        lines: u32,
+        fd: libc::c_int,
+    }
+
+    #[repr(C)]
+    struct HandleData {
+        values: [u8; 64],
+    }
+
+    /// `_IOWR(0xB4, 0x03, struct gpiohandle_request)`
+    const GET_LINEHANDLE: u32 = 0xC16C_B403;
+    /// `_IOWR(0xB4, 0x09, struct gpiohandle_data)`
+    const SET_LINE_VALUES: u32 = 0xC040_B409;
+    const REQUEST_OUTPUT: u32 = 1 << 1;
+
+    // The sizes the ioctl numbers were made with:
+    const _: () = assert!(std::mem::size_of::<HandleRequest>() == 364);
+    const _: () = assert!(std::mem::size_of::<HandleData>() == 64);
+
+    pub struct Line {
+        handle: File,
+    }
+
+    impl Line {
+        /// Requests a line as an output, starting high or low.
+        pub fn request(pin: &GpioPin, high: bool) -> io::Result<Line> {
+            let chip = File::open(pin.chip_path())?;
+            let mut request = HandleRequest {
+                line_offsets: [0; 64],
+                flags: REQUEST_OUTPUT,
+                default_values: [0; 64],
+                consumer_label: [0; 32],
+                lines: 1,
+                fd: -1,
+            };
+            request.line_offsets[0] = pin.offset;
+            request.default_values[0] = high as u8;
+            let label = env!("CARGO_PKG_NAME").as_bytes();
+            let len = label.len().min(31);
+            request.consumer_label[..len].copy_from_slice(&label[..len]);
+            match unsafe { libc::ioctl(chip.as_raw_fd(), GET_LINEHANDLE as _, &mut request) } {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(Line {
+                    handle: unsafe { File::from_raw_fd(request.fd) },
+                }),
+            }
+        }
+    }
+
+    impl Pin for Line {
+        fn write(&mut self, high: bool) -> io::Result<()> {
+            let mut data = HandleData { values: [0; 64] };
+            data.values[0] = high as u8;
+            match unsafe { libc::ioctl(self.handle.as_raw_fd(), SET_LINE_VALUES as _, &mut data) } {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyer::Timing;
+    use crate::keying::{self, KeyingOptions};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    /// When each pin was written, and its level.
+    type Writes = Arc<Mutex<Vec<(Instant, &'static str, bool)>>>;
+
+    /// A simulated pin, recording when it was written.
+    struct Simulated {
+        name: &'static str,
+        writes: Writes,
+    }
+
+    impl Pin for Simulated {
+        fn write(&mut self, high: bool) -> io::Result<()> {
+            self.writes
+                .lock()
+                .unwrap()
+                .push((Instant::now(), self.name, high));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_gpio_pin() {
+        assert_eq!(
+            "17".parse(),
+            Ok(GpioPin {
+                chip: "gpiochip0".to_string(),
+                offset: 17
+            })
+        );
+        let pin: GpioPin = "gpiochip4:27".parse().unwrap();
+        assert_eq!(
+            (pin.chip_path().as_str(), pin.offset),
+            ("/dev/gpiochip4", 27)
+        );
+        assert!("GPIO17".parse::<GpioPin>().is_err());
+        assert!(":17".parse::<GpioPin>().is_err());
+    }
+
+    #[test]
+    fn test_simulated_keying() {
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let pin = |name| Simulated {
+            name,
+            writes: writes.clone(),
+        };
+        let output = GpioKey::new(pin("key"), Some(pin("ptt")), true).unwrap();
+        let options = KeyingOptions {
+            ptt: true,
+            lead_in: Duration::from_millis(30),
+            tail: Duration::from_millis(10),
+        };
+        let events = Timing::new(20, None).key_events("..");
+        let before = Instant::now();
+        keying::key(Box::new(output), &options, &events).unwrap();
+        let writes = writes.lock().unwrap();
+        let levels: Vec<(&str, bool)> =
+            writes.iter().map(|&(_, name, high)| (name, high)).collect();
+        // Active-low, so each pin idles high:
+        assert_eq!(
+            levels,
+            [
+                ("key", true),
+                ("ptt", true),
+                ("ptt", false),
+                ("key", false),
+                ("key", true),
+                ("key", false),
+                ("key", true),
+                ("ptt", true),
+                ("key", true),
+                ("ptt", true),
+            ]
+        );
+        // Each change is never early, from the start: the lead-in, the
+        // dits and their gap, and the tail:
+        for (i, ms) in [(3, 30), (4, 50), (5, 70), (6, 90), (7, 100)] {
+            let at = writes[i].0 - before;
+            assert!(at >= Duration::from_millis(ms), "{i}: {at:?}");
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use std::str::FromStr;
+
+/// How an iambic keyer ends a squeeze, when both paddles are let go.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// The element being sent is finished, and nothing more: only the
+    /// paddles held at the end of each element decide the next one.
+    A,
+    /// One more element is sent, the opposite of the one being sent:
+    /// the other paddle held at any time during an element is
+    /// remembered, and decides the next one.
+    B,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "a" => Ok(Mode::A),
+            "b" => Ok(Mode::B),
+            _ => Err(format!("Unknown iambic mode: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Paddle {
+    Dit,
+    Dah,
+}
+
+impl Paddle {
+    fn other(self) -> Paddle {
+        match self {
+            Paddle::Dit => Paddle::Dah,
+            Paddle::Dah => Paddle::Dit,
+        }
+    }
+}
+
+/// An element sent by the keyer, timed in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Element {
+    pub paddle: Paddle,
+    /// When its mark starts.
+    pub start: u64,
+    /// How long its mark is, and the space after it.
+    pub mark: u32,
+    pub space: u32,
+}
+
+/// An iambic keyer: from the presses and releases of its two paddles,
+/// it sends dits and dahs timed at its speed, alternating them while
+/// both paddles are squeezed.
+///
+/// It is driven by the paddle events and the time, in milliseconds
+/// from any start, given in order: an element ends (and the next one
+/// is chosen) once the time has passed its space, whether it is given
+/// by `poll` or by the next paddle event.
+pub struct IambicKeyer {
+    mode: Mode,
+    dot: u32,
+    /// The weight of the marks, 50 being one dot for a dit.
+    weight: u32,
+    dit: bool,
+    dah: bool,
+    // With mode B, whether the other paddle was held during the element:
+    memory: bool,
+    sending: Option<Element>,
+    // The elements started and not polled yet:
+    started: Vec<Element>,
+}
+
+impl IambicKeyer {
+    /// Creates an IambicKeyer.
+    ///
+    /// # Arguments
+    /// * `dot_duration` - The dot duration in milliseconds.
+    /// * `weight` - The weight of the marks [25..75]: at 50, a dit is one
+    ///   dot long, like the space after it; more makes the marks longer
+    ///   and their spaces shorter by as much, at the same speed.
+    pub fn new(mode: Mode, dot_duration: u32, weight: u32) -> Self {
+        Self {
+            mode,
+            dot: dot_duration.max(1),
+            weight: weight.clamp(25, 75),
+            dit: false,
+            dah: false,
+            memory: false,
+            sending: None,
+            started: Vec::new(),
+        }
+    }
+
+    fn held(&self, paddle: Paddle) -> bool {
+        match paddle {
+            Paddle::Dit => self.dit,
+            Paddle::Dah => self.dah,
+        }
+    }
+
+    /// Starts sending an element at some time.
+    fn start(&mut self, paddle: Paddle, at: u64) {
+        let length = match paddle {
+            Paddle::Dit => self.dot,
+            Paddle::Dah => self.dot * 3,
+        };
+        // The dot moved from the space to the mark, by the weight:
+        let shift = (self.dot as i64 * (self.weight as i64 - 50) / 50) as i32;
+        let element = Element {
+            paddle,
+            start: at,
+            mark: (length as i32 + shift) as u32,
+            space: (self.dot as i32 - shift) as u32,
+        };
+        self.memory = self.mode == Mode::B && self.held(paddle.other());
+        self.sending = Some(element);
+        self.started.push(element);
+    }
+
+    /// Ends the elements whose spaces are over by some time, starting
+    /// the next ones as the paddles ask.
+    fn advance(&mut self, now: u64) {
+        while let Some(element) = self.sending {
+            let end = element.start + (element.mark + element.space) as u64;
+            if end > now {
+                break;
+            }
+            let other = element.paddle.other();
+            let next = match (self.held(other) || self.memory, self.held(element.paddle)) {
+                (true, _) => Some(other),
+                (false, true) => Some(element.paddle),
+                (false, false) => None,
+            };
+            self.sending = None;
+            self.memory = false;
+            if let Some(paddle) = next {
+                self.start(paddle, end);
+            }
+        }
+    }
+
+    /// A paddle was pressed, at some time.
+    pub fn press(&mut self, paddle: Paddle, at: u64) {
+        self.advance(at);
+        match paddle {
+            Paddle::Dit => self.dit = true,
+            Paddle::Dah => self.dah = true,
+        }
+        match self.sending {
+            None => self.start(paddle, at),
+            Some(element) if element.paddle != paddle && self.mode == Mode::B => self.memory = true,
+            Some(_) => {}
+        }
+    }
+
+    /// A paddle was let go, at some time.
+    pub fn release(&mut self, paddle: Paddle, at: u64) {
+        self.advance(at);
+        match paddle {
+            Paddle::Dit => self.dit = false,
+            Paddle::Dah => self.dah = false,
+        }
+    }
+
+    /// The elements started by some time, since the last poll.
+    pub fn poll(&mut self, now: u64) -> Vec<Element> {
+        self.advance(now);
+        std::mem::take(&mut self.started)
+    }
+
+    /// When the element being sent ends, and the next one may start, or
+    /// None when nothing is sent, with both paddles let go.
+    pub fn next_change(&self) -> Option<u64> {
+        self.sending
+            .map(|element| element.start + (element.mark + element.space) as u64)
+    }
+}
+
+/// Turns the elements sent by a keyer into the durations of the marks
+/// (true) and of the spaces between them, in milliseconds.
+pub fn signals(elements: &[Element]) -> Vec<(bool, u32)> {
+    let mut signals = Vec::new();
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            let last = elements[i - 1];
+            let space = element.start.saturating_sub(last.start + last.mark as u64);
+            signals.push((false, space as u32));
+        }
+        signals.push((true, element.mark));
+    }
+    signals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(elements: &[Element]) -> String {
+        elements
+            .iter()
+            .map(|element| match element.paddle {
+                Paddle::Dit => '.',
+                Paddle::Dah => '-',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_single_paddle() {
+        let mut keyer = IambicKeyer::new(Mode::A, 60, 50);
+        keyer.press(Paddle::Dit, 1000);
+        // Dits repeat while it is held, one every two dots:
+        assert_eq!(code(&keyer.poll(1300)), "...");
+        keyer.release(Paddle::Dit, 1300);
+        // The third is finished, with its space:
+        assert_eq!(keyer.next_change(), Some(1360));
+        assert!(keyer.poll(2000).is_empty());
+        assert_eq!(keyer.next_change(), None);
+        keyer.press(Paddle::Dah, 2000);
+        keyer.release(Paddle::Dah, 2010);
+        let elements = keyer.poll(3000);
+        assert_eq!(
+            elements,
+            vec![Element {
+                paddle: Paddle::Dah,
+                start: 2000,
+                mark: 180,
+                space: 60
+            }]
+        );
+    }
+
+    #[test]
+    fn test_squeeze() {
+        // C: dah first, then both paddles squeezed, and let go during
+        // the second dit, which is sent from 600 to 660:
+        let squeeze = |mode| {
+            let mut keyer = IambicKeyer::new(mode, 60, 50);
+            keyer.press(Paddle::Dah, 0);
+            keyer.press(Paddle::Dit, 100);
+            keyer.release(Paddle::Dah, 620);
+            keyer.release(Paddle::Dit, 630);
+            code(&keyer.poll(2000))
+        };
+        assert_eq!(squeeze(Mode::A), "-.-.");
+        // Mode B sends one more, the dah that the dit was squeezed with:
+        assert_eq!(squeeze(Mode::B), "-.-.-");
+    }
+
+    #[test]
+    fn test_paddle_memory() {
+        // A dit tapped during a dah is sent after it with mode B only:
+        let tap = |mode| {
+            let mut keyer = IambicKeyer::new(mode, 60, 50);
+            keyer.press(Paddle::Dah, 0);
+            keyer.release(Paddle::Dah, 50);
+            keyer.press(Paddle::Dit, 100);
+            keyer.release(Paddle::Dit, 150);
+            code(&keyer.poll(1000))
+        };
+        assert_eq!(tap(Mode::A), "-");
+        assert_eq!(tap(Mode::B), "-.");
+    }
+
+    #[test]
+    fn test_weight() {
+        let mut keyer = IambicKeyer::new(Mode::B, 60, 60);
+        keyer.press(Paddle::Dit, 0);
+        keyer.press(Paddle::Dah, 10);
+        keyer.release(Paddle::Dit, 20);
+        keyer.release(Paddle::Dah, 20);
+        let elements = keyer.poll(1000);
+        // The marks are longer by a fifth of a dot, the spaces shorter:
+        assert_eq!(
+            signals(&elements),
+            vec![(true, 72), (false, 48), (true, 192)]
+        );
+        assert_eq!((elements[1].start, elements[1].space), (120, 48));
+        assert_eq!(
+            "c".parse::<Mode>(),
+            Err("Unknown iambic mode: c".to_string())
+        );
+    }
+}
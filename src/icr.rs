@@ -0,0 +1,480 @@
+use crate::export::wall_clock;
+use crate::fecr_quiz::QuizResult;
+use crate::input::{reaction, KeyReader, Keystroke};
+use crate::morse::MorsePlayer;
+use chrono::{DateTime, Local};
+use crossterm::event::KeyCode;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{stdout, Write};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+use tabled::settings::style::Style;
+use tabled::{Table, Tabled};
+use textwrap::wrap;
+
+/// How long an answer is still waited for after the window has closed,
+/// to time it, though it is a miss.
+const LATE: Duration = Duration::from_secs(2);
+
+/// How many of the last answers the pressure mode judges the accuracy
+/// of the window by.
+const RECENT: usize = 10;
+
+/// How far below the target the accuracy of the last answers has to
+/// fall for the pressure mode to widen the window again, in percent.
+const WIDEN_BELOW: f64 = 20.0;
+
+/// How many interquartile ranges above the third quartile a response
+/// time is an outlier in the records, as for the FECR quiz.
+pub const OUTLIER_IQR: f64 = 3.0;
+
+/// How the window of the pressure mode shrinks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pressure {
+    /// The narrowest it shrinks to.
+    pub min: Duration,
+    /// How much it shrinks, or widens again, at a time.
+    pub step: Duration,
+    /// The percentage of the last answers inside the window that
+    /// shrinks it.
+    pub target: f64,
+}
+
+/// How long after the end of a character its answer has to be typed.
+pub struct Window {
+    pub current: Duration,
+    // The window it started at, the widest it gets again:
+    start: Duration,
+    pressure: Option<Pressure>,
+    // Whether each of the last answers was right and inside the window:
+    recent: VecDeque<bool>,
+}
+
+impl Window {
+    pub fn new(window: Duration, pressure: Option<Pressure>) -> Self {
+        Self {
+            current: window,
+            start: window,
+            pressure,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Counts an answer. Under pressure, the window shrinks by a step
+    /// once `RECENT` answers in a row at its width are accurate enough,
+    /// and widens back by one when they are far from it.
+    ///
+    /// # Returns
+    /// The new window, if it changed.
+    pub fn answer(&mut self, hit: bool) -> Option<Duration> {
+        let pressure = self.pressure?;
+        self.recent.push_back(hit);
+        if self.recent.len() > RECENT {
+            self.recent.pop_front();
+        }
+        if self.recent.len() < RECENT {
+            return None;
+        }
+        let accuracy =
+            self.recent.iter().filter(|&&hit| hit).count() as f64 / RECENT as f64 * 100.0;
+        let window = match accuracy {
+            accuracy if accuracy >= pressure.target => {
+                self.current.saturating_sub(pressure.step).max(pressure.min)
+            }
+            accuracy if accuracy < pressure.target - WIDEN_BELOW => {
+                (self.current + pressure.step).min(self.start)
+            }
+            _ => self.current,
+        };
+        if window == self.current {
+            return None;
+        }
+        // Each width is judged by its own answers:
+        self.recent.clear();
+        self.current = window;
+        Some(window)
+    }
+}
+
+/// How one character was answered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Answer {
+    pub character: String,
+    /// The first character typed, if any, even after the window.
+    pub typed: Option<String>,
+    /// How long after the end of its sound it was typed.
+    pub time: Option<Duration>,
+    /// Whether it was typed before the sound had ended.
+    pub ahead: bool,
+    /// The window it had to be typed in.
+    pub window: Duration,
+    pub sent_at: DateTime<Local>,
+}
+
+impl Answer {
+    pub fn correct(&self) -> bool {
+        self.typed.as_deref() == Some(self.character.as_str())
+    }
+
+    pub fn in_window(&self) -> bool {
+        self.time.is_some_and(|time| time <= self.window)
+    }
+
+    /// Right, and in time: a late answer is a miss, even if right.
+    pub fn hit(&self) -> bool {
+        self.correct() && self.in_window()
+    }
+}
+
+/// The answers of a drill, as the results of a quiz, to record them the
+/// same way: a late answer is not a correct one.
+pub fn quiz_result(answers: &[Answer]) -> QuizResult {
+    QuizResult {
+        prompts: answers.iter().map(|a| a.character.clone()).collect(),
+        responses: answers.iter().map(|a| Some(a.hit())).collect(),
+        reaction_times: answers.iter().map(|a| a.time).collect(),
+        typed_ahead: answers.iter().map(|a| a.ahead).collect(),
+        typed: answers.iter().map(|a| a.typed.clone()).collect(),
+        sent_at: answers.iter().map(|a| a.sent_at).collect(),
+    }
+}
+
+/// The first character typed before a deadline.
+///
+/// # Returns
+/// The key, None if there was none in time, or Err if the drill was
+/// stopped.
+fn read_character(keys: &KeyReader, deadline: Instant) -> Result<Option<Keystroke>, ()> {
+    loop {
+        match keys.next_until(Some(deadline)) {
+            Ok(key) if key.code == KeyCode::Esc || key.interrupt() => return Err(()),
+            Ok(
+                key @ Keystroke {
+                    code: KeyCode::Char(_),
+                    ..
+                },
+            ) => return Ok(Some(key)),
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => return Ok(None),
+            Err(RecvTimeoutError::Disconnected) => return Err(()),
+        }
+    }
+}
+
+/// Runs the drill: the characters are sent one at a time, each to be
+/// typed within the window after its sound ends.
+///
+/// # Returns
+/// The answers, or None if the drill was stopped before it began.
+pub fn run_icr(
+    characters: &[String],
+    trials: u32,
+    dot_duration: u32,
+    tone_freq: f32,
+    mut window: Window,
+    rng: &mut impl Rng,
+) -> Option<Vec<Answer>> {
+    let paragraph = format!("Instant Character Recognition drill.\n\n{trials} characters will be played one at a time. Type each one as soon as you recognize it: an answer typed more than {}ms after the character ends is a miss, even if it is right.\n", window.current.as_millis())
+        + match window.pressure {
+            Some(_) => "\nThe window shrinks as your answers inside it get more accurate.\n",
+            None => "",
+        };
+    for line in wrap(&paragraph, 70) {
+        println!("{}", line);
+    }
+    let player = MorsePlayer::new();
+    println!("\nYou may stop the drill at any time by pressing the ESC key.\nTo begin the drill press the Enter key.");
+    let keys = KeyReader::start();
+    // Raw mode captures key presses without Enter:
+    let _raw = keys.raw_mode();
+    loop {
+        match keys.next() {
+            Some(key) if key.code == KeyCode::Enter => break,
+            Some(key) if key.code != KeyCode::Esc && !key.interrupt() => {}
+            _ => {
+                print!("\r\nDrill stopped.\r\n");
+                return None;
+            }
+        }
+    }
+    print!("Begin!\r\n\r\n");
+
+    let mut answers = Vec::new();
+    let mut stdout = stdout();
+    for _ in 0..trials {
+        std::thread::sleep(Duration::from_millis(700));
+        let character = characters.choose(rng).expect("No characters to drill");
+        // Forget any keys pressed between the characters:
+        keys.clear();
+        let (_sound, length) = player.start(character, dot_duration, tone_freq);
+        let sound_end = Instant::now() + length;
+        let deadline = sound_end + window.current;
+        let key = match read_character(&keys, deadline) {
+            Ok(Some(key)) => Some(key),
+            Ok(None) => {
+                // The window has closed, but a late answer is still timed:
+                print!("  too late ");
+                stdout.flush().unwrap();
+                match read_character(&keys, deadline + LATE) {
+                    Ok(key) => key,
+                    Err(()) => break,
+                }
+            }
+            Err(()) => break,
+        };
+        let (time, ahead) = match key {
+            Some(key) => {
+                let (time, ahead) = reaction(key.at, sound_end);
+                (Some(time), ahead)
+            }
+            None => (None, false),
+        };
+        let answer = Answer {
+            character: character.clone(),
+            typed: key.and_then(|key| match key.code {
+                KeyCode::Char(ch) => Some(ch.to_uppercase().collect()),
+                _ => None,
+            }),
+            time,
+            ahead,
+            window: window.current,
+            sent_at: wall_clock(sound_end),
+        };
+        let mark = match (&answer.typed, answer.correct(), answer.in_window()) {
+            (None, _, _) => "no answer".to_string(),
+            (Some(_), true, true) => "right".to_string(),
+            (Some(_), true, false) => "right, but late".to_string(),
+            (Some(typed), false, _) => format!("typed {typed}"),
+        };
+        let timing = time.map_or(String::new(), |time| format!(" ({}ms)", time.as_millis()));
+        print!("  {}: {mark}{timing}\r\n", answer.character);
+        if let Some(new) = window.answer(answer.hit()) {
+            print!("  The window is now {}ms.\r\n", new.as_millis());
+        }
+        answers.push(answer);
+    }
+    if answers.len() < trials as usize {
+        print!("\r\nDrill stopped.\r\n");
+    }
+    Some(answers)
+}
+
+/// The response times, as shares of their window, of `print_report`.
+const SHARES: [&str; 7] = [
+    "typed ahead",
+    "0-25% of the window",
+    "25-50%",
+    "50-75%",
+    "75-100%",
+    "late",
+    "no answer",
+];
+
+/// Which of the `SHARES` of the window an answer was typed in.
+fn share(answer: &Answer) -> usize {
+    match answer.time {
+        _ if answer.ahead => 0,
+        None => 6,
+        Some(_) if !answer.in_window() => 5,
+        Some(time) => {
+            let share = time.as_secs_f64() / answer.window.as_secs_f64().max(f64::EPSILON);
+            1 + ((share * 4.0) as usize).min(3)
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct ShareRow {
+    response_time: String,
+    answers: usize,
+    right: usize,
+    share: String,
+}
+
+#[derive(Tabled)]
+struct CharacterRow {
+    character: String,
+    count: usize,
+    in_window: String,
+    median_time: String,
+}
+
+/// Prints how many answers were right inside their window, and how the
+/// response times compare to it.
+///
+/// # Returns
+/// The tables, to be copied.
+pub fn print_report(answers: &[Answer]) -> String {
+    let total = answers.len().max(1);
+    let percent = |count: usize| format!("{:.0}%", count as f64 / total as f64 * 100.0);
+    let hits = answers.iter().filter(|a| a.hit()).count();
+    let late = answers
+        .iter()
+        .filter(|a| a.correct() && !a.in_window())
+        .count();
+    println!(
+        "\nRight inside the window: {hits} of {} ({})",
+        answers.len(),
+        percent(hits)
+    );
+    println!("Right, but too late: {late}");
+    if let (Some(first), Some(last)) = (answers.first(), answers.last()) {
+        match first.window == last.window {
+            true => println!("Window: {}ms", first.window.as_millis()),
+            false => println!(
+                "Window: {}ms, down to {}ms (narrowest {}ms)",
+                first.window.as_millis(),
+                last.window.as_millis(),
+                answers.iter().map(|a| a.window).min().unwrap().as_millis()
+            ),
+        }
+    }
+
+    let mut shares: Vec<ShareRow> = SHARES
+        .iter()
+        .map(|name| ShareRow {
+            response_time: name.to_string(),
+            answers: 0,
+            right: 0,
+            share: String::new(),
+        })
+        .collect();
+    for answer in answers {
+        let row = &mut shares[share(answer)];
+        row.answers += 1;
+        row.right += answer.correct() as usize;
+    }
+    for row in &mut shares {
+        row.share = format!(
+            "{} {}",
+            percent(row.answers),
+            "#".repeat(row.answers * 30 / total)
+        );
+    }
+    let shares = Table::new(&shares).with(Style::rounded()).to_string();
+    println!("\nResponse times, against the window:\n");
+    println!("{shares}");
+
+    let mut characters: BTreeMap<&str, Vec<&Answer>> = BTreeMap::new();
+    for answer in answers {
+        characters
+            .entry(&answer.character)
+            .or_default()
+            .push(answer);
+    }
+    let mut rows: Vec<(f64, CharacterRow)> = characters
+        .into_iter()
+        .map(|(character, answers)| {
+            let hits = answers.iter().filter(|a| a.hit()).count();
+            let mut times: Vec<Duration> = answers.iter().filter_map(|a| a.time).collect();
+            times.sort();
+            let rate = hits as f64 / answers.len() as f64;
+            let row = CharacterRow {
+                character: character.to_string(),
+                count: answers.len(),
+                in_window: format!("{:.0}%", rate * 100.0),
+                median_time: match times.is_empty() {
+                    true => "-".to_string(),
+                    false => format!(
+                        "{}ms",
+                        crate::distribution::percentile(&times, 0.5).as_millis()
+                    ),
+                },
+            };
+            (rate, row)
+        })
+        .collect();
+    // The weakest characters first:
+    rows.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let rows: Vec<CharacterRow> = rows.into_iter().map(|(_, row)| row).collect();
+    let characters = Table::new(&rows).with(Style::rounded()).to_string();
+    println!("\nCharacters, the weakest first:\n");
+    println!("{characters}");
+    format!("{shares}\n\n{characters}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn answer(typed: Option<&str>, time: Option<u64>) -> Answer {
+        Answer {
+            character: "K".to_string(),
+            typed: typed.map(String::from),
+            time: time.map(ms),
+            ahead: time == Some(0),
+            window: ms(800),
+            sent_at: Local::now(),
+        }
+    }
+
+    #[test]
+    fn test_window() {
+        let mut fixed = Window::new(ms(800), None);
+        assert_eq!(fixed.answer(true), None);
+        let pressure = Pressure {
+            min: ms(700),
+            step: ms(50),
+            target: 90.0,
+        };
+        let mut window = Window::new(ms(800), Some(pressure));
+        for _ in 0..RECENT - 1 {
+            assert_eq!(window.answer(true), None);
+        }
+        assert_eq!(window.answer(true), Some(ms(750)));
+        // 80% at 750ms holds it:
+        for hit in [true, true, false, true, true, true, false, true, true] {
+            assert_eq!(window.answer(hit), None);
+        }
+        assert_eq!(window.answer(true), None);
+        // Judged over the last 10, still 80%, then 90%:
+        assert_eq!(window.answer(true), None);
+        assert_eq!(window.answer(true), None);
+        assert_eq!(window.answer(true), Some(ms(700)));
+        for _ in 0..RECENT {
+            assert_eq!(window.answer(true), None);
+        }
+        // Never below the minimum, and held down to 70%:
+        assert_eq!(window.current, ms(700));
+        for _ in 0..3 {
+            assert_eq!(window.answer(false), None);
+        }
+        assert_eq!(window.answer(false), Some(ms(750)));
+    }
+
+    #[test]
+    fn test_answers() {
+        let answers = [
+            answer(Some("K"), Some(0)),
+            answer(Some("K"), Some(300)),
+            answer(Some("K"), Some(900)),
+            answer(Some("R"), Some(500)),
+            answer(None, None),
+        ];
+        assert_eq!(
+            answers.iter().map(share).collect::<Vec<_>>(),
+            [0, 2, 5, 3, 6]
+        );
+        assert!(answers[2].correct() && !answers[2].hit());
+        let quiz = quiz_result(&answers);
+        assert_eq!(
+            quiz.responses,
+            [
+                Some(true),
+                Some(true),
+                Some(false),
+                Some(false),
+                Some(false)
+            ]
+        );
+        assert_eq!(quiz.accuracy(), 40.0);
+        let tables = print_report(&answers);
+        assert!(tables.contains("no answer"));
+    }
+}
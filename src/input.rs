@@ -0,0 +1,542 @@
+use crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement};
+use std::io::{stdin, stdout, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// A key that was pressed, and when.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keystroke {
+    pub code: KeyCode,
+    pub at: Instant,
+    /// Whether Ctrl was held down.
+    pub ctrl: bool,
+    /// Whether the key was let go instead, only with `KeyReader::with_releases`.
+    pub released: bool,
+}
+
+impl Keystroke {
+    /// Ctrl-C, which doesn't interrupt the program in raw mode.
+    pub fn interrupt(&self) -> bool {
+        self.ctrl && self.code == KeyCode::Char('c')
+    }
+}
+
+/// Reads the name of a key that can pause a session, one that is never
+/// typed to copy: esc, tab, insert, home, end, or f1 to f12.
+pub fn parse_key(name: &str) -> Result<KeyCode, String> {
+    let function = name
+        .strip_prefix(['f', 'F'])
+        .and_then(|n| n.parse::<u8>().ok())
+        .filter(|n| (1..=12).contains(n));
+    match (name.to_lowercase().as_str(), function) {
+        (_, Some(n)) => Ok(KeyCode::F(n)),
+        ("esc", _) => Ok(KeyCode::Esc),
+        ("tab", _) => Ok(KeyCode::Tab),
+        ("insert", _) => Ok(KeyCode::Insert),
+        ("home", _) => Ok(KeyCode::Home),
+        ("end", _) => Ok(KeyCode::End),
+        _ => Err(format!("Unknown key: {name}")),
+    }
+}
+
+/// Reads the name of a key to key morse code with: space, an arrow
+/// (left, right, up or down), a character such as `k`, or one of the
+/// keys of `parse_key` except esc, which stops.
+pub fn parse_straight_key(name: &str) -> Result<KeyCode, String> {
+    let mut chars = name.chars();
+    match (name.to_lowercase().as_str(), chars.next(), chars.next()) {
+        ("space", _, _) => Ok(KeyCode::Char(' ')),
+        ("left", _, _) => Ok(KeyCode::Left),
+        ("right", _, _) => Ok(KeyCode::Right),
+        ("up", _, _) => Ok(KeyCode::Up),
+        ("down", _, _) => Ok(KeyCode::Down),
+        (_, Some(ch), None) => Ok(KeyCode::Char(ch.to_ascii_lowercase())),
+        ("esc", _, _) => Err("The ESC key stops the drill, choose another".to_string()),
+        _ => parse_key(name),
+    }
+}
+
+/// Reads the keys typed on a thread of its own, taking the time of each
+/// as soon as it arrives, so that they are timed precisely even while
+/// the sound is being played.
+///
+/// When stdin is not a terminal (e.g. a script piping in the answers),
+/// it is read a line at a time instead: each line is typed at once, and
+/// the end of the input stops like the ESC key.
+pub struct KeyReader {
+    receiver: Receiver<Keystroke>,
+    terminal: bool,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    // Whether the terminal was asked to report the releases:
+    enhanced: bool,
+}
+
+impl KeyReader {
+    pub fn start() -> Self {
+        Self::reading(false)
+    }
+
+    /// Starts reading the keys like `start`, and when they are let go
+    /// too, e.g. to key morse code with one. Only the terminals that
+    /// support the kitty keyboard protocol report the releases (on Unix),
+    /// and input that is piped in can't be keyed.
+    pub fn with_releases() -> Result<Self, String> {
+        if !stdin().is_terminal() {
+            return Err("Keying needs the input to be a terminal".to_string());
+        }
+        // The Windows console always reports the releases:
+        if cfg!(windows) {
+            return Ok(Self::reading(true));
+        }
+        if !supports_keyboard_enhancement().unwrap_or(false) {
+            return Err(
+                "This terminal doesn't report when keys are released: try kitty, foot, WezTerm or Ghostty"
+                    .to_string(),
+            );
+        }
+        execute!(
+            stdout(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )
+        .map_err(|e| format!("Could not ask the terminal for the key releases: {e}"))?;
+        let mut reader = Self::reading(true);
+        reader.enhanced = true;
+        Ok(reader)
+    }
+
+    fn reading(releases: bool) -> Self {
+        let (sender, receiver) = channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let terminal = stdin().is_terminal();
+        let thread = if terminal {
+            let stop = stop.clone();
+            Some(std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    // Poll, so that the thread can be stopped:
+                    match event::poll(Duration::from_millis(20)) {
+                        Ok(false) => continue,
+                        Ok(true) => {}
+                        Err(_) => break,
+                    }
+                    match event::read() {
+                        Ok(Event::Key(key))
+                            if key.kind == KeyEventKind::Press
+                                || (releases && key.kind == KeyEventKind::Release) =>
+                        {
+                            let keystroke = Keystroke {
+                                code: key.code,
+                                at: Instant::now(),
+                                ctrl: key.modifiers.contains(KeyModifiers::CONTROL),
+                                released: key.kind == KeyEventKind::Release,
+                            };
+                            if sender.send(keystroke).is_err() {
+                                break;
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(_) => break,
+                    }
+                }
+            }))
+        } else {
+            // Reading a line can't be interrupted, so the thread is left
+            // to end with the input:
+            std::thread::spawn(move || {
+                for line in stdin().lock().lines() {
+                    let Ok(line) = line else { break };
+                    let at = Instant::now();
+                    let keys = line.chars().map(KeyCode::Char).chain([KeyCode::Enter]);
+                    for code in keys {
+                        let (ctrl, released) = (false, false);
+                        if sender
+                            .send(Keystroke {
+                                code,
+                                at,
+                                ctrl,
+                                released,
+                            })
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                }
+                let _ = sender.send(Keystroke {
+                    code: KeyCode::Esc,
+                    at: Instant::now(),
+                    ctrl: false,
+                    released: false,
+                });
+            });
+            None
+        };
+        KeyReader {
+            receiver,
+            terminal,
+            stop,
+            thread,
+            enhanced: false,
+        }
+    }
+
+    /// Waits for the next key, or None once there are no more.
+    pub fn next(&self) -> Option<Keystroke> {
+        self.receiver.recv().ok()
+    }
+
+    /// The next key if one was typed already, without waiting.
+    #[allow(dead_code)]
+    pub fn try_next(&self) -> Option<Keystroke> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Waits for the next key until a deadline, if any.
+    pub fn next_until(&self, deadline: Option<Instant>) -> Result<Keystroke, RecvTimeoutError> {
+        match deadline {
+            Some(deadline) => self
+                .receiver
+                .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => self
+                .receiver
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        }
+    }
+
+    /// Forgets the keys typed so far, e.g. before a new prompt.
+    pub fn clear(&self) {
+        while self.receiver.try_recv().is_ok() {}
+    }
+
+    /// Forgets the keys typed so far like `clear`, except for those that
+    /// pause or stop the session.
+    pub fn clear_flow(&self, flow: &mut Flow) {
+        while let Ok(key) = self.receiver.try_recv() {
+            flow.key(&key, false);
+        }
+    }
+
+    /// Puts the terminal in raw mode, so that each key is read as it is
+    /// typed without being echoed, until the returned guard is dropped.
+    pub fn raw_mode(&self) -> RawMode {
+        let enabled = self.terminal
+            && enable_raw_mode()
+                .map_err(|e| eprintln!("Error enabling raw mode: {}", e))
+                .is_ok();
+        RawMode { enabled }
+    }
+}
+
+impl Drop for KeyReader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        if self.enhanced {
+            let _ = execute!(stdout(), PopKeyboardEnhancementFlags);
+        }
+    }
+}
+
+/// Keeps the terminal in raw mode while it is alive.
+pub struct RawMode {
+    enabled: bool,
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if self.enabled {
+            if let Err(e) = disable_raw_mode() {
+                eprintln!("Error disabling raw mode: {}", e);
+            }
+        }
+    }
+}
+
+/// Where a session is, as far as pausing and stopping it go.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlowState {
+    Running,
+    /// The pause key was pressed while a sound was playing: the session
+    /// pauses once it has been copied, before the next one.
+    Pausing,
+    /// Paused since then, until a key is pressed.
+    Paused(Instant),
+    Stopped,
+}
+
+/// Pauses and stops a session with the keys typed during it. Ctrl-C
+/// stops it at any time, and so does `q` while it is paused; any other
+/// key resumes it.
+pub struct Flow {
+    /// The key that pauses the session, or None for the ESC key to stop
+    /// it instead.
+    pause_key: Option<KeyCode>,
+    state: FlowState,
+    /// How long the session has been paused, overall.
+    paused: Duration,
+}
+
+impl Flow {
+    pub fn new(pause_key: Option<KeyCode>) -> Self {
+        Flow {
+            pause_key,
+            state: FlowState::Running,
+            paused: Duration::ZERO,
+        }
+    }
+
+    /// How long the session has been paused, to leave out of its times.
+    pub fn paused(&self) -> Duration {
+        self.paused
+    }
+
+    /// Goes on with a key typed, while a sound was playing or not.
+    ///
+    /// # Returns
+    /// Whether the key paused, resumed or stopped the session, rather
+    /// than being typed.
+    pub fn key(&mut self, key: &Keystroke, playing: bool) -> bool {
+        match self.state {
+            FlowState::Stopped => true,
+            _ if key.interrupt() => {
+                self.state = FlowState::Stopped;
+                true
+            }
+            FlowState::Paused(_) if key.code == KeyCode::Char('q') => {
+                self.state = FlowState::Stopped;
+                true
+            }
+            FlowState::Paused(since) => {
+                self.paused += key.at.saturating_duration_since(since);
+                self.state = FlowState::Running;
+                true
+            }
+            _ if self.pause_key.is_none() && key.code == KeyCode::Esc => {
+                self.state = FlowState::Stopped;
+                true
+            }
+            _ if self.pause_key == Some(key.code) => {
+                self.state = match playing {
+                    true => FlowState::Pausing,
+                    false => FlowState::Paused(key.at),
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Pauses, if it was asked to while a sound was playing, now that the
+    /// sound has been copied.
+    pub fn boundary(&mut self, now: Instant) {
+        if self.state == FlowState::Pausing {
+            self.state = FlowState::Paused(now);
+        }
+    }
+
+    /// Waits for a key while paused, until it resumes or stops.
+    ///
+    /// # Returns
+    /// Whether the session goes on.
+    pub fn wait(&mut self, keys: &KeyReader) -> bool {
+        if let FlowState::Paused(_) = self.state {
+            print!("\r\n  PAUSED \u{2014} press any key, or q to stop\r\n");
+            stdout().flush().unwrap();
+        }
+        while let FlowState::Paused(_) = self.state {
+            match keys.next() {
+                Some(key) => {
+                    self.key(&key, false);
+                }
+                None => self.state = FlowState::Stopped,
+            }
+        }
+        self.state != FlowState::Stopped
+    }
+}
+
+/// What was typed to copy something that was played.
+pub enum Copied {
+    /// The characters typed and when, and when Enter was pressed.
+    Typed(Vec<(char, Instant)>, Instant),
+    /// Enter wasn't pressed in time, which was up then.
+    TimedOut(Instant),
+    Stop,
+}
+
+/// Reads the keys typed until Enter is pressed, echoing them. Keys typed
+/// while the sound was still playing are kept with the time they were
+/// typed, and those typed after a pause with the time without it.
+///
+/// # Arguments
+/// * `sound_end` - When the sound being copied ends: the pause key pauses
+///   at once after it, and once it has been copied before.
+/// * `timeout` - How long after the end of the sound to wait for Enter,
+///   not counting the pauses, if not forever.
+/// * `typeahead` - Whether to take the keys typed while the sound was
+///   still playing, rather than ignore them.
+pub fn read_copy(
+    keys: &KeyReader,
+    flow: &mut Flow,
+    sound_end: Instant,
+    timeout: Option<Duration>,
+    typeahead: bool,
+) -> Copied {
+    let mut stdout = stdout();
+    let mut typed = Vec::new();
+    let paused = flow.paused();
+    loop {
+        let deadline = timeout.map(|timeout| sound_end + timeout + (flow.paused() - paused));
+        let key = match keys.next_until(deadline) {
+            Ok(key) => key,
+            Err(RecvTimeoutError::Timeout) => {
+                return Copied::TimedOut(sound_end + timeout.unwrap_or_default())
+            }
+            Err(RecvTimeoutError::Disconnected) => return Copied::Stop,
+        };
+        if flow.key(&key, key.at < sound_end) {
+            if !flow.wait(keys) {
+                return Copied::Stop;
+            }
+            if flow.paused() > paused {
+                let copied: String = typed.iter().map(|&(ch, _)| ch).collect();
+                print!("> {copied}");
+            }
+        } else if !typeahead && key.at < sound_end {
+            // Ignored, to wait for the end of the sound.
+        } else {
+            // The time it would have been, had it not been paused:
+            let at = key.at - (flow.paused() - paused);
+            match key.code {
+                KeyCode::Char(ch) => {
+                    typed.push((ch, at));
+                    print!("{ch}");
+                }
+                KeyCode::Backspace if typed.pop().is_some() => print!("\u{8} \u{8}"),
+                KeyCode::Enter => return Copied::Typed(typed, at),
+                _ => {}
+            }
+        }
+        stdout.flush().unwrap();
+    }
+}
+
+/// When a key was typed, compared with the end of the sound it answers.
+///
+/// # Returns
+/// How long after the end of the sound it was typed, and whether it was
+/// typed ahead, before the sound ended (counted as no time at all).
+pub fn reaction(at: Instant, sound_end: Instant) -> (Duration, bool) {
+    (at.saturating_duration_since(sound_end), at < sound_end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, at: Instant) -> Keystroke {
+        Keystroke {
+            code,
+            at,
+            ctrl: false,
+            released: false,
+        }
+    }
+
+    #[test]
+    fn test_pause_during_sound() {
+        let (start, ms) = (Instant::now(), Duration::from_millis);
+        let mut flow = Flow::new(Some(KeyCode::Esc));
+        assert!(flow.key(&press(KeyCode::Esc, start), true));
+        assert_eq!(flow.state, FlowState::Pausing);
+        // The copy goes on until the sound is copied:
+        assert!(!flow.key(&press(KeyCode::Char('k'), start + ms(100)), true));
+        assert!(!flow.key(&press(KeyCode::Enter, start + ms(900)), false));
+        flow.boundary(start + ms(1000));
+        assert_eq!(flow.state, FlowState::Paused(start + ms(1000)));
+        assert!(flow.key(&press(KeyCode::Char(' '), start + ms(4000)), false));
+        assert_eq!(flow.state, FlowState::Running);
+        assert_eq!(flow.paused(), ms(3000));
+    }
+
+    #[test]
+    fn test_pause_waiting_for_copy() {
+        let (start, ms) = (Instant::now(), Duration::from_millis);
+        let mut flow = Flow::new(Some(KeyCode::Tab));
+        // The ESC key isn't the pause key, and doesn't stop either:
+        assert!(!flow.key(&press(KeyCode::Esc, start), false));
+        assert!(flow.key(&press(KeyCode::Tab, start), false));
+        assert_eq!(flow.state, FlowState::Paused(start));
+        flow.key(&press(KeyCode::Char('x'), start + ms(500)), false);
+        assert!(flow.key(&press(KeyCode::Tab, start + ms(600)), false));
+        // `q` stops while paused, without counting the pause:
+        assert!(flow.key(&press(KeyCode::Char('q'), start + ms(2000)), false));
+        assert_eq!(flow.state, FlowState::Stopped);
+        assert_eq!(flow.paused(), ms(500));
+        // And is typed as any other key while not:
+        let mut flow = Flow::new(Some(KeyCode::Tab));
+        assert!(!flow.key(&press(KeyCode::Char('q'), start), false));
+    }
+
+    #[test]
+    fn test_stop_during_review() {
+        let (start, ms) = (Instant::now(), Duration::from_millis);
+        let ctrl_c = Keystroke {
+            ctrl: true,
+            ..press(KeyCode::Char('c'), start + ms(200))
+        };
+        // A review round, after a pause that was asked during a group:
+        let mut flow = Flow::new(Some(KeyCode::Esc));
+        flow.key(&press(KeyCode::Esc, start), true);
+        assert!(flow.key(&ctrl_c, true));
+        assert_eq!(flow.state, FlowState::Stopped);
+        // It stays stopped:
+        flow.boundary(start + ms(300));
+        assert!(flow.key(&press(KeyCode::Char('a'), start + ms(400)), false));
+        assert_eq!(flow.state, FlowState::Stopped);
+        // Without a pause key, the ESC key stops:
+        let mut flow = Flow::new(None);
+        assert!(flow.key(&press(KeyCode::Esc, start), true));
+        assert_eq!(flow.state, FlowState::Stopped);
+        assert_eq!(flow.paused(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_parse_straight_key() {
+        assert_eq!(parse_straight_key("space"), Ok(KeyCode::Char(' ')));
+        assert_eq!(parse_straight_key("K"), Ok(KeyCode::Char('k')));
+        assert_eq!(parse_straight_key("Left"), Ok(KeyCode::Left));
+        assert_eq!(parse_straight_key("f5"), Ok(KeyCode::F(5)));
+        assert!(parse_straight_key("esc").is_err());
+        assert!(parse_straight_key("enter").is_err());
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("esc"), Ok(KeyCode::Esc));
+        assert_eq!(parse_key("Tab"), Ok(KeyCode::Tab));
+        assert_eq!(parse_key("f5"), Ok(KeyCode::F(5)));
+        assert!(parse_key("f13").is_err());
+        assert!(parse_key("p").is_err());
+    }
+
+    #[test]
+    fn test_reaction() {
+        let end = Instant::now();
+        let ms = Duration::from_millis;
+        assert_eq!(reaction(end + ms(250), end), (ms(250), false));
+        assert_eq!(reaction(end, end), (Duration::ZERO, false));
+        assert_eq!(reaction(end - ms(40), end), (Duration::ZERO, true));
+    }
+}
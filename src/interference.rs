@@ -0,0 +1,170 @@
+use crate::callsign::random_callsign;
+use crate::codec::{encode, Separators};
+use crate::keyer::{Keyer, Mixer, Timing, SAMPLE_RATE};
+use crate::words::{read_words, WordPicker, WORDS};
+use rand::Rng;
+use std::str::FromStr;
+
+/// What an interfering station sends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Content {
+    /// Random callsigns.
+    Calls,
+    /// Common words.
+    Text,
+}
+
+impl FromStr for Content {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "calls" => Ok(Content::Calls),
+            "text" => Ok(Content::Text),
+            _ => Err(format!("Unknown interferer: {s}")),
+        }
+    }
+}
+
+/// When an interfering station starts, against the signal to copy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Start {
+    /// Already sending when the signal starts.
+    Before,
+    /// Breaking in while the signal is sent.
+    During,
+}
+
+impl FromStr for Start {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "before" => Ok(Start::Before),
+            "during" => Ok(Start::During),
+            _ => Err(format!("Unknown interferer start: {s}")),
+        }
+    }
+}
+
+/// How long an interferer starting before the signal is sent alone, at
+/// least and at most, in milliseconds.
+const LEAD: (u32, u32) = (1000, 2500);
+
+/// A station sending at the same time as the one to copy, at another
+/// pitch and level, for the practice of listening to one signal only.
+pub struct Interferer {
+    pub content: Content,
+    /// In Hz, from the pitch of the signal.
+    pub offset: f32,
+    /// In dB, from the level of the signal.
+    pub level: f32,
+    pub start: Start,
+    picker: WordPicker,
+}
+
+impl Interferer {
+    pub fn new(content: Content, offset: f32, level: f32, start: Start) -> Self {
+        Self {
+            content,
+            offset,
+            level,
+            start,
+            picker: WordPicker::new(read_words(WORDS, (2, 6))),
+        }
+    }
+
+    /// Some text sent by the interferer, long enough to go on for
+    /// `length` milliseconds at least at some timing.
+    fn text(&self, timing: Timing, length: u32, rng: &mut impl Rng) -> String {
+        let mut words: Vec<String> = Vec::new();
+        loop {
+            words.push(match self.content {
+                Content::Calls => random_callsign(rng),
+                Content::Text => self.picker.pick(rng).to_string(),
+            });
+            let code = encode(&words.join(" "), &Separators::default());
+            if timing
+                .key_events(&code)
+                .iter()
+                .map(|&(_, ms)| ms)
+                .sum::<u32>()
+                >= length
+            {
+                return words.join(" ");
+            }
+        }
+    }
+
+    /// Mixes the keyer of the signal to copy with the interferer.
+    ///
+    /// # Arguments
+    /// * `length` - How long the signal is, in milliseconds.
+    /// * `timing` - The timing of the signal, that of the interferer too.
+    /// * `tone` - The pitch of the signal, in Hz.
+    ///
+    /// # Returns
+    /// The mix, and how long after its start the signal starts, in
+    /// milliseconds.
+    pub fn mix(
+        &self,
+        signal: Keyer,
+        length: u32,
+        timing: Timing,
+        tone: f32,
+        rng: &mut impl Rng,
+    ) -> (Mixer, u32) {
+        let (lead, delay) = match self.start {
+            Start::Before => (rng.gen_range(LEAD.0..=LEAD.1), 0),
+            Start::During => (0, rng.gen_range(0..=length / 2)),
+        };
+        let text = self.text(timing, length + lead - delay, rng);
+        let code = encode(&text, &Separators::default());
+        let interferer = Keyer::new(
+            &timing.key_events(&code),
+            SAMPLE_RATE,
+            (tone + self.offset).max(100.0),
+            0.5,
+            5.0,
+        );
+        let mixer = Mixer::new(SAMPLE_RATE)
+            .with_voice(signal, lead, 1.0)
+            .with_voice(interferer, delay, 10f32.powf(self.level / 20.0));
+        (mixer, lead)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_interferer() {
+        assert_eq!("calls".parse::<Content>(), Ok(Content::Calls));
+        assert_eq!("during".parse::<Start>(), Ok(Start::During));
+        assert!("loud".parse::<Start>().is_err());
+        let mut rng = StdRng::seed_from_u64(3);
+        let timing = Timing::new(60, None);
+        let interferer = Interferer::new(Content::Text, 150.0, -6.0, Start::Before);
+        let text = interferer.text(timing, 5000, &mut rng);
+        let code = encode(&text, &Separators::default());
+        assert!(
+            timing
+                .key_events(&code)
+                .iter()
+                .map(|&(_, ms)| ms)
+                .sum::<u32>()
+                >= 5000
+        );
+        let signal = Keyer::new(&[(true, 100)], SAMPLE_RATE, 600.0, 0.5, 5.0);
+        let (mut mixer, lead) = interferer.mix(signal, 100, timing, 600.0, &mut rng);
+        assert!((LEAD.0..=LEAD.1).contains(&lead));
+        // The interferer alone before the signal, at half its level:
+        let mut out = vec![0.0; SAMPLE_RATE as usize * lead as usize / 1000];
+        assert_eq!(mixer.fill(&mut out), out.len());
+        let peak = out.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+        assert!((peak - 0.25).abs() < 0.01, "{peak}");
+    }
+}
@@ -0,0 +1,312 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Quotes a string for JSON.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            ch if ch.is_control() => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A number, or an Option of one, that can be written in JSON.
+pub trait Number: Copy {
+    /// The number as it is displayed, or None for null: when there is
+    /// none, or when it isn't finite.
+    fn to_json(self) -> Option<String>;
+
+    fn to_f64(self) -> Option<f64>;
+}
+
+macro_rules! number {
+    ($($t:ty),*) => {$(
+        impl Number for $t {
+            #[allow(clippy::unnecessary_cast)]
+            fn to_json(self) -> Option<String> {
+                (self as f64).is_finite().then(|| self.to_string())
+            }
+
+            fn to_f64(self) -> Option<f64> {
+                Some(self as f64)
+            }
+        }
+    )*};
+}
+
+number!(i32, i64, u32, u64, u128, usize, f32, f64);
+
+impl<N: Number> Number for Option<N> {
+    fn to_json(self) -> Option<String> {
+        self.and_then(N::to_json)
+    }
+
+    fn to_f64(self) -> Option<f64> {
+        self.and_then(N::to_f64)
+    }
+}
+
+/// Writes a JSON object a field at a time, e.g.
+/// `Object::new().string("char", "C").number("wpm", 22).end()`.
+///
+/// The keys are quoted, and a number that isn't finite is written as
+/// null, as JSON has no NaN or infinity. A field given None is null too.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Object {
+    fields: String,
+}
+
+impl Object {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn field(mut self, key: &str, value: Option<String>) -> Self {
+        if !self.fields.is_empty() {
+            self.fields.push(',');
+        }
+        self.fields.push_str(&quote(key));
+        self.fields.push(':');
+        self.fields.push_str(value.as_deref().unwrap_or("null"));
+        self
+    }
+
+    pub fn string<'a>(self, key: &str, value: impl Into<Option<&'a str>>) -> Self {
+        self.field(key, value.into().map(quote))
+    }
+
+    pub fn number(self, key: &str, value: impl Number) -> Self {
+        self.field(key, value.to_json())
+    }
+
+    /// A number written with this many decimals.
+    pub fn fixed(self, key: &str, value: impl Number, decimals: usize) -> Self {
+        let value = value.to_f64().filter(|value| value.is_finite());
+        self.field(key, value.map(|value| format!("{value:.decimals$}")))
+    }
+
+    pub fn bool(self, key: &str, value: bool) -> Self {
+        self.field(key, Some(value.to_string()))
+    }
+
+    /// A field of JSON already written, e.g. another object.
+    pub fn json(self, key: &str, value: impl Into<Option<String>>) -> Self {
+        self.field(key, value.into())
+    }
+
+    /// An array of JSON already written.
+    pub fn array(self, key: &str, items: impl IntoIterator<Item = String>) -> Self {
+        let items: Vec<String> = items.into_iter().collect();
+        self.field(key, Some(format!("[{}]", items.join(","))))
+    }
+
+    /// The object, as JSON.
+    pub fn end(self) -> String {
+        format!("{{{}}}", self.fields)
+    }
+}
+
+/// A JSON value, with just enough of JSON to keep the records of the
+/// practice in.
+#[derive(Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parses a whole JSON document, or None if it is not valid.
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut chars = text.trim().chars().peekable();
+        let json = Self::value(&mut chars)?;
+        chars.next().is_none().then_some(json)
+    }
+
+    fn value(chars: &mut Peekable<Chars>) -> Option<Json> {
+        while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+        let word = |chars: &mut Peekable<Chars>, word: &str, json| {
+            word.chars()
+                .all(|ch| chars.next() == Some(ch))
+                .then_some(json)
+        };
+        match chars.peek()? {
+            'n' => word(chars, "null", Json::Null),
+            't' => word(chars, "true", Json::Bool(true)),
+            'f' => word(chars, "false", Json::Bool(false)),
+            '"' => Self::string(chars).map(Json::String),
+            '[' => {
+                chars.next();
+                let mut items = Vec::new();
+                loop {
+                    while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+                    if items.is_empty() && chars.next_if_eq(&']').is_some() {
+                        return Some(Json::Array(items));
+                    }
+                    items.push(Self::value(chars)?);
+                    while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+                    match chars.next()? {
+                        ',' => {}
+                        ']' => return Some(Json::Array(items)),
+                        _ => return None,
+                    }
+                }
+            }
+            '{' => {
+                chars.next();
+                let mut fields = Vec::new();
+                loop {
+                    while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+                    if fields.is_empty() && chars.next_if_eq(&'}').is_some() {
+                        return Some(Json::Object(fields));
+                    }
+                    let key = Self::string(chars)?;
+                    while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+                    chars.next_if_eq(&':')?;
+                    fields.push((key, Self::value(chars)?));
+                    while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+                    match chars.next()? {
+                        ',' => {}
+                        '}' => return Some(Json::Object(fields)),
+                        _ => return None,
+                    }
+                }
+            }
+            _ => {
+                let mut number = String::new();
+                while let Some(ch) = chars.next_if(|ch| "+-.eE0123456789".contains(*ch)) {
+                    number.push(ch);
+                }
+                number.parse().ok().map(Json::Number)
+            }
+        }
+    }
+
+    fn string(chars: &mut Peekable<Chars>) -> Option<String> {
+        chars.next_if_eq(&'"')?;
+        let mut text = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(text),
+                '\\' => text.push(match chars.next()? {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    }
+                    ch => ch,
+                }),
+                ch => text.push(ch),
+            }
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// A whole number written as a string, which keeps all its digits
+    /// where a JSON number would keep only 53 bits of them, or, as the
+    /// older records have it, as a number.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::String(text) => text.parse().ok(),
+            Json::Number(number) => Some(*number as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let json =
+            Json::parse(r#" {"a": [1, -2.5e1, "\"x\u0041\n"], "b": {}, "c": null} "#).unwrap();
+        let items = json.get("a").and_then(Json::as_array).unwrap();
+        assert_eq!(items[1].as_f64(), Some(-25.0));
+        assert_eq!(items[2].as_str(), Some("\"xA\n"));
+        assert_eq!(json.get("b"), Some(&Json::Object(Vec::new())));
+        assert_eq!(json.get("c"), Some(&Json::Null));
+        assert_eq!(
+            Json::parse(&quote("a\"b\\c\u{1}")).unwrap().as_str(),
+            Some("a\"b\\c\u{1}")
+        );
+        assert_eq!(
+            Json::parse(&quote(&u64::MAX.to_string())).unwrap().as_u64(),
+            Some(u64::MAX)
+        );
+        assert_eq!(Json::parse("42").unwrap().as_u64(), Some(42));
+        assert_eq!(Json::parse(r#"{"a": 1"#), None);
+        assert_eq!(Json::parse(r#"[1, 2] 3"#), None);
+    }
+
+    #[test]
+    fn test_object() {
+        let object = Object::new()
+            .string("a \"key\"", "C")
+            .number("wpm", 22)
+            .number("snr_db", f32::NAN)
+            .number("gain", f64::NEG_INFINITY)
+            .number("none", None::<u32>)
+            .fixed("t", 1.5, 3)
+            .fixed("level", Some(f32::INFINITY), 4)
+            .bool("correct", true)
+            .json("band", Object::new().number("qrn", 0.5).end())
+            .array("items", ["1".to_string(), "2".to_string()])
+            .end();
+        assert_eq!(
+            object,
+            r#"{"a \"key\"":"C","wpm":22,"snr_db":null,"gain":null,"none":null,"t":1.500,"level":null,"correct":true,"band":{"qrn":0.5},"items":[1,2]}"#
+        );
+        assert!(Json::parse(&object).is_some());
+        assert_eq!(Object::new().end(), "{}");
+    }
+}
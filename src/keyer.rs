@@ -0,0 +1,496 @@
+use crate::band::Band;
+
+/// The sample rate that morse code is sent at.
+pub const SAMPLE_RATE: u32 = 48000;
+
+/// The lengths of the elements and gaps of morse code, in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timing {
+    pub dot: u32,
+    pub letter_gap: u32,
+    pub word_gap: u32,
+}
+
+impl Timing {
+    /// Finds the timing to send characters with the given dot duration.
+    ///
+    /// With Farnsworth timing, the characters are still sent at the
+    /// speed of the dot duration, but the gaps between them are made
+    /// longer, to send at an overall speed of `effective_wpm`.
+    pub fn new(dot_duration: u32, effective_wpm: Option<u32>) -> Self {
+        let dot = dot_duration.max(1);
+        let wpm = 1200.0 / dot as f32;
+        match effective_wpm {
+            Some(effective) if (effective as f32) < wpm => {
+                let effective = effective.max(1) as f32;
+                // The delay to add to each word, split between its 4 letter
+                // gaps (of 3 units) and its word gap (of 7 units):
+                let delay = (60.0 * wpm - 37.2 * effective) / (wpm * effective) * 1000.0;
+                Self {
+                    dot,
+                    letter_gap: (3.0 * delay / 19.0).round() as u32,
+                    word_gap: (7.0 * delay / 19.0).round() as u32,
+                }
+            }
+            _ => Self {
+                dot,
+                letter_gap: dot * 3,
+                word_gap: dot * 7,
+            },
+        }
+    }
+
+    /// Stretches the gaps between words by a factor, e.g. 2.0 for gaps
+    /// twice as long, to make the words stand out while learning.
+    pub fn with_word_space(self, factor: f32) -> Self {
+        Self {
+            word_gap: (self.word_gap as f32 * factor).round() as u32,
+            ..self
+        }
+    }
+
+    /// Finds when each character of morse code text ends, in
+    /// milliseconds from the start of its key events.
+    pub fn character_ends(&self, code: &str) -> Vec<u32> {
+        let mut ends = Vec::new();
+        let mut time = 0;
+        for (w, word) in code.split('/').map(str::trim).enumerate() {
+            if w > 0 {
+                time += self.word_gap;
+            }
+            for (l, letter) in word.split_whitespace().enumerate() {
+                if l > 0 {
+                    time += self.letter_gap;
+                }
+                for (e, element) in letter.chars().enumerate() {
+                    if e > 0 {
+                        time += self.dot;
+                    }
+                    time += match element {
+                        '.' => self.dot,
+                        '-' => self.dot * 3,
+                        _ => 0,
+                    };
+                }
+                ends.push(time);
+            }
+        }
+        ends
+    }
+
+    /// Turns morse code text (e.g. `-.-. --.- / -.. .`) into the times
+    /// the key is held down and up, in milliseconds.
+    pub fn key_events(&self, code: &str) -> Vec<(bool, u32)> {
+        let mut events = Vec::new();
+        for (w, word) in code.split('/').map(str::trim).enumerate() {
+            if w > 0 {
+                events.push((false, self.word_gap));
+            }
+            for (l, letter) in word.split_whitespace().enumerate() {
+                if l > 0 {
+                    events.push((false, self.letter_gap));
+                }
+                for (e, element) in letter.chars().enumerate() {
+                    if e > 0 {
+                        events.push((false, self.dot));
+                    }
+                    match element {
+                        '.' => events.push((true, self.dot)),
+                        '-' => events.push((true, self.dot * 3)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        events
+    }
+}
+
+/// Synthesizes the sound of a key, a block of samples at a time.
+///
+/// Each tone fades in and out over the rise time, so that it doesn't
+/// click when the key goes down and up.
+#[allow(dead_code)]
+pub struct Keyer {
+    // The number of samples of each key down (true) and up:
+    events: Vec<(bool, usize)>,
+    sample_rate: u32,
+    tone_freq: f32,
+    volume: f32,
+    rise_samples: usize,
+    index: usize,
+    position: usize,
+    phase: f32,
+    band: Option<Band>,
+}
+
+#[allow(dead_code)]
+impl Keyer {
+    /// Creates a Keyer.
+    ///
+    /// # Arguments
+    /// * `events` - The times the key is held down and up, in milliseconds.
+    /// * `volume` - The peak amplitude of the tone [0.0..1.0].
+    /// * `rise_time` - How long each tone fades in and out, in milliseconds.
+    pub fn new(
+        events: &[(bool, u32)],
+        sample_rate: u32,
+        tone_freq: f32,
+        volume: f32,
+        rise_time: f32,
+    ) -> Self {
+        let samples = |ms: f32| (ms * sample_rate as f32 / 1000.0).round() as usize;
+        Self {
+            events: events
+                .iter()
+                .map(|&(down, duration)| (down, samples(duration as f32)))
+                .collect(),
+            sample_rate,
+            tone_freq,
+            volume,
+            rise_samples: samples(rise_time),
+            index: 0,
+            position: 0,
+            phase: 0.0,
+            band: None,
+        }
+    }
+
+    /// Sends the sound through the noise, static crashes and fading of
+    /// a band, if there is one.
+    pub fn with_band(self, band: Option<Band>) -> Self {
+        Self { band, ..self }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Whether all of the samples have been written.
+    pub fn is_done(&self) -> bool {
+        self.index >= self.events.len()
+    }
+
+    /// Writes silence into `out`, with the noise of the band if there
+    /// is one, e.g. before and after the code.
+    pub fn fill_silence(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        if let Some(band) = &self.band {
+            band.apply(out, self.volume, self.sample_rate);
+        }
+    }
+
+    /// Writes the next samples into `out`.
+    ///
+    /// # Returns
+    /// The number of samples written, less than the size of `out` only
+    /// once the last of them was written.
+    pub fn fill(&mut self, out: &mut [f32]) -> usize {
+        let step = 2.0 * std::f32::consts::PI * self.tone_freq / self.sample_rate as f32;
+        let mut written = 0;
+        while written < out.len() && !self.is_done() {
+            let (down, length) = self.events[self.index];
+            let count = (length - self.position).min(out.len() - written);
+            for sample in &mut out[written..written + count] {
+                *sample = if down {
+                    // Fade in and out, even when the tone is shorter than that:
+                    let rise = self.rise_samples.min(length / 2);
+                    let edge = self.position.min(length - self.position);
+                    let gain = if edge < rise {
+                        0.5 - 0.5 * (std::f32::consts::PI * edge as f32 / rise as f32).cos()
+                    } else {
+                        1.0
+                    };
+                    self.volume * gain * self.phase.sin()
+                } else {
+                    0.0
+                };
+                self.phase = (self.phase + step) % (2.0 * std::f32::consts::PI);
+                self.position += 1;
+            }
+            written += count;
+            if self.position == length {
+                self.index += 1;
+                self.position = 0;
+            }
+        }
+        if let Some(band) = &self.band {
+            band.apply(&mut out[..written], self.volume, self.sample_rate);
+        }
+        written
+    }
+}
+
+/// One of the keyers of a mix, starting some samples into it.
+struct Voice {
+    keyer: Keyer,
+    start: usize,
+    gain: f32,
+}
+
+/// Mixes the sounds of several keyers, e.g. of the stations of a
+/// pileup, each with its own timeline starting when it should, at its
+/// own level.
+pub struct Mixer {
+    voices: Vec<Voice>,
+    sample_rate: u32,
+    // The samples written so far:
+    position: usize,
+    block: Vec<f32>,
+    band: Option<Band>,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            voices: Vec::new(),
+            sample_rate,
+            position: 0,
+            block: Vec::new(),
+            band: None,
+        }
+    }
+
+    /// Adds a keyer to the mix, at the same sample rate.
+    ///
+    /// # Arguments
+    /// * `delay` - How long after the start of the mix it starts, in
+    ///   milliseconds.
+    /// * `gain` - How much louder than its volume it is mixed, e.g. 0.5
+    ///   for 6 dB quieter.
+    pub fn with_voice(mut self, keyer: Keyer, delay: u32, gain: f32) -> Self {
+        let start = (delay as u64 * self.sample_rate as u64 / 1000) as usize;
+        self.voices.push(Voice { keyer, start, gain });
+        self
+    }
+
+    /// Sends the mix through the noise, static crashes and fading of a
+    /// band, measured against the first of its keyers.
+    pub fn with_band(self, band: Option<Band>) -> Self {
+        Self { band, ..self }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Whether all of the keyers have been written.
+    pub fn is_done(&self) -> bool {
+        self.voices
+            .iter()
+            .all(|voice| voice.start <= self.position && voice.keyer.is_done())
+    }
+
+    /// Writes the next samples of the mix into `out`.
+    ///
+    /// # Returns
+    /// The number of samples written, less than the size of `out` only
+    /// once the last of them was written.
+    pub fn fill(&mut self, out: &mut [f32]) -> usize {
+        out.fill(0.0);
+        self.block.resize(out.len(), 0.0);
+        let mut end = 0;
+        for voice in &mut self.voices {
+            let skip = voice.start.saturating_sub(self.position).min(out.len());
+            let count = voice.keyer.fill(&mut self.block[..out.len() - skip]);
+            for (sample, voiced) in out[skip..skip + count].iter_mut().zip(&self.block) {
+                *sample += voice.gain * voiced;
+            }
+            end = end.max(skip + count);
+        }
+        let written = match self.voices.iter().all(|voice| voice.keyer.is_done()) {
+            true => end,
+            false => out.len(),
+        };
+        self.position += written;
+        for sample in &mut out[..written] {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+        if let (Some(band), Some(voice)) = (&self.band, self.voices.first()) {
+            band.apply(
+                &mut out[..written],
+                voice.gain * voice.keyer.volume,
+                self.sample_rate,
+            );
+        }
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::band::Conditions;
+    use crate::codec::{encode, Separators};
+    use crate::detect::TextOptions;
+    use crate::filter::Detector;
+    use crate::wav::decode_samples;
+
+    #[test]
+    fn test_timing() {
+        let timing = Timing::new(60, None);
+        assert_eq!(
+            timing.key_events("-.- / ."),
+            vec![
+                (true, 180),
+                (false, 60),
+                (true, 60),
+                (false, 60),
+                (true, 180),
+                (false, 420),
+                (true, 60),
+            ]
+        );
+        assert_eq!(timing.key_events(".. -"), timing.key_events("..  -  "));
+        // An effective speed that isn't slower changes nothing:
+        assert_eq!(Timing::new(60, Some(25)), timing);
+    }
+
+    #[test]
+    fn test_character_ends() {
+        let timing = Timing::new(60, Some(10));
+        assert_eq!(timing.character_ends("-.- / ."), vec![540, 540 + 1525 + 60]);
+        let code = encode("CQ DE K1ABC", &Separators::default());
+        let length: u32 = timing.key_events(&code).iter().map(|&(_, ms)| ms).sum();
+        assert_eq!(timing.character_ends(&code).len(), 9);
+        assert_eq!(timing.character_ends(&code).last(), Some(&length));
+    }
+
+    #[test]
+    fn test_farnsworth_timing() {
+        // 20 WPM characters, at 10 WPM overall:
+        assert_eq!(
+            Timing::new(60, Some(10)),
+            Timing {
+                dot: 60,
+                letter_gap: 654,
+                word_gap: 1525,
+            }
+        );
+    }
+
+    #[test]
+    fn test_farnsworth_timeline() {
+        // The standard word PARIS, and the gap after it, are 50 dots. At
+        // 25 WPM characters and 12 WPM overall, the gaps follow the ARRL
+        // formula ta = (60c - 37.2s) / sc seconds, 3/19 of it for each
+        // letter gap and 7/19 for the word gap:
+        let code = encode("PARIS PARIS", &Separators::default());
+        let events = Timing::new(48, Some(12)).key_events(&code);
+        let ta: f32 = (60.0 * 25.0 - 37.2 * 12.0) / (12.0 * 25.0) * 1000.0;
+        let gaps: Vec<u32> = events
+            .iter()
+            .filter(|&&(down, length)| !down && length > 48)
+            .map(|&(_, length)| length)
+            .collect();
+        let letter_gap = (3.0 * ta / 19.0).round() as u32;
+        let word_gap = (7.0 * ta / 19.0).round() as u32;
+        assert_eq!(gaps.len(), 9);
+        assert!(gaps[..4].iter().all(|&gap| gap == letter_gap));
+        assert_eq!(gaps[4], word_gap);
+        // One word and its gap take a minute over 12 words:
+        let end = events.iter().position(|&event| event == (false, word_gap));
+        let word: u32 = events[..=end.unwrap()]
+            .iter()
+            .map(|&(_, length)| length)
+            .sum();
+        assert!(word.abs_diff(5000) <= 5, "{word}");
+    }
+
+    #[test]
+    fn test_word_space() {
+        let timing = Timing::new(60, None).with_word_space(2.0);
+        assert_eq!(timing.letter_gap, 180);
+        assert_eq!(timing.word_gap, 840);
+        assert_eq!(
+            Timing::new(60, Some(10)).with_word_space(1.5).word_gap,
+            2288
+        );
+    }
+
+    #[test]
+    fn test_keyer_envelope() {
+        // 10ms of tone at 1kHz, with a 2ms rise, then 5ms of silence:
+        let mut keyer = Keyer::new(&[(true, 10), (false, 5)], 8000, 1000.0, 0.5, 2.0);
+        let mut out = vec![1.0; 130];
+        assert_eq!(keyer.fill(&mut out[..50]), 50);
+        assert_eq!(keyer.fill(&mut out[50..]), 70);
+        assert!(keyer.is_done());
+        assert_eq!(keyer.fill(&mut out), 0);
+        assert_eq!(out[0], 0.0);
+        let peak = |samples: &[f32]| samples.iter().fold(0.0_f32, |a, s| a.max(s.abs()));
+        assert!(peak(&out[..8]) < 0.5);
+        assert!((peak(&out[16..64]) - 0.5).abs() < 0.01);
+        assert!(peak(&out[72..80]) < 0.5);
+        assert_eq!(peak(&out[80..120]), 0.0);
+    }
+
+    #[test]
+    fn test_keyer_band() {
+        let conditions = Conditions::new(Some(0.0), None, None, Some(3));
+        let mut keyer = Keyer::new(&[(true, 10), (false, 10)], 8000, 1000.0, 0.5, 2.0)
+            .with_band(conditions.map(Band::new));
+        let mut out = vec![0.0; 160];
+        assert_eq!(keyer.fill(&mut out), 160);
+        // The key up is heard in the noise:
+        assert!(out[80..].iter().any(|s| s.abs() > 0.05));
+        let mut silence = vec![1.0; 80];
+        keyer.fill_silence(&mut silence);
+        assert!(silence.iter().all(|s| s.abs() < 1.0) && silence.iter().any(|s| *s != 0.0));
+    }
+
+    #[test]
+    fn test_mixer() {
+        // Two tones, the second 5ms in and at half the level:
+        let keyer = |tone| Keyer::new(&[(true, 10)], 8000, tone, 0.5, 0.0);
+        let mut mixer = Mixer::new(8000)
+            .with_voice(keyer(1000.0), 0, 1.0)
+            .with_voice(keyer(2000.0), 5, 0.5);
+        let mut out = vec![0.0; 200];
+        assert_eq!(mixer.fill(&mut out[..60]), 60);
+        assert!(!mixer.is_done());
+        assert_eq!(mixer.fill(&mut out[60..]), 60);
+        assert!(mixer.is_done());
+        let mut first = vec![0.0; 80];
+        keyer(1000.0).fill(&mut first);
+        let mut second = vec![0.0; 80];
+        keyer(2000.0).fill(&mut second);
+        assert_eq!(out[..40], first[..40]);
+        for i in 40..80 {
+            assert!((out[i] - first[i] - 0.5 * second[i - 40]).abs() < 1e-6);
+        }
+        for i in 80..120 {
+            assert!((out[i] - 0.5 * second[i - 40]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_keyer_loopback() {
+        let code = encode("CQ DE K1ABC", &Separators::default());
+        let events = Timing::new(50, Some(18)).key_events(&code);
+        let mut keyer = Keyer::new(&events, SAMPLE_RATE, 700.0, 0.5, 5.0);
+        let mut samples = Vec::new();
+        let mut block = [0.0; 512];
+        while !keyer.is_done() {
+            let count = keyer.fill(&mut block);
+            samples.extend_from_slice(&block[..count]);
+        }
+        samples.resize(samples.len() + SAMPLE_RATE as usize * 2, 0.0);
+        let messages = decode_samples(
+            &samples,
+            SAMPLE_RATE,
+            700.0,
+            None,
+            200.0,
+            Some(0.3),
+            12,
+            Detector::Goertzel,
+            50,
+            5000,
+            false,
+            TextOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(messages, vec!["CQ DE K1ABC"]);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// What a transmitter is keyed with, e.g. a line of a serial port or a
+/// GPIO pin for each signal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Signal {
+    Key,
+    Ptt,
+}
+
+/// Something keying a transmitter, that releases its signals when it is
+/// dropped, even by a panic.
+pub trait Output: Send {
+    fn set(&mut self, signal: Signal, raised: bool) -> io::Result<()>;
+}
+
+/// How the PTT is raised around the marks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyingOptions {
+    /// Whether to raise the PTT while sending.
+    pub ptt: bool,
+    /// How long the PTT is raised before the first mark.
+    pub lead_in: Duration,
+    /// How long the PTT stays raised after the last mark.
+    pub tail: Duration,
+}
+
+/// When each signal goes up or down, from the start of the sending,
+/// for the key events of the keyer (`Timing::key_events`).
+fn schedule(events: &[(bool, u32)], options: &KeyingOptions) -> Vec<(Duration, Signal, bool)> {
+    let mut changes = Vec::new();
+    let mut time = Duration::ZERO;
+    if options.ptt {
+        changes.push((time, Signal::Ptt, true));
+        time += options.lead_in;
+    }
+    for &(down, ms) in events {
+        if down {
+            changes.push((time, Signal::Key, true));
+        }
+        time += Duration::from_millis(ms as u64);
+        if down {
+            changes.push((time, Signal::Key, false));
+        }
+    }
+    if options.ptt {
+        changes.push((time + options.tail, Signal::Ptt, false));
+    }
+    changes
+}
+
+/// Set by Ctrl-C while keying, to release the signals and stop.
+static ABORTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn abort_keying(_signal: libc::c_int) {
+    ABORTED.store(true, Ordering::SeqCst);
+}
+
+/// Runs something that Ctrl-C stops (as `aborted` tells it) rather than
+/// ending the program, e.g. the keying of a transmitter, to release it.
+pub fn abortable<T>(f: impl FnOnce() -> T) -> T {
+    ABORTED.store(false, Ordering::SeqCst);
+    #[cfg(unix)]
+    let previous = unsafe {
+        libc::signal(
+            libc::SIGINT,
+            abort_keying as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        )
+    };
+    let result = f();
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGINT, previous);
+    }
+    result
+}
+
+/// Whether Ctrl-C was pressed, within `abortable`.
+pub fn aborted() -> bool {
+    ABORTED.load(Ordering::SeqCst)
+}
+
+/// Waits until the deadline, sleeping until just before it and then
+/// spinning, so that the element boundaries don't drift with the
+/// scheduler.
+///
+/// # Returns
+/// Whether the keying was aborted meanwhile.
+fn wait_until(deadline: Instant) -> bool {
+    loop {
+        if aborted() {
+            return true;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        let left = deadline - now;
+        if left > Duration::from_millis(2) {
+            // Short enough to notice an abort soon:
+            std::thread::sleep((left - Duration::from_millis(1)).min(Duration::from_millis(10)));
+        } else {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+/// Makes the changes of the signals at their times from the start.
+///
+/// # Returns
+/// Whether all of them were made, or false if aborted.
+fn run(changes: &[(Duration, Signal, bool)], output: &mut dyn Output) -> io::Result<bool> {
+    let start = Instant::now();
+    for &(at, signal, raised) in changes {
+        if wait_until(start + at) {
+            return Ok(false);
+        }
+        output.set(signal, raised)?;
+    }
+    Ok(true)
+}
+
+/// Keys the output for the key events of the keyer
+/// (`Timing::key_events`), raising the PTT around them with `ptt`,
+/// until all of it was sent or Ctrl-C is pressed.
+///
+/// The keying runs on its own thread, each change of the signals being
+/// scheduled from the start against the monotonic clock, so that the
+/// lateness of one doesn't add up in the ones after it.
+pub fn key(
+    mut output: Box<dyn Output>,
+    options: &KeyingOptions,
+    events: &[(bool, u32)],
+) -> io::Result<()> {
+    let changes = schedule(events, options);
+    let result = abortable(|| std::thread::spawn(move || run(&changes, output.as_mut())).join());
+    // The signals were released as the output was dropped, whatever
+    // happened:
+    match result {
+        Ok(Ok(true)) => Ok(()),
+        Ok(Ok(false)) => {
+            info!("Keying aborted");
+            Ok(())
+        }
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(io::Error::other("The keying thread panicked")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyer::Timing;
+
+    /// Records when each signal was set.
+    struct Recorder {
+        start: Instant,
+        made: Vec<(Duration, Signal, bool)>,
+    }
+
+    impl Output for Recorder {
+        fn set(&mut self, signal: Signal, raised: bool) -> io::Result<()> {
+            self.made.push((self.start.elapsed(), signal, raised));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_schedule() {
+        let ms = Duration::from_millis;
+        let events = Timing::new(60, None).key_events("-.");
+        let mut options = KeyingOptions {
+            ptt: false,
+            lead_in: ms(50),
+            tail: ms(20),
+        };
+        assert_eq!(
+            schedule(&events, &options),
+            vec![
+                (ms(0), Signal::Key, true),
+                (ms(180), Signal::Key, false),
+                (ms(240), Signal::Key, true),
+                (ms(300), Signal::Key, false),
+            ]
+        );
+        options.ptt = true;
+        let changes = schedule(&events, &options);
+        assert_eq!(changes[0], (ms(0), Signal::Ptt, true));
+        assert_eq!(changes[1], (ms(50), Signal::Key, true));
+        assert_eq!(changes.last(), Some(&(ms(370), Signal::Ptt, false)));
+    }
+
+    #[test]
+    fn test_run() {
+        let ms = Duration::from_millis;
+        let changes = [
+            (ms(0), Signal::Ptt, true),
+            (ms(20), Signal::Key, true),
+            (ms(40), Signal::Key, false),
+            (ms(60), Signal::Ptt, false),
+        ];
+        let mut recorder = Recorder {
+            start: Instant::now(),
+            made: Vec::new(),
+        };
+        assert!(run(&changes, &mut recorder).unwrap());
+        assert_eq!(recorder.made.len(), changes.len());
+        for ((at, signal, raised), (made_at, made_signal, made_raised)) in
+            changes.iter().zip(&recorder.made)
+        {
+            assert_eq!((signal, raised), (made_signal, made_raised));
+            assert!(made_at >= at, "{made_at:?} is before {at:?}");
+        }
+    }
+}
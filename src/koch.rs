@@ -0,0 +1,149 @@
+use crate::fecr_quiz::QuizResult;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The order that the characters are learned in, two of them in the
+/// first lesson and one more in each lesson after that.
+pub const KOCH_ORDER: &str = "KMURESNAPTLWI.JZ=FOY,VG5/Q92H38B?47C1D60X";
+
+/// The last lesson, which has all of the characters.
+pub fn last_lesson() -> u32 {
+    KOCH_ORDER.chars().count() as u32 - 1
+}
+
+/// The characters practiced in a lesson (1 to `last_lesson()`).
+pub fn lesson_characters(lesson: u32) -> String {
+    KOCH_ORDER.chars().take(lesson as usize + 1).collect()
+}
+
+/// Where the lessons are recorded, unless given with --history.
+pub fn default_history_path() -> Option<PathBuf> {
+    crate::profile::path("koch-history.txt")
+}
+
+/// Describes one session for the history.
+fn history_line(date: &str, lesson: u32, accuracy: f64, passed: bool) -> String {
+    let result = match passed {
+        true if lesson < last_lesson() => format!("passed, next lesson {}", lesson + 1),
+        true => "passed".to_string(),
+        false => "repeat".to_string(),
+    };
+    format!("{date}\tlesson {lesson}\t{accuracy:.0}%\t{result}")
+}
+
+/// The lesson to practice next, going by the last one in the history:
+/// the one after it if it was passed, or the first if there is none.
+pub fn current_lesson(history: &Path) -> u32 {
+    let text = std::fs::read_to_string(history).unwrap_or_default();
+    text.lines()
+        .rev()
+        .find_map(|line| {
+            let mut fields = line.split('\t').skip(1);
+            let lesson: u32 = fields.next()?.strip_prefix("lesson ")?.parse().ok()?;
+            let passed = fields.nth(1)?.starts_with("passed");
+            Some(match passed {
+                true => (lesson + 1).min(last_lesson()),
+                false => lesson,
+            })
+        })
+        .unwrap_or(1)
+}
+
+/// Appends a session to the history file.
+fn record_session(path: &Path, lesson: u32, accuracy: f64, passed: bool) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let date = chrono::Local::now().format("%Y-%m-%d %H:%M").to_string();
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", history_line(&date, lesson, accuracy, passed))
+}
+
+/// Suggests the next lesson when the session was accurate enough, and
+/// records the session in the history.
+///
+/// # Arguments
+/// * `pass_accuracy` - The accuracy in percent needed to move on.
+pub fn finish_lesson(
+    results: &QuizResult,
+    lesson: u32,
+    pass_accuracy: f64,
+    history: Option<&Path>,
+) {
+    if results.prompts.is_empty() {
+        return;
+    }
+    let accuracy = results.accuracy();
+    let passed = accuracy > pass_accuracy;
+    println!("\nLesson {lesson} accuracy: {accuracy:.0}%");
+    if passed && lesson < last_lesson() {
+        println!(
+            "Above {pass_accuracy:.0}%, you are ready for lesson {}:",
+            lesson + 1
+        );
+        println!(
+            "\n   {} practice --koch {}",
+            env!("CARGO_BIN_NAME"),
+            lesson + 1
+        );
+    } else if passed {
+        println!("You have learned all of the characters!");
+    } else {
+        println!("Keep practicing lesson {lesson} until it is above {pass_accuracy:.0}%.");
+    }
+
+    if let Some(path) = history {
+        if let Err(e) = record_session(path, lesson, accuracy, passed) {
+            eprintln!("Could not record the session in {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lesson_characters() {
+        assert_eq!(lesson_characters(1), "KM");
+        assert_eq!(lesson_characters(5), "KMURES");
+        assert_eq!(last_lesson(), 40);
+        assert_eq!(lesson_characters(last_lesson()), KOCH_ORDER);
+    }
+
+    #[test]
+    fn test_current_lesson() {
+        let path = std::env::temp_dir().join(format!("koch-{}.txt", std::process::id()));
+        assert_eq!(current_lesson(&path), 1);
+        let history = [
+            history_line("2024-11-02 18:30", 3, 95.0, true),
+            history_line("2024-11-03 18:30", 4, 80.0, false),
+        ];
+        std::fs::write(&path, history.join("\n") + "\nnot a lesson\n").unwrap();
+        assert_eq!(current_lesson(&path), 4);
+        std::fs::write(&path, &history[0]).unwrap();
+        assert_eq!(current_lesson(&path), 4);
+        std::fs::write(&path, history_line("2024-11-04 18:30", 40, 99.0, true)).unwrap();
+        assert_eq!(current_lesson(&path), 40);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_history_line() {
+        assert_eq!(
+            history_line("2024-11-02 18:30", 1, 80.0, false),
+            "2024-11-02 18:30\tlesson 1\t80%\trepeat"
+        );
+        assert_eq!(
+            history_line("2024-11-02 18:30", 1, 92.5, true),
+            "2024-11-02 18:30\tlesson 1\t92%\tpassed, next lesson 2"
+        );
+        assert_eq!(
+            history_line("2024-11-02 18:30", 40, 95.0, true),
+            "2024-11-02 18:30\tlesson 40\t95%\tpassed"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use crate::adif::{AdifFile, AdifRecord};
+use crate::callsign::callsign_regex;
+use crate::prelude::*;
+use crate::rigctl::Tuning;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// The report given unless one is copied, or typed.
+const DEFAULT_RST: &str = "599";
+
+/// A contact, as heard and then confirmed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Qso {
+    pub call: String,
+    /// When it was first heard.
+    pub time: DateTime<Utc>,
+    pub freq_hz: Option<u64>,
+    pub rst_sent: String,
+    pub rst_rcvd: String,
+}
+
+impl Qso {
+    /// The contact, as a record of ADIF, its frequency in MHz.
+    pub fn to_adif(&self, my_call: &str) -> AdifRecord {
+        AdifRecord::new()
+            .with("CALL", &self.call)
+            .with("QSO_DATE", &self.time.format("%Y%m%d").to_string())
+            .with("TIME_ON", &self.time.format("%H%M%S").to_string())
+            .with(
+                "FREQ",
+                &self
+                    .freq_hz
+                    .map_or(String::new(), |freq| format!("{:.6}", freq as f64 / 1e6)),
+            )
+            .with("MODE", "CW")
+            .with("RST_SENT", &self.rst_sent)
+            .with("RST_RCVD", &self.rst_rcvd)
+            .with("STATION_CALLSIGN", my_call)
+    }
+
+    /// The contact on a line, to be confirmed, e.g. `K1ABC 18:30 UTC
+    /// 14025.70 kHz sent 599 received 579`.
+    fn to_text(&self) -> String {
+        format!(
+            "{} {} {} sent {} received {}",
+            self.call,
+            self.time.format("%H:%M UTC"),
+            Tuning {
+                freq_hz: self.freq_hz,
+                mode: None,
+            }
+            .to_text(),
+            self.rst_sent,
+            self.rst_rcvd
+        )
+    }
+}
+
+/// A signal report, e.g. `579`, or `5NN` with cut numbers.
+fn read_rst(word: &str) -> Option<String> {
+    let rst: String = word
+        .chars()
+        .map(|ch| match ch {
+            'N' => '9',
+            ch => ch,
+        })
+        .collect();
+    let bytes = rst.as_bytes();
+    match bytes.len() == 3
+        && (b'1'..=b'5').contains(&bytes[0])
+        && bytes[1..].iter().all(|b| (b'1'..=b'9').contains(b))
+    {
+        true => Some(rst),
+        false => None,
+    }
+}
+
+/// The other station and the report it gave in a message that has both
+/// callsigns, e.g. `W1AW DE K1ABC UR 579 5NN`, the first report copied
+/// being taken.
+pub fn exchange(
+    message: &str,
+    my_call: &str,
+    callsign: &Regex,
+) -> Option<(String, Option<String>)> {
+    let words: Vec<&str> = message.split_whitespace().collect();
+    if !words.iter().any(|word| word.eq_ignore_ascii_case(my_call)) {
+        return None;
+    }
+    let call = words
+        .iter()
+        .find(|word| callsign.is_match(word) && !word.eq_ignore_ascii_case(my_call))?;
+    let rst = words.iter().find_map(|word| read_rst(word));
+    Some((call.to_string(), rst))
+}
+
+/// Asks for a field, keeping its value when nothing is typed.
+///
+/// # Returns
+/// None at the end of the input.
+fn ask(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    name: &str,
+    value: &str,
+) -> io::Result<Option<String>> {
+    write!(output, "{name} [{value}]: ")?;
+    output.flush()?;
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(match line.trim() {
+        "" => value.to_string(),
+        typed => typed.to_uppercase(),
+    }))
+}
+
+/// The contacts heard with `--log-qso`, in the messages that have my
+/// callsign and another one, to be confirmed and logged to an ADI file
+/// at the end of the session. It is shared by the stations.
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct Logbook {
+    my_call: String,
+    file: Arc<Mutex<AdifFile>>,
+    heard: Arc<Mutex<Vec<Qso>>>,
+    callsign: Regex,
+}
+
+#[allow(dead_code)]
+impl Logbook {
+    /// Opens the ADI file to log to, now, so that it can't fail only at
+    /// the end.
+    pub fn open(path: &Path, my_call: &str) -> io::Result<Self> {
+        Ok(Self {
+            my_call: my_call.to_uppercase(),
+            file: Arc::new(Mutex::new(AdifFile::open(path)?)),
+            heard: Arc::new(Mutex::new(Vec::new())),
+            callsign: callsign_regex(),
+        })
+    }
+
+    /// Takes in a decoded message, the contact with its station heard
+    /// once however many times it calls, its latest report kept.
+    pub fn message(&self, message: &str, tuning: Option<&Tuning>) {
+        let Some((call, rst)) = exchange(message, &self.my_call, &self.callsign) else {
+            return;
+        };
+        let freq_hz = tuning.and_then(|tuning| tuning.freq_hz);
+        let mut heard = self.heard.lock().unwrap();
+        match heard.iter_mut().find(|qso| qso.call == call) {
+            Some(qso) => {
+                if let Some(rst) = rst {
+                    qso.rst_rcvd = rst;
+                }
+                qso.freq_hz = qso.freq_hz.or(freq_hz);
+            }
+            None => heard.push(Qso {
+                call,
+                time: Utc::now(),
+                freq_hz,
+                rst_sent: DEFAULT_RST.to_string(),
+                rst_rcvd: rst.unwrap_or_else(|| DEFAULT_RST.to_string()),
+            }),
+        }
+    }
+
+    /// Asks whether to log each contact heard, or to edit it first.
+    ///
+    /// # Returns
+    /// The contacts to log, those not confirmed before the end of the
+    /// input being left out.
+    pub fn confirm(&self, mut input: impl BufRead, mut output: impl Write) -> io::Result<Vec<Qso>> {
+        let heard = self.heard.lock().unwrap().clone();
+        let mut confirmed = Vec::new();
+        'heard: for mut qso in heard {
+            loop {
+                write!(output, "{}\nLog it? [Y/n/e] ", qso.to_text())?;
+                output.flush()?;
+                let mut line = String::new();
+                if input.read_line(&mut line)? == 0 {
+                    break 'heard;
+                }
+                match line.trim().to_lowercase().as_str() {
+                    "" | "y" | "yes" => {
+                        confirmed.push(qso);
+                        break;
+                    }
+                    "n" | "no" => break,
+                    "e" | "edit" => match edit(&mut input, &mut output, qso.clone())? {
+                        Some(edited) => qso = edited,
+                        None => break 'heard,
+                    },
+                    _ => {}
+                }
+            }
+        }
+        Ok(confirmed)
+    }
+
+    /// Confirms the contacts heard on the terminal, and appends them to
+    /// the ADI file.
+    pub fn finish(&self, output: impl Write) -> io::Result<usize> {
+        let confirmed = self.confirm(io::stdin().lock(), output)?;
+        let mut file = self.file.lock().unwrap();
+        for qso in &confirmed {
+            file.write(&qso.to_adif(&self.my_call))?;
+        }
+        Ok(confirmed.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heard.lock().unwrap().is_empty()
+    }
+}
+
+/// Edits a contact, field by field.
+///
+/// # Returns
+/// None at the end of the input.
+fn edit(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    mut qso: Qso,
+) -> io::Result<Option<Qso>> {
+    let Some(call) = ask(input, output, "Call", &qso.call)? else {
+        return Ok(None);
+    };
+    let Some(rst_sent) = ask(input, output, "RST sent", &qso.rst_sent)? else {
+        return Ok(None);
+    };
+    let Some(rst_rcvd) = ask(input, output, "RST received", &qso.rst_rcvd)? else {
+        return Ok(None);
+    };
+    let freq = qso
+        .freq_hz
+        .map_or(String::new(), |freq| format!("{:.2}", freq as f64 / 1000.0));
+    let freq_hz = loop {
+        let Some(typed) = ask(input, output, "Frequency in kHz", &freq)? else {
+            return Ok(None);
+        };
+        if typed.is_empty() {
+            break None;
+        }
+        match typed.parse::<f64>() {
+            Ok(khz) if khz > 0.0 => break Some((khz * 1000.0).round() as u64),
+            _ => writeln!(output, "Not a frequency: {typed}")?,
+        }
+    };
+    qso.call = call;
+    qso.rst_sent = rst_sent;
+    qso.rst_rcvd = rst_rcvd;
+    qso.freq_hz = freq_hz;
+    Ok(Some(qso))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_exchange() {
+        let callsign = callsign_regex();
+        assert_eq!(
+            exchange("W1AW DE K1ABC UR 5NN 5NN", "W1AW", &callsign),
+            Some(("K1ABC".to_string(), Some("599".to_string())))
+        );
+        assert_eq!(
+            exchange("W1AW DE DL/K1ABC TNX", "w1aw", &callsign),
+            Some(("DL/K1ABC".to_string(), None))
+        );
+        // Not for me:
+        assert_eq!(exchange("CQ CQ DE K1ABC K", "W1AW", &callsign), None);
+        assert_eq!(exchange("W1AW DE K1A?C", "W1AW", &callsign), None);
+        assert_eq!(read_rst("579"), Some("579".to_string()));
+        assert_eq!(read_rst("609"), None);
+        assert_eq!(read_rst("1234"), None);
+    }
+
+    #[test]
+    fn test_logbook() {
+        let path = std::env::temp_dir().join(format!("logbook-test-{}.adi", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let logbook = Logbook::open(&path, "w1aw").unwrap();
+        let tuning = Tuning {
+            freq_hz: Some(14025700),
+            mode: Some("CW".to_string()),
+        };
+        logbook.message("W1AW DE K1ABC K1ABC", None);
+        logbook.message("W1AW UR 579 579 DE K1ABC", Some(&tuning));
+        logbook.message("CQ DE N0CALL", None);
+        logbook.message("W1AW DE G4XYZ", None);
+        let heard = logbook.heard.lock().unwrap().clone();
+        assert_eq!(
+            heard
+                .iter()
+                .map(|qso| (qso.call.as_str(), qso.freq_hz, qso.rst_rcvd.as_str()))
+                .collect::<Vec<_>>(),
+            [("K1ABC", Some(14025700), "579"), ("G4XYZ", None, "599")]
+        );
+
+        // The first kept, the second edited, and nothing left to log:
+        let mut output = Vec::new();
+        let confirmed = logbook
+            .confirm(&b"\ne\n\n559\n\ngarbage\n7030\ny\n"[..], &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Frequency in kHz []: Not a frequency: GARBAGE"));
+        assert_eq!(confirmed.len(), 2);
+        assert_eq!(confirmed[0], heard[0]);
+        assert_eq!(
+            (confirmed[1].rst_sent.as_str(), confirmed[1].freq_hz),
+            ("559", Some(7030000))
+        );
+        assert_eq!(logbook.confirm(&b"n\n"[..], &mut Vec::new()).unwrap(), []);
+        std::fs::remove_file(&path).unwrap();
+
+        let qso = Qso {
+            time: Utc.with_ymd_and_hms(2024, 11, 2, 18, 30, 5).unwrap(),
+            ..heard[0].clone()
+        };
+        assert_eq!(
+            qso.to_adif("W1AW").to_adi(),
+            "<CALL:5>K1ABC <QSO_DATE:8>20241102 <TIME_ON:6>183005 <FREQ:9>14.025700 <MODE:2>CW <RST_SENT:3>599 <RST_RCVD:3>579 <STATION_CALLSIGN:4>W1AW <EOR>\n"
+        );
+    }
+}
@@ -1,32 +1,267 @@
 use clap_complete::shells::Shell;
 
+mod abbreviations;
+mod adif;
+mod alert;
+mod answer;
+mod autotone;
+mod band;
+mod bench;
+mod callsign;
 mod cli;
+mod clipboard;
+mod codec;
+mod config;
+mod confusion;
+mod contest;
+mod controls;
+mod copy_behind;
 mod credits;
+mod cwdaemon;
+mod daemon;
+mod detect;
+mod distribution;
+mod events;
+mod export;
 mod fecr_quiz;
+mod feedback;
 mod filter;
+mod fldigi;
+mod gate;
+mod gpio;
+mod iambic;
+mod icr;
+mod input;
+mod interference;
+mod json;
+mod keyer;
+mod keying;
+mod koch;
+mod logbook;
 mod message;
+mod meter;
+mod monitor;
+mod mopp;
 mod morse;
+mod mqtt;
+mod narrator;
+mod notify;
+mod passthrough;
 mod pipewire;
+mod practice;
 mod prelude;
+mod profile;
+mod qso;
+mod recording;
+mod replay;
+mod review;
+mod rigctl;
+mod ring;
+mod rufz;
+mod scan;
+mod score;
+mod script;
+mod scrollback;
+mod selftest;
+mod send;
+mod serial;
+mod server;
+mod sidetone;
+mod sparkline;
+mod speech;
+mod speed;
+mod spot;
+mod stats;
 mod term;
+mod threshold;
+mod transcript;
+mod tui;
+mod udp;
+mod uinput;
+mod waterfall;
+mod wav;
+mod websocket;
+mod winkeyer;
+mod words;
 
 use is_terminal::IsTerminal;
 use prelude::*;
-use std::io::BufRead;
+use rand::SeedableRng;
+use std::io::{BufRead, Write};
 
 use crate::pipewire::ensure_pipewire;
 
 use crate::{credits::print_credits, morse::text_to_morse};
 
+/// The command line, with the defaults that the config file, the profile
+/// and the preset gave it, and where they came from for `config --show`.
+struct Setup {
+    cmd: clap::Command,
+    matches: clap::ArgMatches,
+    origins: config::Origins,
+    layers: Vec<config::Layer>,
+    config_path: String,
+    profile_origin: String,
+    preset: Option<String>,
+    preset_origin: &'static str,
+}
+
+impl Setup {
+    /// Chooses the profile and the preset, with the values given early,
+    /// and parses the command line with the defaults taken from them and
+    /// the config file.
+    fn new(cmd: clap::Command, early_value: impl Fn(&str) -> Option<String>) -> Self {
+        let config_file = config::Config::load();
+        let config_path = config::path()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        let env_profile = std::env::var(profile::ENV).ok();
+        if let Some(Err(e)) = env_profile.as_deref().map(profile::validate) {
+            eprintln!("Error: {} is not a profile name. {e}", profile::ENV);
+            std::process::exit(1);
+        }
+        let profile_origin = match (early_value("profile"), &env_profile, &config_file.profile) {
+            (Some(_), _, _) => "--profile".to_string(),
+            (None, Some(_), _) => profile::ENV.to_string(),
+            (None, None, Some(_)) => "the config file".to_string(),
+            (None, None, None) => "default".to_string(),
+        };
+        profile::set_current(&profile::choose(
+            early_value("profile").as_deref(),
+            env_profile.as_deref(),
+            config_file.profile.as_deref(),
+        ));
+        let preset = early_value("preset").or(config_file.preset.clone());
+        let preset_origin = match early_value("preset") {
+            Some(_) => "--preset",
+            None => "the config file",
+        };
+        let mut layers = vec![config::Layer {
+            origin: "the config file".to_string(),
+            file: config_path.clone(),
+            settings: config_file.settings.clone(),
+        }];
+        layers.extend(profile::settings());
+        if let Some(preset) = &preset {
+            let Some(settings) = config_file.presets.get(preset) else {
+                eprintln!("Error: There is no preset named {preset} in {config_path}");
+                std::process::exit(1);
+            };
+            layers.push(config::Layer {
+                origin: format!("preset {preset}"),
+                file: config_path.clone(),
+                settings: settings.clone(),
+            });
+        }
+        let (cmd, origins) = config::apply(cmd, &layers);
+        let matches = cmd.clone().get_matches();
+        Self {
+            cmd,
+            matches,
+            origins,
+            layers,
+            config_path,
+            profile_origin,
+            preset,
+            preset_origin,
+        }
+    }
+}
+
+/// The global arguments, which the subcommands share.
+struct Globals {
+    /// None when the tone should be found automatically.
+    tone_setting: Option<f32>,
+    tone_freq: f32,
+    text: bool,
+    sound: bool,
+    /// Whether the speed should follow the sender.
+    adaptive_speed: bool,
+    dot_duration: u32,
+    charset: morse::Charset,
+}
+
+impl Globals {
+    fn new(matches: &clap::ArgMatches) -> Self {
+        let given =
+            |id: &str| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine);
+
+        // The tone frequency is None when it should be found automatically:
+        let tone_setting: Option<f32> = *matches
+            .get_one::<Option<f32>>("tone")
+            .expect("Missing --tone arg default");
+        if tone_setting.is_none()
+            && !matches!(matches.subcommand_name(), Some("listen" | "bench-decode"))
+        {
+            eprintln!(
+                "Error: '--tone auto' is only supported by the listen and bench-decode commands."
+            );
+            std::process::exit(1);
+        }
+        let tone_freq = tone_setting.unwrap_or(440.0);
+        let text = *matches
+            .get_one::<bool>("text")
+            .expect("Missing --text arg default");
+        let sound = *matches
+            .get_one::<bool>("sound")
+            .expect("Missing --sound arg default");
+
+        // The speed is None when it should follow the sender:
+        let wpm_setting = matches.get_one::<Option<u32>>("wpm").copied();
+        let adaptive_speed = wpm_setting == Some(None);
+        if adaptive_speed && !matches!(matches.subcommand_name(), Some("listen" | "bench-decode")) {
+            eprintln!(
+                "Error: '--wpm auto' is only supported by the listen and bench-decode commands."
+            );
+            std::process::exit(1);
+        }
+
+        // Calculate dot duration from wpm if not provided:
+        // (one given on the command line over one of the config file)
+        let dot_duration = match (matches.get_one::<u32>("dot"), wpm_setting.flatten()) {
+            (Some(_), Some(_)) if given("dot") == given("wpm") => {
+                eprintln!("Error: '--dot' and '--wpm' cannot be used together.");
+                std::process::exit(1);
+            }
+            (Some(&dot), None) => dot,
+            (Some(&dot), Some(_)) if given("dot") => dot,
+            (_, Some(wpm)) => morse::wpm_to_dot_length(wpm),
+            (None, None) => 60, // Default dot duration @ 20WPM
+        };
+        let charset = *matches
+            .get_one::<morse::Charset>("charset")
+            .expect("Missing --charset arg default");
+        Self {
+            tone_setting,
+            tone_freq,
+            text,
+            sound,
+            adaptive_speed,
+            dot_duration,
+            charset,
+        }
+    }
+}
+
 fn main() {
-    let mut cmd = cli::app();
-    let matches = cmd.clone().get_matches();
+    let cmd = cli::app();
+    // Parsed once for the logging, the profile and the preset, before the
+    // config file gives the defaults of the command line:
+    let early = cmd.clone().ignore_errors(true).try_get_matches().ok();
+    let early_value = |id: &str| {
+        early
+            .as_ref()
+            .and_then(|matches| matches.get_one::<String>(id).cloned())
+    };
 
     // Configure logging:
-    let log_level = if matches.get_flag("verbose") {
+    let log_level = if early
+        .as_ref()
+        .is_some_and(|matches| matches.get_flag("verbose"))
+    {
         Some("debug".to_string())
     } else {
-        matches.get_one::<String>("log").cloned()
+        early_value("log")
     };
     // Use RUST_LOG env var if no command-line option is provided
     let log_level = log_level.or_else(|| std::env::var("RUST_LOG").ok());
@@ -38,208 +273,1859 @@ fn main() {
         .init();
     debug!("logging initialized.");
 
+    // Choose the profile and the preset, and take the defaults from them
+    // and the config file:
+    let setup = Setup::new(cmd, early_value);
+    let matches = &setup.matches;
+    term::set_accessible(matches.get_flag("accessible"));
+    term::restore_on_panic();
+
     // Print help if no subcommand is given:
     if matches.subcommand_name().is_none() {
-        cmd.print_help().unwrap();
+        setup.cmd.clone().print_help().unwrap();
         println!();
         return;
     }
-
-    // Global arguments
-    let tone_freq: f32 = *matches
-        .get_one::<f32>("tone")
-        .expect("Missing --tone arg default");
-    let text = *matches
-        .get_one::<bool>("text")
-        .expect("Missing --text arg default");
-    let sound = *matches
-        .get_one::<bool>("sound")
-        .expect("Missing --sound arg default");
-
-    // Calculate dot duration from wpm if not provided:
-    let dot_duration = match (matches.get_one::<u32>("dot"), matches.get_one::<u32>("wpm")) {
-        (Some(_), Some(_)) => {
-            eprintln!("Error: '--dot' and '--wpm' cannot be used together.");
-            std::process::exit(1);
-        }
-        (Some(&dot), None) => dot,
-        (None, Some(&wpm)) => morse::wpm_to_dot_length(wpm),
-        (None, None) => 60, // Default dot duration @ 20WPM
-    };
+    let globals = Globals::new(matches);
 
     // Handle the subcommands:
     let exit_code = match matches.subcommand() {
-        Some(("fecr-quiz", sub_matches)) => {
-            let trials = sub_matches
-                .get_one::<u32>("trials")
-                .expect("Missing trials arg default");
-            let char_set = sub_matches
-                .get_one::<String>("characters")
-                .expect("Missing --character arg default");
-            let randomize = sub_matches
-                .get_one::<bool>("random")
-                .expect("Missing random arg default");
-            let calibration_mode = sub_matches
-                .get_one::<bool>("baseline-calibration")
-                .expect("Missing --baseline-calibration arg default");
-            let baseline = sub_matches
-                .get_one::<u32>("baseline")
-                .expect("Missing --baseline arg default");
-            fecr_quiz::start_quiz(
-                *trials,
-                char_set,
-                dot_duration,
-                tone_freq,
-                text,
-                *randomize,
-                *calibration_mode,
-                *baseline,
-            );
-            0
-        }
+        Some(("fecr-quiz", sub_matches)) => run_fecr_quiz(sub_matches, &globals),
+        Some(("icr", sub_matches)) => run_icr(sub_matches, &globals),
+        Some(("practice", sub_matches)) => run_practice(sub_matches, &globals),
+        Some(("config", sub_matches)) => run_config(sub_matches, &setup),
+        Some(("profile", sub_matches)) => run_profile(sub_matches),
+        Some(("stats", sub_matches)) => run_stats(sub_matches),
         Some(("test-sound", _sub_matches)) => {
             let player = morse::MorsePlayer::new();
             let message = "If sound is working, you should hear this test message now.";
             println!("{}", message);
             println!("{}", text_to_morse(message));
-            player.play(message, dot_duration, tone_freq);
+            player.play(message, globals.dot_duration, globals.tone_freq);
             0
         }
-        Some(("read", sub_matches)) => {
-            let player = morse::MorsePlayer::new();
-            let morse = sub_matches
-                .get_one::<bool>("morse")
-                .expect("Missing --morse arg default");
-
-            let stdin = std::io::stdin();
-            if stdin.is_terminal() {
-                println!("## Type some text and it will be output as morse code.");
-                println!("## You may also pipe text to this same command.");
-                println!("## Press Enter after each line.");
-                println!("## When done, press Ctrl-D to exit.");
-            }
-            for line in stdin.lock().lines() {
-                match line {
-                    Ok(line) => {
-                        if text {
-                            // Output text instead of sound
-                            if *morse {
-                                // stdin is already morse encoded, convert it to text:
-                                println!("{}", morse::code_to_text(&line));
-                                if sound {
-                                    player.play_morse(&line, dot_duration, tone_freq);
-                                    player.play_gap(dot_duration * 14);
-                                }
-                            } else {
-                                // Encode stdin as morse code:
-                                println!("{}", morse::text_to_morse(&line));
-                                if sound {
-                                    player.play(&line, dot_duration, tone_freq);
-                                    player.play_gap(dot_duration * 14);
-                                }
-                            }
-                        } else if *morse {
-                            // stdin is already morse encoded:
+        Some(("read", sub_matches)) => run_read(sub_matches, &globals),
+        Some((command @ ("encode" | "decode"), sub_matches)) => {
+            let separators: Vec<&String> = sub_matches
+                .get_many::<String>("separator")
+                .expect("Missing --separator arg default")
+                .collect();
+            let separators = codec::Separators {
+                letter: separators[0].clone(),
+                word: separators[1].clone(),
+            };
+            let convert = |input: &str| match command {
+                "encode" => codec::encode(input, &separators),
+                _ => codec::decode(input, &separators, globals.charset),
+            };
+            match sub_matches.get_many::<String>("input") {
+                Some(input) => {
+                    let input: Vec<&str> = input.map(|s| s.as_str()).collect();
+                    println!("{}", convert(&input.join(" ")));
+                }
+                None => {
+                    for line in std::io::stdin().lock().lines() {
+                        match line {
+                            Ok(line) => println!("{}", convert(&line)),
+                            Err(e) => eprintln!("Error reading line: {}", e),
+                        }
+                    }
+                }
+            }
+            0
+        }
+        Some(("send", sub_matches)) => run_send(sub_matches, &globals),
+        Some(("render", sub_matches)) => run_render(sub_matches, &globals),
+        Some(("bench-decode", sub_matches)) => run_bench_decode(sub_matches, &globals),
+        Some(("selftest", sub_matches)) => run_selftest(sub_matches, &globals),
+        Some(("listen", sub_matches)) => run_listen(sub_matches, &globals),
+        Some(("cwdaemon", sub_matches)) => run_cwdaemon(sub_matches, &globals),
+        Some(("connect", sub_matches)) => {
+            let addr = sub_matches
+                .get_one::<server::ServeAddr>("address")
+                .expect("Missing address arg");
+            if addr.protocol != server::Protocol::Tcp {
+                error!("Can only connect to a --serve over tcp.");
+                std::process::exit(1);
+            }
+            let live = io::stdout().is_terminal() && !term::accessible();
+            match server::connect(addr, live) {
+                Ok(()) => 0,
+                Err(e) => {
+                    error!("Could not connect to {}: {e}", addr.addr);
+                    1
+                }
+            }
+        }
+        Some(("completions", sub_matches)) => run_completions(sub_matches),
+        Some(("credits", _sub_matches)) => {
+            print_credits();
+            0
+        }
+        _ => 1,
+    };
+
+    eprintln!();
+    std::process::exit(exit_code);
+}
+
+/// Runs `fecr-quiz`, recording its session, or saving the baseline
+/// measured with `--baseline-calibration`.
+fn run_fecr_quiz(sub_matches: &clap::ArgMatches, globals: &Globals) -> i32 {
+    let &Globals {
+        tone_freq,
+        text,
+        dot_duration,
+        charset,
+        ..
+    } = globals;
+    let trials = sub_matches
+        .get_one::<u32>("trials")
+        .expect("Missing trials arg default");
+    let char_set = sub_matches
+        .get_one::<String>("characters")
+        .cloned()
+        .unwrap_or_else(|| charset.characters());
+    let randomize = sub_matches
+        .get_one::<bool>("random")
+        .expect("Missing random arg default");
+    let calibration_mode = sub_matches
+        .get_one::<bool>("baseline-calibration")
+        .expect("Missing --baseline-calibration arg default");
+    let baseline = *sub_matches
+        .get_one::<u32>("baseline")
+        .expect("Missing --baseline arg default");
+    let outlier_iqr = *sub_matches
+        .get_one::<f64>("outlier-iqr")
+        .expect("Missing --outlier-iqr arg default");
+    let histogram = sub_matches
+        .get_one::<bool>("histogram")
+        .expect("Missing --histogram arg default");
+    let began = std::time::Instant::now();
+    let results = fecr_quiz::start_quiz(
+        *trials,
+        &char_set,
+        dot_duration,
+        tone_freq,
+        text,
+        *randomize,
+        *calibration_mode,
+        baseline,
+        outlier_iqr,
+        *histogram,
+    );
+    match results {
+        Some(results) if !calibration_mode && !results.prompts.is_empty() => {
+            stats::record(
+                &stats::Session::new(
+                    "fecr-quiz",
+                    &char_set,
+                    morse::dot_length_to_wpm(dot_duration),
+                    began.elapsed(),
+                )
+                .with_characters(&results, dot_duration, outlier_iqr)
+                .with_trials(export::quiz_trials(
+                    &results,
+                    morse::dot_length_to_wpm(dot_duration),
+                    tone_freq,
+                )),
+            );
+        }
+        Some(results) if results.baseline(outlier_iqr) > 0 => {
+            let baseline = results.baseline(outlier_iqr);
+            match profile::save_setting("baseline", toml::Value::Integer(baseline.into())) {
+                Ok(_) => println!(
+                    "Saved as the baseline of the FECR quiz, for the {} profile.",
+                    profile::current()
+                ),
+                Err(e) => warn!("Could not save the baseline: {e}"),
+            }
+        }
+        _ => {}
+    }
+    0
+}
+
+/// Runs `icr`, the drill of instant character recognition.
+fn run_icr(sub_matches: &clap::ArgMatches, globals: &Globals) -> i32 {
+    let &Globals {
+        tone_freq,
+        dot_duration,
+        charset,
+        ..
+    } = globals;
+    let characters: Vec<String> = sub_matches
+        .get_one::<String>("characters")
+        .cloned()
+        .unwrap_or_else(|| charset.characters())
+        .to_uppercase()
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .map(String::from)
+        .collect();
+    if characters.is_empty() {
+        error!("There are no characters to drill.");
+        std::process::exit(1);
+    }
+    let trials = *sub_matches
+        .get_one::<u32>("trials")
+        .expect("Missing --trials arg default");
+    let ms = |ms: &u64| std::time::Duration::from_millis(*ms);
+    let window = ms(sub_matches
+        .get_one::<u64>("window-ms")
+        .expect("Missing --window-ms arg default"));
+    let pressure = sub_matches.get_flag("pressure").then(|| icr::Pressure {
+        min: ms(sub_matches
+            .get_one::<u64>("min-window-ms")
+            .expect("Missing --min-window-ms arg default")),
+        step: ms(sub_matches
+            .get_one::<u64>("pressure-step-ms")
+            .expect("Missing --pressure-step-ms arg default")),
+        target: *sub_matches
+            .get_one::<f64>("pressure-target")
+            .expect("Missing --pressure-target arg default"),
+    });
+    let seed = band::seed_or_random(sub_matches.get_one::<u64>("seed").copied());
+    println!("Seed: {seed} (--seed {seed} to drill the same characters again)\n");
+    let began = std::time::Instant::now();
+    let answers = icr::run_icr(
+        &characters,
+        trials,
+        dot_duration,
+        tone_freq,
+        icr::Window::new(window, pressure),
+        &mut rand::rngs::StdRng::seed_from_u64(seed),
+    );
+    if let Some(answers) = answers.filter(|answers| !answers.is_empty()) {
+        icr::print_report(&answers);
+        let results = icr::quiz_result(&answers);
+        let wpm = morse::dot_length_to_wpm(dot_duration);
+        stats::record(
+            &stats::Session::new("icr", &characters.concat(), wpm, began.elapsed())
+                .with_characters(&results, dot_duration, icr::OUTLIER_IQR)
+                .with_trials(export::quiz_trials(&results, wpm, tone_freq))
+                .with_incomplete(answers.len() < trials as usize)
+                .with_seed(seed),
+        );
+    }
+    0
+}
+
+/// Runs `read`, playing each line typed or piped in as morse code.
+fn run_read(sub_matches: &clap::ArgMatches, globals: &Globals) -> i32 {
+    let &Globals {
+        tone_freq,
+        text,
+        sound,
+        dot_duration,
+        ..
+    } = globals;
+    let player = morse::MorsePlayer::new();
+    let morse = sub_matches
+        .get_one::<bool>("morse")
+        .expect("Missing --morse arg default");
+
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        println!("## Type some text and it will be output as morse code.");
+        println!("## You may also pipe text to this same command.");
+        println!("## Press Enter after each line.");
+        println!("## When done, press Ctrl-D to exit.");
+    }
+    for line in stdin.lock().lines() {
+        match line {
+            Ok(line) => {
+                if text {
+                    // Output text instead of sound
+                    if *morse {
+                        // stdin is already morse encoded, convert it to text:
+                        println!("{}", morse::code_to_text(&line));
+                        if sound {
                             player.play_morse(&line, dot_duration, tone_freq);
                             player.play_gap(dot_duration * 14);
-                        } else {
-                            // Convert stdin into morse and play it:
+                        }
+                    } else {
+                        // Encode stdin as morse code:
+                        println!("{}", morse::text_to_morse(&line));
+                        if sound {
                             player.play(&line, dot_duration, tone_freq);
                             player.play_gap(dot_duration * 14);
                         }
                     }
-                    Err(e) => eprintln!("Error reading line: {}", e),
+                } else if *morse {
+                    // stdin is already morse encoded:
+                    player.play_morse(&line, dot_duration, tone_freq);
+                    player.play_gap(dot_duration * 14);
+                } else {
+                    // Convert stdin into morse and play it:
+                    player.play(&line, dot_duration, tone_freq);
+                    player.play_gap(dot_duration * 14);
                 }
             }
+            Err(e) => eprintln!("Error reading line: {}", e),
+        }
+    }
+    0
+}
+
+/// Runs `send`, sending the text given with a Winkeyer, a keying
+/// output, or else as sound.
+fn run_send(sub_matches: &clap::ArgMatches, globals: &Globals) -> i32 {
+    let &Globals {
+        tone_freq,
+        text,
+        sound,
+        dot_duration,
+        ..
+    } = globals;
+    if let Some(port) = sub_matches.get_one::<String>("winkeyer") {
+        let text_input = read_input(sub_matches);
+        if text {
+            println!(
+                "{}",
+                codec::encode(&text_input, &codec::Separators::default())
+            );
+        }
+        match open_winkeyer(port, sub_matches, dot_duration).and_then(|mut winkeyer| {
+            let sent = keying::abortable(|| {
+                winkeyer.send(
+                    &text_input,
+                    |c| {
+                        print!("{c}");
+                        let _ = io::stdout().flush();
+                    },
+                    keying::aborted,
+                )
+            });
+            println!();
+            sent
+        }) {
+            Ok(_) => 0,
+            Err(e) => {
+                error!("Could not send with the Winkeyer on {port}: {e}");
+                1
+            }
+        }
+    } else if let Some(output) = open_keying(sub_matches, dot_duration) {
+        let code = match sub_matches.get_one::<usize>("key-test") {
+            Some(dits) => ".".repeat(*dits),
+            None => codec::encode(&read_input(sub_matches), &codec::Separators::default()),
+        };
+        if text {
+            println!("{code}");
+        }
+        let options = keying::KeyingOptions {
+            ptt: sub_matches.get_flag("ptt") || sub_matches.contains_id("ptt-gpio"),
+            lead_in: std::time::Duration::from_millis(
+                *sub_matches
+                    .get_one::<u64>("lead-in")
+                    .expect("Missing --lead-in arg default"),
+            ),
+            tail: std::time::Duration::from_millis(
+                *sub_matches
+                    .get_one::<u64>("tail")
+                    .expect("Missing --tail arg default"),
+            ),
+        };
+        let timing = make_timing(sub_matches, dot_duration);
+        match output.and_then(|output| keying::key(output, &options, &timing.key_events(&code))) {
+            Ok(()) => 0,
+            Err(e) => {
+                error!("Could not key the transmitter: {e}");
+                1
+            }
+        }
+    } else {
+        let target = sub_matches
+            .get_one::<String>("target")
+            .map(|s| s.to_string());
+        let code = codec::encode(&read_input(sub_matches), &codec::Separators::default());
+        if text {
+            println!("{code}");
+        }
+        if !text || sound {
+            let keyer = make_keyer(sub_matches, &code, dot_duration, tone_freq);
+            if cfg!(target_os = "linux") {
+                ensure_pipewire();
+                pipewire::send(target, keyer).expect("pipewire::send() failed");
+            } else {
+                error!("Sorry, the send feature is only supported on Linux right now.");
+                std::process::exit(1);
+            }
+        }
+        0
+    }
+}
+
+/// Runs `render`, writing the text given to an audio file.
+fn run_render(sub_matches: &clap::ArgMatches, globals: &Globals) -> i32 {
+    let &Globals {
+        tone_freq,
+        text,
+        dot_duration,
+        ..
+    } = globals;
+    let out = sub_matches
+        .get_one::<String>("out")
+        .expect("Missing --out arg");
+    let padding = *sub_matches
+        .get_one::<f32>("padding")
+        .expect("Missing --padding arg default");
+    let preamble = *sub_matches
+        .get_one::<bool>("preamble")
+        .expect("Missing --preamble arg default");
+    let mut text_input = read_input(sub_matches);
+    if preamble {
+        text_input.insert_str(0, "VVV ");
+    }
+    let code = codec::encode(&text_input, &codec::Separators::default());
+    if text {
+        println!("{code}");
+    }
+    let keyer = make_keyer(sub_matches, &code, dot_duration, tone_freq);
+    match wav::write_file(out, keyer, (padding * 1000.0) as u32) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Could not write {out}: {e}");
+            1
+        }
+    }
+}
+
+/// Runs `selftest`, playing a message to a sink and decoding it from
+/// its monitor.
+fn run_selftest(sub_matches: &clap::ArgMatches, globals: &Globals) -> i32 {
+    let &Globals {
+        tone_freq,
+        dot_duration,
+        charset,
+        ..
+    } = globals;
+    let target = sub_matches
+        .get_one::<String>("target")
+        .map(|s| s.to_string());
+    let message = sub_matches
+        .get_one::<String>("message")
+        .expect("Missing --message arg default");
+    let threshold = sub_matches
+        .get_one::<Option<f32>>("threshold")
+        .copied()
+        .unwrap_or(Some(0.3));
+    let detector = sub_matches
+        .get_one::<String>("detector")
+        .expect("Missing --detector arg default")
+        .parse::<filter::Detector>()
+        .expect("Invalid --detector");
+    // Decode with the defaults of listen:
+    let timeout_duration = dot_duration * 20;
+    let pipeline = detect::SignalPipeline::new(detect::PipelineOptions {
+        tone_freq,
+        threshold,
+        debounce_duration: (dot_duration as f32 * 0.25).round() as u32,
+        detector,
+        dot_duration,
+        timeout_duration,
+        text_options: detect::TextOptions {
+            charset,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let separators = codec::Separators::default();
+    let code = codec::encode(message, &separators);
+    // What the message should be decoded as:
+    let expected = codec::decode(&code, &separators, charset);
+    let events = keyer::Timing::new(dot_duration, None).key_events(&code);
+    let keyer = keyer::Keyer::new(&events, keyer::SAMPLE_RATE, tone_freq, 0.5, 5.0);
+    if cfg!(target_os = "linux") {
+        ensure_pipewire();
+        let wait = std::time::Duration::from_millis(timeout_duration as u64 + 1000);
+        let test = pipewire::selftest(target, keyer, selftest::SelfTest::new(pipeline), wait)
+            .expect("pipewire::selftest() failed");
+        let (passed, report) = test.report(&expected, dot_duration);
+        println!("{report}");
+        if passed {
             0
+        } else {
+            1
         }
-        Some(("listen", sub_matches)) => {
-            //
-            let morse = sub_matches
-                .get_one::<bool>("morse")
-                .expect("Missing --morse arg default");
-            let device = sub_matches
-                .get_one::<String>("device")
-                .map(|s| s.to_string());
-            let file = sub_matches.get_one::<String>("file").map(|s| s.to_string());
-            let threshold = sub_matches
-                .get_one::<f32>("threshold")
-                .copied()
-                .unwrap_or(0.3);
-            let bandwidth = sub_matches
-                .get_one::<f32>("bandwidth")
-                .copied()
-                .unwrap_or(200.0);
-            match (&device, &file) {
-                (None, Some(_file)) => {
-                    error!("TODO. Audio file input is not supported yet.");
-                    std::process::exit(1);
+    } else {
+        error!("Sorry, the selftest feature is only supported on Linux right now.");
+        std::process::exit(1);
+    }
+}
+
+/// Runs `cwdaemon`, keying what its clients send.
+fn run_cwdaemon(sub_matches: &clap::ArgMatches, globals: &Globals) -> i32 {
+    let &Globals {
+        tone_freq,
+        dot_duration,
+        ..
+    } = globals;
+    let winkeyer = sub_matches.get_one::<String>("winkeyer").map(|path| {
+        winkeyer::open(path).unwrap_or_else(|e| {
+            error!("Could not open the Winkeyer on {path}: {e}");
+            std::process::exit(1);
+        })
+    });
+    if winkeyer.is_none() {
+        if !cfg!(target_os = "linux") {
+            error!("Sorry, the cwdaemon feature is only supported on Linux right now.");
+            std::process::exit(1);
+        }
+        ensure_pipewire();
+    }
+    let port = *sub_matches
+        .get_one::<u16>("port")
+        .expect("Missing --port arg default");
+    let volume = *sub_matches
+        .get_one::<f32>("volume")
+        .expect("Missing --volume arg default");
+    let settings = daemon::Settings {
+        wpm: morse::dot_length_to_wpm(dot_duration),
+        tone: tone_freq.round() as u32,
+        weight: 0,
+        ptt_delay: 0,
+        volume: (volume * 100.0).round() as u32,
+    };
+    let options = sidetone::SidetoneOptions {
+        freq: tone_freq,
+        volume,
+        rise_time: *sub_matches
+            .get_one::<f32>("rise-time")
+            .expect("Missing --rise-time arg default"),
+        target: sub_matches.get_one::<String>("target").cloned(),
+    };
+    match daemon::serve(port, settings, options, winkeyer) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Could not listen on UDP port {port}: {e}");
+            1
+        }
+    }
+}
+
+/// Runs `completions`, printing the completion script of a shell, or
+/// how to enable them.
+fn run_completions(sub_matches: &clap::ArgMatches) -> i32 {
+    if let Some(shell) = sub_matches.get_one::<String>("shell") {
+        match shell.as_str() {
+            "bash" => generate_completion_script(Shell::Bash),
+            "zsh" => generate_completion_script(Shell::Zsh),
+            "fish" => generate_completion_script(Shell::Fish),
+            shell => eprintln!("Unsupported shell: {shell}"),
+        }
+        0
+    } else {
+        eprintln!(
+            "### Instructions to enable tab completion for {}",
+            env!("CARGO_BIN_NAME")
+        );
+        eprintln!();
+        eprintln!("### Bash (put this in ~/.bashrc:)");
+        eprintln!("  source <({} completions bash)", env!("CARGO_BIN_NAME"));
+        eprintln!();
+        eprintln!("### To make an alias (eg. 'h'), add this too:");
+        eprintln!("  alias h={}", env!("CARGO_BIN_NAME"));
+        eprintln!(
+            "  complete -F _{} -o bashdefault -o default h",
+            env!("CARGO_BIN_NAME")
+        );
+        eprintln!();
+        eprintln!("### If you don't use Bash, you can also use Fish or Zsh:");
+        eprintln!("### Fish (put this in ~/.config/fish/config.fish");
+        eprintln!("  {} completions fish | source)", env!("CARGO_BIN_NAME"));
+        eprintln!("### Zsh (put this in ~/.zshrc)");
+        eprintln!(
+            "  autoload -U compinit; compinit; source <({} completions zsh)",
+            env!("CARGO_BIN_NAME")
+        );
+        1
+    }
+}
+
+/// Runs `practice`, the drill asked for.
+fn run_practice(sub_matches: &clap::ArgMatches, globals: &Globals) -> i32 {
+    let &Globals {
+        tone_freq,
+        dot_duration,
+        charset,
+        ..
+    } = globals;
+    let script = sub_matches.get_one::<String>("script").map(|path| {
+        let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            error!("Could not read {path}: {e}");
+            std::process::exit(1);
+        });
+        script::parse_script(&text, charset).unwrap_or_else(|e| {
+            error!("{path}: {e}");
+            std::process::exit(1);
+        })
+    });
+    let lesson = sub_matches.get_one::<u32>("koch").copied();
+    let exclude = sub_matches
+        .get_one::<String>("exclude")
+        .expect("Missing --exclude arg default");
+    let pool = match (sub_matches.get_one::<String>("characters"), lesson) {
+        (Some(characters), _) => practice::parse_pool(characters, charset),
+        (None, Some(lesson)) => Ok(koch::lesson_characters(lesson)
+            .chars()
+            .map(String::from)
+            .collect()),
+        (None, None) => practice::parse_pool("letters,digits", charset),
+    }
+    .and_then(|pool| practice::exclude(pool, exclude, charset))
+    .unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    });
+    let group_size = *sub_matches
+        .get_one::<(usize, usize)>("group-size")
+        .expect("Missing --group-size arg default");
+    let rufz = *sub_matches
+        .get_one::<bool>("rufz")
+        .expect("Missing --rufz arg default");
+    // A speed ladder sends its number of calls:
+    let count = sub_matches
+        .get_one::<u32>("count")
+        .copied()
+        .or(rufz.then_some(rufz::CALLS));
+    let duration = match (sub_matches.get_one::<f32>("duration"), count) {
+        (Some(minutes), _) => Some(minutes * 60.0),
+        (None, Some(_)) => None,
+        (None, None) => Some(5.0 * 60.0),
+    };
+    let farnsworth = sub_matches.get_one::<u32>("farnsworth").copied();
+    let word_space = *sub_matches
+        .get_one::<f32>("extra-word-space")
+        .expect("Missing --extra-word-space arg default");
+    // Everything drawn at random is drawn from it, and the noise
+    // seeded with it, to practice the very same drill again:
+    let seed = band::seed_or_random(sub_matches.get_one::<u64>("seed").copied());
+    println!("Seed: {seed} (--seed {seed} to practice the same drill again)\n");
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let conditions =
+        make_conditions(sub_matches).map(|conditions| band::Conditions { seed, ..conditions });
+    if let Some(conditions) = conditions {
+        println!("Band conditions: {conditions}\n");
+    }
+    let band = conditions.map(band::Band::new);
+    let began = std::time::Instant::now();
+    if let Some(script) = sub_matches.get_one::<String>("qso") {
+        let text = match qso::SCRIPTS.iter().find(|(name, _)| name == script) {
+            Some((_, text)) => text.to_string(),
+            None => std::fs::read_to_string(script).unwrap_or_else(|e| {
+                error!("Could not read {script}: {e}");
+                std::process::exit(1);
+            }),
+        };
+        let script = qso::parse_script(&text).unwrap_or_else(|e| {
+            error!("{script}: {e}");
+            std::process::exit(1);
+        });
+        let copies = qso::run_qso(
+            &qso::Qso {
+                script,
+                mycall: sub_matches.get_one::<String>("mycall").cloned(),
+                dot_duration,
+                farnsworth,
+                word_space,
+                tone_freq,
+                band: band.clone(),
+            },
+            &mut rng,
+        );
+        qso::print_report(&copies);
+        if !copies.is_empty() {
+            let (copied, sent) = qso::copied_words(&copies);
+            stats::record(
+                &stats::Session::new(
+                    "qso",
+                    "",
+                    morse::dot_length_to_wpm(dot_duration),
+                    began.elapsed(),
+                )
+                .with_accuracy(copied as f64 / sent.max(1) as f64 * 100.0)
+                .with_band(conditions)
+                .with_seed(seed),
+            );
+        }
+        std::process::exit(0);
+    }
+    if let Some(&lag) = sub_matches.get_one::<u32>("copy-behind") {
+        let stream = copy_behind::run_copy_behind(
+            &copy_behind::CopyBehind {
+                pool: pool.clone(),
+                lag,
+                length: *sub_matches
+                    .get_one::<u32>("stream-length")
+                    .expect("Missing --stream-length arg default"),
+                dot_duration,
+                farnsworth,
+                tone_freq,
+                band: band.clone(),
+            },
+            &mut rng,
+        );
+        let tables = Vec::from_iter(copy_behind::print_report(&stream, lag));
+        let session = (!stream.sent.is_empty()).then(|| {
+            stats::Session::new("copy-behind", &pool.concat(), stream.wpm, began.elapsed())
+                .with_accuracy(stream.accuracy())
+                .with_trials(stream.trials())
+                .with_incomplete(stream.stopped)
+                .with_band(conditions)
+                .with_seed(seed)
+        });
+        finish_practice(sub_matches, &tables, session.as_ref());
+        std::process::exit(0);
+    }
+    let pass_accuracy = *sub_matches
+        .get_one::<f32>("pass")
+        .expect("Missing --pass arg default");
+    let history = sub_matches
+        .get_one::<String>("history")
+        .map(std::path::PathBuf::from)
+        .or_else(koch::default_history_path);
+    if let Some(lesson) = lesson {
+        println!("Koch method practice, lesson {lesson}.\n");
+    }
+    let callsigns = *sub_matches
+        .get_one::<bool>("callsigns")
+        .expect("Missing --callsigns arg default");
+    let words = sub_matches.contains_id("words");
+    let abbreviations = sub_matches.contains_id("abbreviations").then(|| {
+        let mut table = abbreviations::Meanings::builtin();
+        if let Some(path) = sub_matches.get_one::<String>("abbreviations") {
+            if let Err(e) = std::fs::read_to_string(path)
+                .map_err(|e| format!("Could not read {path}: {e}"))
+                .and_then(|text| {
+                    table
+                        .extend_from_toml(&text)
+                        .map_err(|e| format!("{path}: {e}"))
+                })
+            {
+                error!("{e}");
+                std::process::exit(1);
+            }
+        }
+        let direction = *sub_matches
+            .get_one::<abbreviations::Direction>("direction")
+            .expect("Missing --direction arg default");
+        match direction {
+            abbreviations::Direction::Hear => println!("Abbreviation drill: their meanings are shown with the answers.\n"),
+            abbreviations::Direction::Read => println!("Abbreviation drill: the meaning of each one is shown in place of its code, for you to type (or key) the abbreviation.\n"),
+        }
+        (table, direction)
+    });
+    // Scored and reviewed a whole word at a time:
+    let word_drill = words || abbreviations.is_some();
+    let head_copy = *sub_matches
+        .get_one::<bool>("head-copy")
+        .expect("Missing --head-copy arg default");
+    let exchange = sub_matches.get_one::<String>("exchange");
+    let review = *sub_matches
+        .get_one::<bool>("review")
+        .expect("Missing --review arg default");
+    let today = chrono::Local::now().date_naive();
+    let (mode, pool_text) = match (lesson, exchange, &script) {
+        (_, _, Some(script)) => ("script", script.name.clone()),
+        _ if review => ("review", String::new()),
+        (_, Some(template), _) => ("exchange", template.clone()),
+        _ if head_copy => ("head-copy", String::new()),
+        _ if abbreviations.is_some() => ("abbreviations", String::new()),
+        _ if words => ("words", String::new()),
+        _ if rufz => ("rufz", String::new()),
+        _ if callsigns => ("callsigns", String::new()),
+        (Some(_), None, _) => ("koch", pool.concat()),
+        (None, None, _) => ("groups", pool.concat()),
+    };
+    let focus_weak = *sub_matches
+        .get_one::<bool>("focus-weak")
+        .expect("Missing --focus-weak arg default");
+    // The chance of each character, from how it was copied before:
+    let focus = focus_weak.then(|| {
+        let mix = *sub_matches
+            .get_one::<f64>("focus-mix")
+            .expect("Missing --focus-mix arg default");
+        let sessions = match stats::default_path().map(|path| stats::read_sessions(&path)) {
+            Some(Ok((sessions, _))) => sessions,
+            Some(Err(e)) => {
+                warn!("Could not read the recorded sessions: {e}");
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        let history: Vec<Option<stats::CharacterStats>> = pool
+            .iter()
+            .map(|character| stats::baseline(&sessions, character))
+            .collect();
+        let weights = practice::focus_weights(&history, mix);
+        (pool.clone(), weights, history)
+    });
+    let groups = if script.is_some() {
+        // Each section of the script sends its own:
+        practice::Groups::Sequence(std::collections::VecDeque::new())
+    } else if review {
+        let schedule = review::load_schedule();
+        let mut items = schedule.due(today);
+        if items.is_empty() {
+            let lesson = history.as_deref().map_or(1, koch::current_lesson);
+            items = koch::lesson_characters(lesson)
+                .chars()
+                .map(String::from)
+                .filter(|character| !schedule.contains(character))
+                .collect();
+            match (items.is_empty(), schedule.cards().first()) {
+                (true, Some(card)) => {
+                    println!("Nothing is due for review until {}.", card.due);
+                    std::process::exit(0);
+                }
+                (true, None) => {
+                    println!("Nothing is due for review.");
+                    std::process::exit(0);
                 }
-                (Some(_device), None) => {
-                    error!("TODO. Setting the input device name is not supported yet. Leave this setting unset to use the default device.");
+                (false, _) => println!(
+                    "Nothing is due for review: learning the new characters of Koch lesson {lesson}.\n"
+                ),
+            }
+        }
+        practice::Groups::review(&items, group_size, &mut rng)
+    } else if let Some(template) = exchange {
+        let cut_numbers = *sub_matches
+            .get_one::<bool>("cut-numbers")
+            .expect("Missing --cut-numbers arg default");
+        practice::Groups::Exchanges(
+            contest::Exchange::new(template, cut_numbers, &mut rng).unwrap_or_else(|e| {
+                error!("{e}");
+                std::process::exit(1);
+            }),
+        )
+    } else if let Some((table, _)) = &abbreviations {
+        practice::Groups::Words(words::WordPicker::new(table.codes()))
+    } else if words {
+        let min_len = *sub_matches
+            .get_one::<u32>("min-len")
+            .expect("Missing --min-len arg default");
+        let max_len = sub_matches.get_one::<u32>("max-len").copied();
+        let length = (min_len as usize, max_len.map_or(usize::MAX, |n| n as usize));
+        let (source, text) = match sub_matches.get_one::<String>("words") {
+            Some(path) => (
+                path.as_str(),
+                std::fs::read_to_string(path).unwrap_or_else(|e| {
+                    error!("Could not read {path}: {e}");
+                    std::process::exit(1);
+                }),
+            ),
+            None => ("the word list", words::WORDS.to_string()),
+        };
+        let list = words::read_words(&text, length);
+        if list.is_empty() {
+            error!("There are no words of that length in {source}");
+            std::process::exit(1);
+        }
+        let picker = words::WordPicker::new(list);
+        match head_copy {
+            true => practice::Groups::Phrases {
+                picker,
+                words: *sub_matches
+                    .get_one::<(usize, usize)>("phrase-words")
+                    .expect("Missing --phrase-words arg default"),
+            },
+            false => practice::Groups::Words(picker),
+        }
+    } else if callsigns {
+        practice::Groups::Callsigns(sub_matches.get_one::<String>("from-file").map(|path| {
+            match std::fs::read_to_string(path).map(|text| callsign::read_callsigns(&text)) {
+                Ok(list) if !list.is_empty() => list,
+                Ok(_) => {
+                    error!("There are no callsigns in {path}");
                     std::process::exit(1);
                 }
-                (Some(_device), Some(_file)) => {
-                    error!("Cannot specify --device and --file simultaneousy.");
+                Err(e) => {
+                    error!("Could not read {path}: {e}");
                     std::process::exit(1);
                 }
-                _ => {}
             }
-            if cfg!(target_os = "linux") {
-                ensure_pipewire();
-                pipewire::listen(tone_freq, bandwidth, threshold, dot_duration, *morse)
-                    .expect("pipewire::listen() failed");
-            } else {
-                error!("Sorry, the listen feature is only supported on Linux right now.");
+        }))
+    } else {
+        practice::Groups::Random {
+            pool,
+            size: group_size,
+            weights: focus.as_ref().map(|(_, weights, _)| {
+                rand::distributions::WeightedIndex::new(weights)
+                    .expect("Focus weights should add up to 1")
+            }),
+        }
+    };
+    let groups = match groups {
+        practice::Groups::Words(picker)
+            if *sub_matches
+                .get_one::<bool>("send")
+                .expect("Missing --send arg default") =>
+        {
+            let mut drill = send::SendDrill {
+                picker,
+                count,
+                duration: duration.map(std::time::Duration::from_secs_f32),
+                dot_duration,
+                sidetone: make_sidetone(sub_matches, tone_freq),
+                charset,
+                keys: make_keys(sub_matches),
+                meanings: abbreviations
+                    .clone()
+                    .filter(|(_, direction)| *direction == abbreviations::Direction::Read)
+                    .map(|(table, _)| table),
+            };
+            let sending = send::run_send(&mut drill, &mut rng).unwrap_or_else(|e| {
+                error!("{e}");
                 std::process::exit(1);
+            });
+            let tables = Vec::from_iter(send::print_report(&sending));
+            let session = (!sending.words.is_empty()).then(|| {
+                stats::Session::new(
+                    "send",
+                    "",
+                    sending
+                        .wpm()
+                        .unwrap_or(morse::dot_length_to_wpm(dot_duration)),
+                    began.elapsed(),
+                )
+                .with_accuracy(sending.accuracy())
+                .with_incomplete(sending.stopped)
+                .with_seed(seed)
+            });
+            finish_practice(sub_matches, &tables, session.as_ref());
+            std::process::exit(0);
+        }
+        groups => groups,
+    };
+    let resend = sub_matches.get_one::<u32>("resend").copied();
+    let ramp = sub_matches
+        .get_one::<(u32, u32)>("speed-ramp")
+        .map(|&(start, end)| practice::SpeedRamp {
+            start,
+            end,
+            steps: sub_matches.get_one::<u32>("ramp-every").map(|&every| {
+                let step = *sub_matches
+                    .get_one::<u32>("ramp-step")
+                    .expect("Missing --ramp-step arg default");
+                (step, every)
+            }),
+            floor: sub_matches.get_one::<f32>("ramp-floor").map(|&f| f.into()),
+        });
+    let random_speed = sub_matches.get_one::<(u32, u32)>("random-speed").copied();
+    let random_pitch = sub_matches.get_one::<(f32, f32)>("random-pitch").copied();
+    let varied_speed = ramp.is_some() || random_speed.is_some() || rufz;
+    // A review ends with its groups, unless it is cut short:
+    let (count, duration) = match groups.remaining() {
+        Some(remaining) => (
+            Some(count.map_or(remaining as u32, |count| count.min(remaining as u32))),
+            sub_matches
+                .get_one::<f32>("duration")
+                .map(|minutes| minutes * 60.0),
+        ),
+        None => (count, duration),
+    };
+    let mut drill = practice::Drill {
+        groups,
+        count,
+        duration: duration.map(std::time::Duration::from_secs_f32),
+        dot_duration,
+        farnsworth,
+        word_space,
+        tone_freq,
+        resend,
+        ramp,
+        random_speed,
+        random_pitch,
+        feedback: *sub_matches
+            .get_one::<feedback::Feedback>("feedback")
+            .expect("Missing --feedback arg default"),
+        review_passes: match sub_matches
+            .get_one::<bool>("review-missed")
+            .expect("Missing --review-missed arg default")
+        {
+            true => *sub_matches
+                .get_one::<u32>("review-passes")
+                .expect("Missing --review-passes arg default"),
+            false => 0,
+        },
+        review_speed_offset: *sub_matches
+            .get_one::<u32>("review-speed-offset")
+            .expect("Missing --review-speed-offset arg default"),
+        reveal: sub_matches
+            .get_one::<practice::Reveal>("reveal")
+            .map(|&reveal| {
+                let delay = sub_matches
+                    .get_one::<u64>("reveal-delay-ms")
+                    .expect("Missing --reveal-delay-ms arg default");
+                (reveal, std::time::Duration::from_millis(*delay))
+            }),
+        pause_key: *sub_matches
+            .get_one::<crossterm::event::KeyCode>("pause-key")
+            .expect("Missing --pause-key arg default"),
+        answer_timeout: sub_matches
+            .get_one::<u64>("answer-timeout-ms")
+            .map(|&ms| std::time::Duration::from_millis(ms)),
+        attempts: *sub_matches
+            .get_one::<u32>("attempts")
+            .expect("Missing --attempts arg default"),
+        typeahead: !*sub_matches
+            .get_one::<bool>("no-typeahead")
+            .expect("Missing --no-typeahead arg default"),
+        band,
+        abbreviations: abbreviations.clone(),
+        interferer: sub_matches
+            .get_one::<interference::Content>("interferer")
+            .map(|&content| {
+                interference::Interferer::new(
+                    content,
+                    *sub_matches
+                        .get_one::<f32>("interferer-offset-hz")
+                        .expect("Missing --interferer-offset-hz arg default"),
+                    *sub_matches
+                        .get_one::<f32>("interferer-level-db")
+                        .expect("Missing --interferer-level-db arg default"),
+                    *sub_matches
+                        .get_one::<interference::Start>("interferer-start")
+                        .expect("Missing --interferer-start arg default"),
+                )
+            }),
+        ladder: rufz.then(|| {
+            rufz::Ladder::new(
+                morse::dot_length_to_wpm(dot_duration),
+                *sub_matches
+                    .get_one::<rufz::Step>("rufz-up")
+                    .expect("Missing --rufz-up arg default"),
+                *sub_matches
+                    .get_one::<rufz::Step>("rufz-down")
+                    .expect("Missing --rufz-down arg default"),
+                *sub_matches
+                    .get_one::<rufz::Scoring>("rufz-scoring")
+                    .expect("Missing --rufz-scoring arg default"),
+            )
+        }),
+        panes: io::stdout().is_terminal() && !term::accessible() && !sub_matches.get_flag("plain"),
+        answers: match sub_matches.get_one::<answer::AnswerMode>("answer-mode") {
+            Some(answer::AnswerMode::Key) => Box::new(
+                answer::Keyer::new(
+                    make_keys(sub_matches),
+                    &make_sidetone(sub_matches, tone_freq),
+                    dot_duration,
+                    charset,
+                )
+                .unwrap_or_else(|e| {
+                    error!("{e}");
+                    std::process::exit(1);
+                }),
+            ),
+            _ => Box::new(answer::Keyboard::new()),
+        },
+    };
+    let outlier_iqr = *sub_matches
+        .get_one::<f64>("outlier-iqr")
+        .expect("Missing --outlier-iqr arg default");
+    let (results, sections) = match &script {
+        Some(script) => {
+            let (results, sections) =
+                script::run_script(script, &mut drill, group_size, conditions, seed, &mut rng);
+            (results, Some(sections))
+        }
+        None => (practice::run_drill(&mut drill, &mut rng), None),
+    };
+    if results.incomplete {
+        println!("\nThe drill was stopped: these are its results so far, recorded as incomplete.");
+    }
+    // The tables of the summary, for --copy-on-exit:
+    let mut tables = Vec::new();
+    if !word_drill && exchange.is_none() {
+        tables.push(fecr_quiz::print_results(
+            &results.characters,
+            std::time::Duration::from_millis(dot_duration.into()),
+            false,
+            0,
+            outlier_iqr,
+            *sub_matches
+                .get_one::<bool>("histogram")
+                .expect("Missing --histogram arg default"),
+        ));
+    }
+    if callsigns || word_drill || exchange.is_some() || review || script.is_some() {
+        practice::print_groups(&results);
+    }
+    if let Some(ladder) = &drill.ladder {
+        match ladder.sustained_wpm() {
+            Some(wpm) => println!(
+                "\nScore: {} points. Highest speed held for {} calls in a row: {wpm} WPM.",
+                ladder.score,
+                rufz::SUSTAINED
+            ),
+            None => println!("\nScore: {} points.", ladder.score),
+        }
+    }
+    if let Some(sections) = &sections {
+        tables.push(script::print_sections(sections));
+    }
+    if head_copy {
+        tables.extend(practice::print_phrases(&results));
+    }
+    if varied_speed {
+        tables.extend(practice::print_speeds(&results));
+    }
+    if random_pitch.is_some() {
+        tables.extend(practice::print_tones(&results));
+    }
+    if let Some((table, _)) = &abbreviations {
+        tables.extend(abbreviations::print_unrecognized(
+            &results,
+            table,
+            dot_duration,
+        ));
+    }
+    if let Some(keying) = drill.answers.keying() {
+        tables.push(send::print_keying(keying));
+    }
+    if drill.attempts > 1 {
+        practice::print_attempts(&results, drill.attempts);
+    }
+    if !results.review.is_empty() {
+        practice::print_review(&results);
+    }
+    if *sub_matches
+        .get_one::<bool>("confusion")
+        .expect("Missing --confusion arg default")
+    {
+        let characters = &results.characters;
+        confusion::print_confusions(&confusion::confusions(characters), |character| {
+            characters
+                .prompts
+                .iter()
+                .filter(|c| *c == character)
+                .count() as u32
+        });
+    }
+    if let Some((pool, weights, history)) = &focus {
+        practice::print_focus(&results, pool, weights, history, outlier_iqr);
+    }
+    let session = (!results.groups.is_empty()).then(|| {
+        let session = stats::Session::new(
+            mode,
+            &pool_text,
+            results
+                .wpm()
+                .unwrap_or_else(|| morse::dot_length_to_wpm(dot_duration)),
+            began.elapsed().saturating_sub(results.paused),
+        );
+        match word_drill || exchange.is_some() {
+            true if head_copy => session.with_accuracy(practice::word_accuracy(&results.groups)),
+            true => session.with_accuracy(results.group_accuracy()),
+            false => session.with_characters(&results.characters, dot_duration, outlier_iqr),
+        }
+        .with_trials(results.trials.clone())
+        .with_incomplete(results.incomplete)
+        .with_band(conditions)
+        .with_seed(seed)
+        .with_sustained_wpm(drill.ladder.as_ref().and_then(rufz::Ladder::sustained_wpm))
+    });
+    finish_practice(sub_matches, &tables, session.as_ref());
+    if review || word_drill || lesson.is_some() {
+        let mut schedule = review::load_schedule();
+        schedule.answer_drill(
+            &results,
+            |sent| drill.groups.is_word(sent),
+            dot_duration,
+            today,
+        );
+        review::save_schedule(&schedule);
+    }
+    if let Some(lesson) = lesson {
+        koch::finish_lesson(
+            &results.characters,
+            lesson,
+            pass_accuracy.into(),
+            history.as_deref(),
+        );
+    }
+    0
+}
+
+/// Runs `config`: shows the settings with `--show`, or else where the
+/// config file is.
+fn run_config(sub_matches: &clap::ArgMatches, setup: &Setup) -> i32 {
+    if sub_matches.get_flag("show") {
+        // The sections of the file, and those that are the most used:
+        let mut sections: Vec<String> = [config::OUTPUT, "listen", "practice"]
+            .map(String::from)
+            .into();
+        for layer in &setup.layers {
+            for setting in &layer.settings {
+                if !sections.contains(&setting.section) {
+                    sections.push(setting.section.clone());
+                }
             }
-            0
         }
-        Some(("completions", sub_matches)) => {
-            if let Some(shell) = sub_matches.get_one::<String>("shell") {
-                match shell.as_str() {
-                    "bash" => generate_completion_script(Shell::Bash),
-                    "zsh" => generate_completion_script(Shell::Zsh),
-                    "fish" => generate_completion_script(Shell::Fish),
-                    shell => eprintln!("Unsupported shell: {shell}"),
+        print!(
+            "{}",
+            config::show(
+                &setup.cmd,
+                &setup.matches,
+                &setup.origins,
+                &[
+                    (
+                        "profile",
+                        Some(profile::current().to_string()),
+                        setup.profile_origin.clone()
+                    ),
+                    (
+                        "preset",
+                        setup.preset.clone(),
+                        setup.preset_origin.to_string()
+                    ),
+                ],
+                &sections,
+            )
+        );
+    } else {
+        println!("{}", setup.config_path);
+    }
+    0
+}
+
+/// Runs `profile`, listing, creating or deleting the profiles.
+fn run_profile(sub_matches: &clap::ArgMatches) -> i32 {
+    match sub_matches.subcommand() {
+        Some(("list", _)) => match profile::list() {
+            Ok(names) => {
+                for name in names {
+                    match name == profile::current() {
+                        true => println!("* {name}"),
+                        false => println!("  {name}"),
+                    }
                 }
                 0
-            } else {
-                eprintln!(
-                    "### Instructions to enable tab completion for {}",
-                    env!("CARGO_BIN_NAME")
-                );
-                eprintln!();
-                eprintln!("### Bash (put this in ~/.bashrc:)");
-                eprintln!("  source <({} completions bash)", env!("CARGO_BIN_NAME"));
-                eprintln!();
-                eprintln!("### To make an alias (eg. 'h'), add this too:");
-                eprintln!("  alias h={}", env!("CARGO_BIN_NAME"));
-                eprintln!(
-                    "  complete -F _{} -o bashdefault -o default h",
-                    env!("CARGO_BIN_NAME")
-                );
-                eprintln!();
-                eprintln!("### If you don't use Bash, you can also use Fish or Zsh:");
-                eprintln!("### Fish (put this in ~/.config/fish/config.fish");
-                eprintln!("  {} completions fish | source)", env!("CARGO_BIN_NAME"));
-                eprintln!("### Zsh (put this in ~/.zshrc)");
-                eprintln!(
-                    "  autoload -U compinit; compinit; source <({} completions zsh)",
-                    env!("CARGO_BIN_NAME")
-                );
+            }
+            Err(e) => {
+                error!("Could not list the profiles: {e}");
                 1
             }
+        },
+        Some(("create", create_matches)) => {
+            let name = create_matches
+                .get_one::<String>("name")
+                .expect("Missing profile name");
+            match profile::create(name) {
+                Ok(dir) => {
+                    println!("Created the profile {name}, in {}", dir.display());
+                    0
+                }
+                Err(e) => {
+                    error!("{e}");
+                    1
+                }
+            }
         }
-        Some(("credits", _sub_matches)) => {
-            print_credits();
-            0
+        Some(("delete", delete_matches)) => {
+            let name = delete_matches
+                .get_one::<String>("name")
+                .expect("Missing profile name");
+            let dir = match profile::deletable(name) {
+                Ok(dir) => dir,
+                Err(e) => {
+                    error!("{e}");
+                    std::process::exit(1);
+                }
+            };
+            if !delete_matches.get_flag("yes") {
+                print!("Delete the profile {name}, with all of its history? [y/N] ");
+                std::io::stdout().flush().unwrap();
+                let mut answer = String::new();
+                std::io::stdin()
+                    .lock()
+                    .read_line(&mut answer)
+                    .unwrap_or_default();
+                if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                    std::process::exit(1);
+                }
+            }
+            match std::fs::remove_dir_all(&dir) {
+                Ok(()) => {
+                    println!("Deleted the profile {name}");
+                    0
+                }
+                Err(e) => {
+                    error!("Could not delete {}: {e}", dir.display());
+                    1
+                }
+            }
         }
-        _ => 1,
+        _ => unreachable!("A profile subcommand is required"),
+    }
+}
+
+/// Runs `stats`, showing the history of the sessions practiced.
+fn run_stats(sub_matches: &clap::ArgMatches) -> i32 {
+    if *sub_matches
+        .get_one::<bool>("due")
+        .expect("Missing --due arg default")
+    {
+        review::print_schedule(&review::load_schedule(), chrono::Local::now().date_naive());
+        std::process::exit(0);
+    }
+    let Some(path) = stats::default_path() else {
+        error!("Could not find the user data directory");
+        std::process::exit(1);
     };
+    let (sessions, skipped) = stats::read_sessions(&path).unwrap_or_else(|e| {
+        error!("Could not read {}: {e}", path.display());
+        std::process::exit(1);
+    });
+    if skipped > 0 {
+        warn!("Skipped {skipped} unreadable records in {}", path.display());
+    }
+    let last = sub_matches.get_one::<u32>("last").map(|&n| n as usize);
+    match (
+        sub_matches.subcommand(),
+        sub_matches.get_one::<String>("char"),
+    ) {
+        (Some(("confusion", confusion_matches)), _) => stats::print_confusions(
+            &sessions,
+            confusion_matches
+                .get_one::<u32>("last")
+                .map(|&n| n as usize),
+        ),
+        (Some(("export", export_matches)), _) => {
+            let last = export_matches.get_one::<u32>("last").map(|&n| n as usize);
+            if !export_trials(export_matches, "to", &sessions, last) {
+                std::process::exit(1);
+            }
+        }
+        _ if *sub_matches
+            .get_one::<bool>("trend")
+            .expect("Missing --trend arg default") =>
+        {
+            stats::print_sparklines(
+                &sessions,
+                last.unwrap_or(10),
+                *sub_matches
+                    .get_one::<stats::TrendOrder>("sort")
+                    .expect("Missing --sort arg default"),
+                *sub_matches
+                    .get_one::<bool>("ascii")
+                    .expect("Missing --ascii arg default")
+                    || !sparkline::unicode_supported(),
+            )
+        }
+        (_, Some(character)) => stats::print_character(&sessions, character, last),
+        _ => stats::print_trends(&sessions, last.unwrap_or(10)),
+    }
+    0
+}
 
-    eprintln!();
-    std::process::exit(exit_code);
+/// Runs `bench-decode`, scoring the decoder on a grid of clips.
+fn run_bench_decode(sub_matches: &clap::ArgMatches, globals: &Globals) -> i32 {
+    let &Globals {
+        tone_setting,
+        tone_freq,
+        adaptive_speed,
+        dot_duration,
+        charset,
+        ..
+    } = globals;
+    let grid = bench::Grid {
+        wpms: sub_matches
+            .get_many::<u32>("wpm-grid")
+            .expect("Missing --wpm-grid arg default")
+            .copied()
+            .collect(),
+        snrs: sub_matches
+            .get_many::<f32>("snr-grid")
+            .expect("Missing --snr-grid arg default")
+            .copied()
+            .collect(),
+        impulses: *sub_matches
+            .get_one::<f32>("impulses")
+            .expect("Missing --impulses arg default"),
+        tone_freq,
+    };
+    let decoder = bench::Decoder {
+        tone_freq: tone_setting,
+        tone_range: (300.0, 1200.0),
+        bandwidth: *sub_matches
+            .get_one::<f32>("bandwidth")
+            .expect("Missing --bandwidth arg default"),
+        // Following the noise floor, unless told otherwise, since
+        // most of the corpus is noisy:
+        threshold: sub_matches
+            .get_one::<Option<f32>>("threshold")
+            .copied()
+            .unwrap_or(None),
+        detector: sub_matches
+            .get_one::<String>("detector")
+            .expect("Missing --detector arg default")
+            .parse::<filter::Detector>()
+            .expect("Invalid --detector"),
+        dot_duration,
+        adaptive_speed,
+        text_options: detect::TextOptions {
+            charset,
+            ..Default::default()
+        },
+    };
+    let mut rng = rand::rngs::StdRng::seed_from_u64(
+        *sub_matches
+            .get_one::<u64>("seed")
+            .expect("Missing --seed arg default"),
+    );
+    let dir = sub_matches
+        .get_one::<String>("dir")
+        .map(std::path::Path::new);
+    let cases = match (sub_matches.get_flag("synthesize"), dir) {
+        (true, dir) => {
+            let count = *sub_matches
+                .get_one::<u64>("count")
+                .expect("Missing --count arg default");
+            let cases = bench::synthesize(&grid, count as usize, &mut rng);
+            if let Some(dir) = dir {
+                if let Err(e) = bench::save(dir, &cases) {
+                    error!("Could not write the corpus to {}: {e}", dir.display());
+                    std::process::exit(1);
+                }
+            }
+            cases
+        }
+        (false, Some(dir)) => match bench::load(dir, &grid, &mut rng) {
+            Ok(cases) => cases,
+            Err(e) => {
+                error!("Could not read the corpus of {}: {e}", dir.display());
+                std::process::exit(1);
+            }
+        },
+        (false, None) => unreachable!("The corpus arg group is required"),
+    };
+    match bench::bench(&cases, &decoder) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("{e}");
+            1
+        }
+    }
+}
+
+/// Runs `listen`, decoding the file given, or else the audio captured.
+fn run_listen(sub_matches: &clap::ArgMatches, globals: &Globals) -> i32 {
+    let &Globals {
+        tone_setting,
+        adaptive_speed,
+        dot_duration,
+        charset,
+        ..
+    } = globals;
+    //
+    let morse = sub_matches
+        .get_one::<bool>("morse")
+        .expect("Missing --morse arg default");
+    let text_options = detect::TextOptions {
+        show_symbols: *sub_matches
+            .get_one::<bool>("show-symbols")
+            .expect("Missing --show-symbols arg default"),
+        prosigns: !*sub_matches
+            .get_one::<bool>("no-prosigns")
+            .expect("Missing --no-prosigns arg default"),
+        error_char: *sub_matches
+            .get_one::<Option<char>>("error-char")
+            .expect("Missing --error-char arg default"),
+        max_errors: *sub_matches
+            .get_one::<usize>("max-errors")
+            .expect("Missing --max-errors arg default"),
+        charset,
+        farnsworth: sub_matches.get_one::<u32>("farnsworth").copied(),
+    };
+    let target = sub_matches
+        .get_one::<String>("target")
+        .map(|s| s.to_string());
+    let file = sub_matches.get_one::<String>("file").map(|s| s.to_string());
+    // The threshold is None when it should adapt to the signal:
+    let threshold = sub_matches
+        .get_one::<Option<f32>>("threshold")
+        .copied()
+        .unwrap_or(Some(0.3));
+    // Ignore changes shorter than a fraction of a dot:
+    let debounce = sub_matches
+        .get_one::<f32>("debounce")
+        .expect("Missing --debounce arg default");
+    let debounce_duration = (dot_duration as f32 * debounce).round() as u32;
+    // End the message after a silence of this many dots:
+    let timeout_dots = sub_matches
+        .get_one::<u32>("timeout-dots")
+        .expect("Missing --timeout-dots arg default");
+    let mut timeout_duration = dot_duration * timeout_dots;
+    if let Some(wpm) = text_options.farnsworth {
+        // The spread out word gaps shouldn't end the message:
+        let word_gap = keyer::Timing::new(dot_duration, Some(wpm)).word_gap;
+        timeout_duration = timeout_duration.max(2 * word_gap);
+    }
+    let channel = *sub_matches
+        .get_one::<detect::Channel>("channel")
+        .expect("Missing --channel arg default");
+    let split_stereo = *sub_matches
+        .get_one::<bool>("split-stereo")
+        .expect("Missing --split-stereo arg default");
+    let ring_capacity = *sub_matches
+        .get_one::<usize>("ring-capacity")
+        .expect("Missing --ring-capacity arg default");
+    let bandwidth = sub_matches
+        .get_one::<f32>("bandwidth")
+        .copied()
+        .unwrap_or(200.0);
+    let detector = sub_matches
+        .get_one::<String>("detector")
+        .expect("Missing --detector arg default")
+        .parse::<filter::Detector>()
+        .expect("Invalid --detector");
+    let tone_range = *sub_matches
+        .get_one::<(f32, f32)>("tone-range")
+        .expect("Missing --tone-range arg default");
+    let station = |label: Option<&str>, channel, tone: Option<&f32>| {
+        let label = label.map(|l| l.to_string());
+        match tone.copied().or(tone_setting) {
+            Some(tone_freq) => detect::Station {
+                label,
+                channel,
+                tone_freq,
+                tone_range: None,
+            },
+            // Start in the middle of the search range when the tone is unknown:
+            None => detect::Station {
+                label,
+                channel,
+                tone_freq: (tone_range.0 + tone_range.1) / 2.0,
+                tone_range: Some(tone_range),
+            },
+        }
+    };
+    let scan = sub_matches.get_one::<scan::ScanRange>("scan").copied();
+    let stations = if split_stereo {
+        vec![
+            station(
+                Some("L:"),
+                detect::Channel::Index(0),
+                sub_matches.get_one::<f32>("tone-freq-left"),
+            ),
+            station(
+                Some("R:"),
+                detect::Channel::Index(1),
+                sub_matches.get_one::<f32>("tone-freq-right"),
+            ),
+        ]
+    } else if let Some(range) = scan {
+        range
+            .freqs()
+            .into_iter()
+            .map(|tone_freq| detect::Station {
+                label: Some(scan::label(tone_freq)),
+                channel,
+                tone_freq,
+                tone_range: None,
+            })
+            .collect()
+    } else {
+        vec![station(None, channel, None)]
+    };
+    let list_targets = sub_matches
+        .get_one::<bool>("list-targets")
+        .expect("Missing --list-targets arg default");
+    let monitor = sub_matches
+        .get_flag("monitor")
+        .then(|| monitor::MonitorOptions {
+            bell: sub_matches.get_flag("monitor-bell"),
+            only: sub_matches.get_flag("monitor-only"),
+        });
+    let passthrough = sub_matches.get_one::<String>("monitor-audio").map(|sink| {
+        passthrough::PassthroughOptions {
+            target: (!sink.is_empty()).then(|| sink.clone()),
+            volume: *sub_matches
+                .get_one::<f32>("monitor-audio-volume")
+                .expect("Missing --monitor-audio-volume arg default"),
+            marker: sub_matches.get_flag("monitor-audio-marker"),
+            force: sub_matches.get_flag("force"),
+        }
+    });
+    let record = sub_matches
+        .get_one::<String>("record")
+        .map(|path| recording::RecordOptions {
+            path: path.into(),
+            filtered: sub_matches.get_flag("record-filtered"),
+            split: sub_matches
+                .get_one::<recording::Split>("record-split")
+                .copied(),
+        });
+    let alerts = sub_matches.get_many::<String>("alert").map(|patterns| {
+        let patterns: Vec<String> = patterns.cloned().collect();
+        let exec = sub_matches.get_one::<String>("alert-exec").cloned();
+        alert::Alerts::new(&patterns, exec).unwrap_or_else(|e| {
+            error!("Invalid --alert pattern: {e}");
+            std::process::exit(1);
+        })
+    });
+    let speaker = sub_matches
+        .get_one::<speech::SpeakMode>("speak")
+        .map(|&mode| {
+            let min_length = *sub_matches
+                .get_one::<usize>("speak-min-length")
+                .expect("Missing --speak-min-length arg default");
+            speech::Speaker::start(mode, min_length)
+        });
+    let output = *sub_matches
+        .get_one::<events::OutputFormat>("output")
+        .expect("Missing --output arg default");
+    if output == events::OutputFormat::Json
+        && sub_matches
+            .get_one::<String>("log-file")
+            .map(String::as_str)
+            == Some("-")
+    {
+        error!("Cannot write both --output json and --log-file - to stdout.");
+        std::process::exit(1);
+    }
+    let transcript = sub_matches.get_one::<String>("log-file").map(|path| {
+        let rotation = sub_matches
+            .get_one::<transcript::Rotation>("log-rotate")
+            .copied();
+        transcript::Transcript::open(path, rotation).unwrap_or_else(|e| {
+            error!("Could not open the log file {path}: {e}");
+            std::process::exit(1);
+        })
+    });
+    let server = sub_matches
+        .get_one::<server::ServeAddr>("serve")
+        .map(|addr| {
+            let format = *sub_matches
+                .get_one::<events::OutputFormat>("serve-format")
+                .expect("Missing --serve-format arg default");
+            let backlog = *sub_matches
+                .get_one::<usize>("serve-backlog")
+                .expect("Missing --serve-backlog arg default");
+            server::Server::start(addr, format, backlog).unwrap_or_else(|e| {
+                error!("Could not serve on {}: {e}", addr.addr);
+                std::process::exit(1);
+            })
+        });
+    let udp = sub_matches.get_one::<String>("udp-target").map(|target| {
+        let format = *sub_matches
+            .get_one::<udp::UdpFormat>("udp-format")
+            .expect("Missing --udp-format arg default");
+        let source_port = sub_matches.get_one::<u16>("udp-source-port").copied();
+        udp::UdpSender::open(target, source_port, format).unwrap_or_else(|e| {
+            error!("Could not send to {target}: {e}");
+            std::process::exit(1);
+        })
+    });
+    let mqtt = sub_matches.get_one::<mqtt::MqttUrl>("mqtt").map(|url| {
+        let topic = sub_matches
+            .get_one::<String>("mqtt-topic")
+            .expect("Missing --mqtt-topic arg default")
+            .clone();
+        let mut url = url.clone();
+        if let Some(username) = sub_matches.get_one::<String>("mqtt-username") {
+            url.username = Some(username.clone());
+        }
+        if let Some(password) = sub_matches.get_one::<String>("mqtt-password") {
+            url.password = Some(password.clone());
+        }
+        mqtt::Publisher::start(mqtt::MqttOptions {
+            url,
+            status_topic: sub_matches
+                .get_one::<String>("mqtt-status-topic")
+                .cloned()
+                .unwrap_or_else(|| format!("{topic}/status")),
+            topic,
+            retain: sub_matches.get_flag("mqtt-retain"),
+        })
+    });
+    let spotter = sub_matches
+        .get_one::<spot::SpotTarget>("spot")
+        .map(|target| {
+            let minutes = *sub_matches
+                .get_one::<u64>("spot-window")
+                .expect("Missing --spot-window arg default");
+            spot::Spotter::start(
+                target.clone(),
+                sub_matches
+                    .get_one::<String>("spotter")
+                    .expect("Missing --spotter arg default"),
+                std::time::Duration::from_secs(minutes * 60),
+            )
+        });
+    let fldigi = sub_matches
+        .get_one::<fldigi::FldigiUrl>("fldigi")
+        .map(|url| fldigi::Fldigi::start(url.clone(), sub_matches.get_flag("fldigi-poll")));
+    let logbook = sub_matches.get_one::<String>("log-qso").map(|path| {
+        let mycall = sub_matches
+            .get_one::<String>("mycall")
+            .expect("Missing --mycall arg");
+        logbook::Logbook::open(std::path::Path::new(path), mycall).unwrap_or_else(|e| {
+            error!("Could not open the ADIF file {path}: {e}");
+            std::process::exit(1);
+        })
+    });
+    let typist = sub_matches.get_flag("type-output").then(|| {
+        let layout = sub_matches
+            .get_one::<uinput::Layout>("type-layout")
+            .expect("Missing --type-layout arg default");
+        let rate = *sub_matches
+            .get_one::<u32>("type-rate")
+            .expect("Missing --type-rate arg default");
+        uinput::Typist::start(layout.clone(), rate).unwrap_or_else(|e| {
+            error!("Could not make the virtual keyboard with /dev/uinput: {e}");
+            std::process::exit(1);
+        })
+    });
+    let rig = sub_matches
+        .get_one::<String>("rigctl")
+        .map(|addr| rigctl::Rig::start(addr, sub_matches.get_flag("rigctl-follow")));
+    if let Some(addr) = sub_matches.get_one::<String>("mopp-listen") {
+        let station = &stations[0];
+        let pipeline = detect::SignalPipeline::new(detect::PipelineOptions {
+            tone_freq: station.tone_freq,
+            bandwidth,
+            threshold,
+            debounce_duration,
+            detector,
+            dot_duration,
+            timeout_duration,
+            adaptive_speed,
+            text_options: text_options.clone(),
+            ..Default::default()
+        });
+        let timeout = std::time::Duration::from_millis(timeout_duration as u64);
+        let result = mopp::MoppListener::bind(addr).and_then(|mut listener| {
+            info!("Listening for MOPP on {addr}");
+            mopp::listen(&mut listener, pipeline, timeout, *morse)
+        });
+        if let Err(e) = result {
+            error!("Could not listen for MOPP on {addr}: {e}");
+            std::process::exit(1);
+        }
+    }
+    if let Some(files) = sub_matches.get_many::<String>("replay") {
+        let files: Vec<String> = files.cloned().collect();
+        let expect: Vec<String> = sub_matches
+            .get_many::<String>("expect")
+            .map_or(Vec::new(), |texts| texts.cloned().collect());
+        if let Err(e) = replay::replay(
+            &files,
+            &expect,
+            &stations,
+            bandwidth,
+            threshold,
+            debounce_duration,
+            detector,
+            dot_duration,
+            timeout_duration,
+            adaptive_speed,
+            &text_options,
+            *morse,
+            *sub_matches
+                .get_one::<replay::ReplaySpeed>("speed")
+                .expect("Missing --speed arg default"),
+        ) {
+            error!("{e}");
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+    match (&target, &file) {
+        (None, Some(file)) => {
+            if let Err(e) = wav::decode_file(
+                file,
+                &stations,
+                scan,
+                bandwidth,
+                threshold,
+                debounce_duration,
+                detector,
+                dot_duration,
+                timeout_duration,
+                adaptive_speed,
+                &text_options,
+                *morse,
+            ) {
+                error!("Could not decode {file}: {e}");
+                std::process::exit(1);
+            }
+            std::process::exit(0);
+        }
+        (Some(_target), Some(_file)) => {
+            error!("Cannot specify --target and --file simultaneousy.");
+            std::process::exit(1);
+        }
+        _ => {}
+    }
+    if cfg!(target_os = "linux") {
+        ensure_pipewire();
+        if *list_targets {
+            pipewire::list_targets().expect("pipewire::list_targets() failed");
+        } else {
+            pipewire::listen(
+                target,
+                stations,
+                detect::PipelineOptions {
+                    bandwidth,
+                    threshold,
+                    debounce_duration,
+                    detector,
+                    dot_duration,
+                    timeout_duration,
+                    adaptive_speed,
+                    text_options,
+                    ..Default::default()
+                },
+                ring_capacity,
+                pipewire::DisplayOptions {
+                    output,
+                    verbose_events: sub_matches.get_flag("verbose-events"),
+                    plain: sub_matches.get_flag("plain"),
+                    waterfall: sub_matches.get_flag("waterfall"),
+                    meter: !sub_matches.get_flag("no-meter"),
+                    monitor,
+                    output_morse: *morse,
+                },
+                pipewire::ListenOutputs {
+                    scan,
+                    passthrough,
+                    record,
+                    transcript,
+                    alerts,
+                    notify: sub_matches.get_flag("notify"),
+                    speaker,
+                    server,
+                    udp,
+                    mqtt,
+                    rig,
+                    spotter,
+                    logbook,
+                    fldigi,
+                    typist,
+                },
+                message::SessionOptions {
+                    scrollback: *sub_matches
+                        .get_one::<usize>("scrollback")
+                        .expect("Missing --scrollback arg default"),
+                    copy_on_exit: sub_matches
+                        .get_one::<clipboard::CopyOnExit>("copy-on-exit")
+                        .copied(),
+                    dump: sub_matches
+                        .get_one::<String>("dump-session")
+                        .map(Into::into),
+                },
+            )
+            .expect("pipewire::listen() failed");
+        }
+    } else {
+        error!("Sorry, the listen feature is only supported on Linux right now.");
+        std::process::exit(1);
+    }
+    0
 }
 
 fn generate_completion_script(shell: clap_complete::shells::Shell) {
@@ -250,3 +2136,206 @@ fn generate_completion_script(shell: clap_complete::shells::Shell) {
         &mut io::stdout(),
     )
 }
+
+/// Reads the text to send, from the arguments, a file or stdin.
+fn read_input(sub_matches: &clap::ArgMatches) -> String {
+    match (
+        sub_matches.get_many::<String>("input"),
+        sub_matches.get_one::<String>("file"),
+    ) {
+        (Some(input), _) => input.map(|s| s.as_str()).collect::<Vec<_>>().join(" "),
+        (None, Some(path)) => match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                error!("Could not read {path}: {e}");
+                std::process::exit(1);
+            }
+        },
+        (None, None) => std::io::read_to_string(std::io::stdin()).unwrap_or_else(|e| {
+            error!("Error reading stdin: {e}");
+            std::process::exit(1);
+        }),
+    }
+}
+
+/// The end of every practice drill: copies the tables of its summary with
+/// --copy-on-exit, and records its session, if anything was practiced,
+/// and exports its trials with --export.
+fn finish_practice(
+    sub_matches: &clap::ArgMatches,
+    tables: &[String],
+    session: Option<&stats::Session>,
+) {
+    if sub_matches.get_flag("copy-on-exit") && !tables.is_empty() {
+        println!("\n{}", clipboard::copy_and_tell(&tables.join("\n\n")));
+    }
+    if let Some(session) = session {
+        stats::record(session);
+        export_trials(sub_matches, "export", std::slice::from_ref(session), None);
+    }
+}
+
+/// Exports the trials of the sessions to each of the exports given.
+///
+/// # Returns
+/// Whether all of them were written.
+fn export_trials(
+    sub_matches: &clap::ArgMatches,
+    id: &str,
+    sessions: &[stats::Session],
+    last: Option<usize>,
+) -> bool {
+    let mut exported = true;
+    for export in sub_matches
+        .get_many::<export::Export>(id)
+        .into_iter()
+        .flatten()
+    {
+        match stats::export_trials(sessions, last, export) {
+            Ok(count) => println!("Exported {count} trials to {}", export.path.display()),
+            Err(e) => {
+                error!("Could not export to {}: {e}", export.path.display());
+                exported = false;
+            }
+        }
+    }
+    exported
+}
+
+/// The serial port or GPIO pins to key a transmitter with, or the MOPP
+/// target to send to, if send was given any.
+fn open_keying(
+    sub_matches: &clap::ArgMatches,
+    dot_duration: u32,
+) -> Option<io::Result<Box<dyn keying::Output>>> {
+    if let Some(target) = sub_matches.get_one::<String>("mopp-send") {
+        let wpm = morse::dot_length_to_wpm(dot_duration);
+        return Some(mopp::MoppKey::open(target, wpm).map(|key| Box::new(key) as _));
+    }
+    if let Some(port) = sub_matches.get_one::<serial::KeySerial>("key-serial") {
+        let ptt = sub_matches.get_flag("ptt");
+        return Some(serial::SerialKey::open(port, ptt).map(|key| Box::new(key) as _));
+    }
+    let pin = sub_matches.get_one::<gpio::GpioPin>("key-gpio")?;
+    Some(gpio::open(
+        pin,
+        sub_matches.get_one::<gpio::GpioPin>("ptt-gpio"),
+        sub_matches.get_flag("gpio-active-low"),
+    ))
+}
+
+/// Opens the Winkeyer of send, at the speed of the sending args.
+fn open_winkeyer(
+    path: &str,
+    sub_matches: &clap::ArgMatches,
+    dot_duration: u32,
+) -> io::Result<winkeyer::Winkeyer<std::fs::File>> {
+    let mut winkeyer = winkeyer::open(path)?;
+    info!("Winkeyer version {} on {path}", winkeyer.version);
+    winkeyer.set_speed(morse::dot_length_to_wpm(dot_duration))?;
+    if let Some(farnsworth) = sub_matches.get_one::<u32>("farnsworth") {
+        winkeyer.set_farnsworth(*farnsworth)?;
+    }
+    let ms = |id: &str| {
+        std::time::Duration::from_millis(
+            *sub_matches
+                .get_one::<u64>(id)
+                .expect("Missing PTT timing arg default"),
+        )
+    };
+    winkeyer.set_ptt(ms("lead-in"), ms("tail"))?;
+    Ok(winkeyer)
+}
+
+/// The sidetone of the keying args, at the tone given unless they set
+/// their own.
+fn make_sidetone(sub_matches: &clap::ArgMatches, tone_freq: f32) -> sidetone::SidetoneOptions {
+    sidetone::SidetoneOptions {
+        freq: sub_matches
+            .get_one::<f32>("sidetone-freq")
+            .copied()
+            .unwrap_or(tone_freq),
+        volume: *sub_matches
+            .get_one::<f32>("sidetone-volume")
+            .expect("Missing --sidetone-volume arg default"),
+        rise_time: *sub_matches
+            .get_one::<f32>("sidetone-rise-time")
+            .expect("Missing --sidetone-rise-time arg default"),
+        target: sub_matches.get_one::<String>("sidetone-target").cloned(),
+    }
+}
+
+/// The keys of the keyboard to key with, of the keying args.
+fn make_keys(sub_matches: &clap::ArgMatches) -> send::Keys {
+    match sub_matches.get_one::<iambic::Mode>("iambic") {
+        Some(&mode) => {
+            let paddles = *sub_matches
+                .get_one::<(crossterm::event::KeyCode, crossterm::event::KeyCode)>("paddles")
+                .expect("Missing --paddles arg default");
+            let (dit, dah) = match *sub_matches
+                .get_one::<bool>("paddle-reverse")
+                .expect("Missing --paddle-reverse arg default")
+            {
+                true => (paddles.1, paddles.0),
+                false => paddles,
+            };
+            send::Keys::Paddles {
+                dit,
+                dah,
+                mode,
+                weight: *sub_matches
+                    .get_one::<u32>("weight")
+                    .expect("Missing --weight arg default"),
+            }
+        }
+        None => send::Keys::Straight(
+            *sub_matches
+                .get_one::<crossterm::event::KeyCode>("send-key")
+                .expect("Missing --send-key arg default"),
+        ),
+    }
+}
+
+/// The timing of the sending args, with their Farnsworth and word spacing.
+fn make_timing(sub_matches: &clap::ArgMatches, dot_duration: u32) -> keyer::Timing {
+    let farnsworth = sub_matches.get_one::<u32>("farnsworth").copied();
+    let word_space = *sub_matches
+        .get_one::<f32>("extra-word-space")
+        .expect("Missing --extra-word-space arg default");
+    keyer::Timing::new(dot_duration, farnsworth).with_word_space(word_space)
+}
+
+/// The conditions of the band args, with the seed given or one picked
+/// at random, or None for a clean band.
+fn make_conditions(sub_matches: &clap::ArgMatches) -> Option<band::Conditions> {
+    band::Conditions::new(
+        sub_matches.get_one::<f32>("noise-snr").copied(),
+        sub_matches.get_one::<f32>("qrn").copied(),
+        sub_matches.get_one::<band::Qsb>("qsb").copied(),
+        sub_matches.get_one::<u64>("seed").copied(),
+    )
+}
+
+/// Creates the keyer that sends morse code text, with the timing, sound
+/// and band conditions given to send or render.
+fn make_keyer(
+    sub_matches: &clap::ArgMatches,
+    code: &str,
+    dot_duration: u32,
+    tone_freq: f32,
+) -> keyer::Keyer {
+    let volume = *sub_matches
+        .get_one::<f32>("volume")
+        .expect("Missing --volume arg default");
+    let rise_time = *sub_matches
+        .get_one::<f32>("rise-time")
+        .expect("Missing --rise-time arg default");
+    keyer::Keyer::new(
+        &make_timing(sub_matches, dot_duration).key_events(code),
+        keyer::SAMPLE_RATE,
+        tone_freq,
+        volume,
+        rise_time,
+    )
+    .with_band(make_conditions(sub_matches).map(band::Band::new))
+}
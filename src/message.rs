@@ -1,5 +1,260 @@
+use crate::clipboard::CopyOnExit;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
 #[allow(dead_code)]
+#[derive(Clone, Debug)]
 pub struct Message {
     pub timestamp: String, // Timestamp in the format `YY-MM-DD HH:MM:SS`
     pub content: String,   // The actual message content
+    pub wpm: Option<u32>,  // The measured sending speed, if known
+}
+
+/// The last items pushed, at most some number of them, the oldest
+/// being dropped for the memory to stay bounded, however long a session
+/// lasts.
+#[allow(dead_code)]
+#[derive(Clone, Debug, Default)]
+pub struct Backlog<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+    /// How many were dropped.
+    dropped: usize,
+}
+
+#[allow(dead_code)]
+impl<T> Backlog<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::new(),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if self.items.len() >= self.capacity {
+            self.items.pop_front();
+            self.dropped += 1;
+        }
+        self.items.push_back(item);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.items.back()
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+/// The messages of a listening session, of every station, kept to be
+/// browsed, copied and dumped: the last `--scrollback` of them, even
+/// those cleared from the screen.
+#[allow(dead_code)]
+pub struct SessionLog {
+    // With the label of their station:
+    messages: Backlog<(Option<String>, Message)>,
+}
+
+#[allow(dead_code)]
+impl SessionLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            messages: Backlog::new(capacity),
+        }
+    }
+
+    pub fn push(&mut self, label: Option<&str>, message: Message) {
+        self.messages.push((label.map(str::to_string), message));
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// How many of the messages were dropped, being over the capacity.
+    pub fn dropped(&self) -> usize {
+        self.messages.dropped()
+    }
+
+    /// The messages, a line each, e.g. `25-02-14 18:02:31 PM L: CQ DE K1ABC`.
+    pub fn lines(&self) -> Vec<String> {
+        self.messages
+            .iter()
+            .map(|(label, message)| {
+                let label = label
+                    .as_deref()
+                    .map(|l| format!("{l} "))
+                    .unwrap_or_default();
+                format!("{} {label}{}", message.timestamp, message.content)
+            })
+            .collect()
+    }
+
+    /// The lines of the messages, each ending with a line break.
+    pub fn transcript(&self) -> String {
+        self.lines().into_iter().map(|line| line + "\n").collect()
+    }
+
+    /// The last message completed, if any.
+    pub fn last(&self) -> Option<&str> {
+        self.messages
+            .last()
+            .map(|(_, message)| message.content.as_str())
+    }
+}
+
+/// What is done with the messages of a listening session, besides
+/// showing them.
+#[allow(dead_code)]
+#[derive(Clone, Debug, Default)]
+pub struct SessionOptions {
+    /// How many are kept, with `--scrollback`.
+    pub scrollback: usize,
+    /// What is copied on quitting, with `--copy-on-exit`.
+    pub copy_on_exit: Option<CopyOnExit>,
+    /// Where they are written on quitting, with `--dump-session`.
+    pub dump: Option<PathBuf>,
+}
+
+/// What was decoded while listening, for the summary shown at the end.
+#[allow(dead_code)]
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub messages: usize,
+    /// The characters decoded, without the spaces between words.
+    pub characters: usize,
+    // The measured speeds, weighted by the characters sent at them:
+    wpm_total: u64,
+    wpm_characters: usize,
+}
+
+#[allow(dead_code)]
+impl Summary {
+    pub fn add(&mut self, message: &Message) {
+        let characters = message
+            .content
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .count();
+        self.messages += 1;
+        self.characters += characters;
+        if let Some(wpm) = message.wpm {
+            self.wpm_total += wpm as u64 * characters as u64;
+            self.wpm_characters += characters;
+        }
+    }
+
+    /// The average measured speed of the characters, if any was measured.
+    pub fn wpm(&self) -> Option<u32> {
+        (self.wpm_characters > 0)
+            .then(|| (self.wpm_total as f64 / self.wpm_characters as f64).round() as u32)
+    }
+
+    /// The summary of a session that lasted some time, e.g.
+    /// `listened for 12.5 minutes: 7 messages, 312 characters at 18 WPM`.
+    pub fn report(&self, duration: std::time::Duration) -> String {
+        let minutes = format!("listened for {:.1} minutes", duration.as_secs_f64() / 60.0);
+        format!("{minutes}: {}", self.counts())
+    }
+
+    /// What was decoded so far, e.g. `7 messages, 312 characters at 18 WPM`.
+    pub fn counts(&self) -> String {
+        if self.messages == 0 {
+            return "no messages".to_string();
+        }
+        let messages = match self.messages {
+            1 => "1 message".to_string(),
+            n => format!("{n} messages"),
+        };
+        let speed = match self.wpm() {
+            Some(wpm) => format!(" at {wpm} WPM"),
+            None => String::new(),
+        };
+        format!("{messages}, {} characters{speed}", self.characters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn message(content: &str, wpm: Option<u32>) -> Message {
+        Message {
+            timestamp: String::new(),
+            content: content.to_string(),
+            wpm,
+        }
+    }
+
+    #[test]
+    fn test_session_log() {
+        let mut log = SessionLog::new(2);
+        assert!(log.is_empty());
+        assert_eq!(log.last(), None);
+        let at = |timestamp: &str, content: &str| Message {
+            timestamp: timestamp.to_string(),
+            ..message(content, None)
+        };
+        log.push(None, at("25-02-14 18:02:31 PM", "CQ DE K1ABC"));
+        log.push(Some("R:"), at("25-02-14 18:03:05 PM", "K1ABC DE W2XYZ"));
+        assert_eq!(
+            log.transcript(),
+            "25-02-14 18:02:31 PM CQ DE K1ABC\n25-02-14 18:03:05 PM R: K1ABC DE W2XYZ\n"
+        );
+        assert_eq!(log.last(), Some("K1ABC DE W2XYZ"));
+        // Bounded:
+        log.push(None, at("25-02-14 18:04:12 PM", "TNX"));
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.dropped(), 1);
+        assert_eq!(log.lines()[0], "25-02-14 18:03:05 PM R: K1ABC DE W2XYZ");
+        let mut none = Backlog::new(0);
+        none.push(1);
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut summary = Summary::default();
+        assert_eq!(
+            summary.report(Duration::from_secs(30)),
+            "listened for 0.5 minutes: no messages"
+        );
+        summary.add(&message("CQ CQ", Some(20)));
+        assert_eq!(
+            summary.report(Duration::from_secs(90)),
+            "listened for 1.5 minutes: 1 message, 4 characters at 20 WPM"
+        );
+        // The speed is averaged over the characters:
+        summary.add(&message("DE K1ABC", Some(14)));
+        summary.add(&message("TU", None));
+        assert_eq!(summary.wpm(), Some(16));
+        assert_eq!(
+            summary.report(Duration::from_secs(750)),
+            "listened for 12.5 minutes: 3 messages, 13 characters at 16 WPM"
+        );
+    }
 }
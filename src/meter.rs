@@ -0,0 +1,108 @@
+use crate::detect::ToneLevel;
+
+/// How many cells wide the level bar is.
+const WIDTH: usize = 20;
+
+/// Draws the level of the tone as a bar, scaled to twice the threshold
+/// so that the threshold is always in the middle, with the noise floor
+/// marked on it, e.g. `▕██████░┆░│░░░░░░░░░▏`.
+#[allow(dead_code)]
+pub struct LevelMeter {
+    unicode: bool,
+}
+
+#[allow(dead_code)]
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self {
+            unicode: crate::sparkline::unicode_supported(),
+        }
+    }
+
+    pub fn bar(&self, level: &ToneLevel) -> String {
+        let (filled, empty, threshold, noise, left, right) = match self.unicode {
+            true => ('█', '░', '│', '┆', '▕', '▏'),
+            false => ('#', '.', '|', ':', '|', '|'),
+        };
+        // Until the threshold is known, the peak (or the level) fills it:
+        let full = match level.threshold {
+            Some(threshold) => threshold * 2.0,
+            None => level.peak.unwrap_or(0.0).max(level.level),
+        }
+        .max(f32::EPSILON);
+        let cell = |value: f32| ((value / full * WIDTH as f32).round() as usize).min(WIDTH);
+        let mut cells: Vec<char> = (0..WIDTH)
+            .map(|i| if i < cell(level.level) { filled } else { empty })
+            .collect();
+        if let Some(value) = level.noise {
+            cells[cell(value).min(WIDTH - 1)] = noise;
+        }
+        if level.threshold.is_some() {
+            cells[WIDTH / 2] = threshold;
+        }
+        format!("{left}{}{right}", cells.into_iter().collect::<String>())
+    }
+}
+
+/// The numbers behind the meter, e.g.
+/// `level 0.412 threshold 0.300 noise 0.010 peak 0.520 offset -35 Hz`.
+#[allow(dead_code)]
+pub fn readout(level: &ToneLevel) -> String {
+    let mut text = format!("level {:.3} threshold ", level.level);
+    match level.threshold {
+        Some(threshold) => text += &format!("{threshold:.3}"),
+        None => text += "--",
+    }
+    if let Some(noise) = level.noise {
+        text += &format!(" noise {noise:.3}");
+    }
+    if let Some(peak) = level.peak {
+        text += &format!(" peak {peak:.3}");
+    }
+    match level.offset {
+        Some(offset) => text += &format!(" offset {:+} Hz", offset.round() as i32),
+        None => text += " offset --",
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_meter() {
+        let meter = LevelMeter { unicode: false };
+        let mut level = ToneLevel {
+            level: 0.3,
+            threshold: Some(0.2),
+            peak: None,
+            noise: None,
+            tone: true,
+            offset: Some(-35.4),
+        };
+        assert_eq!(meter.bar(&level), "|##########|####.....|");
+        assert_eq!(readout(&level), "level 0.300 threshold 0.200 offset -35 Hz");
+        // Levels past the end fill the bar, the noise floor is marked:
+        level.level = 1.0;
+        level.noise = Some(0.04);
+        assert_eq!(meter.bar(&level), "|##:#######|#########|");
+        level.level = 0.0;
+        assert_eq!(meter.bar(&level), "|..:.......|.........|");
+
+        // Before the adaptive threshold is known:
+        level = ToneLevel {
+            level: 0.1,
+            threshold: None,
+            peak: Some(0.4),
+            noise: Some(0.1),
+            tone: false,
+            offset: None,
+        };
+        assert_eq!(meter.bar(&level), "|#####:..............|");
+        assert_eq!(
+            readout(&level),
+            "level 0.100 threshold -- noise 0.100 peak 0.400 offset --"
+        );
+    }
+}
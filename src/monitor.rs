@@ -0,0 +1,104 @@
+use crate::detect::ToneLevel;
+use std::collections::VecDeque;
+
+/// How many windows of samples the monitor shows, 10ms each.
+const HISTORY: usize = 40;
+
+/// How `listen --monitor` shows the tone detector.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MonitorOptions {
+    /// Ring the terminal bell whenever a tone starts.
+    pub bell: bool,
+    /// Show the monitor instead of the decoded text.
+    pub only: bool,
+}
+
+/// Follows the tone detector window by window, to show exactly when it
+/// hears a tone: the recent windows scroll by as a strip of blocks.
+#[allow(dead_code)]
+pub struct KeyingMonitor {
+    pub options: MonitorOptions,
+    history: VecDeque<bool>,
+    unicode: bool,
+}
+
+#[allow(dead_code)]
+impl KeyingMonitor {
+    pub fn new(options: MonitorOptions) -> Self {
+        Self {
+            options,
+            history: VecDeque::with_capacity(HISTORY),
+            unicode: crate::sparkline::unicode_supported(),
+        }
+    }
+
+    /// Records the level of the next window.
+    ///
+    /// # Returns
+    /// Whether to ring the bell, as a tone just started.
+    pub fn push(&mut self, level: &ToneLevel) -> bool {
+        let started = level.tone && !self.history.back().copied().unwrap_or(false);
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(level.tone);
+        started && self.options.bell
+    }
+
+    /// The strip of the recent windows, and whether the latest one was
+    /// a tone, e.g. `▕▁▁▁████▁▁▏ TONE`.
+    pub fn line(&self) -> String {
+        let (on, off, left, right) = match self.unicode {
+            true => ('█', '▁', '▕', '▏'),
+            false => ('#', '_', '|', '|'),
+        };
+        let strip: String = std::iter::repeat_n(off, HISTORY - self.history.len())
+            .chain(self.history.iter().map(|&tone| if tone { on } else { off }))
+            .collect();
+        let tone = match self.history.back() {
+            Some(true) => "TONE",
+            _ => "    ",
+        };
+        format!("{left}{strip}{right} {tone}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(tone: bool) -> ToneLevel {
+        ToneLevel {
+            level: if tone { 0.5 } else { 0.1 },
+            threshold: Some(0.3),
+            peak: None,
+            noise: None,
+            tone,
+            offset: None,
+        }
+    }
+
+    #[test]
+    fn test_keying_monitor() {
+        let mut monitor = KeyingMonitor::new(MonitorOptions {
+            bell: true,
+            only: false,
+        });
+        monitor.unicode = false;
+        assert_eq!(monitor.line(), format!("|{}|     ", "_".repeat(HISTORY)));
+        // The bell only rings as each tone starts:
+        let rings: Vec<bool> = [false, true, true, false, true]
+            .iter()
+            .map(|&tone| monitor.push(&level(tone)))
+            .collect();
+        assert_eq!(rings, vec![false, true, false, false, true]);
+        assert_eq!(monitor.line(), format!("|{}_##_#| TONE", "_".repeat(35)));
+        // The strip scrolls, keeping the latest windows:
+        for _ in 0..HISTORY {
+            monitor.push(&level(false));
+        }
+        assert_eq!(monitor.line(), format!("|{}|     ", "_".repeat(HISTORY)));
+        monitor.options.bell = false;
+        assert!(!monitor.push(&level(true)));
+    }
+}
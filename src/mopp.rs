@@ -0,0 +1,524 @@
+use crate::detect::{DecodeEvent, SignalPipeline};
+use crate::keying::{Output, Signal};
+use crate::morse::{text_to_morse, wpm_to_dot_length};
+use crate::prelude::*;
+use crate::term::{MessagePrinter, PrintUpdate};
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// The port of the Morserino-32 for MOPP, on the local network and on
+/// the servers of the practice nets.
+pub const DEFAULT_PORT: u16 = 7373;
+/// The version of MOPP, in the first two bits of a packet.
+const VERSION: u8 = 1;
+
+/// An element of a packet of MOPP, in two bits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Element {
+    Dit,
+    Dah,
+    EndOfCharacter,
+    EndOfWord,
+}
+
+impl Element {
+    fn bits(self) -> u8 {
+        match self {
+            Element::EndOfCharacter => 0b00,
+            Element::Dit => 0b01,
+            Element::Dah => 0b10,
+            Element::EndOfWord => 0b11,
+        }
+    }
+
+    fn from_bits(bits: u8) -> Element {
+        match bits & 0b11 {
+            0b00 => Element::EndOfCharacter,
+            0b01 => Element::Dit,
+            0b10 => Element::Dah,
+            _ => Element::EndOfWord,
+        }
+    }
+}
+
+/// A packet of MOPP (the Morserino-32 protocol), a word sent by a
+/// Morserino: the version, a serial number and the speed, in 14 bits,
+/// then its elements, in two bits each, padded with zeros to a byte.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Packet {
+    /// Counting the packets of a sender, from 0 to 63 and again.
+    pub serial: u8,
+    pub wpm: u8,
+    pub elements: Vec<Element>,
+}
+
+impl Packet {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bits: Vec<u8> = vec![VERSION >> 1, VERSION & 1];
+        for (value, width) in [(self.serial, 6), (self.wpm, 6)] {
+            bits.extend((0..width).rev().map(|i| (value >> i) & 1));
+        }
+        for element in &self.elements {
+            let element = element.bits();
+            bits.extend([element >> 1, element & 1]);
+        }
+        bits.chunks(8)
+            .map(|byte| {
+                byte.iter()
+                    .chain(std::iter::repeat(&0))
+                    .take(8)
+                    .fold(0, |acc, bit| (acc << 1) | bit)
+            })
+            .collect()
+    }
+
+    /// Reads a packet, the zeros of the padding after the last end of
+    /// word being left out.
+    pub fn parse(bytes: &[u8]) -> Result<Packet, String> {
+        let bit = |i: usize| (bytes[i / 8] >> (7 - i % 8)) & 1;
+        let bits = |start: usize, width: usize| {
+            (start..start + width).fold(0, |acc, i| (acc << 1) | bit(i))
+        };
+        if bytes.len() < 2 {
+            return Err(format!("A MOPP packet is at least 2 bytes: {bytes:02x?}"));
+        }
+        if bits(0, 2) != VERSION {
+            return Err(format!(
+                "Not a MOPP packet of version {VERSION}: {bytes:02x?}"
+            ));
+        }
+        let mut elements: Vec<Element> = (14..bytes.len() * 8 - 1)
+            .step_by(2)
+            .map(|i| Element::from_bits(bits(i, 2)))
+            .collect();
+        while elements.last() == Some(&Element::EndOfCharacter)
+            && elements.contains(&Element::EndOfWord)
+        {
+            elements.pop();
+        }
+        Ok(Packet {
+            serial: bits(2, 6),
+            wpm: bits(8, 6),
+            elements,
+        })
+    }
+
+    /// The tones and gaps of the packet, at its speed, the packet
+    /// ending a word whether it ends with one or not.
+    pub fn key_events(&self) -> Vec<(bool, u32)> {
+        let dot = wpm_to_dot_length(self.wpm.max(1) as u32);
+        let mut events: Vec<(bool, u32)> = Vec::new();
+        for element in &self.elements {
+            let (down, dots) = match element {
+                Element::Dit => (true, 1),
+                Element::Dah => (true, 3),
+                Element::EndOfCharacter => (false, 3),
+                Element::EndOfWord => (false, 7),
+            };
+            match (events.last_mut(), down) {
+                (Some((false, gap)), true) => *gap = (*gap).max(dot),
+                (Some((false, gap)), false) => *gap = (*gap).max(dots * dot),
+                (Some((true, _)), true) => events.push((false, dot)),
+                (_, false) => events.push((false, dots * dot)),
+                (None, true) => {}
+            }
+            if down {
+                events.push((true, dots * dot));
+                // The gap after it, until the next element says more:
+                events.push((false, dot));
+            }
+        }
+        match events.last_mut() {
+            Some((false, gap)) => *gap = (*gap).max(7 * dot),
+            _ => events.push((false, 7 * dot)),
+        }
+        // Nothing but gaps, before the first tone:
+        let first = events
+            .iter()
+            .position(|&(down, _)| down)
+            .unwrap_or(events.len());
+        events.drain(..first);
+        events
+    }
+}
+
+/// A `host:port` to send to or to listen on, the port of MOPP if it is
+/// left out.
+fn socket_addr(addr: &str) -> io::Result<SocketAddr> {
+    let with_port = match addr.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => addr.to_string(),
+        _ => format!("{addr}:{DEFAULT_PORT}"),
+    };
+    with_port
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No address for {addr}")))
+}
+
+/// Sends what is keyed as MOPP, with `send --mopp-send`, a packet for
+/// each word, as a Morserino does: the marks and gaps are told apart by
+/// their lengths at the speed of the sending, as they come.
+pub struct MoppKey {
+    socket: UdpSocket,
+    target: SocketAddr,
+    wpm: u8,
+    dot: Duration,
+    serial: u8,
+    elements: Vec<Element>,
+    // When the key last went down or up:
+    last: Option<Instant>,
+}
+
+impl MoppKey {
+    /// Sends to a `host:port`, e.g. a server, or the broadcast address of
+    /// the local network to the Morserinos on it.
+    pub fn open(target: &str, wpm: u32) -> io::Result<MoppKey> {
+        let target = socket_addr(target)?;
+        let socket = match target {
+            SocketAddr::V4(_) => UdpSocket::bind(("0.0.0.0", 0))?,
+            SocketAddr::V6(_) => UdpSocket::bind(("::", 0))?,
+        };
+        socket.set_broadcast(true)?;
+        // The speed of MOPP is in 6 bits:
+        let wpm = wpm.clamp(1, 63);
+        Ok(MoppKey {
+            socket,
+            target,
+            wpm: wpm as u8,
+            dot: Duration::from_millis(wpm_to_dot_length(wpm) as u64),
+            serial: 0,
+            elements: Vec::new(),
+            last: None,
+        })
+    }
+
+    /// Takes in the key going down or up at a time.
+    ///
+    /// # Returns
+    /// The packet of a word that ended, to send.
+    fn key(&mut self, down: bool, at: Instant) -> Option<Packet> {
+        let since = self.last.map(|then| at - then);
+        self.last = Some(at);
+        let dots = since?.as_secs_f64() / self.dot.as_secs_f64();
+        match down {
+            // The gap before the mark:
+            true if dots >= 5.0 => return self.end_word(),
+            true if dots >= 2.0 => self.elements.push(Element::EndOfCharacter),
+            true => {}
+            false if dots >= 2.0 => self.elements.push(Element::Dah),
+            false => self.elements.push(Element::Dit),
+        }
+        None
+    }
+
+    /// The packet of the word keyed so far, if there is one.
+    fn end_word(&mut self) -> Option<Packet> {
+        if self.elements.is_empty() {
+            return None;
+        }
+        let mut elements = std::mem::take(&mut self.elements);
+        elements.push(Element::EndOfWord);
+        let packet = Packet {
+            serial: self.serial,
+            wpm: self.wpm,
+            elements,
+        };
+        self.serial = (self.serial + 1) % 64;
+        Some(packet)
+    }
+
+    fn send(&self, packet: &Packet) -> io::Result<()> {
+        self.socket.send_to(&packet.to_bytes(), self.target)?;
+        Ok(())
+    }
+}
+
+impl Output for MoppKey {
+    fn set(&mut self, signal: Signal, raised: bool) -> io::Result<()> {
+        match signal {
+            Signal::Key => match self.key(raised, Instant::now()) {
+                Some(packet) => self.send(&packet),
+                None => Ok(()),
+            },
+            Signal::Ptt => Ok(()),
+        }
+    }
+}
+
+impl Drop for MoppKey {
+    fn drop(&mut self) {
+        if let Some(packet) = self.end_word() {
+            if let Err(e) = self.send(&packet) {
+                warn!("Could not send the last word over MOPP: {e}");
+            }
+        }
+    }
+}
+
+/// Receives the packets of MOPP, on a UDP port, or of a multicast
+/// group joined on it.
+pub struct MoppListener {
+    socket: UdpSocket,
+    // The serial number last received from each sender:
+    serials: HashMap<SocketAddr, u8>,
+}
+
+impl MoppListener {
+    pub fn bind(addr: &str) -> io::Result<MoppListener> {
+        let addr = socket_addr(addr)?;
+        let socket = match addr {
+            SocketAddr::V4(v4) if v4.ip().is_multicast() => {
+                let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, v4.port()))?;
+                socket.join_multicast_v4(v4.ip(), &Ipv4Addr::UNSPECIFIED)?;
+                socket
+            }
+            addr => UdpSocket::bind(addr)?,
+        };
+        Ok(MoppListener {
+            socket,
+            serials: HashMap::new(),
+        })
+    }
+
+    /// Waits for the next packet, for at most some time. The packets
+    /// received again (with the serial number of the last one of their
+    /// sender) are left out, and the ones that aren't MOPP are told at the
+    /// debug level.
+    ///
+    /// # Returns
+    /// None when nothing came in time.
+    pub fn recv(&mut self, timeout: Duration) -> io::Result<Option<Packet>> {
+        let until = Instant::now() + timeout;
+        let mut datagram = [0; 512];
+        loop {
+            let left = until.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(None);
+            }
+            self.socket.set_read_timeout(Some(left))?;
+            let (len, from) = match self.socket.recv_from(&mut datagram) {
+                Ok(received) => received,
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e),
+            };
+            let packet = match Packet::parse(&datagram[..len]) {
+                Ok(packet) => packet,
+                Err(e) => {
+                    debug!("{from}: {e}");
+                    continue;
+                }
+            };
+            match self.serials.insert(from, packet.serial) {
+                Some(last) if last == packet.serial => continue,
+                Some(last) if (last + 1) % 64 != packet.serial => {
+                    debug!(
+                        "{from}: packets lost from {} to {}",
+                        last + 1,
+                        packet.serial
+                    )
+                }
+                _ => {}
+            }
+            return Ok(Some(packet));
+        }
+    }
+}
+
+/// Decodes the words of MOPP as they come, with `listen --mopp-listen`,
+/// their elements given to the decoder without any sound, at the speed
+/// of each packet. The message ends after the timeout of the pipeline
+/// without a packet.
+pub fn listen(
+    listener: &mut MoppListener,
+    mut pipeline: SignalPipeline,
+    timeout: Duration,
+    output_morse: bool,
+) -> io::Result<()> {
+    let live = io::stdout().is_terminal();
+    let mut printer = MessagePrinter::default();
+    let mut stdout = io::stdout();
+    // Whether a packet came since the last message ended:
+    let mut pending = false;
+    loop {
+        let events = match listener.recv(timeout)? {
+            Some(packet) => {
+                pending = true;
+                let dot = wpm_to_dot_length(packet.wpm.max(1) as u32);
+                if dot != pipeline.dot_duration() {
+                    pipeline.set_dot_duration(dot);
+                }
+                packet
+                    .key_events()
+                    .into_iter()
+                    .flat_map(|(down, ms)| pipeline.push_signal(down, ms))
+                    .collect()
+            }
+            None if pending => {
+                pending = false;
+                pipeline.finish()
+            }
+            None => continue,
+        };
+        for event in events {
+            match event {
+                DecodeEvent::Partial(msg) if live => match printer.update(&msg) {
+                    PrintUpdate::Append(text) => write!(stdout, "{text}")?,
+                    PrintUpdate::Redraw(text) => write!(stdout, "\r\x1b[K{text}")?,
+                    PrintUpdate::Unchanged => {}
+                },
+                DecodeEvent::Message(msg) => {
+                    let msg = match output_morse {
+                        true => text_to_morse(&msg),
+                        false => msg,
+                    };
+                    match live {
+                        true => writeln!(stdout, "\r\x1b[K{msg}")?,
+                        false => writeln!(stdout, "{msg}")?,
+                    }
+                    printer.reset();
+                }
+                _ => {}
+            }
+        }
+        stdout.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::PipelineOptions;
+    use crate::filter::Detector;
+    use Element::*;
+
+    /// The word `CQ` at 20 WPM, the 64th packet of its sender: `01`,
+    /// the serial `111111`, the speed `010100`, `10 01 10 01 00` then
+    /// `10 10 01 10 11`, and two bits of padding.
+    const CQ: [u8; 5] = [0x7f, 0x52, 0x64, 0xa6, 0xc0];
+
+    fn cq(serial: u8) -> Packet {
+        Packet {
+            serial,
+            wpm: 20,
+            elements: vec![
+                Dah,
+                Dit,
+                Dah,
+                Dit,
+                EndOfCharacter,
+                Dah,
+                Dah,
+                Dit,
+                Dah,
+                EndOfWord,
+            ],
+        }
+    }
+
+    #[test]
+    fn test_packet() {
+        assert_eq!(cq(63).to_bytes(), CQ);
+        assert_eq!(Packet::parse(&CQ), Ok(cq(63)));
+        // As a Morserino ends a word, the end of a character before it:
+        let mut ended = cq(1);
+        ended.elements.insert(9, EndOfCharacter);
+        assert_eq!(Packet::parse(&ended.to_bytes()), Ok(ended));
+        assert!(Packet::parse(&[0x7f]).is_err());
+        assert!(Packet::parse(&[0xbf, 0xd2]).is_err());
+        // E, and a word space: `01 000000 010100 01 11`, padded:
+        let e = Packet::parse(&[0x40, 0x51, 0xc0]).unwrap();
+        assert_eq!((e.serial, e.wpm, e.elements), (0, 20, vec![Dit, EndOfWord]));
+        assert_eq!(
+            Packet::parse(&[0x40, 0x51, 0xc0]).unwrap().key_events(),
+            [(true, 60), (false, 420)]
+        );
+        assert_eq!(
+            cq(0).key_events()[..6],
+            [
+                (true, 180),
+                (false, 60),
+                (true, 60),
+                (false, 60),
+                (true, 180),
+                (false, 60)
+            ]
+        );
+        assert_eq!(cq(0).key_events()[7], (false, 180));
+        assert_eq!(cq(0).key_events().last(), Some(&(false, 420)));
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut pipeline = SignalPipeline::new(PipelineOptions {
+            tone_freq: 600.0,
+            debounce_duration: 0,
+            detector: Detector::Goertzel,
+            ..Default::default()
+        });
+        let mut q = cq(1);
+        q.wpm = 15;
+        let mut events: Vec<DecodeEvent> = [cq(0), q]
+            .iter()
+            .flat_map(|packet| {
+                let dot = wpm_to_dot_length(packet.wpm as u32);
+                pipeline.set_dot_duration(dot);
+                packet
+                    .key_events()
+                    .into_iter()
+                    .flat_map(|(down, ms)| pipeline.push_signal(down, ms))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        events.extend(pipeline.finish());
+        let messages: Vec<&DecodeEvent> = events
+            .iter()
+            .filter(|event| matches!(event, DecodeEvent::Message(_)))
+            .collect();
+        assert_eq!(messages, [&DecodeEvent::Message("CQ CQ".to_string())]);
+    }
+
+    #[test]
+    fn test_mopp_key() {
+        let mut key = MoppKey::open("127.0.0.1", 20).unwrap();
+        assert_eq!(key.target.port(), DEFAULT_PORT);
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut packets = Vec::new();
+        // The events of the keyer, C Q and E, a little late:
+        let mut time = 0;
+        for (down, length) in crate::keyer::Timing::new(60, None).key_events("-.-. --.- / .") {
+            if down {
+                packets.extend(key.key(true, ms(time + 3)));
+                packets.extend(key.key(false, ms(time + length as u64)));
+            }
+            time += length as u64;
+        }
+        packets.extend(key.end_word());
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0], cq(0));
+        assert_eq!(packets[1].serial, 1);
+        assert_eq!(packets[1].elements, [Dit, EndOfWord]);
+    }
+
+    #[test]
+    fn test_listener() {
+        let mut listener = MoppListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.socket.local_addr().unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for datagram in [&CQ[..], &CQ, b"\xff", &cq(0).to_bytes()] {
+            sender.send_to(datagram, addr).unwrap();
+        }
+        let timeout = Duration::from_secs(5);
+        assert_eq!(listener.recv(timeout).unwrap(), Some(cq(63)));
+        // Received again, then not MOPP:
+        assert_eq!(listener.recv(timeout).unwrap(), Some(cq(0)));
+        assert_eq!(listener.recv(Duration::from_millis(10)).unwrap(), None);
+    }
+}
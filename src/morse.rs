@@ -1,5 +1,7 @@
+use crate::keyer::{Keyer, Mixer};
 use rodio::{OutputStream, Sink, Source};
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
@@ -60,12 +62,201 @@ impl Source for Tone {
     }
 }
 
+/// Prosigns, each sent as one character without the gaps between its letters.
+pub const PROSIGNS: [(&str, &str); 8] = [
+    ("AR", ".-.-."),
+    ("AS", ".-..."),
+    ("BK", "-...-.-"),
+    ("BT", "-...-"),
+    ("CT", "-.-.-"),
+    ("KN", "-.--."),
+    ("SK", "...-.-"),
+    ("SN", "...-."),
+];
+
+/// Finds the prosign sent as the given dots and dashes, e.g. "AR" for ".-.-.".
+pub fn prosign(code: &str) -> Option<&'static str> {
+    PROSIGNS
+        .iter()
+        .find_map(|&(prosign, c)| (c == code).then_some(prosign))
+}
+
+/// The Russian letters, as sent in Cyrillic morse code.
+const CYRILLIC: [(char, &str); 32] = [
+    ('А', ".-"),
+    ('Б', "-..."),
+    ('В', ".--"),
+    ('Г', "--."),
+    ('Д', "-.."),
+    ('Е', "."),
+    ('Ж', "...-"),
+    ('З', "--.."),
+    ('И', ".."),
+    ('Й', ".---"),
+    ('К', "-.-"),
+    ('Л', ".-.."),
+    ('М', "--"),
+    ('Н', "-."),
+    ('О', "---"),
+    ('П', ".--."),
+    ('Р', ".-."),
+    ('С', "..."),
+    ('Т', "-"),
+    ('У', "..-"),
+    ('Ф', "..-."),
+    ('Х', "...."),
+    ('Ц', "-.-."),
+    ('Ч', "---."),
+    ('Ш', "----"),
+    ('Щ', "--.-"),
+    ('Ъ', "--.--"),
+    ('Ы', "-.--"),
+    ('Ь', "-..-"),
+    ('Э', "..-.."),
+    ('Ю', "..--"),
+    ('Я', ".-.-"),
+];
+
+/// The Japanese kana, as sent in Wabun code.
+const WABUN: [(char, &str); 55] = [
+    ('イ', ".-"),
+    ('ロ', ".-.-"),
+    ('ハ', "-..."),
+    ('ニ', "-.-."),
+    ('ホ', "-.."),
+    ('ヘ', "."),
+    ('ト', "..-.."),
+    ('チ', "..-."),
+    ('リ', "--."),
+    ('ヌ', "...."),
+    ('ル', "-.--."),
+    ('ヲ', ".---"),
+    ('ワ', "-.-"),
+    ('カ', ".-.."),
+    ('ヨ', "--"),
+    ('タ', "-."),
+    ('レ', "---"),
+    ('ソ', "---."),
+    ('ツ', ".--."),
+    ('ネ', "--.-"),
+    ('ナ', ".-."),
+    ('ラ', "..."),
+    ('ム', "-"),
+    ('ウ', "..-"),
+    ('ヰ', ".-..-"),
+    ('ノ', "..--"),
+    ('オ', ".-..."),
+    ('ク', "...-"),
+    ('ヤ', ".--"),
+    ('マ', "-..-"),
+    ('ケ', "-.--"),
+    ('フ', "--.."),
+    ('コ', "----"),
+    ('エ', "-.---"),
+    ('テ', ".-.--"),
+    ('ア', "--.--"),
+    ('サ', "-.-.-"),
+    ('キ', "-.-.."),
+    ('ユ', "-..--"),
+    ('メ', "-...-"),
+    ('ミ', "..-.-"),
+    ('シ', "--.-."),
+    ('ヱ', ".--.."),
+    ('ヒ', "--..-"),
+    ('モ', "-..-."),
+    ('セ', ".---."),
+    ('ス', "---.-"),
+    ('ン', ".-.-."),
+    ('゛', ".."),
+    ('゜', "..--."),
+    ('ー', ".--.-"),
+    ('、', ".-.-.-"),
+    ('」', ".-.-.."),
+    ('（', "-.--.-"),
+    ('）', ".-..-."),
+];
+
+/// Switches from Latin morse code to Wabun code.
+pub const WABUN_START: &str = "-..---";
+
+/// Switches from Wabun code back to Latin morse code.
+pub const WABUN_END: &str = "...-.";
+
+/// The alphabets that morse code can be read in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Charset {
+    Latin,
+    Cyrillic,
+    /// Japanese kana.
+    Wabun,
+}
+
+impl FromStr for Charset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latin" => Ok(Charset::Latin),
+            "cyrillic" => Ok(Charset::Cyrillic),
+            "wabun" => Ok(Charset::Wabun),
+            _ => Err(format!("Unknown charset: {s}")),
+        }
+    }
+}
+
+impl Charset {
+    /// The letters that are sent differently than in Latin morse code.
+    fn letters(&self) -> &'static [(char, &'static str)] {
+        match self {
+            Charset::Latin => &[],
+            Charset::Cyrillic => &CYRILLIC,
+            Charset::Wabun => &WABUN,
+        }
+    }
+
+    /// Finds the character sent as the given dots and dashes. Numbers and
+    /// punctuation that the alphabet doesn't have are read as in Latin.
+    pub fn letter(&self, code: &str) -> Option<char> {
+        self.letters()
+            .iter()
+            .find_map(|&(ch, c)| (c == code).then_some(ch))
+            .or_else(|| get_morse_maps().1.get(code).copied())
+    }
+
+    /// The letters and numbers to practice with.
+    pub fn characters(&self) -> String {
+        let letters: String = match self {
+            Charset::Latin => "ABCDEFGHIJKLMNOPQRSTUVWXYZ".to_string(),
+            Charset::Cyrillic => CYRILLIC.iter().map(|&(ch, _)| ch).collect(),
+            // Only the kana, not the marks and punctuation:
+            Charset::Wabun => WABUN[..48].iter().map(|&(ch, _)| ch).collect(),
+        };
+        letters + "1234567890"
+    }
+}
+
+/// Finds how a character of any of the alphabets is sent.
+pub fn char_to_morse(ch: char) -> Option<String> {
+    let upper = ch.to_uppercase().next().unwrap_or(ch);
+    get_morse_maps().0.get(&upper).cloned().or_else(|| {
+        [Charset::Cyrillic, Charset::Wabun]
+            .iter()
+            .flat_map(|charset| charset.letters())
+            .find_map(|&(c, code)| (c == upper).then(|| code.to_string()))
+    })
+}
+
 /// Converts words per minute (WPM) into a dot length in milliseconds
 /// Based on standard Morse code timing where "PARIS" defines one word.
 pub fn wpm_to_dot_length(wpm: u32) -> u32 {
     1200 / wpm
 }
 
+/// Converts a dot length in milliseconds into words per minute (WPM).
+pub fn dot_length_to_wpm(dot_length: u32) -> u32 {
+    1200 / dot_length.max(1)
+}
+
 fn get_morse_maps() -> (HashMap<char, String>, HashMap<String, char>) {
     let forward_map = vec![
         ('A', ".-".to_string()),
@@ -110,6 +301,7 @@ fn get_morse_maps() -> (HashMap<char, String>, HashMap<String, char>) {
         ('!', "-.-.--".to_string()),
         ('-', "-....-".to_string()),
         ('/', "-..-.".to_string()),
+        ('=', "-...-".to_string()),
         ('@', ".--.-.".to_string()),
         ('(', "-.--.".to_string()),
         (')', "-.--.-".to_string()),
@@ -144,15 +336,7 @@ pub fn text_to_morse(text: &str) -> String {
     text.split_whitespace()
         .map(|word| {
             word.chars()
-                .filter_map(|ch| {
-                    get_morse_maps().0.iter().find_map(|(c, m)| {
-                        if *c == ch.to_ascii_uppercase() {
-                            Some(m.clone())
-                        } else {
-                            None
-                        }
-                    })
-                })
+                .filter_map(char_to_morse)
                 .collect::<Vec<String>>()
                 .join(" ")
         })
@@ -160,7 +344,7 @@ pub fn text_to_morse(text: &str) -> String {
         .join(" / ") // word gap
 }
 
-fn encode_morse(text: &str, dot_duration: u32, tone_freq: f32) -> Vec<(f32, u32)> {
+pub fn encode_morse(text: &str, dot_duration: u32, tone_freq: f32) -> Vec<(f32, u32)> {
     let morse_code = text_to_morse(text);
     let morse_code = regex::Regex::new(r"\s{3,}") // Match three or more spaces
         .unwrap()
@@ -174,7 +358,7 @@ fn encode_morse(text: &str, dot_duration: u32, tone_freq: f32) -> Vec<(f32, u32)
     morse_to_tones(&morse_code, dot_duration, tone_freq)
 }
 
-fn morse_to_tones(morse_code: &str, dot_duration: u32, tone_freq: f32) -> Vec<(f32, u32)> {
+pub fn morse_to_tones(morse_code: &str, dot_duration: u32, tone_freq: f32) -> Vec<(f32, u32)> {
     let dash_duration = dot_duration * 3; // Duration of a dash
     let char_gap_duration = dot_duration * 3; // Gap between characters
     let word_gap_duration = dot_duration * 7; // Gap between words
@@ -257,6 +441,66 @@ impl MorsePlayer {
         play_morse_code(tones, &sink);
         sink.sleep_until_end();
     }
+
+    /// Starts playing a message, without waiting for it to end. It stops
+    /// when the returned sink is dropped.
+    ///
+    /// # Returns
+    /// The sink, and how long from now until the last tone ends.
+    pub fn start(&self, message: &str, dot_duration: u32, tone_freq: f32) -> (Sink, Duration) {
+        let sink = Sink::try_new(&self.stream_handle).unwrap();
+        let tones = encode_morse(message, dot_duration, tone_freq);
+        let sounding = tones.iter().rposition(|&(freq, _)| freq > 0.0);
+        let length = sounding.map_or(0, |last| tones[..=last].iter().map(|&(_, d)| d).sum());
+        play_morse_code(tones, &sink);
+        (sink, Duration::from_millis(length.into()))
+    }
+
+    /// Starts playing some tones, as (frequency, milliseconds), without
+    /// waiting for them to end. They stop when the returned sink is
+    /// dropped.
+    ///
+    /// # Returns
+    /// The sink, and how long from now until the tones end.
+    pub fn start_tones(&self, tones: Vec<(f32, u32)>) -> (Sink, Duration) {
+        let sink = Sink::try_new(&self.stream_handle).unwrap();
+        let length: u32 = tones.iter().map(|&(_, duration)| duration).sum();
+        play_morse_code(tones, &sink);
+        (sink, Duration::from_millis(length.into()))
+    }
+
+    /// Starts playing the sound of a keyer, e.g. with Farnsworth timing,
+    /// without waiting for it to end. It stops when the returned sink is dropped.
+    pub fn start_keyer(&self, mut keyer: Keyer) -> Sink {
+        let mut samples = Vec::new();
+        let mut block = [0.0; 1024];
+        loop {
+            let count = keyer.fill(&mut block);
+            if count == 0 {
+                break;
+            }
+            samples.extend_from_slice(&block[..count]);
+        }
+        self.start_samples(samples, keyer.sample_rate())
+    }
+
+    /// Starts playing the mix of several keyers, e.g. a signal and the
+    /// one interfering with it, the same way as `start_keyer`.
+    pub fn start_mixer(&self, mut mixer: Mixer) -> Sink {
+        let mut samples = Vec::new();
+        let mut block = [0.0; 1024];
+        while !mixer.is_done() {
+            let count = mixer.fill(&mut block);
+            samples.extend_from_slice(&block[..count]);
+        }
+        self.start_samples(samples, mixer.sample_rate())
+    }
+
+    fn start_samples(&self, samples: Vec<f32>, sample_rate: u32) -> Sink {
+        let sink = Sink::try_new(&self.stream_handle).unwrap();
+        sink.append(rodio::buffer::SamplesBuffer::new(1, sample_rate, samples));
+        sink
+    }
 }
 
 //pub fn play_intro(message: &str, dot_duration: u32, tone_freq: f32) {}
@@ -273,4 +517,23 @@ mod tests {
             ".... . .-.. .-.. --- / .-- --- .-. .-.. -.. / .---- ..--- ...-- .-.-.- / .... --- .-- / .- .-. . / -.-- --- ..- ..--.."
         );
     }
+
+    #[test]
+    fn test_text_to_morse_charsets() {
+        assert_eq!(text_to_morse("Привет"), ".--. .-. .. .-- . -");
+        assert_eq!(text_to_morse("イロハ"), ".- .-.- -...");
+    }
+
+    #[test]
+    fn test_charset_letters() {
+        assert_eq!(Charset::Latin.letter("-.-."), Some('C'));
+        assert_eq!(Charset::Cyrillic.letter("-.-."), Some('Ц'));
+        assert_eq!(Charset::Cyrillic.letter("---."), Some('Ч'));
+        assert_eq!(Charset::Wabun.letter("-.-."), Some('ニ'));
+        // Numbers are the same in every alphabet:
+        assert_eq!(Charset::Wabun.letter(".----"), Some('1'));
+        assert_eq!(Charset::Cyrillic.letter("........"), None);
+        assert_eq!(Charset::Cyrillic.characters().chars().count(), 42);
+        assert_eq!(Charset::Wabun.characters().chars().count(), 58);
+    }
 }
@@ -0,0 +1,160 @@
+use crate::detect::ToneLevel;
+
+/// How many windows of levels (10ms each) the strength of the signal is
+/// judged over, before it is told if it changed.
+const SIGNAL_WINDOWS: usize = 300;
+
+/// How strong the signal is, as it is told with --accessible.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strength {
+    None,
+    Weak,
+    Strong,
+}
+
+#[allow(dead_code)]
+impl Strength {
+    pub fn sentence(&self) -> &'static str {
+        match self {
+            Strength::None => "No signal.",
+            Strength::Weak => "Signal weak.",
+            Strength::Strong => "Signal strong.",
+        }
+    }
+}
+
+/// Tells what is being decoded in whole words and sentences, for a
+/// screen reader to read out, with --accessible: a word once it is
+/// complete, instead of a character at a time, and the strength of the
+/// signal when it changes, instead of the meter.
+#[allow(dead_code)]
+#[derive(Clone, Debug, Default)]
+pub struct Narrator {
+    // How many words of the message have been told:
+    told: usize,
+    // The loudest level heard in the windows so far, against the threshold:
+    loudest: Option<f32>,
+    windows: usize,
+    strength: Option<Strength>,
+    calibrated: bool,
+}
+
+#[allow(dead_code)]
+impl Narrator {
+    /// The words of the message so far that are complete, and not told
+    /// yet. A word is complete once the space after it is decoded.
+    pub fn words(&mut self, partial: &str) -> Vec<String> {
+        let words: Vec<&str> = partial.split_whitespace().collect();
+        let complete = match partial.ends_with(char::is_whitespace) {
+            true => words.len(),
+            false => words.len().saturating_sub(1),
+        };
+        let told = self.told.min(complete);
+        self.told = self.told.max(complete);
+        words[told..complete]
+            .iter()
+            .map(|w| w.to_string())
+            .collect()
+    }
+
+    /// The words left to tell of a message that ended.
+    pub fn finish(&mut self, message: &str) -> Vec<String> {
+        let words = self.words(&format!("{message} "));
+        self.told = 0;
+        words
+    }
+
+    /// Whether the first threshold measured is to be told, as the end of
+    /// the calibration.
+    pub fn calibrated(&mut self) -> bool {
+        !std::mem::replace(&mut self.calibrated, true)
+    }
+
+    /// Follows the level of each window.
+    ///
+    /// # Returns
+    /// How strong the signal became, every few seconds, when it changed.
+    pub fn level(&mut self, level: &ToneLevel) -> Option<Strength> {
+        let threshold = level.threshold?;
+        let loudness = level.level / threshold.max(f32::EPSILON);
+        self.loudest = Some(self.loudest.map_or(loudness, |l| l.max(loudness)));
+        self.windows += 1;
+        if self.windows < SIGNAL_WINDOWS {
+            return None;
+        }
+        let strength = match self.loudest.take().unwrap_or(0.0) {
+            l if l < 1.0 => Strength::None,
+            l if l < 2.0 => Strength::Weak,
+            _ => Strength::Strong,
+        };
+        self.windows = 0;
+        (self.strength.replace(strength) != Some(strength)).then_some(strength)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(level: f32, threshold: Option<f32>) -> ToneLevel {
+        ToneLevel {
+            level,
+            threshold,
+            peak: None,
+            noise: None,
+            tone: threshold.is_some_and(|t| level > t),
+            offset: None,
+        }
+    }
+
+    #[test]
+    fn test_narrator_words() {
+        let mut narrator = Narrator::default();
+        assert!(narrator.words("C").is_empty());
+        assert!(narrator.words("CQ").is_empty());
+        assert_eq!(narrator.words("CQ "), vec!["CQ"]);
+        assert!(narrator.words("CQ CQ").is_empty());
+        // A word taken back isn't told again:
+        assert!(narrator.words("CQ").is_empty());
+        assert_eq!(narrator.words("CQ CQ DE "), vec!["CQ", "DE"]);
+        assert_eq!(narrator.finish("CQ CQ DE K1ABC"), vec!["K1ABC"]);
+        assert_eq!(narrator.finish("QRZ"), vec!["QRZ"]);
+    }
+
+    #[test]
+    fn test_narrator_signal() {
+        let mut narrator = Narrator::default();
+        // Nothing until the threshold is known:
+        for _ in 0..SIGNAL_WINDOWS {
+            assert_eq!(narrator.level(&level(0.9, None)), None);
+        }
+        let mut told = Vec::new();
+        // Times the threshold:
+        for loudness in [0.2, 1.2, 3.0, 1.2, 0.2, 0.2] {
+            for _ in 0..SIGNAL_WINDOWS {
+                told.extend(narrator.level(&level(loudness * 0.1, Some(0.1))));
+            }
+        }
+        // Only the changes:
+        assert_eq!(
+            told,
+            [
+                Strength::None,
+                Strength::Weak,
+                Strength::Strong,
+                Strength::Weak,
+                Strength::None
+            ]
+        );
+        // Judged by the loudest window, as the tone is keyed:
+        for i in 0..SIGNAL_WINDOWS {
+            let loudness = if i % 10 == 0 { 3.0 } else { 0.2 };
+            told.extend(narrator.level(&level(loudness * 0.1, Some(0.1))));
+        }
+        assert_eq!(told.last(), Some(&Strength::Strong));
+        assert_eq!(Strength::Strong.sentence(), "Signal strong.");
+
+        assert!(narrator.calibrated());
+        assert!(!narrator.calibrated());
+    }
+}
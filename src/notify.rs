@@ -0,0 +1,150 @@
+use crate::prelude::*;
+use std::process::Command;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// How long the messages that come after a notification are gathered,
+/// to be shown together in the next one.
+const COALESCE: Duration = Duration::from_secs(5);
+
+/// A message that was heard, to notify.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Note {
+    /// The label of the station, with --split-stereo.
+    pub station: Option<String>,
+    pub message: String,
+    pub wpm: Option<u32>,
+    /// Whether an `--alert` pattern is in it.
+    pub alert: bool,
+}
+
+/// The title and the body of the notification of some messages.
+pub fn notification(notes: &[Note]) -> (String, String) {
+    let alerts = notes.iter().filter(|note| note.alert).count();
+    let title = match (notes.len(), alerts) {
+        (1, 0) => "Morse code message".to_string(),
+        (1, _) => "Morse code alert".to_string(),
+        (n, 0) => format!("{n} morse code messages"),
+        (n, _) => format!("{n} morse code messages, with an alert"),
+    };
+    let body: Vec<String> = notes
+        .iter()
+        .map(|note| {
+            let station = note
+                .station
+                .as_ref()
+                .map_or(String::new(), |label| format!("{label} "));
+            let wpm = note
+                .wpm
+                .map_or(String::new(), |wpm| format!(" ({wpm} WPM)"));
+            format!("{station}{}{wpm}", note.message)
+        })
+        .collect();
+    (title, body.join("\n"))
+}
+
+/// Sends a desktop notification for each message, with `notify-send`
+/// (which shows it through the org.freedesktop.Notifications service),
+/// on a thread of its own so that the decoding never waits for it.
+///
+/// The messages that come soon after one are shown together, so that
+/// fast traffic doesn't make a storm of notifications. When they can't
+/// be shown, it is told once, and the decoding goes on.
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct Notifier {
+    sender: Sender<Note>,
+}
+
+#[allow(dead_code)]
+impl Notifier {
+    pub fn start() -> Self {
+        let (sender, receiver) = channel::<Note>();
+        std::thread::spawn(move || {
+            let mut failed = false;
+            while let Ok(note) = receiver.recv() {
+                show(&[note], &mut failed);
+                loop {
+                    let until = Instant::now() + COALESCE;
+                    let mut notes = Vec::new();
+                    loop {
+                        match receiver.recv_timeout(until.saturating_duration_since(Instant::now()))
+                        {
+                            Ok(note) => notes.push(note),
+                            Err(RecvTimeoutError::Timeout) => break,
+                            Err(RecvTimeoutError::Disconnected) => {
+                                return show(&notes, &mut failed)
+                            }
+                        }
+                    }
+                    if notes.is_empty() {
+                        break;
+                    }
+                    show(&notes, &mut failed);
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    pub fn send(&self, note: Note) {
+        let _ = self.sender.send(note);
+    }
+}
+
+fn show(notes: &[Note], failed: &mut bool) {
+    if notes.is_empty() || *failed {
+        return;
+    }
+    let (title, body) = notification(notes);
+    let urgency = match notes.iter().any(|note| note.alert) {
+        true => "critical",
+        false => "normal",
+    };
+    let shown = Command::new("notify-send")
+        .args(["--app-name", env!("CARGO_PKG_NAME"), "--urgency", urgency])
+        .arg(&title)
+        .arg(&body)
+        .output();
+    let error = match shown {
+        Ok(output) if output.status.success() => return,
+        Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        Err(e) => e.to_string(),
+    };
+    warn!("Could not show a desktop notification, they are turned off: {error}");
+    *failed = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(message: &str, wpm: Option<u32>, alert: bool) -> Note {
+        Note {
+            station: None,
+            message: message.to_string(),
+            wpm,
+            alert,
+        }
+    }
+
+    #[test]
+    fn test_notification() {
+        assert_eq!(
+            notification(&[note("CQ DE N0CALL", Some(18), false)]),
+            (
+                "Morse code message".to_string(),
+                "CQ DE N0CALL (18 WPM)".to_string()
+            )
+        );
+        let mut first = note("CQ TEST", None, true);
+        first.station = Some("L:".to_string());
+        assert_eq!(
+            notification(&[first, note("5NN", Some(32), false)]),
+            (
+                "2 morse code messages, with an alert".to_string(),
+                "L: CQ TEST\n5NN (32 WPM)".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,167 @@
+/// How long the marker takes to fade in and out, in seconds, so that it
+/// doesn't click.
+const MARKER_RAMP_SECS: f32 = 0.005;
+
+/// How loud the marker is, next to the filtered signal.
+const MARKER_LEVEL: f32 = 0.3;
+
+/// What `listen --monitor-audio` plays, and where.
+#[derive(Clone, Debug, PartialEq)]
+#[allow(dead_code)]
+pub struct PassthroughOptions {
+    /// The sink to play to, or None for the default one.
+    pub target: Option<String>,
+    /// The gain of the filtered signal, from 0 to 1.
+    pub volume: f32,
+    /// Whether to mark the tones the gate lets through with a quieter
+    /// tone an octave above.
+    pub marker: bool,
+    /// Play even to the node that is captured.
+    pub force: bool,
+}
+
+/// Whether playing the filtered audio to a sink would feed it back
+/// into the capture: when the monitor of that same sink is captured.
+///
+/// # Arguments
+/// * `capture` - The node captured, or None for the default sink.
+/// * `capture_sink` - Whether the monitor of a sink is captured.
+/// * `output` - The sink played to, or None for the default one.
+/// * `default_sink` - The name of the default sink, if it is known.
+///
+/// # Returns
+/// Why it would, if it does.
+#[allow(dead_code)]
+pub fn feedback(
+    capture: Option<&str>,
+    capture_sink: bool,
+    output: Option<&str>,
+    default_sink: Option<&str>,
+) -> Option<String> {
+    if !capture_sink {
+        return None;
+    }
+    // The default sink, given by its name:
+    let capture = capture.filter(|&capture| Some(capture) != default_sink);
+    let output = output.filter(|&output| Some(output) != default_sink);
+    match (capture, output) {
+        (None, None) => Some(
+            "The monitor of the default sink is captured, and would hear the audio played to it"
+                .to_string(),
+        ),
+        (Some(capture), Some(output)) if capture == output => Some(format!(
+            "The monitor of {capture} is captured, and would hear the audio played to it"
+        )),
+        _ => None,
+    }
+}
+
+/// Mixes the filtered samples to play, with the marker, as they are
+/// taken from the pipeline.
+#[allow(dead_code)]
+pub struct Mixer {
+    volume: f32,
+    marker: bool,
+    rate: u32,
+    marker_freq: f32,
+    phase: f32,
+    // From 0 to 1, as the marker fades in and out:
+    envelope: f32,
+}
+
+#[allow(dead_code)]
+impl Mixer {
+    pub fn new(options: &PassthroughOptions, tone_freq: f32, rate: u32) -> Self {
+        Self {
+            volume: options.volume.clamp(0.0, 1.0),
+            marker: options.marker,
+            rate: rate.max(1),
+            marker_freq: tone_freq * 2.0,
+            phase: 0.0,
+            envelope: 0.0,
+        }
+    }
+
+    /// Moves the marker along with the tone.
+    pub fn tune(&mut self, tone_freq: f32) {
+        self.marker_freq = tone_freq * 2.0;
+    }
+
+    /// Mixes samples taken from the pipeline, each with whether the
+    /// gate let a tone through, to be played. Muted, they are silence.
+    pub fn mix(&mut self, tapped: &[(f32, bool)], muted: bool, out: &mut Vec<f32>) {
+        let ramp = 1.0 / (MARKER_RAMP_SECS * self.rate as f32);
+        let step = std::f32::consts::TAU * self.marker_freq / self.rate as f32;
+        out.extend(tapped.iter().map(|&(sample, on)| {
+            let target = match self.marker && on {
+                true => 1.0,
+                false => 0.0,
+            };
+            self.envelope = match target > self.envelope {
+                true => (self.envelope + ramp).min(target),
+                false => (self.envelope - ramp).max(target),
+            };
+            let marker = match self.envelope > 0.0 {
+                true => MARKER_LEVEL * self.envelope * self.phase.sin(),
+                false => 0.0,
+            };
+            self.phase = (self.phase + step) % std::f32::consts::TAU;
+            match muted {
+                true => 0.0,
+                false => ((sample + marker) * self.volume).clamp(-1.0, 1.0),
+            }
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(marker: bool) -> PassthroughOptions {
+        PassthroughOptions {
+            target: None,
+            volume: 0.5,
+            marker,
+            force: false,
+        }
+    }
+
+    #[test]
+    fn test_feedback() {
+        assert!(feedback(None, true, None, None).is_some());
+        assert!(feedback(Some("speakers"), true, Some("speakers"), None).is_some());
+        assert!(feedback(Some("speakers"), true, Some("headphones"), None).is_none());
+        assert!(feedback(Some("speakers"), true, None, None).is_none());
+        // The default sink, by its name on one side and left out on the other:
+        assert!(feedback(Some("speakers"), true, None, Some("speakers")).is_some());
+        assert!(feedback(None, true, Some("speakers"), Some("speakers")).is_some());
+        assert!(feedback(Some("speakers"), true, None, Some("headphones")).is_none());
+        assert!(feedback(None, true, Some("speakers"), Some("headphones")).is_none());
+        // A source is no sink that is played to:
+        assert!(feedback(Some("radio"), false, Some("radio"), None).is_none());
+    }
+
+    #[test]
+    fn test_mix() {
+        let mut out = Vec::new();
+        let tapped: Vec<(f32, bool)> = (0..480).map(|i| (0.4, i >= 240)).collect();
+        Mixer::new(&options(false), 700.0, 48000).mix(&tapped, false, &mut out);
+        assert!(out.iter().all(|&s| (s - 0.2).abs() < 1e-6));
+
+        // The marker fades in once the gate is on:
+        out.clear();
+        let silence: Vec<(f32, bool)> = (0..4800).map(|i| (0.0, i >= 2400)).collect();
+        let mut mixer = Mixer::new(&options(true), 700.0, 48000);
+        mixer.mix(&silence, false, &mut out);
+        assert!(out[..2400].iter().all(|&s| s == 0.0));
+        let peak = out[2400..].iter().fold(0.0, |a: f32, &s| a.max(s.abs()));
+        assert!((peak - MARKER_LEVEL * 0.5).abs() < 0.01, "{peak}");
+        assert!(out[2401].abs() < 0.01);
+
+        // Muted:
+        out.clear();
+        mixer.mix(&tapped, true, &mut out);
+        assert!(out.iter().all(|&s| s == 0.0));
+    }
+}
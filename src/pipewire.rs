@@ -1,119 +1,2322 @@
 #[allow(unused_imports)]
+use crate::alert::Alerts;
+#[allow(unused_imports)]
+use crate::clipboard::{copy_and_tell, CopyOnExit};
+#[allow(unused_imports)]
+use crate::controls::{arguments, Control, TONE_STEP};
+#[allow(unused_imports)]
+use crate::detect::{
+    Channel, DecodeEvent, PipelineOptions, SignalPipeline, Station, TextOptions, ToneLevel,
+};
+#[allow(unused_imports)]
+use crate::events::{EventStream, OutputFormat};
+#[allow(unused_imports)]
 use crate::filter::*;
 #[allow(unused_imports)]
-use crate::message::Message;
+use crate::fldigi::Fldigi;
+#[allow(unused_imports)]
+use crate::input::{KeyReader, RawMode};
+#[allow(unused_imports)]
+use crate::keyer::Keyer;
+#[allow(unused_imports)]
+use crate::logbook::Logbook;
 #[allow(unused_imports)]
-use crate::morse::text_to_morse;
+use crate::message::{Message, SessionLog, SessionOptions, Summary};
+#[allow(unused_imports)]
+use crate::meter::{readout, LevelMeter};
+#[allow(unused_imports)]
+use crate::monitor::{KeyingMonitor, MonitorOptions};
+#[allow(unused_imports)]
+use crate::morse::{dot_length_to_wpm, text_to_morse};
+#[allow(unused_imports)]
+use crate::mqtt::Publisher;
+#[allow(unused_imports)]
+use crate::narrator::Narrator;
+#[allow(unused_imports)]
+use crate::notify::{Note, Notifier};
+#[allow(unused_imports)]
+use crate::passthrough::{feedback, Mixer, PassthroughOptions};
 #[cfg(target_os = "linux")]
 use crate::pipewire::spa::pod::Pod;
 #[allow(unused_imports)]
 use crate::prelude::*;
 #[allow(unused_imports)]
-use crate::term::log_message;
+use crate::recording::{RecordOptions, Recorder};
+#[allow(unused_imports)]
+use crate::rigctl::{Rig, Tuning};
+#[allow(unused_imports)]
+use crate::ring::{ring_buffer, Consumer, Producer};
+#[allow(unused_imports)]
+use crate::scan::{ScanBank, ScanRange};
+#[allow(unused_imports)]
+use crate::scrollback::Scrollback;
+#[allow(unused_imports)]
+use crate::selftest::SelfTest;
+#[allow(unused_imports)]
+use crate::server::{Protocol, Server, Serving};
+#[allow(unused_imports)]
+use crate::sidetone::{Marks, SidetoneTone};
+#[allow(unused_imports)]
+use crate::speech::Speaker;
+#[allow(unused_imports)]
+use crate::spot::Spotter;
+#[allow(unused_imports)]
+use crate::term::{print_status, rows_of};
+#[allow(unused_imports)]
+use crate::transcript::{Record, Transcript};
+#[allow(unused_imports)]
+use crate::tui::{leave, listen_screen, ListenEvent, ListenView, Screen};
+#[allow(unused_imports)]
+use crate::udp::UdpSender;
+#[allow(unused_imports)]
+use crate::uinput::Typist;
+#[allow(unused_imports)]
+use crate::waterfall::{Waterfall, MAX_FREQ, MIN_FREQ};
 #[allow(unused_imports)]
 use chrono::Local;
+#[cfg(target_os = "linux")]
+use pipewire as pw;
+#[cfg(target_os = "linux")]
+use pw::properties::properties;
+#[cfg(target_os = "linux")]
+use pw::{context::Context, main_loop::MainLoop, spa};
+#[allow(unused_imports)]
+use std::cell::{Cell, RefCell};
+#[allow(unused_imports)]
+use std::io::{IsTerminal, Write};
+#[allow(unused_imports)]
+use std::process::Command;
+#[allow(unused_imports)]
+use std::rc::Rc;
+#[allow(unused_imports)]
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+#[allow(unused_imports)]
+use std::sync::{Arc, Mutex};
 #[allow(unused_imports)]
-use morse_codec::decoder::Decoder;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "linux")]
+struct UserData {
+    format: spa::param::audio::AudioInfoRaw,
+    // How to read the samples in the buffers:
+    raw_format: RawFormat,
+    // The samples of the frame being read, one per channel:
+    input_frame: Vec<f32>,
+    // One sample per station for each frame, for the decoding thread:
+    producer: Producer,
+    frame: Vec<f32>,
+    sample_rate: Arc<AtomicU32>,
+    // Every channel of each frame, for --record:
+    recording: Option<Producer>,
+    capture_channels: Arc<AtomicU32>,
+}
+
+/// The decoding state of one station, owned by the decoding thread.
+#[cfg(target_os = "linux")]
+struct Receiver {
+    label: Option<String>,
+    pipeline: SignalPipeline,
+    message_log: Vec<Message>,
+    // The current message:
+    partial: String,
+    // The measured dot duration of the sender, in milliseconds:
+    dot_estimate: Option<u32>,
+    // The level of the latest window, with the meter or the monitor:
+    level: Option<ToneLevel>,
+    levels_shown: u64,
+    meter: Option<LevelMeter>,
+    monitor: Option<KeyingMonitor>,
+    // Whether the settings are shown, as they can be changed with keys:
+    settings: bool,
+    // The argument that sets the tone of the station:
+    tone_arg: &'static str,
+    summary: Summary,
+    // Shared by the stations, the messages kept to be copied:
+    session: Arc<Mutex<SessionLog>>,
+    // Shared by the stations, the panes of the screen, with Display::Text:
+    panes: Option<Arc<Mutex<Panes>>>,
+    // While the scrollback is shown over the screen:
+    scrolled: bool,
+    // With --scan, as one of its channels:
+    scanning: bool,
+    // Shared by the stations, to append the messages to:
+    transcript: Option<Arc<Mutex<Transcript>>>,
+    // With --output json:
+    events: Option<EventStream>,
+    // With --accessible:
+    narrator: Narrator,
+    alerts: Option<Alerts>,
+    // With --notify:
+    notifier: Option<Notifier>,
+    // With --speak:
+    speaker: Option<Speaker>,
+    // With --serve:
+    serving: Option<Serving>,
+    // With --udp-target:
+    udp: Option<Arc<UdpSender>>,
+    // With --mqtt:
+    mqtt: Option<Publisher>,
+    // With --rigctl:
+    rig: Option<Rig>,
+    // With --spot:
+    spotter: Option<Spotter>,
+    // With --log-qso:
+    logbook: Option<Logbook>,
+    // With --fldigi:
+    fldigi: Option<Fldigi>,
+    // With --type-output, for the first station only, as the others
+    // would type over its corrections:
+    typist: Option<Typist>,
+}
+
+/// The panes of the screen, and the rows of them on the terminal.
+#[cfg(target_os = "linux")]
+struct Panes {
+    view: ListenView,
+    screen: Screen,
+}
+
+/// How the decoding is shown.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Display {
+    /// The panes of the transcript, the status bar and the stats.
+    Text,
+    Waterfall,
+    /// Each message on a line of its own, without redrawing anything,
+    /// when stdout is not a terminal or with --plain.
+    Plain,
+    /// Whole words and sentences, for a screen reader, with --accessible.
+    Accessible,
+    /// Only the records of the messages, written to stdout.
+    Records,
+    /// The events as JSON, with --output json.
+    Events,
+    /// A line for each channel heard, with --scan.
+    Scan,
+}
+
+#[cfg(target_os = "linux")]
+impl Display {
+    /// Whether stdout is for another program to read, and kept free of
+    /// anything else.
+    fn is_machine(&self) -> bool {
+        matches!(self, Display::Records | Display::Events)
+    }
+
+    /// Whether the screen is redrawn as it goes, under the status line.
+    fn redraws(&self) -> bool {
+        matches!(self, Display::Text | Display::Waterfall | Display::Scan)
+    }
+}
+
+/// How soon after the first a second Ctrl-C exits without finishing.
+#[cfg(target_os = "linux")]
+const FORCE_QUIT: Duration = Duration::from_secs(2);
+
+/// The status line is redrawn for every this many windows of levels.
+#[cfg(target_os = "linux")]
+const LEVEL_REDRAW_WINDOWS: u64 = 2;
+
+/// The keys typed while listening, to change the settings as it goes
+/// (see `Control`). They are only read when the input is a terminal,
+/// which is then kept in raw mode.
+#[cfg(target_os = "linux")]
+struct Controls {
+    keys: KeyReader,
+    _raw: RawMode,
+    // The lowest tone that leaves room for the passband:
+    lowest: f32,
+    // With `h` or Page Up:
+    scrollback: Option<Scrollback>,
+    // Whether the audio of --monitor-audio is muted, if it is played:
+    muted: Option<Arc<AtomicBool>>,
+}
+
+#[cfg(target_os = "linux")]
+impl Controls {
+    fn start(bandwidth: f32, muted: Option<Arc<AtomicBool>>) -> Option<Self> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        let keys = KeyReader::start();
+        let raw = keys.raw_mode();
+        Some(Self {
+            keys,
+            _raw: raw,
+            lowest: MIN_FREQ.max(bandwidth / 2.0 + TONE_STEP),
+            scrollback: None,
+            muted,
+        })
+    }
+
+    /// Applies the keys typed since the last time to every station.
+    ///
+    /// # Returns
+    /// Whether to quit.
+    fn read(&mut self, receivers: &mut [Receiver]) -> bool {
+        while let Some(key) = self.keys.try_next() {
+            if let Some(scrollback) = self.scrollback.as_mut() {
+                // Still scrolled, the screen is drawn again for the summary:
+                if key.interrupt() {
+                    return true;
+                }
+                if !scrollback.key(&key) {
+                    self.scrollback = None;
+                    for receiver in receivers.iter_mut() {
+                        receiver.scrolled = false;
+                    }
+                    continue;
+                }
+                draw_scrollback(scrollback);
+                continue;
+            }
+            match Control::from_key(&key) {
+                None => continue,
+                Some(Control::Quit) => return true,
+                Some(Control::Clear) => {
+                    for receiver in receivers.iter_mut() {
+                        receiver.pipeline.clear();
+                        receiver.message_log.clear();
+                        receiver.partial.clear();
+                    }
+                    if let Some(panes) = &receivers[0].panes {
+                        panes.lock().unwrap().view.apply(ListenEvent::Clear);
+                    }
+                    redraw(receivers);
+                }
+                Some(control @ (Control::CopyTranscript | Control::CopyMessage)) => {
+                    let last = control == Control::CopyMessage;
+                    tell(receivers, copy_session(&receivers[0].session, last));
+                }
+                Some(control @ (Control::Scrollback | Control::Search)) => {
+                    let (_, height) = term_size::dimensions().unwrap_or((80, 24));
+                    let lines = receivers[0].session.lock().unwrap().lines();
+                    let mut scrollback = Scrollback::new(lines, height.saturating_sub(1));
+                    if control == Control::Search {
+                        scrollback.search();
+                    }
+                    draw_scrollback(&mut scrollback);
+                    self.scrollback = Some(scrollback);
+                    for receiver in receivers.iter_mut() {
+                        receiver.scrolled = true;
+                    }
+                    continue;
+                }
+                Some(Control::Mute) => {
+                    if let Some(muted) = &self.muted {
+                        let note = match muted.fetch_xor(true, Ordering::Relaxed) {
+                            false => "monitor audio muted",
+                            true => "monitor audio unmuted",
+                        };
+                        tell(receivers, note.to_string());
+                    }
+                }
+                Some(Control::Tone(_)) if receivers[0].scanning => {}
+                Some(control) => {
+                    for receiver in receivers.iter_mut() {
+                        control.apply(&mut receiver.pipeline, self.lowest, MAX_FREQ);
+                    }
+                }
+            }
+            show_status(receivers);
+        }
+        false
+    }
+
+    /// Draws the scrollback again for a new size of the terminal.
+    ///
+    /// # Returns
+    /// Whether it is shown.
+    fn resized(&mut self) -> bool {
+        match self.scrollback.as_mut() {
+            Some(scrollback) => {
+                draw_scrollback(scrollback);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Draws the scrollback over the whole screen, to the size of the
+/// terminal.
+#[cfg(target_os = "linux")]
+fn draw_scrollback(scrollback: &mut Scrollback) {
+    let (width, height) = term_size::dimensions().unwrap_or((80, 24));
+    scrollback.resize(height.saturating_sub(1));
+    clear_screen();
+    print!("{}", scrollback.render(width).join("\r\n"));
+    io::stdout().flush().unwrap();
+}
+
+/// Copies the messages of the session (or the last one) to the
+/// clipboard, telling where they went.
+#[cfg(target_os = "linux")]
+fn copy_session(session: &Mutex<SessionLog>, last: bool) -> String {
+    let session = session.lock().unwrap();
+    let text = match last {
+        true => session.last().map(str::to_string),
+        false => (!session.is_empty()).then(|| session.transcript()),
+    };
+    match text {
+        Some(text) => copy_and_tell(&text),
+        None => "No message to copy yet".to_string(),
+    }
+}
+
+/// The waterfall drawn by `listen --waterfall`, below the status line.
+#[cfg(target_os = "linux")]
+struct WaterfallView {
+    waterfall: Waterfall,
+}
+
+#[cfg(target_os = "linux")]
+impl WaterfallView {
+    fn new() -> Self {
+        let columns = term_size::dimensions().map_or(80, |(w, _)| w);
+        Self {
+            waterfall: Waterfall::new(columns.saturating_sub(1).clamp(16, 512)),
+        }
+    }
+
+    /// Draws the rows of the spectrum completed by some samples.
+    fn draw(&mut self, samples: &[f32], rate: u32, tone_freq: f32) {
+        for row in self.waterfall.push(samples, rate) {
+            print!("{}\r\n", self.waterfall.row(&row, tone_freq));
+        }
+        io::stdout().flush().unwrap();
+    }
+}
+
+/// The filtered audio played by `listen --monitor-audio`, on a stream
+/// started once the sample rate of the capture is known.
+#[cfg(target_os = "linux")]
+struct Passthrough {
+    options: PassthroughOptions,
+    // Toggled with the mute key:
+    muted: Arc<AtomicBool>,
+    // The rate the stream was started at, if it was:
+    rate: u32,
+    mixer: Option<Mixer>,
+    producer: Option<Producer>,
+    samples: Vec<f32>,
+    // Kept to play, until it is dropped:
+    _stream: Option<SidetoneStream>,
+}
+
+#[cfg(target_os = "linux")]
+impl Passthrough {
+    fn new(options: PassthroughOptions, muted: Arc<AtomicBool>) -> Self {
+        Self {
+            options,
+            muted,
+            rate: 0,
+            mixer: None,
+            producer: None,
+            samples: Vec::new(),
+            _stream: None,
+        }
+    }
+
+    /// Plays the samples taken from the pipeline of the first station.
+    fn play(&mut self, tapped: &[(f32, bool)], rate: u32, tone_freq: f32) {
+        if tapped.is_empty() || rate == 0 {
+            return;
+        }
+        if rate != self.rate {
+            self.rate = rate;
+            // Up to a second, of which only a couple of quanta are played:
+            let (producer, consumer) = ring_buffer(rate as usize);
+            self._stream = None;
+            match start_passthrough(self.options.target.clone(), rate, consumer) {
+                Ok(stream) => {
+                    self._stream = Some(stream);
+                    self.producer = Some(producer);
+                    self.mixer = Some(Mixer::new(&self.options, tone_freq, rate));
+                }
+                Err(e) => {
+                    error!("{e}");
+                    self.producer = None;
+                }
+            }
+        }
+        let (Some(mixer), Some(producer)) = (self.mixer.as_mut(), self.producer.as_mut()) else {
+            return;
+        };
+        mixer.tune(tone_freq);
+        self.samples.clear();
+        mixer.mix(
+            tapped,
+            self.muted.load(Ordering::Relaxed),
+            &mut self.samples,
+        );
+        for &sample in &self.samples {
+            if !producer.push(sample) {
+                break;
+            }
+        }
+    }
+}
+
+/// The audio written by `listen --record`: every channel of the frames
+/// captured, from a ring of their own, or the first station's audio as
+/// it comes out of the filter.
+#[cfg(target_os = "linux")]
+struct Recording {
+    recorder: Recorder,
+    // The frames captured, unless the filtered audio is recorded:
+    consumer: Option<Consumer>,
+    channels: Arc<AtomicU32>,
+    samples: Vec<f32>,
+}
+
+#[cfg(target_os = "linux")]
+impl Recording {
+    /// Writes the frames captured since the last time.
+    fn record(&mut self, rate: u32) -> Result<(), hound::Error> {
+        let Some(consumer) = self.consumer.as_mut() else {
+            return Ok(());
+        };
+        let channels = self.channels.load(Ordering::Acquire).max(1) as usize;
+        // Whole frames at a time:
+        self.samples.resize(4096 - 4096 % channels, 0.0);
+        loop {
+            let count = consumer.pop_slice(&mut self.samples);
+            if count == 0 {
+                return Ok(());
+            }
+            self.recorder
+                .write(&self.samples[..count], channels as u16, rate)?;
+        }
+    }
+
+    /// Writes the samples taken from the filter of the first station.
+    fn record_filtered(&mut self, tapped: &[(f32, bool)], rate: u32) -> Result<(), hound::Error> {
+        if self.consumer.is_some() {
+            return Ok(());
+        }
+        self.samples.clear();
+        self.samples
+            .extend(tapped.iter().map(|&(sample, _)| sample));
+        self.recorder.write(&self.samples, 1, rate)
+    }
+
+    /// Fixes up the header of the last file, telling where it all went.
+    fn finish(self) {
+        if let Some(overruns) = self.consumer.as_ref().map(Consumer::overruns) {
+            if overruns > 0 {
+                warn!("The recording has gaps, where it fell behind {overruns} times");
+            }
+        }
+        match self.recorder.finish() {
+            Ok(paths) => {
+                for path in paths {
+                    info!("Recorded to {}", path.display());
+                }
+            }
+            Err(e) => error!("Could not finish the recording: {e}"),
+        }
+    }
+}
+
+/// Draws the whole screen again, for a new size of the terminal or once
+/// the scrollback is closed.
+#[cfg(target_os = "linux")]
+fn resize(receivers: &mut [Receiver], view: &mut Option<WaterfallView>) {
+    if let Some(panes) = &receivers[0].panes {
+        let _ = panes.lock().unwrap().screen.clear();
+    }
+    redraw(receivers);
+    if let Some(view) = view.as_mut() {
+        *view = WaterfallView::new();
+        print!("{}\r\n", view.waterfall.scale());
+    }
+    io::stdout().flush().unwrap();
+}
+
+/// How long the decoding thread waits for more samples.
+#[allow(dead_code)]
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+#[cfg(target_os = "windows")]
+pub fn ensure_pipewire() {}
+
+#[cfg(target_os = "linux")]
+pub fn ensure_pipewire() {
+    let service_status = Command::new("systemctl")
+        .args(["--user", "is-active", "pipewire"])
+        .output();
+
+    match service_status {
+        Ok(output) if output.status.success() => {
+            //println!("PipeWire service is active");
+        }
+        _ => {
+            eprintln!("The pipewire service is not active. Checking installation ...");
+            let program_check = Command::new("pipewire").arg("--version").output();
+            match program_check {
+                Ok(output) if output.status.success() => {
+                    eprintln!(
+                        "pipewire is installed, but the service is not active. Please start it using: 'systemctl --user start pipewire'"
+                    );
+                }
+                _ => {
+                    eprintln!("pipewire is not installed. Please install it to proceed.");
+                }
+            }
+
+            std::process::exit(1);
+        }
+    }
+}
+
+/// An audio node in the PipeWire graph that can be captured from.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioNode {
+    pub id: u32,
+    pub name: String,
+    pub description: String,
+    pub media_class: String,
+}
+
+/// Finds the node given to `--target`, either by its id or by its name.
+#[allow(dead_code)]
+fn find_target<'a>(nodes: &'a [AudioNode], target: &str) -> Option<&'a AudioNode> {
+    match target.parse::<u32>() {
+        Ok(id) => nodes.iter().find(|n| n.id == id),
+        Err(_) => nodes.iter().find(|n| n.name == target),
+    }
+}
+
+/// The node name in a value of the "default" metadata, such as
+/// `{"name":"alsa_output.pci-0000_00_1f.3.analog-stereo"}`.
+#[allow(dead_code)]
+fn metadata_name(value: &str) -> Option<String> {
+    let (_, rest) = value.split_once("\"name\"")?;
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let (name, _) = rest.strip_prefix('"')?.split_once('"')?;
+    Some(name.to_string())
+}
+
+/// The layouts of captured samples that can be converted to f32.
+#[allow(dead_code)]
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RawFormat {
+    F32LE,
+    S16LE,
+    /// 24 bit samples in the low bytes of 32 bits.
+    S24_32LE,
+    S32LE,
+}
+
+#[allow(dead_code)]
+impl RawFormat {
+    const ALL: [RawFormat; 4] = [
+        RawFormat::F32LE,
+        RawFormat::S16LE,
+        RawFormat::S24_32LE,
+        RawFormat::S32LE,
+    ];
+
+    /// The number of bytes in each sample.
+    fn width(&self) -> usize {
+        match self {
+            RawFormat::S16LE => 2,
+            RawFormat::F32LE | RawFormat::S24_32LE | RawFormat::S32LE => 4,
+        }
+    }
+
+    /// Converts one sample to f32, in -1.0..1.0.
+    fn sample(&self, bytes: &[u8]) -> f32 {
+        let word = |bytes: &[u8]| [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            RawFormat::F32LE => f32::from_le_bytes(word(bytes)),
+            RawFormat::S16LE => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            RawFormat::S24_32LE => {
+                // Sign extend from the 24th bit:
+                let value = (i32::from_le_bytes(word(bytes)) << 8) >> 8;
+                value as f32 / 8388608.0
+            }
+            RawFormat::S32LE => i32::from_le_bytes(word(bytes)) as f32 / 2147483648.0,
+        }
+    }
+
+    /// Converts as many samples as fit in `out`.
+    fn convert(&self, bytes: &[u8], out: &mut [f32]) {
+        for (sample, bytes) in out.iter_mut().zip(bytes.chunks_exact(self.width())) {
+            *sample = self.sample(bytes);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl RawFormat {
+    fn from_audio_format(format: spa::param::audio::AudioFormat) -> Option<Self> {
+        use spa::param::audio::AudioFormat;
+        match format {
+            AudioFormat::F32LE => Some(RawFormat::F32LE),
+            AudioFormat::S16LE => Some(RawFormat::S16LE),
+            AudioFormat::S24_32LE => Some(RawFormat::S24_32LE),
+            AudioFormat::S32LE => Some(RawFormat::S32LE),
+            _ => None,
+        }
+    }
+
+    fn audio_format(&self) -> spa::param::audio::AudioFormat {
+        use spa::param::audio::AudioFormat;
+        match self {
+            RawFormat::F32LE => AudioFormat::F32LE,
+            RawFormat::S16LE => AudioFormat::S16LE,
+            RawFormat::S24_32LE => AudioFormat::S24_32LE,
+            RawFormat::S32LE => AudioFormat::S32LE,
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn list_targets() -> Result<(), std::io::Error> {
+    Ok(())
+}
+
+/// Collects all of the audio nodes currently known to the registry.
+#[cfg(target_os = "linux")]
+fn audio_nodes(mainloop: &MainLoop, core: &pw::core::Core) -> Result<Vec<AudioNode>, pw::Error> {
+    let registry = core.get_registry()?;
+    let nodes = Rc::new(RefCell::new(Vec::new()));
+    let done = Rc::new(Cell::new(false));
+
+    // The registry sends all existing globals before answering the sync:
+    let pending = core.sync(0)?;
+    let done_clone = done.clone();
+    let loop_clone = mainloop.clone();
+    let _listener_core = core
+        .add_listener_local()
+        .done(move |id, seq| {
+            if id == pw::core::PW_ID_CORE && seq == pending {
+                done_clone.set(true);
+                loop_clone.quit();
+            }
+        })
+        .register();
+    let nodes_clone = nodes.clone();
+    let _listener_reg = registry
+        .add_listener_local()
+        .global(move |global| {
+            if global.type_ != pw::types::ObjectType::Node {
+                return;
+            }
+            let Some(props) = global.props else {
+                return;
+            };
+            let media_class = props.get(*pw::keys::MEDIA_CLASS).unwrap_or_default();
+            if !media_class.starts_with("Audio/") {
+                return;
+            }
+            nodes_clone.borrow_mut().push(AudioNode {
+                id: global.id,
+                name: props
+                    .get(*pw::keys::NODE_NAME)
+                    .unwrap_or_default()
+                    .to_string(),
+                description: props
+                    .get(*pw::keys::NODE_DESCRIPTION)
+                    .unwrap_or_default()
+                    .to_string(),
+                media_class: media_class.to_string(),
+            });
+        })
+        .register();
+
+    while !done.get() {
+        mainloop.run();
+    }
+    let nodes = nodes.borrow().clone();
+    Ok(nodes)
+}
+
+/// The name of the default sink, as the session manager keeps it in the
+/// "default" metadata, if it has one.
+#[cfg(target_os = "linux")]
+fn default_sink(mainloop: &MainLoop, core: &pw::core::Core) -> Result<Option<String>, pw::Error> {
+    let registry = Rc::new(core.get_registry()?);
+    let sink = Rc::new(RefCell::new(None));
+    // The metadata bound, kept until its properties have come:
+    let bound = Rc::new(RefCell::new(Vec::new()));
+
+    let registry_clone = registry.clone();
+    let sink_clone = sink.clone();
+    let bound_clone = bound.clone();
+    let _listener_reg = registry
+        .add_listener_local()
+        .global(move |global| {
+            if global.type_ != pw::types::ObjectType::Metadata
+                || global.props.and_then(|props| props.get("metadata.name")) != Some("default")
+            {
+                return;
+            }
+            let Ok(metadata) = registry_clone.bind::<pw::metadata::Metadata, _>(global) else {
+                return;
+            };
+            let sink = sink_clone.clone();
+            let listener = metadata
+                .add_listener_local()
+                .property(move |_, key, _, value| {
+                    if key == Some("default.audio.sink") {
+                        *sink.borrow_mut() = value.and_then(metadata_name);
+                    }
+                    0
+                })
+                .register();
+            bound_clone.borrow_mut().push((metadata, listener));
+        })
+        .register();
+
+    // The first sync is answered after the globals, the second after the
+    // properties of the metadata bound on seeing them:
+    for _ in 0..2 {
+        let pending = core.sync(0)?;
+        let done = Rc::new(Cell::new(false));
+        let done_clone = done.clone();
+        let loop_clone = mainloop.clone();
+        let _listener_core = core
+            .add_listener_local()
+            .done(move |id, seq| {
+                if id == pw::core::PW_ID_CORE && seq == pending {
+                    done_clone.set(true);
+                    loop_clone.quit();
+                }
+            })
+            .register();
+        while !done.get() {
+            mainloop.run();
+        }
+    }
+    let sink = sink.borrow().clone();
+    Ok(sink)
+}
+
+/// Prints the audio nodes that may be given to `listen --target`.
+#[cfg(target_os = "linux")]
+pub fn list_targets() -> Result<(), pw::Error> {
+    pw::init();
+    let mainloop = MainLoop::new(None)?;
+    let context = Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+
+    println!("{:>5}  {:<16} {:<48} DESCRIPTION", "ID", "CLASS", "NAME");
+    for node in audio_nodes(&mainloop, &core)? {
+        println!(
+            "{:>5}  {:<16} {:<48} {}",
+            node.id, node.media_class, node.name, node.description
+        );
+    }
+    Ok(())
+}
+
+/// How the decoding is shown while listening.
+#[allow(dead_code)]
+pub struct DisplayOptions {
+    /// The format of stdout, with `--output`.
+    pub output: OutputFormat,
+    /// Whether the events of `--output json` include the levels heard.
+    pub verbose_events: bool,
+    /// Whether the messages are printed one after another, with `--plain`.
+    pub plain: bool,
+    /// Whether the audio is drawn as it is heard, with `--waterfall`.
+    pub waterfall: bool,
+    /// Whether the status line has a level meter, unless `--no-meter`.
+    pub meter: bool,
+    /// The keying shown on the status line, with `--monitor`.
+    pub monitor: Option<MonitorOptions>,
+    /// Whether the dots and dashes are printed too, with `--morse`.
+    pub output_morse: bool,
+}
+
+/// Where the decoding goes besides the screen, and what else is heard
+/// and kept while listening.
+#[allow(dead_code)]
+pub struct ListenOutputs {
+    /// The channels of `--scan`, decoded in place of the stations.
+    pub scan: Option<ScanRange>,
+    /// The filtered audio, played with `--monitor-audio`.
+    pub passthrough: Option<PassthroughOptions>,
+    /// The audio written to a file, with `--record`.
+    pub record: Option<RecordOptions>,
+    /// The messages written as they are completed, with `--log-file`.
+    pub transcript: Option<Transcript>,
+    /// What is highlighted in the messages, with `--alert`.
+    pub alerts: Option<Alerts>,
+    /// Whether an alert is told by a desktop notification, with `--notify`.
+    pub notify: bool,
+    /// The messages read aloud, with `--speak`.
+    pub speaker: Option<Speaker>,
+    /// The decoding served to clients, with `--serve`.
+    pub server: Option<Server>,
+    /// The decoding sent in datagrams, with `--udp-target`.
+    pub udp: Option<UdpSender>,
+    /// The messages published to a broker, with `--mqtt`.
+    pub mqtt: Option<Publisher>,
+    /// The radio whose frequency tags the messages, with `--rigctl`.
+    pub rig: Option<Rig>,
+    /// The stations heard calling CQ, spotted with `--spot`.
+    pub spotter: Option<Spotter>,
+    /// The contacts heard, logged to an ADIF file with `--log-qso`.
+    pub logbook: Option<Logbook>,
+    /// The characters pushed into the RX text of fldigi, with `--fldigi`.
+    pub fldigi: Option<Fldigi>,
+    /// The messages typed into another program, with `--type-output`.
+    pub typist: Option<Typist>,
+}
+
+#[cfg(target_os = "windows")]
+pub fn listen(
+    _target: Option<String>,
+    _stations: Vec<Station>,
+    _pipeline: PipelineOptions,
+    _ring_capacity: usize,
+    _display: DisplayOptions,
+    _outputs: ListenOutputs,
+    _session: SessionOptions,
+) -> Result<(), std::io::Error> {
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn send(_target: Option<String>, _keyer: Keyer) -> Result<(), std::io::Error> {
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn selftest(
+    _target: Option<String>,
+    _keyer: Keyer,
+    test: SelfTest,
+    _wait: Duration,
+) -> Result<SelfTest, std::io::Error> {
+    Ok(test)
+}
+
+/// Plays morse code through a PipeWire playback stream, until all of
+/// it was played or Ctrl-C is pressed.
+#[cfg(target_os = "linux")]
+pub fn send(target: Option<String>, keyer: Keyer) -> Result<(), pipewire::Error> {
+    pw::init();
+    let mainloop = MainLoop::new(None)?;
+    let context = Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+
+    let mut props = properties!(
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CATEGORY => "Playback",
+        *pw::keys::MEDIA_ROLE => "Communication"
+    );
+    if let Some(target) = target {
+        let nodes = audio_nodes(&mainloop, &core)?;
+        match find_target(&nodes, &target) {
+            Some(node) => props.insert(*pw::keys::TARGET_OBJECT, node.name.clone()),
+            None => {
+                error!("PipeWire node not found: {target}");
+                error!("Use 'listen --list-targets' to see the available nodes.");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let stream = pw::stream::Stream::new(&core, "audio-playback", props)?;
+    let sample_rate = keyer.sample_rate();
+
+    // Stop playing on Ctrl-C, and once all of it was played:
+    let quit_loop = mainloop.clone();
+    let _sigint = mainloop
+        .loop_()
+        .add_signal_local(pw::loop_::Signal::SIGINT, move || quit_loop.quit());
+    let drained_loop = mainloop.clone();
+    let _listener = play_keyer(&stream, keyer, || {}, move || drained_loop.quit())?;
+
+    let values = playback_format(sample_rate);
+    let mut params = [Pod::from_bytes(&values).unwrap()];
+
+    // Without RT_PROCESS, the buffers are filled on the main loop, which
+    // may then be quit from there:
+    stream.connect(
+        spa::utils::Direction::Output,
+        None,
+        pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+        &mut params,
+    )?;
+
+    mainloop.run();
+    Ok(())
+}
+
+/// The number of samples of each buffer asked of the sidetone stream,
+/// for the tone to follow the key closely.
+#[allow(dead_code)]
+const SIDETONE_QUANTUM: u32 = 256;
+
+/// A sidetone stream played on a thread of its own, until it is dropped.
+#[cfg(target_os = "windows")]
+pub struct SidetoneStream;
+
+#[cfg(target_os = "windows")]
+pub fn start_sidetone(
+    _target: Option<String>,
+    _tone: SidetoneTone,
+    _marks: Arc<Mutex<Marks>>,
+) -> Result<SidetoneStream, String> {
+    Ok(SidetoneStream)
+}
+
+/// A stream played on a thread of its own (the sidetone, or the audio of
+/// `--monitor-audio`), until it is dropped.
+#[cfg(target_os = "linux")]
+pub struct SidetoneStream {
+    quit: pw::channel::Sender<()>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for SidetoneStream {
+    fn drop(&mut self) {
+        let _ = self.quit.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Starts playing a sidetone that follows the marks of the key, on a
+/// playback stream that stays connected, fed silence in between them.
+/// Each buffer is filled as it is asked for, from the marks at that
+/// time, so that a mark is heard within one quantum.
+#[cfg(target_os = "linux")]
+pub fn start_sidetone(
+    target: Option<String>,
+    tone: SidetoneTone,
+    marks: Arc<Mutex<Marks>>,
+) -> Result<SidetoneStream, String> {
+    let sample_rate = tone.sample_rate();
+    start_playback("sidetone", target, sample_rate, move |samples| {
+        tone.fill(samples, Instant::now(), &mut marks.lock().unwrap())
+    })
+    .map_err(|e| format!("Could not play the sidetone: {e}"))
+}
+
+/// Starts playing the filtered audio of `listen --monitor-audio`, as it
+/// is taken from the ring by the decoding thread. What is more than a
+/// couple of quanta behind is skipped, to keep up with the capture, and
+/// silence is played while there is none.
+#[cfg(target_os = "linux")]
+fn start_passthrough(
+    target: Option<String>,
+    sample_rate: u32,
+    mut consumer: Consumer,
+) -> Result<SidetoneStream, String> {
+    start_playback("monitor-audio", target, sample_rate, move |samples| {
+        let behind = consumer
+            .available()
+            .saturating_sub(samples.len() + 2 * SIDETONE_QUANTUM as usize);
+        consumer.skip(behind);
+        let count = consumer.pop_slice(samples);
+        samples[count..].fill(0.0);
+    })
+    .map_err(|e| format!("Could not play the monitored audio: {e}"))
+}
+
+/// Starts a playback stream of one quantum a buffer, filled as each is
+/// asked for.
+#[cfg(target_os = "linux")]
+fn start_playback<F>(
+    name: &'static str,
+    target: Option<String>,
+    sample_rate: u32,
+    fill: F,
+) -> Result<SidetoneStream, String>
+where
+    F: FnMut(&mut [f32]) + Send + 'static,
+{
+    let (quit, quit_receiver) = pw::channel::channel::<()>();
+    let (ready, ready_receiver) = std::sync::mpsc::channel::<Result<(), String>>();
+    let thread = std::thread::spawn(move || {
+        let run = || -> Result<(), String> {
+            pw::init();
+            let mainloop = MainLoop::new(None).map_err(|e| e.to_string())?;
+            let context = Context::new(&mainloop).map_err(|e| e.to_string())?;
+            let core = context.connect(None).map_err(|e| e.to_string())?;
+            let mut props = properties!(
+                *pw::keys::MEDIA_TYPE => "Audio",
+                *pw::keys::MEDIA_CATEGORY => "Playback",
+                *pw::keys::MEDIA_ROLE => "Communication",
+                *pw::keys::NODE_LATENCY => format!("{SIDETONE_QUANTUM}/{sample_rate}")
+            );
+            if let Some(target) = target {
+                let nodes = audio_nodes(&mainloop, &core).map_err(|e| e.to_string())?;
+                match find_target(&nodes, &target) {
+                    Some(node) => props.insert(*pw::keys::TARGET_OBJECT, node.name.clone()),
+                    None => return Err(format!("PipeWire node not found: {target}")),
+                }
+            }
+            let stream = pw::stream::Stream::new(&core, name, props).map_err(|e| e.to_string())?;
+            let mut samples = Vec::new();
+            let _listener = stream
+                .add_local_listener_with_user_data(fill)
+                .process(move |stream, fill| {
+                    let Some(mut buffer) = stream.dequeue_buffer() else {
+                        return;
+                    };
+                    let datas = buffer.datas_mut();
+                    if datas.is_empty() {
+                        return;
+                    }
+                    let data = &mut datas[0];
+                    let stride = RawFormat::F32LE.width();
+                    let size = match data.data() {
+                        Some(bytes) => {
+                            // One quantum, not the whole buffer, to be played soon:
+                            let count = (bytes.len() / stride).min(SIDETONE_QUANTUM as usize);
+                            samples.resize(count, 0.0);
+                            fill(&mut samples);
+                            for (out, sample) in bytes.chunks_exact_mut(stride).zip(&samples) {
+                                out.copy_from_slice(&sample.to_le_bytes());
+                            }
+                            count * stride
+                        }
+                        None => 0,
+                    };
+                    let chunk = data.chunk_mut();
+                    *chunk.offset_mut() = 0;
+                    *chunk.stride_mut() = stride as _;
+                    *chunk.size_mut() = size as _;
+                })
+                .register()
+                .map_err(|e| e.to_string())?;
+            let values = playback_format(sample_rate);
+            let mut params = [Pod::from_bytes(&values).unwrap()];
+            stream
+                .connect(
+                    spa::utils::Direction::Output,
+                    None,
+                    pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+                    &mut params,
+                )
+                .map_err(|e| e.to_string())?;
+            let quit_loop = mainloop.clone();
+            let _quit = quit_receiver.attach(mainloop.loop_(), move |_| quit_loop.quit());
+            let _ = ready.send(Ok(()));
+            mainloop.run();
+            Ok(())
+        };
+        if let Err(e) = run() {
+            let _ = ready.send(Err(e));
+        }
+    });
+    match ready_receiver.recv() {
+        Ok(Ok(())) => Ok(SidetoneStream {
+            quit,
+            thread: Some(thread),
+        }),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err("The stream stopped".to_string()),
+    }
+}
+
+/// The state of the capture stream of a self test.
+#[cfg(target_os = "linux")]
+struct LoopbackData {
+    format: spa::param::audio::AudioInfoRaw,
+    raw_format: RawFormat,
+    input_frame: Vec<f32>,
+    samples: Vec<f32>,
+    test: Rc<RefCell<SelfTest>>,
+}
+
+/// Plays morse code to a sink, while decoding what is heard from its
+/// monitor. Decoding stops `wait` after all of it was played, or when
+/// Ctrl-C is pressed.
+#[cfg(target_os = "linux")]
+pub fn selftest(
+    target: Option<String>,
+    keyer: Keyer,
+    test: SelfTest,
+    wait: Duration,
+) -> Result<SelfTest, pipewire::Error> {
+    pw::init();
+    let mainloop = MainLoop::new(None)?;
+    let context = Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+
+    let mut playback_props = properties!(
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CATEGORY => "Playback",
+        *pw::keys::MEDIA_ROLE => "Communication"
+    );
+    let mut capture_props = properties!(
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CATEGORY => "Capture",
+        *pw::keys::MEDIA_ROLE => "Communication",
+        *pw::keys::STREAM_CAPTURE_SINK => "true"
+    );
+    if let Some(target) = target {
+        let nodes = audio_nodes(&mainloop, &core)?;
+        match find_target(&nodes, &target) {
+            Some(node) if node.media_class == "Audio/Sink" => {
+                playback_props.insert(*pw::keys::TARGET_OBJECT, node.name.clone());
+                capture_props.insert(*pw::keys::TARGET_OBJECT, node.name.clone());
+            }
+            Some(node) => {
+                error!("Not a sink: {} ({})", node.name, node.media_class);
+                error!("The self test plays to a sink, and listens to its monitor.");
+                std::process::exit(1);
+            }
+            None => {
+                error!("PipeWire node not found: {target}");
+                error!("Use 'listen --list-targets' to see the available nodes.");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let test = Rc::new(RefCell::new(test));
+    let sample_rate = keyer.sample_rate();
+    // When all of the message was played:
+    let drained_at: Rc<Cell<Option<Instant>>> = Rc::new(Cell::new(None));
+
+    let quit_loop = mainloop.clone();
+    let sigint = mainloop
+        .loop_()
+        .add_signal_local(pw::loop_::Signal::SIGINT, move || quit_loop.quit());
+
+    let capture = pw::stream::Stream::new(&core, "audio-capture", capture_props)?;
+    let data = LoopbackData {
+        format: Default::default(),
+        raw_format: RawFormat::F32LE,
+        input_frame: Vec::new(),
+        samples: Vec::new(),
+        test: test.clone(),
+    };
+    let capture_listener = capture
+        .add_local_listener_with_user_data(data)
+        .param_changed(|_, data, id, param| {
+            let Some(param) = param else {
+                return;
+            };
+            if id != pw::spa::param::ParamType::Format.as_raw() {
+                return;
+            }
+            let (raw_format, channels) = capture_format(&mut data.format, param);
+            data.raw_format = raw_format;
+            data.input_frame = vec![0.0; channels];
+        })
+        .process(|stream, data| match stream.dequeue_buffer() {
+            None => println!("Out of buffers"),
+            Some(mut buffer) => {
+                let datas = buffer.datas_mut();
+                if datas.is_empty() {
+                    return;
+                }
+                let buffer_data = &mut datas[0];
+                let frame_size = data.raw_format.width() * data.input_frame.len();
+                let size = buffer_data.chunk().size() as usize;
+                if let Some(bytes) = buffer_data.data() {
+                    let size = size.min(bytes.len());
+                    data.samples.clear();
+                    for frame in bytes[..size].chunks_exact(frame_size.max(1)) {
+                        data.raw_format.convert(frame, &mut data.input_frame);
+                        data.samples.push(Channel::Mix.sample(&data.input_frame));
+                    }
+                    let mut test = data.test.borrow_mut();
+                    let events = test
+                        .pipeline
+                        .push_samples(&data.samples, data.format.rate());
+                    test.handle(events, Instant::now());
+                }
+            }
+        })
+        .register()?;
+
+    let playback = pw::stream::Stream::new(&core, "audio-playback", playback_props)?;
+    let keyed_test = test.clone();
+    let drained = drained_at.clone();
+    let playback_listener = play_keyer(
+        &playback,
+        keyer,
+        move || keyed_test.borrow_mut().keyed(Instant::now()),
+        move || drained.set(Some(Instant::now())),
+    )?;
+
+    // Keep listening for a while after the message was played, for the
+    // decoder to time out:
+    let timer_loop = mainloop.clone();
+    let timer_test = test.clone();
+    let timer = mainloop.loop_().add_timer(move |_| {
+        if drained_at.get().is_some_and(|at| at.elapsed() >= wait) {
+            let mut test = timer_test.borrow_mut();
+            let events = test.pipeline.finish();
+            test.handle(events, Instant::now());
+            timer_loop.quit();
+        }
+    });
+    timer
+        .update_timer(Some(POLL_INTERVAL * 20), Some(POLL_INTERVAL * 20))
+        .into_result()?;
+
+    // Without RT_PROCESS, both streams are handled on the main loop,
+    // which shares the decoder between them:
+    let values = capture_formats();
+    let mut params = [Pod::from_bytes(&values).unwrap()];
+    capture.connect(
+        spa::utils::Direction::Input,
+        None,
+        pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+        &mut params,
+    )?;
+    let values = playback_format(sample_rate);
+    let mut params = [Pod::from_bytes(&values).unwrap()];
+    playback.connect(
+        spa::utils::Direction::Output,
+        None,
+        pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+        &mut params,
+    )?;
+
+    mainloop.run();
+
+    // Let go of the test, to hand it back:
+    drop((timer, sigint, playback_listener, capture_listener));
+    Ok(Rc::into_inner(test)
+        .expect("Self test still in use")
+        .into_inner())
+}
+
+/// Plays the sound of a keyer on a playback stream.
+///
+/// `keyed` is called once the first of it was written, and `drained`
+/// once all of it was played.
+#[cfg(target_os = "linux")]
+fn play_keyer<K, D>(
+    stream: &pw::stream::Stream,
+    keyer: Keyer,
+    mut keyed: K,
+    mut drained: D,
+) -> Result<pw::stream::StreamListener<Keyer>, pw::Error>
+where
+    K: FnMut() + 'static,
+    D: FnMut() + 'static,
+{
+    let mut samples = Vec::new();
+    let mut started = false;
+    let mut draining = false;
+    stream
+        .add_local_listener_with_user_data(keyer)
+        .process(move |stream, keyer| match stream.dequeue_buffer() {
+            None => println!("Out of buffers"),
+            Some(mut buffer) => {
+                let datas = buffer.datas_mut();
+                if datas.is_empty() {
+                    return;
+                }
+                let data = &mut datas[0];
+                let stride = RawFormat::F32LE.width();
+                let size = match data.data() {
+                    Some(bytes) => {
+                        samples.resize(bytes.len() / stride, 0.0);
+                        let count = keyer.fill(&mut samples);
+                        for (out, sample) in bytes.chunks_exact_mut(stride).zip(&samples[..count]) {
+                            out.copy_from_slice(&sample.to_le_bytes());
+                        }
+                        count * stride
+                    }
+                    None => 0,
+                };
+                let chunk = data.chunk_mut();
+                *chunk.offset_mut() = 0;
+                *chunk.stride_mut() = stride as _;
+                *chunk.size_mut() = size as _;
+                if size > 0 && !started {
+                    started = true;
+                    keyed();
+                }
+                if keyer.is_done() && !draining {
+                    // Queue the last buffer, and wait for it to be played:
+                    drop(buffer);
+                    draining = true;
+                    if let Err(e) = stream.flush(true) {
+                        error!("Could not drain the stream: {e}");
+                    }
+                }
+            }
+        })
+        .drained(move |_, _| drained())
+        .register()
+}
+
+/// The format of a playback stream, mono F32LE samples.
+#[cfg(target_os = "linux")]
+fn playback_format(sample_rate: u32) -> Vec<u8> {
+    let mut audio_info = spa::param::audio::AudioInfoRaw::new();
+    audio_info.set_format(spa::param::audio::AudioFormat::F32LE);
+    audio_info.set_rate(sample_rate);
+    audio_info.set_channels(1);
+    let obj = pw::spa::pod::Object {
+        type_: pw::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+        id: pw::spa::param::ParamType::EnumFormat.as_raw(),
+        properties: audio_info.into(),
+    };
+    pw::spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &pw::spa::pod::Value::Object(obj),
+    )
+    .unwrap()
+    .0
+    .into_inner()
+}
+
+/// The formats of a capture stream, all of the sample formats that can
+/// be converted, preferring F32LE.
+#[cfg(target_os = "linux")]
+fn capture_formats() -> Vec<u8> {
+    let audio_info = spa::param::audio::AudioInfoRaw::new();
+    let mut properties: Vec<pw::spa::pod::Property> = audio_info.into();
+    properties.push(pw::spa::pod::Property::new(
+        spa::sys::SPA_FORMAT_AUDIO_format,
+        pw::spa::pod::Value::Choice(pw::spa::pod::ChoiceValue::Id(spa::utils::Choice(
+            spa::utils::ChoiceFlags::empty(),
+            spa::utils::ChoiceEnum::Enum {
+                default: spa::utils::Id(spa::param::audio::AudioFormat::F32LE.as_raw()),
+                alternatives: RawFormat::ALL
+                    .iter()
+                    .map(|f| spa::utils::Id(f.audio_format().as_raw()))
+                    .collect(),
+            },
+        ))),
+    ));
+    let obj = pw::spa::pod::Object {
+        type_: pw::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+        id: pw::spa::param::ParamType::EnumFormat.as_raw(),
+        properties,
+    };
+    pw::spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &pw::spa::pod::Value::Object(obj),
+    )
+    .unwrap()
+    .0
+    .into_inner()
+}
+
+/// Reads the format negotiated for a capture stream.
+///
+/// # Returns
+/// How to read its samples, and the number of channels.
+#[cfg(target_os = "linux")]
+fn capture_format(format: &mut spa::param::audio::AudioInfoRaw, param: &Pod) -> (RawFormat, usize) {
+    format.parse(param).unwrap();
+    let Some(raw_format) = RawFormat::from_audio_format(format.format()) else {
+        error!("Unsupported sample format: {:?}", format.format());
+        std::process::exit(1);
+    };
+    (raw_format, format.channels().max(1) as usize)
+}
+
+/// Redraws the terminal with the messages of every station.
+///
+/// With more than one station, each one gets its own section, headed
+/// by its label and ending with the message it is receiving.
+/// Only the latest messages that fit on the screen are shown.
+#[cfg(target_os = "linux")]
+fn redraw(receivers: &[Receiver]) {
+    if receivers[0].scanning {
+        return redraw_scan(receivers);
+    }
+    if let Some(panes) = &receivers[0].panes {
+        return draw_panes(receivers, &mut panes.lock().unwrap());
+    }
+    // The waterfall is drawn under the status line:
+    clear_screen();
+    print!("{}\r\n", status_line(receivers));
+}
+
+/// Draws the panes to the size of the terminal, with the status line
+/// of the stations as it is now. Only the rows that changed are written.
+#[cfg(target_os = "linux")]
+fn draw_panes(receivers: &[Receiver], panes: &mut Panes) {
+    panes
+        .view
+        .apply(ListenEvent::Status(status_line(receivers)));
+    let Panes { view, screen } = panes;
+    if let Err(e) = screen.draw(|frame| frame.render_widget(&*view, frame.area())) {
+        warn!("Could not draw the screen: {e}");
+    }
+}
+
+/// Shows the status line again, e.g. once a setting was changed.
+#[cfg(target_os = "linux")]
+fn show_status(receivers: &[Receiver]) {
+    match receivers[0].panes {
+        Some(_) => redraw(receivers),
+        None => {
+            print_status(&status_line(receivers));
+            io::stdout().flush().unwrap();
+        }
+    }
+}
+
+/// Tells something that happened, e.g. where the session was copied: in
+/// the stats pane, or on a line of its own under the waterfall.
+#[cfg(target_os = "linux")]
+fn tell(receivers: &[Receiver], note: String) {
+    match &receivers[0].panes {
+        Some(panes) => {
+            panes.lock().unwrap().view.apply(ListenEvent::Note(note));
+            redraw(receivers);
+        }
+        None => print!("\r\n{note}\r\n"),
+    }
+}
+
+/// Draws the screen of `--scan`: a line for each channel that heard
+/// anything, with the end of its latest messages and the one it is
+/// receiving, the latest ones first once they don't all fit.
+#[cfg(target_os = "linux")]
+fn redraw_scan(receivers: &[Receiver]) {
+    clear_screen();
+    let status = status_line(receivers);
+    print!("{status}\r\n");
+    let (width, height) = term_size::dimensions().unwrap_or((80, 24));
+    let rows = height.saturating_sub(rows_of(&status, width) + 1);
+    let mut active: Vec<(&Receiver, Option<&String>)> = receivers
+        .iter()
+        .map(|receiver| (receiver, receiver.message_log.last().map(|m| &m.timestamp)))
+        .filter(|(receiver, heard)| heard.is_some() || !receiver.partial.is_empty())
+        .collect();
+    // Those receiving now, then by the latest message:
+    active.sort_by(|a, b| (a.0.partial.is_empty(), b.1).cmp(&(b.0.partial.is_empty(), a.1)));
+    active.truncate(rows);
+    active.sort_by(|a, b| {
+        a.0.pipeline
+            .tone_freq()
+            .total_cmp(&b.0.pipeline.tone_freq())
+    });
+    for (receiver, _) in active {
+        let label = receiver.label.as_deref().unwrap_or_default();
+        let wpm = match receiver.dot_estimate {
+            Some(dot) => format!("{:>2} WPM", dot_length_to_wpm(dot)),
+            None => "-- WPM".to_string(),
+        };
+        let mut text = receiver.partial.clone();
+        for message in receiver.message_log.iter().rev() {
+            if text.chars().count() >= width {
+                break;
+            }
+            text = format!("{} {text}", message.content);
+        }
+        let text = text.trim();
+        let prefix = format!("{label:>8} {wpm}  ");
+        let room = width.saturating_sub(prefix.chars().count() + 1);
+        let skip = text.chars().count().saturating_sub(room);
+        let text: String = text.chars().skip(skip).collect();
+        let line = match &receiver.alerts {
+            Some(alerts) => alerts.highlight(&text),
+            None => text,
+        };
+        print!("{prefix}{line}\r\n");
+    }
+    io::stdout().flush().unwrap();
+}
+
+/// The measured speed of every station, e.g. `speed: 18 WPM (dot 66ms)`,
+/// followed by the level meter and the monitor when they are shown.
+#[cfg(target_os = "linux")]
+fn status_line(receivers: &[Receiver]) -> String {
+    if receivers[0].scanning {
+        let heard = receivers
+            .iter()
+            .filter(|r| !r.message_log.is_empty() || !r.partial.is_empty())
+            .count();
+        let (low, high) = (
+            receivers[0].pipeline.tone_freq(),
+            receivers[receivers.len() - 1].pipeline.tone_freq(),
+        );
+        return format!(
+            "scanning {low:.0}-{high:.0} Hz: {} channels, {heard} heard",
+            receivers.len()
+        );
+    }
+    receivers
+        .iter()
+        .map(|receiver| {
+            let label = match &receiver.label {
+                Some(label) => format!("{label} "),
+                None => String::new(),
+            };
+            let speed = match receiver.dot_estimate {
+                Some(dot) => format!("speed: {} WPM (dot {dot}ms)", dot_length_to_wpm(dot)),
+                None => "speed: -- WPM".to_string(),
+            };
+            let mut parts = Vec::new();
+            if !receiver.monitor.as_ref().is_some_and(|m| m.options.only) {
+                parts.push(speed);
+            }
+            if receiver.settings {
+                let threshold = match receiver.pipeline.threshold() {
+                    Some(threshold) => format!("{threshold:.3}"),
+                    None => "--".to_string(),
+                };
+                parts.push(format!(
+                    "tone {:.0} Hz  threshold {threshold}  decoder {} WPM",
+                    receiver.pipeline.tone_freq(),
+                    dot_length_to_wpm(receiver.pipeline.dot_duration())
+                ));
+            }
+            if let Some(level) = &receiver.level {
+                if let Some(meter) = &receiver.meter {
+                    parts.push(meter.bar(level));
+                }
+                if let Some(monitor) = &receiver.monitor {
+                    parts.push(monitor.line());
+                }
+                parts.push(readout(level));
+            }
+            format!("{label}{}", parts.join("  "))
+        })
+        .collect::<Vec<_>>()
+        .join("    ")
+}
+
+/// Keeps the level and the speed of a station for its status line.
+///
+/// # Returns
+/// Whether the status line is to be shown again.
+#[cfg(target_os = "linux")]
+fn update_status(receiver: &mut Receiver, event: &DecodeEvent) -> bool {
+    match event {
+        DecodeEvent::Level(level) => {
+            let bell = receiver
+                .monitor
+                .as_mut()
+                .is_some_and(|monitor| monitor.push(level));
+            receiver.level = Some(*level);
+            receiver.levels_shown += 1;
+            if bell {
+                print!("\x07");
+            }
+            bell || receiver.levels_shown % LEVEL_REDRAW_WINDOWS == 0
+        }
+        DecodeEvent::DotEstimate(dot) => {
+            receiver.dot_estimate = Some(*dot);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Updates the panes for something that happened while decoding, only
+/// keeping up with it while the scrollback is shown over them.
 #[cfg(target_os = "linux")]
-use pipewire as pw;
+fn pane_event(receivers: &mut [Receiver], index: usize, event: DecodeEvent, output_morse: bool) {
+    let receiver = &mut receivers[index];
+    let label = match &receiver.label {
+        Some(label) => format!("{label} "),
+        None => String::new(),
+    };
+    let monitor_only = receiver
+        .monitor
+        .as_ref()
+        .is_some_and(|monitor| monitor.options.only);
+    let morse = |msg: String| match output_morse {
+        true => text_to_morse(&msg),
+        false => msg,
+    };
+    let update = match event {
+        DecodeEvent::Level(_) | DecodeEvent::DotEstimate(_) => {
+            if !update_status(receiver, &event) {
+                return;
+            }
+            None
+        }
+        DecodeEvent::Signal { .. } => return,
+        // The decoded text is left out with --monitor-only:
+        DecodeEvent::Partial(_) | DecodeEvent::Message(_) | DecodeEvent::Speed(_)
+            if monitor_only =>
+        {
+            return
+        }
+        DecodeEvent::Partial(msg) => {
+            receiver.partial = morse(msg);
+            Some(ListenEvent::Partial(index, receiver.partial.clone()))
+        }
+        DecodeEvent::Message(msg) => {
+            let m = Message {
+                timestamp: Local::now().format("%y-%m-%d %H:%M:%S %p").to_string(),
+                content: morse(msg),
+                wpm: receiver.dot_estimate.map(dot_length_to_wpm),
+            };
+            write_record(receiver, &m.content);
+            receiver.summary.add(&m);
+            receiver.message_log.push(m.clone());
+            receiver.partial.clear();
+            Some(ListenEvent::Message(index, m))
+        }
+        DecodeEvent::ToneLocked(freq) => {
+            Some(ListenEvent::Note(format!("{label}locked to {freq:.0} Hz")))
+        }
+        DecodeEvent::Threshold(chosen) => {
+            Some(ListenEvent::Note(format!("{label}threshold {chosen:.3}")))
+        }
+        DecodeEvent::Speed(dot) => Some(ListenEvent::Note(format!(
+            "{label}tracking {} WPM",
+            dot_length_to_wpm(dot)
+        ))),
+    };
+    if let (Some(update), Some(panes)) = (update, &receivers[0].panes) {
+        panes.lock().unwrap().view.apply(update);
+    }
+    if !receivers[0].scrolled {
+        redraw(receivers);
+    }
+}
+
+/// Keeps up with something that happened while decoding, without
+/// printing it, as the scrollback is shown over the screen (the messages
+/// are shown once it is closed), or for `--scan` to draw the channels.
 #[cfg(target_os = "linux")]
-use pw::properties::properties;
+fn quiet_event(receiver: &mut Receiver, display: Display, event: DecodeEvent, output_morse: bool) {
+    let monitor_only = receiver
+        .monitor
+        .as_ref()
+        .is_some_and(|monitor| monitor.options.only);
+    let morse = |msg: String| match output_morse {
+        true => text_to_morse(&msg),
+        false => msg,
+    };
+    match event {
+        DecodeEvent::Level(level) => {
+            if let Some(monitor) = receiver.monitor.as_mut() {
+                monitor.push(&level);
+            }
+            receiver.level = Some(level);
+        }
+        DecodeEvent::DotEstimate(dot) => receiver.dot_estimate = Some(dot),
+        DecodeEvent::Partial(_) | DecodeEvent::Message(_) if monitor_only => {}
+        DecodeEvent::Partial(msg) => receiver.partial = morse(msg),
+        DecodeEvent::Message(msg) => {
+            let m = Message {
+                timestamp: Local::now().format("%y-%m-%d %H:%M:%S %p").to_string(),
+                content: morse(msg),
+                wpm: receiver.dot_estimate.map(dot_length_to_wpm),
+            };
+            write_record(receiver, &m.content);
+            receiver.summary.add(&m);
+            // The waterfall doesn't keep them, printing each one at once:
+            if display != Display::Waterfall {
+                receiver.message_log.push(m);
+            }
+            receiver.partial.clear();
+        }
+        _ => {}
+    }
+}
+
+/// Updates the terminal for something that happened while decoding,
+/// below the waterfall. The terminal may be in raw mode, and each
+/// message is printed whole on its own line between the rows.
 #[cfg(target_os = "linux")]
-use pw::{context::Context, main_loop::MainLoop, spa};
-#[allow(unused_imports)]
-use regex::Regex;
-#[allow(unused_imports)]
-use std::process::Command;
-#[allow(unused_imports)]
-use std::time::Instant;
+fn show_waterfall_event(
+    receivers: &mut [Receiver],
+    index: usize,
+    event: DecodeEvent,
+    output_morse: bool,
+) {
+    match event {
+        DecodeEvent::Message(mut msg) => {
+            if output_morse {
+                msg = text_to_morse(&msg);
+            }
+            let timestamp = Local::now().format("%y-%m-%d %H:%M:%S %p");
+            let receiver = &mut receivers[index];
+            match &receiver.alerts {
+                Some(alerts) => print!("{timestamp} {}\r\n", alerts.highlight(&msg)),
+                None => print!("{timestamp} {msg}\r\n"),
+            }
+            write_record(receiver, &msg);
+            receiver.summary.add(&Message {
+                timestamp: timestamp.to_string(),
+                content: msg,
+                wpm: receiver.dot_estimate.map(dot_length_to_wpm),
+            });
+        }
+        DecodeEvent::ToneLocked(freq) => print!("locked to {freq:.0} Hz\r\n"),
+        DecodeEvent::Level(_) | DecodeEvent::DotEstimate(_)
+            if update_status(&mut receivers[index], &event) =>
+        {
+            show_status(receivers)
+        }
+        _ => {}
+    }
+}
 
+/// Prints each message when it is complete, for stdout to be read (or
+/// kept) as it is written, with --plain or when it is not a terminal.
 #[cfg(target_os = "linux")]
-struct UserData {
-    format: spa::param::audio::AudioInfoRaw,
-    filter: Option<BandpassFilter>,
-    message_log: Vec<Message>,
+fn plain_event(receivers: &mut [Receiver], index: usize, event: DecodeEvent, output_morse: bool) {
+    let receiver = &mut receivers[index];
+    let label = match &receiver.label {
+        Some(label) => format!("{label} "),
+        None => String::new(),
+    };
+    match event {
+        DecodeEvent::Message(mut msg) => {
+            if output_morse {
+                msg = text_to_morse(&msg);
+            }
+            let timestamp = Local::now().format("%y-%m-%d %H:%M:%S %p").to_string();
+            println!("{timestamp} {label}{msg}");
+            write_record(receiver, &msg);
+            receiver.summary.add(&Message {
+                timestamp,
+                content: msg,
+                wpm: receiver.dot_estimate.map(dot_length_to_wpm),
+            });
+        }
+        DecodeEvent::ToneLocked(freq) => println!("{label}locked to {freq:.0} Hz"),
+        DecodeEvent::Threshold(chosen) => println!("{label}threshold {chosen:.3}"),
+        DecodeEvent::Speed(dot) => println!("{label}tracking {} WPM", dot_length_to_wpm(dot)),
+        DecodeEvent::DotEstimate(dot) => receiver.dot_estimate = Some(dot),
+        _ => {}
+    }
 }
 
-#[cfg(target_os = "windows")]
-pub fn ensure_pipewire() {}
+/// Tells what is decoded in whole words and sentences, for a screen
+/// reader, with --accessible.
+#[cfg(target_os = "linux")]
+fn accessible_event(
+    receivers: &mut [Receiver],
+    index: usize,
+    event: DecodeEvent,
+    output_morse: bool,
+) {
+    let receiver = &mut receivers[index];
+    let label = match &receiver.label {
+        Some(label) => format!("{label} "),
+        None => String::new(),
+    };
+    match event {
+        DecodeEvent::Partial(msg) => {
+            for word in receiver.narrator.words(&msg) {
+                println!("{label}{word}");
+            }
+        }
+        DecodeEvent::Message(mut msg) => {
+            for word in receiver.narrator.finish(&msg) {
+                println!("{label}{word}");
+            }
+            println!("{label}Message complete.");
+            if output_morse {
+                msg = text_to_morse(&msg);
+            }
+            write_record(receiver, &msg);
+            receiver.summary.add(&Message {
+                timestamp: Local::now().format("%y-%m-%d %H:%M:%S %p").to_string(),
+                content: msg,
+                wpm: receiver.dot_estimate.map(dot_length_to_wpm),
+            });
+        }
+        DecodeEvent::Level(level) => {
+            if let Some(strength) = receiver.narrator.level(&level) {
+                println!("{label}{}", strength.sentence());
+            }
+        }
+        DecodeEvent::ToneLocked(freq) => println!("{label}Tone found at {freq:.0} hertz."),
+        DecodeEvent::Threshold(chosen) => {
+            if receiver.narrator.calibrated() {
+                println!("{label}Calibration complete, threshold {chosen:.3}.");
+            }
+        }
+        DecodeEvent::Speed(dot) => println!(
+            "{label}Tracking {} words per minute.",
+            dot_length_to_wpm(dot)
+        ),
+        DecodeEvent::DotEstimate(dot) => receiver.dot_estimate = Some(dot),
+        DecodeEvent::Signal { .. } => {}
+    }
+}
 
+/// Only records the messages, when they are written to stdout.
 #[cfg(target_os = "linux")]
-pub fn ensure_pipewire() {
-    let service_status = Command::new("systemctl")
-        .args(["--user", "is-active", "pipewire"])
-        .output();
+fn record_event(receivers: &mut [Receiver], index: usize, event: DecodeEvent, output_morse: bool) {
+    let receiver = &mut receivers[index];
+    match event {
+        DecodeEvent::Message(mut msg) => {
+            if output_morse {
+                msg = text_to_morse(&msg);
+            }
+            write_record(receiver, &msg);
+            receiver.summary.add(&Message {
+                timestamp: Local::now().format("%y-%m-%d %H:%M:%S %p").to_string(),
+                content: msg,
+                wpm: receiver.dot_estimate.map(dot_length_to_wpm),
+            });
+        }
+        DecodeEvent::DotEstimate(dot) => receiver.dot_estimate = Some(dot),
+        _ => {}
+    }
+}
 
-    match service_status {
-        Ok(output) if output.status.success() => {
-            //println!("PipeWire service is active");
+/// Writes the events as lines of JSON, with --output json.
+#[cfg(target_os = "linux")]
+fn json_event(receivers: &mut [Receiver], index: usize, event: DecodeEvent) {
+    let receiver = &mut receivers[index];
+    let t = Local::now().timestamp_millis() as f64 / 1000.0;
+    let wpm = receiver.dot_estimate.map(dot_length_to_wpm);
+    let tuning = tuning(receiver);
+    if let Some(events) = receiver.events.as_mut() {
+        events.tuning = tuning;
+        let mut stdout = io::stdout().lock();
+        for line in events.lines(&event, t, wpm) {
+            writeln!(stdout, "{line}").unwrap();
         }
-        _ => {
-            eprintln!("The pipewire service is not active. Checking installation ...");
-            let program_check = Command::new("pipewire").arg("--version").output();
-            match program_check {
-                Ok(output) if output.status.success() => {
-                    eprintln!(
-                        "pipewire is installed, but the service is not active. Please start it using: 'systemctl --user start pipewire'"
-                    );
-                }
-                _ => {
-                    eprintln!("pipewire is not installed. Please install it to proceed.");
-                }
-            }
+        stdout.flush().unwrap();
+    }
+    match event {
+        DecodeEvent::Message(msg) => {
+            write_record(receiver, &msg);
+            receiver.summary.add(&Message {
+                timestamp: Local::now().format("%y-%m-%d %H:%M:%S %p").to_string(),
+                content: msg,
+                wpm,
+            });
+        }
+        DecodeEvent::DotEstimate(dot) => receiver.dot_estimate = Some(dot),
+        _ => {}
+    }
+}
 
-            std::process::exit(1);
+#[cfg(target_os = "linux")]
+fn display_event(
+    display: Display,
+    receivers: &mut [Receiver],
+    index: usize,
+    event: DecodeEvent,
+    output_morse: bool,
+) {
+    // The alerts ring whatever is shown, on a terminal:
+    if let Some(alerts) = receivers[index].alerts.as_mut() {
+        let ring = match &event {
+            DecodeEvent::Partial(msg) => alerts.partial(msg),
+            DecodeEvent::Message(msg) => alerts.message(msg),
+            _ => false,
+        };
+        if ring && !display.is_machine() && io::stdout().is_terminal() {
+            print!("\x07");
+            io::stdout().flush().unwrap();
+        }
+    }
+    let receiver = &receivers[index];
+    if let (Some(notifier), DecodeEvent::Message(msg)) = (&receiver.notifier, &event) {
+        notifier.send(Note {
+            station: receiver.label.clone(),
+            message: msg.clone(),
+            wpm: receiver.dot_estimate.map(dot_length_to_wpm),
+            alert: receiver.alerts.as_ref().is_some_and(|a| a.is_match(msg)),
+        });
+    }
+    let receiver = &mut receivers[index];
+    let tuning = tuning(receiver);
+    let wpm = receiver.dot_estimate.map(dot_length_to_wpm);
+    if let DecodeEvent::Message(msg) = &event {
+        let message = Message {
+            timestamp: Local::now().format("%y-%m-%d %H:%M:%S %p").to_string(),
+            content: match output_morse {
+                true => text_to_morse(msg),
+                false => msg.clone(),
+            },
+            wpm,
+        };
+        let label = receiver.label.as_deref();
+        receiver.session.lock().unwrap().push(label, message);
+    }
+    if let Some(serving) = receiver.serving.as_mut() {
+        serving.tune(tuning.clone());
+        serving.event(&event, wpm);
+    }
+    if let (Some(mqtt), DecodeEvent::Level(level)) = (receiver.mqtt.as_mut(), &event) {
+        mqtt.level(receiver.label.as_deref(), level, wpm);
+    }
+    if let Some(spotter) = receiver.spotter.as_mut() {
+        match &event {
+            DecodeEvent::Level(level) => spotter.level(level),
+            DecodeEvent::Message(msg) => {
+                spotter.message(msg, wpm, tuning.clone(), receiver.label.as_deref());
+            }
+            _ => {}
+        }
+    }
+    if let (Some(logbook), DecodeEvent::Message(msg)) = (&receiver.logbook, &event) {
+        logbook.message(msg, tuning.as_ref());
+    }
+    if let Some(fldigi) = receiver.fldigi.as_mut() {
+        match &event {
+            DecodeEvent::Partial(msg) => fldigi.partial(msg),
+            DecodeEvent::Message(msg) => fldigi.message(msg),
+            _ => {}
+        }
+    }
+    if let Some(typist) = receiver.typist.as_mut() {
+        match &event {
+            DecodeEvent::Partial(msg) => typist.partial(msg),
+            DecodeEvent::Message(msg) => typist.message(msg),
+            _ => {}
+        }
+    }
+    if let Some(speaker) = receivers[index].speaker.as_mut() {
+        match &event {
+            DecodeEvent::Partial(msg) => speaker.partial(msg),
+            DecodeEvent::Message(msg) => speaker.message(msg),
+            _ => {}
         }
     }
+    match display {
+        // Kept up with while the scrollback is shown, to draw it after:
+        Display::Text => pane_event(receivers, index, event, output_morse),
+        Display::Waterfall | Display::Scan if receivers[index].scrolled => {
+            quiet_event(&mut receivers[index], display, event, output_morse)
+        }
+        Display::Scan => {
+            let shown = matches!(event, DecodeEvent::Partial(_) | DecodeEvent::Message(_));
+            quiet_event(&mut receivers[index], display, event, output_morse);
+            if shown {
+                redraw(receivers);
+            }
+        }
+        Display::Waterfall => show_waterfall_event(receivers, index, event, output_morse),
+        Display::Plain => plain_event(receivers, index, event, output_morse),
+        Display::Accessible => accessible_event(receivers, index, event, output_morse),
+        Display::Records => record_event(receivers, index, event, output_morse),
+        Display::Events => json_event(receivers, index, event),
+    }
 }
 
-#[cfg(target_os = "windows")]
-pub fn listen(
-    _tone_freq: f32,
-    _bandwidth: f32,
-    _threshold: f32,
-    _dot_duration: u32,
-    _output_morse: bool,
-) -> Result<(), std::io::Error> {
-    return Ok(());
+/// What the radio is tuned to, for the messages and events of a
+/// station, with --rigctl or --fldigi-poll.
+#[cfg(target_os = "linux")]
+fn tuning(receiver: &Receiver) -> Option<Tuning> {
+    match (&receiver.rig, &receiver.fldigi) {
+        (Some(rig), _) => Some(rig.tuning(receiver.pipeline.tone_freq())),
+        (None, Some(fldigi)) => fldigi.tuning(),
+        (None, None) => None,
+    }
+}
+
+/// Appends a message of a station to the transcript, and sends it to
+/// the --udp-target and the --mqtt broker, if there are.
+#[cfg(target_os = "linux")]
+fn write_record(receiver: &Receiver, message: &str) {
+    if receiver.transcript.is_none() && receiver.udp.is_none() && receiver.mqtt.is_none() {
+        return;
+    }
+    let record = Record {
+        time: Local::now(),
+        station: receiver.label.clone(),
+        message: message.to_string(),
+        wpm: receiver.dot_estimate.map(dot_length_to_wpm),
+        tone: receiver.pipeline.tone_freq(),
+        rig: tuning(receiver),
+    };
+    if let Some(udp) = &receiver.udp {
+        udp.send(&record);
+    }
+    if let Some(mqtt) = &receiver.mqtt {
+        mqtt.message(&record);
+    }
+    let Some(transcript) = &receiver.transcript else {
+        return;
+    };
+    if let Err(e) = transcript.lock().unwrap().write(&record) {
+        error!("Could not write to the log file: {e}");
+    }
 }
 
+/// Listens to PipeWire audio and decodes the morse code heard.
+///
+/// Each station is decoded separately, from its own channel, with the
+/// settings of `pipeline` but for the tone. With `outputs.scan`, the
+/// stations are the channels of the scan instead, all decoded from the
+/// samples of the first one.
 #[cfg(target_os = "linux")]
 pub fn listen(
-    tone_freq: f32,
-    bandwidth: f32,
-    threshold: f32,
-    dot_duration: u32,
-    output_morse: bool,
+    target: Option<String>,
+    stations: Vec<Station>,
+    pipeline: PipelineOptions,
+    ring_capacity: usize,
+    display: DisplayOptions,
+    outputs: ListenOutputs,
+    session: SessionOptions,
 ) -> Result<(), pipewire::Error> {
+    // The tone of each station is its own:
+    let PipelineOptions {
+        bandwidth,
+        threshold,
+        debounce_duration,
+        detector,
+        dot_duration,
+        timeout_duration,
+        adaptive_speed,
+        text_options,
+        ..
+    } = pipeline;
+    let DisplayOptions {
+        output,
+        verbose_events,
+        plain,
+        waterfall,
+        meter,
+        monitor,
+        output_morse,
+    } = display;
+    let ListenOutputs {
+        scan,
+        passthrough,
+        record,
+        transcript,
+        alerts,
+        notify,
+        speaker,
+        server,
+        udp,
+        mqtt,
+        rig,
+        spotter,
+        logbook,
+        fldigi,
+        mut typist,
+    } = outputs;
     pw::init();
     let mainloop = MainLoop::new(None)?;
     let context = Context::new(&mainloop)?;
     let core = context.connect(None)?;
 
+    // The channels of a scan share the samples:
+    let channels: Vec<Channel> = match scan {
+        Some(_) => vec![stations[0].channel],
+        None => stations.iter().map(|s| s.channel).collect(),
+    };
+    let (producer, consumer) = ring_buffer(ring_capacity * channels.len());
+    let sample_rate = Arc::new(AtomicU32::new(0));
+    // The frames of --record have every channel, with room for stereo:
+    let capture_channels = Arc::new(AtomicU32::new(0));
+    let (recording_producer, recording) = match record {
+        Some(record) if !record.filtered => {
+            let (producer, consumer) = ring_buffer(ring_capacity * 2);
+            (Some(producer), Some((record, Some(consumer))))
+        }
+        Some(record) => (None, Some((record, None))),
+        None => (None, None),
+    };
+    let recording = recording.map(|(record, consumer)| Recording {
+        recorder: Recorder::new(record),
+        consumer,
+        channels: capture_channels.clone(),
+        samples: Vec::new(),
+    });
+    let display = match &transcript {
+        _ if output == OutputFormat::Json => Display::Events,
+        Some(transcript) if transcript.is_stdout() => Display::Records,
+        _ if crate::term::accessible() => Display::Accessible,
+        _ if plain || !io::stdout().is_terminal() => Display::Plain,
+        _ if scan.is_some() => Display::Scan,
+        _ if waterfall => Display::Waterfall,
+        _ => Display::Text,
+    };
+    let transcript = transcript.map(|transcript| Arc::new(Mutex::new(transcript)));
+    let notifier = notify.then(Notifier::start);
+    let udp = udp.map(Arc::new);
+    // The page of --serve ws: shows a meter:
+    let serve_levels = server
+        .as_ref()
+        .is_some_and(|server| server.protocol == Protocol::WebSocket);
     let data = UserData {
         format: Default::default(),
-        filter: None,
-        message_log: Vec::new(),
+        raw_format: RawFormat::F32LE,
+        input_frame: Vec::new(),
+        producer,
+        frame: vec![0.0; channels.len()],
+        sample_rate: sample_rate.clone(),
+        recording: recording_producer,
+        capture_channels,
+    };
+    let options = session;
+    let session = Arc::new(Mutex::new(SessionLog::new(options.scrollback)));
+    let panes = (display == Display::Text).then(|| {
+        let screen = match listen_screen() {
+            Ok(screen) => screen,
+            Err(e) => {
+                error!("Could not draw the screen: {e}");
+                std::process::exit(1);
+            }
+        };
+        let view = ListenView::new(stations.iter().map(|s| s.label.clone()).collect())
+            .with_alerts(vec![alerts.clone(); stations.len()]);
+        Arc::new(Mutex::new(Panes { view, screen }))
+    });
+    let mut receivers: Vec<Receiver> = stations
+        .into_iter()
+        .enumerate()
+        .map(|(index, station)| Receiver {
+            tone_arg: match station.label.as_deref() {
+                Some("L:") => "tone-freq-left",
+                Some("R:") => "tone-freq-right",
+                _ => "tone",
+            },
+            // Before the label is moved:
+            events: (display == Display::Events).then(|| {
+                EventStream::new(station.label.as_deref(), verbose_events)
+                    .with_levels(verbose_events)
+            }),
+            serving: server
+                .as_ref()
+                .map(|server| Serving::new(server, station.label.as_deref())),
+            label: station.label,
+            pipeline: SignalPipeline::new(PipelineOptions {
+                tone_freq: station.tone_freq,
+                tone_range: station.tone_range,
+                bandwidth,
+                threshold,
+                debounce_duration,
+                detector,
+                dot_duration,
+                timeout_duration,
+                adaptive_speed,
+                text_options: text_options.clone(),
+            })
+            .with_levels(
+                display == Display::Accessible
+                    || (display.redraws() && (meter || monitor.is_some()))
+                    || (display == Display::Events && verbose_events)
+                    || serve_levels
+                    // For the status of --mqtt, and the SNR of --spot:
+                    || mqtt.is_some()
+                    || spotter.is_some(),
+            )
+            // The audio of --monitor-audio and --record-filtered is the
+            // first station's:
+            .with_tap(
+                index == 0
+                    && (passthrough.is_some()
+                        || recording.as_ref().is_some_and(|r| r.consumer.is_none())),
+            ),
+            message_log: Vec::new(),
+            partial: String::new(),
+            dot_estimate: None,
+            level: None,
+            levels_shown: 0,
+            meter: meter.then(LevelMeter::new),
+            monitor: monitor.map(KeyingMonitor::new),
+            settings: false,
+            summary: Summary::default(),
+            session: session.clone(),
+            panes: panes.clone(),
+            scrolled: false,
+            scanning: scan.is_some(),
+            narrator: Narrator::default(),
+            alerts: alerts.clone(),
+            notifier: notifier.clone(),
+            speaker: speaker.clone(),
+            udp: udp.clone(),
+            mqtt: mqtt.clone(),
+            rig: rig.clone(),
+            spotter: spotter.clone(),
+            logbook: logbook.clone(),
+            fldigi: fldigi.clone(),
+            typist: typist.take(),
+            transcript: transcript.clone(),
+        })
+        .collect();
+
+    // Without a target, capture the monitor of the default sink:
+    let mut capture_sink = true;
+    let target_node = match target {
+        Some(target) => {
+            let nodes = audio_nodes(&mainloop, &core)?;
+            match find_target(&nodes, &target) {
+                Some(node) => {
+                    capture_sink = node.media_class == "Audio/Sink";
+                    Some(node.name.clone())
+                }
+                None => {
+                    error!("PipeWire node not found: {target}");
+                    error!("Use 'listen --list-targets' to see the available nodes.");
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Playing the filtered audio to the sink that is captured would feed
+    // it back, and every tone would go on ringing:
+    let muted = Arc::new(AtomicBool::new(false));
+    let passthrough = match passthrough {
+        Some(mut options) => {
+            if let Some(sink) = options.target.take() {
+                let nodes = audio_nodes(&mainloop, &core)?;
+                match find_target(&nodes, &sink) {
+                    Some(node) => options.target = Some(node.name.clone()),
+                    None => {
+                        error!("PipeWire node not found: {sink}");
+                        error!("Use 'listen --list-targets' to see the available nodes.");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            // Either may be the default sink, by its name or by leaving it out:
+            let default = if capture_sink {
+                default_sink(&mainloop, &core)?
+            } else {
+                None
+            };
+            if let Some(reason) = feedback(
+                target_node.as_deref(),
+                capture_sink,
+                options.target.as_deref(),
+                default.as_deref(),
+            ) {
+                if !options.force {
+                    error!("{reason}, which would feed back.");
+                    error!("Give --monitor-audio another sink, or --force to play it anyway.");
+                    std::process::exit(1);
+                }
+                warn!("{reason}, which may feed back.");
+            }
+            Some(Passthrough::new(options, muted.clone()))
+        }
+        None => None,
     };
 
-    let props = properties!(
+    let mut props = properties!(
         *pw::keys::MEDIA_TYPE => "Audio",
         *pw::keys::MEDIA_CATEGORY => "Capture",
         *pw::keys::MEDIA_ROLE => "Communication",
-        *pw::keys::STREAM_CAPTURE_SINK => "true"
+        *pw::keys::STREAM_CAPTURE_SINK => if capture_sink { "true" } else { "false" }
     );
+    if let Some(node_name) = target_node {
+        props.insert(*pw::keys::TARGET_OBJECT, node_name);
+    }
 
     let stream = pw::stream::Stream::new(&core, "audio-capture", props)?;
 
-    let mut decoder = Decoder::<9999>::new()
-        .with_reference_short_ms(dot_duration as u16)
-        .build();
-    let mut last_signal_change = Instant::now();
-    let mut last_signal_state = false;
-    let whitespace_regex = Regex::new(r"\s+").unwrap();
-
-    clear_screen();
+    if display.redraws() {
+        redraw(&receivers);
+    } else if display == Display::Accessible {
+        println!("Listening.");
+        if threshold.is_none() {
+            println!("Calibration starting.");
+        }
+    }
 
+    let param_channels = channels.clone();
     let _listener = stream
         .add_local_listener_with_user_data(data)
         .param_changed(move |_, user_data, id, param| {
@@ -123,16 +2326,21 @@ pub fn listen(
             if id != pw::spa::param::ParamType::Format.as_raw() {
                 return;
             }
-            user_data.format.parse(param).unwrap();
-            user_data.filter = Some(
-                BandpassFilter::new(
-                    5,
-                    tone_freq.into(),
-                    bandwidth.into(),
-                    user_data.format.rate() as f64,
-                )
-                .expect("expected filter"),
-            );
+            let (raw_format, channels) = capture_format(&mut user_data.format, param);
+            user_data.raw_format = raw_format;
+            user_data.input_frame = vec![0.0; channels];
+            for channel in &param_channels {
+                if let Err(e) = channel.check(user_data.format.channels() as usize) {
+                    error!("{e}");
+                    std::process::exit(1);
+                }
+            }
+            user_data
+                .capture_channels
+                .store(channels as u32, Ordering::Release);
+            user_data
+                .sample_rate
+                .store(user_data.format.rate(), Ordering::Release);
         })
         .process(move |stream, user_data| match stream.dequeue_buffer() {
             None => println!("Out of buffers"),
@@ -143,98 +2351,27 @@ pub fn listen(
                 }
 
                 let data = &mut datas[0];
-                let n_channels = user_data.format.channels();
+                let frame_size = user_data.raw_format.width() * user_data.input_frame.len();
+                // Only part of the buffer may have been filled:
+                let size = data.chunk().size() as usize;
                 if let Some(samples) = data.data() {
-                    let float_samples: &mut [f32] = bytemuck::cast_slice_mut(samples);
-
-                    for c in 0..n_channels {
-                        let mut max: f32 = 0.0;
-                        let mut sum = 0.0_f32;
-                        let mut count = 0;
-
-                        let channel_samples: Vec<f64> = float_samples
-                            .iter()
-                            .skip(c.try_into().expect("Invalid skip in float_samples"))
-                            .step_by(n_channels as usize)
-                            .map(|&s| s as f64)
-                            .collect();
-
-                        let filtered_samples = channel_samples;
-
-                        // Find peak and average sample
-                        for &sample in &filtered_samples {
-                            let abs_sample = sample.abs() as f32;
-                            max = max.max(abs_sample);
-                            sum += abs_sample;
-                            count += 1;
+                    let size = size.min(samples.len());
+                    // Only copy the samples of each station's channel here,
+                    // the decoding thread does the rest:
+                    for frame in samples[..size].chunks_exact(frame_size.max(1)) {
+                        let input_frame = &mut user_data.input_frame;
+                        user_data.raw_format.convert(frame, input_frame);
+                        for (sample, channel) in user_data.frame.iter_mut().zip(&channels) {
+                            *sample = channel.sample(input_frame);
                         }
-                        let average = if count > 0 { sum / count as f32 } else { 0.0 } * 30.0;
-                        //println!("{average}");
-                        let tone_detected = average as f32 > threshold;
-                        let timeout_duration = 20 * dot_duration;
-                        let now = Instant::now();
-                        let duration = now.duration_since(last_signal_change).as_millis() as u32;
-
-                        // Detect message characters:
-                        if tone_detected != last_signal_state {
-                            decoder.signal_event(duration as u16, last_signal_state);
-                            let mut msg = decoder.message.as_str().to_string();
-                            msg = whitespace_regex.replace_all(&msg, " ").to_string();
-
-                            if !msg.is_empty() {
-                                clear_screen();
-                                // Print all previous messages with timestamp
-                                for logged_msg in &user_data.message_log {
-                                    log_message(logged_msg);
-                                }
-                                // Print the current message as it is received:
-                                if output_morse {
-                                    println!("{}", text_to_morse(&msg));
-                                } else {
-                                    println!("{msg}");
-                                }
+                        if let Some(recording) = user_data.recording.as_mut() {
+                            if !recording.push_frame(input_frame) {
+                                recording.overrun();
                             }
-
-                            last_signal_change = now;
-                            last_signal_state = tone_detected;
                         }
-
-                        // Detect message end:
-                        if duration > timeout_duration {
-                            last_signal_change = now;
-                            last_signal_state = false;
-                            let mut msg = decoder.message.as_str().to_string();
-                            msg = whitespace_regex.replace_all(&msg, " ").to_string();
-
-                            if !msg.is_empty() {
-                                decoder.signal_event_end(false);
-                                decoder.signal_event_end(true);
-                                msg = decoder.message.as_str().to_string();
-                                msg = whitespace_regex.replace_all(&msg, " ").to_string();
-
-                                clear_screen();
-                                // Print all previous messages with timestamp
-                                for logged_msg in &user_data.message_log {
-                                    log_message(logged_msg);
-                                }
-                                // Get the current timestamp
-                                let timestamp =
-                                    Local::now().format("%y-%m-%d %H:%M:%S %p").to_string();
-                                // Print the new message and add it to the log
-                                let mut m = Message {
-                                    timestamp: timestamp.clone(),
-                                    content: msg.clone(),
-                                };
-                                if output_morse {
-                                    m.content = text_to_morse(&m.content);
-                                }
-                                log_message(&m);
-
-                                // Push the complete message into the log
-                                user_data.message_log.push(m);
-                                // Clear the decoder to prepare for a new message:
-                                decoder.message.clear();
-                            }
+                        if !user_data.producer.push_frame(&user_data.frame) {
+                            user_data.producer.overrun();
+                            break;
                         }
                     }
                 }
@@ -242,21 +2379,7 @@ pub fn listen(
         })
         .register()?;
 
-    let mut audio_info = spa::param::audio::AudioInfoRaw::new();
-    audio_info.set_format(spa::param::audio::AudioFormat::F32LE);
-    let obj = pw::spa::pod::Object {
-        type_: pw::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
-        id: pw::spa::param::ParamType::EnumFormat.as_raw(),
-        properties: audio_info.into(),
-    };
-    let values: Vec<u8> = pw::spa::pod::serialize::PodSerializer::serialize(
-        std::io::Cursor::new(Vec::new()),
-        &pw::spa::pod::Value::Object(obj),
-    )
-    .unwrap()
-    .0
-    .into_inner();
-
+    let values = capture_formats();
     let mut params = [Pod::from_bytes(&values).unwrap()];
 
     stream.connect(
@@ -268,6 +2391,367 @@ pub fn listen(
         &mut params,
     )?;
 
+    // The first Ctrl-C (or SIGTERM) ends the session, after the message
+    // being received and the summary are shown. Another one soon after
+    // exits at once, in case that hangs:
+    let stop = Arc::new(AtomicBool::new(false));
+    let interrupted: Rc<Cell<Option<Instant>>> = Rc::new(Cell::new(None));
+    let on_signal = || {
+        let (stop, interrupted) = (stop.clone(), interrupted.clone());
+        move || match interrupted.get() {
+            Some(at) if at.elapsed() < FORCE_QUIT => {
+                let _ = crossterm::terminal::disable_raw_mode();
+                std::process::exit(130);
+            }
+            _ => {
+                interrupted.set(Some(Instant::now()));
+                stop.store(true, Ordering::Release);
+            }
+        }
+    };
+    let _sigint = mainloop
+        .loop_()
+        .add_signal_local(pw::loop_::Signal::SIGINT, on_signal());
+    let _sigterm = mainloop
+        .loop_()
+        .add_signal_local(pw::loop_::Signal::SIGTERM, on_signal());
+
+    // The keys only work with the status line, to show what they did:
+    let controls = match display.redraws() {
+        true => Controls::start(bandwidth, passthrough.as_ref().map(|_| muted)),
+        false => None,
+    };
+    // The tones of a scan can't be changed:
+    for receiver in receivers.iter_mut() {
+        receiver.settings = controls.is_some() && scan.is_none();
+    }
+    let view = (display == Display::Waterfall).then(WaterfallView::new);
+    let bank = scan.as_ref().map(ScanBank::new);
+    std::thread::spawn(move || {
+        decode_thread(
+            receivers,
+            consumer,
+            sample_rate,
+            output_morse,
+            display,
+            controls,
+            view,
+            bank,
+            passthrough,
+            recording,
+            stop,
+            options,
+        )
+    });
+
     mainloop.run();
     Ok(())
 }
+
+/// Finishes the message being received, and exits with a summary of
+/// the session.
+#[cfg(target_os = "linux")]
+fn end_session(
+    mut receivers: Vec<Receiver>,
+    controls: Option<Controls>,
+    display: Display,
+    output_morse: bool,
+    started: Instant,
+    options: SessionOptions,
+    recording: Option<Recording>,
+) -> ! {
+    // Back from the scrollback, for the summary:
+    if receivers[0].scrolled {
+        for receiver in receivers.iter_mut() {
+            receiver.scrolled = false;
+        }
+        if let Some(panes) = &receivers[0].panes {
+            let _ = panes.lock().unwrap().screen.clear();
+        }
+        redraw(&receivers);
+    }
+    for index in 0..receivers.len() {
+        for event in receivers[index].pipeline.finish() {
+            display_event(display, &mut receivers, index, event, output_morse);
+        }
+    }
+    // Leave raw mode:
+    drop(controls);
+    // The summary is kept out of the records on stdout:
+    let records = display.is_machine();
+    if let Some(panes) = &receivers[0].panes {
+        // Under the panes, which stay on the screen:
+        if let Err(e) = leave(&mut panes.lock().unwrap().screen) {
+            warn!("Could not draw the screen: {e}");
+        }
+    }
+    if display.redraws() {
+        println!();
+    }
+    for receiver in &receivers {
+        // The channels of a scan that heard nothing are left out:
+        if receiver.scanning && receiver.summary.messages == 0 {
+            continue;
+        }
+        let label = match &receiver.label {
+            Some(label) => format!("{label} "),
+            None => String::new(),
+        };
+        let summary = receiver.summary.report(started.elapsed());
+        match records {
+            true => eprintln!("{label}{summary}"),
+            false => println!("{label}{summary}"),
+        }
+        // How to start with the settings changed with the keys:
+        if receiver.settings {
+            println!(
+                "{label}settings: {}",
+                arguments(&receiver.pipeline, receiver.tone_arg)
+            );
+        }
+    }
+    let mut told = Vec::new();
+    if let Some(what) = options.copy_on_exit {
+        told.push(copy_session(
+            &receivers[0].session,
+            what == CopyOnExit::Message,
+        ));
+    }
+    let session = receivers[0].session.lock().unwrap();
+    if let Some(path) = &options.dump {
+        told.push(match std::fs::write(path, session.transcript()) {
+            Ok(()) => format!("Session written to {}", path.display()),
+            Err(e) => format!("Could not write the session to {}: {e}", path.display()),
+        });
+    }
+    if session.dropped() > 0 && (options.copy_on_exit.is_some() || options.dump.is_some()) {
+        told.push(format!(
+            "The {} first messages were dropped, beyond --scrollback {}",
+            session.dropped(),
+            options.scrollback
+        ));
+    }
+    drop(session);
+    for told in told {
+        match records {
+            true => eprintln!("{told}"),
+            false => println!("{told}"),
+        }
+    }
+    // The contacts are confirmed now, as asking while decoding would
+    // fight with the screen:
+    if let Some(logbook) = receivers[0].logbook.as_ref().filter(|l| !l.is_empty()) {
+        let logged = match records {
+            true => logbook.finish(io::stderr()),
+            false => logbook.finish(io::stdout()),
+        };
+        match logged {
+            Ok(count) => info!("Logged {count} contacts"),
+            Err(e) => error!("Could not log the contacts: {e}"),
+        }
+    }
+    if let Some(recording) = recording {
+        recording.finish();
+    }
+    std::process::exit(0);
+}
+
+/// Drains the ring buffer, decoding and printing the received samples.
+#[cfg(target_os = "linux")]
+#[allow(clippy::too_many_arguments)]
+fn decode_thread(
+    mut receivers: Vec<Receiver>,
+    mut consumer: Consumer,
+    sample_rate: Arc<AtomicU32>,
+    output_morse: bool,
+    display: Display,
+    mut controls: Option<Controls>,
+    mut view: Option<WaterfallView>,
+    mut bank: Option<ScanBank>,
+    mut passthrough: Option<Passthrough>,
+    mut recording: Option<Recording>,
+    stop: Arc<AtomicBool>,
+    options: SessionOptions,
+) {
+    let started = Instant::now();
+    let stations = receivers.len();
+    // The channels of a scan share one column of the frames:
+    let columns = match bank {
+        Some(_) => 1,
+        None => stations,
+    };
+    let mut frames = vec![0.0; 1024 * columns];
+    let mut samples = vec![0.0; 1024];
+    let mut reported_overruns = 0;
+    if let Some(view) = &view {
+        print!("{}\r\n", view.waterfall.scale());
+    }
+    let mut size = term_size::dimensions();
+    loop {
+        let scrolled = receivers[0].scrolled;
+        if controls.as_mut().is_some_and(|c| c.read(&mut receivers)) || stop.load(Ordering::Acquire)
+        {
+            end_session(
+                receivers,
+                controls,
+                display,
+                output_morse,
+                started,
+                options,
+                recording,
+            );
+        }
+        // Back from the scrollback:
+        if scrolled && !receivers[0].scrolled {
+            resize(&mut receivers, &mut view);
+        }
+        if display.redraws() && term_size::dimensions() != size {
+            size = term_size::dimensions();
+            if !controls.as_mut().is_some_and(Controls::resized) {
+                resize(&mut receivers, &mut view);
+            }
+        }
+        let rate = sample_rate.load(Ordering::Acquire);
+        if let Some(Err(e)) = recording.as_mut().map(|r| r.record(rate)) {
+            error!("Could not record: {e}");
+            recording = None;
+        }
+        let count = consumer.pop_slice(&mut frames);
+        if count == 0 {
+            std::thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        let overruns = consumer.overruns();
+        if overruns != reported_overruns {
+            reported_overruns = overruns;
+            let note = format!("ring buffer full, samples dropped ({overruns} overruns)");
+            match display.redraws() {
+                false => eprintln!("{note}"),
+                true => tell(&receivers, note),
+            }
+        }
+        if let Some(bank) = bank.as_mut() {
+            for levels in bank.push(&frames[..count], rate) {
+                for (index, level) in levels.into_iter().enumerate() {
+                    for event in receivers[index]
+                        .pipeline
+                        .push_level(level, bank.window(), rate)
+                    {
+                        display_event(display, &mut receivers, index, event, output_morse);
+                    }
+                }
+            }
+            continue;
+        }
+        for index in 0..stations {
+            // Pick this station's samples out of the frames:
+            samples.clear();
+            samples.extend(frames[..count].iter().skip(index).step_by(stations));
+            for event in receivers[index].pipeline.push_samples(&samples, rate) {
+                display_event(display, &mut receivers, index, event, output_morse);
+            }
+            if index == 0 {
+                let pipeline = &mut receivers[index].pipeline;
+                let tapped = pipeline.take_tap();
+                if let Some(passthrough) = passthrough.as_mut() {
+                    passthrough.play(&tapped, rate, pipeline.tone_freq());
+                }
+                if let Some(Err(e)) = recording.as_mut().map(|r| r.record_filtered(&tapped, rate)) {
+                    error!("Could not record: {e}");
+                    recording = None;
+                }
+            }
+            if let Some(view) = view.as_mut().filter(|_| !receivers[index].scrolled) {
+                view.draw(&samples, rate, receivers[index].pipeline.tone_freq());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u32, name: &str, media_class: &str) -> AudioNode {
+        AudioNode {
+            id,
+            name: name.to_string(),
+            description: String::new(),
+            media_class: media_class.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_target() {
+        let nodes = vec![
+            node(
+                42,
+                "alsa_output.pci-0000_00_1f.3.analog-stereo",
+                "Audio/Sink",
+            ),
+            node(57, "sdr-sink", "Audio/Sink"),
+            node(63, "alsa_input.usb-mic", "Audio/Source"),
+        ];
+        assert_eq!(find_target(&nodes, "sdr-sink"), Some(&nodes[1]));
+        assert_eq!(find_target(&nodes, "63"), Some(&nodes[2]));
+        assert_eq!(find_target(&nodes, "99"), None);
+        assert_eq!(find_target(&nodes, "missing"), None);
+    }
+
+    #[test]
+    fn test_metadata_name() {
+        assert_eq!(
+            metadata_name(r#"{"name":"alsa_output.pci-0000_00_1f.3.analog-stereo"}"#).as_deref(),
+            Some("alsa_output.pci-0000_00_1f.3.analog-stereo")
+        );
+        assert_eq!(
+            metadata_name(r#"{ "name": "sdr-sink" }"#).as_deref(),
+            Some("sdr-sink")
+        );
+        assert_eq!(metadata_name("{}"), None);
+    }
+
+    fn convert(format: RawFormat, bytes: &[u8]) -> Vec<f32> {
+        let mut out = vec![0.0; bytes.len() / format.width()];
+        format.convert(bytes, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_convert_f32le() {
+        let bytes: Vec<u8> = [0.5_f32, -0.25]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert_eq!(convert(RawFormat::F32LE, &bytes), vec![0.5, -0.25]);
+    }
+
+    #[test]
+    fn test_convert_s16le() {
+        let bytes: Vec<u8> = [16384_i16, -32768, 0]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert_eq!(convert(RawFormat::S16LE, &bytes), vec![0.5, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_convert_s24_32le() {
+        // The top byte is padding, and may hold anything:
+        let bytes = [
+            0x00, 0x00, 0x40, 0x00, // 0.5
+            0x00, 0x00, 0x80, 0xff, // -1.0
+            0x00, 0x00, 0xc0, 0x12, // -0.5, with junk padding
+        ];
+        assert_eq!(convert(RawFormat::S24_32LE, &bytes), vec![0.5, -1.0, -0.5]);
+    }
+
+    #[test]
+    fn test_convert_s32le() {
+        let bytes: Vec<u8> = [1_073_741_824_i32, i32::MIN]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert_eq!(convert(RawFormat::S32LE, &bytes), vec![0.5, -1.0]);
+    }
+}
@@ -0,0 +1,1712 @@
+use crate::abbreviations::{Direction, Meanings};
+use crate::answer::AnswerSource;
+use crate::band::Band;
+use crate::callsign::random_callsign;
+use crate::codec::{encode, Separators};
+use crate::contest::Exchange;
+use crate::export::{timestamp, wall_clock, Trial};
+use crate::fecr_quiz::QuizResult;
+use crate::feedback::{self, Feedback};
+use crate::input::{reaction, Copied, Flow};
+use crate::interference::Interferer;
+use crate::keyer::{Keyer, Mixer, Timing, SAMPLE_RATE};
+use crate::morse::{
+    char_to_morse, dot_length_to_wpm, wpm_to_dot_length, Charset, MorsePlayer, PROSIGNS,
+};
+use crate::prelude::*;
+use crate::qso::{copied_words, diff_words, WordDiff};
+use crate::rufz::Ladder;
+use crate::score::edit_distance;
+use crate::stats::{character_stats, CharacterStats};
+use crate::tui::{practice_screen, PracticeView};
+use crate::words::WordPicker;
+use crossterm::event::KeyCode;
+use crossterm::execute;
+use crossterm::terminal::{Clear, ClearType};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rodio::Sink;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{stdout, Write};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tabled::settings::style::Style;
+use tabled::{Table, Tabled};
+use textwrap::wrap;
+
+/// The punctuation that can be practiced.
+const PUNCTUATION: &str = ".,?!-/@()=";
+
+/// Finds the characters of a named set, e.g. "letters".
+fn named_set(name: &str, charset: Charset) -> Option<Vec<String>> {
+    let chars = |s: &str| s.chars().map(String::from).collect();
+    match name.to_lowercase().as_str() {
+        "letters" => Some(chars(
+            charset.characters().trim_end_matches(char::is_numeric),
+        )),
+        "digits" => Some(chars("0123456789")),
+        "punctuation" => Some(chars(PUNCTUATION)),
+        "prosigns" => Some(
+            PROSIGNS
+                .iter()
+                .map(|(prosign, _)| format!("<{prosign}>"))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Splits characters into the ones that are sent, a prosign written in
+/// angle brackets (e.g. `<AR>`) being one of them.
+pub fn split_characters(text: &str) -> Vec<String> {
+    let mut characters = Vec::new();
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '<' {
+            if let Some((name, rest)) = chars.as_str().split_once('>') {
+                characters.push(format!("<{}>", name.to_uppercase()));
+                chars = rest.chars();
+                continue;
+            }
+        }
+        characters.extend(ch.to_uppercase().map(String::from));
+    }
+    characters
+}
+
+/// Reads one item of a pool: a named set, a range like `A-Z`, or
+/// characters.
+fn pool_item(item: &str, charset: Charset) -> Result<Vec<String>, String> {
+    if let Some(set) = named_set(item, charset) {
+        return Ok(set);
+    }
+    let chars: Vec<char> = item.chars().collect();
+    let characters = match chars[..] {
+        [first, '-', last] if first != '-' && last != '-' => {
+            let first = first.to_uppercase().next().unwrap_or(first);
+            let last = last.to_uppercase().next().unwrap_or(last);
+            if first > last {
+                return Err(format!("Empty range: {item}"));
+            }
+            // Skip the characters in between that have no morse code:
+            return Ok((first..=last)
+                .filter(|&ch| char_to_morse(ch).is_some())
+                .map(String::from)
+                .collect());
+        }
+        _ => split_characters(item),
+    };
+    for character in &characters {
+        if encode(character, &Separators::default()).is_empty() {
+            return Err(format!("No morse code for {character:?}"));
+        }
+    }
+    Ok(characters)
+}
+
+/// Reads a pool of characters to practice, e.g. `letters,0-9,<AR>`.
+///
+/// The pool is a list of items separated by commas or spaces, each a
+/// named set (letters, digits, punctuation or prosigns), a range of
+/// characters, or just characters.
+pub fn parse_pool(spec: &str, charset: Charset) -> Result<Vec<String>, String> {
+    let mut characters = Vec::new();
+    for item in spec.split([',', ' ']).filter(|item| !item.is_empty()) {
+        for character in pool_item(item, charset)? {
+            if !characters.contains(&character) {
+                characters.push(character);
+            }
+        }
+    }
+    Ok(characters)
+}
+
+/// Leaves the characters of the `exclude` pool out of a pool.
+pub fn exclude(pool: Vec<String>, exclude: &str, charset: Charset) -> Result<Vec<String>, String> {
+    let excluded = parse_pool(exclude, charset)?;
+    let pool: Vec<String> = pool
+        .into_iter()
+        .filter(|character| !excluded.contains(character))
+        .collect();
+    if pool.is_empty() {
+        return Err("There are no characters to practice".to_string());
+    }
+    Ok(pool)
+}
+
+/// What is sent in a drill.
+pub enum Groups {
+    /// Random groups of characters from a pool, with the smallest and
+    /// largest number of characters in a group. The characters are
+    /// picked evenly, unless by weights (see `focus_weights`).
+    Random {
+        pool: Vec<String>,
+        size: (usize, usize),
+        weights: Option<WeightedIndex<f64>>,
+    },
+    /// Callsigns picked from a list, or made up without one.
+    Callsigns(Option<Vec<String>>),
+    /// Words, checked as a whole instead of by character.
+    Words(WordPicker),
+    /// Phrases of words, with the fewest and most words in a phrase, to
+    /// head copy: they are copied once played, word by word.
+    Phrases {
+        picker: WordPicker,
+        words: (usize, usize),
+    },
+    /// Contest exchanges, checked a whole word at a time.
+    Exchanges(Exchange),
+    /// The groups of a review (see `Groups::review`), with the words
+    /// among them, which are checked as a whole.
+    Review {
+        groups: VecDeque<Vec<String>>,
+        words: Vec<String>,
+    },
+    /// Groups sent in order, e.g. the words of a text of a drill script.
+    Sequence(VecDeque<Vec<String>>),
+}
+
+impl Groups {
+    /// Makes the groups to review some characters and words: the words
+    /// one at a time, and the characters in random groups of them.
+    pub fn review(items: &[String], size: (usize, usize), rng: &mut impl Rng) -> Self {
+        let (words, mut characters): (Vec<String>, Vec<String>) = items
+            .iter()
+            .cloned()
+            .partition(|item| split_characters(item).len() > 1);
+        characters.shuffle(rng);
+        let mut groups: Vec<Vec<String>> = Vec::new();
+        let mut characters = &characters[..];
+        while !characters.is_empty() {
+            let size = rng.gen_range(size.0..=size.1).min(characters.len());
+            groups.push(characters[..size].to_vec());
+            characters = &characters[size..];
+        }
+        groups.extend(words.iter().map(|word| split_characters(word)));
+        groups.shuffle(rng);
+        Groups::Review {
+            groups: groups.into(),
+            words,
+        }
+    }
+
+    /// How many groups are left, if there is a limit.
+    pub fn remaining(&self) -> Option<usize> {
+        match self {
+            Groups::Review { groups, .. } | Groups::Sequence(groups) => Some(groups.len()),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self, rng: &mut impl Rng) -> Vec<String> {
+        match self {
+            Groups::Random {
+                pool,
+                size,
+                weights,
+            } => (0..rng.gen_range(size.0..=size.1))
+                .map(|_| match weights {
+                    Some(weights) => pool[weights.sample(rng)].clone(),
+                    None => pool[rng.gen_range(0..pool.len())].clone(),
+                })
+                .collect(),
+            Groups::Callsigns(Some(list)) => split_characters(&list[rng.gen_range(0..list.len())]),
+            Groups::Callsigns(None) => split_characters(&random_callsign(rng)),
+            Groups::Words(picker) => split_characters(picker.pick(rng)),
+            Groups::Phrases { picker, words } => {
+                let phrase: Vec<&str> = (0..rng.gen_range(words.0..=words.1))
+                    .map(|_| picker.pick(rng))
+                    .collect();
+                split_characters(&phrase.join(" "))
+            }
+            Groups::Exchanges(exchange) => split_characters(&exchange.next(rng)),
+            Groups::Review { groups, .. } | Groups::Sequence(groups) => {
+                groups.pop_front().unwrap_or_default()
+            }
+        }
+    }
+
+    /// Whether the characters of a group are scored one by one, rather
+    /// than only the whole group.
+    fn by_character(&self, group: &[String]) -> bool {
+        match self {
+            Groups::Words(_) | Groups::Phrases { .. } | Groups::Exchanges(_) => false,
+            Groups::Review { words, .. } => !words.contains(&group.concat()),
+            _ => true,
+        }
+    }
+
+    /// Whether a group was copied exactly.
+    fn check(&self, group: &[String], copied: &str) -> bool {
+        match self {
+            Groups::Exchanges(exchange) => exchange.check(&group.concat(), copied),
+            Groups::Phrases { .. } => {
+                let copied: Vec<String> =
+                    copied.split_whitespace().map(str::to_uppercase).collect();
+                group
+                    .concat()
+                    .split_whitespace()
+                    .eq(copied.iter().map(String::as_str))
+            }
+            _ => split_characters(&copied.replace(char::is_whitespace, "")) == group,
+        }
+    }
+
+    /// Whether a group that was sent is a word.
+    pub fn is_word(&self, sent: &str) -> bool {
+        match self {
+            Groups::Words(_) => true,
+            Groups::Review { words, .. } => words.iter().any(|word| word == sent),
+            _ => false,
+        }
+    }
+
+    /// Tells whether a group was copied exactly.
+    fn copied(&mut self, group: &str, exact: bool) {
+        match self {
+            Groups::Words(picker) => picker.copied(group, exact),
+            Groups::Phrases { picker, .. } => {
+                for word in group.split_whitespace() {
+                    picker.copied(word, exact);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// How much weaker a character is, from its history: the share of its
+/// copies missed, plus half of how much slower it is copied than the
+/// mean reaction time.
+fn weakness(stats: &CharacterStats, mean_reaction: Duration) -> f64 {
+    let missed = 1.0 - stats.accuracy() / 100.0;
+    let slower = stats.reaction.as_secs_f64() / mean_reaction.as_secs_f64().max(0.001) - 1.0;
+    missed + slower.max(0.0) / 2.0
+}
+
+/// Weighs the characters of a pool by how weak they are, so that those
+/// copied badly or slowly are picked more often.
+///
+/// # Arguments
+/// * `history` - How each character was copied before, if it was.
+///   Those never copied are weighed like the weakest ones.
+/// * `mix` - The share (0 to 1) of the picks made by weakness, the rest
+///   being made evenly so that the strong characters still appear.
+///
+/// # Returns
+/// The chance of picking each character, adding up to 1.
+pub fn focus_weights(history: &[Option<CharacterStats>], mix: f64) -> Vec<f64> {
+    let known: Vec<&CharacterStats> = history.iter().flatten().collect();
+    let copies: u32 = known.iter().map(|stats| stats.count).sum();
+    let mean_reaction = known
+        .iter()
+        .map(|stats| stats.reaction * stats.count)
+        .sum::<Duration>()
+        / copies.max(1);
+    let weaknesses: Vec<Option<f64>> = history
+        .iter()
+        .map(|stats| stats.as_ref().map(|stats| weakness(stats, mean_reaction)))
+        .collect();
+    let weakest = weaknesses.iter().flatten().copied().fold(0.0, f64::max);
+    let weaknesses: Vec<f64> = weaknesses
+        .into_iter()
+        .map(|weakness| weakness.unwrap_or(weakest))
+        .collect();
+    let total: f64 = weaknesses.iter().sum();
+    let even = 1.0 / history.len().max(1) as f64;
+    weaknesses
+        .into_iter()
+        .map(|weakness| match total > 0.0 {
+            true => (1.0 - mix) * even + mix * weakness / total,
+            false => even,
+        })
+        .collect()
+}
+
+/// How the speed climbs during a drill.
+pub struct SpeedRamp {
+    pub start: u32,
+    pub end: u32,
+    // Climb this many WPM every this many groups, rather than smoothly
+    // over the whole drill:
+    pub steps: Option<(u32, u32)>,
+    // The accuracy in percent needed at a speed to keep climbing:
+    pub floor: Option<f64>,
+}
+
+impl SpeedRamp {
+    /// The speed after climbing for some groups, or some share (0 to 1)
+    /// of the drill.
+    fn wpm(&self, groups: u32, progress: f64) -> u32 {
+        match self.steps {
+            Some((step, every)) => (self.start + step * (groups / every)).min(self.end),
+            None => {
+                let climb = (self.end - self.start) as f64 * progress.min(1.0);
+                self.start + climb.round() as u32
+            }
+        }
+    }
+}
+
+/// How a missed group is played again, right after its answer is shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reveal {
+    /// Once, at the speed it was sent at.
+    Once,
+    /// Twice, slower.
+    Slow,
+}
+
+impl FromStr for Reveal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "once" => Ok(Reveal::Once),
+            "slow" => Ok(Reveal::Slow),
+            _ => Err(format!("Unknown reveal: {s}")),
+        }
+    }
+}
+
+impl Reveal {
+    /// The speed of each replay of a group sent at some speed.
+    fn speeds(&self, wpm: u32) -> Vec<u32> {
+        match self {
+            Reveal::Once => vec![wpm],
+            Reveal::Slow => vec![(wpm * 3 / 4).max(5); 2],
+        }
+    }
+}
+
+/// The accuracy in percent of the groups sent at a speed, if any were.
+fn speed_accuracy(groups: &[GroupCopy], wpm: u32) -> Option<f64> {
+    let accuracies: Vec<f64> = groups
+        .iter()
+        .filter(|group| group.wpm == wpm)
+        .map(|group| group.accuracy)
+        .collect();
+    match accuracies.is_empty() {
+        true => None,
+        false => Some(accuracies.iter().sum::<f64>() / accuracies.len() as f64 * 100.0),
+    }
+}
+
+/// The options of a code group drill.
+pub struct Drill {
+    pub groups: Groups,
+    // The drill ends after this many groups, or this long:
+    pub count: Option<u32>,
+    pub duration: Option<Duration>,
+    pub dot_duration: u32,
+    pub farnsworth: Option<u32>,
+    pub word_space: f32,
+    pub tone_freq: f32,
+    // The speed to send a missed group again at, before moving on:
+    pub resend: Option<u32>,
+    // Sends at a changing speed, instead of that of the dot duration:
+    pub ramp: Option<SpeedRamp>,
+    // Sends each group at a random speed of this range, in WPM, and at
+    // a random tone of this one, in Hz:
+    pub random_speed: Option<(u32, u32)>,
+    pub random_pitch: Option<(f32, f32)>,
+    // How each copy is told to be right or wrong:
+    pub feedback: Feedback,
+    // How many times the missed groups are sent again at the end, or 0:
+    pub review_passes: u32,
+    // How much slower they are sent again, in WPM:
+    pub review_speed_offset: u32,
+    // Shows the answer of a missed group and plays it again, for this
+    // long at least:
+    pub reveal: Option<(Reveal, Duration)>,
+    // Pauses the drill, Ctrl-C stopping it:
+    pub pause_key: KeyCode,
+    // A copy not finished this long after its sound is a miss:
+    pub answer_timeout: Option<Duration>,
+    // How many times a group is played until it is copied exactly:
+    pub attempts: u32,
+    // Whether a group may be copied while it is playing:
+    pub typeahead: bool,
+    // The noise, static crashes and fading to send it through:
+    pub band: Option<Band>,
+    // Another station sending at the same time, that is not copied:
+    pub interferer: Option<Interferer>,
+    // The meanings of the abbreviations drilled, shown with the answers,
+    // or in place of the code with Direction::Read:
+    pub abbreviations: Option<(Meanings, Direction)>,
+    // Steps the speed of each group up or down by how the last was
+    // copied, scoring the copies:
+    pub ladder: Option<Ladder>,
+    // Where the copies come from, typed or keyed:
+    pub answers: Box<dyn AnswerSource>,
+    // Draws the panes of the accuracy and of the latest groups over the
+    // prompt, on a terminal:
+    pub panes: bool,
+}
+
+/// How a whole group was copied.
+pub struct GroupCopy {
+    pub sent: String,
+    pub copied: String,
+    pub exact: bool,
+    /// How long it took to finish the copy, after the group was played,
+    /// over all of its attempts.
+    pub time: Duration,
+    pub wpm: u32,
+    pub tone: f32,
+    /// The share of its characters copied, or for words and exchanges
+    /// whether it was copied exactly.
+    pub accuracy: f64,
+    /// The attempt it was copied exactly on, or the last one.
+    pub attempt: u32,
+}
+
+/// How a character of a group was copied, over the attempts at it.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct CharacterCopy {
+    copy: Option<String>,
+    /// None when there was no answer in time.
+    reaction: Option<Duration>,
+    ahead: bool,
+    correct: bool,
+    /// The attempt it was copied on, or the last one.
+    attempt: u32,
+    /// The reaction times of all of its attempts, added up.
+    total: Duration,
+}
+
+impl CharacterCopy {
+    /// What was copied, and whether it was right, to color it.
+    fn mark(&self) -> (String, bool) {
+        (self.copy.clone().unwrap_or("_".to_string()), self.correct)
+    }
+}
+
+/// Scores another attempt at copying a group, for the characters that
+/// weren't copied yet.
+///
+/// # Arguments
+/// * `scores` - The score of each character in this attempt, see
+///   `score_group`.
+/// * `timed_out` - Whether there was no answer in time.
+fn copy_attempt(
+    characters: &mut [CharacterCopy],
+    sent: &[String],
+    scores: Vec<(Option<String>, Duration, bool)>,
+    attempt: u32,
+    timed_out: bool,
+) {
+    for ((character, (copy, reaction, ahead)), sent) in characters.iter_mut().zip(scores).zip(sent)
+    {
+        if character.correct {
+            continue;
+        }
+        *character = CharacterCopy {
+            correct: copy.as_ref() == Some(sent),
+            copy,
+            reaction: (!timed_out).then_some(reaction),
+            ahead,
+            attempt,
+            total: character.total + reaction,
+        };
+    }
+}
+
+/// How each character, and each whole group, of a drill was copied.
+/// Words are only scored as a whole.
+pub struct DrillResults {
+    pub characters: QuizResult,
+    pub groups: Vec<GroupCopy>,
+    /// Each character, or word or exchange, in the order it was sent.
+    pub trials: Vec<Trial>,
+    /// The copies of the missed groups sent again at the end, a list per
+    /// round. Only the first copies are scored.
+    pub review: Vec<Vec<GroupCopy>>,
+    /// How long the drill was paused, left out of its times.
+    pub paused: Duration,
+    /// Whether it was stopped before it ended.
+    pub incomplete: bool,
+}
+
+impl DrillResults {
+    /// The percentage of the groups copied exactly.
+    pub fn group_accuracy(&self) -> f64 {
+        let exact = self.groups.iter().filter(|group| group.exact).count();
+        exact as f64 / self.groups.len().max(1) as f64 * 100.0
+    }
+
+    /// The percentage of the groups copied exactly, the first time or
+    /// in a review round.
+    pub fn reviewed_accuracy(&self) -> f64 {
+        let exact = self.groups.iter().filter(|group| group.exact).count()
+            + self
+                .review
+                .iter()
+                .flatten()
+                .filter(|copy| copy.exact)
+                .count();
+        exact as f64 / self.groups.len().max(1) as f64 * 100.0
+    }
+
+    /// Adds the results of a drill that followed, e.g. of the next
+    /// section of a script.
+    pub fn extend(&mut self, other: DrillResults) {
+        let (characters, other_characters) = (&mut self.characters, other.characters);
+        characters.prompts.extend(other_characters.prompts);
+        characters.responses.extend(other_characters.responses);
+        characters
+            .reaction_times
+            .extend(other_characters.reaction_times);
+        characters.typed_ahead.extend(other_characters.typed_ahead);
+        characters.typed.extend(other_characters.typed);
+        characters.sent_at.extend(other_characters.sent_at);
+        self.groups.extend(other.groups);
+        self.trials.extend(other.trials);
+        for (i, round) in other.review.into_iter().enumerate() {
+            match self.review.get_mut(i) {
+                Some(copies) => copies.extend(round),
+                None => self.review.push(round),
+            }
+        }
+        self.paused += other.paused;
+        self.incomplete |= other.incomplete;
+    }
+
+    /// The mean speed of the groups, or None if none were sent.
+    pub fn wpm(&self) -> Option<u32> {
+        let total: u32 = self.groups.iter().map(|group| group.wpm).sum();
+        (!self.groups.is_empty()).then(|| total / self.groups.len() as u32)
+    }
+}
+
+/// Scores a copied group, character by character.
+///
+/// # Arguments
+/// * `sent` - The characters of the group.
+/// * `ends` - When the sound of each character ended.
+/// * `typed` - Each key typed, and when.
+/// * `done` - When the copy was finished.
+///
+/// # Returns
+/// What was typed for each character (if anything), how long after the
+/// end of its sound, and whether it was typed ahead of it.
+fn score_group(
+    sent: &[String],
+    ends: &[Instant],
+    typed: &[(char, Instant)],
+    done: Instant,
+) -> Vec<(Option<String>, Duration, bool)> {
+    // A prosign is copied once its closing bracket is typed:
+    let text: String = typed.iter().map(|&(ch, _)| ch).collect();
+    let mut times = typed.iter().filter(|(ch, _)| !ch.is_whitespace());
+    let copied: Vec<(String, Instant)> = split_characters(&text)
+        .into_iter()
+        .filter(|character| !character.trim().is_empty())
+        .map(|character| {
+            let time = times.by_ref().take(character.chars().count()).last();
+            (character, time.map_or(done, |&(_, time)| time))
+        })
+        .collect();
+    (0..sent.len())
+        .map(|i| {
+            let (copy, time) = match copied.get(i) {
+                Some((copy, time)) => (Some(copy.clone()), *time),
+                None => (None, done),
+            };
+            let end = ends.get(i).or(ends.last()).copied().unwrap_or(done);
+            let (reaction, ahead) = reaction(time, end);
+            (copy, reaction, ahead)
+        })
+        .collect()
+}
+
+/// The partial credit of the copy of a phrase, from 0 to 1: how little
+/// of it has to be changed to make the phrase, see `score::edit_distance`.
+fn phrase_credit(sent: &str, copied: &str) -> f64 {
+    let words = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let (sent, copied) = (words(sent), words(&copied.to_uppercase()));
+    let length = sent.chars().count().max(copied.chars().count()).max(1);
+    1.0 - edit_distance(&sent, &copied) as f64 / length as f64
+}
+
+/// Plays the groups of a drill, and scores the copy of each of them.
+pub fn run_drill<R: Rng>(drill: &mut Drill, rng: &mut R) -> DrillResults {
+    let ends = match (drill.count, drill.duration) {
+        (Some(count), None) => format!("after {count} groups"),
+        (None, Some(duration)) => format!("after {} minutes", duration.as_secs_f32() / 60.0),
+        (Some(count), Some(duration)) => format!(
+            "after {count} groups or {} minutes",
+            duration.as_secs_f32() / 60.0
+        ),
+        (None, None) => "when you stop it".to_string(),
+    };
+    let head_copy = matches!(drill.groups, Groups::Phrases { .. });
+    let paragraph = match &drill.groups {
+        Groups::Random { pool, .. } => format!("Code group drill of: {}\n\nGroups of random characters will be played.", pool.join(" ")),
+        Groups::Callsigns(_) => "Callsign drill.\n\nCallsigns will be played.".to_string(),
+        Groups::Words(_) => "Word drill.\n\nWords will be played, the missed ones coming again sooner.".to_string(),
+        Groups::Phrases { .. } => "Head copy drill.\n\nPhrases of words will be played, the missed ones coming again sooner. Don't type while a phrase plays: copy it from memory at the \"copy?\" prompt.".to_string(),
+        Groups::Exchanges(exchange) => format!("Contest exchange drill of: {}\n\nExchanges will be played, the serial numbers counting up.", exchange.template()),
+        Groups::Review { groups, .. } => format!("Review.\n\n{} groups of the characters and words due today will be played.", groups.len()),
+        Groups::Sequence(groups) => format!("Text.\n\n{} groups of the text will be played in order.", groups.len()),
+    } + &match drill.answers.keying() {
+        // Keyed answers are decoded, and end by themselves:
+        Some(_) => " Key each one once it has played: it is decoded when you stop keying for a moment, or press Enter.".to_string(),
+        None => format!(" Type each one {}, then press Enter.", if head_copy { "after it" } else { "as you hear it" }),
+    } + &format!(" The drill ends {ends}. Press {} to pause it, and Ctrl-C to stop early.\n", drill.pause_key)
+        + &match &drill.ramp {
+            Some(ramp) => format!("\nThe speed climbs from {} to {} WPM.\n", ramp.start, ramp.end),
+            None => String::new(),
+        }
+        + &match &drill.ladder {
+            Some(ladder) => format!("\nThe speed starts at {} WPM, going up after each call copied exactly and down after each error. The faster the call, the more it scores.\n", ladder.wpm),
+            None => String::new(),
+        }
+        + match drill.typeahead {
+            true => "",
+            false => "\nWait for the end of each group: what is typed while it plays is ignored.\n",
+        };
+    for line in wrap(&paragraph, 70) {
+        println!("{}", line);
+    }
+
+    let mut results = DrillResults {
+        characters: QuizResult {
+            prompts: Vec::new(),
+            responses: Vec::new(),
+            reaction_times: Vec::new(),
+            typed_ahead: Vec::new(),
+            typed: Vec::new(),
+            sent_at: Vec::new(),
+        },
+        groups: Vec::new(),
+        trials: Vec::new(),
+        review: Vec::new(),
+        paused: Duration::ZERO,
+        incomplete: false,
+    };
+    let player = MorsePlayer::new();
+    let tone_freq = drill.tone_freq;
+    let band = drill.band.clone();
+    // Plays some code, and reads the copy typed while and after it plays:
+    // It pauses before the code if asked to while the last was playing:
+    let mut play = |code: &str,
+                    prompt: Option<String>,
+                    timing: Timing,
+                    tone: f32,
+                    flow: &mut Flow,
+                    rng: &mut R| {
+        let keys = drill.answers.keys();
+        let _raw = keys.raw_mode();
+        keys.clear_flow(flow);
+        flow.boundary(Instant::now());
+        if !flow.wait(keys) {
+            return (Copied::Stop, Vec::new());
+        }
+        if let Some(prompt) = &prompt {
+            print!("{prompt}\r\n");
+        }
+        if !head_copy {
+            print!("> ");
+            stdout().flush().unwrap();
+        }
+        // A prompt is shown instead of the code, the copy timed from it:
+        let (_sound, start, sound_ends) = match prompt {
+            Some(_) => (None, Instant::now(), vec![Instant::now()]),
+            None => {
+                let events = timing.key_events(code);
+                let keyer = Keyer::new(&events, SAMPLE_RATE, tone, 0.5, 5.0);
+                let (mixer, delay) = match &drill.interferer {
+                    Some(interferer) => {
+                        let length = events.iter().map(|&(_, ms)| ms).sum();
+                        interferer.mix(keyer, length, timing, tone, rng)
+                    }
+                    None => (Mixer::new(SAMPLE_RATE).with_voice(keyer, 0, 1.0), 0),
+                };
+                let sound = player.start_mixer(mixer.with_band(band.clone()));
+                // The group starts after the interferer, if that is sent
+                // first:
+                let start = Instant::now() + Duration::from_millis(delay.into());
+                let sound_ends: Vec<Instant> = timing
+                    .character_ends(code)
+                    .into_iter()
+                    .map(|end| start + Duration::from_millis(end.into()))
+                    .collect();
+                (Some(sound), start, sound_ends)
+            }
+        };
+        let sound_end = *sound_ends.last().unwrap_or(&start);
+        if head_copy {
+            // Copied from memory, once it has all been played:
+            std::thread::sleep(sound_end.saturating_duration_since(Instant::now()));
+            print!("copy? ");
+            stdout().flush().unwrap();
+        }
+        let copied = drill.answers.read(
+            flow,
+            sound_end,
+            drill.answer_timeout,
+            drill.typeahead && !head_copy,
+        );
+        (copied, sound_ends)
+    };
+    // The feedback sound that is playing, and when it ends. The next
+    // group waits for it, so that they never overlap:
+    let mut feedback_sound: Option<(Sink, Instant)> = None;
+    let wait_feedback = |sound: &mut Option<(Sink, Instant)>| {
+        if let Some((_, end)) = sound.take() {
+            std::thread::sleep(end.saturating_duration_since(Instant::now()));
+        }
+    };
+    let feedback_kind = drill.feedback;
+    // Tells whether a copy was right, returning the sound that plays:
+    let give_feedback = |marks: &[(String, bool)], exact: bool| {
+        if feedback_kind.visual() && crate::term::accessible() {
+            println!("> {}", feedback::spoken(marks));
+        } else if feedback_kind.visual() {
+            print!("\r> {}", feedback::colored(marks));
+            let _ = execute!(stdout(), Clear(ClearType::UntilNewLine));
+        }
+        feedback_kind.sound().then(|| {
+            let (sound, length) = player.start_tones(feedback::tones(exact));
+            (sound, Instant::now() + length)
+        })
+    };
+    // Shows the answer of a missed group, plays it again, and leaves it
+    // on the screen for a while. It is not scored:
+    let reveal = |answer: &str, code: &str, wpm: u32, tone: f32| {
+        println!("\n  {answer}");
+        let Some((reveal, delay)) = drill.reveal else {
+            return;
+        };
+        let shown = Instant::now();
+        for wpm in reveal.speeds(wpm) {
+            let timing =
+                Timing::new(wpm_to_dot_length(wpm), None).with_word_space(drill.word_space);
+            let sound = player.start_keyer(
+                Keyer::new(&timing.key_events(code), SAMPLE_RATE, tone, 0.5, 5.0)
+                    .with_band(band.clone()),
+            );
+            sound.sleep_until_end();
+        }
+        std::thread::sleep(delay.saturating_sub(shown.elapsed()));
+    };
+    // What is shown in place of a group, if anything, and the answer
+    // shown after it:
+    let prompt = |group: &str| match &drill.abbreviations {
+        Some((meanings, Direction::Read)) => meanings.meaning(group).map(str::to_string),
+        _ => None,
+    };
+    let answer = |group: &str| match &drill.abbreviations {
+        Some((meanings, _)) => meanings.answer(group),
+        None => group.to_string(),
+    };
+    // The groups missed, and the speed they were sent at:
+    let mut missed: Vec<(Vec<String>, u32, f32)> = Vec::new();
+    let mut panes = match drill.panes.then(practice_screen) {
+        Some(Ok(screen)) => Some((PracticeView::new(), screen)),
+        Some(Err(e)) => {
+            warn!("Could not draw the panes: {e}");
+            None
+        }
+        None => None,
+    };
+    let mut stopped = false;
+    let mut flow = Flow::new(Some(drill.pause_key));
+    let start = Instant::now();
+    // How far the speed has climbed, in groups and as a share of the
+    // drill:
+    let (mut climbed, mut progress) = (0, 0.0);
+    let mut holding = false;
+    'drill: while drill
+        .count
+        .is_none_or(|count| results.groups.len() < count as usize)
+        && drill
+            .duration
+            .is_none_or(|duration| start.elapsed().saturating_sub(flow.paused()) < duration)
+    {
+        let (group_start, paused) = (Instant::now(), flow.paused());
+        std::thread::sleep(Duration::from_millis(500));
+        wait_feedback(&mut feedback_sound);
+        // The speed and the tone only change between groups:
+        let (dot_duration, wpm) = match (&drill.ramp, drill.random_speed, &drill.ladder) {
+            (Some(ramp), _, _) => {
+                let wpm = ramp.wpm(climbed, progress);
+                (wpm_to_dot_length(wpm), wpm)
+            }
+            (None, Some((min, max)), _) => {
+                let wpm = rng.gen_range(min..=max);
+                (wpm_to_dot_length(wpm), wpm)
+            }
+            (None, None, Some(ladder)) => (wpm_to_dot_length(ladder.wpm), ladder.wpm),
+            (None, None, None) => (drill.dot_duration, dot_length_to_wpm(drill.dot_duration)),
+        };
+        let tone = drill
+            .random_pitch
+            .map_or(tone_freq, |(low, high)| rng.gen_range(low..=high).round());
+        // Drawn again for each group, at the size of the terminal, as the
+        // copy scrolls under them:
+        if let Some((view, screen)) = panes.as_mut().filter(|(view, _)| !view.is_empty()) {
+            if let Err(e) = view.draw(screen) {
+                warn!("Could not draw the panes: {e}");
+            }
+        }
+        let group = drill.groups.next(rng);
+        let code = encode(&group.concat(), &Separators::default());
+        let timing = Timing::new(dot_duration, drill.farnsworth).with_word_space(drill.word_space);
+        let by_character = drill.groups.by_character(&group);
+        let mut characters = vec![CharacterCopy::default(); group.len()];
+        // The last attempt, and the time taken over all of them:
+        let (mut attempt, mut time) = (0, Duration::ZERO);
+        let (copied, exact, sound_ends, timed_out, last_time) = loop {
+            attempt += 1;
+            let (copy, sound_ends) =
+                play(&code, prompt(&group.concat()), timing, tone, &mut flow, rng);
+            let (typed, done, timed_out) = match copy {
+                Copied::Typed(typed, done) => (typed, done, false),
+                Copied::TimedOut(at) => (Vec::new(), at, true),
+                Copied::Stop => {
+                    println!("\nDrill stopped.");
+                    stopped = true;
+                    break 'drill;
+                }
+            };
+            let copied: String = typed.iter().map(|&(ch, _)| ch).collect();
+            let exact = drill.groups.check(&group, &copied);
+            let sound_end = *sound_ends.last().unwrap_or(&done);
+            let last_time = done.saturating_duration_since(sound_end);
+            time += last_time;
+            if by_character {
+                let scores = score_group(&group, &sound_ends, &typed, done);
+                copy_attempt(&mut characters, &group, scores, attempt, timed_out);
+            }
+            if exact || attempt >= drill.attempts {
+                break (copied, exact, sound_ends, timed_out, last_time);
+            }
+            let marks = match by_character {
+                true => characters.iter().map(CharacterCopy::mark).collect(),
+                false => vec![(copied.trim().to_uppercase(), false)],
+            };
+            feedback_sound = give_feedback(&marks, false);
+            let timeout = if timed_out { ", no answer in time" } else { "" };
+            println!("\n  try {}/{}{timeout}", attempt + 1, drill.attempts);
+            std::thread::sleep(Duration::from_millis(500));
+            wait_feedback(&mut feedback_sound);
+        };
+        let mut accuracy = match head_copy {
+            true => phrase_credit(&group.concat(), &copied),
+            false => exact as u32 as f64,
+        };
+        // What was copied, and whether it was right, to color it:
+        let mut marks = vec![(copied.trim().to_uppercase(), exact)];
+        if by_character {
+            accuracy = characters.iter().filter(|copy| copy.correct).count() as f64
+                / characters.len().max(1) as f64;
+            marks = characters.iter().map(CharacterCopy::mark).collect();
+            for ((copy, character), end) in
+                characters.into_iter().zip(group.clone()).zip(&sound_ends)
+            {
+                let sent_at = wall_clock(*end);
+                results.trials.push(Trial {
+                    timestamp: timestamp(&sent_at),
+                    sent: character.clone(),
+                    typed: copy.copy.clone(),
+                    correct: copy.correct,
+                    reaction: copy.reaction,
+                    wpm,
+                    attempt: copy.attempt,
+                    total: Some(copy.total),
+                    tone: Some(tone),
+                });
+                results.characters.prompts.push(character);
+                results.characters.responses.push(Some(copy.correct));
+                results.characters.typed.push(copy.copy);
+                results.characters.reaction_times.push(copy.reaction);
+                results.characters.typed_ahead.push(copy.ahead);
+                results.characters.sent_at.push(sent_at);
+            }
+        }
+        if timed_out {
+            print!(" (no answer in time)");
+        }
+        feedback_sound = give_feedback(&marks, exact);
+        if !exact {
+            missed.push((group.clone(), wpm, tone));
+        }
+        drill.groups.copied(&group.concat(), exact);
+        if !by_character {
+            let sound_end = *sound_ends.last().unwrap_or(&Instant::now());
+            results.trials.push(Trial {
+                timestamp: timestamp(&wall_clock(sound_end)),
+                sent: group.concat(),
+                typed: Some(copied.trim().to_uppercase()).filter(|copied| !copied.is_empty()),
+                correct: exact,
+                reaction: (!timed_out).then_some(last_time),
+                wpm,
+                attempt,
+                total: Some(time),
+                tone: Some(tone),
+            });
+        }
+        results.groups.push(GroupCopy {
+            sent: group.concat(),
+            copied: copied.trim().to_uppercase(),
+            exact,
+            time,
+            wpm,
+            tone,
+            accuracy,
+            attempt,
+        });
+        if let (Some((view, _)), Some(group)) = (panes.as_mut(), results.groups.last()) {
+            view.copied(group);
+        }
+
+        let group_time = group_start.elapsed().saturating_sub(flow.paused() - paused);
+        if let Some(ramp) = &drill.ramp {
+            // Hold the speed while its accuracy is below the floor:
+            let below = ramp.floor.is_some_and(|floor| {
+                speed_accuracy(&results.groups, wpm).is_some_and(|accuracy| accuracy < floor)
+            });
+            if below && !holding {
+                print!("\n  holding at {wpm} WPM");
+            }
+            holding = below;
+            if !holding {
+                climbed += 1;
+                progress += match (drill.count, drill.duration) {
+                    (Some(count), None) => 1.0 / count as f64,
+                    (None, Some(duration)) => group_time.as_secs_f64() / duration.as_secs_f64(),
+                    (Some(count), Some(duration)) => {
+                        (1.0 / count as f64).max(group_time.as_secs_f64() / duration.as_secs_f64())
+                    }
+                    (None, None) => 0.0,
+                };
+            }
+        }
+
+        // Only the first copy is scored:
+        if let (false, Some(wpm)) = (exact, drill.resend) {
+            println!("\n  again at {wpm} WPM");
+            let timing =
+                Timing::new(wpm_to_dot_length(wpm), None).with_word_space(drill.word_space);
+            wait_feedback(&mut feedback_sound);
+            if let (Copied::Stop, _) =
+                play(&code, prompt(&group.concat()), timing, tone, &mut flow, rng)
+            {
+                println!("\n  {}\nDrill stopped.", group.concat());
+                stopped = true;
+                break 'drill;
+            }
+        }
+        if !exact {
+            wait_feedback(&mut feedback_sound);
+            reveal(&answer(&group.concat()), &code, wpm, tone);
+        } else {
+            println!("\n  {}", answer(&group.concat()));
+        }
+        if let Some(ladder) = &mut drill.ladder {
+            let points = ladder.answer(&group.concat(), &copied.trim().to_uppercase(), exact);
+            println!(
+                "  +{points} points: score {}, next call at {} WPM",
+                ladder.score, ladder.wpm
+            );
+        }
+    }
+
+    // A drill that only ends when stopped is complete when it is, and
+    // one stopped during the review has all of its scored groups:
+    results.incomplete = stopped && (drill.count.is_some() || drill.duration.is_some());
+
+    // Send the missed groups again, and those missed again once more:
+    for round in 1..=drill.review_passes {
+        if stopped || missed.is_empty() {
+            break;
+        }
+        println!("\nReview round {round}: {} missed groups.", missed.len());
+        let mut copies = Vec::new();
+        for (group, wpm, tone) in std::mem::take(&mut missed) {
+            std::thread::sleep(Duration::from_millis(500));
+            wait_feedback(&mut feedback_sound);
+            let wpm = wpm.saturating_sub(drill.review_speed_offset).max(5);
+            let code = encode(&group.concat(), &Separators::default());
+            let timing = Timing::new(wpm_to_dot_length(wpm), drill.farnsworth)
+                .with_word_space(drill.word_space);
+            let (typed, done, sound_ends) =
+                match play(&code, prompt(&group.concat()), timing, tone, &mut flow, rng) {
+                    (Copied::Typed(typed, done), sound_ends) => (typed, done, sound_ends),
+                    (Copied::TimedOut(at), sound_ends) => {
+                        print!(" (no answer in time)");
+                        (Vec::new(), at, sound_ends)
+                    }
+                    (Copied::Stop, _) => {
+                        println!("\n  {}\nReview stopped.", group.concat());
+                        stopped = true;
+                        break;
+                    }
+                };
+            let copied: String = typed.iter().map(|&(ch, _)| ch).collect();
+            let exact = drill.groups.check(&group, &copied);
+            let copied = copied.trim().to_uppercase();
+            feedback_sound = give_feedback(&[(copied.clone(), exact)], exact);
+            if !exact {
+                wait_feedback(&mut feedback_sound);
+                reveal(&answer(&group.concat()), &code, wpm, tone);
+                missed.push((group.clone(), wpm, tone));
+            } else {
+                println!("\n  {}", answer(&group.concat()));
+            }
+            copies.push(GroupCopy {
+                sent: group.concat(),
+                copied,
+                exact,
+                time: done.saturating_duration_since(*sound_ends.last().unwrap_or(&done)),
+                wpm,
+                tone,
+                accuracy: exact as u32 as f64,
+                attempt: 1,
+            });
+        }
+        results.review.push(copies);
+    }
+    results.paused = flow.paused();
+    results
+}
+
+/// Prints how many of the missed groups were copied in each review
+/// round, and the accuracy before and after them.
+pub fn print_review(results: &DrillResults) {
+    println!(
+        "\nFirst pass: {:.0}% of the groups copied exactly",
+        results.group_accuracy()
+    );
+    for (round, copies) in results.review.iter().enumerate() {
+        let exact = copies.iter().filter(|copy| copy.exact).count();
+        println!(
+            "Review round {}: {exact}/{} copied exactly",
+            round + 1,
+            copies.len()
+        );
+    }
+    println!(
+        "After the review: {:.0}% of the groups copied exactly",
+        results.reviewed_accuracy()
+    );
+}
+
+/// Prints how many of the groups were copied exactly, how long they
+/// took to copy, and which were missed along with what was typed.
+pub fn print_groups(results: &DrillResults) {
+    let exact = results.groups.iter().filter(|group| group.exact).count();
+    println!("\nCopied exactly: {exact}/{}", results.groups.len());
+    if !results.groups.is_empty() {
+        let time: Duration = results.groups.iter().map(|group| group.time).sum();
+        println!(
+            "Average time to copy: {:.1}s",
+            time.as_secs_f32() / results.groups.len() as f32
+        );
+    }
+    let missed: Vec<&GroupCopy> = results.groups.iter().filter(|group| !group.exact).collect();
+    if missed.is_empty() {
+        return;
+    }
+    let width = missed
+        .iter()
+        .map(|group| group.sent.chars().count())
+        .max()
+        .unwrap_or(0);
+    println!("Missed:");
+    for group in missed {
+        println!("  {:<width$}  typed {:?}", group.sent, group.copied);
+    }
+}
+
+/// How many of the words of some phrases were copied exactly, and how
+/// many were sent, lined up as in a QSO (see `qso::diff_words`).
+fn words_copied<'a>(groups: impl IntoIterator<Item = &'a GroupCopy>) -> (usize, usize) {
+    let copies: Vec<(String, Vec<WordDiff>)> = groups
+        .into_iter()
+        .map(|group| (group.sent.clone(), diff_words(&group.sent, &group.copied)))
+        .collect();
+    copied_words(&copies)
+}
+
+/// The percentage of the words of the phrases copied exactly.
+pub fn word_accuracy(groups: &[GroupCopy]) -> f64 {
+    let (copied, sent) = words_copied(groups);
+    copied as f64 / sent.max(1) as f64 * 100.0
+}
+
+#[derive(Tabled)]
+struct PhraseRow {
+    words: usize,
+    phrases: usize,
+    copied_exactly: String,
+    words_copied: String,
+    partial_credit: String,
+}
+
+/// Prints how the phrases were copied by their number of words, to see
+/// how the copy holds up as they get longer.
+///
+/// # Returns
+/// The table, if it had any rows, to be copied.
+pub fn print_phrases(results: &DrillResults) -> Option<String> {
+    let mut lengths: Vec<usize> = results
+        .groups
+        .iter()
+        .map(|group| group.sent.split_whitespace().count())
+        .collect();
+    lengths.sort();
+    lengths.dedup();
+    let rows: Vec<PhraseRow> = lengths
+        .into_iter()
+        .map(|words| {
+            let phrases: Vec<&GroupCopy> = results
+                .groups
+                .iter()
+                .filter(|group| group.sent.split_whitespace().count() == words)
+                .collect();
+            let percent = |share: f64| format!("{:.0}%", share * 100.0);
+            let exact = phrases.iter().filter(|group| group.exact).count();
+            let (copied, sent) = words_copied(phrases.iter().copied());
+            let credit: f64 = phrases.iter().map(|group| group.accuracy).sum();
+            PhraseRow {
+                words,
+                phrases: phrases.len(),
+                copied_exactly: percent(exact as f64 / phrases.len() as f64),
+                words_copied: percent(copied as f64 / sent.max(1) as f64),
+                partial_credit: percent(credit / phrases.len() as f64),
+            }
+        })
+        .collect();
+    if rows.is_empty() {
+        return None;
+    }
+    let table = Table::new(rows).with(Style::rounded()).to_string();
+    println!("\nBy the number of words in a phrase:\n");
+    println!("{table}");
+    Some(table)
+}
+
+/// Prints how many of the groups were copied exactly on each attempt,
+/// and how many were missed on all of them.
+pub fn print_attempts(results: &DrillResults, attempts: u32) {
+    println!();
+    for attempt in 1..=attempts {
+        let copied = results
+            .groups
+            .iter()
+            .filter(|group| group.exact && group.attempt == attempt)
+            .count();
+        println!("Copied exactly on attempt {attempt}: {copied}");
+    }
+    let missed = results.groups.iter().filter(|group| !group.exact).count();
+    println!("Missed on all {attempts} attempts: {missed}");
+}
+
+#[derive(Tabled)]
+struct SpeedRow {
+    wpm: u32,
+    groups: usize,
+    accuracy: String,
+}
+
+/// Prints the accuracy at each speed of a drill whose speed changed.
+///
+/// # Returns
+/// The table, if it had any rows, to be copied.
+pub fn print_speeds(results: &DrillResults) -> Option<String> {
+    let mut speeds: Vec<u32> = results.groups.iter().map(|group| group.wpm).collect();
+    speeds.sort_unstable();
+    speeds.dedup();
+    let rows: Vec<SpeedRow> = speeds
+        .into_iter()
+        .map(|wpm| SpeedRow {
+            wpm,
+            groups: results
+                .groups
+                .iter()
+                .filter(|group| group.wpm == wpm)
+                .count(),
+            accuracy: format!(
+                "{:.0}%",
+                speed_accuracy(&results.groups, wpm).unwrap_or_default()
+            ),
+        })
+        .collect();
+    if rows.is_empty() {
+        return None;
+    }
+    let table = Table::new(&rows).with(Style::rounded()).to_string();
+    println!("\nAccuracy by speed:\n");
+    println!("{table}");
+    Some(table)
+}
+
+/// How wide each range of tones of `print_tones` is, in Hz.
+const TONE_RANGE: u32 = 50;
+
+#[derive(Tabled)]
+struct ToneRow {
+    tone: String,
+    groups: usize,
+    accuracy: String,
+}
+
+/// Prints the accuracy over each range of tones of a drill whose tone
+/// changed, e.g. 600-649 Hz.
+///
+/// # Returns
+/// The table, if it had any rows, to be copied.
+pub fn print_tones(results: &DrillResults) -> Option<String> {
+    let mut ranges: BTreeMap<u32, Vec<f64>> = BTreeMap::new();
+    for group in &results.groups {
+        let range = group.tone.max(0.0) as u32 / TONE_RANGE * TONE_RANGE;
+        ranges.entry(range).or_default().push(group.accuracy);
+    }
+    let rows: Vec<ToneRow> = ranges
+        .into_iter()
+        .map(|(low, accuracies)| ToneRow {
+            tone: format!("{low}-{} Hz", low + TONE_RANGE - 1),
+            groups: accuracies.len(),
+            accuracy: format!(
+                "{:.0}%",
+                accuracies.iter().sum::<f64>() / accuracies.len() as f64 * 100.0
+            ),
+        })
+        .collect();
+    if rows.is_empty() {
+        return None;
+    }
+    let table = Table::new(&rows).with(Style::rounded()).to_string();
+    println!("\nAccuracy by tone:\n");
+    println!("{table}");
+    Some(table)
+}
+
+#[derive(Tabled)]
+struct FocusRow {
+    character: String,
+    chance: String,
+    accuracy: String,
+    avg_reaction_time: String,
+    result: String,
+}
+
+/// Prints the characters that were sent more often for being weak, and
+/// how their copy compares with their history.
+///
+/// # Arguments
+/// * `weights` - The chance of picking each character of the pool.
+/// * `history` - How each character was copied before the drill.
+/// * `outlier_iqr` - How many interquartile ranges above the third
+///   quartile a reaction time is an outlier.
+pub fn print_focus(
+    results: &DrillResults,
+    pool: &[String],
+    weights: &[f64],
+    history: &[Option<CharacterStats>],
+    outlier_iqr: f64,
+) {
+    let even = 1.0 / pool.len().max(1) as f64;
+    let characters = &results.characters;
+    let now = character_stats(characters, characters.outlier_limit(outlier_iqr));
+    let before_after = |before: Option<String>, after: Option<String>| {
+        format!(
+            "{} -> {}",
+            before.unwrap_or("-".to_string()),
+            after.unwrap_or("-".to_string())
+        )
+    };
+    let mut rows: Vec<(f64, FocusRow)> = Vec::new();
+    let mut improved = 0;
+    for ((character, &weight), before) in pool.iter().zip(weights).zip(history) {
+        if weight <= even * 1.05 {
+            continue;
+        }
+        let after = now.iter().find(|stats| &stats.character == character);
+        let result = match (before, after) {
+            (_, None) => "not sent",
+            (None, Some(_)) => "new",
+            (Some(before), Some(after)) => {
+                // More accurate, or as accurate and faster:
+                let better =
+                    (after.accuracy(), before.reaction) > (before.accuracy(), after.reaction);
+                let worse =
+                    (after.accuracy(), before.reaction) < (before.accuracy(), after.reaction);
+                match (better, worse) {
+                    (true, _) => {
+                        improved += 1;
+                        "improved"
+                    }
+                    (_, true) => "worse",
+                    _ => "same",
+                }
+            }
+        };
+        let accuracy =
+            |stats: Option<&CharacterStats>| stats.map(|s| format!("{:.0}%", s.accuracy()));
+        let reaction =
+            |stats: Option<&CharacterStats>| stats.map(|s| format!("{}ms", s.reaction.as_millis()));
+        rows.push((
+            weight,
+            FocusRow {
+                character: character.clone(),
+                chance: format!("{:.0}%", weight * 100.0),
+                accuracy: before_after(accuracy(before.as_ref()), accuracy(after)),
+                avg_reaction_time: before_after(reaction(before.as_ref()), reaction(after)),
+                result: result.to_string(),
+            },
+        ));
+    }
+    if rows.is_empty() {
+        return;
+    }
+    // The most boosted first:
+    rows.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let count = rows.len();
+    let rows: Vec<FocusRow> = rows.into_iter().map(|(_, row)| row).collect();
+    println!(
+        "\nWeak characters sent more often (each would be {:.0}% evenly), before and now:\n",
+        even * 100.0
+    );
+    println!("{}", Table::new(&rows).with(Style::rounded()));
+    println!("Improved on their history: {improved}/{count}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(characters: &[&str]) -> Vec<String> {
+        characters.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pool() {
+        let pool = |include, exclude| {
+            parse_pool(include, Charset::Latin)
+                .and_then(|pool| super::exclude(pool, exclude, Charset::Latin))
+        };
+        assert_eq!(
+            pool("ABCDE12345", "").unwrap(),
+            strings(&["A", "B", "C", "D", "E", "1", "2", "3", "4", "5"])
+        );
+        assert_eq!(pool("a-e  0-2", "").unwrap().concat(), "ABCDE012");
+        assert_eq!(pool("letters", "QXZ").unwrap().len(), 23);
+        assert_eq!(
+            pool("digits,prosigns", "0-7,<SN> <AS>").unwrap(),
+            strings(&["8", "9", "<AR>", "<BK>", "<BT>", "<CT>", "<KN>", "<SK>"])
+        );
+        assert_eq!(pool("punctuation", "").unwrap().concat(), PUNCTUATION);
+        assert_eq!(pool("А-Г", "").unwrap().concat(), "АБВГ");
+        assert_eq!(parse_pool("letters", Charset::Cyrillic).unwrap().len(), 32);
+        assert!(pool("AB#", "").is_err());
+        assert!(pool("Z-A", "").is_err());
+        assert!(pool("digits", "digits").is_err());
+    }
+
+    #[test]
+    fn test_speed_ramp() {
+        let mut ramp = SpeedRamp {
+            start: 15,
+            end: 25,
+            steps: None,
+            floor: None,
+        };
+        assert_eq!(ramp.wpm(0, 0.0), 15);
+        assert_eq!(ramp.wpm(7, 0.52), 20);
+        assert_eq!(ramp.wpm(30, 1.2), 25);
+        ramp.steps = Some((2, 5));
+        assert_eq!(ramp.wpm(4, 0.9), 15);
+        assert_eq!(ramp.wpm(5, 0.0), 17);
+        assert_eq!(ramp.wpm(27, 0.0), 25);
+    }
+
+    #[test]
+    fn test_review_groups() {
+        use rand::SeedableRng;
+        let items = strings(&["K", "M", "THE", "U", "<AR>", "R", "73"]);
+        let review = |seed| {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            match Groups::review(&items, (2, 3), &mut rng) {
+                Groups::Review { groups, words } => (Vec::from(groups), words),
+                _ => unreachable!(),
+            }
+        };
+        let (groups, words) = review(7);
+        assert_eq!((groups.clone(), words.clone()), review(7));
+        assert_eq!(words, ["THE", "73"]);
+        let mut sent: Vec<String> = groups.iter().flatten().cloned().collect();
+        sent.sort();
+        let mut expected = strings(&["K", "M", "T", "H", "E", "U", "<AR>", "R", "7", "3"]);
+        expected.sort();
+        assert_eq!(sent, expected);
+        for group in &groups {
+            let word = words.contains(&group.concat());
+            assert!(word || (1..=3).contains(&group.len()), "{groups:?}");
+        }
+    }
+
+    #[test]
+    fn test_focus_weights() {
+        let stats = |correct, ms| {
+            Some(CharacterStats {
+                character: String::new(),
+                count: 10,
+                correct,
+                reaction: Duration::from_millis(ms),
+                distribution: None,
+            })
+        };
+        let weights = focus_weights(&[stats(10, 300), stats(5, 300), None], 0.5);
+        let expected = [1.0 / 6.0, 1.0 / 6.0 + 0.25, 1.0 / 6.0 + 0.25];
+        for (weight, expected) in weights.iter().zip(expected) {
+            assert!((weight - expected).abs() < 1e-9, "{weights:?}");
+        }
+        // Slower counts like missing:
+        let weights = focus_weights(&[stats(10, 200), stats(10, 400)], 1.0);
+        assert!((weights[1] - 1.0).abs() < 1e-9, "{weights:?}");
+        // Without a history, or mixing none in, it is even:
+        assert_eq!(focus_weights(&[None, None], 0.5), vec![0.5, 0.5]);
+        assert_eq!(
+            focus_weights(&[stats(10, 300), stats(0, 300)], 0.0),
+            vec![0.5, 0.5]
+        );
+    }
+
+    #[test]
+    fn test_focus_distribution() {
+        let mut rng = rand::thread_rng();
+        let weights = vec![0.1, 0.2, 0.7];
+        let mut groups = Groups::Random {
+            pool: strings(&["K", "M", "U"]),
+            size: (5, 5),
+            weights: Some(WeightedIndex::new(&weights).unwrap()),
+        };
+        let mut counts = [0; 3];
+        for _ in 0..4000 {
+            for character in groups.next(&mut rng) {
+                counts[["K", "M", "U"]
+                    .iter()
+                    .position(|&c| c == character)
+                    .unwrap()] += 1;
+            }
+        }
+        for (count, weight) in counts.iter().zip(weights) {
+            let share = *count as f64 / 20000.0;
+            assert!((share - weight).abs() < 0.02, "{counts:?}");
+        }
+    }
+
+    #[test]
+    fn test_speed_accuracy() {
+        let group = |wpm, accuracy| GroupCopy {
+            sent: String::new(),
+            copied: String::new(),
+            exact: accuracy == 1.0,
+            time: Duration::ZERO,
+            wpm,
+            tone: 600.0,
+            accuracy,
+            attempt: 1,
+        };
+        let groups = [group(15, 1.0), group(16, 0.6), group(16, 0.8)];
+        assert_eq!(speed_accuracy(&groups, 15), Some(100.0));
+        assert_eq!(speed_accuracy(&groups, 16).map(f64::round), Some(70.0));
+        assert_eq!(speed_accuracy(&groups, 17), None);
+    }
+
+    #[test]
+    fn test_reveal() {
+        assert_eq!("once".parse::<Reveal>().map(|r| r.speeds(20)), Ok(vec![20]));
+        assert_eq!(
+            "slow".parse::<Reveal>().map(|r| r.speeds(20)),
+            Ok(vec![15, 15])
+        );
+        assert_eq!(Reveal::Slow.speeds(6), [5, 5]);
+        assert!("twice".parse::<Reveal>().is_err());
+    }
+
+    #[test]
+    fn test_reviewed_accuracy() {
+        let group = |exact| GroupCopy {
+            sent: String::new(),
+            copied: String::new(),
+            exact,
+            time: Duration::ZERO,
+            wpm: 20,
+            tone: 600.0,
+            accuracy: exact as u32 as f64,
+            attempt: 1,
+        };
+        let characters = QuizResult {
+            prompts: Vec::new(),
+            responses: Vec::new(),
+            reaction_times: Vec::new(),
+            typed_ahead: Vec::new(),
+            typed: Vec::new(),
+            sent_at: Vec::new(),
+        };
+        let results = DrillResults {
+            characters,
+            groups: vec![group(true), group(false), group(false), group(false)],
+            trials: Vec::new(),
+            // Two of the three missed, then one of the one missed again:
+            review: vec![
+                vec![group(true), group(false), group(true)],
+                vec![group(false)],
+            ],
+            paused: Duration::ZERO,
+            incomplete: false,
+        };
+        assert_eq!(results.group_accuracy(), 25.0);
+        assert_eq!(results.reviewed_accuracy(), 75.0);
+    }
+
+    #[test]
+    fn test_phrase_credit() {
+        assert_eq!(phrase_credit("GM OM", "gm  om"), 1.0);
+        assert_eq!(phrase_credit("TNX FER CALL", "TNX FR CALL"), 11.0 / 12.0);
+        assert_eq!(phrase_credit("ES", ""), 0.0);
+        let groups = Groups::Phrases {
+            picker: WordPicker::new(strings(&["GM"])),
+            words: (1, 3),
+        };
+        let phrase = split_characters("GM OM");
+        assert!(groups.check(&phrase, "gm om"));
+        assert!(!groups.check(&phrase, "gmom"));
+        let copy = |sent: &str, copied: &str| GroupCopy {
+            sent: sent.to_string(),
+            copied: copied.to_string(),
+            exact: sent == copied,
+            time: Duration::ZERO,
+            wpm: 20,
+            tone: 600.0,
+            accuracy: phrase_credit(sent, copied),
+            attempt: 1,
+        };
+        let phrases = [copy("GM", "GM"), copy("TNX FER CALL", "TNX CALL")];
+        assert_eq!(word_accuracy(&phrases), 75.0);
+    }
+
+    #[test]
+    fn test_copy_attempt() {
+        let ms = Duration::from_millis;
+        let sent = strings(&["K", "M", "R"]);
+        let mut characters = vec![CharacterCopy::default(); 3];
+        let score = |copy: Option<&str>, reaction| (copy.map(str::to_string), ms(reaction), false);
+        copy_attempt(
+            &mut characters,
+            &sent,
+            vec![
+                score(Some("K"), 300),
+                score(Some("N"), 400),
+                score(None, 900),
+            ],
+            1,
+            false,
+        );
+        // No answer in time, then the rest copied:
+        let timed_out = vec![score(None, 2000), score(None, 2000), score(None, 2000)];
+        copy_attempt(&mut characters, &sent, timed_out, 2, true);
+        assert_eq!(characters[1].reaction, None);
+        copy_attempt(
+            &mut characters,
+            &sent,
+            vec![
+                score(Some("K"), 200),
+                score(Some("M"), 250),
+                score(Some("R"), 350),
+            ],
+            3,
+            false,
+        );
+        let copied: Vec<(u32, Option<Duration>, Duration)> = characters
+            .iter()
+            .map(|copy| (copy.attempt, copy.reaction, copy.total))
+            .collect();
+        assert_eq!(
+            copied,
+            [
+                (1, Some(ms(300)), ms(300)),
+                (3, Some(ms(250)), ms(2650)),
+                (3, Some(ms(350)), ms(3250)),
+            ]
+        );
+        assert!(characters.iter().all(|copy| copy.correct));
+    }
+
+    #[test]
+    fn test_score_group() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let at = |time| start + ms(time);
+        let sent = strings(&["K", "<AR>", "M", "E"]);
+        let ends = [at(300), at(600), at(950), at(1100)];
+        let typed = [
+            ('k', at(400)),
+            (' ', at(500)),
+            ('<', at(600)),
+            ('a', at(700)),
+            ('r', at(800)),
+            ('>', at(900)),
+            ('N', at(1000)),
+        ];
+        assert_eq!(
+            score_group(&sent, &ends, &typed, at(1500)),
+            vec![
+                (Some("K".to_string()), ms(100), false),
+                (Some("<AR>".to_string()), ms(300), false),
+                (Some("N".to_string()), ms(50), false),
+                (None, ms(400), false),
+            ]
+        );
+        // A whole group typed before the end of its third character, the
+        // copies still going to the characters in order:
+        let group = strings(&["Q", "R", "<SK>", "5", "/"]);
+        let group_ends = [at(200), at(400), at(800), at(1000), at(1200)];
+        let group_typed: Vec<(char, Instant)> = "qr<sk>5/"
+            .chars()
+            .zip([150, 420, 500, 520, 540, 560, 700, 790])
+            .map(|(ch, time)| (ch, at(time)))
+            .collect();
+        let scores = score_group(&group, &group_ends, &group_typed, at(1300));
+        assert_eq!(
+            scores
+                .iter()
+                .map(|(copy, ..)| copy.as_deref())
+                .collect::<Vec<_>>(),
+            [Some("Q"), Some("R"), Some("<SK>"), Some("5"), Some("/")]
+        );
+        assert_eq!(
+            scores
+                .iter()
+                .map(|&(_, time, ahead)| (time, ahead))
+                .collect::<Vec<_>>(),
+            [
+                (ms(0), true),
+                (ms(20), false),
+                (ms(0), true),
+                (ms(0), true),
+                (ms(0), true)
+            ]
+        );
+        // The copy of a character typed before the end of its sound:
+        let typed = [
+            ('k', at(250)),
+            ('<', at(550)),
+            ('a', at(560)),
+            ('r', at(570)),
+        ];
+        assert_eq!(
+            score_group(&sent[..2], &ends[..2], &typed, at(900))[..],
+            [
+                (Some("K".to_string()), ms(0), true),
+                (Some("<".to_string()), ms(0), true)
+            ]
+        );
+    }
+}
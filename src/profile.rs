@@ -0,0 +1,287 @@
+use crate::config::{self, Layer, Setting};
+use crate::prelude::*;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Once, OnceLock};
+
+/// The profile used when none is chosen, which has the data recorded
+/// before there were profiles.
+pub const DEFAULT: &str = "default";
+
+/// The environment variable choosing the profile, unless --profile does.
+pub const ENV: &str = "CODE_SMORE_PROFILE";
+
+/// The files that were kept in the data directory itself before there
+/// were profiles.
+const LEGACY: [&str; 3] = ["sessions.jsonl", "koch-history.txt", "review.jsonl"];
+
+/// The file of the default settings of a profile.
+const SETTINGS: &str = "settings.toml";
+
+static CURRENT: OnceLock<String> = OnceLock::new();
+static MIGRATION: Once = Once::new();
+
+/// Chooses the profile, for the whole program.
+pub fn set_current(name: &str) {
+    let _ = CURRENT.set(name.to_string());
+}
+
+/// The profile that everything is recorded in.
+pub fn current() -> &'static str {
+    CURRENT.get().map(String::as_str).unwrap_or(DEFAULT)
+}
+
+/// Which profile to use: the one given with --profile, or else the one
+/// of the environment variable, or else the one of the config file.
+pub fn choose(flag: Option<&str>, env: Option<&str>, config: Option<&str>) -> String {
+    flag.or(env)
+        .or(config)
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT)
+        .to_string()
+}
+
+/// Checks that a profile name can be the name of its directory.
+pub fn validate(name: &str) -> Result<(), String> {
+    let allowed = |ch: char| ch.is_alphanumeric() || "-_.".contains(ch);
+    match name.starts_with('.') || !name.chars().all(allowed) || name.is_empty() {
+        true => Err(format!(
+            "A profile name may only have letters, digits, '-', '_' and '.', and not start with '.': {name:?}"
+        )),
+        false => Ok(()),
+    }
+}
+
+/// The directory of the program's data, which has a directory for each
+/// profile.
+fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")))
+}
+
+fn profile_dir(data: &Path, name: &str) -> PathBuf {
+    data.join("profiles").join(name)
+}
+
+/// Moves the files recorded before there were profiles into the default
+/// profile, leaving alone any that it already has.
+///
+/// # Returns
+/// The files that were moved.
+fn migrate(data: &Path) -> io::Result<Vec<&'static str>> {
+    let dir = profile_dir(data, DEFAULT);
+    let old: Vec<&str> = LEGACY
+        .into_iter()
+        .filter(|file| data.join(file).is_file() && !dir.join(file).exists())
+        .collect();
+    if !old.is_empty() {
+        std::fs::create_dir_all(&dir)?;
+    }
+    for file in &old {
+        std::fs::rename(data.join(file), dir.join(file))?;
+    }
+    Ok(old)
+}
+
+/// The data directory, once the files recorded before there were
+/// profiles are in the default one.
+fn migrated_data_dir() -> Option<PathBuf> {
+    let data = data_dir()?;
+    MIGRATION.call_once(|| match migrate(&data) {
+        Ok(moved) if !moved.is_empty() => info!(
+            "Moved {} into the {DEFAULT} profile, in {}",
+            moved.join(", "),
+            profile_dir(&data, DEFAULT).display()
+        ),
+        Ok(_) => {}
+        Err(e) => warn!("Could not move the old records into the {DEFAULT} profile: {e}"),
+    });
+    Some(data)
+}
+
+/// Where a file of the current profile is kept, e.g. `sessions.jsonl`.
+pub fn path(file: &str) -> Option<PathBuf> {
+    migrated_data_dir().map(|data| profile_dir(&data, current()).join(file))
+}
+
+/// The names of the profiles, the default and the current ones always
+/// among them.
+pub fn list() -> io::Result<Vec<String>> {
+    // The current one may have nothing recorded yet:
+    let mut names = vec![DEFAULT.to_string(), current().to_string()];
+    let Some(data) = migrated_data_dir() else {
+        names.dedup();
+        return Ok(names);
+    };
+    match std::fs::read_dir(data.join("profiles")) {
+        Ok(entries) => {
+            for entry in entries {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    names.extend(entry.file_name().into_string().ok());
+                }
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Makes the directory of a new profile.
+pub fn create(name: &str) -> Result<PathBuf, String> {
+    validate(name)?;
+    let data = migrated_data_dir().ok_or("Could not find the user data directory")?;
+    let dir = profile_dir(&data, name);
+    if dir.exists() || name == DEFAULT {
+        return Err(format!("The profile {name} already exists"));
+    }
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Could not create {}: {e}", dir.display()))?;
+    Ok(dir)
+}
+
+/// The directory of a profile that can be deleted: one that is not in
+/// use.
+pub fn deletable(name: &str) -> Result<PathBuf, String> {
+    validate(name)?;
+    if name == current() {
+        return Err(format!(
+            "The profile {name} is in use, choose another with --profile to delete it"
+        ));
+    }
+    let data = migrated_data_dir().ok_or("Could not find the user data directory")?;
+    let dir = profile_dir(&data, name);
+    if !dir.is_dir() {
+        return Err(format!("There is no profile named {name}"));
+    }
+    Ok(dir)
+}
+
+/// The section of each of the default settings of a profile: they are
+/// the options of `output`, but for the baseline of the FECR quiz.
+fn section(key: &str) -> &'static str {
+    match key {
+        "baseline" => "fecr-quiz",
+        _ => config::OUTPUT,
+    }
+}
+
+/// Reads the default settings of a profile.
+fn parse_settings(text: &str) -> Result<Vec<Setting>, String> {
+    let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let lines = config::key_lines(text);
+    Ok(table
+        .into_iter()
+        .map(|(key, value)| Setting {
+            section: section(&key).to_string(),
+            line: lines.get(&key).copied(),
+            key,
+            value,
+        })
+        .collect())
+}
+
+/// The default settings of the current profile, used for those not given
+/// on the command line (see `config::apply`), e.g.:
+///
+/// ```toml
+/// wpm = 25
+/// tone = 600.0
+/// charset = "latin"
+/// baseline = 430
+/// ```
+pub fn settings() -> Option<Layer> {
+    let path = path(SETTINGS)?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("Could not read {}: {e}", path.display());
+            return None;
+        }
+    };
+    match parse_settings(&text) {
+        Ok(settings) => Some(Layer {
+            origin: format!("profile {}", current()),
+            file: path.display().to_string(),
+            settings,
+        }),
+        Err(e) => {
+            warn!("Ignoring {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Changes one of the settings of the current profile in its file,
+/// keeping the others.
+pub fn save_setting(key: &str, value: toml::Value) -> Result<PathBuf, String> {
+    let path = path(SETTINGS).ok_or("Could not find the user data directory")?;
+    let mut table: toml::Table = match std::fs::read_to_string(&path) {
+        Ok(text) => text
+            .parse()
+            .map_err(|e: toml::de::Error| format!("Could not read {}: {e}", path.display()))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => toml::Table::new(),
+        Err(e) => return Err(format!("Could not read {}: {e}", path.display())),
+    };
+    table.insert(key.to_string(), value);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Could not create {}: {e}", dir.display()))?;
+    }
+    std::fs::write(&path, table.to_string())
+        .map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose() {
+        assert_eq!(choose(Some("cw"), Some("env"), Some("file")), "cw");
+        assert_eq!(choose(None, Some("env"), Some("file")), "env");
+        assert_eq!(choose(None, None, Some("file")), "file");
+        assert_eq!(choose(None, None, None), DEFAULT);
+        assert!(validate("field-day_2").is_ok());
+        assert!(validate("../other").is_err());
+        assert!(validate("").is_err());
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings = parse_settings("wpm = 25\ncharset = \"cyrillic\"\nbaseline = 430").unwrap();
+        let sections: Vec<_> = settings
+            .iter()
+            .map(|setting| (setting.section.as_str(), setting.key.as_str(), setting.line))
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                ("fecr-quiz", "baseline", Some(3)),
+                (config::OUTPUT, "charset", Some(2)),
+                (config::OUTPUT, "wpm", Some(1))
+            ]
+        );
+        assert!(parse_settings("wpm = ").is_err());
+    }
+
+    #[test]
+    fn test_migrate() {
+        let data = std::env::temp_dir().join(format!("profiles-{}", std::process::id()));
+        std::fs::create_dir_all(profile_dir(&data, DEFAULT)).unwrap();
+        std::fs::write(data.join("sessions.jsonl"), "old\n").unwrap();
+        std::fs::write(data.join("review.jsonl"), "old\n").unwrap();
+        // Already in the profile, so not to be overwritten:
+        std::fs::write(profile_dir(&data, DEFAULT).join("review.jsonl"), "new\n").unwrap();
+        assert_eq!(migrate(&data).unwrap(), ["sessions.jsonl"]);
+        let moved = profile_dir(&data, DEFAULT).join("sessions.jsonl");
+        assert_eq!(std::fs::read_to_string(moved).unwrap(), "old\n");
+        assert!(!data.join("sessions.jsonl").exists());
+        assert!(migrate(&data).unwrap().is_empty());
+        std::fs::remove_dir_all(&data).unwrap();
+    }
+}
@@ -0,0 +1,450 @@
+use crate::band::Band;
+use crate::callsign::random_callsign;
+use crate::codec::{encode, Separators};
+use crate::input::{read_copy, Copied, Flow, KeyReader};
+use crate::keyer::{Keyer, Timing, SAMPLE_RATE};
+use crate::morse::MorsePlayer;
+use rand::Rng;
+use std::io::{stdout, Write};
+use std::time::{Duration, Instant};
+use textwrap::{wrap, Options};
+
+/// The scripts that can be practiced by name.
+pub const SCRIPTS: [(&str, &str); 3] = [
+    (
+        "ragchew",
+        "# A relaxed contact, answering a CQ.
+them: CQ CQ CQ DE {CALL} {CALL} {CALL} K
+me: {CALL} DE {MYCALL} {MYCALL} K
+them: {MYCALL} DE {CALL} GM OM TNX FER CALL = UR RST {RST} {RST} = NAME {NAME} {NAME} = QTH {QTH} {QTH} = HW CPY? {MYCALL} DE {CALL} KN
+me: {CALL} DE {MYCALL} R TNX {NAME} = UR RST {MYRST} {MYRST} = NAME {MYNAME} {MYNAME} = QTH {MYQTH} {MYQTH} = {CALL} DE {MYCALL} KN
+them: {MYCALL} DE {CALL} R FB {MYNAME} TNX FER QSO = 73 ES GL <SK> {MYCALL} DE {CALL} EE
+",
+    ),
+    (
+        "contest",
+        "# Answering a station calling CQ in a contest, with a serial number.
+them: CQ TEST {CALL} {CALL}
+me: {MYCALL}
+them: {MYCALL} 5NN {NR}
+me: TU 5NN {MYNR}
+them: TU {CALL} TEST
+",
+    ),
+    (
+        "pota",
+        "# Hunting a Parks on the Air activator.
+them: CQ POTA CQ POTA DE {CALL} {CALL} {PARK} K
+me: {MYCALL}
+them: {MYCALL} GM UR {RST} {RST} {STATE} {STATE} BK
+me: BK TU UR {MYRST} {MYRST} {MYSTATE} {MYSTATE} 73 BK
+them: TU 73 {PARK} EE
+",
+    ),
+];
+
+/// The fields of a station that a script can fill in, e.g. `{CALL}` for
+/// the other station's callsign or `{MYCALL}` for your own.
+const FIELDS: [&str; 7] = ["CALL", "NAME", "QTH", "STATE", "RST", "NR", "PARK"];
+
+pub const NAMES: [&str; 24] = [
+    "BOB", "JIM", "TOM", "JOHN", "MIKE", "DAVE", "BILL", "STEVE", "MARY", "ANN", "SUE", "KEN",
+    "RON", "JOE", "PAUL", "ED", "AL", "DON", "HANS", "YURI", "KEIKO", "PIERRE", "LUIS", "JAN",
+];
+
+const CITIES: [&str; 20] = [
+    "BOSTON", "DENVER", "ATLANTA", "SEATTLE", "DALLAS", "CHICAGO", "PHOENIX", "TAMPA", "OMAHA",
+    "RENO", "TOLEDO", "TUCSON", "TORONTO", "OTTAWA", "LONDON", "BERLIN", "MUNICH", "TOKYO",
+    "OSAKA", "MADRID",
+];
+
+pub const STATES: [&str; 20] = [
+    "MA", "CO", "GA", "WA", "TX", "IL", "AZ", "FL", "NE", "NV", "OH", "CA", "NY", "PA", "MI", "OR",
+    "VA", "NC", "MN", "ON",
+];
+
+const REPORTS: [&str; 7] = ["599", "589", "579", "569", "559", "449", "339"];
+
+/// Who sends a line of a script.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    /// The other station, whose lines you copy.
+    Them,
+    /// You, whose lines are shown for you to send.
+    Me,
+}
+
+/// One transmission of a script.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub side: Side,
+    pub text: String,
+}
+
+/// Checks that the fields of a template, like `{CALL}`, are closed and
+/// known.
+pub fn check_fields(text: &str, known: impl Fn(&str) -> bool) -> Result<(), String> {
+    let mut rest = text;
+    while let Some((_, after)) = rest.split_once('{') {
+        let Some((field, after)) = after.split_once('}') else {
+            return Err("an unclosed {".to_string());
+        };
+        let field = field.trim().to_uppercase();
+        if !known(&field) {
+            return Err(format!("an unknown field {{{field}}}"));
+        }
+        rest = after;
+    }
+    Ok(())
+}
+
+/// Fills in the fields of a template, once they are checked.
+pub fn fill_fields(text: &str, mut value: impl FnMut(&str) -> String) -> String {
+    let mut filled = String::new();
+    let mut rest = text;
+    while let Some((before, after)) = rest.split_once('{') {
+        let (field, after) = after.split_once('}').unwrap_or((after, ""));
+        filled.push_str(before);
+        filled.push_str(&value(&field.trim().to_uppercase()));
+        rest = after;
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Reads a script: one transmission per line, starting with `them:` or
+/// `me:`, with fields like `{CALL}` filled in for each contact. Empty
+/// lines and comments starting with '#' are skipped.
+pub fn parse_script(text: &str) -> Result<Vec<Line>, String> {
+    let mut lines = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (side, text) = match line.split_once(':') {
+            Some((side, text)) if side.trim().eq_ignore_ascii_case("them") => (Side::Them, text),
+            Some((side, text)) if side.trim().eq_ignore_ascii_case("me") => (Side::Me, text),
+            _ => {
+                return Err(format!(
+                    "Line {} must start with 'them:' or 'me:'",
+                    number + 1
+                ))
+            }
+        };
+        check_fields(text, |field| {
+            FIELDS.contains(&field.strip_prefix("MY").unwrap_or(field))
+        })
+        .map_err(|e| format!("Line {} has {e}", number + 1))?;
+        lines.push(Line {
+            side,
+            text: text.trim().to_uppercase(),
+        });
+    }
+    if !lines.iter().any(|line| line.side == Side::Them) {
+        return Err("The script has no lines for the other station".to_string());
+    }
+    Ok(lines)
+}
+
+/// The details of one station in a contact.
+struct Station {
+    call: String,
+    name: String,
+    qth: String,
+    state: String,
+    rst: String,
+    nr: String,
+    park: String,
+}
+
+impl Station {
+    fn random(rng: &mut impl Rng) -> Self {
+        let mut pick = |list: &[&str]| list[rng.gen_range(0..list.len())].to_string();
+        let (name, qth, state, rst) = (pick(&NAMES), pick(&CITIES), pick(&STATES), pick(&REPORTS));
+        Station {
+            call: random_callsign(rng),
+            name,
+            qth,
+            state,
+            rst,
+            nr: rng.gen_range(1..=999).to_string(),
+            park: format!("K-{:04}", rng.gen_range(1..=9999)),
+        }
+    }
+
+    fn field(&self, field: &str) -> &str {
+        match field {
+            "CALL" => &self.call,
+            "NAME" => &self.name,
+            "QTH" => &self.qth,
+            "STATE" => &self.state,
+            "RST" => &self.rst,
+            "NR" => &self.nr,
+            _ => &self.park,
+        }
+    }
+}
+
+/// Fills in the fields of a line of a script.
+fn fill(text: &str, them: &Station, me: &Station) -> String {
+    fill_fields(text, |field| {
+        match field.strip_prefix("MY") {
+            Some(field) => me.field(field),
+            None => them.field(field),
+        }
+        .to_string()
+    })
+}
+
+/// How one word of a transmission was copied.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WordDiff {
+    Same(String),
+    Missed(String),
+    /// The word sent, and what was copied instead.
+    Wrong(String, String),
+    /// A word copied that was not sent.
+    Extra(String),
+}
+
+/// A prosign may be copied with or without its angle brackets.
+fn same_word(sent: &str, copied: &str) -> bool {
+    let bare = |word: &str| word.replace(['<', '>'], "").to_uppercase();
+    bare(sent) == bare(copied)
+}
+
+/// Compares the words copied with the words sent, lining them up so
+/// that one missed word does not make all of the words after it wrong.
+pub fn diff_words(sent: &str, copied: &str) -> Vec<WordDiff> {
+    let sent: Vec<&str> = sent.split_whitespace().collect();
+    let copied: Vec<&str> = copied.split_whitespace().collect();
+    // The longest common subsequence of the words after each position:
+    let mut common = vec![vec![0; copied.len() + 1]; sent.len() + 1];
+    for i in (0..sent.len()).rev() {
+        for j in (0..copied.len()).rev() {
+            common[i][j] = match same_word(sent[i], copied[j]) {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut missed, mut extra) = (Vec::new(), Vec::new());
+    // Pairs up the words missed and copied between two that match:
+    let flush = |diff: &mut Vec<WordDiff>, missed: &mut Vec<&str>, extra: &mut Vec<&str>| {
+        let mut extra_words = extra.drain(..);
+        for word in missed.drain(..) {
+            diff.push(match extra_words.next() {
+                Some(copy) => WordDiff::Wrong(word.to_string(), copy.to_uppercase()),
+                None => WordDiff::Missed(word.to_string()),
+            });
+        }
+        diff.extend(extra_words.map(|copy| WordDiff::Extra(copy.to_uppercase())));
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < sent.len() || j < copied.len() {
+        if i < sent.len() && j < copied.len() && same_word(sent[i], copied[j]) {
+            flush(&mut diff, &mut missed, &mut extra);
+            diff.push(WordDiff::Same(sent[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j == copied.len() || (i < sent.len() && common[i + 1][j] >= common[i][j + 1]) {
+            missed.push(sent[i]);
+            i += 1;
+        } else {
+            extra.push(copied[j]);
+            j += 1;
+        }
+    }
+    flush(&mut diff, &mut missed, &mut extra);
+    diff
+}
+
+/// The options of a simulated contact.
+pub struct Qso {
+    pub script: Vec<Line>,
+    pub mycall: Option<String>,
+    pub dot_duration: u32,
+    pub farnsworth: Option<u32>,
+    pub word_space: f32,
+    pub tone_freq: f32,
+    /// The noise, static crashes and fading to send it through.
+    pub band: Option<Band>,
+}
+
+/// Plays the other station's side of a contact, showing your side for
+/// you to send, and compares your copy of each transmission.
+///
+/// # Returns
+/// Each transmission copied, and how its words were copied.
+pub fn run_qso(qso: &Qso, rng: &mut impl Rng) -> Vec<(String, Vec<WordDiff>)> {
+    let paragraph = "QSO simulation.\n\nThe other station's transmissions will be played: type what you copy as you hear each one, then press Enter. Your own transmissions are shown for you to read. Press the ESC key to stop early.\n";
+    for line in wrap(paragraph, 70) {
+        println!("{}", line);
+    }
+
+    let them = Station::random(rng);
+    let mut me = Station::random(rng);
+    if let Some(call) = &qso.mycall {
+        me.call = call.to_uppercase();
+    }
+    println!("Your callsign is {}.\n", me.call);
+
+    let player = MorsePlayer::new();
+    let keys = KeyReader::start();
+    let timing = Timing::new(qso.dot_duration, qso.farnsworth).with_word_space(qso.word_space);
+    let mut copies = Vec::new();
+    let mut flow = Flow::new(None);
+    for line in &qso.script {
+        let text = fill(&line.text, &them, &me);
+        if line.side == Side::Me {
+            println!("  ME: {text}");
+            std::thread::sleep(Duration::from_millis(1500));
+            continue;
+        }
+        std::thread::sleep(Duration::from_millis(500));
+        let code = encode(&text, &Separators::default());
+        let raw = keys.raw_mode();
+        keys.clear();
+        print!("> ");
+        stdout().flush().unwrap();
+        // Copy while the transmission is being played:
+        let _sound = player.start_keyer(
+            Keyer::new(
+                &timing.key_events(&code),
+                SAMPLE_RATE,
+                qso.tone_freq,
+                0.5,
+                5.0,
+            )
+            .with_band(qso.band.clone()),
+        );
+        let copy = read_copy(&keys, &mut flow, Instant::now(), None, true);
+        drop(raw);
+        let Copied::Typed(typed, _) = copy else {
+            println!("\nQSO stopped.");
+            break;
+        };
+        println!();
+        let copied: String = typed.iter().map(|&(ch, _)| ch).collect();
+        let diff = diff_words(&text, &copied);
+        copies.push((text, diff));
+    }
+    copies
+}
+
+/// How many of the words sent were copied, and how many were sent.
+pub fn copied_words(copies: &[(String, Vec<WordDiff>)]) -> (usize, usize) {
+    let diffs = copies.iter().flat_map(|(_, diff)| diff);
+    let copied = diffs
+        .clone()
+        .filter(|word| matches!(word, WordDiff::Same(_)))
+        .count();
+    let sent = diffs
+        .filter(|word| !matches!(word, WordDiff::Extra(_)))
+        .count();
+    (copied, sent)
+}
+
+/// Prints each transmission with the words that were missed, or copied
+/// wrong, marked like `[-SENT-]{+COPIED+}`, and how many words were
+/// copied.
+pub fn print_report(copies: &[(String, Vec<WordDiff>)]) {
+    if copies.is_empty() {
+        return;
+    }
+    println!("\nYour copy, with [-missed-] and {{+wrong or extra+}} words marked:\n");
+    for (_, diff) in copies {
+        let words: Vec<String> = diff
+            .iter()
+            .map(|word| match word {
+                WordDiff::Same(word) => word.clone(),
+                WordDiff::Missed(word) => format!("[-{word}-]"),
+                WordDiff::Wrong(word, copy) => format!("[-{word}-]{{+{copy}+}}"),
+                WordDiff::Extra(copy) => format!("{{+{copy}+}}"),
+            })
+            .collect();
+        for line in wrap(&words.join(" "), Options::new(70).subsequent_indent("    ")) {
+            println!("{}", line);
+        }
+        println!();
+    }
+    let (copied, sent) = copied_words(copies);
+    println!(
+        "Copied {copied}/{sent} words ({:.0}%)",
+        copied as f64 / sent.max(1) as f64 * 100.0
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        assert_eq!(
+            parse_script("# test\nthem: cq de {call}\n\n Me : {CALL} de {mycall}\n").unwrap(),
+            vec![
+                Line {
+                    side: Side::Them,
+                    text: "CQ DE {CALL}".to_string()
+                },
+                Line {
+                    side: Side::Me,
+                    text: "{CALL} DE {MYCALL}".to_string()
+                },
+            ]
+        );
+        assert!(parse_script("CQ DE {CALL}").is_err());
+        assert!(parse_script("them: UR {GRID}").is_err());
+        assert!(parse_script("them: UR {RST").is_err());
+        assert!(parse_script("me: CQ").is_err());
+        for (name, script) in SCRIPTS {
+            assert!(parse_script(script).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_fill() {
+        let mut rng = rand::thread_rng();
+        let them = Station::random(&mut rng);
+        let mut me = Station::random(&mut rng);
+        me.call = "K1ABC".to_string();
+        assert_eq!(
+            fill("{MYCALL} DE {CALL} UR {RST}", &them, &me),
+            format!("K1ABC DE {} UR {}", them.call, them.rst)
+        );
+    }
+
+    #[test]
+    fn test_diff_words() {
+        use WordDiff::*;
+        let word = |w: &str| w.to_string();
+        assert_eq!(
+            diff_words("UR RST 599 NAME BOB <SK>", "ur rst 579 bob sk"),
+            vec![
+                Same(word("UR")),
+                Same(word("RST")),
+                Wrong(word("599"), word("579")),
+                Missed(word("NAME")),
+                Same(word("BOB")),
+                Same(word("<SK>")),
+            ]
+        );
+        assert_eq!(
+            diff_words("TU 73", "TU TU 73 EE"),
+            vec![
+                Same(word("TU")),
+                Extra(word("TU")),
+                Same(word("73")),
+                Extra(word("EE")),
+            ]
+        );
+        assert_eq!(
+            diff_words("CQ TEST", ""),
+            vec![Missed(word("CQ")), Missed(word("TEST"))]
+        );
+    }
+}
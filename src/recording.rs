@@ -0,0 +1,234 @@
+use crate::prelude::*;
+use crate::transcript::Rotation;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+/// When `listen --record` starts a new file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Split {
+    /// After this many minutes of audio.
+    Minutes(u64),
+    /// Before a file would get bigger than this many bytes.
+    Size(u64),
+}
+
+impl FromStr for Split {
+    type Err = String;
+
+    /// A number of minutes like `30min`, or a size like `100MB`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        if let Some(minutes) = lower.strip_suffix("min") {
+            return match minutes.trim().parse::<u64>() {
+                Ok(minutes) if minutes > 0 => Ok(Split::Minutes(minutes)),
+                _ => Err(format!(
+                    "Must be minutes like 30min, or a size like 100MB: {s}"
+                )),
+            };
+        }
+        match s.parse::<Rotation>() {
+            Ok(Rotation::Size(size)) => Ok(Split::Size(size)),
+            _ => Err(format!(
+                "Must be minutes like 30min, or a size like 100MB: {s}"
+            )),
+        }
+    }
+}
+
+/// What `listen --record` writes, and where.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordOptions {
+    pub path: PathBuf,
+    /// The audio of the first station as it comes out of the filter,
+    /// rather than every channel of the capture.
+    pub filtered: bool,
+    pub split: Option<Split>,
+}
+
+/// About the bytes of the header of a WAV file, to split by size.
+const HEADER_SIZE: u64 = 44;
+
+/// Writes the audio heard while listening to WAV files, as 32 bit float
+/// samples, exactly as they were captured, at the sample rate and with
+/// the channels of the capture. Split, the files are numbered, e.g.
+/// `heard-001.wav`, `heard-002.wav`.
+///
+/// The header is brought up to date every second, so that a file stays
+/// readable even if the program doesn't end well, and for good with
+/// `finish`.
+#[allow(dead_code)]
+pub struct Recorder {
+    options: RecordOptions,
+    writer: Option<WavWriter<BufWriter<File>>>,
+    // The channels and sample rate of the file being written:
+    spec: (u16, u32),
+    // The number of the file being written, from 1:
+    part: usize,
+    // Frames written to this file, and as the header was last updated:
+    frames: u64,
+    flushed: u64,
+    // The files written so far:
+    paths: Vec<PathBuf>,
+}
+
+#[allow(dead_code)]
+impl Recorder {
+    pub fn new(options: RecordOptions) -> Self {
+        Self {
+            options,
+            writer: None,
+            spec: (0, 0),
+            part: 0,
+            frames: 0,
+            flushed: 0,
+            paths: Vec::new(),
+        }
+    }
+
+    /// The path of a file, numbered when split.
+    fn path(&self, part: usize) -> PathBuf {
+        let path = &self.options.path;
+        if self.options.split.is_none() {
+            return path.clone();
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path
+            .extension()
+            .map_or(String::new(), |e| format!(".{}", e.to_string_lossy()));
+        path.with_file_name(format!("{stem}-{part:03}{extension}"))
+    }
+
+    /// Whether the file being written is full.
+    fn due(&self) -> bool {
+        let (channels, rate) = self.spec;
+        match self.options.split {
+            None => false,
+            Some(Split::Minutes(minutes)) => self.frames >= minutes * 60 * rate as u64,
+            Some(Split::Size(size)) => HEADER_SIZE + self.frames * channels as u64 * 4 >= size,
+        }
+    }
+
+    /// Starts the next file.
+    fn open(&mut self, channels: u16, rate: u32) -> Result<(), hound::Error> {
+        self.finish_file()?;
+        self.part += 1;
+        let path = self.path(self.part);
+        let spec = WavSpec {
+            channels,
+            sample_rate: rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        self.writer = Some(WavWriter::create(&path, spec)?);
+        self.spec = (channels, rate);
+        self.frames = 0;
+        self.flushed = 0;
+        self.paths.push(path);
+        Ok(())
+    }
+
+    /// Writes some frames of samples, with the channels interleaved. A
+    /// new file is started when the channels or the sample rate change,
+    /// and when one is full.
+    pub fn write(&mut self, samples: &[f32], channels: u16, rate: u32) -> Result<(), hound::Error> {
+        if samples.is_empty() || channels == 0 || rate == 0 {
+            return Ok(());
+        }
+        if self.writer.is_none() || self.spec != (channels, rate) || self.due() {
+            self.open(channels, rate)?;
+        }
+        let writer = self.writer.as_mut().expect("The file was opened");
+        for &sample in samples {
+            writer.write_sample(sample)?;
+        }
+        self.frames += (samples.len() / channels as usize) as u64;
+        if self.frames - self.flushed >= rate as u64 {
+            writer.flush()?;
+            self.flushed = self.frames;
+        }
+        Ok(())
+    }
+
+    /// Finishes the file being written, fixing up its header.
+    fn finish_file(&mut self) -> Result<(), hound::Error> {
+        match self.writer.take() {
+            Some(writer) => writer.finalize(),
+            None => Ok(()),
+        }
+    }
+
+    /// Finishes the recording.
+    ///
+    /// # Returns
+    /// The files written.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>, hound::Error> {
+        self.finish_file()?;
+        Ok(std::mem::take(&mut self.paths))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::WavReader;
+    use std::path::Path;
+
+    fn options(path: &Path, split: Option<Split>) -> RecordOptions {
+        RecordOptions {
+            path: path.to_path_buf(),
+            filtered: false,
+            split,
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!("30min".parse::<Split>(), Ok(Split::Minutes(30)));
+        assert_eq!("100MB".parse::<Split>(), Ok(Split::Size(100 << 20)));
+        assert!("0min".parse::<Split>().is_err());
+        assert!("daily".parse::<Split>().is_err());
+        assert!("soon".parse::<Split>().is_err());
+    }
+
+    #[test]
+    fn test_record() {
+        let path = std::env::temp_dir().join(format!("record-{}.wav", std::process::id()));
+        let mut recorder = Recorder::new(options(&path, None));
+        let frames: Vec<f32> = (0..4800).map(|i| (i % 2) as f32 * 0.5 - 0.25).collect();
+        recorder.write(&frames, 2, 2000).unwrap();
+        // Readable before it is finished, after a second:
+        assert_eq!(WavReader::open(&path).unwrap().duration(), 2400);
+        recorder.write(&frames, 2, 2000).unwrap();
+        assert_eq!(recorder.finish().unwrap(), vec![path.clone()]);
+        let mut reader = WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, 2000);
+        let samples: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+        assert_eq!(samples.len(), 9600);
+        assert_eq!(samples[..2], [-0.25, 0.25]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_record_split() {
+        let path = std::env::temp_dir().join(format!("split-{}.wav", std::process::id()));
+        // A file a block, and another when the rate changes:
+        let mut recorder = Recorder::new(options(&path, Some(Split::Size(44 + 4000))));
+        recorder.write(&[0.0; 1000], 1, 8000).unwrap();
+        recorder.write(&[0.0; 1000], 1, 8000).unwrap();
+        recorder.write(&[0.0; 500], 1, 16000).unwrap();
+        let paths = recorder.finish().unwrap();
+        assert_eq!(paths.len(), 3);
+        assert!(paths[0].ends_with(format!("split-{}-001.wav", std::process::id())));
+        assert_eq!(
+            WavReader::open(&paths[2]).unwrap().spec().sample_rate,
+            16000
+        );
+        for path in paths {
+            assert!(WavReader::open(&path).unwrap().duration() > 0);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::detect::{Station, TextOptions};
+use crate::filter::Detector;
+use crate::morse::text_to_morse;
+use crate::prelude::*;
+use crate::score::Score;
+use crate::wav::{decode_paced, read_channel};
+use std::time::{Duration, Instant};
+
+/// How fast `listen --replay` goes through the recordings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplaySpeed {
+    /// As fast as they can be decoded.
+    Max,
+    /// This many times the speed they were recorded at.
+    Times(f32),
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = String;
+
+    /// `max`, or a factor like `10x`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "max" {
+            return Ok(ReplaySpeed::Max);
+        }
+        match s.trim_end_matches('x').parse::<f32>() {
+            Ok(times) if times > 0.0 && times.is_finite() => Ok(ReplaySpeed::Times(times)),
+            _ => Err(format!("Speed must be 'max' or a factor like 10x: {s}")),
+        }
+    }
+}
+
+impl ReplaySpeed {
+    /// How many times faster than it was recorded the audio is paced
+    /// to, if it is.
+    fn pace(self) -> Option<f32> {
+        match self {
+            ReplaySpeed::Max => None,
+            ReplaySpeed::Times(times) => Some(times),
+        }
+    }
+}
+
+/// How long it took to decode some audio.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timing {
+    pub audio: Duration,
+    pub elapsed: Duration,
+}
+
+impl Timing {
+    /// Adds up the timings of several recordings.
+    fn add(&mut self, other: Timing) {
+        self.audio += other.audio;
+        self.elapsed += other.elapsed;
+    }
+}
+
+impl std::fmt::Display for Timing {
+    /// E.g. `12.5 s of audio in 0.210 s, 60x realtime`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let elapsed = self.elapsed.as_secs_f64();
+        write!(
+            f,
+            "{:.1} s of audio in {elapsed:.3} s, {:.0}x realtime",
+            self.audio.as_secs_f64(),
+            self.audio.as_secs_f64() / elapsed.max(1e-6)
+        )
+    }
+}
+
+/// Replays WAV recordings (e.g. of `listen --record`) through the
+/// decoder, printing the messages of each, how long it took, and, with
+/// a text file expected of each, how well it was decoded, then the
+/// totals of all of them.
+///
+/// Each station is decoded from its own channel, as with `listen
+/// --file`, all the messages of a recording making up the text that is
+/// compared to the one expected.
+#[allow(clippy::too_many_arguments)]
+pub fn replay(
+    files: &[String],
+    expect: &[String],
+    stations: &[Station],
+    bandwidth: f32,
+    threshold: Option<f32>,
+    debounce_duration: u32,
+    detector: Detector,
+    dot_duration: u32,
+    timeout_duration: u32,
+    adaptive_speed: bool,
+    text_options: &TextOptions,
+    output_morse: bool,
+    speed: ReplaySpeed,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !expect.is_empty() && expect.len() != files.len() {
+        return Err(format!(
+            "--expect needs a text for each recording: {} recordings and {} texts",
+            files.len(),
+            expect.len()
+        )
+        .into());
+    }
+    let mut total_timing = Timing::default();
+    let mut total_score = Score::default();
+    for (i, file) in files.iter().enumerate() {
+        if files.len() > 1 {
+            println!("{file}:");
+        }
+        let mut decoded = Vec::new();
+        let mut timing = Timing::default();
+        for station in stations {
+            let (samples, sample_rate) = read_channel(file, station.channel)
+                .map_err(|e| format!("Could not read {file}: {e}"))?;
+            let started = Instant::now();
+            decode_paced(
+                &samples,
+                sample_rate,
+                station.tone_freq,
+                station.tone_range,
+                bandwidth,
+                threshold,
+                debounce_duration,
+                detector,
+                dot_duration,
+                timeout_duration,
+                adaptive_speed,
+                text_options.clone(),
+                speed.pace(),
+                &mut |message| {
+                    let shown = match output_morse {
+                        true => text_to_morse(&message),
+                        false => message.clone(),
+                    };
+                    match &station.label {
+                        Some(label) => println!("{label} {shown}"),
+                        None => println!("{shown}"),
+                    }
+                    decoded.push(message);
+                },
+            )?;
+            timing.add(Timing {
+                audio: Duration::from_secs_f64(samples.len() as f64 / sample_rate.max(1) as f64),
+                elapsed: started.elapsed(),
+            });
+        }
+        println!("  {timing}");
+        total_timing.add(timing);
+        if let Some(path) = expect.get(i) {
+            let expected =
+                std::fs::read_to_string(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+            let score = Score::of(&decoded.join(" "), &expected);
+            println!("  {score}");
+            total_score.add(score);
+        }
+    }
+    if files.len() > 1 {
+        println!("{} recordings: {total_timing}", files.len());
+        if !expect.is_empty() {
+            println!("  {total_score}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_speed() {
+        assert_eq!("max".parse::<ReplaySpeed>(), Ok(ReplaySpeed::Max));
+        assert_eq!("10x".parse::<ReplaySpeed>(), Ok(ReplaySpeed::Times(10.0)));
+        assert_eq!("2.5".parse::<ReplaySpeed>(), Ok(ReplaySpeed::Times(2.5)));
+        assert!("0x".parse::<ReplaySpeed>().is_err());
+        assert!("fast".parse::<ReplaySpeed>().is_err());
+        let timing = Timing {
+            audio: Duration::from_secs(30),
+            elapsed: Duration::from_millis(500),
+        };
+        assert_eq!(
+            timing.to_string(),
+            "30.0 s of audio in 0.500 s, 60x realtime"
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use crate::json::{Json, Object};
+use crate::practice::DrillResults;
+use chrono::{Days, NaiveDate};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tabled::settings::style::Style;
+use tabled::{Table, Tabled};
+
+/// The ease of an item that was never reviewed.
+const START_EASE: f64 = 2.5;
+
+/// The ease never goes below this, or the item would come back every
+/// day forever.
+const MIN_EASE: f64 = 1.3;
+
+/// Where the schedule of the reviews is kept.
+pub fn default_path() -> Option<PathBuf> {
+    crate::profile::path("review.jsonl")
+}
+
+/// Grades an answer from 0 to 5, as SM-2 does: 1 when it was wrong,
+/// and from 3 to 5 when it was right, depending on how fast it was.
+///
+/// # Arguments
+/// * `fast` - The reaction time of a perfect answer.
+pub fn grade(correct: bool, reaction: Duration, fast: Duration) -> u8 {
+    match correct {
+        false => 1,
+        true if reaction <= fast => 5,
+        true if reaction <= fast * 2 => 4,
+        true => 3,
+    }
+}
+
+/// When a character or word is due to be reviewed again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Card {
+    pub item: String,
+    pub ease: f64,
+    /// The days between the last review and the next.
+    pub interval: u32,
+    /// How many times in a row it was answered right.
+    pub repetitions: u32,
+    pub due: NaiveDate,
+}
+
+impl Card {
+    /// A new item, due from today.
+    pub fn new(item: &str, today: NaiveDate) -> Self {
+        Card {
+            item: item.to_string(),
+            ease: START_EASE,
+            interval: 0,
+            repetitions: 0,
+            due: today,
+        }
+    }
+
+    /// Schedules the next review after an answer, graded from 0 to 5
+    /// (see `grade`). Below 3, the item starts over from the next day.
+    pub fn review(&mut self, quality: u8, today: NaiveDate) {
+        let quality = quality.min(5);
+        if quality < 3 {
+            self.repetitions = 0;
+            self.interval = 1;
+        } else {
+            self.interval = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (self.interval as f64 * self.ease).round() as u32,
+            };
+            self.repetitions += 1;
+        }
+        let miss = (5 - quality) as f64;
+        self.ease = (self.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+        self.due = today + Days::new(self.interval.into());
+    }
+
+    fn to_json(&self) -> String {
+        Object::new()
+            .string("item", self.item.as_str())
+            .fixed("ease", self.ease, 2)
+            .number("interval", self.interval)
+            .number("repetitions", self.repetitions)
+            .string("due", self.due.to_string().as_str())
+            .end()
+    }
+
+    fn from_json(line: &str) -> Option<Self> {
+        let json = Json::parse(line)?;
+        Some(Card {
+            item: json.get("item")?.as_str()?.to_string(),
+            ease: json.get("ease")?.as_f64()?,
+            interval: json.get("interval")?.as_f64()? as u32,
+            repetitions: json.get("repetitions")?.as_f64()? as u32,
+            due: json.get("due")?.as_str()?.parse().ok()?,
+        })
+    }
+}
+
+/// The cards of all of the items reviewed so far.
+#[derive(Debug, Default, PartialEq)]
+pub struct Schedule {
+    cards: Vec<Card>,
+}
+
+impl Schedule {
+    /// Reads the schedule, or an empty one if there is none yet.
+    ///
+    /// # Returns
+    /// The schedule, and how many of the lines could not be read, which
+    /// are skipped.
+    pub fn load(path: &Path) -> io::Result<(Self, usize)> {
+        let text = match std::fs::read(path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let mut schedule = Schedule::default();
+        let mut skipped = 0;
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            match Card::from_json(line) {
+                Some(card) if !schedule.contains(&card.item) => schedule.cards.push(card),
+                _ => skipped += 1,
+            }
+        }
+        Ok((schedule, skipped))
+    }
+
+    /// Writes the schedule. It is written to another file first, so that
+    /// the old one is kept whole if that fails.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temporary = path.with_extension("jsonl.new");
+        let mut file = std::fs::File::create(&temporary)?;
+        for card in &self.cards {
+            writeln!(file, "{}", card.to_json())?;
+        }
+        file.sync_all()?;
+        std::fs::rename(temporary, path)
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.cards.iter().any(|card| card.item == item)
+    }
+
+    /// The cards, the soonest due first.
+    pub fn cards(&self) -> Vec<&Card> {
+        let mut cards: Vec<&Card> = self.cards.iter().collect();
+        cards.sort_by(|a, b| a.due.cmp(&b.due).then(a.ease.total_cmp(&b.ease)));
+        cards
+    }
+
+    /// The items due for review by a day, the most overdue, then the
+    /// hardest, first.
+    pub fn due(&self, today: NaiveDate) -> Vec<String> {
+        self.cards()
+            .into_iter()
+            .filter(|card| card.due <= today)
+            .map(|card| card.item.clone())
+            .collect()
+    }
+
+    /// Reviews an item, with the grades of each time it was answered
+    /// in a session, adding it if it is new.
+    pub fn answer(&mut self, item: &str, grades: &[u8], today: NaiveDate) {
+        if grades.is_empty() {
+            return;
+        }
+        let quality = grades.iter().map(|&grade| grade as f64).sum::<f64>() / grades.len() as f64;
+        if !self.contains(item) {
+            self.cards.push(Card::new(item, today));
+        }
+        if let Some(card) = self.cards.iter_mut().find(|card| card.item == item) {
+            card.review(quality.round() as u8, today);
+        }
+    }
+
+    /// Reviews the characters and words copied in a drill.
+    ///
+    /// # Arguments
+    /// * `is_word` - Whether a group that was sent is a word, to be
+    ///   reviewed as a whole.
+    /// * `dot_duration` - The dot length the drill was sent at.
+    pub fn answer_drill(
+        &mut self,
+        results: &DrillResults,
+        is_word: impl Fn(&str) -> bool,
+        dot_duration: u32,
+        today: NaiveDate,
+    ) {
+        // Fast enough, as for the FECR score:
+        let fast = Duration::from_millis(dot_duration.into()) * 2;
+        let mut grades: Vec<(&str, Vec<u8>)> = Vec::new();
+        let mut add = |item, grade| match grades.iter_mut().find(|(i, _)| *i == item) {
+            Some((_, item_grades)) => item_grades.push(grade),
+            None => grades.push((item, vec![grade])),
+        };
+        let characters = &results.characters;
+        for (i, prompt) in characters.prompts.iter().enumerate() {
+            let correct = characters.responses[i] == Some(true);
+            // Not answered in time, it was too slow for more than a 3:
+            let reaction = characters.reaction_times[i].unwrap_or(Duration::MAX);
+            add(prompt.as_str(), grade(correct, reaction, fast));
+        }
+        for group in results.groups.iter().filter(|group| is_word(&group.sent)) {
+            let letters = group.sent.chars().count() as u32;
+            add(
+                group.sent.as_str(),
+                grade(group.exact, group.time, fast * letters),
+            );
+        }
+        for (item, grades) in grades {
+            self.answer(item, &grades, today);
+        }
+    }
+}
+
+/// Reads the schedule in the user data directory, skipping what can't
+/// be read.
+pub fn load_schedule() -> Schedule {
+    let Some(path) = default_path() else {
+        return Schedule::default();
+    };
+    match Schedule::load(&path) {
+        Ok((schedule, skipped)) => {
+            if skipped > 0 {
+                eprintln!("Skipped {skipped} unreadable cards in {}", path.display());
+            }
+            schedule
+        }
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path.display(), e);
+            Schedule::default()
+        }
+    }
+}
+
+/// Writes the schedule to the user data directory.
+pub fn save_schedule(schedule: &Schedule) {
+    let Some(path) = default_path() else {
+        return;
+    };
+    if let Err(e) = schedule.save(&path) {
+        eprintln!("Could not save the reviews in {}: {}", path.display(), e);
+    }
+}
+
+#[derive(Tabled)]
+struct CardRow {
+    item: String,
+    due: String,
+    interval: String,
+    ease: String,
+    repetitions: u32,
+}
+
+/// Prints when each item is due for review.
+pub fn print_schedule(schedule: &Schedule, today: NaiveDate) {
+    let cards = schedule.cards();
+    if cards.is_empty() {
+        println!("Nothing has been scheduled for review yet.");
+        return;
+    }
+    let rows: Vec<CardRow> = cards
+        .iter()
+        .map(|card| CardRow {
+            item: card.item.clone(),
+            due: match card.due <= today {
+                true => "today".to_string(),
+                false => card.due.to_string(),
+            },
+            interval: format!("{} days", card.interval),
+            ease: format!("{:.2}", card.ease),
+            repetitions: card.repetitions,
+        })
+        .collect();
+    println!("{}", Table::new(&rows).with(Style::rounded()));
+    println!("\nDue today: {}", schedule.due(today).len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fecr_quiz::QuizResult;
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 11, day).unwrap()
+    }
+
+    #[test]
+    fn test_review() {
+        let mut card = Card::new("K", day(1));
+        card.review(5, day(1));
+        assert_eq!((card.interval, card.due), (1, day(2)));
+        card.review(4, day(2));
+        assert_eq!((card.interval, card.due), (6, day(8)));
+        card.review(5, day(8));
+        // 6 days times the ease, which went 2.5, 2.6, 2.6, 2.7:
+        assert_eq!(card.interval, 16);
+        assert!((card.ease - 2.7).abs() < 1e-9, "{}", card.ease);
+        card.review(1, day(24));
+        assert_eq!((card.interval, card.repetitions), (1, 0));
+        assert!((card.ease - 2.16).abs() < 1e-9, "{}", card.ease);
+        for _ in 0..10 {
+            card.review(0, day(25));
+        }
+        assert_eq!(card.ease, MIN_EASE);
+    }
+
+    #[test]
+    fn test_grade() {
+        let ms = Duration::from_millis;
+        assert_eq!(grade(false, ms(100), ms(240)), 1);
+        assert_eq!(grade(true, ms(240), ms(240)), 5);
+        assert_eq!(grade(true, ms(400), ms(240)), 4);
+        assert_eq!(grade(true, ms(900), ms(240)), 3);
+    }
+
+    #[test]
+    fn test_answer_drill() {
+        let ms = Duration::from_millis;
+        // Both copied right, K too late to have a time:
+        let results = DrillResults {
+            characters: QuizResult {
+                prompts: ["K", "M"].map(String::from).into(),
+                responses: vec![Some(true), Some(true)],
+                reaction_times: vec![None, Some(ms(100))],
+                typed_ahead: vec![false; 2],
+                typed: vec![Some("K".to_string()), Some("M".to_string())],
+                sent_at: vec![chrono::Local::now(); 2],
+            },
+            groups: Vec::new(),
+            trials: Vec::new(),
+            review: Vec::new(),
+            paused: Duration::ZERO,
+            incomplete: false,
+        };
+        let mut schedule = Schedule::default();
+        schedule.answer_drill(&results, |_| false, 60, day(1));
+        let ease = |item| {
+            schedule
+                .cards
+                .iter()
+                .find(|card| card.item == item)
+                .unwrap()
+                .ease
+        };
+        // Graded 3 and 5:
+        assert!((ease("K") - 2.36).abs() < 1e-9, "{}", ease("K"));
+        assert!((ease("M") - 2.6).abs() < 1e-9, "{}", ease("M"));
+    }
+
+    #[test]
+    fn test_schedule() {
+        let mut schedule = Schedule::default();
+        schedule.answer("K", &[5, 5], day(1));
+        schedule.answer("M", &[1, 5], day(1));
+        schedule.answer("THE", &[2], day(1));
+        schedule.answer("U", &[], day(1));
+        assert!(!schedule.contains("U"));
+        assert_eq!(schedule.due(day(1)), Vec::<String>::new());
+        // The hardest first:
+        assert_eq!(schedule.due(day(2)), ["THE", "M", "K"]);
+
+        let path = std::env::temp_dir().join(format!("review-{}.jsonl", std::process::id()));
+        schedule.save(&path).unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"{\"item\":\"E\",\"ease\":2.5,\"inter")
+            .unwrap();
+        let (loaded, skipped) = Schedule::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(loaded.due(day(2)), ["THE", "M", "K"]);
+    }
+}
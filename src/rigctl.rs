@@ -0,0 +1,259 @@
+use crate::json::Object;
+use crate::prelude::*;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often the frequency and mode are asked of rigctld.
+const POLL: Duration = Duration::from_secs(2);
+/// The longest wait before connecting again to rigctld.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How long an answer of rigctld may take.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What the radio was tuned to, as rigctld last told it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RigState {
+    pub freq_hz: u64,
+    /// The mode, e.g. `CW`, `CWR` or `USB`.
+    pub mode: String,
+}
+
+/// The frequency and mode a message was heard on, with `--rigctl`,
+/// for the records and the events. Both are unknown while rigctld
+/// can't be reached.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tuning {
+    pub freq_hz: Option<u64>,
+    pub mode: Option<String>,
+}
+
+impl Tuning {
+    /// Adds the fields of the tuning to an object of JSON, e.g.
+    /// `"freq_hz":14025700,"mode":"CW"`.
+    pub fn json_fields(&self, object: Object) -> Object {
+        object
+            .number("freq_hz", self.freq_hz)
+            .string("mode", self.mode.as_deref())
+    }
+
+    /// The tuning, as text, e.g. `14025.70 kHz CW`.
+    pub fn to_text(&self) -> String {
+        let freq = self.freq_hz.map_or("--".to_string(), |freq| {
+            format!("{:.2}", freq as f64 / 1000.0)
+        });
+        match &self.mode {
+            Some(mode) => format!("{freq} kHz {mode}"),
+            None => format!("{freq} kHz"),
+        }
+    }
+}
+
+/// The frequency of a signal heard on a tone, tuned to a VFO frequency
+/// in a mode: the tone is above the VFO, except in the lower sideband
+/// modes.
+fn rf_freq(freq_hz: u64, mode: &str, tone: f32) -> u64 {
+    let tone = tone.round() as i64;
+    let offset = match mode {
+        "LSB" | "CWR" | "PKTLSB" => -tone,
+        _ => tone,
+    };
+    (freq_hz as i64 + offset).max(0) as u64
+}
+
+/// The radio, as a running rigctld (of Hamlib) tells what it is tuned
+/// to. It is asked on a thread every few seconds; when it can't be
+/// reached, it is tried again later, waiting longer each time.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Rig {
+    state: Arc<Mutex<Option<RigState>>>,
+    /// Whether to tag the messages with the frequency of the signal,
+    /// the VFO frequency with the offset of their tone.
+    follow: bool,
+}
+
+#[allow(dead_code)]
+impl Rig {
+    /// Starts asking rigctld at a `host:port`.
+    pub fn start(addr: &str, follow: bool) -> Self {
+        let rig = Rig {
+            state: Arc::new(Mutex::new(None)),
+            follow,
+        };
+        let state = rig.state.clone();
+        let addr = addr.to_string();
+        std::thread::spawn(move || poll(&addr, &state));
+        rig
+    }
+
+    /// What a message heard on a tone is tagged with.
+    pub fn tuning(&self, tone: f32) -> Tuning {
+        match &*self.state.lock().unwrap() {
+            Some(state) => Tuning {
+                freq_hz: Some(match self.follow {
+                    true => rf_freq(state.freq_hz, &state.mode, tone),
+                    false => state.freq_hz,
+                }),
+                mode: Some(state.mode.clone()),
+            },
+            None => Tuning::default(),
+        }
+    }
+}
+
+/// A connection to rigctld, asking it one command at a time.
+struct Connection {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Connection {
+    fn open(addr: &str) -> io::Result<Connection> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("No address for {addr}"))
+        })?;
+        let stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            stream,
+        })
+    }
+
+    /// Sends a command, and reads the lines of its answer.
+    fn ask(&mut self, command: &str, lines: usize) -> io::Result<Vec<String>> {
+        self.stream.write_all(format!("{command}\n").as_bytes())?;
+        let mut answer = Vec::new();
+        for _ in 0..lines {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let line = line.trim().to_string();
+            // An error is answered with a single line, e.g. `RPRT -11`:
+            if line.starts_with("RPRT") {
+                return Err(io::Error::other(format!("rigctld answered {line}")));
+            }
+            answer.push(line);
+        }
+        Ok(answer)
+    }
+
+    /// The frequency of the VFO (`f`), and its mode (`m`, answered with
+    /// the mode and the passband).
+    fn state(&mut self) -> io::Result<RigState> {
+        let freq = self.ask("f", 1)?;
+        let freq_hz = freq[0]
+            .parse::<f64>()
+            .map_err(|_| io::Error::other(format!("Not a frequency: {}", freq[0])))?;
+        let mode = self.ask("m", 2)?;
+        Ok(RigState {
+            freq_hz: freq_hz.round() as u64,
+            mode: mode[0].clone(),
+        })
+    }
+}
+
+/// Asks rigctld for the state of the radio, forever.
+fn poll(addr: &str, state: &Mutex<Option<RigState>>) {
+    let mut backoff = Duration::from_secs(1);
+    let mut reachable = true;
+    loop {
+        let result = Connection::open(addr).and_then(|mut connection| -> io::Result<()> {
+            loop {
+                let rig = connection.state()?;
+                if !reachable {
+                    info!("rigctld at {addr} is back");
+                    reachable = true;
+                }
+                backoff = Duration::from_secs(1);
+                *state.lock().unwrap() = Some(rig);
+                std::thread::sleep(POLL);
+            }
+        });
+        if let Err(e) = result {
+            if reachable {
+                warn!("Could not ask rigctld at {addr} for the frequency: {e}");
+                reachable = false;
+            }
+        }
+        *state.lock().unwrap() = None;
+        std::thread::sleep(backoff);
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_tuning() {
+        let tuning = Tuning {
+            freq_hz: Some(14025700),
+            mode: Some("CW".to_string()),
+        };
+        assert_eq!(
+            tuning.json_fields(Object::new()).end(),
+            "{\"freq_hz\":14025700,\"mode\":\"CW\"}"
+        );
+        assert_eq!(tuning.to_text(), "14025.70 kHz CW");
+        assert_eq!(
+            Tuning::default().json_fields(Object::new()).end(),
+            "{\"freq_hz\":null,\"mode\":null}"
+        );
+        assert_eq!(Tuning::default().to_text(), "-- kHz");
+        assert_eq!(rf_freq(14025000, "CW", 700.2), 14025700);
+        assert_eq!(rf_freq(7030000, "CWR", 600.0), 7029400);
+    }
+
+    #[test]
+    fn test_rig() {
+        // As rigctld answers, the mode with its passband:
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                let answer = match line.trim() {
+                    "f" => "14025000\n",
+                    "m" => "CW\n500\n",
+                    _ => "RPRT -1\n",
+                };
+                stream.write_all(answer.as_bytes()).unwrap();
+                line.clear();
+            }
+        });
+        let rig = Rig::start(&addr, true);
+        let mut tuning = Tuning::default();
+        for _ in 0..100 {
+            tuning = rig.tuning(650.0);
+            if tuning.freq_hz.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(
+            tuning,
+            Tuning {
+                freq_hz: Some(14025650),
+                mode: Some("CW".to_string()),
+            }
+        );
+
+        // Nothing listening, so nothing known:
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = closed.local_addr().unwrap().to_string();
+        drop(closed);
+        let rig = Rig::start(&addr, false);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(rig.tuning(650.0), Tuning::default());
+    }
+}
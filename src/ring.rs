@@ -0,0 +1,187 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A fixed size, lock free queue of samples from one producer thread to
+/// one consumer thread. Pushing never blocks or allocates, so it is
+/// safe to do from the realtime audio callback.
+#[allow(dead_code)]
+struct RingBuffer {
+    // Samples are stored as their bits, so they can be shared safely:
+    samples: Box<[AtomicU32]>,
+    // The number of samples ever written and read:
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    // The number of times samples were dropped because it was full:
+    overruns: AtomicUsize,
+}
+
+/// The writing end of a ring buffer.
+#[allow(dead_code)]
+pub struct Producer {
+    ring: Arc<RingBuffer>,
+}
+
+/// The reading end of a ring buffer.
+#[allow(dead_code)]
+pub struct Consumer {
+    ring: Arc<RingBuffer>,
+}
+
+/// Creates a ring buffer that holds up to `capacity` samples.
+#[allow(dead_code)]
+pub fn ring_buffer(capacity: usize) -> (Producer, Consumer) {
+    let ring = Arc::new(RingBuffer {
+        samples: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        overruns: AtomicUsize::new(0),
+    });
+    (Producer { ring: ring.clone() }, Consumer { ring })
+}
+
+#[allow(dead_code)]
+impl Producer {
+    /// Adds a sample, unless the buffer is full.
+    ///
+    /// # Returns
+    /// false if the sample was dropped.
+    pub fn push(&mut self, sample: f32) -> bool {
+        let ring = &self.ring;
+        let head = ring.head.load(Ordering::Relaxed);
+        if head - ring.tail.load(Ordering::Acquire) == ring.samples.len() {
+            return false;
+        }
+        ring.samples[head % ring.samples.len()].store(sample.to_bits(), Ordering::Relaxed);
+        ring.head.store(head + 1, Ordering::Release);
+        true
+    }
+
+    /// Adds a whole frame of samples, unless there isn't room for all
+    /// of them. The consumer never sees part of a frame.
+    ///
+    /// # Returns
+    /// false if the frame was dropped.
+    pub fn push_frame(&mut self, frame: &[f32]) -> bool {
+        let ring = &self.ring;
+        let head = ring.head.load(Ordering::Relaxed);
+        let used = head - ring.tail.load(Ordering::Acquire);
+        if used + frame.len() > ring.samples.len() {
+            return false;
+        }
+        for (i, &sample) in frame.iter().enumerate() {
+            ring.samples[(head + i) % ring.samples.len()]
+                .store(sample.to_bits(), Ordering::Relaxed);
+        }
+        ring.head.store(head + frame.len(), Ordering::Release);
+        true
+    }
+
+    /// Counts an overrun, when samples had to be dropped.
+    pub fn overrun(&mut self) {
+        self.ring.overruns.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[allow(dead_code)]
+impl Consumer {
+    /// Takes as many samples as are available, up to the size of `out`.
+    ///
+    /// # Returns
+    /// The number of samples written to `out`.
+    pub fn pop_slice(&mut self, out: &mut [f32]) -> usize {
+        let ring = &self.ring;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        let available = ring.head.load(Ordering::Acquire) - tail;
+        let count = available.min(out.len());
+        for (i, sample) in out[..count].iter_mut().enumerate() {
+            let bits = ring.samples[(tail + i) % ring.samples.len()].load(Ordering::Relaxed);
+            *sample = f32::from_bits(bits);
+        }
+        ring.tail.store(tail + count, Ordering::Release);
+        count
+    }
+
+    /// The number of samples that can be taken.
+    pub fn available(&self) -> usize {
+        let ring = &self.ring;
+        ring.head.load(Ordering::Acquire) - ring.tail.load(Ordering::Relaxed)
+    }
+
+    /// Drops up to `count` of the oldest samples, e.g. to catch up.
+    pub fn skip(&mut self, count: usize) {
+        let ring = &self.ring;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        let count = count.min(ring.head.load(Ordering::Acquire) - tail);
+        ring.tail.store(tail + count, Ordering::Release);
+    }
+
+    /// The number of overruns so far.
+    pub fn overruns(&self) -> usize {
+        self.ring.overruns.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_fills_and_drains() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        let mut out = [0.0; 8];
+        assert_eq!(consumer.pop_slice(&mut out), 0);
+        for i in 0..4 {
+            assert!(producer.push(i as f32));
+        }
+        assert!(!producer.push(4.0));
+        producer.overrun();
+        assert_eq!(consumer.overruns(), 1);
+        assert_eq!(consumer.pop_slice(&mut out[..3]), 3);
+        assert_eq!(out[..3], [0.0, 1.0, 2.0]);
+        // Wrap around the end of the buffer:
+        assert!(producer.push(5.0));
+        assert!(producer.push(6.0));
+        assert_eq!(consumer.pop_slice(&mut out), 3);
+        assert_eq!(out[..3], [3.0, 5.0, 6.0]);
+        // Catch up, dropping the oldest:
+        for i in 0..3 {
+            assert!(producer.push(i as f32));
+        }
+        assert_eq!(consumer.available(), 3);
+        consumer.skip(2);
+        assert_eq!(consumer.available(), 1);
+        consumer.skip(5);
+        assert_eq!(consumer.pop_slice(&mut out), 0);
+    }
+
+    #[test]
+    fn test_ring_buffer_whole_frames() {
+        let (mut producer, mut consumer) = ring_buffer(5);
+        assert!(producer.push_frame(&[1.0, 2.0]));
+        assert!(producer.push_frame(&[3.0, 4.0]));
+        assert!(!producer.push_frame(&[5.0, 6.0]));
+        let mut out = [0.0; 6];
+        assert_eq!(consumer.pop_slice(&mut out), 4);
+        assert_eq!(out[..4], [1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_ring_buffer_across_threads() {
+        let (mut producer, mut consumer) = ring_buffer(64);
+        let writer = std::thread::spawn(move || {
+            for i in 0..10_000 {
+                while !producer.push(i as f32) {
+                    std::thread::yield_now();
+                }
+            }
+        });
+        let mut received = Vec::new();
+        let mut out = [0.0; 16];
+        while received.len() < 10_000 {
+            let count = consumer.pop_slice(&mut out);
+            received.extend_from_slice(&out[..count]);
+        }
+        writer.join().unwrap();
+        assert!(received.iter().enumerate().all(|(i, &s)| s == i as f32));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::score::edit_distance;
+use std::str::FromStr;
+
+/// How many calls a speed ladder sends, unless told otherwise.
+pub const CALLS: u32 = 50;
+
+/// How many calls in a row the speed has to be held for, to count as
+/// sustained.
+pub const SUSTAINED: usize = 5;
+
+/// The slowest and the fastest speeds of a ladder, in WPM.
+const SPEEDS: (u32, u32) = (5, 99);
+
+/// How much the speed of a ladder goes up after a call copied exactly,
+/// or down after one that wasn't.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    Wpm(u32),
+    /// A share of the speed, always at least 1 WPM.
+    Percent(f32),
+}
+
+impl FromStr for Step {
+    type Err = String;
+
+    /// A number of WPM, or a percentage of the speed, e.g. `2`, `5%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("A step must be a number of WPM or a percentage, like 2 or 5%: {s}");
+        match s.trim().strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|percent| *percent > 0.0 && *percent < 100.0)
+                .map(Step::Percent)
+                .ok_or_else(invalid),
+            None => s
+                .trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|wpm| *wpm > 0)
+                .map(Step::Wpm)
+                .ok_or_else(invalid),
+        }
+    }
+}
+
+impl Step {
+    /// The speed after a step up or down from another.
+    fn apply(self, wpm: u32, up: bool) -> u32 {
+        let step = match self {
+            Step::Wpm(step) => step,
+            Step::Percent(percent) => ((wpm as f32 * percent / 100.0).round() as u32).max(1),
+        };
+        match up {
+            true => wpm + step,
+            false => wpm.saturating_sub(step),
+        }
+        .clamp(SPEEDS.0, SPEEDS.1)
+    }
+}
+
+/// How a call copied at some speed scores.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scoring {
+    /// The speed for each character copied, those that would have to
+    /// be changed left out (see `edit_distance`).
+    Partial,
+    /// The speed for each character, only for a call copied exactly.
+    Exact,
+}
+
+impl FromStr for Scoring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "partial" => Ok(Scoring::Partial),
+            "exact" => Ok(Scoring::Exact),
+            _ => Err(format!("Unknown scoring: {s}")),
+        }
+    }
+}
+
+impl Scoring {
+    fn points(self, wpm: u32, sent: &str, copied: &str, exact: bool) -> u32 {
+        let letters = sent.chars().count();
+        let copied_letters = match (self, exact) {
+            (_, true) => letters,
+            (Scoring::Partial, false) => letters.saturating_sub(edit_distance(sent, copied)),
+            (Scoring::Exact, false) => 0,
+        };
+        wpm * copied_letters as u32
+    }
+}
+
+/// A RufzXP style speed ladder: each call copied exactly raises the
+/// speed of the next by a step, each error lowers it, and the calls
+/// score by their speed.
+pub struct Ladder {
+    /// The speed of the next call.
+    pub wpm: u32,
+    up: Step,
+    down: Step,
+    scoring: Scoring,
+    pub score: u32,
+    // The speed of each call sent so far:
+    speeds: Vec<u32>,
+}
+
+impl Ladder {
+    pub fn new(wpm: u32, up: Step, down: Step, scoring: Scoring) -> Self {
+        Self {
+            wpm: wpm.clamp(SPEEDS.0, SPEEDS.1),
+            up,
+            down,
+            scoring,
+            score: 0,
+            speeds: Vec::new(),
+        }
+    }
+
+    /// Scores the copy of a call sent at the current speed, and steps
+    /// the speed up or down.
+    ///
+    /// # Returns
+    /// The points it scored.
+    pub fn answer(&mut self, sent: &str, copied: &str, exact: bool) -> u32 {
+        let points = self.scoring.points(self.wpm, sent, copied, exact);
+        self.score += points;
+        self.speeds.push(self.wpm);
+        self.wpm = match exact {
+            true => self.up.apply(self.wpm, true),
+            false => self.down.apply(self.wpm, false),
+        };
+        points
+    }
+
+    /// The fastest speed that the calls never went below for
+    /// `SUSTAINED` calls in a row, or None if fewer were sent.
+    pub fn sustained_wpm(&self) -> Option<u32> {
+        self.speeds
+            .windows(SUSTAINED)
+            .filter_map(|calls| calls.iter().min().copied())
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step() {
+        assert_eq!("2".parse::<Step>(), Ok(Step::Wpm(2)));
+        assert_eq!("5%".parse::<Step>(), Ok(Step::Percent(5.0)));
+        assert!("0".parse::<Step>().is_err());
+        assert!("fast".parse::<Step>().is_err());
+        assert_eq!(Step::Wpm(2).apply(20, true), 22);
+        assert_eq!(Step::Wpm(2).apply(6, false), 5);
+        assert_eq!(Step::Percent(10.0).apply(30, true), 33);
+        // Always at least 1 WPM:
+        assert_eq!(Step::Percent(1.0).apply(20, false), 19);
+    }
+
+    #[test]
+    fn test_ladder() {
+        let mut ladder = Ladder::new(20, Step::Wpm(2), Step::Wpm(3), Scoring::Partial);
+        assert_eq!(ladder.answer("K1ABC", "K1ABC", true), 100);
+        assert_eq!(ladder.wpm, 22);
+        // One character of four wrong, at 22 WPM:
+        assert_eq!(ladder.answer("W2XY", "W2XV", false), 66);
+        assert_eq!(ladder.wpm, 19);
+        assert_eq!(ladder.score, 166);
+        assert_eq!(ladder.sustained_wpm(), None);
+        for _ in 0..6 {
+            ladder.answer("N3Z", "N3Z", true);
+        }
+        // 20, 22, 19, 21, 23, 25, 27, 29: 21 from the fourth call on.
+        assert_eq!(ladder.sustained_wpm(), Some(21));
+        let mut exact = Ladder::new(20, Step::Wpm(2), Step::Wpm(2), Scoring::Exact);
+        assert_eq!(exact.answer("W2XY", "W2XV", false), 0);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::detect::WINDOW_MS;
+use crate::prelude::*;
+use std::collections::VecDeque;
+
+/// The channels of a scan can't be closer than this, in Hz, as their
+/// detectors would need windows too long to follow the keying.
+const MIN_STEP: f32 = 20.0;
+
+/// A scan decodes at most this many channels.
+const MAX_CHANNELS: usize = 200;
+
+/// Levels below this part of the loudest one are left out, as what the
+/// taper leaks of the tones of the other channels.
+const LEAKAGE: f32 = 0.07;
+
+/// The audio frequencies decoded at once by `listen --scan`, e.g.
+/// `400:900:50` for a channel every 50 Hz from 400 to 900 Hz.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScanRange {
+    pub low: f32,
+    pub high: f32,
+    pub step: f32,
+}
+
+impl FromStr for ScanRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<f32> = s
+            .split(':')
+            .map(|part| part.trim().parse::<f32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("Must be LOW:HIGH:STEP in Hz, e.g. 400:900:50: {s}"))?;
+        let [low, high, step] = parts[..] else {
+            return Err(format!("Must be LOW:HIGH:STEP in Hz, e.g. 400:900:50: {s}"));
+        };
+        if low <= 0.0 || high < low {
+            return Err(format!("The range must go up from above 0 Hz: {s}"));
+        }
+        if step < MIN_STEP {
+            return Err(format!("The step must be at least {MIN_STEP} Hz: {s}"));
+        }
+        let range = ScanRange { low, high, step };
+        if range.freqs().len() > MAX_CHANNELS {
+            return Err(format!(
+                "At most {MAX_CHANNELS} channels can be scanned: {s}"
+            ));
+        }
+        Ok(range)
+    }
+}
+
+impl ScanRange {
+    /// The frequency of each channel, from the lowest.
+    pub fn freqs(&self) -> Vec<f32> {
+        let count = ((self.high - self.low) / self.step + 1e-3).floor() as usize + 1;
+        (0..count)
+            .map(|i| self.low + i as f32 * self.step)
+            .collect()
+    }
+}
+
+/// The label of the station of a channel, e.g. `650 Hz:`.
+pub fn label(freq: f32) -> String {
+    format!("{freq:.0} Hz:")
+}
+
+/// A bank of Goertzel detectors, one for each channel of a scan, all
+/// fed the same samples. The samples are tapered once for all of
+/// them, and each window is measured in a single pass, so a channel
+/// only costs a few multiplications a sample, with no filter of its
+/// own. The levels go to the `SignalPipeline` of each channel, given
+/// with `push_level`.
+pub struct ScanBank {
+    freqs: Vec<f32>,
+    step: f32,
+    sample_rate: u32,
+    // The Goertzel coefficient of each channel, at the sample rate:
+    coeffs: Vec<f64>,
+    // The Hann window the samples are tapered with:
+    taper: Vec<f64>,
+    // Makes up for what the taper takes away from a tone:
+    gain: f64,
+    // The latest samples, as many as are measured at once:
+    history: VecDeque<f32>,
+    // How many samples a window of the pipelines has, and how many
+    // came since the last one:
+    window: usize,
+    fresh: usize,
+}
+
+impl ScanBank {
+    pub fn new(range: &ScanRange) -> Self {
+        Self {
+            freqs: range.freqs(),
+            step: range.step,
+            sample_rate: 0,
+            coeffs: Vec::new(),
+            taper: Vec::new(),
+            gain: 1.0,
+            history: VecDeque::new(),
+            window: 0,
+            fresh: 0,
+        }
+    }
+
+    /// The frequency of each channel, from the lowest.
+    #[allow(dead_code)]
+    pub fn freqs(&self) -> &[f32] {
+        &self.freqs
+    }
+
+    /// How many samples each set of levels is for.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Sets the detectors up for another sample rate. The samples are
+    /// measured over a period of the step, to tell the channels apart,
+    /// every `WINDOW_MS` as the pipelines expect.
+    fn rebuild(&mut self, rate: u32) {
+        self.sample_rate = rate;
+        self.window = (rate * WINDOW_MS / 1000).max(1) as usize;
+        let length = ((rate as f32 / self.step) as usize).max(self.window);
+        self.coeffs = self
+            .freqs
+            .iter()
+            .map(|&freq| 2.0 * (2.0 * std::f64::consts::PI * freq as f64 / rate as f64).cos())
+            .collect();
+        self.taper = (0..length)
+            .map(|i| {
+                let phase = 2.0 * std::f64::consts::PI * (i as f64 + 0.5) / length as f64;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+        self.gain = length as f64 / self.taper.iter().sum::<f64>();
+        self.history = std::iter::repeat_n(0.0, length).collect();
+        self.fresh = 0;
+    }
+
+    /// Measures the next block of mono samples.
+    ///
+    /// # Returns
+    /// The level of each channel, for each window completed.
+    pub fn push(&mut self, samples: &[f32], rate: u32) -> Vec<Vec<f32>> {
+        let mut windows = Vec::new();
+        if samples.is_empty() || rate == 0 {
+            return windows;
+        }
+        if rate != self.sample_rate {
+            self.rebuild(rate);
+        }
+        for &sample in samples {
+            self.history.pop_front();
+            self.history.push_back(sample);
+            self.fresh += 1;
+            if self.fresh == self.window {
+                self.fresh = 0;
+                windows.push(self.levels());
+            }
+        }
+        windows
+    }
+
+    /// The level of each channel over the latest samples: the amplitude
+    /// of the tone at its frequency. Unlike `goertzel_level`, it isn't
+    /// a part of the whole power, which the tones of the other channels
+    /// would make swing as they are keyed. A channel only hears a tone
+    /// when it is louder there than on the channels beside it, as one
+    /// tone is heard a little on those, and above the leakage of the
+    /// loudest.
+    fn levels(&self) -> Vec<f32> {
+        let n = self.taper.len() as f64;
+        let mut states = vec![(0.0, 0.0); self.coeffs.len()];
+        for (&sample, &taper) in self.history.iter().zip(&self.taper) {
+            let x = sample as f64 * taper;
+            for ((s1, s2), &coeff) in states.iter_mut().zip(&self.coeffs) {
+                let s0 = x + coeff * *s1 - *s2;
+                *s2 = *s1;
+                *s1 = s0;
+            }
+        }
+        let tones: Vec<f32> = states
+            .iter()
+            .zip(&self.coeffs)
+            .map(|(&(s1, s2), &coeff)| {
+                let magnitude_squared = (s1 * s1 + s2 * s2 - coeff * s1 * s2).max(0.0);
+                (2.0 * magnitude_squared.sqrt() / n * self.gain).min(1.0) as f32
+            })
+            .collect();
+        let loudest = tones.iter().fold(0.0, |a: f32, &b| a.max(b));
+        (0..tones.len())
+            .map(|i| {
+                let beside = [i.checked_sub(1), Some(i + 1)]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|j| tones.get(j))
+                    .fold(0.0, |a: f32, &b| a.max(b));
+                match beside > tones[i] || tones[i] <= loudest * LEAKAGE {
+                    true => 0.0,
+                    false => tones[i],
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_range() {
+        let range: ScanRange = "400:900:50".parse().unwrap();
+        let freqs = range.freqs();
+        assert_eq!(freqs.len(), 11);
+        assert_eq!(freqs[0], 400.0);
+        assert_eq!(freqs[10], 900.0);
+        assert_eq!(
+            "500:500:50".parse::<ScanRange>().unwrap().freqs(),
+            vec![500.0]
+        );
+        assert!("400:900".parse::<ScanRange>().is_err());
+        assert!("900:400:50".parse::<ScanRange>().is_err());
+        assert!("400:900:5".parse::<ScanRange>().is_err());
+        assert!("100:9000:20".parse::<ScanRange>().is_err());
+        assert_eq!(label(650.0), "650 Hz:");
+    }
+
+    #[test]
+    fn test_bank_levels() {
+        let rate = 48000;
+        let mut bank = ScanBank::new(&"400:900:50".parse().unwrap());
+        let samples: Vec<f32> = (0..rate / 10)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 650.0 * i as f32 / rate as f32).sin())
+            .collect();
+        let windows = bank.push(&samples, rate);
+        assert_eq!(windows.len(), 10);
+        assert_eq!(bank.window(), 480);
+        let levels = windows.last().unwrap();
+        assert!((levels[5] - 0.5).abs() < 0.01, "650 Hz: {}", levels[5]);
+        // Not on the channels beside it, nor further:
+        for (i, level) in levels.iter().enumerate().filter(|&(i, _)| i != 5) {
+            assert_eq!(*level, 0.0, "channel {i}");
+        }
+        assert!(bank
+            .push(&[0.0; 4800], rate)
+            .last()
+            .unwrap()
+            .iter()
+            .all(|&l| l == 0.0));
+    }
+}
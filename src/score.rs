@@ -0,0 +1,147 @@
+/// How many characters have to be typed, left out or changed to make a
+/// text another, the Levenshtein distance.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    distance(&a, &b)
+}
+
+/// How many items have to be added, left out or changed to make a list
+/// another, e.g. its characters or its words.
+fn distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, item) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + (*item != b[j]) as usize)
+                .min(above + 1)
+                .min(row[j] + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// A text as it is compared: in upper case, its words a space apart,
+/// whatever lines and spaces were between them.
+pub fn normalize(text: &str) -> String {
+    text.to_uppercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// How well a text was decoded, as the characters (or the words) of
+/// the expected one that had to be changed, e.g. over a corpus of
+/// recordings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Score {
+    /// The characters (or words) typed, left out or changed, see
+    /// `edit_distance`.
+    pub errors: usize,
+    /// The characters (or words) of the expected text.
+    pub length: usize,
+}
+
+impl Score {
+    /// Compares a decoded text to the one expected, once normalized.
+    pub fn of(decoded: &str, expected: &str) -> Self {
+        let (decoded, expected) = (normalize(decoded), normalize(expected));
+        Self {
+            errors: edit_distance(&decoded, &expected),
+            length: expected.chars().count(),
+        }
+    }
+
+    /// Compares the words of a decoded text to those of the one
+    /// expected, a word decoded wrong being one error however many of
+    /// its characters were.
+    pub fn of_words(decoded: &str, expected: &str) -> Self {
+        let (decoded, expected) = (normalize(decoded), normalize(expected));
+        let decoded: Vec<&str> = decoded.split(' ').filter(|w| !w.is_empty()).collect();
+        let expected: Vec<&str> = expected.split(' ').filter(|w| !w.is_empty()).collect();
+        Self {
+            errors: distance(&decoded, &expected),
+            length: expected.len(),
+        }
+    }
+
+    /// The error rate, from 0 (or above 1 when much more was decoded
+    /// than expected).
+    pub fn error_rate(&self) -> f64 {
+        match self.length {
+            0 => (self.errors > 0) as u8 as f64,
+            length => self.errors as f64 / length as f64,
+        }
+    }
+
+    /// Adds up the scores of several texts.
+    pub fn add(&mut self, other: Score) {
+        self.errors += other.errors;
+        self.length += other.length;
+    }
+}
+
+impl std::fmt::Display for Score {
+    /// E.g. `CER 2.1% (3 errors in 142 characters)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = match self.errors {
+            1 => "",
+            _ => "s",
+        };
+        write!(
+            f,
+            "CER {:.1}% ({} error{plural} in {} characters)",
+            self.error_rate() * 100.0,
+            self.errors,
+            self.length
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("KITTEN", "SITTING"), 3);
+        assert_eq!(edit_distance("", "TU"), 2);
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(normalize(" cq  cq\nde k1abc "), "CQ CQ DE K1ABC");
+        let score = Score::of("CQ CQ DE K1ABD", "cq cq de k1abc\n");
+        assert_eq!(
+            score,
+            Score {
+                errors: 1,
+                length: 14
+            }
+        );
+        assert_eq!(score.to_string(), "CER 7.1% (1 error in 14 characters)");
+        let mut total = score;
+        total.add(Score::of("TNX", "TNX"));
+        assert_eq!(
+            total,
+            Score {
+                errors: 1,
+                length: 17
+            }
+        );
+        assert_eq!(Score::of("", "").error_rate(), 0.0);
+        assert_eq!(Score::of("E", "").error_rate(), 1.0);
+        let words = Score::of_words("CQ CQ DE K1ABD", "CQ CQ DE K1ABC");
+        assert_eq!(
+            words,
+            Score {
+                errors: 1,
+                length: 4
+            }
+        );
+        assert_eq!(Score::of_words("CQ DE", "CQ CQ DE").errors, 1);
+    }
+}
@@ -0,0 +1,437 @@
+use crate::band::{Band, Conditions, Qsb};
+use crate::koch;
+use crate::morse::{wpm_to_dot_length, Charset};
+use crate::practice::{parse_pool, run_drill, split_characters, Drill, DrillResults, Groups};
+use crate::words::{read_words, WordPicker, WORDS};
+use rand::Rng;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tabled::settings::style::Style;
+use tabled::{Table, Tabled};
+
+/// What a section of a drill script sends.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Items {
+    /// Some text, a group per word, sent in order.
+    Text(String),
+    /// Random groups of the characters of a pool, e.g. of a Koch lesson.
+    Groups(Vec<String>),
+    Callsigns,
+    Words,
+}
+
+/// A part of a drill script, sent at its own speed, tone and band
+/// conditions, those left out being the ones of the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub name: String,
+    pub items: Items,
+    /// How many times a text is sent, or how many items are generated,
+    /// unless it goes on for some minutes.
+    pub count: Option<u32>,
+    pub minutes: Option<f32>,
+    pub wpm: Option<u32>,
+    pub farnsworth: Option<u32>,
+    pub tone: Option<f32>,
+    pub snr: Option<f32>,
+    pub qrn: Option<f32>,
+    pub qsb: Option<Qsb>,
+}
+
+/// A custom drill, its sections sent one after the other.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    pub name: String,
+    pub sections: Vec<Section>,
+}
+
+/// The fields that a section may have.
+const FIELDS: [&str; 12] = [
+    "name",
+    "text",
+    "generate",
+    "repeat",
+    "count",
+    "minutes",
+    "wpm",
+    "farnsworth",
+    "tone",
+    "noise_snr",
+    "qrn",
+    "qsb",
+];
+
+/// Reads a drill script, in TOML: an optional `name`, and the
+/// `[[section]]` tables in the order they are sent, e.g.
+///
+/// ```toml
+/// name = "Warmup"
+///
+/// [[section]]
+/// name = "callsigns"
+/// generate = "callsigns"
+/// count = 10
+/// wpm = 22
+/// ```
+///
+/// # Arguments
+/// * `charset` - The alphabet of the pools of `generate = "groups ..."`.
+///
+/// # Returns
+/// The script, or an error naming the section and the field that is
+/// wrong.
+pub fn parse_script(text: &str, charset: Charset) -> Result<Script, String> {
+    let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    if let Some(key) = table
+        .keys()
+        .find(|key| !["name", "section"].contains(&key.as_str()))
+    {
+        return Err(format!(
+            "Unknown field {key}: a script has a name and sections"
+        ));
+    }
+    let name = match table.get("name") {
+        Some(toml::Value::String(name)) => name.clone(),
+        Some(_) => return Err("The name of the script must be a string".to_string()),
+        None => "script".to_string(),
+    };
+    let sections = match table.get("section") {
+        Some(toml::Value::Array(sections)) if !sections.is_empty() => sections,
+        _ => return Err("A script needs some [[section]] tables".to_string()),
+    };
+    let sections = sections
+        .iter()
+        .enumerate()
+        .map(|(i, section)| match section {
+            toml::Value::Table(section) => parse_section(section, i + 1, charset),
+            _ => Err(format!("Section {} must be a [[section]] table", i + 1)),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Script { name, sections })
+}
+
+fn parse_section(table: &toml::Table, number: usize, charset: Charset) -> Result<Section, String> {
+    let label = match table.get("name") {
+        Some(toml::Value::String(name)) => format!("Section {number} ({name})"),
+        _ => format!("Section {number}"),
+    };
+    let invalid = |field: &str, what: &str| format!("{label}: {field} must be {what}");
+    if let Some(key) = table.keys().find(|key| !FIELDS.contains(&key.as_str())) {
+        return Err(format!(
+            "{label}: unknown field {key}, expected one of {}",
+            FIELDS.join(", ")
+        ));
+    }
+    let string = |field: &str| match table.get(field) {
+        Some(toml::Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(invalid(field, "a string")),
+        None => Ok(None),
+    };
+    let whole = |field: &str| match table.get(field) {
+        Some(toml::Value::Integer(value)) if *value > 0 => Ok(Some(*value as u32)),
+        Some(_) => Err(invalid(field, "a whole number above 0")),
+        None => Ok(None),
+    };
+    let number = |field: &str, positive: bool| {
+        let value = match table.get(field) {
+            Some(toml::Value::Integer(value)) => Some(*value as f32),
+            Some(toml::Value::Float(value)) => Some(*value as f32),
+            Some(_) => return Err(invalid(field, "a number")),
+            None => None,
+        };
+        match value {
+            Some(value) if positive && value <= 0.0 => Err(invalid(field, "above 0")),
+            value => Ok(value),
+        }
+    };
+    let name = string("name")?.ok_or_else(|| format!("{label}: name is missing"))?;
+    let items = match (string("text")?, string("generate")?) {
+        (Some(text), None) if !text.trim().is_empty() => Items::Text(text),
+        (Some(_), None) => return Err(invalid("text", "some text to send")),
+        (None, Some(spec)) => generator(&spec, charset).map_err(|e| format!("{label}: {e}"))?,
+        (Some(_), Some(_)) => return Err(format!("{label}: has both text and generate")),
+        (None, None) => return Err(format!("{label}: needs a text or a generate")),
+    };
+    let count = match (&items, whole("repeat")?, whole("count")?) {
+        (_, Some(_), Some(_)) => return Err(format!("{label}: has both repeat and count")),
+        (Items::Text(_), repeat, None) => repeat,
+        (Items::Text(_), None, Some(_)) => {
+            return Err(format!("{label}: count is for generated items, use repeat"))
+        }
+        (_, Some(_), None) => return Err(format!("{label}: repeat is for a text, use count")),
+        (_, None, count) => count,
+    };
+    let minutes = number("minutes", true)?;
+    if matches!(items, Items::Text(_)) && minutes.is_some() {
+        return Err(format!("{label}: minutes is for generated items"));
+    }
+    if !matches!(items, Items::Text(_)) && count.is_none() && minutes.is_none() {
+        return Err(format!("{label}: needs a count or minutes"));
+    }
+    let wpm = whole("wpm")?;
+    let farnsworth = whole("farnsworth")?;
+    if let (Some(wpm), Some(farnsworth)) = (wpm, farnsworth) {
+        if farnsworth > wpm {
+            return Err(invalid("farnsworth", "a speed no faster than wpm"));
+        }
+    }
+    let qsb = match string("qsb")? {
+        Some(qsb) => Some(qsb.parse::<Qsb>().map_err(|e| format!("{label}: {e}"))?),
+        None => None,
+    };
+    Ok(Section {
+        name,
+        items,
+        count,
+        minutes,
+        wpm,
+        farnsworth,
+        tone: number("tone", true)?,
+        snr: number("noise_snr", false)?,
+        qrn: number("qrn", true)?,
+        qsb,
+    })
+}
+
+/// Reads what a section generates: `callsigns`, `words`, `koch N` for
+/// the characters of a Koch lesson, or `groups POOL` (see
+/// `parse_pool`).
+fn generator(spec: &str, charset: Charset) -> Result<Items, String> {
+    let (kind, argument) = spec.trim().split_once(' ').unwrap_or((spec.trim(), ""));
+    match (kind, argument.trim()) {
+        ("callsigns", "") => Ok(Items::Callsigns),
+        ("words", "") => Ok(Items::Words),
+        ("koch", lesson) => match lesson.parse::<u32>() {
+            Ok(lesson) if (1..=koch::last_lesson()).contains(&lesson) => Ok(Items::Groups(
+                koch::lesson_characters(lesson)
+                    .chars()
+                    .map(String::from)
+                    .collect(),
+            )),
+            _ => Err(format!(
+                "generate koch needs a lesson from 1 to {}, like \"koch 5\"",
+                koch::last_lesson()
+            )),
+        },
+        ("groups", pool) if !pool.is_empty() => parse_pool(pool, charset).map(Items::Groups),
+        _ => Err(format!(
+            "generate must be callsigns, words, koch N or groups POOL, not \"{spec}\""
+        )),
+    }
+}
+
+impl Section {
+    /// What the drill of the section sends, and how many groups and
+    /// for how long at most.
+    fn groups(&self, size: (usize, usize)) -> (Groups, Option<u32>, Option<Duration>) {
+        let duration = self
+            .minutes
+            .map(|minutes| Duration::from_secs_f32(minutes * 60.0));
+        match &self.items {
+            Items::Text(text) => {
+                let words: Vec<Vec<String>> =
+                    text.split_whitespace().map(split_characters).collect();
+                let groups: VecDeque<Vec<String>> = (0..self.count.unwrap_or(1))
+                    .flat_map(|_| words.iter().cloned())
+                    .collect();
+                let count = groups.len() as u32;
+                (Groups::Sequence(groups), Some(count), None)
+            }
+            Items::Groups(pool) => (
+                Groups::Random {
+                    pool: pool.clone(),
+                    size,
+                    weights: None,
+                },
+                self.count,
+                duration,
+            ),
+            Items::Callsigns => (Groups::Callsigns(None), self.count, duration),
+            Items::Words => (
+                Groups::Words(WordPicker::new(read_words(WORDS, (2, 6)))),
+                self.count,
+                duration,
+            ),
+        }
+    }
+}
+
+/// How one section of a script was copied.
+pub struct SectionResults {
+    pub name: String,
+    pub groups: usize,
+    pub exact: usize,
+    pub wpm: Option<u32>,
+}
+
+/// Runs the sections of a script one after the other, as drills with
+/// the options of `drill` but for those of each section. It stops
+/// with the first section that is stopped.
+///
+/// # Arguments
+/// * `size` - The smallest and largest number of characters of the
+///   random groups.
+/// * `conditions` - The band of the command line, that the conditions
+///   of a section are added to.
+/// * `seed` - The seed of the noise of the sections.
+///
+/// # Returns
+/// The results of all the sections together, and of each of them.
+pub fn run_script<R: Rng>(
+    script: &Script,
+    drill: &mut Drill,
+    size: (usize, usize),
+    conditions: Option<Conditions>,
+    seed: u64,
+    rng: &mut R,
+) -> (DrillResults, Vec<SectionResults>) {
+    let (dot_duration, farnsworth, tone_freq) =
+        (drill.dot_duration, drill.farnsworth, drill.tone_freq);
+    let band = drill.band.clone();
+    let mut total: Option<DrillResults> = None;
+    let mut sections = Vec::new();
+    for (i, section) in script.sections.iter().enumerate() {
+        println!(
+            "\n{}, section {}/{}: {}\n",
+            script.name,
+            i + 1,
+            script.sections.len(),
+            section.name
+        );
+        let (groups, count, duration) = section.groups(size);
+        drill.groups = groups;
+        drill.count = count;
+        drill.duration = duration;
+        drill.dot_duration = section.wpm.map_or(dot_duration, wpm_to_dot_length);
+        drill.farnsworth = section.farnsworth.or(farnsworth);
+        drill.tone_freq = section.tone.unwrap_or(tone_freq);
+        // The band of the command line goes on through the sections
+        // that don't change it:
+        drill.band = match (section.snr, section.qrn, section.qsb) {
+            (None, None, None) => band.clone(),
+            (snr, qrn, qsb) => Conditions::new(
+                snr.or(conditions.and_then(|conditions| conditions.snr)),
+                qrn.or(conditions.and_then(|conditions| conditions.qrn)),
+                qsb.or(conditions.and_then(|conditions| conditions.qsb)),
+                Some(seed),
+            )
+            .map(Band::new),
+        };
+        let results = run_drill(drill, rng);
+        sections.push(SectionResults {
+            name: section.name.clone(),
+            groups: results.groups.len(),
+            exact: results.groups.iter().filter(|group| group.exact).count(),
+            wpm: results.wpm(),
+        });
+        let stopped = results.incomplete;
+        match &mut total {
+            Some(total) => total.extend(results),
+            None => total = Some(results),
+        }
+        if stopped {
+            break;
+        }
+    }
+    drill.band = band;
+    (total.expect("A script has sections"), sections)
+}
+
+#[derive(Tabled)]
+struct SectionRow {
+    section: String,
+    groups: usize,
+    exact: usize,
+    accuracy: String,
+    wpm: String,
+}
+
+/// Prints how each section of a script was copied.
+///
+/// # Returns
+/// The table, to be copied.
+pub fn print_sections(sections: &[SectionResults]) -> String {
+    let rows: Vec<SectionRow> = sections
+        .iter()
+        .map(|section| SectionRow {
+            section: section.name.clone(),
+            groups: section.groups,
+            exact: section.exact,
+            accuracy: format!(
+                "{:.0}%",
+                section.exact as f64 / section.groups.max(1) as f64 * 100.0
+            ),
+            wpm: section.wpm.map_or("-".to_string(), |wpm| wpm.to_string()),
+        })
+        .collect();
+    let table = Table::new(&rows).with(Style::rounded()).to_string();
+    println!("\nAccuracy by section:\n");
+    println!("{table}");
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLES: [(&str, &str); 2] = [
+        ("warmup", include_str!("../scripts/warmup.toml")),
+        ("contest", include_str!("../scripts/contest.toml")),
+    ];
+
+    #[test]
+    fn test_examples() {
+        for (name, text) in EXAMPLES {
+            let script = parse_script(text, Charset::Latin);
+            assert!(script.is_ok(), "{name}: {script:?}");
+        }
+        let warmup = parse_script(EXAMPLES[0].1, Charset::Latin).unwrap();
+        assert_eq!(warmup.sections[0].items, Items::Text("VVV".to_string()));
+        assert_eq!(warmup.sections[0].count, Some(3));
+        let (groups, count, _) = warmup.sections[0].groups((5, 5));
+        assert_eq!(groups.remaining(), Some(3));
+        assert_eq!(count, Some(3));
+        assert_eq!(warmup.sections[1].items, Items::Callsigns);
+        assert_eq!(warmup.sections[1].wpm, Some(22));
+        assert_eq!(warmup.sections[2].minutes, Some(3.0));
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let section = |fields: &str| {
+            parse_script(&format!("[[section]]\n{fields}"), Charset::Latin).map(|script| {
+                assert_eq!(script.name, "script");
+                script.sections[0].clone()
+            })
+        };
+        let koch =
+            section("name = \"koch\"\ngenerate = \"koch 2\"\ncount = 5\nqsb = \"8:0.5\"").unwrap();
+        assert_eq!(
+            koch.items,
+            Items::Groups(vec!["K".into(), "M".into(), "U".into()])
+        );
+        assert!(koch.qsb.is_some());
+        let error = |fields: &str| section(fields).unwrap_err();
+        assert_eq!(
+            error("name = \"fast\"\ntext = \"CQ\"\nwpm = \"fast\""),
+            "Section 1 (fast): wpm must be a whole number above 0"
+        );
+        assert_eq!(error("text = \"CQ\""), "Section 1: name is missing");
+        assert_eq!(
+            error("name = \"calls\"\ngenerate = \"callsigns\""),
+            "Section 1 (calls): needs a count or minutes"
+        );
+        assert_eq!(
+            error("name = \"calls\"\ngenerate = \"callsign\"\ncount = 3"),
+            "Section 1 (calls): generate must be callsigns, words, koch N or groups POOL, not \"callsign\""
+        );
+        assert!(error("name = \"x\"\ntext = \"CQ\"\nspeed = 20").contains("unknown field speed"));
+        assert!(error("name = \"x\"\ntext = \"CQ\"\nrepeat = 0").contains("repeat must be"));
+        assert!(error("name = \"x\"\ntext = \"CQ\"\ncount = 2").contains("use repeat"));
+        assert!(
+            error("name = \"x\"\ntext = \"CQ\"\nwpm = 15\nfarnsworth = 20").contains("farnsworth")
+        );
+        assert!(parse_script("name = \"empty\"", Charset::Latin).is_err());
+        assert!(parse_script("[[section]\n", Charset::Latin).is_err());
+    }
+}
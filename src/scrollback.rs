@@ -0,0 +1,263 @@
+use crate::input::Keystroke;
+use crossterm::event::KeyCode;
+use crossterm::style::Stylize;
+
+/// The messages of the session, shown over the screen while listening
+/// (with `h` or Page Up), to page back through them and search them
+/// with `/`. The decoding goes on meanwhile, and is shown again once
+/// this is closed.
+#[allow(dead_code)]
+pub struct Scrollback {
+    lines: Vec<String>,
+    /// The first line shown.
+    top: usize,
+    /// How many lines are shown at once.
+    rows: usize,
+    /// The search being typed, after `/`.
+    typing: Option<String>,
+    /// The last search, highlighted in the lines.
+    pattern: Option<String>,
+    /// What the footer tells, e.g. that nothing matched.
+    note: Option<String>,
+}
+
+#[allow(dead_code)]
+impl Scrollback {
+    /// Shows the lines of a transcript, from its end.
+    pub fn new(lines: Vec<String>, rows: usize) -> Self {
+        let mut scrollback = Self {
+            lines,
+            top: 0,
+            rows: rows.max(1),
+            typing: None,
+            pattern: None,
+            note: None,
+        };
+        scrollback.top = scrollback.bottom();
+        scrollback
+    }
+
+    /// The top of the last page.
+    fn bottom(&self) -> usize {
+        self.lines.len().saturating_sub(self.rows)
+    }
+
+    /// Shows fewer or more lines, for a new size of the terminal.
+    pub fn resize(&mut self, rows: usize) {
+        self.rows = rows.max(1);
+        self.top = self.top.min(self.bottom());
+    }
+
+    /// Starts typing a search.
+    pub fn search(&mut self) {
+        self.typing = Some(String::new());
+        self.note = None;
+    }
+
+    /// Whether a line has the pattern, ignoring the case.
+    fn matches(line: &str, pattern: &str) -> bool {
+        line.to_uppercase().contains(&pattern.to_uppercase())
+    }
+
+    /// Moves to the next line (or the previous one) with the search, from
+    /// the top, the first line shown being where it matched.
+    fn find(&mut self, forward: bool) {
+        let Some(pattern) = &self.pattern else {
+            return;
+        };
+        let found = match forward {
+            true => {
+                (self.top + 1..self.lines.len()).find(|&i| Self::matches(&self.lines[i], pattern))
+            }
+            false => (0..self.top)
+                .rev()
+                .find(|&i| Self::matches(&self.lines[i], pattern)),
+        };
+        match found {
+            Some(line) => {
+                self.top = line.min(self.bottom());
+                self.note = None;
+            }
+            None => self.note = Some(format!("No more {pattern:?}")),
+        }
+    }
+
+    /// Does what a key asks.
+    ///
+    /// # Returns
+    /// Whether to keep showing the scrollback.
+    pub fn key(&mut self, key: &Keystroke) -> bool {
+        if key.released {
+            return true;
+        }
+        if let Some(typing) = self.typing.as_mut() {
+            match key.code {
+                KeyCode::Enter => {
+                    let typed = std::mem::take(typing);
+                    self.typing = None;
+                    self.pattern = (!typed.is_empty()).then_some(typed);
+                    // From the line shown first, included:
+                    if self.pattern.as_ref().is_some_and(|p| {
+                        self.lines
+                            .get(self.top)
+                            .is_some_and(|line| Self::matches(line, p))
+                    }) {
+                        return true;
+                    }
+                    self.find(false);
+                    if self.note.is_some() {
+                        self.find(true);
+                    }
+                }
+                KeyCode::Esc => self.typing = None,
+                KeyCode::Backspace => {
+                    typing.pop();
+                }
+                KeyCode::Char(c) => typing.push(c),
+                _ => {}
+            }
+            return true;
+        }
+        let page = self.rows.saturating_sub(1).max(1);
+        match key.code {
+            KeyCode::PageUp => self.top = self.top.saturating_sub(page),
+            KeyCode::PageDown => self.top = (self.top + page).min(self.bottom()),
+            KeyCode::Up | KeyCode::Char('k') => self.top = self.top.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.top = (self.top + 1).min(self.bottom()),
+            KeyCode::Home | KeyCode::Char('g') => self.top = 0,
+            KeyCode::End | KeyCode::Char('G') => self.top = self.bottom(),
+            KeyCode::Char('/') => self.search(),
+            KeyCode::Char('n') => self.find(false),
+            KeyCode::Char('N') => self.find(true),
+            KeyCode::Char('q') | KeyCode::Char('h') | KeyCode::Esc => return false,
+            _ => {}
+        }
+        true
+    }
+
+    /// Highlights the search in a line.
+    fn highlight(line: &str, pattern: &str) -> String {
+        let upper = line.to_uppercase();
+        let pattern = pattern.to_uppercase();
+        // Only where the case doesn't change the length:
+        if upper.len() != line.len() || pattern.is_empty() {
+            return line.to_string();
+        }
+        let mut highlighted = String::new();
+        let mut done = 0;
+        for (start, _) in upper.match_indices(&pattern) {
+            if start < done {
+                continue;
+            }
+            let end = start + pattern.len();
+            highlighted.push_str(&line[done..start]);
+            highlighted.push_str(&line[start..end].reverse().to_string());
+            done = end;
+        }
+        highlighted.push_str(&line[done..]);
+        highlighted
+    }
+
+    /// The lines to draw on a terminal of some width, cut to it, with a
+    /// line telling where they are and the keys at the bottom.
+    pub fn render(&self, width: usize) -> Vec<String> {
+        let mut rendered: Vec<String> = self.lines[self.top..]
+            .iter()
+            .take(self.rows)
+            .map(|line| {
+                let line: String = line.chars().take(width).collect();
+                match &self.pattern {
+                    Some(pattern) => Self::highlight(&line, pattern),
+                    None => line,
+                }
+            })
+            .collect();
+        rendered.resize(self.rows, String::new());
+        let footer = match (&self.typing, &self.note) {
+            (Some(typing), _) => format!("/{typing}"),
+            (None, Some(note)) => note.clone(),
+            (None, None) if self.lines.is_empty() => "No message yet  q: back".to_string(),
+            (None, None) => format!(
+                "{}-{} of {} messages  PgUp/PgDn: scroll  /: search  n/N: older/newer match  q: back",
+                self.top + 1,
+                (self.top + self.rows).min(self.lines.len()),
+                self.lines.len()
+            ),
+        };
+        rendered.push(
+            footer
+                .chars()
+                .take(width)
+                .collect::<String>()
+                .reverse()
+                .to_string(),
+        );
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn press(scrollback: &mut Scrollback, code: KeyCode) -> bool {
+        scrollback.key(&Keystroke {
+            code,
+            at: Instant::now(),
+            ctrl: false,
+            released: false,
+        })
+    }
+
+    #[test]
+    fn test_scroll() {
+        let lines: Vec<String> = (1..=10).map(|n| format!("message {n}")).collect();
+        let mut scrollback = Scrollback::new(lines, 4);
+        // From the end:
+        assert_eq!(scrollback.render(80)[0], "message 7");
+        press(&mut scrollback, KeyCode::PageUp);
+        assert_eq!(scrollback.render(80)[0], "message 4");
+        press(&mut scrollback, KeyCode::Home);
+        press(&mut scrollback, KeyCode::Up);
+        assert_eq!(scrollback.render(80)[0], "message 1");
+        press(&mut scrollback, KeyCode::PageDown);
+        press(&mut scrollback, KeyCode::PageDown);
+        press(&mut scrollback, KeyCode::PageDown);
+        assert_eq!(scrollback.render(80)[0], "message 7");
+        assert_eq!(scrollback.render(80).len(), 5);
+        // Cut to the width:
+        assert_eq!(scrollback.render(4)[0], "mess");
+        scrollback.resize(20);
+        assert_eq!(scrollback.render(80)[0], "message 1");
+        assert!(!press(&mut scrollback, KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn test_search() {
+        let lines = ["CQ DE K1ABC", "K1ABC DE W2XYZ", "TNX", "CQ TEST", "RR"]
+            .map(str::to_string)
+            .to_vec();
+        let mut scrollback = Scrollback::new(lines, 2);
+        press(&mut scrollback, KeyCode::Char('/'));
+        for c in "cq".chars() {
+            press(&mut scrollback, KeyCode::Char(c));
+        }
+        assert!(scrollback.render(80)[2].contains("/cq"));
+        press(&mut scrollback, KeyCode::Enter);
+        // The latest match above, highlighted:
+        let rendered = scrollback.render(80);
+        assert_eq!(rendered[0], format!("{} TEST", "CQ".reverse()));
+        press(&mut scrollback, KeyCode::Char('n'));
+        assert!(scrollback.render(80)[0].ends_with(" DE K1ABC"));
+        press(&mut scrollback, KeyCode::Char('n'));
+        assert!(scrollback.render(80)[2].contains("No more \"cq\""));
+        press(&mut scrollback, KeyCode::Char('N'));
+        assert!(scrollback.render(80)[0].ends_with(" TEST"));
+        // Typing q in a search doesn't close it:
+        press(&mut scrollback, KeyCode::Char('/'));
+        assert!(press(&mut scrollback, KeyCode::Char('q')));
+        press(&mut scrollback, KeyCode::Esc);
+        assert!(!press(&mut scrollback, KeyCode::Esc));
+    }
+}
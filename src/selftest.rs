@@ -0,0 +1,135 @@
+use crate::detect::{DecodeEvent, SignalPipeline};
+use std::time::{Duration, Instant};
+
+/// Decodes the morse code sent by a loopback self test, and measures
+/// how well it was received.
+#[allow(dead_code)]
+pub struct SelfTest {
+    pub pipeline: SignalPipeline,
+    messages: Vec<String>,
+    dot_estimate: Option<u32>,
+    // When the first tone was sent:
+    keyed: Option<Instant>,
+    // How long after it was sent the first tone was heard:
+    latency: Option<Duration>,
+}
+
+#[allow(dead_code)]
+impl SelfTest {
+    pub fn new(pipeline: SignalPipeline) -> Self {
+        Self {
+            pipeline,
+            messages: Vec::new(),
+            dot_estimate: None,
+            keyed: None,
+            latency: None,
+        }
+    }
+
+    /// Notes when the first tone was sent.
+    pub fn keyed(&mut self, at: Instant) {
+        self.keyed.get_or_insert(at);
+    }
+
+    /// Collects what was decoded, from events received at `now`.
+    pub fn handle(&mut self, events: Vec<DecodeEvent>, now: Instant) {
+        for event in events {
+            match event {
+                // The first tone is only reported once it ended:
+                DecodeEvent::Signal {
+                    is_high: true,
+                    duration,
+                } if self.latency.is_none() => {
+                    if let Some(keyed) = self.keyed {
+                        let heard = now - Duration::from_millis(duration.into());
+                        self.latency = Some(heard.saturating_duration_since(keyed));
+                    }
+                }
+                DecodeEvent::DotEstimate(dot) => self.dot_estimate = Some(dot),
+                DecodeEvent::Message(msg) => self.messages.push(msg),
+                _ => {}
+            }
+        }
+    }
+
+    /// Describes how the message was received.
+    ///
+    /// # Returns
+    /// Whether the message was decoded as it was sent, and the report.
+    pub fn report(&self, expected: &str, dot_duration: u32) -> (bool, String) {
+        let decoded = self.messages.join(" ");
+        let passed = decoded == expected;
+        let mut report = format!("sent:     {expected}\ndecoded:  {decoded}\n");
+        match self.dot_estimate {
+            Some(dot) => {
+                let error = (dot as f32 - dot_duration as f32) / dot_duration as f32 * 100.0;
+                report +=
+                    &format!("timing:   dot {dot}ms, sent as {dot_duration}ms ({error:+.0}%)\n");
+            }
+            None => report += "timing:   not enough tones heard\n",
+        }
+        match self.latency {
+            Some(latency) => report += &format!("latency:  {}ms\n", latency.as_millis()),
+            None => report += "latency:  no tone heard\n",
+        }
+        report += if passed { "PASS" } else { "FAIL" };
+        (passed, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::PipelineOptions;
+    use crate::morse::encode_morse;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn self_test() -> SelfTest {
+        SelfTest::new(SignalPipeline::new(PipelineOptions::default()))
+    }
+
+    #[test]
+    fn test_self_test_passes() {
+        let mut test = self_test();
+        let start = Instant::now();
+        test.keyed(start);
+        // Heard after 100ms of silence:
+        let mut samples = vec![0.0_f32; SAMPLE_RATE as usize / 10];
+        for (freq, duration) in encode_morse("PARIS PARIS", 60, 700.0) {
+            let n = (SAMPLE_RATE * duration / 1000) as usize;
+            samples.extend((0..n).map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                0.5 * (2.0 * std::f32::consts::PI * freq * t).sin()
+            }));
+        }
+        // Pretend that the samples arrive in real time:
+        let mut received = 0;
+        for block in samples.chunks(480) {
+            received += block.len();
+            let now = start + Duration::from_secs_f32(received as f32 / SAMPLE_RATE as f32);
+            let events = test.pipeline.push_samples(block, SAMPLE_RATE);
+            test.handle(events, now);
+        }
+        let events = test.pipeline.finish();
+        test.handle(events, Instant::now());
+
+        let (passed, report) = test.report("PARIS PARIS", 60);
+        assert!(passed, "{report}");
+        let latency = test.latency.unwrap().as_millis();
+        assert!((100..=130).contains(&latency), "{latency}");
+        assert!(report.contains("decoded:  PARIS PARIS\n"), "{report}");
+        assert!(report.ends_with("PASS"), "{report}");
+    }
+
+    #[test]
+    fn test_self_test_fails() {
+        let test = self_test();
+        let (passed, report) = test.report("PARIS PARIS", 60);
+        assert!(!passed);
+        assert_eq!(
+            report,
+            "sent:     PARIS PARIS\ndecoded:  \ntiming:   not enough tones heard\nlatency:  no tone heard\nFAIL"
+        );
+    }
+}
@@ -0,0 +1,625 @@
+use crate::abbreviations::Meanings;
+use crate::codec::{decode, encode, Separators};
+use crate::detect::{DECODER_WORD_DOTS, WORD_GAP_DOTS};
+use crate::iambic::{self, Element, IambicKeyer, Mode, Paddle};
+use crate::input::{Flow, KeyReader};
+use crate::morse::{dot_length_to_wpm, Charset};
+use crate::sidetone::{Sidetone, SidetoneOptions};
+use crate::speed::SpeedTracker;
+use crate::words::WordPicker;
+use crossterm::event::KeyCode;
+use rand::Rng;
+use std::io::{stdout, Write};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+use tabled::settings::style::Style;
+use tabled::{Table, Tabled};
+use textwrap::wrap;
+
+/// Marks at least this many dots long are dashes, and spaces this long
+/// end a character, as the speed tracker splits them.
+const DASH_DOTS: f64 = 2.0;
+
+/// The options of a sending drill.
+pub struct SendDrill {
+    pub picker: WordPicker,
+    // The drill ends after this many words, or this long:
+    pub count: Option<u32>,
+    pub duration: Option<Duration>,
+    /// The dot duration to expect until the sender's own is measured.
+    pub dot_duration: u32,
+    pub sidetone: SidetoneOptions,
+    pub charset: Charset,
+    pub keys: Keys,
+    // Shows the meaning of each word in its place, e.g. of an
+    // abbreviation, for the word to be keyed:
+    pub meanings: Option<Meanings>,
+}
+
+/// The marks and spaces of keyed morse code, each measured in dots of
+/// the sender's own speed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Keying {
+    pub dits: Vec<f64>,
+    pub dahs: Vec<f64>,
+    /// The spaces between the elements of a character, ideally 1 dot.
+    pub element_spaces: Vec<f64>,
+    /// The spaces between the characters of a word, ideally 3 dots.
+    pub letter_spaces: Vec<f64>,
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// The standard deviation of some values, as a percentage of their
+/// ideal, or None with fewer than two of them.
+fn spread(values: &[f64], ideal: f64) -> Option<f64> {
+    let mean = mean(values).filter(|_| values.len() > 1)?;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    Some(variance.sqrt() / ideal * 100.0)
+}
+
+impl Keying {
+    pub fn extend(&mut self, other: &Keying) {
+        self.dits.extend(&other.dits);
+        self.dahs.extend(&other.dahs);
+        self.element_spaces.extend(&other.element_spaces);
+        self.letter_spaces.extend(&other.letter_spaces);
+    }
+
+    /// How many times longer the dahs are than the dits, ideally 3.
+    pub fn dah_ratio(&self) -> Option<f64> {
+        Some(mean(&self.dahs)? / mean(&self.dits)?)
+    }
+
+    /// How much the spaces within the characters vary, as a percentage
+    /// of a dot.
+    pub fn element_spread(&self) -> Option<f64> {
+        spread(&self.element_spaces, 1.0)
+    }
+
+    /// How much the spaces between the characters vary, as a percentage
+    /// of their 3 dots.
+    pub fn letter_spread(&self) -> Option<f64> {
+        spread(&self.letter_spaces, 3.0)
+    }
+}
+
+/// Turns the presses and releases of the key, as (down, when), into the
+/// durations of the marks (true) and of the spaces between them, in
+/// milliseconds. A press while the key is down, or a release while it
+/// is up, is left out.
+pub fn signals(edges: &[(bool, Instant)]) -> Vec<(bool, u32)> {
+    let mut signals = Vec::new();
+    let mut last: Option<(bool, Instant)> = None;
+    for &(down, at) in edges {
+        match last {
+            None if !down => continue,
+            Some((was_down, _)) if was_down == down => continue,
+            Some((was_down, since)) => signals.push((
+                was_down,
+                at.saturating_duration_since(since).as_millis() as u32,
+            )),
+            None => {}
+        }
+        last = Some((down, at));
+    }
+    signals
+}
+
+/// Reads keyed marks and spaces as morse code, at the given dot
+/// duration: marks of at least 2 dots are dashes, and spaces of at
+/// least 2 dots end a character, or a word from `WORD_GAP_DOTS`, as
+/// the decoder reads them.
+///
+/// # Returns
+/// The dots and dashes, e.g. `-.-. --.- / -.. .`, and how they were keyed.
+pub fn read_keying(signals: &[(bool, u32)], dot_duration: u32) -> (String, Keying) {
+    let dot = dot_duration.max(1) as f64;
+    let (mut code, mut keying) = (String::new(), Keying::default());
+    for &(mark, duration) in signals {
+        let dots = duration as f64 / dot;
+        match mark {
+            true if dots >= DASH_DOTS => {
+                code.push('-');
+                keying.dahs.push(dots);
+            }
+            true => {
+                code.push('.');
+                keying.dits.push(dots);
+            }
+            false if dots < DASH_DOTS => keying.element_spaces.push(dots),
+            false if dots < WORD_GAP_DOTS as f64 => {
+                code.push(' ');
+                keying.letter_spaces.push(dots);
+            }
+            false => code.push_str(" / "),
+        }
+    }
+    (code, keying)
+}
+
+/// When each character of keyed marks and spaces started, as
+/// `read_keying` splits them into characters.
+///
+/// # Arguments
+/// * `start` - When the first mark started.
+pub fn character_starts(
+    start: Instant,
+    signals: &[(bool, u32)],
+    dot_duration: u32,
+) -> Vec<Instant> {
+    let dot = dot_duration.max(1) as f64;
+    let (mut starts, mut at, mut spaced) = (Vec::new(), start, true);
+    for &(mark, duration) in signals {
+        match mark {
+            true if spaced => {
+                starts.push(at);
+                spaced = false;
+            }
+            true => {}
+            false => spaced = duration as f64 / dot >= DASH_DOTS,
+        }
+        at += Duration::from_millis(duration.into());
+    }
+    starts
+}
+
+/// Measures the dot duration of a sender keying a straight key, from
+/// the marks and spaces of a word added to those before.
+///
+/// # Returns
+/// The dot duration, or the one measured before if it can't tell yet.
+pub fn track_speed(tracker: &mut SpeedTracker, signals: &[(bool, u32)], dot: u32) -> u32 {
+    for &(mark, duration) in signals {
+        match mark {
+            true => tracker.push_tone(duration),
+            false => tracker.push_gap(duration),
+        }
+    }
+    tracker.estimate(dot).unwrap_or(dot)
+}
+
+/// A word that was to be sent, and how it was keyed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SentWord {
+    pub target: String,
+    /// The dots and dashes that were keyed.
+    pub code: String,
+    pub decoded: String,
+    pub keying: Keying,
+    /// The sender's dot duration measured so far, in milliseconds.
+    pub dot: u32,
+}
+
+impl SentWord {
+    pub fn correct(&self) -> bool {
+        self.decoded.eq_ignore_ascii_case(&self.target)
+    }
+}
+
+/// The words of a sending drill.
+pub struct Sending {
+    pub words: Vec<SentWord>,
+    /// Whether it was stopped before its count or duration.
+    pub stopped: bool,
+}
+
+impl Sending {
+    /// The percentage of the words sent right.
+    pub fn accuracy(&self) -> f64 {
+        let correct = self.words.iter().filter(|word| word.correct()).count();
+        correct as f64 / self.words.len().max(1) as f64 * 100.0
+    }
+
+    /// The speed the words were sent at, from the last dot duration measured.
+    pub fn wpm(&self) -> Option<u32> {
+        self.words.last().map(|word| dot_length_to_wpm(word.dot))
+    }
+
+    /// How all of the words were keyed.
+    pub fn keying(&self) -> Keying {
+        let mut keying = Keying::default();
+        for word in &self.words {
+            keying.extend(&word.keying);
+        }
+        keying
+    }
+}
+
+/// How the words are keyed, with the keys of the keyboard.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Keys {
+    /// One key, as a straight key.
+    Straight(KeyCode),
+    /// Two keys, as the dit and dah paddles of an iambic keyer.
+    Paddles {
+        dit: KeyCode,
+        dah: KeyCode,
+        mode: Mode,
+        weight: u32,
+    },
+}
+
+/// What was keyed for a word.
+pub enum Keyed {
+    /// When its first mark started, and its marks and spaces.
+    Signals(Instant, Vec<(bool, u32)>),
+    /// Nothing was keyed before the deadline.
+    TimedOut,
+    Stop,
+}
+
+/// Keys a word with a straight key, with the sidetone while it is down,
+/// until it has been up for `word_end` or Enter is pressed.
+///
+/// # Arguments
+/// * `deadline` - When to stop waiting for the first mark, if ever.
+fn key_straight(
+    keys: &KeyReader,
+    flow: &mut Flow,
+    sidetone: &Sidetone,
+    key: KeyCode,
+    word_end: Duration,
+    deadline: Option<Instant>,
+) -> Keyed {
+    let mut edges: Vec<(bool, Instant)> = Vec::new();
+    loop {
+        let deadline = match edges.last() {
+            Some(&(false, at)) => Some(at + word_end),
+            Some(&(true, _)) => None,
+            None => deadline,
+        };
+        let key_event = match keys.next_until(deadline) {
+            Ok(key_event) => key_event,
+            Err(RecvTimeoutError::Timeout) if edges.is_empty() => return Keyed::TimedOut,
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => return Keyed::Stop,
+        };
+        if key_event.code == key {
+            let down = !key_event.released;
+            if edges
+                .last()
+                .map_or(!down, |&(was_down, _)| was_down == down)
+            {
+                continue;
+            }
+            edges.push((down, key_event.at));
+            match down {
+                true => sidetone.down(key_event.at),
+                false => sidetone.up(key_event.at),
+            }
+        } else if !key_event.released && flow.key(&key_event, false) {
+            if !flow.wait(keys) {
+                return Keyed::Stop;
+            }
+        } else if !key_event.released && key_event.code == KeyCode::Enter && !edges.is_empty() {
+            break;
+        }
+    }
+    // A key still held down is let go at the end:
+    if let Some(&(true, _)) = edges.last() {
+        let now = Instant::now();
+        sidetone.up(now);
+        edges.push((false, now));
+    }
+    Keyed::Signals(edges[0].1, signals(&edges))
+}
+
+/// Keys a word with the paddles of an iambic keyer, with the sidetone
+/// of each of its elements, until it has been silent for `word_end` or
+/// Enter is pressed.
+///
+/// # Arguments
+/// * `deadline` - When to stop waiting for the first element, if ever.
+fn key_paddles(
+    keys: &KeyReader,
+    flow: &mut Flow,
+    sidetone: &Sidetone,
+    (dit, dah): (KeyCode, KeyCode),
+    mut keyer: IambicKeyer,
+    word_end: Duration,
+    deadline: Option<Instant>,
+) -> Keyed {
+    let start = Instant::now();
+    let ms = |at: Instant| at.saturating_duration_since(start).as_millis() as u64;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        for element in keyer.poll(ms(Instant::now())) {
+            sidetone.mark(
+                start + Duration::from_millis(element.start),
+                Duration::from_millis(element.mark.into()),
+            );
+            elements.push(element);
+        }
+        let deadline = match (keyer.next_change(), elements.last()) {
+            (Some(change), _) => Some(start + Duration::from_millis(change)),
+            (None, Some(last)) => {
+                Some(start + Duration::from_millis(last.start + last.mark as u64) + word_end)
+            }
+            (None, None) => deadline,
+        };
+        let key = match keys.next_until(deadline) {
+            Ok(key) => key,
+            // The next element may start:
+            Err(RecvTimeoutError::Timeout) if keyer.next_change().is_some() => continue,
+            Err(RecvTimeoutError::Timeout) if elements.is_empty() => return Keyed::TimedOut,
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => return Keyed::Stop,
+        };
+        let paddle = match key.code {
+            code if code == dit => Some(Paddle::Dit),
+            code if code == dah => Some(Paddle::Dah),
+            _ => None,
+        };
+        match paddle {
+            Some(paddle) if key.released => keyer.release(paddle, ms(key.at)),
+            Some(paddle) => keyer.press(paddle, ms(key.at)),
+            None if key.released => {}
+            None if flow.key(&key, false) && !flow.wait(keys) => return Keyed::Stop,
+            None if key.code == KeyCode::Enter && !elements.is_empty() => break,
+            None => {}
+        }
+    }
+    match elements.first() {
+        Some(first) => Keyed::Signals(
+            start + Duration::from_millis(first.start),
+            iambic::signals(&elements),
+        ),
+        None => Keyed::Stop,
+    }
+}
+
+/// Keys a word with the keys of the keyboard, with the sidetone.
+///
+/// # Arguments
+/// * `dot_duration` - The speed of the iambic keyer.
+/// * `word_end` - How long a silence ends the word.
+/// * `deadline` - When to stop waiting for the first mark, if ever.
+#[allow(clippy::too_many_arguments)]
+pub fn key_word(
+    keys: &KeyReader,
+    flow: &mut Flow,
+    sidetone: &Sidetone,
+    how: Keys,
+    dot_duration: u32,
+    word_end: Duration,
+    deadline: Option<Instant>,
+) -> Keyed {
+    match how {
+        Keys::Straight(key) => key_straight(keys, flow, sidetone, key, word_end, deadline),
+        Keys::Paddles {
+            dit,
+            dah,
+            mode,
+            weight,
+        } => key_paddles(
+            keys,
+            flow,
+            sidetone,
+            (dit, dah),
+            IambicKeyer::new(mode, dot_duration, weight),
+            word_end,
+            deadline,
+        ),
+    }
+}
+
+/// Shows words to key with the keys of the keyboard, with a sidetone,
+/// and decodes each one once it has been silent for as long as the
+/// decoder takes to end a word (or Enter is pressed).
+pub fn run_send(drill: &mut SendDrill, rng: &mut impl Rng) -> Result<Sending, String> {
+    let keys = KeyReader::with_releases()?;
+    let paragraph = match drill.keys {
+        Keys::Straight(key) => format!("Sending practice, with the {key} key as a straight key."),
+        Keys::Paddles { dit, dah, mode, .. } => format!(
+            "Sending practice, with the {dit} and {dah} keys as the dit and dah paddles of an iambic keyer (mode {mode:?}) at {} WPM.",
+            dot_length_to_wpm(drill.dot_duration)
+        ),
+    } + "\n\nKey each word that is shown. It is decoded once you stop keying for a moment, or press Enter, and graded. Press ESC or Ctrl-C to stop.\n";
+    for line in wrap(&paragraph, 70) {
+        println!("{}", line);
+    }
+    let sidetone = Sidetone::start(&drill.sidetone)?;
+    let mut flow = Flow::new(None);
+    let mut tracker = SpeedTracker::new();
+    let mut dot = drill.dot_duration;
+    let mut words = Vec::new();
+    let mut stopped = false;
+    let start = Instant::now();
+    let _raw = keys.raw_mode();
+    let mut stdout = stdout();
+    while drill.count.is_none_or(|count| words.len() < count as usize)
+        && drill
+            .duration
+            .is_none_or(|duration| start.elapsed() < duration)
+    {
+        let target = drill.picker.pick(rng).to_string();
+        let shown = drill
+            .meanings
+            .as_ref()
+            .and_then(|meanings| meanings.meaning(&target))
+            .unwrap_or(&target);
+        print!("\r\n{}. {shown}\r\n", words.len() + 1);
+        stdout.flush().unwrap();
+        keys.clear();
+        // The word ends after a long enough silence, at whichever speed
+        // is the slowest:
+        let word_end =
+            Duration::from_millis((DECODER_WORD_DOTS * dot.max(drill.dot_duration)).into());
+        let keyed = key_word(
+            &keys,
+            &mut flow,
+            &sidetone,
+            drill.keys,
+            drill.dot_duration,
+            word_end,
+            None,
+        );
+        let Keyed::Signals(_, signals) = keyed else {
+            stopped = true;
+            break;
+        };
+        // The keyer sends at its own speed, the sender's is measured:
+        if let Keys::Straight(_) = drill.keys {
+            dot = track_speed(&mut tracker, &signals, dot);
+        }
+        let (code, keying) = read_keying(&signals, dot);
+        let decoded = decode(&code, &Separators::default(), drill.charset);
+        let word = SentWord {
+            target,
+            code,
+            decoded,
+            keying,
+            dot,
+        };
+        print!(
+            "   {} {}  {}\r\n",
+            match word.correct() {
+                true => "✓",
+                false => "✗",
+            },
+            word.decoded,
+            word.code
+        );
+        if !word.correct() {
+            print!(
+                "     {}  {}\r\n",
+                word.target,
+                encode(&word.target, &Separators::default())
+            );
+        }
+        stdout.flush().unwrap();
+        drill.picker.copied(&word.target, word.correct());
+        words.push(word);
+    }
+    print!("\r\n");
+    Ok(Sending { words, stopped })
+}
+
+#[derive(Tabled)]
+struct SpacingRow {
+    keying: String,
+    measured: String,
+    ideal: String,
+}
+
+/// Prints the words sent and how they were decoded, and how evenly
+/// they were keyed.
+///
+/// # Returns
+/// The table of how evenly the words were keyed, unless none were sent.
+pub fn print_report(sending: &Sending) -> Option<String> {
+    if sending.words.is_empty() {
+        return None;
+    }
+    if sending.stopped {
+        println!("\nDrill stopped.");
+    }
+    let correct = sending.words.iter().filter(|word| word.correct()).count();
+    let missed: Vec<String> = sending
+        .words
+        .iter()
+        .filter(|word| !word.correct())
+        .map(|word| format!("{} as {}", word.target, word.decoded))
+        .collect();
+    println!(
+        "\nSent {correct}/{} words right ({:.0}%), at {} WPM",
+        sending.words.len(),
+        sending.accuracy(),
+        sending.wpm().unwrap_or_default()
+    );
+    if !missed.is_empty() {
+        println!("Decoded wrong: {}", missed.join(", "));
+    }
+    Some(print_keying(&sending.keying()))
+}
+
+/// Prints how evenly some morse code was keyed.
+///
+/// # Returns
+/// The table printed.
+pub fn print_keying(keying: &Keying) -> String {
+    let percent = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("±{v:.0}%"));
+    let rows = vec![
+        SpacingRow {
+            keying: "dah/dit ratio".to_string(),
+            measured: keying
+                .dah_ratio()
+                .map_or("-".to_string(), |ratio| format!("{ratio:.1}")),
+            ideal: "3.0".to_string(),
+        },
+        SpacingRow {
+            keying: "spaces within characters".to_string(),
+            measured: percent(keying.element_spread()),
+            ideal: "1 dot".to_string(),
+        },
+        SpacingRow {
+            keying: "spaces between characters".to_string(),
+            measured: percent(keying.letter_spread()),
+            ideal: "3 dots".to_string(),
+        },
+    ];
+    let table = Table::new(rows).with(Style::rounded()).to_string();
+    println!("\nHow evenly it was keyed (the spacing as a standard deviation of its ideal):\n");
+    println!("{table}");
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signals() {
+        let (start, ms) = (Instant::now(), Duration::from_millis);
+        let edges = [
+            // A release before the first press:
+            (false, start),
+            (true, start + ms(100)),
+            // Repeated by the terminal:
+            (true, start + ms(150)),
+            (false, start + ms(160)),
+            (true, start + ms(220)),
+            (false, start + ms(400)),
+        ];
+        assert_eq!(signals(&edges), vec![(true, 60), (false, 60), (true, 180)]);
+    }
+
+    #[test]
+    fn test_read_keying() {
+        // C Q at 20 WPM (60 ms dots), keyed a little unevenly:
+        let signals = [
+            (true, 190),
+            (false, 60),
+            (true, 50),
+            (false, 70),
+            (true, 180),
+            (false, 50),
+            (true, 60),
+            (false, 200),
+            (true, 170),
+            (false, 60),
+            (true, 180),
+            (false, 60),
+            (true, 70),
+            (false, 60),
+            (true, 190),
+        ];
+        let (code, keying) = read_keying(&signals, 60);
+        assert_eq!(code, "-.-. --.-");
+        assert_eq!(decode(&code, &Separators::default(), Charset::Latin), "CQ");
+        let ratio = keying.dah_ratio().unwrap();
+        assert!((ratio - 3.03).abs() < 0.01, "{ratio}");
+        assert_eq!(keying.element_spaces.len(), 6);
+        assert_eq!(keying.letter_spaces.len(), 1);
+        // A single space between characters doesn't vary:
+        assert_eq!(keying.letter_spread(), None);
+        let spread = keying.element_spread().unwrap();
+        assert!((spread - 9.62).abs() < 0.01, "{spread}");
+        // Spaces of 6 dots end the word:
+        let (code, _) = read_keying(&[(true, 60), (false, 360), (true, 180)], 60);
+        assert_eq!(code, ". / -");
+    }
+}
@@ -0,0 +1,171 @@
+use crate::keying::{Output, Signal};
+use crate::prelude::*;
+use std::fs::File;
+
+/// A control line of a serial port, raised to key the transmitter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Line {
+    Dtr,
+    Rts,
+}
+
+impl Line {
+    /// The other line, e.g. for the PTT when the key is on DTR.
+    pub fn other(self) -> Line {
+        match self {
+            Line::Dtr => Line::Rts,
+            Line::Rts => Line::Dtr,
+        }
+    }
+}
+
+impl FromStr for Line {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dtr" => Ok(Line::Dtr),
+            "rts" => Ok(Line::Rts),
+            _ => Err(format!("Serial line must be 'dtr' or 'rts': {s}")),
+        }
+    }
+}
+
+/// The serial port and the line the key is on, e.g. `/dev/ttyUSB0:dtr`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeySerial {
+    pub path: String,
+    pub line: Line,
+}
+
+impl FromStr for KeySerial {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rsplit_once(':') {
+            Some((path, line)) if !path.is_empty() => Ok(KeySerial {
+                path: path.to_string(),
+                line: line.parse()?,
+            }),
+            _ => Err(format!(
+                "Serial keying must be the port and line, e.g. /dev/ttyUSB0:dtr: {s}"
+            )),
+        }
+    }
+}
+
+/// The serial port, with its key and PTT lines held down while it is
+/// open, and released whenever it is dropped, even by a panic.
+pub struct SerialKey {
+    file: File,
+    key: Line,
+    ptt: Option<Line>,
+}
+
+impl SerialKey {
+    /// Opens the port to key the line, with the PTT on the other line
+    /// with `ptt`.
+    pub fn open(port: &KeySerial, ptt: bool) -> io::Result<SerialKey> {
+        let file = open_port(&port.path)?;
+        let serial = SerialKey {
+            file,
+            key: port.line,
+            ptt: ptt.then(|| port.line.other()),
+        };
+        // Opening a tty usually raises both lines, which would key the
+        // transmitter right away:
+        serial.set_line(Line::Dtr, false)?;
+        serial.set_line(Line::Rts, false)?;
+        Ok(serial)
+    }
+
+    fn set_line(&self, line: Line, raised: bool) -> io::Result<()> {
+        set_line(&self.file, line, raised)
+    }
+}
+
+impl Output for SerialKey {
+    fn set(&mut self, signal: Signal, raised: bool) -> io::Result<()> {
+        match (signal, self.ptt) {
+            (Signal::Key, _) => self.set_line(self.key, raised),
+            (Signal::Ptt, Some(ptt)) => self.set_line(ptt, raised),
+            (Signal::Ptt, None) => Ok(()),
+        }
+    }
+}
+
+impl Drop for SerialKey {
+    fn drop(&mut self) {
+        let _ = self.set_line(self.key, false);
+        if let Some(ptt) = self.ptt {
+            let _ = self.set_line(ptt, false);
+        }
+    }
+}
+
+/// Opens a serial port, without it becoming the controlling terminal.
+#[cfg(unix)]
+pub fn open_port(path: &str) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+pub fn open_port(_path: &str) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Serial keying is only supported on Unix",
+    ))
+}
+
+#[cfg(unix)]
+fn set_line(file: &File, line: Line, raised: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let bits: libc::c_int = match line {
+        Line::Dtr => libc::TIOCM_DTR,
+        Line::Rts => libc::TIOCM_RTS,
+    };
+    let request = match raised {
+        true => libc::TIOCMBIS,
+        false => libc::TIOCMBIC,
+    };
+    match unsafe { libc::ioctl(file.as_raw_fd(), request, &bits) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_line(_file: &File, _line: Line, _raised: bool) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_serial() {
+        assert_eq!(
+            "/dev/ttyUSB0:dtr".parse(),
+            Ok(KeySerial {
+                path: "/dev/ttyUSB0".to_string(),
+                line: Line::Dtr,
+            })
+        );
+        assert_eq!(
+            "/dev/serial/by-id/usb-FTDI:RTS"
+                .parse::<KeySerial>()
+                .unwrap()
+                .line,
+            Line::Rts
+        );
+        assert!("/dev/ttyUSB0".parse::<KeySerial>().is_err());
+        assert!("/dev/ttyUSB0:cts".parse::<KeySerial>().is_err());
+        assert!(":dtr".parse::<KeySerial>().is_err());
+    }
+}
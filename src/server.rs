@@ -0,0 +1,441 @@
+use crate::detect::DecodeEvent;
+use crate::events::{EventStream, OutputFormat};
+use crate::json::Json;
+use crate::message::{Backlog, Message};
+use crate::prelude::*;
+use crate::rigctl::Tuning;
+use crate::term::{message_lines, MessagePrinter, PrintUpdate};
+use crate::websocket;
+use chrono::{Local, TimeZone};
+use crossterm::{
+    queue,
+    style::Print,
+    terminal::{Clear, ClearType},
+};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+/// How many lines may wait to be sent to a client, beyond the backlog,
+/// before it is too slow to keep and is dropped.
+const CLIENT_QUEUE: usize = 1024;
+
+/// How the decoded text is streamed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    /// A line for each event, over a plain TCP connection.
+    Tcp,
+    /// A WebSocket message for each event, for a web page, see
+    /// `websocket`.
+    WebSocket,
+}
+
+/// Where `listen --serve` listens, e.g. `tcp:0.0.0.0:7373` or
+/// `ws:0.0.0.0:7374`, TCP being the default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServeAddr {
+    pub protocol: Protocol,
+    pub addr: String,
+}
+
+impl FromStr for ServeAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (protocol, addr) = match s.split_once(':') {
+            Some(("tcp", addr)) => (Protocol::Tcp, addr),
+            Some(("ws", addr)) => (Protocol::WebSocket, addr),
+            _ => (Protocol::Tcp, s),
+        };
+        match addr.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(ServeAddr {
+                    protocol,
+                    addr: addr.to_string(),
+                })
+            }
+            _ => Err(format!(
+                "Address must be like tcp:0.0.0.0:7373 or ws:0.0.0.0:7374: {s}"
+            )),
+        }
+    }
+}
+
+/// A client connected to the server.
+struct Client {
+    lines: SyncSender<String>,
+    stream: TcpStream,
+}
+
+struct Clients {
+    clients: Vec<Client>,
+    /// The lines of the latest messages, sent to each client as it
+    /// connects.
+    backlog: Backlog<String>,
+}
+
+/// Streams the decoded text to every client connected over TCP, with
+/// `listen --serve`, as the lines `--plain` prints, or as the lines of
+/// JSON of `--output json`, which are always sent over a WebSocket.
+///
+/// Each client is written to by a thread of its own, so the decoding
+/// never waits for one: a client that falls too far behind is dropped.
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct Server {
+    pub protocol: Protocol,
+    pub format: OutputFormat,
+    backlog: usize,
+    clients: Arc<Mutex<Clients>>,
+}
+
+#[allow(dead_code)]
+impl Server {
+    /// Listens at an address, keeping the last `backlog` messages for the
+    /// clients that connect later.
+    pub fn start(addr: &ServeAddr, format: OutputFormat, backlog: usize) -> io::Result<Self> {
+        let listener = TcpListener::bind(&addr.addr)?;
+        let format = match addr.protocol {
+            Protocol::Tcp => format,
+            Protocol::WebSocket => OutputFormat::Json,
+        };
+        match addr.protocol {
+            Protocol::Tcp => info!("Serving the decoded text on {}", listener.local_addr()?),
+            Protocol::WebSocket => info!(
+                "Serving the decoded text on ws://{0}/, and a page showing it on http://{0}/",
+                listener.local_addr()?
+            ),
+        }
+        let server = Self {
+            protocol: addr.protocol,
+            format,
+            backlog,
+            clients: Arc::new(Mutex::new(Clients {
+                clients: Vec::new(),
+                backlog: Backlog::new(backlog),
+            })),
+        };
+        let accepting = server.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => match accepting.protocol {
+                        Protocol::Tcp => accepting.add(stream),
+                        // The handshake is waited for on a thread of its own:
+                        Protocol::WebSocket => {
+                            let server = accepting.clone();
+                            std::thread::spawn(move || websocket::serve(stream, &server));
+                        }
+                    },
+                    Err(e) => debug!("Could not accept a client: {e}"),
+                }
+            }
+        });
+        Ok(server)
+    }
+
+    /// Adds a client, to be sent the lines from the backlog on.
+    ///
+    /// # Returns
+    /// The lines to send it, until it is dropped.
+    pub fn register(&self, stream: TcpStream) -> Receiver<String> {
+        let (lines, receiver) = sync_channel::<String>(self.backlog + CLIENT_QUEUE);
+        let mut clients = self.clients.lock().unwrap();
+        for line in clients.backlog.iter() {
+            let _ = lines.try_send(line.clone());
+        }
+        clients.clients.push(Client { lines, stream });
+        receiver
+    }
+
+    fn add(&self, stream: TcpStream) {
+        let peer = stream
+            .peer_addr()
+            .map_or("a client".to_string(), |peer| peer.to_string());
+        let Ok(writer) = stream.try_clone() else {
+            return;
+        };
+        let receiver = self.register(stream);
+        debug!("{peer} connected");
+        std::thread::spawn(move || {
+            let mut writer = io::BufWriter::new(writer);
+            while let Ok(line) = receiver.recv() {
+                let written = writeln!(writer, "{line}").and_then(|_| writer.flush());
+                if written.is_err() {
+                    break;
+                }
+            }
+            debug!("{peer} disconnected");
+        });
+    }
+
+    /// How many clients are connected.
+    pub fn clients(&self) -> usize {
+        self.clients.lock().unwrap().clients.len()
+    }
+
+    /// Sends a line to every client, keeping it for the later ones when
+    /// it is a message.
+    pub fn send(&self, line: String, message: bool) {
+        let mut clients = self.clients.lock().unwrap();
+        if message {
+            clients.backlog.push(line.clone());
+        }
+        clients
+            .clients
+            .retain(|client| match client.lines.try_send(line.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    warn!("Dropped a client that was too slow to keep up");
+                    let _ = client.stream.shutdown(Shutdown::Both);
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+}
+
+/// What a station sends to the server.
+#[allow(dead_code)]
+pub struct Serving {
+    server: Server,
+    label: Option<String>,
+    events: EventStream,
+}
+
+#[allow(dead_code)]
+impl Serving {
+    pub fn new(server: &Server, station: Option<&str>) -> Self {
+        Self {
+            server: server.clone(),
+            label: station.map(str::to_string),
+            // For the meter of the page:
+            events: EventStream::new(station, false)
+                .with_levels(server.protocol == Protocol::WebSocket),
+        }
+    }
+
+    /// Tags the events with what the radio is tuned to, with --rigctl.
+    pub fn tune(&mut self, tuning: Option<Tuning>) {
+        self.events.tuning = tuning;
+    }
+
+    /// Sends the lines of an event of the pipeline, when the sender's
+    /// speed was `wpm`.
+    pub fn event(&mut self, event: &DecodeEvent, wpm: Option<u32>) {
+        let now = Local::now();
+        match self.server.format {
+            OutputFormat::Json => {
+                let t = now.timestamp_millis() as f64 / 1000.0;
+                for line in self.events.lines(event, t, wpm) {
+                    let message = line.starts_with("{\"type\":\"message\"");
+                    self.server.send(line, message);
+                }
+            }
+            OutputFormat::Text => {
+                if let DecodeEvent::Message(msg) = event {
+                    let label = self
+                        .label
+                        .as_ref()
+                        .map_or(String::new(), |label| format!("{label} "));
+                    let timestamp = now.format("%y-%m-%d %H:%M:%S %p");
+                    self.server.send(format!("{timestamp} {label}{msg}"), true);
+                }
+            }
+        }
+    }
+}
+
+/// Shows what a server streams, with `connect`: the messages as
+/// `listen` logs them, and the message in progress as it is decoded,
+/// when the stream is of JSON. The lines of text are shown as they are.
+#[derive(Default)]
+pub struct Viewer {
+    /// Whether the message in progress is shown, on a terminal.
+    pub live: bool,
+    printer: MessagePrinter,
+    // The station of the message in progress:
+    station: Option<String>,
+}
+
+impl Viewer {
+    /// Writes out a line of the stream, on a terminal of some width.
+    pub fn show(&mut self, line: &str, width: usize, out: &mut impl Write) -> io::Result<()> {
+        let Some(event) = line.starts_with('{').then(|| Json::parse(line)).flatten() else {
+            return writeln!(out, "{line}");
+        };
+        let text = |key: &str| event.get(key).and_then(Json::as_str).map(str::to_string);
+        let station = text("station");
+        let label = station
+            .as_ref()
+            .map_or(String::new(), |label| format!("{label}: "));
+        match text("type").as_deref() {
+            Some("char" | "partial") if self.live => {
+                if station != self.station && !self.printer.printed().is_empty() {
+                    writeln!(out)?;
+                    self.printer.reset();
+                }
+                self.station = station;
+                let partial = match text("char") {
+                    Some(c) => format!("{}{c}", self.printer.printed()),
+                    None => text("text").unwrap_or_default(),
+                };
+                match self.printer.update(&partial) {
+                    PrintUpdate::Unchanged => {}
+                    PrintUpdate::Append(added) if partial.len() > added.len() => {
+                        write!(out, "{added}")?
+                    }
+                    PrintUpdate::Append(text) | PrintUpdate::Redraw(text) => {
+                        clear_line(out)?;
+                        write!(out, "{label}{text}")?;
+                    }
+                }
+            }
+            Some("message") => {
+                let end = event.get("end").and_then(Json::as_f64).unwrap_or(0.0);
+                let timestamp = Local
+                    .timestamp_millis_opt((end * 1000.0) as i64)
+                    .single()
+                    .unwrap_or_else(Local::now)
+                    .format("%y-%m-%d %H:%M:%S %p")
+                    .to_string();
+                let content = format!("{label}{}", text("text").unwrap_or_default());
+                let wpm = event.get("wpm").and_then(Json::as_f64).map(|w| w as u32);
+                match self.live {
+                    true => {
+                        clear_line(out)?;
+                        self.printer.reset();
+                        let message = Message {
+                            timestamp,
+                            content,
+                            wpm,
+                        };
+                        for line in message_lines(&message, width) {
+                            writeln!(out, "{line}")?;
+                        }
+                    }
+                    false => writeln!(out, "{timestamp} {content}")?,
+                }
+            }
+            _ => {}
+        }
+        out.flush()
+    }
+}
+
+fn clear_line(out: &mut impl Write) -> io::Result<()> {
+    queue!(out, Print("\r"), Clear(ClearType::CurrentLine))
+}
+
+/// Connects to a `listen --serve`, and shows what it streams until it
+/// ends.
+pub fn connect(addr: &ServeAddr, live: bool) -> io::Result<()> {
+    let stream = TcpStream::connect(&addr.addr)?;
+    let mut viewer = Viewer {
+        live,
+        ..Viewer::default()
+    };
+    let mut stdout = io::stdout();
+    for line in BufReader::new(stream).lines() {
+        let width = term_size::dimensions().map_or(80, |(width, _)| width);
+        viewer.show(&line?, width, &mut stdout)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_serve_addr() {
+        let tcp = |addr: &str| ServeAddr {
+            protocol: Protocol::Tcp,
+            addr: addr.to_string(),
+        };
+        assert_eq!("tcp:0.0.0.0:7373".parse(), Ok(tcp("0.0.0.0:7373")));
+        assert_eq!("localhost:7373".parse(), Ok(tcp("localhost:7373")));
+        assert_eq!(
+            "ws:[::1]:7374".parse(),
+            Ok(ServeAddr {
+                protocol: Protocol::WebSocket,
+                addr: "[::1]:7374".to_string()
+            })
+        );
+        assert!("tcp:7373".parse::<ServeAddr>().is_err());
+        assert!("tcp:localhost:http".parse::<ServeAddr>().is_err());
+    }
+
+    #[test]
+    fn test_server_backlog() {
+        let server = Server::start(&"127.0.0.1:0".parse().unwrap(), OutputFormat::Json, 2).unwrap();
+        let mut serving = Serving::new(&server, None);
+        for msg in ["CQ", "QRZ", "TEST"] {
+            serving.event(&DecodeEvent::Partial(msg.to_string()), Some(20));
+            serving.event(&DecodeEvent::Message(msg.to_string()), Some(20));
+        }
+        // Only the last messages are kept for a client that connects:
+        let backlog: Vec<String> = server
+            .clients
+            .lock()
+            .unwrap()
+            .backlog
+            .iter()
+            .cloned()
+            .collect();
+        assert_eq!(backlog.len(), 2);
+        assert!(backlog[0].contains("\"text\":\"QRZ\""));
+
+        let started = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = started.local_addr().unwrap();
+        let client = TcpStream::connect(addr).unwrap();
+        server.add(started.accept().unwrap().0);
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        serving.event(&DecodeEvent::Partial("E".to_string()), None);
+        let lines: Vec<String> = BufReader::new(client)
+            .lines()
+            .take(3)
+            .map(Result::unwrap)
+            .collect();
+        assert!(lines[1].contains("\"text\":\"TEST\""), "{lines:?}");
+        assert!(lines[2].contains("\"char\":\"E\""), "{lines:?}");
+    }
+
+    #[test]
+    fn test_viewer() {
+        let show = |viewer: &mut Viewer, lines: &[&str]| {
+            let mut out = Vec::new();
+            for line in lines {
+                viewer.show(line, 40, &mut out).unwrap();
+            }
+            String::from_utf8(out).unwrap()
+        };
+        let stream = [
+            "{\"type\":\"char\",\"char\":\"C\",\"t\":1.0,\"wpm\":null}",
+            "{\"type\":\"char\",\"char\":\"Q\",\"t\":1.5,\"wpm\":null}",
+            "{\"type\":\"message\",\"text\":\"CQ\",\"start\":1.0,\"end\":2.0,\"wpm\":20}",
+        ];
+        let mut viewer = Viewer::default();
+        let shown = show(&mut viewer, &stream);
+        // Only the message, when it isn't shown live:
+        assert_eq!(shown.lines().count(), 1);
+        assert!(shown.ends_with(" CQ\n"), "{shown:?}");
+
+        let mut viewer = Viewer {
+            live: true,
+            ..Viewer::default()
+        };
+        let shown = show(&mut viewer, &stream);
+        // Each character once as it comes, then the message logged:
+        assert!(shown.contains("CQ\r"), "{shown:?}");
+        assert_eq!(shown.matches('Q').count(), 2, "{shown:?}");
+        assert!(shown.contains("20 WPM"), "{shown:?}");
+
+        // The lines of text are shown as they are:
+        assert_eq!(show(&mut viewer, &["24-11-02 CQ"]), "24-11-02 CQ\n");
+    }
+}
@@ -0,0 +1,231 @@
+use crate::pipewire::{start_sidetone, SidetoneStream};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How the sidetone of the keying sounds, and where it is played.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SidetoneOptions {
+    pub freq: f32,
+    /// The peak amplitude of the tone [0.0..1.0].
+    pub volume: f32,
+    /// How long the tone fades in and out, in milliseconds.
+    pub rise_time: f32,
+    /// The PipeWire node to play to, instead of the default one.
+    pub target: Option<String>,
+}
+
+/// When the key is held down, for the sidetone to follow: each mark
+/// from when it starts, until it ends if it is known.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Marks {
+    marks: VecDeque<(Instant, Option<Instant>)>,
+}
+
+impl Marks {
+    /// The key went down, e.g. a straight key.
+    pub fn down(&mut self, at: Instant) {
+        self.marks.push_back((at, None));
+    }
+
+    /// The key went up.
+    pub fn up(&mut self, at: Instant) {
+        if let Some((_, end @ None)) = self.marks.back_mut() {
+            *end = Some(at);
+        }
+    }
+
+    /// A mark of a known length, e.g. an element of a keyer.
+    pub fn mark(&mut self, start: Instant, length: Duration) {
+        self.marks.push_back((start, Some(start + length)));
+    }
+
+    /// Lets the key up at some time, forgetting the marks after it, e.g.
+    /// when the sending is aborted.
+    pub fn cut(&mut self, at: Instant) {
+        self.marks.retain(|&(start, _)| start < at);
+        for (_, end) in &mut self.marks {
+            *end = Some(end.map_or(at, |end| end.min(at)));
+        }
+    }
+
+    #[allow(dead_code)]
+    fn is_down(&self, at: Instant) -> bool {
+        self.marks
+            .iter()
+            .any(|&(start, end)| start <= at && end.is_none_or(|end| at < end))
+    }
+
+    /// Forgets the marks that ended before some time.
+    #[allow(dead_code)]
+    fn forget(&mut self, before: Instant) {
+        self.marks
+            .retain(|&(_, end)| end.is_none_or(|end| end >= before));
+    }
+}
+
+/// Synthesizes a sidetone that follows the marks of the key, fading in
+/// and out with a raised cosine so that it doesn't click, and silent
+/// in between.
+#[allow(dead_code)]
+pub struct SidetoneTone {
+    freq: f32,
+    volume: f32,
+    sample_rate: u32,
+    rise_samples: usize,
+    // How far into its rise the tone is, from 0 (silent) to rise_samples:
+    level: usize,
+    phase: f32,
+}
+
+#[allow(dead_code)]
+impl SidetoneTone {
+    pub fn new(options: &SidetoneOptions, sample_rate: u32) -> Self {
+        Self {
+            freq: options.freq,
+            volume: options.volume.clamp(0.0, 1.0),
+            sample_rate,
+            rise_samples: (options.rise_time.max(0.0) * sample_rate as f32 / 1000.0).round()
+                as usize,
+            level: 0,
+            phase: 0.0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Fills a block of samples that is played from `start`, with the
+    /// tone while the key is down in the marks.
+    pub fn fill(&mut self, samples: &mut [f32], start: Instant, marks: &mut Marks) {
+        marks.forget(start);
+        let step = Duration::from_secs(1) / self.sample_rate.max(1);
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let down = marks.is_down(start + step * i as u32);
+            match down {
+                true if self.level < self.rise_samples => self.level += 1,
+                false if self.level > 0 => self.level -= 1,
+                _ => {}
+            }
+            let gain = match self.rise_samples {
+                0 => down as u8 as f32,
+                rise => 0.5 - 0.5 * (PI * self.level as f32 / rise as f32).cos(),
+            };
+            *sample = match gain > 0.0 {
+                true => self.volume * gain * (2.0 * PI * self.phase).sin(),
+                false => 0.0,
+            };
+            self.phase = (self.phase + self.freq / self.sample_rate as f32).fract();
+        }
+    }
+}
+
+/// A sidetone played on a playback stream that is connected once, and
+/// fed silence between the marks, so that the tone starts within one
+/// audio quantum of the key going down.
+pub struct Sidetone {
+    marks: Arc<Mutex<Marks>>,
+    _stream: SidetoneStream,
+}
+
+impl Sidetone {
+    pub fn start(options: &SidetoneOptions) -> Result<Self, String> {
+        let marks = Arc::new(Mutex::new(Marks::default()));
+        let tone = SidetoneTone::new(options, crate::keyer::SAMPLE_RATE);
+        let stream = start_sidetone(options.target.clone(), tone, marks.clone())?;
+        Ok(Self {
+            marks,
+            _stream: stream,
+        })
+    }
+
+    pub fn down(&self, at: Instant) {
+        self.marks.lock().unwrap().down(at);
+    }
+
+    pub fn up(&self, at: Instant) {
+        self.marks.lock().unwrap().up(at);
+    }
+
+    pub fn mark(&self, start: Instant, length: Duration) {
+        self.marks.lock().unwrap().mark(start, length);
+    }
+
+    #[allow(dead_code)]
+    pub fn cut(&self, at: Instant) {
+        self.marks.lock().unwrap().cut(at);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sidetone_envelope() {
+        let options = SidetoneOptions {
+            freq: 1000.0,
+            volume: 0.5,
+            rise_time: 5.0,
+            target: None,
+        };
+        // At 1 kHz, a sample every millisecond:
+        let mut tone = SidetoneTone::new(&options, 1000);
+        tone.freq = 250.0;
+        let (start, ms) = (Instant::now(), Duration::from_millis);
+        let mut marks = Marks::default();
+        marks.down(start + ms(10));
+        let mut samples = [1.0; 40];
+        tone.fill(&mut samples, start, &mut marks);
+        // Silent until the key goes down:
+        assert!(samples[..10].iter().all(|&s| s == 0.0));
+        // The tone then rises over 5 samples, to its volume:
+        let gains: Vec<f32> = (10..=14)
+            .map(|i| 0.5 - 0.5 * (PI * (i - 9) as f32 / 5.0).cos())
+            .collect();
+        for (i, gain) in (10..=14).zip(&gains) {
+            let phase = (i as f32 * 0.25).fract();
+            assert!((samples[i] - 0.5 * gain * (2.0 * PI * phase).sin()).abs() < 1e-4);
+        }
+        let peak = samples[15..].iter().fold(0.0f32, |max, s| max.max(s.abs()));
+        assert!((peak - 0.5).abs() < 1e-4, "{peak}");
+
+        // It falls as smoothly once the key is up, and stays silent:
+        marks.up(start + ms(45));
+        let mut samples = [1.0; 20];
+        tone.fill(&mut samples, start + ms(40), &mut marks);
+        assert!(samples[5..10].iter().all(|s| s.abs() < 0.5));
+        assert!(samples[10..].iter().all(|&s| s == 0.0));
+        // The ended marks are forgotten:
+        tone.fill(&mut samples, start + ms(60), &mut marks);
+        assert_eq!(marks, Marks::default());
+    }
+
+    #[test]
+    fn test_marks() {
+        let (start, ms) = (Instant::now(), Duration::from_millis);
+        let mut marks = Marks::default();
+        marks.mark(start + ms(100), ms(60));
+        marks.mark(start + ms(220), ms(180));
+        assert!(!marks.is_down(start + ms(99)));
+        assert!(marks.is_down(start + ms(100)));
+        assert!(!marks.is_down(start + ms(160)));
+        assert!(marks.is_down(start + ms(399)));
+        marks.forget(start + ms(200));
+        assert_eq!(marks.marks.len(), 1);
+        // Cut short, and the later marks forgotten:
+        let mut cut = Marks::default();
+        cut.mark(start, ms(180));
+        cut.mark(start + ms(240), ms(60));
+        cut.cut(start + ms(100));
+        assert!(cut.is_down(start + ms(99)));
+        assert!(!cut.is_down(start + ms(100)));
+        assert_eq!(cut.marks.len(), 1);
+        // A release without a press is left out:
+        let mut marks = Marks::default();
+        marks.up(start);
+        assert_eq!(marks, Marks::default());
+    }
+}
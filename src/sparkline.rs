@@ -0,0 +1,53 @@
+/// The bars of a sparkline, from the lowest to the highest.
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The same in plain ASCII, for the terminals without block characters.
+const ASCII_BARS: [char; 8] = ['_', '.', ',', '-', '~', '+', '*', '#'];
+
+/// Whether the terminal is likely to show the block characters: when
+/// the locale is UTF-8, and the terminal is not a dumb one.
+pub fn unicode_supported() -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_lowercase();
+    let dumb = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+    (locale.contains("utf-8") || locale.contains("utf8")) && !dumb
+}
+
+/// Draws values as a line of bars, as high as each value is between the
+/// lowest and the highest of them. Values that are all the same are
+/// drawn halfway.
+pub fn sparkline(values: &[f64], ascii: bool) -> String {
+    let bars = match ascii {
+        true => ASCII_BARS,
+        false => BARS,
+    };
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|value| {
+            let level = match max - min {
+                range if range > 0.0 => (value - min) / range,
+                _ => 0.5,
+            };
+            bars[(level * (bars.len() - 1) as f64).round() as usize]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[50.0, 75.0, 100.0, 60.0], false), "▁▅█▂");
+        assert_eq!(sparkline(&[50.0, 75.0, 100.0, 60.0], true), "_~#.");
+        assert_eq!(sparkline(&[90.0, 90.0], false), "▅▅");
+        assert_eq!(sparkline(&[], false), "");
+    }
+}
@@ -0,0 +1,206 @@
+use crate::narrator::Narrator;
+use crate::prelude::*;
+use std::collections::VecDeque;
+use std::process::Command;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// How many texts may wait to be spoken before the oldest are dropped.
+const QUEUE: usize = 8;
+
+/// The programs tried to speak with, in order, with their arguments
+/// before the text. Each one waits until the text is spoken.
+const ENGINES: [(&str, &[&str]); 2] = [("espeak-ng", &[]), ("spd-say", &["--wait"])];
+
+/// What is spoken with `listen --speak`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SpeakMode {
+    /// Each message, once it ended.
+    #[default]
+    Messages,
+    /// Each word, once it is complete.
+    Words,
+}
+
+impl FromStr for SpeakMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "messages" => Ok(SpeakMode::Messages),
+            "words" => Ok(SpeakMode::Words),
+            _ => Err(format!("Speak must be 'messages' or 'words': {s}")),
+        }
+    }
+}
+
+/// The texts waiting to be spoken, shared with the thread that speaks
+/// them.
+#[derive(Debug, Default)]
+struct Queue {
+    texts: Mutex<VecDeque<String>>,
+    ready: Condvar,
+}
+
+impl Queue {
+    /// Adds a text, dropping the oldest one when the queue is full.
+    ///
+    /// # Returns
+    /// Whether one was dropped.
+    fn push(&self, text: String) -> bool {
+        let mut texts = self.texts.lock().unwrap();
+        let dropped = texts.len() >= QUEUE && texts.pop_front().is_some();
+        texts.push_back(text);
+        self.ready.notify_one();
+        dropped
+    }
+
+    fn pop(&self) -> String {
+        let texts = self.texts.lock().unwrap();
+        let mut texts = self.ready.wait_while(texts, |t| t.is_empty()).unwrap();
+        texts.pop_front().expect("waited for a text")
+    }
+}
+
+/// Speaks the decoded text aloud through a text to speech program,
+/// `espeak-ng` or else `spd-say` (of speech-dispatcher), on a thread of
+/// its own so that a slow one never holds up the decoding.
+///
+/// Each station has a clone of its own, sharing the queue.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Speaker {
+    mode: SpeakMode,
+    /// Messages shorter than this many characters aren't spoken, as they
+    /// are likely noise.
+    min_length: usize,
+    queue: Arc<Queue>,
+    narrator: Narrator,
+    // The words of the message not spoken yet, until it is long enough:
+    held: Vec<String>,
+    // Whether the oldest texts are being dropped, to only warn once:
+    dropping: bool,
+}
+
+#[allow(dead_code)]
+impl Speaker {
+    fn new(mode: SpeakMode, min_length: usize) -> Self {
+        Self {
+            mode,
+            min_length,
+            queue: Arc::default(),
+            narrator: Narrator::default(),
+            held: Vec::new(),
+            dropping: false,
+        }
+    }
+
+    /// Starts the thread that speaks.
+    pub fn start(mode: SpeakMode, min_length: usize) -> Self {
+        let speaker = Self::new(mode, min_length);
+        let queue = speaker.queue.clone();
+        std::thread::spawn(move || {
+            let mut engines = ENGINES.iter();
+            let mut engine = engines.next();
+            loop {
+                let text = queue.pop();
+                while let Some((program, args)) = engine {
+                    match Command::new(program).args(*args).arg(&text).output() {
+                        Ok(_) => break,
+                        Err(e) => debug!("Could not speak with {program}: {e}"),
+                    }
+                    engine = engines.next();
+                    if engine.is_none() {
+                        warn!("Could not speak, install espeak-ng or speech-dispatcher");
+                    }
+                }
+            }
+        });
+        speaker
+    }
+
+    fn long_enough(&self, text: &str) -> bool {
+        text.trim().chars().count() >= self.min_length
+    }
+
+    fn speak(&mut self, texts: Vec<String>) {
+        for text in texts {
+            let dropped = self.queue.push(text);
+            if dropped && !self.dropping {
+                warn!("Speaking is behind the decoding, dropping the oldest text");
+            }
+            self.dropping = dropped;
+        }
+    }
+
+    /// Follows the message so far, to speak each word once it is
+    /// complete, with `SpeakMode::Words`.
+    pub fn partial(&mut self, text: &str) {
+        if self.mode != SpeakMode::Words {
+            return;
+        }
+        self.held.extend(self.narrator.words(text));
+        if self.long_enough(text) {
+            let words = std::mem::take(&mut self.held);
+            self.speak(words);
+        }
+    }
+
+    /// Speaks what is left of a message that ended, if it is long
+    /// enough.
+    pub fn message(&mut self, text: &str) {
+        let texts = match self.mode {
+            SpeakMode::Messages => vec![text.trim().to_string()],
+            SpeakMode::Words => {
+                let mut words = std::mem::take(&mut self.held);
+                words.extend(self.narrator.finish(text));
+                words
+            }
+        };
+        if self.long_enough(text) {
+            self.speak(texts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(speaker: &Speaker) -> Vec<String> {
+        speaker.queue.texts.lock().unwrap().drain(..).collect()
+    }
+
+    #[test]
+    fn test_speaker() {
+        let mut speaker = Speaker::new(SpeakMode::Messages, 3);
+        speaker.partial("CQ ");
+        assert!(queued(&speaker).is_empty());
+        speaker.message("CQ DE N0CALL");
+        // Too short, likely noise:
+        speaker.message("E");
+        assert_eq!(queued(&speaker), ["CQ DE N0CALL"]);
+
+        let mut speaker = Speaker::new(SpeakMode::Words, 5);
+        speaker.partial("CQ ");
+        // Held until the message is long enough:
+        assert!(queued(&speaker).is_empty());
+        speaker.partial("CQ DE ");
+        assert_eq!(queued(&speaker), ["CQ", "DE"]);
+        speaker.partial("CQ DE N0");
+        speaker.message("CQ DE N0CALL");
+        assert_eq!(queued(&speaker), ["N0CALL"]);
+        speaker.partial("T ");
+        speaker.message("T E");
+        assert!(queued(&speaker).is_empty());
+
+        // The oldest are dropped when it is behind:
+        for n in 0..QUEUE + 2 {
+            speaker.message(&format!("MESSAGE{n}"));
+        }
+        let texts = queued(&speaker);
+        assert_eq!(texts.len(), QUEUE);
+        assert_eq!(texts[0], "MESSAGE2");
+        assert_eq!(speaker.mode, "words".parse().unwrap());
+        assert!("letters".parse::<SpeakMode>().is_err());
+    }
+}
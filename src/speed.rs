@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+
+/// Number of recent tones (and gaps) the estimate is based on.
+const HISTORY: usize = 16;
+
+/// Fewer tones than this aren't enough to tell dots from dashes.
+const MIN_TONES: usize = 4;
+
+/// Dashes are nominally three dots long.
+const DASH_DOTS: f64 = 3.0;
+
+/// Estimates the sender's dot duration from the tones they send.
+///
+/// The recent tone durations are split into two clusters, the dots and
+/// the dashes, and the dot duration is taken from both of them (a dash
+/// counting as three dots). The detector tends to hear tones a little
+/// longer than they are, and the gaps between them shorter by as much,
+/// so the gaps within characters (one dot each) are averaged in too.
+pub struct SpeedTracker {
+    tones: VecDeque<u32>,
+    gaps: VecDeque<u32>,
+}
+
+fn push_recent(durations: &mut VecDeque<u32>, duration: u32) {
+    if durations.len() == HISTORY {
+        durations.pop_front();
+    }
+    durations.push_back(duration);
+}
+
+impl SpeedTracker {
+    pub fn new() -> Self {
+        Self {
+            tones: VecDeque::with_capacity(HISTORY),
+            gaps: VecDeque::with_capacity(HISTORY),
+        }
+    }
+
+    /// Adds the duration of a received tone, in milliseconds.
+    pub fn push_tone(&mut self, duration: u32) {
+        push_recent(&mut self.tones, duration);
+    }
+
+    /// Adds the duration of a gap between tones, in milliseconds.
+    pub fn push_gap(&mut self, duration: u32) {
+        push_recent(&mut self.gaps, duration);
+    }
+
+    /// Estimates the current dot duration.
+    ///
+    /// # Arguments
+    /// * `dot_duration` - The dot duration believed so far, in milliseconds.
+    ///   It decides whether the tones are dots or dashes when they are
+    ///   all about the same length (e.g. a run of E's or T's).
+    ///
+    /// # Returns
+    /// The dot duration in milliseconds, once enough tones were received.
+    pub fn estimate(&self, dot_duration: u32) -> Option<u32> {
+        if self.tones.len() < MIN_TONES {
+            return None;
+        }
+        let shortest = *self.tones.iter().min()? as f64;
+        let longest = *self.tones.iter().max()? as f64;
+        let mean = |durations: &[f64]| durations.iter().sum::<f64>() / durations.len() as f64;
+        let tones: Vec<f64> = self.tones.iter().map(|&t| t as f64).collect();
+        let total = tones.iter().sum::<f64>();
+        // How many dots long all of the tones are together:
+        let dots = if longest < 2.0 * shortest {
+            // Only one kind of element, pick whichever fits best:
+            let all = mean(&tones);
+            let reference = dot_duration as f64;
+            if (all - reference).abs() < (all - DASH_DOTS * reference).abs() {
+                tones.len() as f64
+            } else {
+                DASH_DOTS * tones.len() as f64
+            }
+        } else {
+            // Two means clustering, starting in between the extremes:
+            let mut split = (shortest + longest) / 2.0;
+            for _ in 0..10 {
+                let (short, long): (Vec<f64>, Vec<f64>) = tones.iter().partition(|&&t| t < split);
+                let next = (mean(&short) + mean(&long)) / 2.0;
+                if next == split {
+                    break;
+                }
+                split = next;
+            }
+            let dashes = tones.iter().filter(|&&t| t >= split).count() as f64;
+            tones.len() as f64 + (DASH_DOTS - 1.0) * dashes
+        };
+        let dot = total / dots;
+        // The gaps between the elements of a character:
+        let element_gaps: Vec<f64> = self
+            .gaps
+            .iter()
+            .map(|&g| g as f64)
+            .filter(|&g| g < 2.0 * dot)
+            .collect();
+        // Each tone is heard longer by as much as each of these gaps is
+        // shorter, which cancels out when they are counted together:
+        let dot = if element_gaps.is_empty() {
+            dot
+        } else {
+            let gap = mean(&element_gaps);
+            (total + tones.len() as f64 * gap) / (dots + tones.len() as f64)
+        };
+        Some(dot.round().max(1.0) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(tones: &[u32], dot_duration: u32) -> Option<u32> {
+        let mut tracker = SpeedTracker::new();
+        for &tone in tones {
+            tracker.push_tone(tone);
+        }
+        tracker.estimate(dot_duration)
+    }
+
+    #[test]
+    fn test_speed_tracker_clusters_dots_and_dashes() {
+        // C Q at 25 WPM (48ms dots), a little ragged:
+        let tones = [150, 46, 140, 50, 145, 144, 49, 150];
+        assert_eq!(track(&tones, 60), Some(49));
+    }
+
+    #[test]
+    fn test_speed_tracker_corrects_with_gaps() {
+        // Tones heard 10ms too long, and gaps 10ms too short:
+        let mut tracker = SpeedTracker::new();
+        for (tone, gap) in [(70, 50), (190, 170), (70, 50), (190, 410)] {
+            tracker.push_tone(tone);
+            tracker.push_gap(gap);
+        }
+        assert_eq!(tracker.estimate(60), Some(60));
+    }
+
+    #[test]
+    fn test_speed_tracker_needs_enough_tones() {
+        assert_eq!(track(&[60, 180, 60], 60), None);
+    }
+
+    #[test]
+    fn test_speed_tracker_one_kind_of_element() {
+        // A run of dashes at 15 WPM isn't mistaken for slow dots:
+        assert_eq!(track(&[240, 240, 240, 240], 60), Some(80));
+        assert_eq!(track(&[80, 80, 80, 80], 60), Some(80));
+    }
+
+    #[test]
+    fn test_speed_tracker_follows_the_recent_tones() {
+        let mut tones = [60, 180, 60, 180].repeat(4);
+        tones.extend([100, 300, 100, 300].repeat(4));
+        assert_eq!(track(&tones, 60), Some(100));
+    }
+}
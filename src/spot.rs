@@ -0,0 +1,372 @@
+use crate::callsign::callsign_regex;
+use crate::detect::ToneLevel;
+use crate::json::Object;
+use crate::prelude::*;
+use crate::rigctl::Tuning;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How many spots may wait to be written or posted, before the new ones
+/// are dropped.
+const QUEUE: usize = 64;
+/// How long the HTTP endpoint may take to answer a spot.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The callsign calling CQ in a message, e.g. `K1ABC` in `CQ CQ DE
+/// K1ABC K1ABC K`.
+///
+/// The rest of the message may be copied partly, but not the callsign:
+/// it is the first one after the `DE` (after a `CQ`) that has none of
+/// its characters missing or in error, the callsigns usually being
+/// sent twice.
+pub fn cq_callsign(message: &str, callsign: &Regex) -> Option<String> {
+    let words: Vec<&str> = message.split_whitespace().collect();
+    let cq = words.iter().position(|&word| word == "CQ")?;
+    let de = cq + words[cq..].iter().position(|&word| word == "DE")?;
+    words[de + 1..]
+        .iter()
+        .take(2)
+        .find(|word| callsign.is_match(word))
+        .map(|word| word.to_string())
+}
+
+/// A station heard calling CQ, as the Reverse Beacon Network spots
+/// them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spot {
+    pub time: DateTime<Utc>,
+    pub callsign: String,
+    /// The frequency it was heard on, with --rigctl.
+    pub freq_hz: Option<u64>,
+    pub mode: Option<String>,
+    pub wpm: Option<u32>,
+    /// The estimate of the signal to noise ratio, in dB.
+    pub snr_db: Option<f32>,
+    /// The label of the station, with --split-stereo.
+    pub station: Option<String>,
+}
+
+impl Spot {
+    /// The spot as a DX cluster line, e.g. `DX de N0CALL-#:   14025.0
+    /// K1ABC        CW    18 dB  22 WPM  CQ      1830Z`. An unknown
+    /// frequency is 0.0, and an unknown SNR or speed is left out.
+    pub fn to_cluster(&self, spotter: &str) -> String {
+        let freq = self.freq_hz.unwrap_or(0) as f64 / 1000.0;
+        let snr = self
+            .snr_db
+            .map_or(String::new(), |snr| format!("{snr:>3.0} dB"));
+        let wpm = self
+            .wpm
+            .map_or(String::new(), |wpm| format!("{wpm:>3} WPM"));
+        format!(
+            "DX de {:<11}{freq:>9.1}  {:<12} {:<4} {snr:>6} {wpm:>7}  CQ      {}Z",
+            format!("{spotter}-#:"),
+            self.callsign,
+            self.mode.as_deref().unwrap_or("CW"),
+            self.time.format("%H%M")
+        )
+    }
+
+    /// The spot as JSON, to post.
+    pub fn to_json(&self) -> String {
+        let timestamp = self.time.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        Object::new()
+            .string("timestamp", timestamp.as_str())
+            .string("callsign", self.callsign.as_str())
+            .number("freq_hz", self.freq_hz)
+            .string("mode", self.mode.as_deref())
+            .number("wpm", self.wpm)
+            .fixed("snr_db", self.snr_db, 0)
+            .string(
+                "station",
+                self.station
+                    .as_deref()
+                    .map(|label| label.trim_end_matches(':')),
+            )
+            .end()
+    }
+}
+
+/// Where the spots go, with `listen --spot`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpotTarget {
+    /// A file, appended a DX cluster line for each spot.
+    File(String),
+    /// An HTTP endpoint, e.g. `http://localhost:8080/spots`, posted the
+    /// JSON of each spot.
+    Http {
+        host: String,
+        port: u16,
+        path: String,
+    },
+}
+
+impl FromStr for SpotTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("https://") {
+            return Err(format!("HTTPS is not supported yet: {s}"));
+        }
+        let Some(rest) = s.strip_prefix("http://") else {
+            return Ok(SpotTarget::File(s.to_string()));
+        };
+        let (addr, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match addr.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().ok()),
+            None => (addr, Some(80)),
+        };
+        match port {
+            Some(port) if !host.is_empty() => Ok(SpotTarget::Http {
+                host: host.to_string(),
+                port,
+                path: path.to_string(),
+            }),
+            _ => Err(format!(
+                "The endpoint must be like http://localhost:8080/spots: {s}"
+            )),
+        }
+    }
+}
+
+/// Posts the JSON of a spot, and checks that it was taken.
+fn post(host: &str, port: u16, path: &str, json: &str) -> io::Result<()> {
+    let mut stream = TcpStream::connect((host, port))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    write!(
+        stream,
+        "POST {path} HTTP/1.1\r\nHost: {host}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{json}",
+        json.len()
+    )?;
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "The endpoint answered {}",
+            status.trim()
+        ))),
+    }
+}
+
+/// Writes or posts the spots as they come, so that the decoding never
+/// waits for them.
+fn deliver(spots: Receiver<Spot>, target: SpotTarget, spotter: String) {
+    for spot in spots {
+        let result = match &target {
+            SpotTarget::File(path) => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", spot.to_cluster(&spotter))),
+            SpotTarget::Http { host, port, path } => post(host, *port, path, &spot.to_json()),
+        };
+        if let Err(e) = result {
+            warn!("Could not spot {}: {e}", spot.callsign);
+        }
+    }
+}
+
+/// Spots the stations heard calling CQ, once for each callsign within
+/// a window of time. Each station of `listen` has its own clone, with
+/// the estimate of the SNR of its signal, the callsigns spotted being
+/// shared.
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct Spotter {
+    spots: SyncSender<Spot>,
+    window: Duration,
+    spotted: Arc<Mutex<HashMap<String, Instant>>>,
+    snr: Option<f32>,
+    callsign: Regex,
+}
+
+#[allow(dead_code)]
+impl Spotter {
+    /// Starts spotting to a target, as the `spotter` callsign of the DX
+    /// cluster lines.
+    pub fn start(target: SpotTarget, spotter: &str, window: Duration) -> Self {
+        let (spots, receiver) = sync_channel(QUEUE);
+        let spotter = spotter.to_string();
+        std::thread::spawn(move || deliver(receiver, target, spotter));
+        Self {
+            spots,
+            window,
+            spotted: Arc::new(Mutex::new(HashMap::new())),
+            snr: None,
+            callsign: callsign_regex(),
+        }
+    }
+
+    /// Measures the SNR of the signal, the strongest during the message,
+    /// from the peak of the tone and the noise floor of the adaptive
+    /// threshold.
+    pub fn level(&mut self, level: &ToneLevel) {
+        if let (Some(peak), Some(noise)) = (level.peak, level.noise) {
+            if level.tone && peak > 0.0 && noise > 0.0 {
+                let snr = 20.0 * (peak / noise).log10();
+                self.snr = Some(self.snr.map_or(snr, |max| max.max(snr)));
+            }
+        }
+    }
+
+    /// Spots the callsign calling CQ in a message, unless it was spotted
+    /// within the window.
+    pub fn message(
+        &mut self,
+        message: &str,
+        wpm: Option<u32>,
+        tuning: Option<Tuning>,
+        station: Option<&str>,
+    ) -> Option<Spot> {
+        let snr = self.snr.take();
+        let callsign = cq_callsign(message, &self.callsign)?;
+        let now = Instant::now();
+        {
+            let mut spotted = self.spotted.lock().unwrap();
+            spotted.retain(|_, at| now.duration_since(*at) < self.window);
+            if spotted.contains_key(&callsign) {
+                debug!("{callsign} was already spotted");
+                return None;
+            }
+            spotted.insert(callsign.clone(), now);
+        }
+        let tuning = tuning.unwrap_or_default();
+        let spot = Spot {
+            time: Utc::now(),
+            callsign,
+            freq_hz: tuning.freq_hz,
+            mode: tuning.mode,
+            wpm,
+            snr_db: snr,
+            station: station.map(str::to_string),
+        };
+        info!("Spotted {}", spot.callsign);
+        match self.spots.try_send(spot.clone()) {
+            Err(TrySendError::Full(_)) => warn!("Too many spots waiting, dropped one"),
+            Err(TrySendError::Disconnected(_)) | Ok(()) => {}
+        }
+        Some(spot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_cq_callsign() {
+        let regex = callsign_regex();
+        let call = |message| cq_callsign(message, &regex);
+        assert_eq!(call("CQ CQ DE K1ABC K1ABC K"), Some("K1ABC".to_string()));
+        assert_eq!(call("CQ TEST DE DL/W5XYZ"), Some("DL/W5XYZ".to_string()));
+        assert_eq!(call("?Q CQ DE 2E0XYZ PSE K"), Some("2E0XYZ".to_string()));
+        // The first callsign is in error, but it was sent twice:
+        assert_eq!(call("CQ CQ DE K1A?C K1ABC"), Some("K1ABC".to_string()));
+        assert_eq!(call("CQ DE W1AW/P"), Some("W1AW/P".to_string()));
+        assert_eq!(call("CQ DE K1A?C"), None);
+        assert_eq!(call("CQ DE K?ABC PSE K"), None);
+        assert_eq!(call("K1ABC DE N0CALL 5NN"), None);
+        assert_eq!(call("CQ CQ D? K1ABC"), None);
+        assert_eq!(call("CQ DE TEST"), None);
+        assert_eq!(call("CQ DE 599"), None);
+    }
+
+    #[test]
+    fn test_spot_lines() {
+        let spot = Spot {
+            time: Utc.with_ymd_and_hms(2024, 11, 2, 18, 30, 12).unwrap(),
+            callsign: "K1ABC".to_string(),
+            freq_hz: Some(14025040),
+            mode: Some("CW".to_string()),
+            wpm: Some(22),
+            snr_db: Some(18.4),
+            station: None,
+        };
+        assert_eq!(
+            spot.to_cluster("N0CALL"),
+            "DX de N0CALL-#:    14025.0  K1ABC        CW    18 dB  22 WPM  CQ      1830Z"
+        );
+        assert_eq!(
+            spot.to_json(),
+            "{\"timestamp\":\"2024-11-02T18:30:12Z\",\"callsign\":\"K1ABC\",\"freq_hz\":14025040,\"mode\":\"CW\",\"wpm\":22,\"snr_db\":18,\"station\":null}"
+        );
+        assert_eq!(
+            "http://localhost:8080/spots".parse(),
+            Ok(SpotTarget::Http {
+                host: "localhost".to_string(),
+                port: 8080,
+                path: "/spots".to_string()
+            })
+        );
+        assert_eq!(
+            "spots.txt".parse(),
+            Ok(SpotTarget::File("spots.txt".to_string()))
+        );
+        assert!("https://example.com/spots".parse::<SpotTarget>().is_err());
+    }
+
+    #[test]
+    fn test_spotter() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let target = SpotTarget::Http {
+            host: "127.0.0.1".to_string(),
+            port,
+            path: "/spots".to_string(),
+        };
+        let mut spotter = Spotter::start(target, "N0CALL", Duration::from_secs(600));
+        let mut other = spotter.clone();
+        spotter.level(&ToneLevel {
+            level: 0.5,
+            threshold: Some(0.2),
+            peak: Some(0.5),
+            noise: Some(0.005),
+            tone: true,
+            offset: None,
+        });
+        let tuning = Tuning {
+            freq_hz: Some(7030000),
+            mode: Some("CW".to_string()),
+        };
+        let spot = spotter
+            .message("CQ DE K1ABC K", Some(20), Some(tuning), Some("L:"))
+            .unwrap();
+        assert_eq!(spot.snr_db, Some(40.0));
+        // Once within the window, whichever station hears it:
+        assert_eq!(other.message("CQ CQ DE K1ABC", None, None, None), None);
+        assert_eq!(spotter.message("QRZ DE N0CALL", None, None, None), None);
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request = String::new();
+        while reader.read_line(&mut request).unwrap() > 2 {}
+        assert!(request.starts_with("POST /spots HTTP/1.1\r\n"), "{request}");
+        let length: usize = request
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut body = vec![0; length];
+        std::io::Read::read_exact(&mut reader, &mut body).unwrap();
+        let json = crate::json::Json::parse(std::str::from_utf8(&body).unwrap()).unwrap();
+        assert_eq!(json.get("callsign").unwrap().as_str(), Some("K1ABC"));
+        assert_eq!(json.get("station").unwrap().as_str(), Some("L"));
+        (&stream)
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+            .unwrap();
+    }
+}
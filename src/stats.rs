@@ -0,0 +1,948 @@
+use crate::band::Conditions;
+use crate::confusion::{confusions, merge, Confusion};
+use crate::distribution::Distribution;
+use crate::export::{Export, Trial};
+use crate::fecr_quiz::QuizResult;
+use crate::json::{Json, Object};
+use crate::sparkline::sparkline;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tabled::settings::style::Style;
+use tabled::{Table, Tabled};
+
+/// How many of the latest sessions of a character make its baseline.
+const BASELINE_SESSIONS: usize = 10;
+
+/// Where the sessions are recorded.
+pub fn default_path() -> Option<PathBuf> {
+    crate::profile::path("sessions.jsonl")
+}
+
+/// How one character was copied during a session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CharacterStats {
+    pub character: String,
+    pub count: u32,
+    pub correct: u32,
+    /// The mean reaction time, without the outliers.
+    pub reaction: Duration,
+    /// How the reaction times were spread, in the records that have it.
+    pub distribution: Option<Distribution>,
+}
+
+/// The record of one practice session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    /// When the session ended, to the second, e.g. 20241102T183000.
+    pub id: String,
+    pub date: String,
+    /// e.g. koch, groups, callsigns, words, exchange, qso, copy-behind,
+    /// send or fecr-quiz.
+    pub mode: String,
+    pub pool: String,
+    pub wpm: u32,
+    /// The percentage of the session copied correctly.
+    pub accuracy: f64,
+    /// The percentage of the characters recognized fast enough, see
+    /// `QuizResult::fast_enough`. Only for the sessions scored by
+    /// character.
+    pub fecr: Option<f64>,
+    pub duration: Duration,
+    /// How the reaction times of all of the characters were spread.
+    pub reaction: Option<Distribution>,
+    pub characters: Vec<CharacterStats>,
+    /// The characters copied as others.
+    pub confusions: Vec<Confusion>,
+    /// Each character, or word or exchange, sent.
+    pub trials: Vec<Trial>,
+    /// Whether it was stopped before it ended, with the results so far.
+    pub incomplete: bool,
+    /// The noise, static crashes and fading it was sent through, or None
+    /// for a clean band.
+    pub band: Option<Conditions>,
+    /// What everything drawn at random in it was seeded with, to
+    /// practice it again.
+    pub seed: Option<u64>,
+    /// The fastest speed held for a few calls in a row of a speed
+    /// ladder, in WPM.
+    pub sustained_wpm: Option<u32>,
+}
+
+impl Session {
+    /// A session that has just ended.
+    pub fn new(mode: &str, pool: &str, wpm: u32, duration: Duration) -> Self {
+        let now = chrono::Local::now();
+        Session {
+            id: now.format("%Y%m%dT%H%M%S").to_string(),
+            date: now.format("%Y-%m-%d %H:%M").to_string(),
+            mode: mode.to_string(),
+            pool: pool.to_string(),
+            wpm,
+            accuracy: 0.0,
+            fecr: None,
+            duration,
+            reaction: None,
+            characters: Vec::new(),
+            confusions: Vec::new(),
+            trials: Vec::new(),
+            incomplete: false,
+            band: None,
+            seed: None,
+            sustained_wpm: None,
+        }
+    }
+
+    pub fn with_accuracy(self, accuracy: f64) -> Self {
+        Self { accuracy, ..self }
+    }
+
+    pub fn with_trials(self, trials: Vec<Trial>) -> Self {
+        Self { trials, ..self }
+    }
+
+    pub fn with_incomplete(self, incomplete: bool) -> Self {
+        Self { incomplete, ..self }
+    }
+
+    pub fn with_band(self, band: Option<Conditions>) -> Self {
+        Self { band, ..self }
+    }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    pub fn with_sustained_wpm(self, sustained_wpm: Option<u32>) -> Self {
+        Self {
+            sustained_wpm,
+            ..self
+        }
+    }
+
+    /// Scores the session by how each character was copied.
+    ///
+    /// # Arguments
+    /// * `dot_duration` - The dot length the characters were sent at.
+    /// * `outlier_iqr` - How many interquartile ranges above the third
+    ///   quartile a reaction time is an outlier, left out of the scores.
+    pub fn with_characters(
+        self,
+        results: &QuizResult,
+        dot_duration: u32,
+        outlier_iqr: f64,
+    ) -> Self {
+        let limit = results.outlier_limit(outlier_iqr);
+        let times: Vec<Duration> = results.reaction_times.iter().flatten().copied().collect();
+        Self {
+            accuracy: results.accuracy(),
+            fecr: (!results.prompts.is_empty()).then(|| {
+                results.fast_enough(Duration::from_millis(dot_duration.into()) * 2, limit)
+            }),
+            reaction: (!times.is_empty()).then(|| Distribution::of(&times, limit)),
+            characters: character_stats(results, limit),
+            confusions: confusions(results),
+            ..self
+        }
+    }
+
+    /// Writes the session as a line of JSON.
+    fn to_json(&self) -> String {
+        let characters: Vec<String> = self
+            .characters
+            .iter()
+            .map(|stats| {
+                Object::new()
+                    .string("char", stats.character.as_str())
+                    .number("count", stats.count)
+                    .number("correct", stats.correct)
+                    .number("reaction_ms", stats.reaction.as_millis())
+                    .json(
+                        "distribution",
+                        stats.distribution.map(Distribution::to_json),
+                    )
+                    .end()
+            })
+            .collect();
+        let confusions: Vec<String> = self
+            .confusions
+            .iter()
+            .map(|confusion| {
+                Object::new()
+                    .string("sent", confusion.sent.as_str())
+                    .string("typed", confusion.typed.as_str())
+                    .number("count", confusion.count)
+                    .end()
+            })
+            .collect();
+        let trials: Vec<String> = self
+            .trials
+            .iter()
+            .map(|trial| trial.to_json(None))
+            .collect();
+        Object::new()
+            .string("id", self.id.as_str())
+            .string("date", self.date.as_str())
+            .string("mode", self.mode.as_str())
+            .string("pool", self.pool.as_str())
+            .number("wpm", self.wpm)
+            .fixed("accuracy", self.accuracy, 1)
+            .fixed("fecr", self.fecr, 1)
+            .number("duration_s", self.duration.as_secs())
+            .json("reaction", self.reaction.map(Distribution::to_json))
+            .array("characters", characters)
+            .array("confusions", confusions)
+            .array("trials", trials)
+            .bool("incomplete", self.incomplete)
+            .json("band", self.band.map(Conditions::to_json))
+            .string("seed", self.seed.map(|seed| seed.to_string()).as_deref())
+            .number("sustained_wpm", self.sustained_wpm)
+            .end()
+    }
+
+    /// Reads a session from a line of JSON, or None if it isn't one.
+    fn from_json(line: &str) -> Option<Self> {
+        let json = Json::parse(line)?;
+        let millis = |value: &Json| value.as_f64().map(|ms| Duration::from_millis(ms as u64));
+        let characters = json
+            .get("characters")?
+            .as_array()?
+            .iter()
+            .map(|stats| {
+                Some(CharacterStats {
+                    character: stats.get("char")?.as_str()?.to_string(),
+                    count: stats.get("count")?.as_f64()? as u32,
+                    correct: stats.get("correct")?.as_f64()? as u32,
+                    reaction: millis(stats.get("reaction_ms")?)?,
+                    distribution: stats.get("distribution").and_then(Distribution::from_json),
+                })
+            })
+            .collect::<Option<Vec<CharacterStats>>>()?;
+        // Not in the records made before the confusions were:
+        let confusions = match json.get("confusions") {
+            Some(confusions) => confusions
+                .as_array()?
+                .iter()
+                .map(|confusion| {
+                    Some(Confusion {
+                        sent: confusion.get("sent")?.as_str()?.to_string(),
+                        typed: confusion.get("typed")?.as_str()?.to_string(),
+                        count: confusion.get("count")?.as_f64()? as u32,
+                    })
+                })
+                .collect::<Option<Vec<Confusion>>>()?,
+            None => Vec::new(),
+        };
+        let trials = match json.get("trials") {
+            Some(trials) => trials
+                .as_array()?
+                .iter()
+                .map(Trial::from_json)
+                .collect::<Option<Vec<Trial>>>()?,
+            None => Vec::new(),
+        };
+        let date = json.get("date")?.as_str()?.to_string();
+        Some(Session {
+            // The records made before the ids only have the date:
+            id: match json.get("id") {
+                Some(id) => id.as_str()?.to_string(),
+                None => date.clone(),
+            },
+            date,
+            mode: json.get("mode")?.as_str()?.to_string(),
+            pool: json.get("pool")?.as_str()?.to_string(),
+            wpm: json.get("wpm")?.as_f64()? as u32,
+            accuracy: json.get("accuracy")?.as_f64()?,
+            fecr: json.get("fecr").and_then(Json::as_f64),
+            duration: Duration::from_secs(json.get("duration_s")?.as_f64()? as u64),
+            // Not in the records made before the distributions were:
+            reaction: json.get("reaction").and_then(Distribution::from_json),
+            characters,
+            confusions,
+            trials,
+            // Not in the records made before sessions could be stopped:
+            incomplete: json
+                .get("incomplete")
+                .and_then(Json::as_bool)
+                .unwrap_or(false),
+            // Not in the records made before the band conditions were:
+            band: json.get("band").and_then(Conditions::from_json),
+            // Not in the records made before the sessions were seeded:
+            seed: json.get("seed").and_then(Json::as_u64),
+            // Not in the records made before the speed ladders:
+            sustained_wpm: json
+                .get("sustained_wpm")
+                .and_then(Json::as_f64)
+                .map(|wpm| wpm as u32),
+        })
+    }
+}
+
+impl CharacterStats {
+    /// The accuracy in percent.
+    pub fn accuracy(&self) -> f64 {
+        self.correct as f64 / self.count.max(1) as f64 * 100.0
+    }
+
+    /// Adds up the copies of a character, e.g. over some sessions.
+    fn combine<'a>(character: &str, stats: impl IntoIterator<Item = &'a CharacterStats>) -> Self {
+        let (mut count, mut correct, mut time) = (0, 0, Duration::ZERO);
+        for stats in stats {
+            count += stats.count;
+            correct += stats.correct;
+            time += stats.reaction * stats.count;
+        }
+        CharacterStats {
+            character: character.to_string(),
+            count,
+            correct,
+            reaction: time / count.max(1),
+            distribution: None,
+        }
+    }
+}
+
+/// How each character of a quiz or drill was copied, in order.
+///
+/// # Arguments
+/// * `outlier_limit` - The reaction time beyond which an answer is left
+///   out of the reaction times, see `QuizResult::outlier_limit`.
+pub fn character_stats(
+    results: &QuizResult,
+    outlier_limit: Option<Duration>,
+) -> Vec<CharacterStats> {
+    let mut characters: BTreeMap<&str, (u32, u32, Vec<Duration>)> = BTreeMap::new();
+    for (i, prompt) in results.prompts.iter().enumerate() {
+        let entry = characters.entry(prompt).or_default();
+        entry.0 += 1;
+        if results.responses[i] == Some(true) {
+            entry.1 += 1;
+        }
+        // Not answered in time, it only counts for the accuracy:
+        entry.2.extend(results.reaction_times[i]);
+    }
+    characters
+        .into_iter()
+        .map(|(character, (count, correct, times))| {
+            let distribution = Distribution::of(&times, outlier_limit);
+            CharacterStats {
+                character: character.to_string(),
+                count,
+                correct,
+                reaction: distribution.mean,
+                distribution: Some(distribution),
+            }
+        })
+        .collect()
+}
+
+/// How a character has been copied over the latest sessions it was
+/// sent in, or None if it never was.
+pub fn baseline(sessions: &[Session], character: &str) -> Option<CharacterStats> {
+    let history: Vec<&CharacterStats> = sessions
+        .iter()
+        .rev()
+        .filter_map(|session| {
+            session
+                .characters
+                .iter()
+                .find(|stats| stats.character == character)
+        })
+        .take(BASELINE_SESSIONS)
+        .collect();
+    match history.is_empty() {
+        true => None,
+        false => Some(CharacterStats::combine(character, history)),
+    }
+}
+
+/// Appends a session to the file of sessions.
+fn append_session(path: &Path, session: &Session) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)?;
+    // A record left half written must not run into this one:
+    let mut line = session.to_json() + "\n";
+    if file.seek(SeekFrom::End(0))? > 0 {
+        let mut last = [0];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            line.insert(0, '\n');
+        }
+    }
+    file.write_all(line.as_bytes())
+}
+
+/// Records a session in the user data directory.
+pub fn record(session: &Session) {
+    let Some(path) = default_path() else {
+        return;
+    };
+    if let Err(e) = append_session(&path, session) {
+        eprintln!("Could not record the session in {}: {}", path.display(), e);
+    }
+}
+
+/// Reads the recorded sessions, oldest first.
+///
+/// # Returns
+/// The sessions, and how many of the lines could not be read (e.g. a
+/// record left half written), which are skipped.
+pub fn read_sessions(path: &Path) -> io::Result<(Vec<Session>, usize)> {
+    let text = match std::fs::read(path) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut skipped = 0;
+    let sessions = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let session = Session::from_json(line);
+            skipped += session.is_none() as usize;
+            session
+        })
+        .collect();
+    Ok((sessions, skipped))
+}
+
+/// How much a percentage went up or down, from the older half of some
+/// values to the newer half.
+fn trend(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+    let (older, newer) = values.split_at(values.len() / 2);
+    Some(mean(newer) - mean(older))
+}
+
+fn format_trend(trend: Option<f64>) -> String {
+    trend.map_or("-".to_string(), |trend| format!("{trend:+.0}%"))
+}
+
+#[derive(Tabled)]
+struct SessionRow {
+    date: String,
+    mode: String,
+    wpm: u32,
+    accuracy: String,
+    fecr: String,
+    minutes: String,
+    band: String,
+}
+
+#[derive(Tabled)]
+struct CharacterRow {
+    character: String,
+    copies: u32,
+    accuracy: String,
+    avg_reaction_time: String,
+    trend: String,
+}
+
+/// Prints the last sessions, the trend of their accuracy, and how each
+/// character has been copied over them.
+pub fn print_trends(sessions: &[Session], last: usize) {
+    let recent = &sessions[sessions.len().saturating_sub(last)..];
+    if recent.is_empty() {
+        println!("No sessions have been recorded yet.");
+        return;
+    }
+    let rows: Vec<SessionRow> = recent
+        .iter()
+        .map(|session| SessionRow {
+            date: session.date.clone(),
+            mode: match session.incomplete {
+                true => format!("{} (incomplete)", session.mode),
+                false => session.mode.clone(),
+            },
+            wpm: session.wpm,
+            accuracy: format!("{:.0}%", session.accuracy),
+            fecr: session
+                .fecr
+                .map_or("-".to_string(), |fecr| format!("{fecr:.0}%")),
+            minutes: format!("{:.1}", session.duration.as_secs_f64() / 60.0),
+            band: session
+                .band
+                .map_or("clean".to_string(), |band| band.to_string()),
+        })
+        .collect();
+    println!("The last {} sessions:\n", recent.len());
+    println!("{}", Table::new(&rows).with(Style::rounded()));
+
+    let accuracies: Vec<f64> = recent.iter().map(|session| session.accuracy).collect();
+    println!(
+        "\nOverall accuracy: {:.0}% (trend {})",
+        accuracies.iter().sum::<f64>() / accuracies.len() as f64,
+        format_trend(trend(&accuracies))
+    );
+    let sustained: Vec<f64> = recent
+        .iter()
+        .filter_map(|session| session.sustained_wpm)
+        .map(f64::from)
+        .collect();
+    if let Some(best) = sustained.iter().copied().reduce(f64::max) {
+        println!(
+            "Best sustained speed of the speed ladders: {best:.0} WPM (trend {})",
+            trend(&sustained).map_or("-".to_string(), |trend| format!("{trend:+.0} WPM"))
+        );
+    }
+
+    let mut characters: BTreeMap<&str, Vec<&CharacterStats>> = BTreeMap::new();
+    for stats in recent.iter().flat_map(|session| &session.characters) {
+        characters.entry(&stats.character).or_default().push(stats);
+    }
+    if characters.is_empty() {
+        return;
+    }
+    let mut rows: Vec<(f64, CharacterRow)> = characters
+        .into_iter()
+        .map(|(character, stats)| {
+            let total = CharacterStats::combine(character, stats.iter().copied());
+            let accuracy = total.accuracy();
+            let accuracies: Vec<f64> = stats.iter().map(|stats| stats.accuracy()).collect();
+            let row = CharacterRow {
+                character: character.to_string(),
+                copies: total.count,
+                accuracy: format!("{accuracy:.0}%"),
+                avg_reaction_time: format!("{}ms", total.reaction.as_millis()),
+                trend: format_trend(trend(&accuracies)),
+            };
+            (accuracy, row)
+        })
+        .collect();
+    // The weakest characters first:
+    rows.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let rows: Vec<CharacterRow> = rows.into_iter().map(|(_, row)| row).collect();
+    println!("\nCharacters, the weakest first:\n");
+    println!("{}", Table::new(&rows).with(Style::rounded()));
+}
+
+/// How many sessions a sparkline needs to show a trend.
+const TREND_POINTS: usize = 3;
+
+/// The order of the characters of `print_sparklines`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrendOrder {
+    /// The least accurate first.
+    Worst,
+    Alphabetical,
+    /// Those whose accuracy went up the most first.
+    Improved,
+}
+
+impl FromStr for TrendOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "worst" => Ok(TrendOrder::Worst),
+            "alphabetical" => Ok(TrendOrder::Alphabetical),
+            "improved" => Ok(TrendOrder::Improved),
+            _ => Err(format!("Unknown order: {s}")),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct SparklineRow {
+    character: String,
+    sessions: usize,
+    accuracy: String,
+    avg_reaction_time: String,
+    trend: String,
+}
+
+/// The sparklines of the accuracy and the mean reaction time of some
+/// sessions, the oldest first. Those without reaction times (e.g. of
+/// words) only count for the accuracy.
+fn sparkline_row(name: &str, points: &[(f64, Option<Duration>)], ascii: bool) -> SparklineRow {
+    let accuracies: Vec<f64> = points.iter().map(|&(accuracy, _)| accuracy).collect();
+    let reactions: Vec<f64> = points
+        .iter()
+        .filter_map(|&(_, reaction)| reaction)
+        .map(|reaction| reaction.as_millis() as f64)
+        .collect();
+    let line = |values: &[f64], unit: &str| match values.last() {
+        Some(latest) if values.len() >= TREND_POINTS => {
+            format!("{} {latest:.0}{unit}", sparkline(values, ascii))
+        }
+        _ => "insufficient data".to_string(),
+    };
+    SparklineRow {
+        character: name.to_string(),
+        sessions: points.len(),
+        accuracy: line(&accuracies, "%"),
+        avg_reaction_time: line(&reactions, "ms"),
+        trend: match accuracies.len() >= TREND_POINTS {
+            true => format_trend(trend(&accuracies)),
+            false => "-".to_string(),
+        },
+    }
+}
+
+/// Prints sparklines of the accuracy and the mean reaction time over the
+/// last sessions, overall and for each character.
+///
+/// # Arguments
+/// * `ascii` - Whether to draw them in plain ASCII, for the terminals
+///   without block characters.
+pub fn print_sparklines(sessions: &[Session], last: usize, order: TrendOrder, ascii: bool) {
+    let recent = &sessions[sessions.len().saturating_sub(last)..];
+    if recent.is_empty() {
+        println!("No sessions have been recorded yet.");
+        return;
+    }
+    let overall: Vec<(f64, Option<Duration>)> = recent
+        .iter()
+        .map(|session| {
+            let reaction = match (&session.reaction, session.characters.is_empty()) {
+                (Some(reaction), _) => Some(reaction.mean),
+                (None, false) => Some(CharacterStats::combine("", &session.characters).reaction),
+                (None, true) => None,
+            };
+            (session.accuracy, reaction)
+        })
+        .collect();
+    let mut characters: BTreeMap<&str, Vec<&CharacterStats>> = BTreeMap::new();
+    for stats in recent.iter().flat_map(|session| &session.characters) {
+        characters.entry(&stats.character).or_default().push(stats);
+    }
+    let mut rows: Vec<(f64, Option<f64>, SparklineRow)> = characters
+        .into_iter()
+        .map(|(character, stats)| {
+            let points: Vec<(f64, Option<Duration>)> = stats
+                .iter()
+                .map(|stats| (stats.accuracy(), Some(stats.reaction)))
+                .collect();
+            let accuracies: Vec<f64> = points.iter().map(|&(accuracy, _)| accuracy).collect();
+            let improvement = trend(&accuracies).filter(|_| points.len() >= TREND_POINTS);
+            (
+                CharacterStats::combine(character, stats.iter().copied()).accuracy(),
+                improvement,
+                sparkline_row(character, &points, ascii),
+            )
+        })
+        .collect();
+    match order {
+        TrendOrder::Worst => rows.sort_by(|a, b| a.0.total_cmp(&b.0)),
+        TrendOrder::Alphabetical => {}
+        // Those with too few sessions to tell last:
+        TrendOrder::Improved => rows.sort_by(|a, b| {
+            b.1.unwrap_or(f64::NEG_INFINITY)
+                .total_cmp(&a.1.unwrap_or(f64::NEG_INFINITY))
+        }),
+    }
+    let rows: Vec<SparklineRow> = std::iter::once(sparkline_row("all", &overall, ascii))
+        .chain(rows.into_iter().map(|(.., row)| row))
+        .collect();
+    println!("The trends of the last {} sessions:\n", recent.len());
+    println!("{}", Table::new(&rows).with(Style::rounded()));
+}
+
+#[derive(Tabled)]
+struct HistoryRow {
+    date: String,
+    mode: String,
+    wpm: u32,
+    copies: u32,
+    accuracy: String,
+    avg_reaction_time: String,
+}
+
+/// Prints how one character was copied in each session it was sent in.
+pub fn print_character(sessions: &[Session], character: &str, last: Option<usize>) {
+    let history: Vec<(&Session, &CharacterStats)> = sessions
+        .iter()
+        .filter_map(|session| {
+            let stats = session
+                .characters
+                .iter()
+                .find(|stats| stats.character.eq_ignore_ascii_case(character))?;
+            Some((session, stats))
+        })
+        .collect();
+    let history = &history[history.len().saturating_sub(last.unwrap_or(usize::MAX))..];
+    if history.is_empty() {
+        println!("{character} has not been practiced yet.");
+        return;
+    }
+    let rows: Vec<HistoryRow> = history
+        .iter()
+        .map(|(session, stats)| HistoryRow {
+            date: session.date.clone(),
+            mode: session.mode.clone(),
+            wpm: session.wpm,
+            copies: stats.count,
+            accuracy: format!("{:.0}%", stats.accuracy()),
+            avg_reaction_time: format!("{}ms", stats.reaction.as_millis()),
+        })
+        .collect();
+    println!("{}", Table::new(&rows).with(Style::rounded()));
+    let accuracies: Vec<f64> = history.iter().map(|(_, stats)| stats.accuracy()).collect();
+    let total = CharacterStats::combine(
+        &history[0].1.character,
+        history.iter().map(|(_, stats)| *stats),
+    );
+    println!(
+        "\n{}: {:.0}% over {} sessions (trend {})",
+        total.character,
+        total.accuracy(),
+        history.len(),
+        format_trend(trend(&accuracies))
+    );
+}
+
+/// Exports the trials of the last sessions, or of all of them.
+///
+/// # Returns
+/// How many trials were exported.
+pub fn export_trials(
+    sessions: &[Session],
+    last: Option<usize>,
+    export: &Export,
+) -> io::Result<usize> {
+    let recent = &sessions[sessions.len().saturating_sub(last.unwrap_or(usize::MAX))..];
+    let trials: Vec<(&str, &Trial)> = recent
+        .iter()
+        .flat_map(|session| {
+            session
+                .trials
+                .iter()
+                .map(|trial| (session.id.as_str(), trial))
+        })
+        .collect();
+    export.save(&trials)?;
+    Ok(trials.len())
+}
+
+/// Prints the characters most often copied as others over the last
+/// sessions, or all of them.
+pub fn print_confusions(sessions: &[Session], last: Option<usize>) {
+    let recent = &sessions[sessions.len().saturating_sub(last.unwrap_or(usize::MAX))..];
+    let merged = merge(
+        recent
+            .iter()
+            .flat_map(|session| session.confusions.iter().cloned()),
+    );
+    crate::confusion::print_confusions(&merged, |character| {
+        recent
+            .iter()
+            .flat_map(|session| &session.characters)
+            .filter(|stats| stats.character == character)
+            .map(|stats| stats.count)
+            .sum()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        let ms = Duration::from_millis;
+        let distribution = Distribution {
+            mean: ms(420),
+            median: ms(400),
+            p90: ms(600),
+            p95: ms(650),
+            std_dev: ms(120),
+            outliers: 1,
+        };
+        Session {
+            id: "20241102T183012".to_string(),
+            date: "2024-11-02 18:30".to_string(),
+            mode: "koch".to_string(),
+            pool: "KM\"".to_string(),
+            wpm: 20,
+            accuracy: 87.5,
+            fecr: Some(62.5),
+            duration: Duration::from_secs(300),
+            reaction: Some(distribution),
+            characters: vec![CharacterStats {
+                character: "K".to_string(),
+                count: 8,
+                correct: 7,
+                reaction: ms(420),
+                distribution: Some(distribution),
+            }],
+            confusions: vec![Confusion {
+                sent: "K".to_string(),
+                typed: "J".to_string(),
+                count: 1,
+            }],
+            trials: vec![Trial {
+                timestamp: "2024-11-02T18:29:50.125+01:00".to_string(),
+                sent: "K".to_string(),
+                typed: Some("J".to_string()),
+                correct: false,
+                reaction: Some(ms(380)),
+                wpm: 20,
+                attempt: 2,
+                total: Some(ms(1130)),
+                tone: Some(600.0),
+            }],
+            incomplete: false,
+            band: None,
+            seed: None,
+            sustained_wpm: None,
+        }
+    }
+
+    #[test]
+    fn test_session_json() {
+        let session = session();
+        let line = session.to_json();
+        assert_eq!(
+            line,
+            r#"{"id":"20241102T183012","date":"2024-11-02 18:30","mode":"koch","pool":"KM\"","wpm":20,"accuracy":87.5,"fecr":62.5,"duration_s":300,"reaction":{"mean_ms":420,"median_ms":400,"p90_ms":600,"p95_ms":650,"stddev_ms":120,"outliers":1},"characters":[{"char":"K","count":8,"correct":7,"reaction_ms":420,"distribution":{"mean_ms":420,"median_ms":400,"p90_ms":600,"p95_ms":650,"stddev_ms":120,"outliers":1}}],"confusions":[{"sent":"K","typed":"J","count":1}],"trials":[{"timestamp":"2024-11-02T18:29:50.125+01:00","sent":"K","typed":"J","correct":false,"reaction_ms":380,"wpm":20,"attempt":2,"total_ms":1130,"tone_hz":600}],"incomplete":false,"band":null,"seed":null,"sustained_wpm":null}"#
+        );
+        assert_eq!(Session::from_json(&line), Some(session.clone()));
+        let before_distributions = Session {
+            reaction: None,
+            characters: vec![CharacterStats {
+                distribution: None,
+                ..session.characters[0].clone()
+            }],
+            ..session.clone()
+        };
+        assert_eq!(
+            Session::from_json(
+                &before_distributions
+                    .to_json()
+                    .replace(r#","reaction":null"#, "")
+                    .replace(r#","distribution":null"#, "")
+            ),
+            Some(before_distributions)
+        );
+        let qso = Session {
+            fecr: None,
+            characters: Vec::new(),
+            confusions: Vec::new(),
+            trials: Vec::new(),
+            incomplete: true,
+            ..session
+        };
+        assert_eq!(Session::from_json(&qso.to_json()), Some(qso.clone()));
+        let noisy = Session {
+            band: Conditions::new(Some(-6.0), Some(2.0), None, Some(42)),
+            seed: Some(42),
+            sustained_wpm: Some(24),
+            ..qso.clone()
+        };
+        assert!(noisy.to_json().ends_with(
+            r#""band":{"snr_db":-6,"qrn":2,"qsb_period_s":null,"qsb_depth":null,"seed":"42"},"seed":"42","sustained_wpm":24}"#
+        ));
+        assert_eq!(Session::from_json(&noisy.to_json()), Some(noisy.clone()));
+        let seeded = Session {
+            band: Conditions::new(Some(-6.0), None, None, Some(u64::MAX)),
+            seed: Some(u64::MAX),
+            ..noisy.clone()
+        };
+        assert_eq!(Session::from_json(&seeded.to_json()), Some(seeded));
+        // The seeds were numbers before they were strings:
+        let before_strings = noisy.to_json().replace(r#""42""#, "42");
+        assert_eq!(Session::from_json(&before_strings), Some(noisy));
+        let before_confusions = qso
+            .to_json()
+            .replace(r#","confusions":[]"#, "")
+            .replace(r#","trials":[]"#, "")
+            .replace(r#","incomplete":true"#, "")
+            .replace(r#","band":null"#, "")
+            .replace(r#","seed":null"#, "")
+            .replace(r#","sustained_wpm":null"#, "");
+        assert_eq!(
+            Session::from_json(&before_confusions),
+            Some(Session {
+                incomplete: false,
+                ..qso.clone()
+            })
+        );
+        let before_ids = qso.to_json().replace(r#""id":"20241102T183012","#, "");
+        assert_eq!(
+            Session::from_json(&before_ids).map(|session| session.id),
+            Some(qso.date)
+        );
+        assert_eq!(Session::from_json(&line[..line.len() / 2]), None);
+        assert_eq!(Session::from_json("[1, 2]"), None);
+    }
+
+    #[test]
+    fn test_read_sessions() {
+        let path = std::env::temp_dir().join(format!("sessions-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read_sessions(&path).unwrap(), (Vec::new(), 0));
+        append_session(&path, &session()).unwrap();
+        // A record cut short, e.g. by a crash:
+        let half = session().to_json();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&half.as_bytes()[..20]).unwrap();
+        append_session(&path, &session()).unwrap();
+        assert_eq!(
+            read_sessions(&path).unwrap(),
+            (vec![session(), session()], 1)
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_character_stats() {
+        let ms = Duration::from_millis;
+        // The second K was copied, but not in time:
+        let results = QuizResult {
+            prompts: ["K", "K", "K"].map(String::from).into(),
+            responses: vec![Some(true), Some(true), Some(false)],
+            reaction_times: vec![Some(ms(300)), None, Some(ms(500))],
+            typed_ahead: vec![false; 3],
+            typed: vec![Some("K".to_string()), Some("K".to_string()), None],
+            sent_at: vec![chrono::Local::now(); 3],
+        };
+        let stats = character_stats(&results, None);
+        assert_eq!((stats[0].count, stats[0].correct), (3, 2));
+        assert_eq!(stats[0].reaction, ms(400));
+        assert_eq!(stats[0].distribution.unwrap().median, ms(400));
+    }
+
+    #[test]
+    fn test_baseline() {
+        let stats = |count, correct, ms| CharacterStats {
+            character: "K".to_string(),
+            count,
+            correct,
+            reaction: Duration::from_millis(ms),
+            distribution: None,
+        };
+        let mut sessions = vec![session(); 12];
+        for (i, session) in sessions.iter_mut().enumerate() {
+            session.characters = vec![stats(4, i as u32 % 5, 100 * i as u64)];
+        }
+        sessions[11].characters.clear();
+        // The latest 10 sessions sending K, i.e. 1 to 10:
+        assert_eq!(baseline(&sessions, "K"), Some(stats(40, 20, 550)));
+        assert_eq!(baseline(&sessions, "M"), None);
+        assert_eq!(stats(8, 6, 0).accuracy(), 75.0);
+    }
+
+    #[test]
+    fn test_trend() {
+        assert_eq!(trend(&[80.0]), None);
+        assert_eq!(trend(&[80.0, 90.0]), Some(10.0));
+        assert_eq!(trend(&[70.0, 80.0, 90.0, 100.0]), Some(20.0));
+    }
+}
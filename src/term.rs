@@ -1,11 +1,38 @@
 use crate::message::Message;
 
 use crossterm::{
-    cursor::MoveTo,
+    cursor::{MoveTo, RestorePosition, SavePosition},
     execute,
-    terminal::{Clear, ClearType},
+    style::Print,
+    terminal::{disable_raw_mode, Clear, ClearType},
 };
 use std::io::stdout;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ACCESSIBLE: AtomicBool = AtomicBool::new(false);
+
+/// Turns on --accessible, for the whole program.
+pub fn set_accessible(accessible: bool) {
+    ACCESSIBLE.store(accessible, Ordering::Relaxed);
+}
+
+/// Whether everything is to be printed as plain lines of text for a
+/// screen reader, with --accessible: without moving the cursor, clearing
+/// the screen, or telling anything only by its color.
+pub fn accessible() -> bool {
+    ACCESSIBLE.load(Ordering::Relaxed)
+}
+
+/// Leaves raw mode before a panic is reported, so that the terminal is
+/// usable again after it, and the report readable.
+pub fn restore_on_panic() {
+    let report = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(stdout(), crossterm::cursor::Show, Print("\r\n"));
+        report(info);
+    }));
+}
 
 #[allow(dead_code)]
 pub fn clear_screen() {
@@ -13,11 +40,11 @@ pub fn clear_screen() {
     execute!(stdout, Clear(ClearType::All), MoveTo(0, 0)).unwrap();
 }
 
+/// The lines a logged message is printed as, on a terminal of some
+/// width: wrapped to 2/3 of it, with the speed and the timestamp on the
+/// right of the first line, and an empty line after it.
 #[allow(dead_code)]
-pub fn log_message(message: &Message) {
-    // Get the terminal dimensions
-    let terminal_width = term_size::dimensions().map_or(80, |(w, _)| w);
-
+pub fn message_lines(message: &Message, terminal_width: usize) -> Vec<String> {
     // Calculate the wrapping width (2/3 of terminal width)
     let wrap_width = (terminal_width as f32 * 2.0 / 3.0) as usize;
 
@@ -26,7 +53,7 @@ pub fn log_message(message: &Message) {
     let mut current_line = String::new();
 
     for word in message.content.split_whitespace() {
-        if current_line.len() + word.len() + 1 > wrap_width {
+        if current_line.chars().count() + word.chars().count() + 1 > wrap_width {
             wrapped_lines.push(current_line);
             current_line = String::new();
         }
@@ -40,18 +67,198 @@ pub fn log_message(message: &Message) {
         wrapped_lines.push(current_line);
     }
 
-    // Print the first line with the timestamp aligned to the right
-    if let Some(first_line) = wrapped_lines.first() {
-        let padding = terminal_width.saturating_sub(first_line.len() + message.timestamp.len());
-        let spaces = " ".repeat(padding);
-        println!("{}{}{}", first_line, spaces, message.timestamp);
+    // The speed and timestamp are aligned to the right of the first line
+    if let Some(first_line) = wrapped_lines.first_mut() {
+        let stamp = match message.wpm {
+            Some(wpm) => format!("{wpm} WPM  {}", message.timestamp),
+            None => message.timestamp.clone(),
+        };
+        let padding = terminal_width.saturating_sub(first_line.chars().count() + stamp.len());
+        first_line.push_str(&" ".repeat(padding));
+        first_line.push_str(&stamp);
+    }
+
+    // An empty line at the end
+    wrapped_lines.push(String::new());
+    wrapped_lines
+}
+
+/// The lines of the latest messages of a log that fit in some rows of
+/// the terminal, the older ones being left out whole.
+#[allow(dead_code)]
+pub fn latest_lines(log: &[Message], terminal_width: usize, rows: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for message in log.iter().rev() {
+        let message_lines = message_lines(message, terminal_width);
+        let used: usize = message_lines
+            .iter()
+            .map(|line| rows_of(line, terminal_width))
+            .sum();
+        if lines.len() + used > rows {
+            break;
+        }
+        lines.splice(0..0, message_lines);
+    }
+    lines
+}
+
+/// How many rows of the terminal a line of text takes, once it wraps.
+#[allow(dead_code)]
+pub fn rows_of(text: &str, terminal_width: usize) -> usize {
+    text.chars().count().div_ceil(terminal_width.max(1)).max(1)
+}
+
+/// Shows a line of status on the first row of the terminal, without
+/// moving the cursor away from the message being printed.
+#[allow(dead_code)]
+pub fn print_status(status: &str) {
+    let mut stdout = stdout();
+    execute!(
+        stdout,
+        SavePosition,
+        MoveTo(0, 0),
+        Clear(ClearType::CurrentLine),
+        Print(status),
+        RestorePosition
+    )
+    .unwrap();
+}
+
+/// Tracks how much of the message in progress has already been
+/// printed, so that only newly decoded characters are written out.
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct MessagePrinter {
+    printed: String,
+}
+
+/// What needs to be written to bring the screen up to date.
+#[allow(dead_code)]
+#[derive(Debug, PartialEq)]
+pub enum PrintUpdate<'a> {
+    /// Nothing new since the last update.
+    Unchanged,
+    /// The message grew, only this suffix needs to be printed.
+    Append(&'a str),
+    /// The message changed in some other way, redraw it entirely.
+    Redraw(&'a str),
+}
+
+#[allow(dead_code)]
+impl MessagePrinter {
+    /// Compares the current message to what was already printed.
+    pub fn update<'a>(&mut self, message: &'a str) -> PrintUpdate<'a> {
+        // strip_prefix only ever splits on a char boundary:
+        let update = match message.strip_prefix(self.printed.as_str()) {
+            Some("") => PrintUpdate::Unchanged,
+            Some(new_text) => PrintUpdate::Append(new_text),
+            None => PrintUpdate::Redraw(message),
+        };
+        self.printed.clear();
+        self.printed.push_str(message);
+        update
+    }
+
+    /// The message as it was last printed.
+    pub fn printed(&self) -> &str {
+        &self.printed
     }
 
-    // Print the rest of the wrapped lines
-    for line in &wrapped_lines[1..] {
-        println!("{}", line);
+    /// Forget the printed message, e.g. once it has been logged.
+    pub fn reset(&mut self) {
+        self.printed.clear();
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use morse_codec::decoder::Decoder;
 
-    // Print an empty line at the end
-    println!();
+    #[test]
+    fn test_message_printer_emits_each_character_once() {
+        let dot = 60;
+        // "CQ" followed by a word gap, as (duration, is_high) signal events:
+        let events = [
+            (dot * 3, true),
+            (dot, false),
+            (dot, true),
+            (dot, false),
+            (dot * 3, true),
+            (dot, false),
+            (dot, true),
+            (dot * 3, false),
+            (dot * 3, true),
+            (dot, false),
+            (dot * 3, true),
+            (dot, false),
+            (dot, true),
+            (dot, false),
+            (dot * 3, true),
+            (dot * 7, false),
+        ];
+        let mut decoder = Decoder::<9999>::new().with_reference_short_ms(dot).build();
+        let mut printer = MessagePrinter::default();
+        let mut emitted = Vec::new();
+        for (duration, is_high) in events {
+            decoder.signal_event(duration, is_high);
+            match printer.update(decoder.message.as_str()) {
+                PrintUpdate::Unchanged => {}
+                PrintUpdate::Append(new_text) => emitted.push(new_text.to_string()),
+                PrintUpdate::Redraw(text) => panic!("Unexpected redraw: {text:?}"),
+            }
+        }
+        assert_eq!(emitted, vec!["C", "Q"]);
+    }
+
+    fn message(content: &str) -> Message {
+        Message {
+            timestamp: "24-11-02 18:30:00 PM".to_string(),
+            content: content.to_string(),
+            wpm: Some(18),
+        }
+    }
+
+    #[test]
+    fn test_message_lines() {
+        let mut long = message("CQ CQ DE VERYLONGCALLSIGN/PORTABLE/MOBILE K");
+        long.wpm = None;
+        let lines = message_lines(&long, 60);
+        assert_eq!(
+            lines,
+            vec![
+                format!("CQ CQ DE{:32}24-11-02 18:30:00 PM", ""),
+                "VERYLONGCALLSIGN/PORTABLE/MOBILE K".to_string(),
+                String::new(),
+            ]
+        );
+        assert!(lines.iter().all(|line| rows_of(line, 60) == 1));
+        assert_eq!(rows_of(&"x".repeat(61), 60), 2);
+    }
+
+    #[test]
+    fn test_latest_lines() {
+        let log = [message("CQ"), message("QRZ"), message("TEST")];
+        // Only the latest messages that fit whole:
+        let lines = latest_lines(&log, 80, 5);
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("QRZ"));
+        assert!(lines[2].starts_with("TEST"));
+        assert_eq!(latest_lines(&log, 80, 6).len(), 6);
+        assert!(latest_lines(&log, 80, 1).is_empty());
+        // A narrower terminal wraps the stamp onto more rows:
+        assert_eq!(latest_lines(&log, 20, 6).len(), 4);
+    }
+
+    #[test]
+    fn test_message_printer_multibyte() {
+        let mut printer = MessagePrinter::default();
+        assert_eq!(printer.update("Ж"), PrintUpdate::Append("Ж"));
+        assert_eq!(printer.update("Ж"), PrintUpdate::Unchanged);
+        assert_eq!(printer.update("ЖЯ"), PrintUpdate::Append("Я"));
+        assert_eq!(printer.update("ЯЖ"), PrintUpdate::Redraw("ЯЖ"));
+        assert_eq!(printer.printed(), "ЯЖ");
+        printer.reset();
+        assert_eq!(printer.update("CQ"), PrintUpdate::Append("CQ"));
+    }
 }
@@ -0,0 +1,293 @@
+/// How quickly the noise floor follows a rising level during silence,
+/// as a time constant in seconds. This is slow so that the start of a
+/// weak tone is not mistaken for noise.
+const NOISE_RISE_SECS: f32 = 2.0;
+
+/// How quickly the noise floor follows a falling level, in seconds.
+const NOISE_FALL_SECS: f32 = 0.05;
+
+/// How quickly the peak follows the level during a tone, in seconds.
+const PEAK_TRACK_SECS: f32 = 0.5;
+
+/// How quickly the peak falls back toward the noise floor when no tone
+/// is heard, in seconds. This lets the peak survive fading and the gaps
+/// between messages, but recover after the volume is turned down.
+const PEAK_DECAY_SECS: f32 = 1.5;
+
+/// The on and off thresholds, as a fraction of the way from the noise
+/// floor to the peak. Having two thresholds keeps a level sitting right
+/// at the threshold from chattering on and off.
+const ON_FRACTION: f32 = 0.6;
+const OFF_FRACTION: f32 = 0.4;
+
+/// A fixed threshold turns the tone off again below this fraction of
+/// the threshold.
+const FIXED_OFF_FRACTION: f32 = 0.9;
+
+/// The peak must be at least this many times the noise floor before
+/// anything is considered a tone.
+const MIN_CONTRAST: f32 = 3.0;
+
+/// The threshold is reported again once it moves by this fraction.
+const REPORT_CHANGE: f32 = 0.25;
+
+/// Decides whether a detector level is a tone or silence.
+pub enum ToneThreshold {
+    /// A tone starts above a fixed threshold, and ends a little below it.
+    Fixed { on: f32, off: f32, tone: bool },
+    /// The threshold is placed between the measured noise floor and peak.
+    Adaptive(AdaptiveThreshold),
+}
+
+impl ToneThreshold {
+    /// Creates a fixed threshold, or an adaptive one if `threshold` is None.
+    pub fn new(threshold: Option<f32>) -> Self {
+        match threshold {
+            Some(threshold) => ToneThreshold::Fixed {
+                on: threshold,
+                off: threshold * FIXED_OFF_FRACTION,
+                tone: false,
+            },
+            None => ToneThreshold::Adaptive(AdaptiveThreshold::default()),
+        }
+    }
+
+    /// Decides whether a tone is present.
+    ///
+    /// # Arguments
+    /// * `level` - The detector level of the latest block of samples.
+    /// * `seconds` - The duration of the block of samples.
+    pub fn detect(&mut self, level: f32, seconds: f32) -> bool {
+        match self {
+            ToneThreshold::Fixed { on, off, tone } => {
+                *tone = level > if *tone { *off } else { *on };
+                *tone
+            }
+            ToneThreshold::Adaptive(adaptive) => adaptive.detect(level, seconds),
+        }
+    }
+
+    /// Returns the adaptive threshold whenever it has changed enough to
+    /// be worth reporting. A fixed threshold never changes.
+    pub fn changed(&mut self) -> Option<f32> {
+        match self {
+            ToneThreshold::Fixed { .. } => None,
+            ToneThreshold::Adaptive(adaptive) => adaptive.changed(),
+        }
+    }
+
+    /// The threshold that the next level is compared with: the on
+    /// threshold during silence, the off one during a tone.
+    pub fn current(&self) -> Option<f32> {
+        match self {
+            ToneThreshold::Fixed { on, off, tone } => Some(if *tone { *off } else { *on }),
+            ToneThreshold::Adaptive(adaptive) => {
+                adaptive
+                    .on_off()
+                    .map(|(on, off)| if adaptive.tone { off } else { on })
+            }
+        }
+    }
+
+    /// The threshold as it would be given to `--threshold`: the fixed
+    /// one, or the middle of the adaptive ones once they are known.
+    pub fn value(&self) -> Option<f32> {
+        match self {
+            ToneThreshold::Fixed { on, .. } => Some(*on),
+            ToneThreshold::Adaptive(adaptive) => adaptive.threshold(),
+        }
+    }
+
+    /// The measured peak level of the tone, for an adaptive threshold.
+    pub fn peak(&self) -> Option<f32> {
+        match self {
+            ToneThreshold::Fixed { .. } => None,
+            ToneThreshold::Adaptive(adaptive) => Some(adaptive.peak),
+        }
+    }
+
+    /// The measured noise floor, for an adaptive threshold.
+    pub fn noise(&self) -> Option<f32> {
+        match self {
+            ToneThreshold::Fixed { .. } => None,
+            ToneThreshold::Adaptive(adaptive) => adaptive.noise,
+        }
+    }
+}
+
+/// Tracks the noise floor during silence and the peak during a tone,
+/// and switches between them with hysteresis.
+#[derive(Default)]
+pub struct AdaptiveThreshold {
+    noise: Option<f32>,
+    peak: f32,
+    tone: bool,
+    reported: Option<f32>,
+}
+
+impl AdaptiveThreshold {
+    /// Decides whether a tone is present, updating the noise floor and
+    /// peak estimates with the new level.
+    pub fn detect(&mut self, level: f32, seconds: f32) -> bool {
+        let noise = self.noise.get_or_insert(level);
+        if level < *noise {
+            *noise = follow(*noise, level, NOISE_FALL_SECS, seconds);
+        } else if !self.tone {
+            *noise = follow(*noise, level, NOISE_RISE_SECS, seconds);
+        }
+        let noise = *noise;
+
+        if level > self.peak {
+            self.peak = level;
+        } else if self.tone {
+            self.peak = follow(self.peak, level, PEAK_TRACK_SECS, seconds);
+        } else {
+            self.peak = follow(self.peak, noise, PEAK_DECAY_SECS, seconds);
+        }
+
+        self.tone = match self.on_off() {
+            Some((on, _)) if !self.tone => level > on,
+            Some((_, off)) => level > off,
+            None => false,
+        };
+        self.tone
+    }
+
+    /// The threshold halfway between the on and off thresholds, if the
+    /// peak stands out far enough from the noise floor.
+    pub fn threshold(&self) -> Option<f32> {
+        self.on_off().map(|(on, off)| (on + off) / 2.0)
+    }
+
+    /// Returns the threshold when it has moved far enough from the
+    /// threshold that was last returned.
+    pub fn changed(&mut self) -> Option<f32> {
+        let threshold = self.threshold()?;
+        match self.reported {
+            Some(reported) if (threshold - reported).abs() <= reported * REPORT_CHANGE => None,
+            _ => {
+                self.reported = Some(threshold);
+                Some(threshold)
+            }
+        }
+    }
+
+    fn on_off(&self) -> Option<(f32, f32)> {
+        let noise = self.noise?;
+        if self.peak <= f32::EPSILON || self.peak < noise * MIN_CONTRAST {
+            return None;
+        }
+        let span = self.peak - noise;
+        Some((noise + span * ON_FRACTION, noise + span * OFF_FRACTION))
+    }
+}
+
+/// Moves `value` toward `target` as a first order low pass filter with
+/// the given time constant.
+fn follow(value: f32, target: f32, time_constant: f32, seconds: f32) -> f32 {
+    let alpha = 1.0 - (-seconds / time_constant).exp();
+    value + (target - value) * alpha
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_SECS: f32 = 0.01;
+
+    /// Feeds a keyed level to the threshold, returning the detected states.
+    fn run(
+        threshold: &mut AdaptiveThreshold,
+        pattern: &[(bool, usize)],
+        on: f32,
+        off: f32,
+    ) -> Vec<bool> {
+        let mut states = Vec::new();
+        for &(tone, blocks) in pattern {
+            let level = if tone { on } else { off };
+            for _ in 0..blocks {
+                states.push(threshold.detect(level, BLOCK_SECS));
+            }
+        }
+        states
+    }
+
+    /// Dots and dashes with the gaps between them, every 10ms block.
+    fn keying(repeat: usize) -> Vec<(bool, usize)> {
+        (0..repeat)
+            .flat_map(|_| [(true, 6), (false, 6), (true, 18), (false, 18)])
+            .collect()
+    }
+
+    fn expected(pattern: &[(bool, usize)]) -> Vec<bool> {
+        pattern
+            .iter()
+            .flat_map(|&(tone, blocks)| std::iter::repeat_n(tone, blocks))
+            .collect()
+    }
+
+    #[test]
+    fn test_adaptive_threshold_follows_volume() {
+        let mut threshold = AdaptiveThreshold::default();
+        let pattern = keying(20);
+        run(&mut threshold, &pattern, 1.0, 0.05);
+        assert_eq!(run(&mut threshold, &pattern, 1.0, 0.05), expected(&pattern));
+        let loud = threshold.threshold().unwrap();
+        assert!(0.05 < loud && loud < 1.0);
+
+        // Turn the volume down by a factor of ten, it should recover
+        // within the first five seconds:
+        let states = run(&mut threshold, &pattern, 0.1, 0.005);
+        assert_eq!(states[500..], expected(&pattern)[500..]);
+        assert!(threshold.threshold().unwrap() < loud / 5.0);
+    }
+
+    #[test]
+    fn test_adaptive_threshold_ignores_noise() {
+        let mut threshold = AdaptiveThreshold::default();
+        let states = run(&mut threshold, &[(false, 500)], 0.0, 0.2);
+        assert!(states.iter().all(|&tone| !tone));
+        assert_eq!(threshold.changed(), None);
+    }
+
+    #[test]
+    fn test_fixed_threshold_hysteresis() {
+        let mut threshold = ToneThreshold::new(Some(0.5));
+        let states: Vec<bool> = [0.47, 0.55, 0.47, 0.4, 0.47]
+            .iter()
+            .map(|&level| threshold.detect(level, BLOCK_SECS))
+            .collect();
+        assert_eq!(states, vec![false, true, true, false, false]);
+    }
+
+    #[test]
+    fn test_current_threshold() {
+        let mut threshold = ToneThreshold::new(Some(0.5));
+        assert_eq!((threshold.current(), threshold.peak()), (Some(0.5), None));
+        assert_eq!(threshold.noise(), None);
+        threshold.detect(0.6, BLOCK_SECS);
+        assert_eq!(threshold.current(), Some(0.45));
+        assert_eq!(threshold.value(), Some(0.5));
+
+        // An adaptive threshold is only known once a tone stands out:
+        let mut threshold = ToneThreshold::new(None);
+        threshold.detect(0.0, BLOCK_SECS);
+        assert_eq!((threshold.current(), threshold.noise()), (None, Some(0.0)));
+        threshold.detect(1.0, BLOCK_SECS);
+        assert_eq!(threshold.peak(), Some(1.0));
+        let off = threshold.current().unwrap();
+        threshold.detect(0.0, BLOCK_SECS);
+        assert!(threshold.current().unwrap() > off);
+    }
+
+    #[test]
+    fn test_adaptive_threshold_hysteresis() {
+        let mut threshold = AdaptiveThreshold::default();
+        run(&mut threshold, &keying(10), 1.0, 0.0);
+        assert!(threshold.detect(1.0, BLOCK_SECS));
+        // Just under the middle stays on, and just over it stays off:
+        assert!(threshold.detect(0.45, BLOCK_SECS));
+        assert!(!threshold.detect(0.0, BLOCK_SECS));
+        assert!(!threshold.detect(0.55, BLOCK_SECS));
+    }
+}
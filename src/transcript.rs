@@ -0,0 +1,350 @@
+use crate::json::Object;
+use crate::rigctl::Tuning;
+use chrono::{DateTime, Local, NaiveDate};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// When the transcript is moved aside, to start a new file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    /// At the first message of each day.
+    Daily,
+    /// Before a message would make it bigger than this many bytes.
+    Size(u64),
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    /// `daily`, or a size like `10MB`, `500k` or `4096`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "daily" {
+            return Ok(Rotation::Daily);
+        }
+        let lower = s.to_lowercase();
+        let digits = lower.trim_end_matches('b');
+        let (number, unit) = match digits.char_indices().last() {
+            Some((i, 'k')) => (&digits[..i], 1 << 10),
+            Some((i, 'm')) => (&digits[..i], 1 << 20),
+            Some((i, 'g')) => (&digits[..i], 1 << 30),
+            _ => (digits, 1),
+        };
+        match number.trim().parse::<u64>() {
+            Ok(size) if size > 0 => Ok(Rotation::Size(size * unit)),
+            _ => Err(format!("Rotation must be 'daily' or a size like 10MB: {s}")),
+        }
+    }
+}
+
+/// One message heard while listening.
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub time: DateTime<Local>,
+    /// The label of the station, with --split-stereo.
+    pub station: Option<String>,
+    pub message: String,
+    /// The measured speed, if known.
+    pub wpm: Option<u32>,
+    /// The tone it was heard on, in Hz.
+    pub tone: f32,
+    /// What the radio was tuned to, with --rigctl.
+    pub rig: Option<Tuning>,
+}
+
+#[allow(dead_code)]
+impl Record {
+    /// The time in ISO 8601, e.g. `2024-11-02T18:30:00+01:00`.
+    fn timestamp(&self) -> String {
+        self.time.format("%Y-%m-%dT%H:%M:%S%:z").to_string()
+    }
+
+    /// Writes the record as a line of text, e.g.
+    /// `2024-11-02T18:30:00+01:00 18 WPM 700 Hz CQ CQ DE N0CALL`, with
+    /// the tuning after the tone with --rigctl, e.g. `14025.70 kHz CW`.
+    pub fn to_text(&self) -> String {
+        let wpm = self.wpm.map_or("--".to_string(), |wpm| wpm.to_string());
+        let rig = self
+            .rig
+            .as_ref()
+            .map_or(String::new(), |rig| format!("{} ", rig.to_text()));
+        let station = self
+            .station
+            .as_ref()
+            .map_or(String::new(), |label| format!("{label} "));
+        format!(
+            "{} {wpm} WPM {:.0} Hz {rig}{station}{}",
+            self.timestamp(),
+            self.tone,
+            self.message
+        )
+    }
+
+    /// Writes the record as a line of JSON.
+    pub fn to_json(&self) -> String {
+        let object = Object::new()
+            .string("timestamp", self.timestamp().as_str())
+            .string(
+                "station",
+                self.station
+                    .as_deref()
+                    .map(|label| label.trim_end_matches(':')),
+            )
+            .fixed("tone_hz", self.tone, 0)
+            .number("wpm", self.wpm)
+            .string("message", self.message.as_str());
+        match &self.rig {
+            Some(rig) => rig.json_fields(object),
+            None => object,
+        }
+        .end()
+    }
+}
+
+/// Where the transcript is written.
+#[allow(dead_code)]
+enum Output {
+    Stdout,
+    File {
+        path: PathBuf,
+        file: File,
+        /// The day of the first message in the file.
+        date: NaiveDate,
+        size: u64,
+    },
+}
+
+/// The transcript of the messages heard while listening, appended a
+/// line each, as text or as JSON when the file is named `.jsonl` or
+/// `.json`, or always as JSON when it is written to stdout.
+///
+/// Each line is flushed as it is written, so that nothing is lost if
+/// the program doesn't end well.
+#[allow(dead_code)]
+pub struct Transcript {
+    output: Output,
+    json: bool,
+    rotation: Option<Rotation>,
+}
+
+#[allow(dead_code)]
+impl Transcript {
+    /// Opens the transcript at a path, or on stdout when it is `-`.
+    pub fn open(path: &str, rotation: Option<Rotation>) -> io::Result<Self> {
+        if path == "-" {
+            return Ok(Self {
+                output: Output::Stdout,
+                json: true,
+                rotation,
+            });
+        }
+        let path = PathBuf::from(path);
+        let json = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("jsonl" | "json")
+        );
+        let (file, size, date) = append(&path)?;
+        Ok(Self {
+            output: Output::File {
+                path,
+                file,
+                date,
+                size,
+            },
+            json,
+            rotation,
+        })
+    }
+
+    /// Whether the records are written to stdout, instead of what is
+    /// usually shown there.
+    pub fn is_stdout(&self) -> bool {
+        matches!(self.output, Output::Stdout)
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        let mut line = match self.json {
+            true => record.to_json(),
+            false => record.to_text(),
+        };
+        line.push('\n');
+        match &mut self.output {
+            Output::Stdout => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(line.as_bytes())?;
+                stdout.flush()
+            }
+            Output::File {
+                path,
+                file,
+                date,
+                size,
+            } => {
+                let rotate = match self.rotation {
+                    _ if *size == 0 => false,
+                    Some(Rotation::Daily) => record.time.date_naive() != *date,
+                    Some(Rotation::Size(limit)) => *size + line.len() as u64 > limit,
+                    None => false,
+                };
+                if rotate {
+                    std::fs::rename(&*path, rotated_path(path, *date))?;
+                    (*file, *size, _) = append(path)?;
+                }
+                if *size == 0 {
+                    *date = record.time.date_naive();
+                }
+                file.write_all(line.as_bytes())?;
+                file.flush()?;
+                *size += line.len() as u64;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Opens a file to append to, with its size and the day it was last
+/// written.
+fn append(path: &Path) -> io::Result<(File, u64, NaiveDate)> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let metadata = file.metadata()?;
+    let date = match metadata.modified() {
+        Ok(modified) => DateTime::<Local>::from(modified).date_naive(),
+        Err(_) => Local::now().date_naive(),
+    };
+    Ok((file, metadata.len(), date))
+}
+
+/// Where a full transcript is moved to, named after the day of its
+/// messages, e.g. `heard.txt` to `heard-2024-11-02.txt`, then
+/// `heard-2024-11-02-2.txt`.
+#[allow(dead_code)]
+fn rotated_path(path: &Path, date: NaiveDate) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("log");
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map_or(String::new(), |e| format!(".{e}"));
+    (1..)
+        .map(|n| {
+            let suffix = match n {
+                1 => String::new(),
+                n => format!("-{n}"),
+            };
+            path.with_file_name(format!("{stem}-{date}{suffix}{extension}"))
+        })
+        .find(|rotated| !rotated.exists())
+        .expect("no free name to rotate to")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(day: u32, message: &str) -> Record {
+        Record {
+            time: Local.with_ymd_and_hms(2024, 11, day, 18, 30, 0).unwrap(),
+            station: None,
+            message: message.to_string(),
+            wpm: Some(18),
+            tone: 700.4,
+            rig: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_rotation() {
+        assert_eq!("daily".parse(), Ok(Rotation::Daily));
+        assert_eq!("10MB".parse(), Ok(Rotation::Size(10 << 20)));
+        assert_eq!("500k".parse(), Ok(Rotation::Size(500 << 10)));
+        assert_eq!("4096".parse(), Ok(Rotation::Size(4096)));
+        assert!("weekly".parse::<Rotation>().is_err());
+        assert!("0".parse::<Rotation>().is_err());
+    }
+
+    #[test]
+    fn test_record_lines() {
+        let mut record = record(2, "CQ \"DE\" N0CALL");
+        let timestamp = record.timestamp();
+        assert!(timestamp.starts_with("2024-11-02T18:30:00"), "{timestamp}");
+        assert_eq!(
+            record.to_text(),
+            format!("{timestamp} 18 WPM 700 Hz CQ \"DE\" N0CALL")
+        );
+        assert_eq!(
+            record.to_json(),
+            format!(
+                "{{\"timestamp\":\"{timestamp}\",\"station\":null,\"tone_hz\":700,\"wpm\":18,\"message\":\"CQ \\\"DE\\\" N0CALL\"}}"
+            )
+        );
+        record.station = Some("L:".to_string());
+        record.wpm = None;
+        assert_eq!(
+            record.to_text(),
+            format!("{timestamp} -- WPM 700 Hz L: CQ \"DE\" N0CALL")
+        );
+        assert!(record
+            .to_json()
+            .contains("\"station\":\"L\",\"tone_hz\":700,\"wpm\":null"));
+        record.rig = Some(Tuning {
+            freq_hz: Some(7030000),
+            mode: Some("CW".to_string()),
+        });
+        assert_eq!(
+            record.to_text(),
+            format!("{timestamp} -- WPM 700 Hz 7030.00 kHz CW L: CQ \"DE\" N0CALL")
+        );
+        assert!(record.to_json().ends_with(
+            "\"message\":\"CQ \\\"DE\\\" N0CALL\",\"freq_hz\":7030000,\"mode\":\"CW\"}"
+        ));
+        // While rigctld can't be reached:
+        record.rig = Some(Tuning::default());
+        assert!(record
+            .to_json()
+            .ends_with(",\"freq_hz\":null,\"mode\":null}"));
+    }
+
+    #[test]
+    fn test_transcript_rotation() {
+        let dir = std::env::temp_dir().join(format!("transcript-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("heard.jsonl");
+        let mut transcript =
+            Transcript::open(path.to_str().unwrap(), Some(Rotation::Daily)).unwrap();
+        transcript.write(&record(2, "CQ")).unwrap();
+        transcript.write(&record(2, "QRZ")).unwrap();
+        // The next day starts a new file:
+        transcript.write(&record(3, "TEST")).unwrap();
+        let read = |path: &Path| std::fs::read_to_string(path).unwrap();
+        let old = read(&dir.join("heard-2024-11-02.jsonl"));
+        assert_eq!(old.lines().count(), 2);
+        assert!(old
+            .lines()
+            .all(|line| crate::json::Json::parse(line).is_some()));
+        assert!(read(&path).contains("\"message\":\"TEST\""));
+
+        // By size, keeping the files already rotated:
+        let path = dir.join("heard.txt");
+        let line = record(3, "CQ").to_text().len() as u64 + 1;
+        let mut transcript =
+            Transcript::open(path.to_str().unwrap(), Some(Rotation::Size(2 * line))).unwrap();
+        for _ in 0..5 {
+            transcript.write(&record(3, "CQ")).unwrap();
+        }
+        assert_eq!(
+            read(&dir.join("heard-2024-11-03.txt")).len() as u64,
+            2 * line
+        );
+        assert_eq!(
+            read(&dir.join("heard-2024-11-03-2.txt")).len() as u64,
+            2 * line
+        );
+        assert_eq!(read(&path).len() as u64, line);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,538 @@
+use crate::alert::Alerts;
+use crate::message::{Message, Summary};
+use crate::practice::GroupCopy;
+use crate::sparkline::unicode_supported;
+use crate::term::latest_lines;
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    symbols::border,
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget},
+    Terminal, TerminalOptions, Viewport,
+};
+use std::io::{self, Stdout};
+
+/// The terminal that the panes are drawn on.
+pub type Screen = Terminal<CrosstermBackend<Stdout>>;
+
+/// The rows of a practice drill left under its panes, for the copy
+/// being typed, the answer and the tries after it.
+pub const PRACTICE_ROWS: u16 = 6;
+
+/// How many of the latest groups the accuracy pane tells apart.
+const LATEST: usize = 10;
+
+/// The whole terminal, for the panes of listen.
+#[allow(dead_code)]
+pub fn listen_screen() -> io::Result<Screen> {
+    Terminal::new(CrosstermBackend::new(io::stdout()))
+}
+
+/// The top of the terminal, for the panes of a drill, which are drawn
+/// over the copy being typed under them.
+pub fn practice_screen() -> io::Result<Screen> {
+    let viewport = Viewport::Fixed(Rect::default());
+    Terminal::with_options(
+        CrosstermBackend::new(io::stdout()),
+        TerminalOptions { viewport },
+    )
+}
+
+/// Leaves the cursor on the last row of the terminal, for what is
+/// printed after the panes, e.g. the summary of the session.
+#[allow(dead_code)]
+pub fn leave<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
+    let size = terminal.size()?;
+    terminal.set_cursor_position((0, size.height.saturating_sub(1)))?;
+    terminal.show_cursor()
+}
+
+/// A pane, under a line with its name.
+fn pane(title: &str, unicode: bool) -> Block<'_> {
+    let set = match unicode {
+        true => border::PLAIN,
+        false => border::Set {
+            horizontal_top: "-",
+            ..border::PLAIN
+        },
+    };
+    Block::new()
+        .borders(Borders::TOP)
+        .border_set(set)
+        .title(format!(" {title} "))
+}
+
+/// Some text cut into rows, as the terminal would wrap it.
+fn rows(text: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    match chars.is_empty() {
+        true => vec![String::new()],
+        false => chars
+            .chunks(width.max(1))
+            .map(|row| row.iter().collect())
+            .collect(),
+    }
+}
+
+/// A row of a message, with what the alerts of its station match in it
+/// highlighted.
+fn highlighted(text: String, alerts: Option<&Alerts>) -> Line<'static> {
+    let Some(alerts) = alerts else {
+        return Line::from(text);
+    };
+    let mut spans = Vec::new();
+    let mut done = 0;
+    for (start, end) in alerts.ranges(&text) {
+        spans.push(Span::raw(text[done..start].to_string()));
+        spans.push(Span::styled(
+            text[start..end].to_string(),
+            Style::new().yellow().bold(),
+        ));
+        done = end;
+    }
+    spans.push(Span::raw(text[done..].to_string()));
+    Line::from(spans)
+}
+
+/// What the listen screen shows of a station.
+#[derive(Default)]
+struct StationPane {
+    label: Option<String>,
+    alerts: Option<Alerts>,
+    messages: Vec<Message>,
+    partial: String,
+    // Counted from the start, the transcript being cleared or not:
+    summary: Summary,
+}
+
+impl StationPane {
+    /// The rows of the station in the transcript: the latest messages
+    /// that fit whole, wrapped to the width, and the end of the one
+    /// being received under them.
+    fn lines(&self, width: usize, height: usize) -> Vec<Line<'static>> {
+        let mut partial = match self.partial.is_empty() {
+            true => Vec::new(),
+            false => rows(&self.partial, width),
+        };
+        partial.drain(..partial.len().saturating_sub(height));
+        let room = height - partial.len();
+        latest_lines(&self.messages, width, room)
+            .iter()
+            .flat_map(|line| rows(line, width))
+            .map(|row| highlighted(row, self.alerts.as_ref()))
+            .chain(partial.into_iter().map(Line::from))
+            .collect()
+    }
+}
+
+/// Something to show while listening.
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum ListenEvent {
+    /// The message a station is receiving, as decoded so far.
+    Partial(usize, String),
+    /// A message a station received whole.
+    Message(usize, Message),
+    /// The speed of every station, the meter and the settings.
+    Status(String),
+    /// Something that happened, e.g. `locked to 600 Hz`, until the next.
+    Note(String),
+    /// Forgets the messages received, with `c`.
+    Clear,
+}
+
+/// The panes of the listen screen: the status bar, with the meter, over
+/// the transcript of the messages of each station, and the stats of the
+/// session under them.
+#[allow(dead_code)]
+pub struct ListenView {
+    stations: Vec<StationPane>,
+    status: String,
+    note: Option<String>,
+    unicode: bool,
+}
+
+#[allow(dead_code)]
+impl ListenView {
+    pub fn new(labels: Vec<Option<String>>) -> Self {
+        Self {
+            stations: labels
+                .into_iter()
+                .map(|label| StationPane {
+                    label,
+                    ..Default::default()
+                })
+                .collect(),
+            status: String::new(),
+            note: None,
+            unicode: unicode_supported(),
+        }
+    }
+
+    /// Also highlights what the alerts of each station match in its
+    /// messages.
+    pub fn with_alerts(mut self, alerts: Vec<Option<Alerts>>) -> Self {
+        for (station, alerts) in self.stations.iter_mut().zip(alerts) {
+            station.alerts = alerts;
+        }
+        self
+    }
+
+    pub fn apply(&mut self, event: ListenEvent) {
+        match event {
+            ListenEvent::Partial(index, partial) => self.stations[index].partial = partial,
+            ListenEvent::Message(index, message) => {
+                let station = &mut self.stations[index];
+                station.summary.add(&message);
+                station.partial.clear();
+                station.messages.push(message);
+            }
+            ListenEvent::Status(status) => self.status = status,
+            ListenEvent::Note(note) => self.note = Some(note),
+            ListenEvent::Clear => {
+                for station in self.stations.iter_mut() {
+                    station.messages.clear();
+                    station.partial.clear();
+                }
+            }
+        }
+    }
+}
+
+impl Widget for &ListenView {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let width = area.width as usize;
+        let mut status = rows(&self.status, width);
+        status.truncate(2);
+        let stats: Vec<Line> = self
+            .stations
+            .iter()
+            .map(|station| {
+                let label = match &station.label {
+                    Some(label) => format!("{label} "),
+                    None => String::new(),
+                };
+                Line::from(format!("{label}{}", station.summary.counts()))
+            })
+            .chain(self.note.as_deref().map(Line::from))
+            .collect();
+        let [status_area, transcript_area, stats_area] = Layout::vertical([
+            Constraint::Length(status.len() as u16),
+            Constraint::Fill(1),
+            Constraint::Length(stats.len() as u16 + 1),
+        ])
+        .areas(area);
+        Paragraph::new(status.into_iter().map(Line::from).collect::<Vec<_>>())
+            .render(status_area, buf);
+
+        let transcript = pane("transcript", self.unicode);
+        let inner = transcript.inner(transcript_area);
+        transcript.render(transcript_area, buf);
+        // The rows are shared by the stations, a row of each for its label:
+        let shared = self.stations.len() > 1;
+        let station_areas =
+            Layout::vertical(self.stations.iter().map(|_| Constraint::Fill(1))).split(inner);
+        for (station, &station_area) in self.stations.iter().zip(station_areas.iter()) {
+            let block = match (&station.label, shared) {
+                (Some(label), true) => Block::new().title(label.as_str()),
+                _ => Block::new(),
+            };
+            let inner = block.inner(station_area);
+            block.render(station_area, buf);
+            Paragraph::new(station.lines(inner.width as usize, inner.height as usize))
+                .render(inner, buf);
+        }
+
+        let pane = pane("stats", self.unicode);
+        Paragraph::new(stats).block(pane).render(stats_area, buf);
+    }
+}
+
+/// The panes over a practice drill: how accurate the copy is so far,
+/// and the prompt, under the latest groups copied.
+pub struct PracticeView {
+    copies: Vec<(String, String, bool)>,
+    // The share of each group copied, to add up:
+    accuracy: Vec<f64>,
+    wpm: Option<u32>,
+    unicode: bool,
+}
+
+impl PracticeView {
+    pub fn new() -> Self {
+        Self {
+            copies: Vec::new(),
+            accuracy: Vec::new(),
+            wpm: None,
+            unicode: unicode_supported(),
+        }
+    }
+
+    /// Adds a group once it has been copied.
+    pub fn copied(&mut self, group: &GroupCopy) {
+        self.copies
+            .push((group.sent.clone(), group.copied.clone(), group.exact));
+        self.accuracy.push(group.accuracy);
+        self.wpm = Some(group.wpm);
+    }
+
+    /// Whether there is anything to show yet: until the first group is
+    /// copied, the explanation of the drill is left on the screen.
+    pub fn is_empty(&self) -> bool {
+        self.copies.is_empty()
+    }
+
+    /// Clears the terminal and draws the panes at the top of it, leaving
+    /// `PRACTICE_ROWS` under them with the cursor, for the copy to be
+    /// typed. A terminal too small to have them is left alone.
+    pub fn draw<B: Backend>(&self, terminal: &mut Terminal<B>) -> io::Result<()> {
+        let size = terminal.size()?;
+        let height = size.height.saturating_sub(PRACTICE_ROWS);
+        if height < 4 {
+            return Ok(());
+        }
+        terminal.resize(Rect::new(0, 0, size.width, height))?;
+        terminal.draw(|frame| {
+            frame.render_widget(self, frame.area());
+            frame.set_cursor_position((0, height));
+        })?;
+        Ok(())
+    }
+}
+
+impl Widget for &PracticeView {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let percent = |shares: &[f64]| {
+            format!(
+                "{:.0}%",
+                shares.iter().sum::<f64>() / shares.len().max(1) as f64 * 100.0
+            )
+        };
+        let exact = self.copies.iter().filter(|(_, _, exact)| *exact).count();
+        let latest = &self.accuracy[self.accuracy.len().saturating_sub(LATEST)..];
+        let wpm = self.wpm.map_or("--".to_string(), |wpm| wpm.to_string());
+        let [accuracy_area, prompt_area] =
+            Layout::vertical([Constraint::Length(2), Constraint::Fill(1)]).areas(area);
+        Paragraph::new(format!(
+            "{} groups, {exact} exact  copied {}  last {}: {}  {wpm} WPM",
+            self.copies.len(),
+            percent(&self.accuracy),
+            latest.len(),
+            percent(latest)
+        ))
+        .block(pane("accuracy", self.unicode))
+        .render(accuracy_area, buf);
+
+        let prompt = pane("prompt", self.unicode);
+        let room = prompt.inner(prompt_area).height as usize;
+        let shown = &self.copies[self.copies.len().saturating_sub(room)..];
+        let sent_width = shown
+            .iter()
+            .map(|(sent, _, _)| sent.chars().count())
+            .max()
+            .unwrap_or(0);
+        let lines: Vec<Line> = shown
+            .iter()
+            .map(|(sent, copied, exact)| {
+                let mark = match (exact, self.unicode) {
+                    (true, true) => "✓".green(),
+                    (false, true) => "✗".red(),
+                    (true, false) => "+".green(),
+                    (false, false) => "-".red(),
+                };
+                Line::from(vec![mark, format!(" {sent:<sent_width$}  {copied}").into()])
+            })
+            .collect();
+        Paragraph::new(lines).block(prompt).render(prompt_area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::style::{Color, Modifier};
+
+    fn message(content: &str) -> Message {
+        Message {
+            timestamp: "24-11-02 18:30:00 PM".to_string(),
+            content: content.to_string(),
+            wpm: Some(18),
+        }
+    }
+
+    /// The rows of a view drawn on a terminal of some size.
+    fn draw(view: &ListenView, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|frame| frame.render_widget(view, frame.area()))
+            .unwrap();
+        texts(terminal.backend().buffer())
+    }
+
+    fn texts(buffer: &Buffer) -> Vec<String> {
+        (0..buffer.area.height)
+            .map(|y| {
+                let row: String = (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect();
+                row.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// A terminal of some size, drawn on from the top as a drill is.
+    fn practice_terminal(width: u16, height: u16) -> Terminal<TestBackend> {
+        let viewport = Viewport::Fixed(Rect::default());
+        Terminal::with_options(
+            TestBackend::new(width, height),
+            TerminalOptions { viewport },
+        )
+        .unwrap()
+    }
+
+    fn rule(title: &str, width: usize) -> String {
+        format!(" {title} {}", "-".repeat(width - title.len() - 2))
+    }
+
+    #[test]
+    fn test_listen_view() {
+        let mut view = ListenView::new(vec![None]);
+        view.unicode = false;
+        view.apply(ListenEvent::Status("speed: 18 WPM (dot 66ms)".to_string()));
+        view.apply(ListenEvent::Message(0, message("CQ CQ DE W1AW K")));
+        view.apply(ListenEvent::Partial(0, "W1AW DE".to_string()));
+        view.apply(ListenEvent::Note("locked to 600 Hz".to_string()));
+        let rows = draw(&view, 60, 12);
+        assert_eq!(rows[0], "speed: 18 WPM (dot 66ms)");
+        assert_eq!(rows[1], rule("transcript", 60));
+        assert!(rows[2].starts_with("CQ CQ DE W1AW K"));
+        assert_eq!(rows[4], "W1AW DE");
+        assert_eq!(
+            rows[9..],
+            [
+                rule("stats", 60),
+                "1 message, 11 characters at 18 WPM".to_string(),
+                "locked to 600 Hz".to_string()
+            ]
+        );
+
+        // The message being received comes in its place once it is whole:
+        view.apply(ListenEvent::Message(0, message("W1AW DE K1ABC")));
+        let rows = draw(&view, 60, 12);
+        assert!(rows[4].starts_with("W1AW DE K1ABC"));
+        assert_eq!(rows[10], "2 messages, 22 characters at 18 WPM");
+
+        // The counts are kept when the transcript is cleared:
+        view.apply(ListenEvent::Clear);
+        let rows = draw(&view, 60, 12);
+        assert!(rows[2..9].iter().all(String::is_empty));
+        assert_eq!(rows[10], "2 messages, 22 characters at 18 WPM");
+    }
+
+    #[test]
+    fn test_listen_view_resized() {
+        let mut view = ListenView::new(vec![None]);
+        view.apply(ListenEvent::Status("speed: -- WPM".repeat(10)));
+        for _ in 0..20 {
+            view.apply(ListenEvent::Message(0, message("CQ CQ DE W1AW W1AW K")));
+        }
+        view.apply(ListenEvent::Partial(0, "TEST ".repeat(30)));
+        for (width, height) in [(80, 24), (20, 10), (7, 3), (1, 1), (0, 0)] {
+            let rows = draw(&view, width, height);
+            assert_eq!(rows.len(), height as usize, "{width}x{height}");
+        }
+        // The end of the message being received, under the latest ones:
+        let rows = draw(&view, 20, 10);
+        assert_eq!(rows[2], rule("transcript", 20));
+        assert!(rows[7].ends_with("TEST"), "{:?}", rows[7]);
+        assert_eq!(rows[8], rule("stats", 20));
+    }
+
+    #[test]
+    fn test_listen_view_stations() {
+        let alerts = Alerts::new(&["qrz".to_string()], None).unwrap();
+        let mut view = ListenView::new(vec![Some("L:".to_string()), Some("R:".to_string())])
+            .with_alerts(vec![None, Some(alerts)]);
+        view.apply(ListenEvent::Message(1, message("QRZ")));
+        view.apply(ListenEvent::Partial(0, "CQ".to_string()));
+        let mut terminal = Terminal::new(TestBackend::new(40, 13)).unwrap();
+        terminal
+            .draw(|frame| frame.render_widget(&view, frame.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows = texts(buffer);
+        // 8 rows for the transcript, 4 for each station:
+        assert_eq!(
+            rows[2..10],
+            ["L:", "CQ", "", "", "R:", rows[7].as_str(), "", ""]
+        );
+        assert!(rows[7].starts_with("QRZ"));
+        // The alert is highlighted:
+        assert_eq!(buffer[(0, 7)].fg, Color::Yellow);
+        assert!(buffer[(0, 7)].modifier.contains(Modifier::BOLD));
+        assert_eq!(buffer[(4, 7)].fg, Color::Reset);
+        assert_eq!(rows[11], "L: no messages");
+        assert_eq!(rows[12], "R: 1 message, 3 characters at 18 WPM");
+    }
+
+    fn copy(sent: &str, copied: &str, accuracy: f64) -> GroupCopy {
+        GroupCopy {
+            sent: sent.to_string(),
+            copied: copied.to_string(),
+            exact: sent == copied,
+            time: std::time::Duration::from_secs(1),
+            wpm: 20,
+            tone: 600.0,
+            accuracy,
+            attempt: 1,
+        }
+    }
+
+    #[test]
+    fn test_practice_view() {
+        let mut view = PracticeView::new();
+        view.unicode = false;
+        assert!(view.is_empty());
+        view.copied(&copy("KMR", "KMR", 1.0));
+        view.copied(&copy("SUAN", "SUEN", 0.75));
+        let mut terminal = practice_terminal(50, PRACTICE_ROWS + 6);
+        view.draw(&mut terminal).unwrap();
+        assert_eq!(
+            texts(terminal.backend().buffer())[..6],
+            [
+                rule("accuracy", 50),
+                "2 groups, 1 exact  copied 88%  last 2: 88%  20 WPM".to_string(),
+                rule("prompt", 50),
+                "+ KMR   KMR".to_string(),
+                "- SUAN  SUEN".to_string(),
+                String::new(),
+            ]
+        );
+        // The copy is typed under the panes:
+        assert_eq!(terminal.get_cursor_position().unwrap(), (0, 6).into());
+
+        // Only the latest groups that fit, and the latest ten told apart:
+        for _ in 0..10 {
+            view.copied(&copy("KM", "KM", 1.0));
+        }
+        let mut terminal = practice_terminal(60, PRACTICE_ROWS + 5);
+        view.draw(&mut terminal).unwrap();
+        let rows = texts(terminal.backend().buffer());
+        assert_eq!(
+            rows[1],
+            "12 groups, 11 exact  copied 98%  last 10: 100%  20 WPM"
+        );
+        assert_eq!(rows[3..5], ["+ KM  KM", "+ KM  KM"]);
+
+        // Too small a terminal is left as it is:
+        let mut terminal = practice_terminal(50, PRACTICE_ROWS + 3);
+        view.draw(&mut terminal).unwrap();
+        assert!(texts(terminal.backend().buffer())
+            .iter()
+            .all(String::is_empty));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::prelude::*;
+use crate::transcript::Record;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+/// What is in each datagram of `listen --udp-target`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UdpFormat {
+    /// The record of the message, as a line of the JSON log file.
+    #[default]
+    Json,
+    /// Only the text of the message.
+    Plain,
+}
+
+impl FromStr for UdpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(UdpFormat::Json),
+            "plain" => Ok(UdpFormat::Plain),
+            _ => Err(format!("UDP format must be 'json' or 'plain': {s}")),
+        }
+    }
+}
+
+/// Sends a datagram for each message heard, e.g. to logging software
+/// on another machine. A datagram that can't be sent is only told at
+/// the debug level, as the decoding goes on whatever happens to it.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct UdpSender {
+    socket: UdpSocket,
+    target: SocketAddr,
+    format: UdpFormat,
+}
+
+#[allow(dead_code)]
+impl UdpSender {
+    /// Sends to a `host:port` (an IPv6 address in brackets, e.g.
+    /// `[::1]:2237`), from a source port, or else any free one.
+    pub fn open(target: &str, source_port: Option<u16>, format: UdpFormat) -> io::Result<Self> {
+        let target = target.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("No address for {target}"))
+        })?;
+        let port = source_port.unwrap_or(0);
+        let socket = match target {
+            SocketAddr::V4(_) => UdpSocket::bind(("0.0.0.0", port))?,
+            SocketAddr::V6(_) => UdpSocket::bind(("::", port))?,
+        };
+        // Never to wait on the decoding thread:
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            target,
+            format,
+        })
+    }
+
+    /// The payload of the datagram of a message.
+    fn payload(&self, record: &Record) -> String {
+        match self.format {
+            UdpFormat::Json => record.to_json(),
+            UdpFormat::Plain => record.message.clone(),
+        }
+    }
+
+    pub fn send(&self, record: &Record) {
+        let payload = self.payload(record);
+        if let Err(e) = self.socket.send_to(payload.as_bytes(), self.target) {
+            debug!("Could not send the message to {}: {e}", self.target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+    use std::time::Duration;
+
+    #[test]
+    fn test_udp_sender() {
+        let record = Record {
+            time: Local::now(),
+            station: None,
+            message: "CQ DE N0CALL".to_string(),
+            wpm: Some(18),
+            tone: 700.0,
+            rig: None,
+        };
+        for (listen, format) in [
+            ("127.0.0.1:0", UdpFormat::Json),
+            ("[::1]:0", UdpFormat::Plain),
+        ] {
+            // Without IPv6 on the machine, there's nothing to check:
+            let Ok(receiver) = UdpSocket::bind(listen) else {
+                continue;
+            };
+            receiver
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let target = receiver.local_addr().unwrap().to_string();
+            let sender = UdpSender::open(&target, None, format).unwrap();
+            sender.send(&record);
+            let mut datagram = [0; 512];
+            let (len, from) = receiver.recv_from(&mut datagram).unwrap();
+            assert_eq!(from.port(), sender.socket.local_addr().unwrap().port());
+            let payload = std::str::from_utf8(&datagram[..len]).unwrap();
+            assert_eq!(payload, sender.payload(&record));
+        }
+        assert!(UdpSender::open("127.0.0.1:9", None, UdpFormat::Json)
+            .unwrap()
+            .payload(&record)
+            .contains("\"message\":\"CQ DE N0CALL\""));
+        assert_eq!("plain".parse(), Ok(UdpFormat::Plain));
+        assert!("xml".parse::<UdpFormat>().is_err());
+    }
+}
@@ -0,0 +1,584 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+/// The keys a layout may name, with their evdev codes (those of
+/// `linux/input-event-codes.h`, without the `KEY_`).
+const KEYS: &[(&str, u16)] = &[
+    ("1", 2),
+    ("2", 3),
+    ("3", 4),
+    ("4", 5),
+    ("5", 6),
+    ("6", 7),
+    ("7", 8),
+    ("8", 9),
+    ("9", 10),
+    ("0", 11),
+    ("minus", 12),
+    ("equal", 13),
+    ("backspace", 14),
+    ("tab", 15),
+    ("q", 16),
+    ("w", 17),
+    ("e", 18),
+    ("r", 19),
+    ("t", 20),
+    ("y", 21),
+    ("u", 22),
+    ("i", 23),
+    ("o", 24),
+    ("p", 25),
+    ("leftbrace", 26),
+    ("rightbrace", 27),
+    ("enter", 28),
+    ("a", 30),
+    ("s", 31),
+    ("d", 32),
+    ("f", 33),
+    ("g", 34),
+    ("h", 35),
+    ("j", 36),
+    ("k", 37),
+    ("l", 38),
+    ("semicolon", 39),
+    ("apostrophe", 40),
+    ("grave", 41),
+    ("leftshift", 42),
+    ("backslash", 43),
+    ("z", 44),
+    ("x", 45),
+    ("c", 46),
+    ("v", 47),
+    ("b", 48),
+    ("n", 49),
+    ("m", 50),
+    ("comma", 51),
+    ("dot", 52),
+    ("slash", 53),
+    ("space", 57),
+    ("102nd", 86),
+    ("rightalt", 100),
+];
+
+/// The built-in layouts, giving only the characters that aren't on the
+/// key of the same name (see `Layout::parse`).
+const LAYOUTS: &[(&str, &str)] = &[
+    (
+        "us",
+        "# US QWERTY
+. dot
+, comma
+? slash+shift
+/ slash
+= equal
++ equal+shift
+- minus
+( 9+shift
+) 0+shift
+\" apostrophe+shift
+' apostrophe
+: semicolon+shift
+; semicolon
+@ 2+shift
+& 7+shift
+! 1+shift
+_ minus+shift
+$ 4+shift
+",
+    ),
+    (
+        "de",
+        "# German QWERTZ
+y z
+z y
+. dot
+, comma
+? minus+shift
+/ 7+shift
+= 0+shift
++ rightbrace
+- slash
+( 8+shift
+) 9+shift
+\" 2+shift
+' backslash+shift
+: dot+shift
+; comma+shift
+@ q+altgr
+& 6+shift
+! 1+shift
+_ slash+shift
+$ 4+shift
+",
+    ),
+    (
+        "fr",
+        "# French AZERTY
+a q
+q a
+z w
+w z
+m semicolon
+1 1+shift
+2 2+shift
+3 3+shift
+4 4+shift
+5 5+shift
+6 6+shift
+7 7+shift
+8 8+shift
+9 9+shift
+0 0+shift
+. comma+shift
+, m
+? m+shift
+/ dot+shift
+= equal
++ equal+shift
+- 6
+( 5
+) minus
+\" 3
+' 4
+: dot
+; comma
+@ 0+altgr
+& 1
+! slash
+_ 8
+$ rightbrace
+",
+    ),
+];
+
+/// A key to press for a character, with the modifiers held down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stroke {
+    pub key: u16,
+    pub shift: bool,
+    pub altgr: bool,
+}
+
+/// The keys of each character, on a keyboard layout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    keys: HashMap<char, Stroke>,
+}
+
+impl Layout {
+    /// Parses a layout table, a line for each character with the key it
+    /// is on, and the modifiers to hold, e.g. `? slash+shift` or
+    /// `@ q+altgr` (`space` is the space character, and `#` starts a
+    /// comment). The letters and digits that aren't given are on the keys
+    /// of the same name, and the capitals are their letter with shift.
+    pub fn parse(table: &str) -> Result<Self, String> {
+        let key_code = |name: &str| {
+            KEYS.iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|&(_, code)| code)
+        };
+        let mut keys: HashMap<char, Stroke> = ('a'..='z')
+            .chain('0'..='9')
+            .chain([' ', '\n'])
+            .map(|c| {
+                let name = match c {
+                    ' ' => "space".to_string(),
+                    '\n' => "enter".to_string(),
+                    _ => c.to_string(),
+                };
+                let stroke = Stroke {
+                    key: key_code(&name).expect("A key of the US layout"),
+                    shift: false,
+                    altgr: false,
+                };
+                (c, stroke)
+            })
+            .collect();
+        for (number, line) in table.lines().enumerate() {
+            let line = match line.trim_start().starts_with('#') {
+                true => "",
+                false => line.trim(),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("Invalid layout line {}: {line}", number + 1);
+            let (c, key) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let c = match c {
+                "space" => ' ',
+                _ if c.chars().count() == 1 => c.chars().next().unwrap(),
+                _ => return Err(invalid()),
+            };
+            let mut parts = key.trim().split('+');
+            let name = parts.next().unwrap_or_default();
+            let mut stroke = Stroke {
+                key: key_code(name)
+                    .ok_or_else(|| format!("Unknown key {name} on line {}", number + 1))?,
+                shift: false,
+                altgr: false,
+            };
+            for modifier in parts {
+                match modifier {
+                    "shift" => stroke.shift = true,
+                    "altgr" => stroke.altgr = true,
+                    _ => return Err(invalid()),
+                }
+            }
+            keys.insert(c, stroke);
+        }
+        Ok(Layout { keys })
+    }
+
+    /// The key of a character, if the layout has it.
+    pub fn stroke(&self, c: char) -> Option<Stroke> {
+        if let Some(stroke) = self.keys.get(&c) {
+            return Some(*stroke);
+        }
+        let stroke = self.keys.get(&c.to_ascii_lowercase())?;
+        c.is_ascii_uppercase().then_some(Stroke {
+            shift: !stroke.shift,
+            ..*stroke
+        })
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    /// A built-in layout (`us`, `de` or `fr`), or the file of a table.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match LAYOUTS.iter().find(|(name, _)| *name == s) {
+            Some((_, table)) => Layout::parse(table),
+            None => {
+                let table = std::fs::read_to_string(s).map_err(|e| {
+                    format!("Not a layout (us, de or fr), nor a layout file: {s}: {e}")
+                })?;
+                Layout::parse(&table)
+            }
+        }
+    }
+}
+
+/// What to type for a message, after what was typed of it: the
+/// number of characters to erase, and those to type after.
+fn edit(typed: &str, message: &str) -> (usize, String) {
+    let same = typed
+        .chars()
+        .zip(message.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (
+        typed.chars().count() - same,
+        message.chars().skip(same).collect(),
+    )
+}
+
+/// Something typed on a keyboard.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Typed {
+    Backspace,
+    Char(char),
+}
+
+/// Presses and releases keys, by their evdev code.
+trait Keyboard {
+    fn key(&mut self, code: u16, down: bool) -> io::Result<()>;
+}
+
+/// Types a character (or a backspace) on a keyboard, with its modifiers.
+fn type_stroke(keyboard: &mut dyn Keyboard, stroke: Stroke) -> io::Result<()> {
+    const LEFTSHIFT: u16 = 42;
+    const RIGHTALT: u16 = 100;
+    let modifiers = [(stroke.shift, LEFTSHIFT), (stroke.altgr, RIGHTALT)];
+    for &(held, code) in &modifiers {
+        if held {
+            keyboard.key(code, true)?;
+        }
+    }
+    keyboard.key(stroke.key, true)?;
+    keyboard.key(stroke.key, false)?;
+    for &(held, code) in modifiers.iter().rev() {
+        if held {
+            keyboard.key(code, false)?;
+        }
+    }
+    Ok(())
+}
+
+/// Types what comes, at most a character every interval, so that the
+/// window with the focus keeps up, until the typist is dropped.
+fn type_all(
+    keyboard: &mut dyn Keyboard,
+    layout: &Layout,
+    interval: Duration,
+    typed: Receiver<Vec<Typed>>,
+) {
+    const BACKSPACE: u16 = 14;
+    for typed in typed {
+        for typed in typed {
+            let stroke = match typed {
+                Typed::Backspace => Some(Stroke {
+                    key: BACKSPACE,
+                    shift: false,
+                    altgr: false,
+                }),
+                Typed::Char(c) => layout.stroke(c),
+            };
+            let Some(stroke) = stroke else {
+                debug!("Not on the keyboard layout: {typed:?}");
+                continue;
+            };
+            if let Err(e) = type_stroke(keyboard, stroke) {
+                error!("Could not type on the virtual keyboard: {e}");
+                return;
+            }
+            std::thread::sleep(interval);
+        }
+    }
+}
+
+/// Types the characters decoded with a virtual keyboard, into the window
+/// that has the focus, with `--type-output`. A character decoded again
+/// differently is erased with backspace, and typed again, and each
+/// message ends with a space.
+#[allow(dead_code)]
+pub struct Typist {
+    typed: Sender<Vec<Typed>>,
+    /// What was typed of the message received so far.
+    message: String,
+}
+
+#[allow(dead_code)]
+impl Typist {
+    /// Creates the virtual keyboard (with `/dev/uinput`, on Linux), to
+    /// type at most `rate` characters a second.
+    pub fn start(layout: Layout, rate: u32) -> io::Result<Self> {
+        let mut keyboard = open_keyboard()?;
+        let interval = Duration::from_secs(1) / rate.max(1);
+        let (typed, receiver) = channel();
+        std::thread::spawn(move || type_all(keyboard.as_mut(), &layout, interval, receiver));
+        Ok(Typist {
+            typed,
+            message: String::new(),
+        })
+    }
+
+    /// Types what changed in the message received so far.
+    pub fn partial(&mut self, message: &str) {
+        let (erased, new) = edit(&self.message, message);
+        let typed: Vec<Typed> = std::iter::repeat_n(Typed::Backspace, erased)
+            .chain(new.chars().map(Typed::Char))
+            .collect();
+        if !typed.is_empty() {
+            let _ = self.typed.send(typed);
+            self.message = message.to_string();
+        }
+    }
+
+    /// Types the rest of a message that ended, and a space.
+    pub fn message(&mut self, message: &str) {
+        self.partial(message);
+        let _ = self.typed.send(vec![Typed::Char(' ')]);
+        self.message.clear();
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_keyboard() -> io::Result<Box<dyn Keyboard + Send>> {
+    Ok(Box::new(device::VirtualKeyboard::open()?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_keyboard() -> io::Result<Box<dyn Keyboard + Send>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Typing needs uinput, of Linux",
+    ))
+}
+
+/// The uinput device of the kernel, see `linux/uinput.h`.
+#[cfg(target_os = "linux")]
+mod device {
+    use super::{Keyboard, KEYS};
+    use crate::prelude::*;
+    use std::fs::File;
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    const UI_SET_EVBIT: u64 = 0x4004_5564;
+    const UI_SET_KEYBIT: u64 = 0x4004_5565;
+    const UI_DEV_CREATE: u64 = 0x5501;
+    const UI_DEV_DESTROY: u64 = 0x5502;
+    const EV_SYN: u16 = 0;
+    const EV_KEY: u16 = 1;
+    const SYN_REPORT: u16 = 0;
+    const BUS_VIRTUAL: u16 = 0x06;
+
+    /// `struct uinput_user_dev`
+    #[repr(C)]
+    struct UserDev {
+        name: [u8; 80],
+        bustype: u16,
+        vendor: u16,
+        product: u16,
+        version: u16,
+        ff_effects_max: u32,
+        absmax: [i32; 64],
+        absmin: [i32; 64],
+        absfuzz: [i32; 64],
+        absflat: [i32; 64],
+    }
+
+    /// `struct input_event`
+    #[repr(C)]
+    struct InputEvent {
+        time: libc::timeval,
+        kind: u16,
+        code: u16,
+        value: i32,
+    }
+
+    /// The bytes of a C struct, to write to the device.
+    fn bytes<T>(value: &T) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+    }
+
+    /// A keyboard made with uinput, which the desktop takes for another
+    /// keyboard plugged in, removed when dropped.
+    pub struct VirtualKeyboard(File);
+
+    impl VirtualKeyboard {
+        pub fn open() -> io::Result<Self> {
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open("/dev/uinput")?;
+            let fd = file.as_raw_fd();
+            let ioctl = |request: u64, arg: libc::c_int| match unsafe {
+                libc::ioctl(fd, request as _, arg)
+            } {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            };
+            ioctl(UI_SET_EVBIT, EV_KEY as libc::c_int)?;
+            for &(_, code) in KEYS {
+                ioctl(UI_SET_KEYBIT, code as libc::c_int)?;
+            }
+            let mut dev: UserDev = unsafe { std::mem::zeroed() };
+            let name = b"code-smore virtual keyboard";
+            dev.name[..name.len()].copy_from_slice(name);
+            dev.bustype = BUS_VIRTUAL;
+            dev.version = 1;
+            (&file).write_all(bytes(&dev))?;
+            ioctl(UI_DEV_CREATE, 0)?;
+            // For the desktop to find the keyboard, before the first key:
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            Ok(VirtualKeyboard(file))
+        }
+
+        fn event(&mut self, kind: u16, code: u16, value: i32) -> io::Result<()> {
+            let event = InputEvent {
+                time: libc::timeval {
+                    tv_sec: 0,
+                    tv_usec: 0,
+                },
+                kind,
+                code,
+                value,
+            };
+            self.0.write_all(bytes(&event))
+        }
+    }
+
+    impl Keyboard for VirtualKeyboard {
+        fn key(&mut self, code: u16, down: bool) -> io::Result<()> {
+            self.event(EV_KEY, code, down as i32)?;
+            self.event(EV_SYN, SYN_REPORT, 0)
+        }
+    }
+
+    impl Drop for VirtualKeyboard {
+        fn drop(&mut self) {
+            unsafe {
+                libc::ioctl(self.0.as_raw_fd(), UI_DEV_DESTROY as _);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let us: Layout = "us".parse().unwrap();
+        let stroke = |key, shift, altgr| Some(Stroke { key, shift, altgr });
+        assert_eq!(us.stroke('k'), stroke(37, false, false));
+        assert_eq!(us.stroke('K'), stroke(37, true, false));
+        assert_eq!(us.stroke('?'), stroke(53, true, false));
+        assert_eq!(us.stroke(' '), stroke(57, false, false));
+        assert_eq!(us.stroke('é'), None);
+        let de: Layout = "de".parse().unwrap();
+        assert_eq!(de.stroke('Z'), stroke(21, true, false));
+        assert_eq!(de.stroke('@'), stroke(16, false, true));
+        let fr: Layout = "fr".parse().unwrap();
+        assert_eq!(fr.stroke('A'), stroke(16, true, false));
+        assert_eq!(fr.stroke('1'), stroke(2, true, false));
+
+        let custom = Layout::parse("# Dvorak, in part\nk v\n\n/ leftbrace\n").unwrap();
+        assert_eq!(custom.stroke('K'), stroke(47, true, false));
+        assert_eq!(custom.stroke('/'), stroke(26, false, false));
+        assert!(Layout::parse("k nokey").is_err());
+        assert!(Layout::parse("k v+ctrl").is_err());
+        assert!(Layout::parse("kv").is_err());
+        assert!("nosuchlayout".parse::<Layout>().is_err());
+    }
+
+    #[test]
+    fn test_edit() {
+        assert_eq!(edit("", "CQ"), (0, "CQ".to_string()));
+        assert_eq!(edit("CQ", "CQ D"), (0, " D".to_string()));
+        assert_eq!(edit("CQ E", "CQ I"), (1, "I".to_string()));
+        assert_eq!(edit("CQ", "CQ"), (0, String::new()));
+    }
+
+    /// Keeps the keys pressed and released.
+    struct Keys(Vec<(u16, bool)>);
+
+    impl Keyboard for Keys {
+        fn key(&mut self, code: u16, down: bool) -> io::Result<()> {
+            self.0.push((code, down));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_type() {
+        let (typed, receiver) = channel();
+        let mut typist = Typist {
+            typed,
+            message: String::new(),
+        };
+        typist.partial("K");
+        typist.partial("KE");
+        typist.message("KI");
+        drop(typist);
+        let mut keys = Keys(Vec::new());
+        let layout: Layout = "us".parse().unwrap();
+        type_all(&mut keys, &layout, Duration::ZERO, receiver);
+        let shifted = |key| [(42, true), (key, true), (key, false), (42, false)];
+        let expected: Vec<(u16, bool)> = [
+            shifted(37).to_vec(),
+            shifted(18).to_vec(),
+            vec![(14, true), (14, false)],
+            shifted(23).to_vec(),
+            vec![(57, true), (57, false)],
+        ]
+        .concat();
+        assert_eq!(keys.0, expected);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::filter::goertzel_level;
+
+/// The lowest and highest audio frequencies shown, in Hz.
+pub const MIN_FREQ: f32 = 100.0;
+pub const MAX_FREQ: f32 = 2000.0;
+
+/// How many rows are drawn each second.
+const ROWS_PER_SEC: u32 = 4;
+
+/// How many decibels above the noise of its row each shade stands for,
+/// the darkest one being 40dB over it.
+const SHADE_DB: f32 = 10.0;
+
+/// A spectrogram of the captured audio, drawn a row at a time as shaded
+/// block characters, one column for each band of frequencies.
+///
+/// The power of each column is measured with a Goertzel detector over
+/// Hann windowed frames, short enough that a tone anywhere in a column
+/// still shows in it, and a row is the average of all the frames of a
+/// quarter of a second. Each row is shaded against its own median, so
+/// that the traces stand out whatever the volume.
+#[allow(dead_code)]
+pub struct Waterfall {
+    columns: usize,
+    sample_rate: u32,
+    frame: Vec<f32>,
+    frame_size: usize,
+    frames_per_row: usize,
+    frames: usize,
+    powers: Vec<f64>,
+    unicode: bool,
+}
+
+#[allow(dead_code)]
+impl Waterfall {
+    pub fn new(columns: usize) -> Self {
+        Self {
+            columns: columns.max(2),
+            sample_rate: 0,
+            frame: Vec::new(),
+            frame_size: 0,
+            frames_per_row: 1,
+            frames: 0,
+            powers: vec![0.0; columns.max(2)],
+            unicode: crate::sparkline::unicode_supported(),
+        }
+    }
+
+    /// The width of each column in Hz.
+    fn step(&self) -> f32 {
+        (MAX_FREQ - MIN_FREQ) / (self.columns - 1) as f32
+    }
+
+    /// The frequency at the middle of a column, in Hz.
+    pub fn frequency(&self, column: usize) -> f32 {
+        MIN_FREQ + self.step() * column as f32
+    }
+
+    /// The column that shows a frequency, if it is in range.
+    pub fn column(&self, freq: f32) -> Option<usize> {
+        let column = ((freq - MIN_FREQ) / self.step()).round();
+        (column >= 0.0 && column < self.columns as f32).then_some(column as usize)
+    }
+
+    /// Adds captured samples.
+    ///
+    /// # Returns
+    /// The power of each column, for each row completed by the samples.
+    pub fn push(&mut self, samples: &[f32], rate: u32) -> Vec<Vec<f64>> {
+        let mut rows = Vec::new();
+        if rate == 0 {
+            return rows;
+        }
+        if rate != self.sample_rate {
+            self.sample_rate = rate;
+            // The main lobe of the window is then two columns wide:
+            let row_size = (rate / ROWS_PER_SEC) as usize;
+            self.frame_size = ((2.0 * rate as f32 / self.step()) as usize)
+                .max(256)
+                .min(row_size);
+            self.frames_per_row = (row_size / self.frame_size).max(1);
+            self.frame.clear();
+            self.frames = 0;
+            self.powers.iter_mut().for_each(|p| *p = 0.0);
+        }
+        for &sample in samples {
+            self.frame.push(sample);
+            if self.frame.len() == self.frame_size {
+                self.analyze_frame();
+                self.frame.clear();
+                self.frames += 1;
+                if self.frames == self.frames_per_row {
+                    let frames = self.frames as f64;
+                    rows.push(self.powers.iter().map(|p| p / frames).collect());
+                    self.powers.iter_mut().for_each(|p| *p = 0.0);
+                    self.frames = 0;
+                }
+            }
+        }
+        rows
+    }
+
+    fn analyze_frame(&mut self) {
+        let n = self.frame.len();
+        let windowed: Vec<f32> = self
+            .frame
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let hann = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / n as f32).cos();
+                s * hann
+            })
+            .collect();
+        for column in 0..self.columns {
+            let freq = self.frequency(column) as f64;
+            self.powers[column] += goertzel_level(&windowed, freq, self.sample_rate as f64) as f64;
+        }
+    }
+
+    /// Draws a row of powers, with a marker at the column of the tone.
+    pub fn row(&self, powers: &[f64], tone_freq: f32) -> String {
+        let shades: &[char] = match self.unicode {
+            true => &[' ', '░', '▒', '▓', '█'],
+            false => &[' ', '.', ':', '+', '#'],
+        };
+        let marker = if self.unicode { '┃' } else { '|' };
+        let mut sorted = powers.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let median = sorted.get(sorted.len() / 2).copied().unwrap_or(0.0);
+        let tone_column = self.column(tone_freq);
+        powers
+            .iter()
+            .enumerate()
+            .map(|(column, &power)| {
+                if Some(column) == tone_column {
+                    return marker;
+                }
+                if power <= 0.0 {
+                    return shades[0];
+                }
+                let db = 10.0 * (power / median.max(1e-12)).log10() as f32;
+                shades[((db / SHADE_DB).max(0.0) as usize).min(shades.len() - 1)]
+            })
+            .collect()
+    }
+
+    /// A line labeling the frequencies under the columns, every 500 Hz.
+    pub fn scale(&self) -> String {
+        let mut line = vec![' '; self.columns];
+        let mut freq = 500.0;
+        while freq < MAX_FREQ {
+            let label = format!("{freq:.0}");
+            if let Some(column) = self.column(freq) {
+                if column + label.len() <= self.columns {
+                    for (i, c) in label.chars().enumerate() {
+                        line[column + i] = c;
+                    }
+                }
+            }
+            freq += 500.0;
+        }
+        line.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// A tone in a little noise.
+    fn sine(freq: f32, seconds: f32) -> Vec<f32> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                0.5 * (2.0 * std::f32::consts::PI * freq * t).sin() + rng.gen_range(-0.05..0.05)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_waterfall_shows_tone() {
+        let mut waterfall = Waterfall::new(39);
+        waterfall.unicode = false;
+        assert_eq!(waterfall.frequency(0), 100.0);
+        assert_eq!(waterfall.frequency(38), 2000.0);
+        assert_eq!(waterfall.column(700.0), Some(12));
+        assert_eq!(waterfall.column(2100.0), None);
+
+        // A second of audio makes four rows, in any size of blocks:
+        let samples = sine(720.0, 1.0);
+        let rows: Vec<Vec<f64>> = samples
+            .chunks(1000)
+            .flat_map(|block| waterfall.push(block, SAMPLE_RATE))
+            .collect();
+        assert_eq!(rows.len(), 4);
+        // The tone, between two columns, still shows in them:
+        let row = waterfall.row(&rows[3], 1500.0);
+        assert_eq!(row.chars().nth(28), Some('|'));
+        let shades: Vec<char> = row.chars().collect();
+        assert_eq!((shades[12], shades[13]), ('#', '#'), "{row}");
+        for (column, &shade) in shades.iter().enumerate() {
+            if !(10..=15).contains(&column) && column != 28 {
+                assert_ne!(shade, '#', "{row}");
+            }
+        }
+        assert_eq!(shades[0], ' ', "{row}");
+
+        // Silence is blank, apart from the marker:
+        let rows = waterfall.push(&vec![0.0; SAMPLE_RATE as usize / 2], SAMPLE_RATE);
+        assert_eq!(waterfall.row(rows.last().unwrap(), 700.0).trim(), "|");
+    }
+
+    #[test]
+    fn test_waterfall_scale() {
+        let waterfall = Waterfall::new(39);
+        assert_eq!(
+            waterfall.scale(),
+            format!("{:8}500{:7}1000{:6}1500{:7}", "", "", "", "")
+        );
+    }
+}
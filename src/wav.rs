@@ -0,0 +1,615 @@
+use crate::detect::{Channel, DecodeEvent, PipelineOptions, SignalPipeline, Station, TextOptions};
+use crate::filter::{BandpassFilter, Detector};
+use crate::keyer::Keyer;
+use crate::morse::{self, text_to_morse};
+use crate::prelude::*;
+use crate::scan::{self, ScanBank, ScanRange};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use std::time::{Duration, Instant};
+
+/// Number of frames processed at a time, similar to a PipeWire buffer.
+const BLOCK_SIZE: usize = 512;
+
+/// Reads a WAV file and prints the decoded morse code to stdout.
+///
+/// Each station is decoded from its own channel, and its messages are
+/// printed together, after its label. With `scan`, the messages of
+/// every channel of the scan are printed as they are completed instead,
+/// after their frequency.
+#[allow(clippy::too_many_arguments)]
+pub fn decode_file(
+    path: &str,
+    stations: &[Station],
+    scan: Option<ScanRange>,
+    bandwidth: f32,
+    threshold: Option<f32>,
+    debounce_duration: u32,
+    detector: Detector,
+    dot_duration: u32,
+    timeout_duration: u32,
+    adaptive_speed: bool,
+    text_options: &TextOptions,
+    output_morse: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(range) = scan {
+        let (samples, sample_rate) = read_channel(path, stations[0].channel)?;
+        for (freq, message) in decode_scan(
+            &samples,
+            sample_rate,
+            &range,
+            threshold,
+            debounce_duration,
+            dot_duration,
+            timeout_duration,
+            adaptive_speed,
+            text_options,
+        ) {
+            let message = match output_morse {
+                true => text_to_morse(&message),
+                false => message,
+            };
+            println!("{} {message}", scan::label(freq));
+        }
+        return Ok(());
+    }
+    for station in stations {
+        let (samples, sample_rate) = read_channel(path, station.channel)?;
+        for message in decode_samples(
+            &samples,
+            sample_rate,
+            station.tone_freq,
+            station.tone_range,
+            bandwidth,
+            threshold,
+            debounce_duration,
+            detector,
+            dot_duration,
+            timeout_duration,
+            adaptive_speed,
+            text_options.clone(),
+        )? {
+            let message = if output_morse {
+                text_to_morse(&message)
+            } else {
+                message
+            };
+            match &station.label {
+                Some(label) => println!("{label} {message}"),
+                None => println!("{message}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes samples to a 16 bit WAV file.
+fn write_samples<W: std::io::Write + std::io::Seek>(
+    writer: &mut WavWriter<W>,
+    samples: &[f32],
+) -> Result<(), hound::Error> {
+    for sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    Ok(())
+}
+
+/// Writes some samples of silence, with the noise of the band of the
+/// keyer if it has one.
+fn write_silence<W: std::io::Write + std::io::Seek>(
+    writer: &mut WavWriter<W>,
+    keyer: &mut Keyer,
+    samples: usize,
+) -> Result<(), hound::Error> {
+    let mut block = [0.0; BLOCK_SIZE];
+    for start in (0..samples).step_by(BLOCK_SIZE) {
+        let count = BLOCK_SIZE.min(samples - start);
+        keyer.fill_silence(&mut block[..count]);
+        write_samples(writer, &block[..count])?;
+    }
+    Ok(())
+}
+
+/// Writes the sound of a keyer to a mono 16 bit WAV file, with
+/// `padding` milliseconds of silence before and after it, or of the
+/// noise of its band.
+pub fn write_file(
+    path: &str,
+    mut keyer: Keyer,
+    padding: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: keyer.sample_rate(),
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut writer = WavWriter::create(path, spec)?;
+    let silence = (keyer.sample_rate() as u64 * padding as u64 / 1000) as usize;
+    let mut block = [0.0; BLOCK_SIZE];
+    write_silence(&mut writer, &mut keyer, silence)?;
+    loop {
+        let count = keyer.fill(&mut block);
+        if count == 0 {
+            break;
+        }
+        write_samples(&mut writer, &block[..count])?;
+    }
+    write_silence(&mut writer, &mut keyer, silence)?;
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Reads all the samples of one channel of a WAV file, or of all the
+/// channels mixed down to mono.
+///
+/// # Returns
+/// The mono samples (normalized to -1.0..1.0) and the sample rate.
+pub fn read_channel(
+    path: &str,
+    channel: Channel,
+) -> Result<(Vec<f32>, u32), Box<dyn std::error::Error>> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        SampleFormat::Int => {
+            let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let channels = spec.channels.max(1) as usize;
+    channel.check(channels)?;
+    let samples = interleaved
+        .chunks(channels)
+        .map(|frame| channel.sample(frame))
+        .collect();
+    Ok((samples, spec.sample_rate))
+}
+
+/// Runs mono samples through the same SignalPipeline as `listen`, a
+/// block at a time.
+///
+/// When `tone_range` is given, the tone frequency is searched for
+/// within that range (in Hz) instead of using `tone_freq`.
+///
+/// # Returns
+/// The list of messages, in the order they were completed.
+#[allow(clippy::too_many_arguments)]
+pub fn decode_samples(
+    samples: &[f32],
+    sample_rate: u32,
+    tone_freq: f32,
+    tone_range: Option<(f32, f32)>,
+    bandwidth: f32,
+    threshold: Option<f32>,
+    debounce_duration: u32,
+    detector: Detector,
+    dot_duration: u32,
+    timeout_duration: u32,
+    adaptive_speed: bool,
+    text_options: TextOptions,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut messages = Vec::new();
+    decode_paced(
+        samples,
+        sample_rate,
+        tone_freq,
+        tone_range,
+        bandwidth,
+        threshold,
+        debounce_duration,
+        detector,
+        dot_duration,
+        timeout_duration,
+        adaptive_speed,
+        text_options,
+        None,
+        &mut |message| messages.push(message),
+    )?;
+    Ok(messages)
+}
+
+/// Runs mono samples through the SignalPipeline like `decode_samples`,
+/// giving each message as it is completed. The timing is that of the
+/// samples, however quickly they go through: as fast as they can, or
+/// with `speed`, paced to that many times the speed they were recorded
+/// at.
+#[allow(clippy::too_many_arguments)]
+pub fn decode_paced(
+    samples: &[f32],
+    sample_rate: u32,
+    tone_freq: f32,
+    tone_range: Option<(f32, f32)>,
+    bandwidth: f32,
+    threshold: Option<f32>,
+    debounce_duration: u32,
+    detector: Detector,
+    dot_duration: u32,
+    timeout_duration: u32,
+    adaptive_speed: bool,
+    text_options: TextOptions,
+    speed: Option<f32>,
+    on_message: &mut dyn FnMut(String),
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Check the filter settings up front, rather than panic in the pipeline:
+    BandpassFilter::new(5, tone_freq.into(), bandwidth.into(), sample_rate as f64)?;
+    let mut pipeline = SignalPipeline::new(PipelineOptions {
+        tone_freq,
+        tone_range,
+        bandwidth,
+        threshold,
+        debounce_duration,
+        detector,
+        dot_duration,
+        timeout_duration,
+        adaptive_speed,
+        text_options,
+    });
+    let mut handle = |events: Vec<DecodeEvent>| {
+        for event in events {
+            match event {
+                DecodeEvent::Message(msg) => on_message(msg),
+                DecodeEvent::ToneLocked(freq) => info!("locked to {freq:.0} Hz"),
+                DecodeEvent::Threshold(chosen) => info!("threshold {chosen:.3}"),
+                DecodeEvent::Speed(dot) => info!("tracking {} WPM", morse::dot_length_to_wpm(dot)),
+                _ => {}
+            }
+        }
+    };
+    let started = Instant::now();
+    for (i, block) in samples.chunks(BLOCK_SIZE).enumerate() {
+        handle(pipeline.push_samples(block, sample_rate));
+        if let Some(speed) = speed {
+            let heard = ((i + 1) * BLOCK_SIZE) as f32 / sample_rate.max(1) as f32;
+            let due = started + Duration::from_secs_f32(heard / speed);
+            std::thread::sleep(due.saturating_duration_since(Instant::now()));
+        }
+    }
+    // The file ended, finish whatever was still being received:
+    handle(pipeline.finish());
+    Ok(())
+}
+
+/// Runs mono samples through the bank of detectors of `--scan`, and a
+/// pipeline for each of its channels, a block at a time.
+///
+/// # Returns
+/// The messages of every channel, with its frequency, in the order
+/// they were completed.
+#[allow(clippy::too_many_arguments)]
+pub fn decode_scan(
+    samples: &[f32],
+    sample_rate: u32,
+    range: &ScanRange,
+    threshold: Option<f32>,
+    debounce_duration: u32,
+    dot_duration: u32,
+    timeout_duration: u32,
+    adaptive_speed: bool,
+    text_options: &TextOptions,
+) -> Vec<(f32, String)> {
+    let mut bank = ScanBank::new(range);
+    let freqs = range.freqs();
+    let mut pipelines: Vec<SignalPipeline> = freqs
+        .iter()
+        .map(|&freq| {
+            SignalPipeline::new(PipelineOptions {
+                tone_freq: freq,
+                bandwidth: range.step,
+                threshold,
+                debounce_duration,
+                detector: Detector::Goertzel,
+                dot_duration,
+                timeout_duration,
+                adaptive_speed,
+                text_options: text_options.clone(),
+                ..Default::default()
+            })
+        })
+        .collect();
+    let mut messages = Vec::new();
+    for block in samples.chunks(BLOCK_SIZE) {
+        for levels in bank.push(block, sample_rate) {
+            for (index, level) in levels.into_iter().enumerate() {
+                for event in pipelines[index].push_level(level, bank.window(), sample_rate) {
+                    if let DecodeEvent::Message(msg) = event {
+                        messages.push((freqs[index], msg));
+                    }
+                }
+            }
+        }
+    }
+    for (pipeline, &freq) in pipelines.iter_mut().zip(&freqs) {
+        for event in pipeline.finish() {
+            if let DecodeEvent::Message(msg) = event {
+                messages.push((freq, msg));
+            }
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{encode, Separators};
+    use crate::keyer::{Timing, SAMPLE_RATE as KEYER_RATE};
+    use crate::morse::encode_morse;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Renders text as a keyed sine wave.
+    fn synthesize(text: &str, dot_duration: u32, tone_freq: f32) -> Vec<f32> {
+        let mut samples = Vec::new();
+        for (freq, duration) in encode_morse(text, dot_duration, tone_freq) {
+            let n = (SAMPLE_RATE * duration / 1000) as usize;
+            for i in 0..n {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                samples.push(0.5 * (2.0 * std::f32::consts::PI * freq * t).sin());
+            }
+        }
+        // Trailing silence so the message times out:
+        samples.resize(samples.len() + SAMPLE_RATE as usize * 2, 0.0);
+        samples
+    }
+
+    #[test]
+    fn test_decode_samples() {
+        let samples = synthesize("CQ CQ DE K1ABC", 60, 700.0);
+        let messages = decode_samples(
+            &samples,
+            SAMPLE_RATE,
+            700.0,
+            None,
+            200.0,
+            Some(0.3),
+            15,
+            Detector::Peak,
+            60,
+            1200,
+            false,
+            TextOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(messages, vec!["CQ CQ DE K1ABC"]);
+    }
+
+    #[test]
+    fn test_decode_paced() {
+        let samples = synthesize("TEST", 60, 700.0);
+        let heard = samples.len() as f32 / SAMPLE_RATE as f32;
+        let mut messages = Vec::new();
+        let started = Instant::now();
+        decode_paced(
+            &samples,
+            SAMPLE_RATE,
+            700.0,
+            None,
+            200.0,
+            Some(0.3),
+            15,
+            Detector::Peak,
+            60,
+            1200,
+            false,
+            TextOptions::default(),
+            Some(50.0),
+            &mut |message| messages.push(message),
+        )
+        .unwrap();
+        // The same messages, no faster than 50 times the speed:
+        assert_eq!(messages, vec!["TEST"]);
+        assert!(started.elapsed() >= Duration::from_secs_f32(heard / 50.0 * 0.9));
+    }
+
+    #[test]
+    fn test_decode_stereo_wav_file() {
+        let path = std::env::temp_dir().join(format!("code-smore-test-{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for sample in synthesize("PARIS", 60, 600.0) {
+            let sample = (sample * i16::MAX as f32) as i16;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let (samples, sample_rate) = read_channel(path.to_str().unwrap(), Channel::Mix).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sample_rate, SAMPLE_RATE);
+        let messages = decode_samples(
+            &samples,
+            sample_rate,
+            600.0,
+            None,
+            200.0,
+            Some(0.3),
+            15,
+            Detector::Goertzel,
+            60,
+            1200,
+            false,
+            TextOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(messages, vec!["PARIS"]);
+    }
+
+    #[test]
+    fn test_decode_split_stereo_wav_file() {
+        let path =
+            std::env::temp_dir().join(format!("code-smore-split-{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        // Two different stations, at different tones:
+        let mut left = synthesize("CQ CQ", 60, 600.0);
+        let mut right = synthesize("TEST DE K1ABC", 50, 900.0);
+        let len = left.len().max(right.len());
+        left.resize(len, 0.0);
+        right.resize(len, 0.0);
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for (l, r) in left.iter().zip(&right) {
+            writer.write_sample(*l).unwrap();
+            writer.write_sample(*r).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let decode = |channel, tone_freq, dot_duration| {
+            let (samples, sample_rate) = read_channel(path.to_str().unwrap(), channel).unwrap();
+            decode_samples(
+                &samples,
+                sample_rate,
+                tone_freq,
+                None,
+                200.0,
+                Some(0.3),
+                15,
+                Detector::Goertzel,
+                dot_duration,
+                dot_duration * 20,
+                false,
+                TextOptions::default(),
+            )
+            .unwrap()
+        };
+        let left = decode(Channel::Index(0), 600.0, 60);
+        let right = decode(Channel::Index(1), 900.0, 50);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(left, vec!["CQ CQ"]);
+        assert_eq!(right, vec!["TEST DE K1ABC"]);
+    }
+
+    #[test]
+    fn test_decode_scan() {
+        // Two stations at once, on two channels of the scan, after some
+        // silence to measure the noise floor:
+        let mut first = vec![0.0_f32; SAMPLE_RATE as usize / 2];
+        first.extend(synthesize("CQ CQ", 60, 500.0));
+        let mut second = vec![0.0_f32; SAMPLE_RATE as usize / 2];
+        second.extend(synthesize("TEST DE K1ABC", 50, 750.0));
+        first.resize(first.len().max(second.len()), 0.0);
+        let samples: Vec<f32> = first
+            .iter()
+            .zip(second.iter().chain(std::iter::repeat(&0.0)))
+            .map(|(a, b)| (a + b) / 2.0)
+            .collect();
+        let range = "400:900:50".parse().unwrap();
+        for threshold in [Some(0.1), None] {
+            let mut messages = decode_scan(
+                &samples,
+                SAMPLE_RATE,
+                &range,
+                threshold,
+                15,
+                55,
+                1100,
+                true,
+                &TextOptions::default(),
+            );
+            messages.sort_by(|a, b| a.0.total_cmp(&b.0));
+            assert_eq!(
+                messages,
+                vec![
+                    (500.0, "CQ CQ".to_string()),
+                    (750.0, "TEST DE K1ABC".to_string())
+                ],
+                "threshold {threshold:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_samples_auto_tone() {
+        // Leave a few seconds of the tone at the start to lock on to:
+        let mut samples = synthesize("TTTTTTTTTT", 60, 642.0);
+        samples.extend(synthesize("CQ DE K1ABC", 60, 642.0));
+        let messages = decode_samples(
+            &samples,
+            SAMPLE_RATE,
+            750.0,
+            Some((300.0, 1200.0)),
+            200.0,
+            Some(0.3),
+            15,
+            Detector::Peak,
+            60,
+            1200,
+            false,
+            TextOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(messages.last().unwrap(), "CQ DE K1ABC");
+    }
+
+    #[test]
+    fn test_decode_samples_auto_threshold() {
+        // A quiet signal, after some silence to measure the noise floor:
+        let mut samples = vec![0.0_f32; SAMPLE_RATE as usize / 2];
+        samples.extend(
+            synthesize("CQ CQ DE K1ABC", 60, 700.0)
+                .iter()
+                .map(|s| s * 0.05),
+        );
+        let messages = decode_samples(
+            &samples,
+            SAMPLE_RATE,
+            700.0,
+            None,
+            200.0,
+            None,
+            15,
+            Detector::Peak,
+            60,
+            1200,
+            false,
+            TextOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(messages, vec!["CQ CQ DE K1ABC"]);
+    }
+
+    #[test]
+    fn test_render_wav_file_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("code-smore-render-{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+        let text = "VVV CQ CQ DE K1ABC <AR>";
+        // At 18 WPM:
+        let code = encode(text, &Separators::default());
+        let events = Timing::new(67, None).key_events(&code);
+        let keyer = Keyer::new(&events, KEYER_RATE, 700.0, 0.5, 5.0);
+        write_file(path, keyer, 500).unwrap();
+
+        let (samples, sample_rate) = read_channel(path, Channel::Mix).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(sample_rate, 48000);
+        let messages = decode_samples(
+            &samples,
+            sample_rate,
+            700.0,
+            None,
+            200.0,
+            Some(0.3),
+            15,
+            Detector::Goertzel,
+            67,
+            67 * 40,
+            false,
+            TextOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(messages, vec![text]);
+    }
+}
@@ -0,0 +1,360 @@
+use crate::prelude::*;
+use crate::server::Server;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The page served over HTTP, showing the stream.
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// Appended to the key of the handshake, as RFC 6455 has it.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// How often a client is pinged when nothing else is sent to it. One
+/// that hasn't answered in three times as long is dropped.
+const PING: Duration = Duration::from_secs(20);
+
+/// The biggest frame a client may send, as it only ever needs to answer
+/// pings.
+const MAX_FRAME: u64 = 1 << 16;
+
+/// The longest request a client may start with, its headers and all.
+const MAX_REQUEST: u64 = 8 * 1024;
+
+const TEXT: u8 = 0x1;
+const CLOSE: u8 = 0x8;
+const PING_FRAME: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+/// The SHA-1 digest of some data, which the handshake is made with.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (word, bytes) in w.iter_mut().zip(chunk.chunks(4)) {
+            *word = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, temp);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut digest = [0; 20];
+    for (bytes, h) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+/// The answer to the key of a client's handshake.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{GUID}", key.trim()).as_bytes()))
+}
+
+/// A frame sent to a client, which isn't masked.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Reads a frame sent by a client, which is masked.
+///
+/// # Returns
+/// Its opcode and its payload.
+fn read_frame(reader: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header)?;
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_FRAME {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too big"));
+    }
+    let mut mask = [0; 4];
+    if header[1] & 0x80 != 0 {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((header[0] & 0x0F, payload))
+}
+
+/// Reads the request a client starts with, up to `MAX_REQUEST` bytes.
+///
+/// # Returns
+/// Its path, and the key of the handshake if it asks for a WebSocket,
+/// or None if it is longer than that.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<(String, Option<String>)>> {
+    let mut reader = reader.take(MAX_REQUEST);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let path = line.split_whitespace().nth(1).unwrap_or("/").to_string();
+    let mut key = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            if reader.limit() == 0 {
+                return Ok(None);
+            }
+            break;
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            }
+        }
+    }
+    Ok(Some((path, key)))
+}
+
+/// Serves a client of `listen --serve ws:`: the stream of events when
+/// it asks for a WebSocket, or else the page showing it.
+pub fn serve(stream: TcpStream, server: &Server) {
+    let peer = stream
+        .peer_addr()
+        .map_or("a client".to_string(), |peer| peer.to_string());
+    match handle(stream, server) {
+        Ok(()) => debug!("{peer} disconnected"),
+        Err(e) => debug!("{peer} disconnected: {e}"),
+    }
+}
+
+fn handle(mut stream: TcpStream, server: &Server) -> io::Result<()> {
+    stream.set_read_timeout(Some(PING * 3))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let Some((path, key)) = read_request(&mut reader)? else {
+        write!(
+            stream,
+            "HTTP/1.1 431 Request Header Fields Too Large\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )?;
+        stream.flush()?;
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "request too big",
+        ));
+    };
+    let Some(key) = key else {
+        let (status, body) = match path.as_str() {
+            "/" | "/index.html" => ("200 OK", DASHBOARD),
+            _ => ("404 Not Found", "Not found\n"),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )?;
+        return stream.flush();
+    };
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )?;
+    let lines = server.register(stream.try_clone()?);
+    let writer = Arc::new(Mutex::new(stream));
+    // The client is read from on a thread of its own, to answer its
+    // pings, and to tell when it is gone:
+    let answering = writer.clone();
+    std::thread::spawn(move || {
+        loop {
+            match read_frame(&mut reader) {
+                Ok((PING_FRAME, payload)) => {
+                    let mut stream = answering.lock().unwrap();
+                    if stream.write_all(&frame(PONG, &payload)).is_err() {
+                        break;
+                    }
+                }
+                Ok((CLOSE, _)) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+        let _ = reader.get_ref().shutdown(Shutdown::Both);
+    });
+    loop {
+        let frame = match lines.recv_timeout(PING) {
+            Ok(line) => frame(TEXT, line.as_bytes()),
+            Err(RecvTimeoutError::Timeout) => frame(PING_FRAME, &[]),
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let mut stream = writer.lock().unwrap();
+        if let Err(e) = stream.write_all(&frame) {
+            let _ = stream.shutdown(Shutdown::Both);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::OutputFormat;
+
+    #[test]
+    fn test_handshake() {
+        let hex: String = sha1(b"abc").iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(hex, "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(base64(b"any carnal pleas"), "YW55IGNhcm5hbCBwbGVhcw==");
+        assert_eq!(base64(b"any carnal pleasu"), "YW55IGNhcm5hbCBwbGVhc3U=");
+        assert_eq!(base64(b"any carnal pleasur"), "YW55IGNhcm5hbCBwbGVhc3Vy");
+        // The example of RFC 6455:
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_frames() {
+        assert_eq!(frame(TEXT, b"CQ"), [0x81, 2, b'C', b'Q']);
+        let long = frame(TEXT, &[b'E'; 300]);
+        assert_eq!(long[..4], [0x81, 126, 1, 44]);
+        assert_eq!(long.len(), 4 + 300);
+
+        // As a client sends it, masked:
+        let mask = [1, 2, 3, 4];
+        let mut sent = vec![0x89, 0x80 | 3];
+        sent.extend_from_slice(&mask);
+        sent.extend(b"abc".iter().zip(mask).map(|(b, m)| b ^ m));
+        assert_eq!(
+            read_frame(&mut sent.as_slice()).unwrap(),
+            (PING_FRAME, b"abc".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_read_request() {
+        let request = "GET /events HTTP/1.1\r\nSec-WebSocket-Key: abc\r\n\r\n";
+        assert_eq!(
+            read_request(&mut request.as_bytes()).unwrap(),
+            Some(("/events".to_string(), Some("abc".to_string())))
+        );
+        // Cut off:
+        assert_eq!(
+            read_request(&mut "GET / HTTP/1.1\r\n".as_bytes()).unwrap(),
+            Some(("/".to_string(), None))
+        );
+        let endless = format!("GET / HTTP/1.1\r\nCookie: {}", "a".repeat(10_000));
+        assert_eq!(read_request(&mut endless.as_bytes()).unwrap(), None);
+        let many = format!("GET / HTTP/1.1\r\n{}\r\n", "X-A: a\r\n".repeat(2_000));
+        assert_eq!(read_request(&mut many.as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_serve() {
+        let addr = "ws:127.0.0.1:0".parse().unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = Server::start(&addr, OutputFormat::Text, 1).unwrap();
+        assert_eq!(server.format, OutputFormat::Json);
+        let connect = |request: &str| {
+            let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            let served = listener.accept().unwrap().0;
+            let server = server.clone();
+            std::thread::spawn(move || serve(served, &server));
+            let mut client = BufReader::new(client);
+            client.get_mut().write_all(request.as_bytes()).unwrap();
+            client
+        };
+
+        let mut page = String::new();
+        connect("GET / HTTP/1.1\r\nHost: radio\r\n\r\n")
+            .read_to_string(&mut page)
+            .unwrap();
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"), "{page}");
+        assert!(page.ends_with(DASHBOARD));
+
+        let mut client = connect(
+            "GET / HTTP/1.1\r\nUpgrade: websocket\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+        );
+        let response: Vec<String> = (&mut client)
+            .lines()
+            .map(Result::unwrap)
+            .take_while(|line| !line.is_empty())
+            .collect();
+        assert_eq!(response[0], "HTTP/1.1 101 Switching Protocols");
+        assert!(
+            response.contains(&"Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string())
+        );
+        // Wait for the client to be registered:
+        while server.clients() == 0 {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        server.send("{\"type\":\"message\"}".to_string(), true);
+        assert_eq!(
+            read_frame(&mut client).unwrap(),
+            (TEXT, b"{\"type\":\"message\"}".to_vec())
+        );
+    }
+}
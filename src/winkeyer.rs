@@ -0,0 +1,457 @@
+use crate::morse::text_to_morse;
+use crate::prelude::*;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+// The commands of the K1EL host protocol, with their parameters:
+const ADMIN: u8 = 0x00;
+const ADMIN_HOST_OPEN: u8 = 0x02;
+const ADMIN_HOST_CLOSE: u8 = 0x03;
+const ADMIN_ECHO: u8 = 0x04;
+const SPEED: u8 = 0x02;
+const WEIGHT: u8 = 0x03;
+const PTT_TIMING: u8 = 0x04;
+const CLEAR_BUFFER: u8 = 0x0A;
+const KEY_IMMEDIATE: u8 = 0x0B;
+const FARNSWORTH: u8 = 0x0D;
+const MODE: u8 = 0x0E;
+const REQUEST_STATUS: u8 = 0x15;
+/// The mode echoing each character back as it is sent, with the
+/// paddles iambic B.
+const MODE_SERIAL_ECHO: u8 = 0x04;
+
+/// How long the keyer may take to answer the handshake.
+const HANDSHAKE: Duration = Duration::from_secs(2);
+/// The most characters given to the keyer before it echoes them back,
+/// well within its buffer.
+const AHEAD: usize = 32;
+/// How long the keyer may stay silent, not busy, after the last
+/// character, before the sending is taken to be over.
+const SETTLE: Duration = Duration::from_secs(2);
+/// How often the status is asked of the keyer meanwhile.
+const STATUS_EVERY: Duration = Duration::from_millis(500);
+
+/// The status byte of a Winkeyer, sent whenever it changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Status(u8);
+
+impl Status {
+    /// The buffer is over two thirds full, to stop writing to it.
+    pub fn xoff(self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    /// The paddles broke in, clearing the buffer.
+    pub fn breakin(self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    /// Sending.
+    pub fn busy(self) -> bool {
+        self.0 & 0x04 != 0
+    }
+}
+
+/// A byte sent by a Winkeyer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Received {
+    /// `110xxxxx`
+    Status(Status),
+    /// `10xxxxxx`, the position of the speed pot.
+    SpeedPot(u8),
+    /// A character as it was sent.
+    Echo(char),
+}
+
+impl From<u8> for Received {
+    fn from(byte: u8) -> Self {
+        match byte {
+            _ if byte & 0xE0 == 0xC0 => Received::Status(Status(byte & 0x1F)),
+            _ if byte & 0xC0 == 0x80 => Received::SpeedPot(byte & 0x3F),
+            _ => Received::Echo(byte as char),
+        }
+    }
+}
+
+/// The characters of a text the keyer can send, in capitals, the
+/// others being left out.
+fn sendable(text: &str) -> Vec<u8> {
+    text.to_uppercase()
+        .chars()
+        .filter(|&c| c == ' ' || (c.is_ascii() && !text_to_morse(&c.to_string()).is_empty()))
+        .map(|c| c as u8)
+        .collect()
+}
+
+/// A Winkeyer (of K1EL), keying the text it is given, on a serial port
+/// (or anything read and written like one, where a read of nothing
+/// means that nothing came in a while). The host mode is closed when it
+/// is dropped, its buffer cleared.
+pub struct Winkeyer<P: Read + Write> {
+    port: P,
+    /// The version of its firmware, e.g. 23 for WK2.3.
+    pub version: u8,
+    status: Status,
+}
+
+impl<P: Read + Write> Winkeyer<P> {
+    /// Checks that a keyer answers the echo test, and opens its host mode,
+    /// with the characters echoed back as they are sent.
+    pub fn open(port: P) -> io::Result<Self> {
+        let mut winkeyer = Winkeyer {
+            port,
+            version: 0,
+            status: Status::default(),
+        };
+        winkeyer.port.write_all(&[ADMIN, ADMIN_ECHO, 0x55])?;
+        if winkeyer.read_until(Instant::now() + HANDSHAKE)? != Some(0x55) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No Winkeyer answered the echo test",
+            ));
+        }
+        winkeyer.port.write_all(&[ADMIN, ADMIN_HOST_OPEN])?;
+        winkeyer.version = winkeyer
+            .read_until(Instant::now() + HANDSHAKE)?
+            .ok_or_else(|| io::Error::other("The Winkeyer didn't open the host mode"))?;
+        winkeyer.port.write_all(&[MODE, MODE_SERIAL_ECHO])?;
+        Ok(winkeyer)
+    }
+
+    /// Reads a byte, if one comes before the deadline.
+    fn read_until(&mut self, deadline: Instant) -> io::Result<Option<u8>> {
+        loop {
+            if let Some(byte) = self.read()? {
+                return Ok(Some(byte));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Reads a byte, if one came.
+    fn read(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.port.read(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock
+                    || e.kind() == io::ErrorKind::Interrupted =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The speed, from 5 to 99 WPM.
+    pub fn set_speed(&mut self, wpm: u32) -> io::Result<()> {
+        self.port.write_all(&[SPEED, wpm.clamp(5, 99) as u8])
+    }
+
+    /// The speed the characters are spread out to, from 10 to 99 WPM.
+    pub fn set_farnsworth(&mut self, wpm: u32) -> io::Result<()> {
+        self.port.write_all(&[FARNSWORTH, wpm.clamp(10, 99) as u8])
+    }
+
+    /// From -40 to 40, as cwdaemon has it, from the 50 of the keyer.
+    pub fn set_weight(&mut self, weight: i32) -> io::Result<()> {
+        self.port
+            .write_all(&[WEIGHT, (50 + weight).clamp(10, 90) as u8])
+    }
+
+    /// How long the PTT is raised before the text, and after it.
+    pub fn set_ptt(&mut self, lead: Duration, tail: Duration) -> io::Result<()> {
+        let tens = |time: Duration| (time.as_millis() / 10).min(250) as u8;
+        self.port.write_all(&[PTT_TIMING, tens(lead), tens(tail)])
+    }
+
+    /// Keys down (or up) right away, e.g. to tune.
+    pub fn key_down(&mut self, down: bool) -> io::Result<()> {
+        self.port.write_all(&[KEY_IMMEDIATE, down as u8])
+    }
+
+    /// Stops sending, and forgets the text in the buffer.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.port.write_all(&[CLEAR_BUFFER])
+    }
+
+    /// Sends a text, each character written as the keyer has room for
+    /// it, until it was all sent, `stop` asked to stop (the buffer then
+    /// being cleared), or the paddles broke in.
+    ///
+    /// # Arguments
+    /// * `echo` - Given each character as the keyer sent it.
+    /// * `stop` - Asked whether to stop, every time the keyer says
+    ///   something, or said nothing for a while.
+    ///
+    /// # Returns
+    /// Whether all of it was sent.
+    pub fn send(
+        &mut self,
+        text: &str,
+        mut echo: impl FnMut(char),
+        mut stop: impl FnMut() -> bool,
+    ) -> io::Result<bool> {
+        let text = sendable(text);
+        let letters = text.iter().filter(|&&c| c != b' ').count();
+        let (mut written, mut echoed) = (0, 0);
+        let mut heard = Instant::now();
+        let mut asked = Instant::now();
+        loop {
+            if stop() {
+                self.clear()?;
+                return Ok(false);
+            }
+            while written < text.len() && !self.status.xoff() && written < echoed + AHEAD {
+                self.port.write_all(&[text[written]])?;
+                written += 1;
+            }
+            let all_written = written == text.len();
+            match self.read()?.map(Received::from) {
+                Some(Received::Status(status)) => {
+                    heard = Instant::now();
+                    self.status = status;
+                    if status.breakin() {
+                        warn!("The paddles of the Winkeyer broke in");
+                        return Ok(false);
+                    }
+                    if all_written && !status.busy() && echoed >= letters {
+                        return Ok(true);
+                    }
+                }
+                Some(Received::Echo(c)) => {
+                    heard = Instant::now();
+                    if c != ' ' {
+                        echoed += 1;
+                    }
+                    echo(c);
+                }
+                Some(Received::SpeedPot(_)) => heard = Instant::now(),
+                None if all_written && asked.elapsed() >= STATUS_EVERY => {
+                    self.port.write_all(&[REQUEST_STATUS])?;
+                    asked = Instant::now();
+                }
+                None => {}
+            }
+            // A character the keyer didn't take, never to be echoed:
+            if all_written && !self.status.busy() && heard.elapsed() >= SETTLE {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl<P: Read + Write> Drop for Winkeyer<P> {
+    fn drop(&mut self) {
+        let _ = self.clear();
+        let _ = self.port.write_all(&[ADMIN, ADMIN_HOST_CLOSE]);
+    }
+}
+
+/// Opens the serial port of a Winkeyer, at 1200 baud with 2 stop bits,
+/// each read waiting at most a tenth of a second.
+#[cfg(unix)]
+pub fn open(path: &str) -> io::Result<Winkeyer<std::fs::File>> {
+    use std::os::unix::io::AsRawFd;
+    let file = crate::serial::open_port(path)?;
+    let fd = file.as_raw_fd();
+    let check = |result: libc::c_int| match result {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    };
+    unsafe {
+        // Blocking again, as the reads wait for VTIME:
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        check(libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK))?;
+        let mut termios: libc::termios = std::mem::zeroed();
+        check(libc::tcgetattr(fd, &mut termios))?;
+        libc::cfmakeraw(&mut termios);
+        check(libc::cfsetispeed(&mut termios, libc::B1200))?;
+        check(libc::cfsetospeed(&mut termios, libc::B1200))?;
+        termios.c_cflag |= libc::CSTOPB | libc::CLOCAL | libc::CREAD;
+        termios.c_cc[libc::VMIN] = 0;
+        termios.c_cc[libc::VTIME] = 1;
+        check(libc::tcsetattr(fd, libc::TCSANOW, &termios))?;
+        check(libc::tcflush(fd, libc::TCIOFLUSH))?;
+    }
+    Winkeyer::open(file)
+}
+
+#[cfg(not(unix))]
+pub fn open(_path: &str) -> io::Result<Winkeyer<std::fs::File>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "The Winkeyer is only supported on Unix",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    /// A Winkeyer, answering as the real one does, a byte for each read:
+    /// its status when it starts or stops sending, and each character
+    /// as it is sent.
+    #[derive(Default)]
+    struct Mock {
+        written: Arc<Mutex<Vec<u8>>>,
+        answers: VecDeque<u8>,
+        buffer: VecDeque<u8>,
+        // The command being written, until it has its parameters:
+        command: Vec<u8>,
+        busy: bool,
+    }
+
+    impl Mock {
+        fn status(&self) -> u8 {
+            0xC0 | ((self.buffer.len() > 20) as u8) | ((self.busy as u8) << 2)
+        }
+
+        fn command(&mut self, command: &[u8]) -> bool {
+            let length = match command[0] {
+                ADMIN if command.len() > 1 && command[1] == ADMIN_ECHO => 3,
+                ADMIN => 2,
+                SPEED | WEIGHT | KEY_IMMEDIATE | FARNSWORTH | MODE => 2,
+                PTT_TIMING => 3,
+                _ => 1,
+            };
+            if command.len() < length {
+                return false;
+            }
+            match command {
+                [ADMIN, ADMIN_ECHO, byte] => self.answers.push_back(*byte),
+                [ADMIN, ADMIN_HOST_OPEN] => self.answers.push_back(23),
+                [CLEAR_BUFFER] => self.buffer.clear(),
+                [REQUEST_STATUS] => self.answers.push_back(self.status()),
+                [c] if *c >= 0x20 => self.buffer.push_back(*c),
+                _ => {}
+            }
+            true
+        }
+    }
+
+    impl Write for Mock {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.written.lock().unwrap().extend(bytes);
+            for &byte in bytes {
+                self.command.push(byte);
+                let command = self.command.clone();
+                if self.command(&command) {
+                    self.command.clear();
+                }
+            }
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for Mock {
+        fn read(&mut self, bytes: &mut [u8]) -> io::Result<usize> {
+            let byte = match (self.answers.pop_front(), self.buffer.is_empty(), self.busy) {
+                (Some(answer), _, _) => answer,
+                (None, false, false) => {
+                    self.busy = true;
+                    self.status()
+                }
+                (None, false, true) => self.buffer.pop_front().unwrap(),
+                (None, true, true) => {
+                    self.busy = false;
+                    self.status()
+                }
+                (None, true, false) => return Ok(0),
+            };
+            bytes[0] = byte;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_received() {
+        assert_eq!(Received::from(0x41), Received::Echo('A'));
+        assert_eq!(Received::from(0x95), Received::SpeedPot(0x15));
+        let status = match Received::from(0xC5) {
+            Received::Status(status) => status,
+            received => panic!("{received:?}"),
+        };
+        assert!(status.xoff() && status.busy() && !status.breakin());
+        assert_eq!(sendable("cq de k1abc é"), b"CQ DE K1ABC ");
+    }
+
+    #[test]
+    fn test_send() {
+        let mock = Mock::default();
+        let written = mock.written.clone();
+        let mut winkeyer = Winkeyer::open(mock).unwrap();
+        assert_eq!(winkeyer.version, 23);
+        winkeyer.set_speed(28).unwrap();
+        let text = "cq test de k1abc k1abc test ".repeat(3);
+        let mut echoed = String::new();
+        assert!(winkeyer.send(&text, |c| echoed.push(c), || false).unwrap());
+        assert_eq!(echoed, text.to_uppercase());
+        drop(winkeyer);
+        let written = written.lock().unwrap();
+        assert_eq!(
+            written[..9],
+            [
+                ADMIN,
+                ADMIN_ECHO,
+                0x55,
+                ADMIN,
+                ADMIN_HOST_OPEN,
+                MODE,
+                MODE_SERIAL_ECHO,
+                SPEED,
+                28
+            ]
+        );
+        assert_eq!(
+            written[written.len() - 3..],
+            [CLEAR_BUFFER, ADMIN, ADMIN_HOST_CLOSE]
+        );
+    }
+
+    #[test]
+    fn test_abort() {
+        let mock = Mock::default();
+        let written = mock.written.clone();
+        let mut winkeyer = Winkeyer::open(mock).unwrap();
+        let echoed = Cell::new(0);
+        let sent = winkeyer
+            .send(
+                "VVV VVV",
+                |_| echoed.set(echoed.get() + 1),
+                || echoed.get() == 2,
+            )
+            .unwrap();
+        assert!(!sent);
+        // Stopped with the buffer cleared, before the end:
+        assert_eq!(written.lock().unwrap().last(), Some(&CLEAR_BUFFER));
+        assert_eq!(echoed.get(), 2);
+
+        // Nothing answers:
+        struct Silent;
+        impl Read for Silent {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Ok(0)
+            }
+        }
+        impl Write for Silent {
+            fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+                Ok(bytes.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        assert!(Winkeyer::open(Silent).is_err());
+    }
+}
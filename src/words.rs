@@ -0,0 +1,106 @@
+use crate::morse::char_to_morse;
+use rand::Rng;
+
+/// The words practiced unless a file of them is given.
+pub const WORDS: &str = include_str!("words.txt");
+
+/// How much more likely a word is to be picked each time it is missed.
+const MISSED_WEIGHT: u32 = 20;
+
+/// Reads a list of words, separated by spaces or lines. Comments
+/// starting with '#' and words that cannot be sent are skipped.
+///
+/// # Arguments
+/// * `length` - The shortest and longest words to keep.
+pub fn read_words(text: &str, length: (usize, usize)) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .map(str::to_uppercase)
+    {
+        let len = word.chars().count();
+        if (length.0..=length.1).contains(&len)
+            && word.chars().all(|ch| char_to_morse(ch).is_some())
+            && !words.contains(&word)
+        {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// Picks words at random, the ones missed lately more often than the
+/// others.
+pub struct WordPicker {
+    words: Vec<String>,
+    weights: Vec<u32>,
+}
+
+impl WordPicker {
+    pub fn new(words: Vec<String>) -> Self {
+        let weights = vec![1; words.len()];
+        WordPicker { words, weights }
+    }
+
+    pub fn pick(&self, rng: &mut impl Rng) -> &str {
+        let total: u32 = self.weights.iter().sum();
+        let mut pick = rng.gen_range(0..total);
+        let index = self
+            .weights
+            .iter()
+            .position(|&weight| {
+                let found = pick < weight;
+                pick = pick.saturating_sub(weight);
+                found
+            })
+            .unwrap_or(0);
+        &self.words[index]
+    }
+
+    /// Makes a word more likely to come again when it was missed, and
+    /// less likely again once it is copied.
+    pub fn copied(&mut self, word: &str, correct: bool) {
+        if let Some(index) = self.words.iter().position(|w| w == word) {
+            let weight = &mut self.weights[index];
+            *weight = match correct {
+                true => (*weight / 2).max(1),
+                false => *weight + MISSED_WEIGHT,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_words() {
+        assert_eq!(
+            read_words("# words\nthe tnx 73\n  QTH ES the\nnaïve to\n", (2, 3)),
+            vec!["THE", "TNX", "73", "QTH", "ES", "TO"]
+        );
+        assert_eq!(read_words("A BE SEE FOUR", (3, 4)), vec!["SEE", "FOUR"]);
+        let words = read_words(WORDS, (1, usize::MAX));
+        assert!(words.len() > 500);
+        assert!(words.iter().any(|word| word == "TNX"));
+    }
+
+    #[test]
+    fn test_word_picker() {
+        let mut picker = WordPicker::new(read_words("RST QTH NAME", (1, 10)));
+        let mut rng = rand::thread_rng();
+        let count = |picker: &WordPicker, rng: &mut rand::rngs::ThreadRng| {
+            (0..3000).filter(|_| picker.pick(rng) == "QTH").count()
+        };
+        assert!((700..1300).contains(&count(&picker, &mut rng)));
+        picker.copied("QTH", false);
+        assert!(count(&picker, &mut rng) > 2400);
+        for _ in 0..5 {
+            picker.copied("QTH", true);
+        }
+        assert!((700..1300).contains(&count(&picker, &mut rng)));
+    }
+}